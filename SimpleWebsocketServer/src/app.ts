@@ -1,49 +1,1501 @@
+import { createHash, createHmac, randomBytes, scryptSync, timingSafeEqual } from 'crypto';
+import { readFileSync } from 'fs';
+import { createServer, IncomingMessage, ServerResponse } from 'http';
 import WebSocket, { WebSocketServer } from 'ws';
 
 const PORT = process.env.PORT ? parseInt(process.env.PORT) : 8080;
 interface User {
     ws: WebSocket;
     nick: String;
+    avatar?: string;
+    // SHA-256 of their Gravatar email, if they chose to show it.
+    gravatar?: string;
+    // Public ECDH key for end-to-end encrypted direct messages.
+    publicKey?: string;
+    // Idle for a while, by their client's reckoning.
+    away?: boolean;
+    // In the voice channel of their room.
+    voice?: boolean;
+    room: String;
+    // The room they were in before this one, where they go back to if this one is deleted.
+    previous?: String;
     isAlive: boolean;
+    // When they last posted a message, for slow mode.
+    lastPosted?: number;
 }
 
 interface Message {
     messageType: String;
     data: String;
     dataArray: String[];
+    attachment?: Attachment;
+    room?: String;
+    to?: String;
+    publicKey?: string;
+    // The invite code given with `register` or `join`, to enter an invite-only room.
+    invite?: string;
+    // The password given with `join`, to enter a password-protected room.
+    password?: string;
 }
 
+interface Credentials {
+    username: string;
+    passwordHash: string;
+}
+
+interface Identity {
+    username: string;
+    avatar?: string;
+}
+
+interface OAuthCode {
+    provider: string;
+    code: string;
+    redirectUri: string;
+}
+
+interface Account {
+    username: string;
+    salt: Buffer;
+    hash: Buffer;
+}
+
+interface Attachment {
+    name: String;
+    mime: String;
+    size: number;
+    data: String;
+}
+
+// Body of `POST /hooks/<room>`: an integration posting into a room.
+interface Webhook {
+    bot: string;
+    text: string;
+    id?: string;
+    title?: string;
+    avatar?: string;
+    buttons?: { action: string; label: string }[];
+    // Where presses of the card's buttons are POSTed.
+    callback?: string;
+}
+
+interface BotAction {
+    bot: string;
+    card?: string;
+    action: string;
+}
+
+// Payload of `relay` from a client: where to mirror the room's messages to.
+interface RelayConfig {
+    // Left out to only switch the relay on or off; empty to remove it.
+    url?: string;
+    enabled: boolean;
+}
+
+// What a member may do in their room. Its owner and the administrators may do it all.
+interface Permissions {
+    post: boolean;
+    // Pin messages and arrange the pins.
+    pin: boolean;
+    // Have the invite code of an invite-only room.
+    invite: boolean;
+    // Send members back to the default room.
+    kick: boolean;
+}
+
+// Payload of `roomroles`: who may do what in the room, besides its moderators. A member's role
+// replaces what everyone may do, so it can take a permission away as well as grant one.
+interface RoomRoles {
+    everyone: Permissions;
+    roles: { name: string; permissions: Permissions }[];
+    // The role of each member given one, by name.
+    members: { [nick: string]: string };
+}
+
+// Payload of `emoji` from a client: adds or replaces `name`, or removes it without an `image`.
+interface EmojiChange {
+    name: string;
+    image?: string;
+}
+
+interface Relay {
+    url: string;
+    target: 'Discord' | 'Slack';
+    enabled: boolean;
+    // Failed deliveries since the last one that worked.
+    failures: number;
+    error?: string;
+}
+
+const DEFAULT_ROOM = 'general';
+// Mirrors `validate_username` in the client.
+const USERNAME_PATTERN = /^[A-Za-z0-9_-]{3,20}$/;
+
 let users: User[] = [];
+const accounts = new Map<string, Account>();
+const refreshTokens = new Map<string, { identity: Identity; expiresAt: number }>();
+const identities = new WeakMap<WebSocket, Identity>();
+
+const JWT_SECRET = process.env.JWT_SECRET || randomBytes(32).toString('hex');
+const JWT_TTL_MS = 15 * 60 * 1000;
+const REFRESH_TTL_MS = 7 * 24 * 60 * 60 * 1000;
+
+// Webhooks are off unless integrations have a token to present.
+const WEBHOOK_TOKEN = process.env.WEBHOOK_TOKEN;
+// Latest callback URL each bot gave, for delivering presses of its buttons.
+const botCallbacks = new Map<string, string>();
+// Where each room's messages are mirrored to, if anywhere.
+const relays = new Map<String, Relay>();
+// Failed deliveries in a row after which a relay switches itself off.
+const RELAY_MAX_FAILURES = 5;
+// Who first entered each room, the only one who may change its custom emoji.
+const owners = new Map<String, String>();
+// Each room's custom emoji, as image data URLs by name.
+const customEmoji = new Map<String, Map<string, string>>();
+// Seconds each member of a room has to wait between messages, for rooms in slow mode.
+const slowMode = new Map<String, number>();
+// Longest wait slow mode can impose between someone's messages.
+const MAX_SLOW_MODE_SECONDS = 60 * 60;
+const slowModeFrame = (room: String) => JSON.stringify({ messageType: 'slowmode', data: slowMode.has(room) ? String(slowMode.get(room)) : undefined });
+// The latest messages of each room, only so that they can be reported: none are replayed.
+const reportable = new Map<String, any[]>();
+const MAX_REPORTABLE = 200;
+// Each room's reported messages, oldest report first.
+const reports = new Map<String, { id: string; reporters: String[] }[]>();
+// Who may no longer post in each room.
+const banned = new Map<String, Set<String>>();
+const bannedFrame = (nick: String) => JSON.stringify({ messageType: 'banned', data: nick });
+// Words starred out of each room's messages: single words, lowercased.
+const blacklists = new Map<String, string[]>();
+const MAX_BLACKLIST_WORDS = 100;
+const MAX_BLACKLIST_WORD_LENGTH = 32;
+const WORD_PATTERN = new RegExp('[\\p{L}\\p{N}_]+', 'gu');
+const blacklistFrame = (room: String) => JSON.stringify({ messageType: 'blacklist', data: JSON.stringify(blacklists.get(room) || []) });
+// The text with the blacklisted words of the member's room masked. If there were any, the member is warned.
+const masked = (member: User, text: string) => {
+    const words = blacklists.get(member.room) || [];
+    const result = `${text || ''}`.replace(WORD_PATTERN, (word) => (words.includes(word.toLowerCase()) ? '*'.repeat([...word].length) : word));
+    if (result !== `${text || ''}`) {
+        member.ws.send(JSON.stringify({ messageType: 'warning', data: "Words this room doesn't allow were masked in your message." }));
+    }
+    return result;
+};
+// Each room's pinned messages, in the order shown; pinning past the limit unpins the one pinned longest ago.
+const pins = new Map<String, { message: any; pinnedBy: String; pinnedAt: number }[]>();
+const PIN_LIMIT = Math.max(1, parseInt(process.env.PIN_LIMIT || '') || 5);
+const pinsFrame = (room: String) => JSON.stringify({ messageType: 'pins', data: JSON.stringify(pins.get(room) || []) });
+// Each room's icon, for rooms that have one: an emoji or a data:image/ URL.
+const icons = new Map<String, string>();
+const MAX_ICON_TEXT_BYTES = 32;
+// What each room is about, for rooms that say: a line shown under its name and in the directory.
+const topics = new Map<String, string>();
+const MAX_TOPIC_LEN = 200;
+const roomUpdateFrame = (room: String) =>
+    JSON.stringify({
+        messageType: 'roomupdate',
+        data: JSON.stringify({ name: room, icon: icons.get(room), topic: topics.get(room), inviteOnly: inviteOnly.has(room) || undefined, passwordProtected: passwords.has(room) || undefined }),
+    });
+const ROOM_NAME_PATTERN = /^[\p{L}\p{N}_-]{1,32}$/u;
+// Moves everything of room `from` to `to`, its members included, or forgets it all without a `to`,
+// leaving its members where they are.
+const moveRoom = (from: String, to?: String) => {
+    const maps: Map<String, any>[] = [relays, owners, customEmoji, slowMode, reportable, reports, banned, blacklists, pins, icons, topics, postedIn, restrictions, inviteOnly, passwords, roomRoles];
+    maps.forEach((map) => {
+        if (map.has(from) && to !== undefined) map.set(to, map.get(from));
+        map.delete(from);
+    });
+    // Codes of a deleted room are forgotten, rather than let anyone into one of the same name.
+    attempts.forEach((_, key) => {
+        if (key.endsWith(`\n${from}`)) attempts.delete(key);
+    });
+    invites.forEach((invite, code) => {
+        if (invite.room !== from) return;
+        if (to === undefined) {
+            invites.delete(code);
+        } else {
+            invite.room = to;
+        }
+    });
+    users.forEach((u) => {
+        if (u.previous === from) u.previous = to;
+        if (u.room === from && to !== undefined) u.room = to;
+    });
+};
+// The invite-only rooms, with whom each let in, who may come back without a code.
+const inviteOnly = new Map<String, Set<String>>();
+// The invite codes of the invite-only rooms, kept once revoked or expired to tell why they no
+// longer work.
+const invites = new Map<string, { room: String; expiresAt: number; revoked: boolean }>();
+const INVITE_TTL_MS = 7 * 24 * 60 * 60 * 1000;
+const inviteFrame = (code: string) => {
+    const invite = invites.get(code)!;
+    return JSON.stringify({ messageType: 'invite', data: JSON.stringify({ room: invite.room, code, expiresAt: invite.expiresAt }) });
+};
+const newInvite = (room: String) => {
+    const code = randomBytes(12).toString('hex');
+    invites.set(code, { room, expiresAt: Date.now() + INVITE_TTL_MS, revoked: false });
+    return code;
+};
+const revokeInvites = (room: String) =>
+    invites.forEach((invite) => {
+        if (invite.room === room) invite.revoked = true;
+    });
+// The passwords of the password-protected rooms, salted and hashed, with whom each let in, who
+// needn't give it again until it changes.
+const passwords = new Map<String, { salt: Buffer; hash: Buffer; admitted: Set<String> }>();
+const MAX_ROOM_PASSWORD_LENGTH = 128;
+// Wrong passwords given lately, by name and room; too many and the next ones wait.
+const attempts = new Map<string, { failures: number; since: number }>();
+const MAX_PASSWORD_ATTEMPTS = 5;
+const PASSWORD_LOCKOUT_MS = 10 * 60 * 1000;
+// Hashed as account passwords are.
+const hashRoomPassword = (salt: Buffer, password: string) => scryptSync(password, salt, 32);
+// Seconds until `nick` may try another password for `room`, if they gave too many wrong ones.
+const retryIn = (nick: String, room: String) => {
+    const tried = attempts.get(`${nick}\n${room}`);
+    if (!tried || tried.failures < MAX_PASSWORD_ATTEMPTS || tried.since + PASSWORD_LOCKOUT_MS <= Date.now()) return undefined;
+    return Math.ceil((tried.since + PASSWORD_LOCKOUT_MS - Date.now()) / 1000);
+};
+const refusedFrame = (nick: String, room: String, reason: string, to: String) =>
+    JSON.stringify({ messageType: 'joinrefused', data: JSON.stringify({ room, reason, to, retryIn: retryIn(nick, room) }) });
+// Why `nick` may not enter `room`, if it won't let them in. Its owner and the administrators
+// always may. An invite-only room lets in whoever has a live code of it, and a password-protected
+// one whoever gives its password, from then on.
+const refusal = (nick: String, room: String, code?: string, password?: string) => {
+    if (owners.get(room) === nick || ADMINS.has(nick as string)) return undefined;
+    return inviteRefusal(nick, room, code) || passwordRefusal(nick, room, password);
+};
+// Whether `reader` may see what is said in `room` without being in it: always, unless it is
+// invite-only and didn't let them in, or has a password they never gave. Its owner and the
+// administrators always may.
+const mayRead = (reader: String, room: String) => {
+    if (owners.get(room) === reader || ADMINS.has(reader as string)) return true;
+    const lock = passwords.get(room);
+    if (lock && !lock.admitted.has(reader)) return false;
+    if (users.some((u) => u.room === room && u.nick === reader)) return true;
+    const admitted = inviteOnly.get(room);
+    return !admitted || admitted.has(reader);
+};
+const inviteRefusal = (nick: String, room: String, code?: string) => {
+    const admitted = inviteOnly.get(room);
+    if (!admitted || admitted.has(nick)) return undefined;
+    if (!code) return 'required';
+    const invite = invites.get(code);
+    if (!invite || invite.room !== room) return 'invalid';
+    if (invite.revoked) return 'revoked';
+    if (invite.expiresAt <= Date.now()) return 'expired';
+    admitted.add(nick);
+    return undefined;
+};
+// Wrong passwords count against the name, so reconnecting doesn't start the count over.
+const passwordRefusal = (nick: String, room: String, password?: string) => {
+    const lock = passwords.get(room);
+    if (!lock || lock.admitted.has(nick)) return undefined;
+    if (retryIn(nick, room) !== undefined) return 'tooManyAttempts';
+    if (!password) return 'password';
+    const key = `${nick}\n${room}`;
+    if (timingSafeEqual(hashRoomPassword(lock.salt, password), lock.hash)) {
+        lock.admitted.add(nick);
+        attempts.delete(key);
+        return undefined;
+    }
+    let tried = attempts.get(key);
+    if (!tried || tried.since + PASSWORD_LOCKOUT_MS <= Date.now()) {
+        tried = { failures: 0, since: Date.now() };
+        attempts.set(key, tried);
+    }
+    tried.failures += 1;
+    return tried.failures >= MAX_PASSWORD_ATTEMPTS ? 'tooManyAttempts' : 'wrongPassword';
+};
+// A message of the server's own, shown as a system line.
+const systemFrame = (text: string) =>
+    JSON.stringify({ messageType: 'message', data: JSON.stringify({ id: randomBytes(8).toString('hex'), from: '', message: text, time: Date.now(), system: true }) });
+// What each room doesn't allow in messages, for rooms that refuse anything.
+const restrictions = new Map<String, { links: boolean; images: boolean; attachments: boolean }>();
+const restrictionsFrame = (room: String) =>
+    JSON.stringify({ messageType: 'restrictions', data: JSON.stringify(restrictions.get(room) || { links: false, images: false, attachments: false }) });
+// Why the room of the member refuses the message, if it does; the member is warned.
+const refused = (member: User, text: string, attachment: any) => {
+    const refuses = restrictions.get(member.room);
+    if (!refuses) return undefined;
+    const image = !!attachment && `${attachment.mime || ''}`.startsWith('image/');
+    const reason =
+        refuses.attachments && attachment
+            ? "Attachments aren't allowed in this room."
+            : refuses.images && image
+            ? "Images aren't allowed in this room."
+            : refuses.links && /https?:\/\//i.test(`${text || ''}`)
+            ? "Links aren't allowed in this room."
+            : undefined;
+    if (reason) member.ws.send(JSON.stringify({ messageType: 'warning', data: reason }));
+    return reason;
+};
+const EMOJI_NAME_PATTERN = /^[a-z0-9_+-]{1,32}$/;
+const MAX_EMOJI = 50;
+// Every client in the room is sent all of them.
+const MAX_EMOJI_BYTES = 64 * 1024;
+// Accounts allowed to see the admin view.
+const ADMINS = new Set(
+    (process.env.ADMINS || '')
+        .split(',')
+        .map((name) => name.trim())
+        .filter((name) => name)
+);
+// A text setting: the contents of the file named by <name>_FILE, or else <name> itself.
+const textSetting = (name: string) =>
+    (process.env[`${name}_FILE`] ? readFileSync(process.env[`${name}_FILE`] as string, 'utf8') : process.env[name] || '').trim();
+// Sent to everyone who registers.
+const WELCOME = textSetting('MOTD');
+// To be accepted before registering. The version changes whenever the text does.
+const TERMS_TEXT = textSetting('TERMS');
+const TERMS = TERMS_TEXT
+    ? { version: createHash('sha256').update(TERMS_TEXT).digest('hex').slice(0, 16), text: TERMS_TEXT }
+    : undefined;
+// The version of the terms each account accepted last.
+const acceptedTerms = new Map<string, string>();
+// The room's owner and administrators moderate it.
+const isModerator = (member: User) => owners.get(member.room) === member.nick || ADMINS.has(member.nick as string);
+// Left out for everyone who doesn't moderate the room.
+const roleOf = (member: User) => (owners.get(member.room) === member.nick ? 'owner' : ADMINS.has(member.nick as string) ? 'moderator' : undefined);
+// Each room's own roles, for rooms whose owner set some up or changed what everyone may do.
+const roomRoles = new Map<String, RoomRoles>();
+const MAX_ROOM_ROLES = 10;
+const MAX_ROLE_NAME_LEN = 32;
+// Everyone may post, and nothing more, until the owner says otherwise.
+const DEFAULT_PERMISSIONS: Permissions = { post: true, pin: false, invite: false, kick: false };
+const ALL_PERMISSIONS: Permissions = { post: true, pin: true, invite: true, kick: true };
+const permissionsFrom = (asked: any): Permissions => {
+    const given = asked && typeof asked === 'object' ? asked : {};
+    const flag = (name: keyof Permissions) => (typeof given[name] === 'boolean' ? given[name] : DEFAULT_PERMISSIONS[name]);
+    return { post: flag('post'), pin: flag('pin'), invite: flag('invite'), kick: flag('kick') };
+};
+const roomRolesFrame = (room: String) =>
+    JSON.stringify({ messageType: 'roomroles', data: JSON.stringify(roomRoles.get(room) || { everyone: DEFAULT_PERMISSIONS, roles: [], members: {} }) });
+const customRoleOf = (member: User) => {
+    const roles = roomRoles.get(member.room);
+    return roles && roles.roles.find((r) => r.name === roles.members[member.nick as string]);
+};
+// The name of the member's role of their room's own, left out for those without one.
+const roomRoleOf = (member: User) => {
+    const role = customRoleOf(member);
+    return role && role.name;
+};
+// What the member may do in their room: everything for its moderators, else what their role
+// there, or everyone's, allows.
+const permissionsOf = (member: User): Permissions => {
+    if (isModerator(member)) return ALL_PERMISSIONS;
+    const role = customRoleOf(member);
+    const roles = roomRoles.get(member.room);
+    return role ? role.permissions : roles ? roles.everyone : DEFAULT_PERMISSIONS;
+};
+const isBanned = (member: User) => (banned.get(member.room) || new Set<String>()).has(member.nick);
+// Reports whose message is no longer reportable can't be acted on, so they are left out.
+const reportsFrame = (room: String) => {
+    const kept = reportable.get(room) || [];
+    const reported = (reports.get(room) || [])
+        .filter((r) => kept.some((m) => m.id === r.id))
+        .map((r) => ({ message: kept.find((m) => m.id === r.id), reporters: r.reporters }));
+    return JSON.stringify({ messageType: 'reports', data: JSON.stringify(reported) });
+};
+const sendReports = (room: String) =>
+    users.filter((u) => u.room === room && isModerator(u)).forEach((u) => u.ws.send(reportsFrame(room)));
+const STARTED_AT = Date.now();
+const MINUTE_MS = 60 * 1000;
+const HOUR_MS = 60 * MINUTE_MS;
+// Longest an announcement can stay pinned.
+const MAX_PIN_MINUTES = 24 * 60;
+// When each message of the last hour was posted, for the admin view.
+let posted: number[] = [];
+let postedTotal = 0;
+// How many messages were posted in each room, for the room directory.
+const postedIn = new Map<String, number>();
+
+const base64url = (input: Buffer | string) => Buffer.from(input).toString('base64url');
+const sign = (payload: string) => createHmac('sha256', JWT_SECRET).update(payload).digest('base64url');
+
+const createJwt = (identity: Identity, expiresAt: number) => {
+    const header = base64url(JSON.stringify({ alg: 'HS256', typ: 'JWT' }));
+    const claims = base64url(
+        JSON.stringify({ sub: identity.username, avatar: identity.avatar, exp: Math.floor(expiresAt / 1000) })
+    );
+    return `${header}.${claims}.${sign(`${header}.${claims}`)}`;
+};
+
+const verifyJwt = (token: string): Identity | undefined => {
+    const [header, claims, signature] = token.split('.');
+    if (!header || !claims || !signature) return undefined;
+    const expected = Buffer.from(sign(`${header}.${claims}`));
+    const actual = Buffer.from(signature);
+    if (expected.length !== actual.length || !timingSafeEqual(expected, actual)) return undefined;
+    const payload = JSON.parse(Buffer.from(claims, 'base64url').toString());
+    if (payload.exp * 1000 < Date.now()) return undefined;
+    return { username: payload.sub, avatar: payload.avatar };
+};
+
+const hashPassword = (passwordHash: string, salt: Buffer) => scryptSync(passwordHash, salt, 32);
+
+const issueToken = (ws: WebSocket, identity: Identity) => {
+    const expiresAt = Date.now() + JWT_TTL_MS;
+    const refreshToken = randomBytes(24).toString('hex');
+    refreshTokens.set(refreshToken, { identity, expiresAt: Date.now() + REFRESH_TTL_MS });
+    ws.send(
+        JSON.stringify({
+            messageType: 'authok',
+            data: JSON.stringify({
+                username: identity.username,
+                token: createJwt(identity, expiresAt),
+                refreshToken,
+                expiresAt,
+            }),
+        })
+    );
+};
+
+// `fetch` is global on Node 18+, but not in the @types/node version we build against.
+const httpFetch: (url: string, init?: any) => Promise<any> = (globalThis as any).fetch;
+
+const exchangeOAuthCode = async ({ provider, code, redirectUri }: OAuthCode): Promise<Identity> => {
+    switch (provider) {
+        case 'github': {
+            const tokenResponse = await httpFetch('https://github.com/login/oauth/access_token', {
+                method: 'POST',
+                headers: { Accept: 'application/json', 'Content-Type': 'application/json' },
+                body: JSON.stringify({
+                    client_id: process.env.GITHUB_CLIENT_ID,
+                    client_secret: process.env.GITHUB_CLIENT_SECRET,
+                    code,
+                    redirect_uri: redirectUri,
+                }),
+            }).then((r) => r.json());
+            const profile = await httpFetch('https://api.github.com/user', {
+                headers: { Authorization: `Bearer ${tokenResponse.access_token}`, 'User-Agent': 'yewchat' },
+            }).then((r) => r.json());
+            if (!profile.login) throw new Error('github: no profile');
+            return { username: profile.login, avatar: profile.avatar_url };
+        }
+        case 'google': {
+            const tokenResponse = await httpFetch('https://oauth2.googleapis.com/token', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/x-www-form-urlencoded' },
+                body: new URLSearchParams({
+                    client_id: process.env.GOOGLE_CLIENT_ID || '',
+                    client_secret: process.env.GOOGLE_CLIENT_SECRET || '',
+                    code,
+                    redirect_uri: redirectUri,
+                    grant_type: 'authorization_code',
+                }).toString(),
+            }).then((r) => r.json());
+            const profile = await httpFetch('https://www.googleapis.com/oauth2/v3/userinfo', {
+                headers: { Authorization: `Bearer ${tokenResponse.access_token}` },
+            }).then((r) => r.json());
+            if (!profile.name) throw new Error('google: no profile');
+            return { username: profile.name, avatar: profile.picture };
+        }
+        default:
+            throw new Error(`unknown provider ${provider}`);
+    }
+};
+
+const authError = (ws: WebSocket, reason: string) => {
+    ws.send(JSON.stringify({ messageType: 'autherror', data: reason }));
+};
+
+// Which service's incoming webhook `url` is, if it is one. Only HTTPS URLs of Discord and Slack
+// are accepted, so that the server posts nowhere else.
+const DISCORD_HOSTS = new Set(['discord.com', 'discordapp.com', 'ptb.discord.com', 'canary.discord.com']);
+const relayTarget = (url: string): 'Discord' | 'Slack' | undefined => {
+    let parsed: URL;
+    try {
+        parsed = new URL(url);
+    } catch (e) {
+        return undefined;
+    }
+    if (parsed.protocol !== 'https:' || parsed.username || parsed.password || parsed.port) return undefined;
+    if (DISCORD_HOSTS.has(parsed.hostname) && parsed.pathname.startsWith('/api/webhooks/')) return 'Discord';
+    if (parsed.hostname === 'hooks.slack.com' && parsed.pathname.startsWith('/services/')) return 'Slack';
+    return undefined;
+};
+
+// The webhook URL works as a password, so clients only learn its kind.
+const relayStatus = (relay: Relay | undefined, refused?: string) =>
+    JSON.stringify({
+        messageType: 'relay',
+        data: JSON.stringify({
+            target: relay && relay.target,
+            enabled: !!relay && relay.enabled,
+            error: refused || (relay && relay.error),
+        }),
+    });
+
+const roomEmoji = (room: String) =>
+    JSON.stringify({
+        messageType: 'emoji',
+        data: JSON.stringify({
+            owner: owners.get(room),
+            emoji: [...(customEmoji.get(room) || new Map<string, string>()).entries()]
+                .sort(([a], [b]) => (a < b ? -1 : 1))
+                .map(([name, image]) => ({ name, image })),
+        }),
+    });
+
+const countPosted = (room: String) => {
+    const now = Date.now();
+    postedTotal += 1;
+    postedIn.set(room, (postedIn.get(room) || 0) + 1);
+    posted = posted.filter((t) => t + HOUR_MS > now);
+    posted.push(now);
+};
+
+// Everyone connected, every room in use and how busy they are, for administrators. No history is
+// kept here, so no room has messages `kept`.
+const adminStats = () => {
+    const now = Date.now();
+    const rooms = [...new Set(users.map((u) => `${u.room}`))].sort();
+    return JSON.stringify({
+        messageType: 'admin',
+        data: JSON.stringify({
+            users: users.map((u) => ({ name: u.nick, room: u.room })),
+            rooms: rooms.map((name) => ({ name, members: users.filter((u) => u.room === name).length, kept: 0, icon: icons.get(name) })),
+            messages: {
+                total: postedTotal,
+                lastMinute: posted.filter((t) => t + MINUTE_MS > now).length,
+                lastHour: posted.filter((t) => t + HOUR_MS > now).length,
+            },
+            startedAt: STARTED_AT,
+        }),
+    });
+};
+
+// The rooms anyone can enter, busiest first: those with someone in them and the default room,
+// leaving out the invite-only ones.
+const publicRooms = () => {
+    const rooms = [...new Set([DEFAULT_ROOM, ...users.map((u) => `${u.room}`)])].filter((room) => !inviteOnly.has(room)).sort();
+    return JSON.stringify({
+        messageType: 'listrooms',
+        data: JSON.stringify(
+            rooms
+                .map((name) => ({
+                    name,
+                    topic: topics.get(name),
+                    icon: icons.get(name),
+                    members: users.filter((u) => u.room === name).length,
+                    posted: postedIn.get(name) || 0,
+                    passwordProtected: passwords.has(name) || undefined,
+                }))
+                .sort((a, b) => b.members - a.members)
+        ),
+    });
+};
+
+// Posts a message to the room's relay, telling the room when deliveries start or stop failing.
+const mirror = (room: String, from: string, text: string) => {
+    const relay = relays.get(room);
+    if (!relay || !relay.enabled) return;
+    const body =
+        relay.target === 'Discord'
+            ? { username: from, content: text, allowed_mentions: { parse: [] } }
+            : { text: `*${from}*: ${text}` };
+    httpFetch(relay.url, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(body),
+    })
+        .then(
+            (r) => (r.ok ? undefined : `${relay.target} answered ${r.status}`),
+            (e) => `couldn't reach ${relay.target}: ${e}`
+        )
+        .then((error?: string) => {
+            // The relay may have been replaced while we waited.
+            if (relays.get(room) !== relay) return;
+            if (!error) {
+                relay.failures = 0;
+                if (!relay.error) return;
+                relay.error = undefined;
+            } else {
+                console.log(`relay of ${room}: ${error}`);
+                relay.failures += 1;
+                relay.error = error;
+                if (relay.failures >= RELAY_MAX_FAILURES) {
+                    relay.enabled = false;
+                    relay.error = `${error} (switched off after ${relay.failures} failures)`;
+                }
+            }
+            broadcast(room, relayStatus(relay));
+        });
+};
+
+const handleWebhook = (req: IncomingMessage, res: ServerResponse) => {
+    const match = /^\/hooks\/([^/?]+)/.exec(req.url || '');
+    if (req.method !== 'POST' || !match || !WEBHOOK_TOKEN) {
+        res.writeHead(404).end();
+        return;
+    }
+    const expected = Buffer.from(`Bearer ${WEBHOOK_TOKEN}`);
+    const actual = Buffer.from(req.headers.authorization || '');
+    if (expected.length !== actual.length || !timingSafeEqual(expected, actual)) {
+        res.writeHead(401).end();
+        return;
+    }
+    let body = '';
+    req.on('data', (chunk) => (body += chunk));
+    req.on('end', () => {
+        try {
+            const hook: Webhook = JSON.parse(body);
+            if (typeof hook.bot !== 'string' || !hook.bot.trim() || typeof hook.text !== 'string') {
+                throw new Error('bot and text are required');
+            }
+            if (hook.callback) {
+                botCallbacks.set(hook.bot, hook.callback);
+            }
+            countPosted(decodeURIComponent(match[1]));
+            broadcast(
+                decodeURIComponent(match[1]),
+                JSON.stringify({
+                    messageType: 'message',
+                    data: JSON.stringify({
+                        id: randomBytes(8).toString('hex'),
+                        from: hook.bot,
+                        message: hook.text,
+                        time: Date.now(),
+                        bot: { id: hook.id, title: hook.title, avatar: hook.avatar, buttons: hook.buttons || [] },
+                    }),
+                })
+            );
+            mirror(decodeURIComponent(match[1]), hook.bot, hook.text);
+            res.writeHead(204).end();
+        } catch (e) {
+            console.log('Error in webhook', e);
+            res.writeHead(422).end();
+        }
+    });
+};
+
+// Read-only endpoints beside the WebSocket, for what a client asks for rather than gets pushed.
+// This server keeps no history, so its pages are always empty.
+const handleApi = (req: IncomingMessage, res: ServerResponse) => {
+    // The client is served from another origin and sends its token in a header.
+    res.setHeader('Access-Control-Allow-Origin', '*');
+    res.setHeader('Access-Control-Allow-Headers', 'authorization');
+    if (req.method === 'OPTIONS') {
+        res.writeHead(204).end();
+        return;
+    }
+    let identity: Identity | undefined;
+    try {
+        identity = verifyJwt((req.headers.authorization || '').replace(/^Bearer /, ''));
+    } catch (e) {
+        identity = undefined;
+    }
+    if (!identity) {
+        res.writeHead(401).end();
+        return;
+    }
+    const json = (body: unknown) =>
+        res.writeHead(200, { 'Content-Type': 'application/json' }).end(JSON.stringify(body));
+    const path = new URL(req.url || '/', 'http://localhost').pathname;
+    let match;
+    if (req.method !== 'GET') {
+        res.writeHead(405).end();
+    } else if ((match = /^\/api\/rooms\/([^/]+)\/messages$/.exec(path))) {
+        if (!mayRead(identity.username, decodeURIComponent(match[1]))) {
+            res.writeHead(403).end();
+            return;
+        }
+        json({ messages: [], more: false });
+    } else if ((match = /^\/api\/rooms\/([^/]+)$/.exec(path))) {
+        const name = decodeURIComponent(match[1]);
+        if (!mayRead(identity.username, name)) {
+            res.writeHead(403).end();
+            return;
+        }
+        const members = users.filter((u) => u.room === name);
+        json({ name, members: members.map((u) => ({ name: u.nick, avatar: u.avatar, gravatar: u.gravatar, key: u.publicKey, away: u.away || undefined, voice: u.voice || undefined, role: roleOf(u), roomRole: roomRoleOf(u) })), kept: 0, icon: icons.get(name) });
+    } else if ((match = /^\/api\/users\/([^/]+)$/.exec(path))) {
+        const name = decodeURIComponent(match[1]);
+        const online = users.find((u) => u.nick === name);
+        const account = accounts.get(name.toLowerCase());
+        if (online) {
+            json({ name: online.nick, avatar: online.avatar, room: online.room });
+        } else if (account) {
+            json({ name: account.username });
+        } else {
+            res.writeHead(404).end();
+        }
+    } else if (path === '/api/terms' && TERMS) {
+        json(TERMS);
+    } else {
+        res.writeHead(404).end();
+    }
+};
 
 console.log(`Listening on port ${PORT}`);
-const wss = new WebSocketServer({ port: PORT });
+const server = createServer((req, res) =>
+    (req.url || '').startsWith('/api/') ? handleApi(req, res) : handleWebhook(req, res)
+);
+const wss = new WebSocketServer({ server });
+server.listen(PORT);
 
-wss.on('connection', (ws: WebSocket) => {
+wss.on('connection', (ws: WebSocket, req: IncomingMessage) => {
     console.log('ws connected');
+    const token = new URL(req.url || '/', 'ws://localhost').searchParams.get('token');
+    const identity = token ? verifyJwt(token) : undefined;
+    if (identity) {
+        identities.set(ws, identity);
+    }
 
     ws.on('message', (data) => {
         const raw_data = data.toString();
         try {
             const parsed_data: Message = JSON.parse(raw_data);
             switch (parsed_data.messageType) {
+                case 'signup': {
+                    const credentials: Credentials = JSON.parse(parsed_data.data as string);
+                    if (!USERNAME_PATTERN.test(credentials.username)) {
+                        authError(ws, 'invalid_name');
+                        break;
+                    }
+                    const key = credentials.username.toLowerCase();
+                    if (accounts.has(key)) {
+                        authError(ws, 'name_taken');
+                        break;
+                    }
+                    const salt = randomBytes(16);
+                    accounts.set(key, {
+                        username: credentials.username,
+                        salt,
+                        hash: hashPassword(credentials.passwordHash, salt),
+                    });
+                    issueToken(ws, { username: credentials.username });
+                    break;
+                }
+                case 'login': {
+                    const credentials: Credentials = JSON.parse(parsed_data.data as string);
+                    const account = accounts.get(credentials.username.toLowerCase());
+                    if (!account) {
+                        authError(ws, 'unknown_user');
+                    } else if (!timingSafeEqual(account.hash, hashPassword(credentials.passwordHash, account.salt))) {
+                        authError(ws, 'wrong_password');
+                    } else {
+                        issueToken(ws, { username: account.username });
+                    }
+                    break;
+                }
+                case 'oauth': {
+                    exchangeOAuthCode(JSON.parse(parsed_data.data as string))
+                        .then((identity) => issueToken(ws, identity))
+                        .catch((e) => {
+                            console.log('OAuth exchange failed', e);
+                            authError(ws, 'o_auth_failed');
+                        });
+                    break;
+                }
+                case 'refresh': {
+                    // Refresh tokens are single use: trading one in revokes it.
+                    const entry = refreshTokens.get(parsed_data.data as string);
+                    refreshTokens.delete(parsed_data.data as string);
+                    if (!entry || entry.expiresAt < Date.now()) {
+                        authError(ws, 'invalid_token');
+                    } else {
+                        issueToken(ws, entry.identity);
+                    }
+                    break;
+                }
                 case 'register':
-                    users.push({ ws, nick: parsed_data.data, isAlive: true });
-                    broadcast(JSON.stringify({ messageType: 'users', dataArray: users.map((u) => u.nick) }));
+                case 'takeover': {
+                    const identity = identities.get(ws);
+                    if (!identity) {
+                        authError(ws, 'invalid_token');
+                        break;
+                    }
+                    if (TERMS && acceptedTerms.get(identity.username) !== TERMS.version) {
+                        ws.send(JSON.stringify({ messageType: 'terms', data: JSON.stringify(TERMS) }));
+                        break;
+                    }
+                    let room = parsed_data.room || DEFAULT_ROOM;
+                    // One connection per name: a second one is refused unless it asks to take over.
+                    const elsewhere = users.find((u) => u.nick === identity.username && u.ws !== ws);
+                    if (elsewhere && parsed_data.messageType !== 'takeover') {
+                        ws.send(JSON.stringify({ messageType: 'conflict' }));
+                        break;
+                    }
+                    if (elsewhere) {
+                        users = users.filter((u) => u !== elsewhere);
+                        elsewhere.ws.send(JSON.stringify({ messageType: 'replaced' }));
+                        if (elsewhere.room !== room) {
+                            broadcastUsers(elsewhere.room);
+                        }
+                    }
+                    // Rooms that won't let them in leave them in the default one, open to everyone.
+                    const reason = refusal(identity.username, room, parsed_data.invite, parsed_data.password);
+                    if (reason) {
+                        ws.send(refusedFrame(identity.username, room, reason, DEFAULT_ROOM));
+                        room = DEFAULT_ROOM;
+                    }
+                    users.push({
+                        ws,
+                        nick: identity.username,
+                        avatar: identity.avatar,
+                        publicKey: parsed_data.publicKey,
+                        room,
+                        isAlive: true,
+                    });
+                    if (!owners.has(room)) owners.set(room, identity.username);
+                    if (WELCOME) ws.send(JSON.stringify({ messageType: 'welcome', data: WELCOME }));
+                    broadcastUsers(room);
+                    if ((banned.get(room) || new Set<String>()).has(identity.username)) ws.send(bannedFrame(identity.username));
                     break;
-                case 'message':
+                }
+                case 'terms': {
+                    const identity = identities.get(ws);
+                    if (identity && TERMS && parsed_data.data === TERMS.version) {
+                        acceptedTerms.set(identity.username, TERMS.version);
+                        console.log(`${identity.username} accepted the terms ${TERMS.version}`);
+                    }
+                    break;
+                }
+                case 'unregister': {
+                    const member = users.find((u) => u.ws === ws);
+                    if (member) {
+                        users = users.filter((u) => u !== member);
+                        broadcastUsers(member.room);
+                    }
+                    break;
+                }
+                case 'join': {
+                    const member = users.find((u) => u.ws === ws);
+                    if (member && parsed_data.data && member.room !== parsed_data.data) {
+                        const reason = refusal(member.nick, parsed_data.data, parsed_data.invite, parsed_data.password);
+                        if (reason) {
+                            // The client already left the room it stays in, so it is sent it again.
+                            ws.send(refusedFrame(member.nick, parsed_data.data, reason, member.room));
+                            broadcastUsers(member.room);
+                            if (isBanned(member)) ws.send(bannedFrame(member.nick));
+                            break;
+                        }
+                        const previous = member.room;
+                        member.previous = previous;
+                        member.room = parsed_data.data;
+                        member.voice = false;
+                        if (!owners.has(member.room)) owners.set(member.room, member.nick);
+                        broadcastUsers(previous);
+                        broadcastUsers(member.room);
+                        if (isBanned(member)) ws.send(bannedFrame(member.nick));
+                    }
+                    break;
+                }
+                case 'gravatar': {
+                    // Anything but a hash clears it; it ends up in other people's image URLs.
+                    const member = users.find((u) => u.ws === ws);
+                    if (member) {
+                        const hash = parsed_data.data as string;
+                        member.gravatar = /^[0-9a-f]{64}$/.test(hash || '') ? hash : undefined;
+                        broadcastUsers(member.room);
+                    }
+                    break;
+                }
+                case 'whisper': {
+                    // For `to` alone, who must be in the sender's room. The sender gets it back to
+                    // show what they whispered; it isn't kept or mirrored.
                     const sender = users.find((u) => u.ws === ws);
-                    if (sender) {
+                    const recipient = users.find((u) => u.nick === parsed_data.to);
+                    if (sender && !isBanned(sender) && permissionsOf(sender).post && recipient && recipient !== sender && recipient.room === sender.room && parsed_data.data) {
+                        const frame = JSON.stringify({
+                            messageType: 'whisper',
+                            data: JSON.stringify({
+                                id: randomBytes(8).toString('hex'),
+                                from: sender.nick,
+                                message: masked(sender, parsed_data.data as string),
+                                time: Date.now(),
+                                to: recipient.nick,
+                            }),
+                        });
+                        recipient.ws.send(frame);
+                        ws.send(frame);
+                    }
+                    break;
+                }
+                case 'away': {
+                    // Sent when the client goes idle, and without data when it is back. Only
+                    // changes are passed on, so a client announcing twice doesn't make the list flicker.
+                    const member = users.find((u) => u.ws === ws);
+                    const away = parsed_data.data != null;
+                    if (member && !!member.away !== away) {
+                        member.away = away;
+                        broadcastUsers(member.room);
+                    }
+                    break;
+                }
+                case 'voice': {
+                    // Joins the room's voice channel, or leaves it without data. Speaking takes
+                    // what posting does.
+                    const member = users.find((u) => u.ws === ws);
+                    const voice = parsed_data.data != null;
+                    if (!member || !!member.voice === voice || (voice && (!permissionsOf(member).post || isBanned(member)))) break;
+                    member.voice = voice;
+                    broadcastUsers(member.room);
+                    break;
+                }
+                case 'direct': {
+                    // End-to-end encrypted: we only see ciphertext and pass it on to both parties,
+                    // along with the public keys it was (supposedly) sealed with.
+                    const sender = users.find((u) => u.ws === ws);
+                    const recipient = users.find((u) => u.nick === parsed_data.to);
+                    if (!sender || !recipient) break;
+                    const data = JSON.stringify({
+                        messageType: 'direct',
+                        data: JSON.stringify({
+                            from: sender.nick,
+                            to: recipient.nick,
+                            payload: parsed_data.data,
+                            fromKey: sender.publicKey,
+                            toKey: recipient.publicKey,
+                            time: Date.now(),
+                        }),
+                    });
+                    users.forEach((u) => {
+                        if ((u.nick === sender.nick || u.nick === recipient.nick) && u.ws.readyState === WebSocket.OPEN) {
+                            u.ws.send(data);
+                        }
+                    });
+                    break;
+                }
+                case 'calloffer':
+                case 'callanswer':
+                case 'icecandidate':
+                case 'callhangup':
+                case 'voiceoffer':
+                case 'voiceanswer':
+                case 'voicecandidate': {
+                    // Call signaling is passed on verbatim; the media itself flows peer to peer.
+                    const sender = users.find((u) => u.ws === ws);
+                    const recipient = users.find((u) => u.nick === parsed_data.to);
+                    if (sender && recipient && recipient.ws.readyState === WebSocket.OPEN) {
+                        recipient.ws.send(
+                            JSON.stringify({
+                                messageType: parsed_data.messageType,
+                                data: parsed_data.data,
+                                from: sender.nick,
+                            })
+                        );
+                    }
+                    break;
+                }
+                case 'botaction': {
+                    const member = users.find((u) => u.ws === ws);
+                    const action: BotAction = JSON.parse(parsed_data.data as string);
+                    const callback = botCallbacks.get(action.bot);
+                    if (member && callback) {
+                        httpFetch(callback, {
+                            method: 'POST',
+                            headers: { 'Content-Type': 'application/json' },
+                            body: JSON.stringify({
+                                room: member.room,
+                                user: member.nick,
+                                card: action.card,
+                                action: action.action,
+                            }),
+                        })
+                            .then((r) => {
+                                if (!r.ok) throw new Error(`HTTP ${r.status}`);
+                            })
+                            .catch((e) => console.log(`bot ${action.bot}: callback failed`, e));
+                    }
+                    break;
+                }
+                case 'relay': {
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member) break;
+                    if (!parsed_data.data) {
+                        ws.send(relayStatus(relays.get(member.room)));
+                        break;
+                    }
+                    if (!isModerator(member)) {
+                        ws.send(relayStatus(relays.get(member.room), "Only the room's owner or an administrator can change its relay"));
+                        break;
+                    }
+                    const config: RelayConfig = JSON.parse(parsed_data.data as string);
+                    const url = config.url === undefined ? undefined : config.url.trim();
+                    if (url === '') {
+                        relays.delete(member.room);
+                    } else if (url !== undefined) {
+                        const target = relayTarget(url);
+                        if (!target) {
+                            ws.send(relayStatus(relays.get(member.room), "That isn't a Discord or Slack webhook URL"));
+                            break;
+                        }
+                        relays.set(member.room, { url, target, enabled: config.enabled, failures: 0 });
+                    } else {
+                        const relay = relays.get(member.room);
+                        if (relay) {
+                            relay.enabled = config.enabled;
+                            relay.failures = 0;
+                            relay.error = undefined;
+                        }
+                    }
+                    console.log(`${member.nick} changed the relay of ${member.room}`);
+                    broadcast(member.room, relayStatus(relays.get(member.room)));
+                    break;
+                }
+                case 'emoji': {
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member) break;
+                    if (!parsed_data.data) {
+                        ws.send(roomEmoji(member.room));
+                        break;
+                    }
+                    const change: EmojiChange = JSON.parse(parsed_data.data as string);
+                    if (owners.get(member.room) !== member.nick || !EMOJI_NAME_PATTERN.test(change.name)) {
+                        console.log(`${member.nick} may not change emoji ${change.name} of ${member.room}`);
+                        break;
+                    }
+                    const emoji = customEmoji.get(member.room) || new Map<string, string>();
+                    if (change.image === undefined) {
+                        emoji.delete(change.name);
+                    } else if (
+                        !change.image.startsWith('data:image/') ||
+                        change.image.length > MAX_EMOJI_BYTES ||
+                        (!emoji.has(change.name) && emoji.size >= MAX_EMOJI)
+                    ) {
+                        break;
+                    } else {
+                        emoji.set(change.name, change.image);
+                    }
+                    customEmoji.set(member.room, emoji);
+                    broadcast(member.room, roomEmoji(member.room));
+                    break;
+                }
+                case 'slowmode': {
+                    // Only the room's owner or an administrator may change it; anyone may ask.
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member) break;
+                    if (parsed_data.data === undefined || parsed_data.data === null) {
+                        ws.send(slowModeFrame(member.room));
+                        break;
+                    }
+                    const identity = identities.get(ws);
+                    const allowed = owners.get(member.room) === member.nick || (identity && ADMINS.has(identity.username));
+                    if (!allowed) break;
+                    const seconds = Math.min(Math.max(Math.floor(Number(parsed_data.data)) || 0, 0), MAX_SLOW_MODE_SECONDS);
+                    if (seconds > 0) {
+                        slowMode.set(member.room, seconds);
+                    } else {
+                        slowMode.delete(member.room);
+                    }
+                    broadcast(member.room, slowModeFrame(member.room));
+                    break;
+                }
+                case 'blacklist': {
+                    // Only the room's owner or an administrator may change it; anyone may ask.
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member) break;
+                    if (parsed_data.data === undefined || parsed_data.data === null) {
+                        ws.send(blacklistFrame(member.room));
+                        break;
+                    }
+                    let words: string[] = [];
+                    try {
+                        words = JSON.parse(parsed_data.data as string);
+                    } catch (e) {}
+                    if (!isModerator(member) || !Array.isArray(words)) break;
+                    const kept: string[] = [];
+                    for (const word of words.map((w) => `${w}`.trim().toLowerCase())) {
+                        const single = [...word].length <= MAX_BLACKLIST_WORD_LENGTH && (word.match(WORD_PATTERN) || []).join('') === word;
+                        if (word && single && !kept.includes(word) && kept.length < MAX_BLACKLIST_WORDS) kept.push(word);
+                    }
+                    if (kept.length > 0) {
+                        blacklists.set(member.room, kept);
+                    } else {
+                        blacklists.delete(member.room);
+                    }
+                    broadcast(member.room, blacklistFrame(member.room));
+                    break;
+                }
+                case 'pin': {
+                    // Only the room's owner, an administrator or a role that may pin.
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member || !permissionsOf(member).pin) break;
+                    const message = (reportable.get(member.room) || []).find((m) => m.id === parsed_data.data);
+                    const pinned = pins.get(member.room) || [];
+                    if (!message || pinned.some((p) => p.message.id === message.id)) break;
+                    while (pinned.length >= PIN_LIMIT) {
+                        const oldest = pinned.reduce((a, p, at) => (p.pinnedAt < pinned[a].pinnedAt ? at : a), 0);
+                        pinned.splice(oldest, 1);
+                    }
+                    pinned.unshift({ message, pinnedBy: member.nick, pinnedAt: Date.now() });
+                    pins.set(member.room, pinned);
+                    broadcast(member.room, pinsFrame(member.room));
+                    break;
+                }
+                case 'pins': {
+                    // Anyone may ask; whoever may pin may reorder and unpin.
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member) break;
+                    if (parsed_data.data === undefined || parsed_data.data === null) {
+                        ws.send(pinsFrame(member.room));
+                        break;
+                    }
+                    let ids: string[] = [];
+                    try {
+                        ids = JSON.parse(parsed_data.data as string);
+                    } catch (e) {}
+                    if (!permissionsOf(member).pin || !Array.isArray(ids)) break;
+                    const pinned = pins.get(member.room) || [];
+                    const arranged = ids.map((id) => pinned.find((p) => p.message.id === id)).filter((p, at, all) => p && all.indexOf(p) === at);
+                    if (arranged.length > 0) {
+                        pins.set(member.room, arranged as { message: any; pinnedBy: String; pinnedAt: number }[]);
+                    } else {
+                        pins.delete(member.room);
+                    }
+                    broadcast(member.room, pinsFrame(member.room));
+                    break;
+                }
+                case 'roomupdate': {
+                    // Only the room's owner may change its settings; anyone may ask.
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member) break;
+                    if (parsed_data.data === undefined || parsed_data.data === null) {
+                        ws.send(roomUpdateFrame(member.room));
+                        break;
+                    }
+                    let asked: any = null;
+                    try {
+                        asked = JSON.parse(parsed_data.data as string);
+                    } catch (e) {}
+                    if (owners.get(member.room) !== member.nick || !asked || typeof asked !== 'object') break;
+                    const icon = typeof asked.icon === 'string' ? asked.icon : '';
+                    const valid = icon.startsWith('data:')
+                        ? icon.startsWith('data:image/') && icon.length <= MAX_EMOJI_BYTES
+                        : Buffer.byteLength(icon) <= MAX_ICON_TEXT_BYTES && !/\s/.test(icon);
+                    if (!valid) break;
+                    const topic = typeof asked.topic === 'string' ? asked.topic.trim() : '';
+                    if ([...topic].length > MAX_TOPIC_LEN || topic.includes('\n')) break;
+                    const closed = asked.inviteOnly === true;
+                    if (closed && member.room === DEFAULT_ROOM) break;
+                    // A new name must be free, and the default room keeps its own.
+                    const from = member.room;
+                    const name = typeof asked.name === 'string' && asked.name !== from ? asked.name : undefined;
+                    const taken = (room: string) => owners.has(room) || users.some((u) => u.room === room);
+                    if (name !== undefined && (!ROOM_NAME_PATTERN.test(name) || from === DEFAULT_ROOM || taken(name))) break;
+                    if (icon) {
+                        icons.set(from, icon);
+                    } else {
+                        icons.delete(from);
+                    }
+                    if (topic) {
+                        topics.set(from, topic);
+                    } else {
+                        topics.delete(from);
+                    }
+                    // Closing the room lets in everyone already in it; opening it revokes its codes.
+                    const closing = closed && !inviteOnly.has(from);
+                    if (closing) {
+                        inviteOnly.set(from, new Set(users.filter((u) => u.room === from).map((u) => u.nick)));
+                    } else if (!closed && inviteOnly.delete(from)) {
+                        revokeInvites(from);
+                    }
+                    if (name === undefined) {
+                        broadcast(from, roomUpdateFrame(from));
+                    } else {
+                        moveRoom(from, name);
+                        broadcast(name, roomUpdateFrame(name));
+                        countPosted(name);
+                        broadcast(name, systemFrame(`${member.nick} renamed #${from} to #${name}`));
+                        users.filter((u) => ADMINS.has(u.nick as string)).forEach((u) => u.ws.send(adminStats()));
+                    }
+                    if (closing) ws.send(inviteFrame(newInvite(member.room)));
+                    break;
+                }
+                case 'invite':
+                case 'regenerateinvite': {
+                    // Only the owner of an invite-only room, and roles that may invite, may have its
+                    // code; only the owner may make a new one, which revokes the rest.
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member || !inviteOnly.has(member.room)) break;
+                    const regenerating = parsed_data.messageType === 'regenerateinvite';
+                    if (regenerating ? owners.get(member.room) !== member.nick : !permissionsOf(member).invite) break;
+                    const room = member.room;
+                    let code: string | undefined;
+                    if (regenerating) {
+                        revokeInvites(room);
+                    } else {
+                        invites.forEach((invite, c) => {
+                            if (invite.room === room && !invite.revoked && invite.expiresAt > Date.now()) code = c;
+                        });
+                    }
+                    ws.send(inviteFrame(code || newInvite(room)));
+                    break;
+                }
+                case 'roompassword': {
+                    // Only the room's owner may; those already in it needn't give the new one.
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member || owners.get(member.room) !== member.nick) break;
+                    const room = member.room;
+                    const password = parsed_data.data as string | undefined;
+                    if (password === undefined || password === null) {
+                        passwords.delete(room);
+                    } else {
+                        if (room === DEFAULT_ROOM || !password || [...password].length > MAX_ROOM_PASSWORD_LENGTH) break;
+                        const salt = randomBytes(16);
+                        const admitted = new Set(users.filter((u) => u.room === room).map((u) => u.nick));
+                        passwords.set(room, { salt, hash: hashRoomPassword(salt, password), admitted });
+                    }
+                    attempts.forEach((_, key) => {
+                        if (key.endsWith(`\n${room}`)) attempts.delete(key);
+                    });
+                    broadcast(room, roomUpdateFrame(room));
+                    break;
+                }
+                case 'transfer': {
+                    // Only the room's owner may, to someone else in the room.
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member) break;
+                    const room = member.room;
+                    const to = parsed_data.data;
+                    if (owners.get(room) !== member.nick || to === member.nick || !users.some((u) => u.room === room && u.nick === to)) break;
+                    owners.set(room, to as string);
+                    broadcast(room, JSON.stringify({ messageType: 'rolechange', data: JSON.stringify({ owner: to, previous: member.nick }) }));
+                    broadcastUsers(room);
+                    countPosted(room);
+                    broadcast(room, systemFrame(`${member.nick} handed #${room} to ${to}`));
+                    break;
+                }
+                case 'deleteroom': {
+                    // Only the room's owner may, giving its name to confirm; the default room stays.
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member) break;
+                    const room = member.room;
+                    const owner = member.nick;
+                    if (owners.get(room) !== owner || parsed_data.data !== room || room === DEFAULT_ROOM) break;
+                    moveRoom(room);
+                    users
+                        .filter((u) => u.room === room)
+                        .forEach((u) => {
+                            // Back where they were, if it would still let them in.
+                            const to = u.previous && !refusal(u.nick, u.previous) ? u.previous : DEFAULT_ROOM;
+                            u.previous = undefined;
+                            u.ws.send(JSON.stringify({ messageType: 'roomdeleted', data: JSON.stringify({ room, by: owner, to }) }));
+                            u.room = to;
+                            u.voice = false;
+                            if (!owners.has(to)) owners.set(to, u.nick);
+                            broadcastUsers(to);
+                            if (isBanned(u)) u.ws.send(bannedFrame(u.nick));
+                            u.ws.send(systemFrame(`${owner} deleted #${room}, so you are back in #${to}.`));
+                        });
+                    users.filter((u) => ADMINS.has(u.nick as string)).forEach((u) => u.ws.send(adminStats()));
+                    break;
+                }
+                case 'restrictions': {
+                    // Only the room's owner or an administrator may change them; anyone may ask.
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member) break;
+                    if (parsed_data.data === undefined || parsed_data.data === null) {
+                        ws.send(restrictionsFrame(member.room));
+                        break;
+                    }
+                    let asked: any = null;
+                    try {
+                        asked = JSON.parse(parsed_data.data as string);
+                    } catch (e) {}
+                    if (!isModerator(member) || !asked || typeof asked !== 'object') break;
+                    const kept = { links: asked.links === true, images: asked.images === true, attachments: asked.attachments === true };
+                    if (kept.links || kept.images || kept.attachments) {
+                        restrictions.set(member.room, kept);
+                    } else {
+                        restrictions.delete(member.room);
+                    }
+                    broadcast(member.room, restrictionsFrame(member.room));
+                    break;
+                }
+                case 'report': {
+                    // Anyone may report someone else's message; the room's moderators are told.
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member) break;
+                    const message = (reportable.get(member.room) || []).find((m) => m.id === parsed_data.data);
+                    if (!message || message.from === member.nick) break;
+                    const queue = (reports.get(member.room) || []).filter((r) =>
+                        (reportable.get(member.room) || []).some((m) => m.id === r.id)
+                    );
+                    const report = queue.find((r) => r.id === message.id);
+                    if (report && report.reporters.includes(member.nick)) break;
+                    if (report) {
+                        report.reporters.push(member.nick);
+                    } else {
+                        queue.push({ id: message.id, reporters: [member.nick] });
+                    }
+                    reports.set(member.room, queue);
+                    sendReports(member.room);
+                    break;
+                }
+                case 'reports': {
+                    const member = users.find((u) => u.ws === ws);
+                    if (member) ws.send(isModerator(member) ? reportsFrame(member.room) : JSON.stringify({ messageType: 'reports' }));
+                    break;
+                }
+                case 'moderate': {
+                    // Approve leaves the message be; delete takes it off everyone's screen; ban also
+                    // stops its sender posting in the room. Moderators can't be banned.
+                    const member = users.find((u) => u.ws === ws);
+                    let moderation: { id?: string; action?: string } = {};
+                    try {
+                        moderation = JSON.parse(parsed_data.data as string);
+                    } catch (e) {}
+                    if (!member || !isModerator(member)) break;
+                    const room = member.room;
+                    const queue = reports.get(room) || [];
+                    if (!queue.some((r) => r.id === moderation.id)) break;
+                    const message = (reportable.get(room) || []).find((m) => m.id === moderation.id);
+                    const author = message && message.from;
+                    if (moderation.action === 'ban' && (!author || owners.get(room) === author || ADMINS.has(author))) break;
+                    if (!['approve', 'delete', 'ban'].includes(moderation.action as string)) break;
+                    reports.set(room, queue.filter((r) => r.id !== moderation.id));
+                    if (moderation.action !== 'approve') {
+                        reportable.set(room, (reportable.get(room) || []).filter((m) => m.id !== moderation.id));
+                        broadcast(room, JSON.stringify({ messageType: 'deleted', data: moderation.id }));
+                        const pinned = pins.get(room) || [];
+                        if (pinned.some((p) => p.message.id === moderation.id)) {
+                            pins.set(room, pinned.filter((p) => p.message.id !== moderation.id));
+                            broadcast(room, pinsFrame(room));
+                        }
+                    }
+                    if (moderation.action === 'ban') {
+                        banned.set(room, (banned.get(room) || new Set<String>()).add(author));
+                        broadcast(room, bannedFrame(author));
+                    }
+                    sendReports(room);
+                    break;
+                }
+                case 'announce': {
+                    // Only from the room's owner or an administrator; pinned for at most a day.
+                    const sender = users.find((u) => u.ws === ws);
+                    const identity = identities.get(ws);
+                    let announcement: { text?: string; pinMinutes?: number } = {};
+                    try {
+                        announcement = JSON.parse(parsed_data.data as string);
+                    } catch (e) {}
+                    const allowed = sender && (owners.get(sender.room) === sender.nick || (identity && ADMINS.has(identity.username)));
+                    if (sender && allowed && announcement.text && announcement.text.trim()) {
+                        countPosted(sender.room);
+                        const now = Date.now();
+                        const pinMinutes = Number(announcement.pinMinutes);
                         broadcast(
+                            sender.room,
                             JSON.stringify({
                                 messageType: 'message',
                                 data: JSON.stringify({
+                                    id: randomBytes(8).toString('hex'),
                                     from: sender.nick,
-                                    message: parsed_data.data,
-                                    time: Date.now(),
+                                    message: announcement.text,
+                                    time: now,
+                                    announcement: true,
+                                    pinnedUntil: pinMinutes > 0 ? now + Math.min(pinMinutes, MAX_PIN_MINUTES) * MINUTE_MS : undefined,
                                 }),
                             })
                         );
                     }
+                    break;
+                }
+                case 'roomroles': {
+                    // Only the room's owner may change them; anyone may ask.
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member) break;
+                    if (parsed_data.data === undefined || parsed_data.data === null) {
+                        ws.send(roomRolesFrame(member.room));
+                        break;
+                    }
+                    let asked: any;
+                    try {
+                        asked = JSON.parse(parsed_data.data as string);
+                    } catch (e) {}
+                    if (owners.get(member.room) !== member.nick || !asked || typeof asked !== 'object') break;
+                    const roles = (Array.isArray(asked.roles) ? asked.roles : []).map((r: any) => ({
+                        name: `${(r && r.name) || ''}`.trim(),
+                        permissions: permissionsFrom(r && r.permissions),
+                    }));
+                    const names: string[] = roles.map((r: { name: string }) => r.name);
+                    if (roles.length > MAX_ROOM_ROLES || names.some((name, at) => !name || [...name].length > MAX_ROLE_NAME_LEN || names.indexOf(name) !== at)) break;
+                    // Members given a role the room doesn't have are left without.
+                    const members: { [nick: string]: string } = {};
+                    Object.entries(asked.members || {}).forEach(([nick, role]) => {
+                        if (names.includes(role as string)) members[nick] = role as string;
+                    });
+                    const everyone = permissionsFrom(asked.everyone);
+                    if (roles.length === 0 && JSON.stringify(everyone) === JSON.stringify(DEFAULT_PERMISSIONS)) {
+                        roomRoles.delete(member.room);
+                    } else {
+                        roomRoles.set(member.room, { everyone, roles, members });
+                    }
+                    broadcast(member.room, roomRolesFrame(member.room));
+                    broadcastUsers(member.room);
+                    break;
+                }
+                case 'kick': {
+                    // Only those whose role may; moderators stay, and so does everyone in the default
+                    // room. Coming back to an invite-only or password-protected room takes a code or
+                    // its password again.
+                    const member = users.find((u) => u.ws === ws);
+                    if (!member || !permissionsOf(member).kick || member.room === DEFAULT_ROOM) break;
+                    const room = member.room;
+                    const kicked = users.find((u) => u.room === room && u.nick === parsed_data.data);
+                    if (!kicked || isModerator(kicked)) break;
+                    const admitted = inviteOnly.get(room);
+                    if (admitted) admitted.delete(kicked.nick);
+                    const lock = passwords.get(room);
+                    if (lock) lock.admitted.delete(kicked.nick);
+                    kicked.room = DEFAULT_ROOM;
+                    kicked.voice = false;
+                    kicked.previous = undefined;
+                    kicked.ws.send(JSON.stringify({ messageType: 'kicked', data: JSON.stringify({ room, by: member.nick, to: DEFAULT_ROOM }) }));
+                    if (!owners.has(DEFAULT_ROOM)) owners.set(DEFAULT_ROOM, kicked.nick);
+                    broadcastUsers(room);
+                    broadcastUsers(DEFAULT_ROOM);
+                    if (isBanned(kicked)) kicked.ws.send(bannedFrame(kicked.nick));
+                    kicked.ws.send(systemFrame(`${member.nick} sent you out of #${room}.`));
+                    countPosted(room);
+                    broadcast(room, systemFrame(`${member.nick} sent ${kicked.nick} out of the room`));
+                    break;
+                }
+                case 'listrooms':
+                    ws.send(publicRooms());
+                    break;
+                case 'admin': {
+                    const identity = identities.get(ws);
+                    ws.send(
+                        identity && ADMINS.has(identity.username) ? adminStats() : JSON.stringify({ messageType: 'admin' })
+                    );
+                    break;
+                }
+                case 'message':
+                    const sender = users.find((u) => u.ws === ws);
+                    const now = Date.now();
+                    if (sender && isBanned(sender)) {
+                        console.log(`${sender.nick}: banned from ${sender.room}`);
+                    } else if (sender && !permissionsOf(sender).post) {
+                        console.log(`${sender.nick}: their role can't post in ${sender.room}`);
+                    } else if (sender && sender.lastPosted && now < sender.lastPosted + (slowMode.get(sender.room) || 0) * 1000) {
+                        console.log(`${sender.nick}: too soon in slow mode`);
+                    } else if (sender && refused(sender, parsed_data.data as string, parsed_data.attachment)) {
+                        console.log(`${sender.nick}: not allowed in ${sender.room}`);
+                    } else if (sender) {
+                        sender.lastPosted = now;
+                        countPosted(sender.room);
+                        const message = {
+                            id: randomBytes(8).toString('hex'),
+                            from: sender.nick,
+                            message: masked(sender, parsed_data.data as string),
+                            attachment: parsed_data.attachment,
+                            time: now,
+                        };
+                        const kept = reportable.get(sender.room) || [];
+                        // Attachments stay out, as they can be large.
+                        kept.push(Object.assign({}, message, { attachment: undefined }));
+                        reportable.set(sender.room, kept.slice(-MAX_REPORTABLE));
+                        broadcast(sender.room, JSON.stringify({ messageType: 'message', data: JSON.stringify(message) }));
+                        // The attachment itself stays here; say that there was one.
+                        const text = parsed_data.attachment
+                            ? `${message.message || ''} [${parsed_data.attachment.name}]`.trim()
+                            : `${message.message || ''}`;
+                        mirror(sender.room, sender.nick as string, text);
+                    }
             }
         } catch (e) {
             console.log('Error in message', e);
@@ -55,15 +1507,28 @@ const interval = setInterval(function ping() {
     const current_clients = Array.from(wss.clients);
     const updated_users = users.filter((u) => current_clients.includes(u.ws));
     if (updated_users.length !== users.length) {
+        const rooms = new Set(users.map((u) => u.room));
         users = updated_users;
-        broadcast(JSON.stringify({ messageType: 'users', dataArray: users.map((u) => u.nick) }));
+        rooms.forEach((room) => broadcastUsers(room));
     }
 }, 5000);
 
-const broadcast = (data: any) => {
-    wss.clients.forEach((client) => {
-        if (client.readyState === WebSocket.OPEN) {
-            client.send(data);
+const broadcast = (room: String, data: any) => {
+    users.forEach((u) => {
+        if (u.room === room && u.ws.readyState === WebSocket.OPEN) {
+            u.ws.send(data);
         }
     });
 };
+
+const broadcastUsers = (room: String) => {
+    const members = users.filter((u) => u.room === room);
+    broadcast(
+        room,
+        JSON.stringify({
+            messageType: 'users',
+            dataArray: members.map((u) => u.nick),
+            data: JSON.stringify(members.map((u) => ({ name: u.nick, avatar: u.avatar, gravatar: u.gravatar, key: u.publicKey, away: u.away || undefined, voice: u.voice || undefined, role: roleOf(u), roomRole: roomRoleOf(u) }))),
+        })
+    );
+};
@@ -0,0 +1,325 @@
+//! Where pictures of users come from.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use gloo_storage::{LocalStorage, Storage};
+use js_sys::{encode_uri_component, Array, Uint8Array};
+use reqwasm::http::Request;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use web_sys::{Blob, BlobPropertyBag, Url};
+
+use crate::sanitize;
+
+const SETTINGS_KEY: &str = "yewchat.avatar";
+
+/// Style of the generated pictures when the build doesn't pick one.
+const DEFAULT_STYLE: &str = "adventurer-neutral";
+
+/// Background colors of initials, picked by name so everyone keeps theirs.
+const INITIALS_COLORS: [&str; 8] = [
+    "#2563eb", "#7c3aed", "#db2777", "#dc2626", "#ea580c", "#16a34a", "#0d9488", "#4b5563",
+];
+
+thread_local! {
+    /// Generated URLs by name and Gravatar hash, so rendering a message doesn't build one again.
+    static GENERATED: RefCell<HashMap<(String, Option<String>), String>> = RefCell::default();
+    /// Object URLs of the pictures fetched so far, by the URL they came from. Never revoked: there
+    /// is one per user seen, and any of them may still be on screen.
+    static FETCHED: RefCell<HashMap<String, String>> = RefCell::default();
+    /// URLs fetched or being fetched, so failures aren't retried on every user list.
+    static REQUESTED: RefCell<HashSet<String>> = RefCell::default();
+}
+
+/// A way of turning a user into a picture URL.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AvatarProvider {
+    /// Generated by DiceBear from the name, in one of its styles such as `bottts`.
+    Dicebear(String),
+    /// A picture of their own, such as from GitHub or their Matrix homeserver.
+    Url(String),
+    /// The Gravatar of an email address, given as its SHA-256 hash. Gravatar serves a DiceBear
+    /// picture for addresses it doesn't know.
+    Gravatar(String),
+    /// The first letters of the name on a colored circle, drawn without asking anyone.
+    Initials,
+}
+
+impl AvatarProvider {
+    /// What users without a picture of their own get, as chosen when building the client with
+    /// `YEWCHAT_AVATARS=dicebear/<style>` or `YEWCHAT_AVATARS=initials`.
+    pub fn configured() -> Self {
+        option_env!("YEWCHAT_AVATARS")
+            .and_then(Self::parse)
+            .unwrap_or_else(|| Self::Dicebear(DEFAULT_STYLE.into()))
+    }
+
+    fn parse(setting: &str) -> Option<Self> {
+        match setting.split_once('/') {
+            Some(("dicebear", style)) if !style.is_empty() => Some(Self::Dicebear(style.into())),
+            None if setting == "initials" => Some(Self::Initials),
+            _ => None,
+        }
+    }
+
+    pub fn url(&self, name: &str) -> String {
+        match self {
+            Self::Dicebear(style) => format!(
+                "https://avatars.dicebear.com/api/{}/{}.svg",
+                style,
+                encode_uri_component(name)
+            ),
+            Self::Url(url) => url.clone(),
+            Self::Gravatar(hash) => {
+                // Gravatar fetches the fallback itself, so it can't be one drawn here.
+                let fallback = match Self::configured() {
+                    Self::Dicebear(style) => Self::Dicebear(style),
+                    _ => Self::Dicebear(DEFAULT_STYLE.into()),
+                };
+                format!(
+                    "https://www.gravatar.com/avatar/{}?s=128&d={}",
+                    hash,
+                    encode_uri_component(&fallback.url(name))
+                )
+            }
+            Self::Initials => initials_svg(name),
+        }
+    }
+}
+
+/// The picture of `name`: `own` if they have one, their Gravatar if they announced one, or else
+/// the configured provider's. Pictures already [`prefetch`]ed come from memory.
+pub fn url_for(name: &str, own: Option<&str>, gravatar: Option<&str>) -> String {
+    let url = match own.and_then(sanitize::image) {
+        Some(url) => AvatarProvider::Url(url).url(name),
+        None => generated(name, gravatar.filter(|h| is_gravatar_hash(h))),
+    };
+    loaded(url)
+}
+
+fn generated(name: &str, gravatar: Option<&str>) -> String {
+    let key = (name.to_string(), gravatar.map(str::to_string));
+    if let Some(url) = GENERATED.with(|urls| urls.borrow().get(&key).cloned()) {
+        return url;
+    }
+    let url = match gravatar {
+        Some(hash) => AvatarProvider::Gravatar(hash.into()),
+        None => AvatarProvider::configured(),
+    }
+    .url(name);
+    GENERATED.with(|urls| urls.borrow_mut().insert(key, url.clone()));
+    url
+}
+
+/// The object URL `url` was fetched into, or `url` itself until it has been.
+pub fn loaded(url: String) -> String {
+    FETCHED.with(|fetched| fetched.borrow().get(&url).cloned().unwrap_or(url))
+}
+
+/// Fetches the pictures behind `urls` into memory, so that they show at once however often the
+/// list they're in is redrawn. Resolves to whether any of them is newly available through
+/// [`loaded`]. Pictures that fail to load, or that don't allow it, are left to the `<img>`.
+pub async fn prefetch(urls: impl IntoIterator<Item = String>) -> bool {
+    let wanted: Vec<String> = urls
+        .into_iter()
+        .filter(|url| url.starts_with("https://") || url.starts_with("http://"))
+        .filter(|url| REQUESTED.with(|requested| requested.borrow_mut().insert(url.clone())))
+        .collect();
+    let mut any = false;
+    for url in wanted {
+        match fetch(&url).await {
+            Ok(object_url) => {
+                FETCHED.with(|fetched| fetched.borrow_mut().insert(url, object_url));
+                any = true;
+            }
+            Err(e) => log::debug!("avatar {} not prefetched: {}", url, e),
+        }
+    }
+    any
+}
+
+async fn fetch(url: &str) -> Result<String, String> {
+    let response = Request::get(url).send().await.map_err(|e| e.to_string())?;
+    if !response.ok() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    let mime = response.headers().get("content-type").ok().flatten();
+    let bytes = response.binary().await.map_err(|e| e.to_string())?;
+    let mut options = BlobPropertyBag::new();
+    if let Some(mime) = &mime {
+        options.type_(mime);
+    }
+    let blob = Blob::new_with_u8_array_sequence_and_options(
+        &Array::of1(&Uint8Array::from(bytes.as_slice())),
+        &options,
+    )
+    .map_err(|e| format!("{:?}", e))?;
+    Url::create_object_url_with_blob(&blob).map_err(|e| format!("{:?}", e))
+}
+
+/// How Gravatar knows an address: the SHA-256 of it, trimmed and in lower case.
+pub fn gravatar_hash(email: &str) -> String {
+    let digest = Sha256::digest(email.trim().to_lowercase().as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Others choose their hash, and it ends up in a URL, so it had better be one.
+fn is_gravatar_hash(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// How someone wants to be pictured, kept in localStorage for each username.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AvatarSettings {
+    /// Show the Gravatar of `email` instead of a generated picture.
+    #[serde(default)]
+    pub use_gravatar: bool,
+    /// Stays in this browser; others only get its hash.
+    #[serde(default)]
+    pub email: String,
+}
+
+impl AvatarSettings {
+    pub fn load(username: &str) -> Self {
+        LocalStorage::get(settings_key(username)).unwrap_or_default()
+    }
+
+    pub fn save(&self, username: &str) {
+        if let Err(e) = LocalStorage::set(settings_key(username), self) {
+            log::error!("failed to save avatar settings: {:?}", e);
+        }
+    }
+
+    /// The hash to announce, when the Gravatar is to be shown.
+    pub fn gravatar(&self) -> Option<String> {
+        (self.use_gravatar && !self.email.trim().is_empty()).then(|| gravatar_hash(&self.email))
+    }
+}
+
+fn settings_key(username: &str) -> String {
+    format!("{}.{}", SETTINGS_KEY, username.to_lowercase())
+}
+
+/// Up to two letters: the starts of the first two words of `name`, or else its first two.
+fn initials(name: &str) -> String {
+    let words: Vec<&str> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    let letters: String = match words.as_slice() {
+        [first, second, ..] => first
+            .chars()
+            .take(1)
+            .chain(second.chars().take(1))
+            .collect(),
+        [only] => only.chars().take(2).collect(),
+        [] => "?".into(),
+    };
+    letters.to_uppercase()
+}
+
+fn initials_svg(name: &str) -> String {
+    let hash = name
+        .bytes()
+        .fold(0usize, |h, b| h.wrapping_mul(31).wrapping_add(b as usize));
+    let svg = format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 64 64"><circle cx="32" cy="32" r="32" fill="{}"/><text x="32" y="32" dy=".35em" text-anchor="middle" font-family="sans-serif" font-size="26" fill="#fff">{}</text></svg>"##,
+        INITIALS_COLORS[hash % INITIALS_COLORS.len()],
+        initials(name)
+    );
+    format!("data:image/svg+xml,{}", encode_uri_component(&svg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn parses_the_build_setting() {
+        assert_eq!(
+            AvatarProvider::parse("dicebear/bottts"),
+            Some(AvatarProvider::Dicebear("bottts".into()))
+        );
+        assert_eq!(
+            AvatarProvider::parse("initials"),
+            Some(AvatarProvider::Initials)
+        );
+        assert_eq!(AvatarProvider::parse("dicebear/"), None);
+        assert_eq!(AvatarProvider::parse("gravatar"), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn picture_urls() {
+        assert_eq!(
+            url_for("alice", Some("https://example.com/a.png"), None),
+            "https://example.com/a.png"
+        );
+        assert_eq!(
+            url_for("alice", Some("javascript:alert(1)"), None),
+            url_for("alice", None, None)
+        );
+        assert_eq!(
+            AvatarProvider::Dicebear("bottts".into()).url("a b"),
+            "https://avatars.dicebear.com/api/bottts/a%20b.svg"
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn prefetched_pictures_come_from_memory() {
+        let url = url_for("carol", None, None);
+        assert_eq!(url_for("carol", None, None), url);
+        FETCHED.with(|fetched| {
+            fetched
+                .borrow_mut()
+                .insert(url.clone(), "blob:carol".into())
+        });
+        assert_eq!(url_for("carol", None, None), "blob:carol");
+        assert_eq!(
+            loaded("https://example.com/a.png".into()),
+            "https://example.com/a.png"
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn gravatars() {
+        // The example from Gravatar's documentation.
+        let hash = gravatar_hash(" MyEmailAddress@example.com ");
+        assert_eq!(
+            hash,
+            "84059b07d4be67b806386c0aad8070a23f18836bbaae342275dc0a83414c32ee"
+        );
+        assert!(url_for("alice", None, Some(&hash)).starts_with(&format!(
+            "https://www.gravatar.com/avatar/{}?s=128&d=https%3A%2F%2F",
+            hash
+        )));
+        assert!(!url_for("alice", None, Some("x?d=https://evil.example")).contains("evil"));
+        assert_eq!(
+            url_for("alice", Some("https://example.com/a.png"), Some(&hash)),
+            "https://example.com/a.png"
+        );
+
+        let settings = AvatarSettings {
+            use_gravatar: false,
+            email: "alice@example.com".into(),
+        };
+        assert_eq!(settings.gravatar(), None);
+        let settings = AvatarSettings {
+            use_gravatar: true,
+            ..settings
+        };
+        assert_eq!(
+            settings.gravatar(),
+            Some(gravatar_hash("alice@example.com"))
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn initials_of_names() {
+        assert_eq!(initials("alice"), "AL");
+        assert_eq!(initials("mary-jane"), "MJ");
+        assert_eq!(initials("@bob:matrix.org"), "BM");
+        assert_eq!(initials("__"), "?");
+    }
+}
@@ -0,0 +1,209 @@
+//! The parts of a message that stand on lines of their own: ```` ```mermaid ```` diagrams, and
+//! tables written in markdown or pasted from a spreadsheet as tab-separated rows.
+
+/// A piece of a message, split by [`split`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Block {
+    Text(String),
+    /// The source of a diagram, without its fences.
+    Diagram(String),
+    Table(Table),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Table {
+    pub header: Vec<String>,
+    /// One for each column.
+    pub align: Vec<Align>,
+    /// As wide as the header, padded or cut to it.
+    pub rows: Vec<Vec<String>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Splits a message into text and the blocks in it. A diagram that is never closed, or a table
+/// without rows, stays text.
+pub fn split(text: &str) -> Vec<Block> {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut blocks = vec![];
+    let mut plain = String::new();
+    let mut at = 0;
+    while at < lines.len() {
+        let found = diagram_at(&lines[at..])
+            .or_else(|| markdown_table_at(&lines[at..]))
+            .or_else(|| tsv_table_at(&lines[at..]));
+        match found {
+            Some((block, len)) => {
+                // A block stands on its own; the line breaks around it would only add space.
+                let before = plain.strip_suffix('\n').unwrap_or(&plain);
+                if !before.is_empty() {
+                    blocks.push(Block::Text(before.to_string()));
+                }
+                plain.clear();
+                blocks.push(block);
+                at += len;
+            }
+            None => {
+                plain.push_str(lines[at]);
+                at += 1;
+            }
+        }
+    }
+    if !plain.is_empty() {
+        blocks.push(Block::Text(plain));
+    }
+    blocks
+}
+
+/// A diagram starting at the first line, and how many lines it takes.
+fn diagram_at(lines: &[&str]) -> Option<(Block, usize)> {
+    if lines.first()?.trim() != "```mermaid" {
+        return None;
+    }
+    let end = lines.iter().skip(1).position(|l| l.trim() == "```")? + 1;
+    let source: String = lines[1..end].concat();
+    Some((Block::Diagram(source.trim_end().to_string()), end + 1))
+}
+
+/// A markdown table starting at the first line: a header row, a `|---|:---:|` line and its rows.
+fn markdown_table_at(lines: &[&str]) -> Option<(Block, usize)> {
+    let header = markdown_row(lines.first()?)?;
+    let align = lines
+        .get(1)
+        .and_then(|l| markdown_row(l))?
+        .iter()
+        .map(|cell| alignment(cell))
+        .collect::<Option<Vec<_>>>()?;
+    if align.len() != header.len() {
+        return None;
+    }
+    let rows: Vec<Vec<String>> = lines[2..].iter().map_while(|l| markdown_row(l)).collect();
+    let len = rows.len() + 2;
+    Some((Block::Table(Table::new(header, align, rows)), len))
+}
+
+/// The cells of a line like `| a | b |`; the outer pipes may be left out.
+fn markdown_row(line: &str) -> Option<Vec<String>> {
+    let line = line.trim();
+    if !line.contains('|') {
+        return None;
+    }
+    let inner = line.strip_prefix('|').unwrap_or(line);
+    let inner = inner.strip_suffix('|').unwrap_or(inner);
+    Some(inner.split('|').map(|c| c.trim().to_string()).collect())
+}
+
+/// How a column of the `|---|:---:|` line is aligned, if the cell is one.
+fn alignment(cell: &str) -> Option<Align> {
+    let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+    if dashes.is_empty() || dashes.chars().any(|c| c != '-') {
+        return None;
+    }
+    Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+        (true, true) => Align::Center,
+        (false, true) => Align::Right,
+        _ => Align::Left,
+    })
+}
+
+/// Rows pasted from a spreadsheet starting at the first line: at least two, with the same number
+/// of tab-separated cells. The first is taken as the header.
+fn tsv_table_at(lines: &[&str]) -> Option<(Block, usize)> {
+    let cells = |line: &str| -> Vec<String> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        line.split('\t').map(|c| c.trim().to_string()).collect()
+    };
+    let header = cells(lines.first()?);
+    if header.len() < 2 {
+        return None;
+    }
+    let rows: Vec<Vec<String>> = lines[1..]
+        .iter()
+        .map(|l| cells(l))
+        .take_while(|row| row.len() == header.len())
+        .collect();
+    if rows.is_empty() {
+        return None;
+    }
+    let len = rows.len() + 1;
+    let align = vec![Align::Left; header.len()];
+    Some((Block::Table(Table::new(header, align, rows)), len))
+}
+
+impl Table {
+    fn new(header: Vec<String>, align: Vec<Align>, mut rows: Vec<Vec<String>>) -> Self {
+        for row in &mut rows {
+            row.resize(header.len(), String::new());
+        }
+        Self {
+            header,
+            align,
+            rows,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    fn strings(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[wasm_bindgen_test]
+    fn finds_mermaid_blocks() {
+        assert_eq!(
+            split("look:\n```mermaid\ngraph TD\n  A --> B\n```\nneat"),
+            [
+                Block::Text("look:".into()),
+                Block::Diagram("graph TD\n  A --> B".into()),
+                Block::Text("neat".into()),
+            ]
+        );
+        let unclosed = "```mermaid\ngraph TD\nA --> B";
+        assert_eq!(split(unclosed), [Block::Text(unclosed.into())]);
+        let rust = "```rust\nfn main() {}\n```";
+        assert_eq!(split(rust), [Block::Text(rust.into())]);
+    }
+
+    #[wasm_bindgen_test]
+    fn finds_markdown_tables() {
+        let blocks =
+            split("results:\n| name | score |\n|:--|--:|\n| ann | 3 |\n| bob |\nthat's all");
+        assert_eq!(
+            blocks,
+            [
+                Block::Text("results:".into()),
+                Block::Table(Table {
+                    header: strings(&["name", "score"]),
+                    align: vec![Align::Left, Align::Right],
+                    rows: vec![strings(&["ann", "3"]), strings(&["bob", ""])],
+                }),
+                Block::Text("that's all".into()),
+            ]
+        );
+        let prose = "a | b\nnot a separator";
+        assert_eq!(split(prose), [Block::Text(prose.into())]);
+    }
+
+    #[wasm_bindgen_test]
+    fn finds_pasted_rows() {
+        assert_eq!(
+            split("city\tpop\nOslo\t700k\nBergen\t290k\n"),
+            [Block::Table(Table {
+                header: strings(&["city", "pop"]),
+                align: vec![Align::Left; 2],
+                rows: vec![strings(&["Oslo", "700k"]), strings(&["Bergen", "290k"])],
+            })]
+        );
+        let single = "just\tone line";
+        assert_eq!(split(single), [Block::Text(single.into())]);
+    }
+}
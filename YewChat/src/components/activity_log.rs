@@ -0,0 +1,93 @@
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::services::activity;
+use crate::store::Connection;
+use crate::time;
+
+#[derive(Properties, PartialEq)]
+pub struct ActivityLogProps {
+    pub username: String,
+}
+
+/// The activity log of `username`, as of when it was opened.
+#[function_component(ActivityLog)]
+pub fn activity_log(props: &ActivityLogProps) -> Html {
+    let entries = {
+        let username = props.username.clone();
+        use_state(move || activity::load(&username))
+    };
+    let clear = {
+        let entries = entries.clone();
+        let username = props.username.clone();
+        Callback::from(move |_| {
+            activity::clear(&username);
+            entries.set(vec![]);
+        })
+    };
+
+    html! {
+        <details class="w-full px-4 py-2 border-b border-gray-200 bg-gray-50 text-sm text-gray-700">
+            <summary class="cursor-pointer select-none">
+                {format!("Activity log · {} messages sent this session", activity::sent())}
+            </summary>
+            <div class="mt-2 max-h-48 overflow-y-auto">
+                {
+                    if entries.is_empty() {
+                        html! { <div class="text-gray-500">{"Nothing recorded yet."}</div> }
+                    } else {
+                        entries.iter().map(|entry| html! {
+                            <div class="flex gap-3 py-0.5">
+                                <span class="text-gray-500 tabular-nums">{time::absolute_label(entry.time)}</span>
+                                <span class="grow">{entry.event.label()}</span>
+                                <span class="text-xs text-gray-400">{format!("{} sent", entry.sent)}</span>
+                            </div>
+                        }).collect::<Html>()
+                    }
+                }
+            </div>
+            <button onclick={clear} class="mt-2 text-xs text-gray-500 hover:text-gray-800">{"Clear"}</button>
+        </details>
+    }
+}
+
+/// Records in the activity log of `username` when the chat started, each `room` entered and the
+/// `connection` being lost and back, for making sense of a flaky connection afterwards.
+pub fn use_activity_log(username: &str, room: &str, connection: Connection) {
+    {
+        let username = username.to_string();
+        use_effect_with_deps(
+            move |_| {
+                activity::record(&username, activity::Event::Started);
+                || ()
+            },
+            (),
+        );
+    }
+    {
+        let username = username.to_string();
+        use_effect_with_deps(
+            move |room: &String| {
+                activity::record(&username, activity::Event::Joined { room: room.clone() });
+                || ()
+            },
+            room.to_string(),
+        );
+    }
+    {
+        let username = username.to_string();
+        use_effect_with_deps(
+            move |connection| {
+                match connection {
+                    Connection::Online => activity::record(&username, activity::Event::Connected),
+                    Connection::Unreachable { attempts: 1 } => {
+                        activity::record(&username, activity::Event::ConnectionLost)
+                    }
+                    _ => {}
+                }
+                || ()
+            },
+            connection,
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use gloo_timers::callback::Interval;
+use yew::functional::*;
+use yew::prelude::*;
+use yew_agent::use_bridge;
+use yew_router::prelude::*;
+
+use crate::components::room_icon::RoomIcon;
+use crate::protocol::{AdminStats, MsgTypes, WebSocketMessage};
+use crate::services::event_bus::EventBus;
+use crate::services::websocket::WebsocketService;
+use crate::time;
+use crate::{Route, User, DEFAULT_ROOM};
+
+/// How often the server is asked again.
+const REFRESH_MS: u32 = 5_000;
+
+/// What the server last answered.
+#[derive(Clone, PartialEq)]
+enum Answer {
+    Waiting,
+    Denied,
+    Stats(AdminStats),
+}
+
+/// The admin view: who is connected where, the rooms in use and how busy the server is. Only the
+/// accounts the server lists as administrators are shown anything.
+#[function_component(Admin)]
+pub fn admin() -> Html {
+    let user = use_context::<User>().expect("No context found.");
+    let answer = use_state(|| Answer::Waiting);
+    let token = user.tokens.borrow().as_ref().map(|t| t.token.clone());
+    // A connection of its own, which doesn't register: we are only looking.
+    let wss = use_state(move || {
+        token.map(|token| WebsocketService::with_token(&token, Callback::noop()))
+    });
+
+    {
+        let answer = answer.clone();
+        use_bridge::<EventBus, _>(move |frame: String| {
+            let Ok(msg) = serde_json::from_str::<WebSocketMessage>(&frame) else {
+                return;
+            };
+            if !matches!(msg.message_type, MsgTypes::Admin) {
+                return;
+            }
+            match msg.data.as_deref().map(serde_json::from_str::<AdminStats>) {
+                Some(Ok(stats)) => answer.set(Answer::Stats(stats)),
+                Some(Err(e)) => log::error!("admin: unreadable stats: {}", e),
+                None => answer.set(Answer::Denied),
+            }
+        });
+    }
+    {
+        let wss = (*wss).clone();
+        use_effect_with_deps(
+            move |_| {
+                let ask = move || {
+                    if let Some(wss) = &wss {
+                        wss.send(&WebSocketMessage::new(MsgTypes::Admin));
+                    }
+                };
+                ask();
+                let refresh = Interval::new(REFRESH_MS, ask);
+                move || drop(refresh)
+            },
+            (),
+        );
+    }
+
+    let back = Route::ChatRoom {
+        room: DEFAULT_ROOM.into(),
+    };
+    let now = time::now();
+
+    html! {
+        <div class="flex flex-col w-screen h-screen bg-gray-50">
+            <div class="w-full h-16 border-b border-gray-200 bg-white shadow-sm flex items-center gap-3 px-4">
+                <Link<Route> to={back} classes="p-2 rounded-full text-gray-600 hover:bg-gray-100 transition-colors">
+                    <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                        <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M15 19l-7-7 7-7" />
+                    </svg>
+                </Link<Route>>
+                <div class="text-xl font-semibold">{"Admin"}</div>
+                {
+                    match &*answer {
+                        Answer::Stats(stats) => html! {
+                            <div class="text-sm text-gray-500">
+                                {format!("Up since {}", time::absolute_label(stats.started_at))}
+                            </div>
+                        },
+                        _ => html! {},
+                    }
+                }
+            </div>
+            <div class="w-full flex-grow overflow-auto p-4">
+                {
+                    match (&*wss, &*answer) {
+                        (None, _) => html! {
+                            <div class="text-center text-gray-500 mt-8">{"Log in with an account of this server first."}</div>
+                        },
+                        (Some(_), Answer::Waiting) => html! {
+                            <div class="text-center text-gray-500 mt-8">{"Asking the server..."}</div>
+                        },
+                        (Some(_), Answer::Denied) => html! {
+                            <div class="text-center text-gray-500 mt-8">{"Only administrators of this server can see this."}</div>
+                        },
+                        (Some(_), Answer::Stats(stats)) => view_stats(stats, now),
+                    }
+                }
+            </div>
+        </div>
+    }
+}
+
+fn view_stats(stats: &AdminStats, now: f64) -> Html {
+    let card = |label: &str, value: String| {
+        html! {
+            <div class="p-4 rounded-lg bg-white shadow-sm">
+                <div class="text-2xl font-semibold">{value}</div>
+                <div class="text-sm text-gray-500">{label.to_string()}</div>
+            </div>
+        }
+    };
+    html! {
+        <div class="max-w-4xl mx-auto flex flex-col gap-6">
+            <div class="grid grid-cols-2 md:grid-cols-4 gap-4">
+                { card("connected", stats.users.len().to_string()) }
+                { card("rooms in use", stats.rooms.len().to_string()) }
+                { card("messages in the last minute", stats.messages.last_minute.to_string()) }
+                { card("messages in the last hour", stats.messages.last_hour.to_string()) }
+            </div>
+            <div class="text-sm text-gray-500">
+                {format!(
+                    "{} messages posted since the server started, {}.",
+                    stats.messages.total,
+                    time::relative_label(stats.started_at, now)
+                )}
+            </div>
+            <div class="grid md:grid-cols-2 gap-6">
+                <table class="w-full text-sm bg-white rounded-lg shadow-sm overflow-hidden">
+                    <thead class="bg-gray-100 text-left">
+                        <tr>
+                            <th class="px-3 py-2">{"Room"}</th>
+                            <th class="px-3 py-2 text-right">{"Connected"}</th>
+                            <th class="px-3 py-2 text-right">{"Messages kept"}</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {
+                            stats.rooms.iter().map(|room| html! {
+                                <tr class="border-t border-gray-100">
+                                    <td class="px-3 py-2">
+                                        <span class="flex items-center gap-2">
+                                            {
+                                                if let Some(icon) = &room.icon {
+                                                    html! { <RoomIcon icon={icon.clone()} class={classes!("w-5", "h-5")} /> }
+                                                } else {
+                                                    html! {}
+                                                }
+                                            }
+                                            {format!("# {}", room.name)}
+                                        </span>
+                                    </td>
+                                    <td class="px-3 py-2 text-right">{room.members}</td>
+                                    <td class="px-3 py-2 text-right">{room.kept}</td>
+                                </tr>
+                            }).collect::<Html>()
+                        }
+                    </tbody>
+                </table>
+                <table class="w-full text-sm bg-white rounded-lg shadow-sm overflow-hidden">
+                    <thead class="bg-gray-100 text-left">
+                        <tr>
+                            <th class="px-3 py-2">{"User"}</th>
+                            <th class="px-3 py-2">{"Room"}</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {
+                            stats.users.iter().map(|user| html! {
+                                <tr class="border-t border-gray-100">
+                                    <td class="px-3 py-2">{user.name.clone()}</td>
+                                    <td class="px-3 py-2">{format!("# {}", user.room)}</td>
+                                </tr>
+                            }).collect::<Html>()
+                        }
+                    </tbody>
+                </table>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,122 @@
+use std::rc::Rc;
+
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlInputElement;
+use yew::functional::*;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::components::message_list::MessageList;
+use crate::protocol::MessageData;
+use crate::services::export::{self, Transcript};
+use crate::time;
+use crate::{Route, User, DEFAULT_ROOM};
+
+/// Read-only viewer for a transcript exported as JSON. Works offline: nothing is sent anywhere.
+#[function_component(Archive)]
+pub fn archive() -> Html {
+    let user = use_context::<User>().expect("No context found.");
+    let transcript = use_state(|| Option::<Rc<Transcript>>::None);
+    let messages = use_state(Vec::<Rc<MessageData>>::new);
+    let error = use_state(|| Option::<String>::None);
+
+    let onchange = {
+        let transcript = transcript.clone();
+        let messages = messages.clone();
+        let error = error.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            let transcript = transcript.clone();
+            let messages = messages.clone();
+            let error = error.clone();
+            spawn_local(async move {
+                match export::read_transcript(&file).await {
+                    Ok(mut loaded) => {
+                        messages.set(loaded.messages.drain(..).map(Rc::new).collect());
+                        transcript.set(Some(Rc::new(loaded)));
+                        error.set(None);
+                    }
+                    Err(e) => error.set(Some(e)),
+                }
+            });
+        })
+    };
+
+    let back = if user.tokens.borrow().is_some() {
+        Route::ChatRoom {
+            room: DEFAULT_ROOM.into(),
+        }
+    } else {
+        Route::Login
+    };
+    let current_username = user.username.borrow().clone();
+
+    html! {
+        <div class="flex flex-col w-screen h-screen bg-gray-50">
+            <div class="w-full h-16 border-b border-gray-200 bg-white shadow-sm flex items-center gap-3 px-4">
+                <Link<Route> to={back} classes="p-2 rounded-full text-gray-600 hover:bg-gray-100 transition-colors">
+                    <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                        <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M15 19l-7-7 7-7" />
+                    </svg>
+                </Link<Route>>
+                {
+                    if let Some(transcript) = &*transcript {
+                        html! {
+                            <>
+                                <div class="text-xl font-semibold">{
+                                    // Direct conversations are exported as `@peer`.
+                                    if transcript.room.starts_with('@') {
+                                        transcript.room.clone()
+                                    } else {
+                                        format!("# {}", transcript.room)
+                                    }
+                                }</div>
+                                <div class="bg-yellow-100 text-yellow-800 text-xs px-2 py-1 rounded-full">{"Imported · read-only"}</div>
+                                <div class="text-sm text-gray-500">
+                                    {format!("Exported {}", time::absolute_label(transcript.exported_at))}
+                                </div>
+                            </>
+                        }
+                    } else {
+                        html! { <div class="text-xl font-semibold">{"Open a transcript"}</div> }
+                    }
+                }
+                <label class="ml-auto px-3 py-2 rounded-full text-sm text-gray-600 hover:bg-gray-100 transition-colors cursor-pointer">
+                    {"Choose file..."}
+                    <input type="file" accept="application/json,.json" class="hidden" {onchange} />
+                </label>
+            </div>
+            {
+                if let Some(error) = &*error {
+                    html! { <div class="w-full px-4 pt-3 text-sm text-red-600">{error.clone()}</div> }
+                } else {
+                    html! {}
+                }
+            }
+            <div class="w-full flex-grow overflow-auto p-4 bg-gradient-to-b from-yellow-50 to-gray-50">
+                {
+                    match &*transcript {
+                        Some(_) if messages.is_empty() => html! {
+                            <div class="text-center text-gray-500 mt-8">{"This transcript has no messages."}</div>
+                        },
+                        Some(_) => html! {
+                            <MessageList
+                                messages={(*messages).clone()}
+                                current_username={current_username.clone()}
+                                now={time::now()}
+                            />
+                        },
+                        None => html! {
+                            <div class="text-center text-gray-500 mt-8">
+                                {"Pick a JSON file saved with \"Export\" to read it here."}
+                            </div>
+                        },
+                    }
+                }
+            </div>
+        </div>
+    }
+}
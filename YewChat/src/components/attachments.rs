@@ -0,0 +1,293 @@
+use std::rc::Rc;
+
+use web_sys::File;
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::protocol::{MsgTypes, Restrictions, WebSocketMessage};
+use crate::services::attachment::{format_size, Attachment, AttachmentReader};
+use crate::services::websocket::WebsocketService;
+
+/// A file currently being read from disk before it is sent.
+#[derive(Clone)]
+struct Upload {
+    name: String,
+    loaded: f64,
+    total: f64,
+    _reader: Rc<AttachmentReader>,
+}
+
+/// Attachments on their way: dragged over the chat, being read, or waiting for confirmation.
+#[derive(Clone, Default)]
+struct Attachments {
+    drag_depth: u32,
+    upload: Option<Upload>,
+    error: Option<String>,
+    /// A pasted image, shown for confirmation before it is sent.
+    pending: Option<Attachment>,
+}
+
+enum AttachmentAction {
+    DragEnter,
+    DragLeave,
+    Dropped,
+    Started(Upload),
+    Progress(f64, f64),
+    /// The read is over. An attachment is only returned when it still needs confirming.
+    Finished(Result<Option<Attachment>, String>),
+    Failed(String),
+    ClearPending,
+}
+
+impl Reducible for Attachments {
+    type Action = AttachmentAction;
+
+    fn reduce(self: Rc<Self>, action: AttachmentAction) -> Rc<Self> {
+        let mut state = (*self).clone();
+        match action {
+            AttachmentAction::DragEnter => state.drag_depth += 1,
+            AttachmentAction::DragLeave => state.drag_depth = state.drag_depth.saturating_sub(1),
+            AttachmentAction::Dropped => state.drag_depth = 0,
+            AttachmentAction::Started(upload) => {
+                state.error = None;
+                state.upload = Some(upload);
+            }
+            AttachmentAction::Progress(loaded, total) => {
+                if let Some(upload) = state.upload.as_mut() {
+                    upload.loaded = loaded;
+                    upload.total = total;
+                }
+            }
+            AttachmentAction::Finished(result) => {
+                state.upload = None;
+                match result {
+                    Ok(pending) => state.pending = pending,
+                    Err(e) => state.error = Some(e),
+                }
+            }
+            AttachmentAction::Failed(e) => state.error = Some(e),
+            AttachmentAction::ClearPending => state.pending = None,
+        }
+        Rc::new(state)
+    }
+}
+
+fn send_attachment(wss: &WebsocketService, attachment: Attachment) {
+    wss.send(&WebSocketMessage {
+        data: Some(String::new()),
+        attachment: Some(attachment),
+        ..WebSocketMessage::new(MsgTypes::Message)
+    });
+}
+
+/// Attachments on their way, and the handlers the chat takes them with.
+pub struct UseAttachments {
+    state: UseReducerHandle<Attachments>,
+    wss: WebsocketService,
+    /// An image pasted or a file picked in the composer, shown for confirmation before it is sent.
+    pub on_file: Callback<File>,
+    pub ondragenter: Callback<DragEvent>,
+    pub ondragleave: Callback<DragEvent>,
+    /// A file dropped on the chat, sent as soon as it is read.
+    pub ondrop: Callback<DragEvent>,
+}
+
+/// Reads the files given to the chat and sends them to the room. They are refused in a `direct`
+/// conversation, and when the room's `restrictions` don't allow their type.
+pub fn use_attachments(
+    wss: &WebsocketService,
+    direct: bool,
+    restrictions: Restrictions,
+) -> UseAttachments {
+    let state = use_reducer(Attachments::default);
+
+    let start_upload = {
+        let attachments = state.dispatcher();
+        let wss = wss.clone();
+        Rc::new(move |file: File, confirm: bool| {
+            if direct {
+                attachments.dispatch(AttachmentAction::Failed(
+                    "Attachments can't be sent in direct messages.".into(),
+                ));
+                return;
+            }
+            if let Some(reason) = restrictions.refuse_file(&file.type_()) {
+                attachments.dispatch(AttachmentAction::Failed(reason.into()));
+                return;
+            }
+            let name = file.name();
+            let total = file.size();
+            let on_progress = {
+                let attachments = attachments.clone();
+                Callback::from(move |(loaded, total)| {
+                    attachments.dispatch(AttachmentAction::Progress(loaded, total))
+                })
+            };
+            let on_done = {
+                let attachments = attachments.clone();
+                let wss = wss.clone();
+                Callback::from(move |result: Result<Attachment, String>| {
+                    let result = match result {
+                        Ok(attachment) if confirm => Ok(Some(attachment)),
+                        Ok(attachment) => {
+                            send_attachment(&wss, attachment);
+                            Ok(None)
+                        }
+                        Err(e) => Err(e),
+                    };
+                    attachments.dispatch(AttachmentAction::Finished(result));
+                })
+            };
+            match AttachmentReader::read(file, on_progress, on_done) {
+                Ok(reader) => attachments.dispatch(AttachmentAction::Started(Upload {
+                    name,
+                    loaded: 0.0,
+                    total,
+                    _reader: Rc::new(reader),
+                })),
+                Err(e) => attachments.dispatch(AttachmentAction::Failed(e)),
+            }
+        })
+    };
+    let on_file = {
+        let start_upload = start_upload.clone();
+        Callback::from(move |file| start_upload(file, true))
+    };
+    let ondrop = {
+        let attachments = state.dispatcher();
+        Callback::from(move |e: DragEvent| {
+            e.prevent_default();
+            attachments.dispatch(AttachmentAction::Dropped);
+            let file = e
+                .data_transfer()
+                .and_then(|dt| dt.files())
+                .and_then(|files| files.get(0));
+            if let Some(file) = file {
+                start_upload(file, false);
+            }
+        })
+    };
+    let ondragenter = {
+        let attachments = state.dispatcher();
+        Callback::from(move |e: DragEvent| {
+            e.prevent_default();
+            attachments.dispatch(AttachmentAction::DragEnter);
+        })
+    };
+    let ondragleave = {
+        let attachments = state.dispatcher();
+        Callback::from(move |_| attachments.dispatch(AttachmentAction::DragLeave))
+    };
+
+    UseAttachments {
+        state,
+        wss: wss.clone(),
+        on_file,
+        ondragenter,
+        ondragleave,
+        ondrop,
+    }
+}
+
+impl UseAttachments {
+    /// Shown over the chat while a file is dragged over it.
+    pub fn view_drop_overlay(&self) -> Html {
+        view_drop_overlay(self.state.drag_depth)
+    }
+
+    /// How the file being read is coming along, or why it couldn't be, then the pasted image
+    /// waiting for confirmation.
+    pub fn view_status(&self) -> Html {
+        html! {
+            <>
+                { view_upload_status(&self.state) }
+                { view_pending_attachment(&self.state, &self.wss) }
+            </>
+        }
+    }
+}
+
+fn view_drop_overlay(drag_depth: u32) -> Html {
+    if drag_depth == 0 {
+        return html! {};
+    }
+
+    html! {
+        <div class="absolute inset-0 z-20 m-4 flex items-center justify-center rounded-2xl border-4 border-dashed border-blue-500 bg-blue-50/80 pointer-events-none">
+            <div class="text-xl font-semibold text-blue-700">{"Drop a file to send it"}</div>
+        </div>
+    }
+}
+
+fn view_pending_attachment(
+    attachments: &UseReducerHandle<Attachments>,
+    wss: &WebsocketService,
+) -> Html {
+    let Some(attachment) = &attachments.pending else {
+        return html! {};
+    };
+    let cancel = {
+        let attachments = attachments.dispatcher();
+        Callback::from(move |_| attachments.dispatch(AttachmentAction::ClearPending))
+    };
+    let confirm = {
+        let attachments = attachments.dispatcher();
+        let wss = wss.clone();
+        let attachment = attachment.clone();
+        Callback::from(move |_| {
+            send_attachment(&wss, attachment.clone());
+            attachments.dispatch(AttachmentAction::ClearPending);
+        })
+    };
+
+    html! {
+        <div class="w-full px-4 pt-3">
+            <div class="flex items-center gap-3 p-3 rounded-lg bg-gray-100">
+                <img class="h-20 max-w-xs rounded-md object-contain bg-white shadow-sm" src={attachment.data.clone()} alt={attachment.name.clone()}/>
+                <div class="flex-grow min-w-0 text-sm text-gray-600">
+                    <div class="font-medium text-gray-800 truncate">{attachment.name.clone()}</div>
+                    <div>{format_size(attachment.size as f64)}</div>
+                </div>
+                <button
+                    onclick={cancel}
+                    class="px-4 py-2 rounded-full text-gray-700 hover:bg-gray-200 transition-colors"
+                >
+                    {"Cancel"}
+                </button>
+                <button
+                    onclick={confirm}
+                    class="px-4 py-2 rounded-full bg-blue-600 text-white hover:bg-blue-700 transition-colors"
+                >
+                    {"Send"}
+                </button>
+            </div>
+        </div>
+    }
+}
+
+fn view_upload_status(attachments: &Attachments) -> Html {
+    if let Some(upload) = &attachments.upload {
+        let percent = if upload.total > 0.0 {
+            (upload.loaded / upload.total * 100.0).round()
+        } else {
+            0.0
+        };
+        html! {
+            <div class="w-full px-4 pt-3 text-sm text-gray-600">
+                <div class="flex justify-between mb-1">
+                    <span class="truncate">{format!("Sending {}", upload.name)}</span>
+                    <span>{format!("{} / {}", format_size(upload.loaded), format_size(upload.total))}</span>
+                </div>
+                <div class="w-full h-2 bg-gray-200 rounded-full overflow-hidden">
+                    <div class="h-full bg-blue-600 transition-all" style={format!("width: {}%", percent)}></div>
+                </div>
+            </div>
+        }
+    } else if let Some(error) = &attachments.error {
+        html! {
+            <div class="w-full px-4 pt-3 text-sm text-red-600">{error.clone()}</div>
+        }
+    } else {
+        html! {}
+    }
+}
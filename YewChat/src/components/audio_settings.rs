@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use gloo_timers::callback::Interval;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlSelectElement;
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::services::audio_devices::{self, Device, Microphone};
+use crate::services::voice_activity::{Meters, TICK_MS};
+
+/// How loud the microphone has to be to fill the test bar. Talking at arm's length is about a
+/// tenth.
+const FULL_LEVEL: f32 = 0.25;
+
+#[derive(Properties, PartialEq)]
+pub struct AudioSettingsProps {
+    /// The speaker calls play through, or none for the browser's default.
+    pub speaker: Option<String>,
+    pub on_speaker_change: Callback<Option<String>>,
+}
+
+fn chosen(e: Event) -> Option<String> {
+    let select: HtmlSelectElement = e.target_unchecked_into();
+    Some(select.value()).filter(|id| !id.is_empty())
+}
+
+fn options(devices: &[Device], chosen: &Option<String>) -> Html {
+    html! {
+        <>
+            <option value="" selected={chosen.is_none()}>{"Default"}</option>
+            {
+                devices.iter().map(|device| html! {
+                    <option value={device.id.clone()} selected={chosen.as_ref() == Some(&device.id)}>
+                        {&device.label}
+                    </option>
+                }).collect::<Html>()
+            }
+        </>
+    }
+}
+
+/// The microphone and speaker for calls and voice channels, with a level bar to try the
+/// microphone. A new microphone is used from the next call or voice channel joined; a new speaker
+/// at once. Where the browser can't choose a speaker, only the microphone is offered.
+#[function_component(AudioSettings)]
+pub fn audio_settings(props: &AudioSettingsProps) -> Html {
+    // Microphones, then speakers.
+    let devices = use_state(|| (Vec::<Device>::new(), Vec::<Device>::new()));
+    let microphone = use_state(audio_devices::microphone);
+    let can_choose_speaker = use_state(audio_devices::can_choose_speaker);
+    // The microphone being tried, until the test is stopped or the settings closed.
+    let test = use_state(|| Option::<Rc<Microphone>>::None);
+    let level = use_state_eq(|| 0u8);
+    let notice = use_state(|| Option::<String>::None);
+
+    let refresh = {
+        let devices = devices.clone();
+        Callback::from(move |_: ()| {
+            let devices = devices.clone();
+            spawn_local(async move {
+                match audio_devices::list().await {
+                    Ok(found) => devices.set(found),
+                    Err(e) => log::debug!("can't list audio devices: {}", e),
+                }
+            });
+        })
+    };
+    // Devices come and go as headsets are plugged in.
+    {
+        let refresh = refresh.clone();
+        use_effect_with_deps(
+            move |_| {
+                refresh.emit(());
+                let media_devices = web_sys::window().unwrap().navigator().media_devices().ok();
+                let listener = Closure::wrap(
+                    Box::new(move |_: Event| refresh.emit(())) as Box<dyn FnMut(Event)>
+                );
+                if let Some(media_devices) = &media_devices {
+                    let _ = media_devices.add_event_listener_with_callback(
+                        "devicechange",
+                        listener.as_ref().unchecked_ref(),
+                    );
+                }
+                move || {
+                    if let Some(media_devices) = media_devices {
+                        let _ = media_devices.remove_event_listener_with_callback(
+                            "devicechange",
+                            listener.as_ref().unchecked_ref(),
+                        );
+                    }
+                }
+            },
+            (),
+        );
+    }
+    {
+        let level = level.clone();
+        use_effect_with_deps(
+            move |stream| {
+                let mut meters = Meters::default();
+                if let Some(stream) = stream {
+                    meters.follow(&BTreeMap::from([(String::new(), stream.clone())]));
+                }
+                let ticks = stream.is_some().then(|| {
+                    let level = level.clone();
+                    Interval::new(TICK_MS, move || {
+                        let loudness = meters.level("").unwrap_or_default();
+                        level.set((loudness / FULL_LEVEL * 100.0).min(100.0) as u8);
+                    })
+                });
+                move || {
+                    drop(ticks);
+                    level.set(0);
+                }
+            },
+            test.as_ref().map(|microphone| microphone.stream().clone()),
+        );
+    }
+
+    let start_test = {
+        let test = test.clone();
+        let notice = notice.clone();
+        Callback::from(move |_: ()| {
+            // Let go of the one being tried before asking for another.
+            test.set(None);
+            notice.set(None);
+            let test = test.clone();
+            let notice = notice.clone();
+            let refresh = refresh.clone();
+            spawn_local(async move {
+                match Microphone::open(false).await {
+                    Ok(microphone) => {
+                        test.set(Some(Rc::new(microphone)));
+                        // Allowed the microphone, the browser now names the devices.
+                        refresh.emit(());
+                    }
+                    Err(e) => {
+                        log::debug!("no microphone to test: {}", e);
+                        notice.set(Some("Microphone access was denied.".into()));
+                    }
+                }
+            });
+        })
+    };
+    let toggle_test = {
+        let test = test.clone();
+        let start_test = start_test.clone();
+        Callback::from(move |_| match *test {
+            Some(_) => test.set(None),
+            None => start_test.emit(()),
+        })
+    };
+    let on_microphone_change = {
+        let microphone = microphone.clone();
+        let testing = test.is_some();
+        Callback::from(move |e: Event| {
+            let id = chosen(e);
+            audio_devices::set_microphone(id.as_deref());
+            microphone.set(id);
+            if testing {
+                start_test.emit(());
+            }
+        })
+    };
+    let on_speaker_change = props.on_speaker_change.reform(|e: Event| {
+        let id = chosen(e);
+        audio_devices::set_speaker(id.as_deref());
+        id
+    });
+
+    html! {
+        <div class="w-full px-4 py-2 border-b border-gray-200 bg-gray-50 text-sm text-gray-700 flex flex-col gap-2">
+            <div class="flex items-center gap-2">
+                <span class="w-20 shrink-0">{"Microphone"}</span>
+                <select onchange={on_microphone_change} class="min-w-0 grow rounded-lg px-2 py-1 border border-gray-200 bg-white">
+                    { options(&devices.0, &microphone) }
+                </select>
+                <button
+                    onclick={toggle_test}
+                    title="Try the microphone: the bar moves as you speak"
+                    class="px-2 py-1 rounded-lg hover:bg-gray-100"
+                >
+                    { if test.is_some() { "Stop" } else { "Test" } }
+                </button>
+            </div>
+            {
+                if test.is_some() {
+                    html! {
+                        <div class="h-2 rounded-full bg-gray-200 overflow-hidden" title="Speak to see the bar move">
+                            <div class="h-full bg-green-500 transition-all" style={format!("width: {}%", *level)}></div>
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }
+            }
+            {
+                if *can_choose_speaker {
+                    html! {
+                        <div class="flex items-center gap-2">
+                            <span class="w-20 shrink-0">{"Speaker"}</span>
+                            <select onchange={on_speaker_change} class="min-w-0 grow rounded-lg px-2 py-1 border border-gray-200 bg-white">
+                                { options(&devices.1, &props.speaker) }
+                            </select>
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }
+            }
+            {
+                if let Some(notice) = &*notice {
+                    html! { <div class="text-xs text-red-600">{notice}</div> }
+                } else {
+                    html! {}
+                }
+            }
+        </div>
+    }
+}
@@ -0,0 +1,95 @@
+use web_sys::HtmlInputElement;
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::avatar::{self, AvatarSettings};
+
+#[derive(Properties, PartialEq)]
+pub struct AvatarSettingsPanelProps {
+    pub username: String,
+    /// Called with the saved settings.
+    pub on_change: Callback<AvatarSettings>,
+}
+
+/// Choosing between a generated picture and a Gravatar.
+#[function_component(AvatarSettingsPanel)]
+pub fn avatar_settings_panel(props: &AvatarSettingsPanelProps) -> Html {
+    let settings = {
+        let username = props.username.clone();
+        use_state(move || AvatarSettings::load(&username))
+    };
+
+    let choose = |use_gravatar: bool| {
+        let settings = settings.clone();
+        Callback::from(move |_| {
+            settings.set(AvatarSettings {
+                use_gravatar,
+                ..(*settings).clone()
+            })
+        })
+    };
+    let oninput = {
+        let settings = settings.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            settings.set(AvatarSettings {
+                email: input.value(),
+                ..(*settings).clone()
+            })
+        })
+    };
+    let onsubmit = {
+        let settings = settings.clone();
+        let username = props.username.clone();
+        let on_change = props.on_change.clone();
+        Callback::from(move |e: FocusEvent| {
+            e.prevent_default();
+            settings.save(&username);
+            on_change.emit((*settings).clone());
+        })
+    };
+
+    let preview = avatar::url_for(&props.username, None, settings.gravatar().as_deref());
+    let incomplete = settings.use_gravatar && settings.email.trim().is_empty();
+
+    html! {
+        <form {onsubmit} class="w-full px-4 py-3 border-b border-gray-200 bg-gray-50 text-sm text-gray-700 flex items-center gap-4">
+            <img class="w-12 h-12 rounded-full shadow-sm" src={preview} alt="avatar" />
+            <div class="flex flex-col gap-2 grow">
+                <div class="flex gap-4">
+                    <label class="flex items-center gap-1">
+                        <input type="radio" name="avatar" checked={!settings.use_gravatar} onchange={choose(false)} />
+                        {"Generated picture"}
+                    </label>
+                    <label class="flex items-center gap-1">
+                        <input type="radio" name="avatar" checked={settings.use_gravatar} onchange={choose(true)} />
+                        {"Gravatar"}
+                    </label>
+                </div>
+                {
+                    if settings.use_gravatar {
+                        html! {
+                            <>
+                                <input
+                                    {oninput}
+                                    value={settings.email.clone()}
+                                    type="email"
+                                    class="rounded-lg px-3 py-2 border border-gray-200 bg-white"
+                                    placeholder="Email address of your Gravatar"
+                                />
+                                <div class="text-xs text-gray-500">
+                                    {"Your address stays in this browser; others only see its hash."}
+                                </div>
+                            </>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+            </div>
+            <button type="submit" disabled={incomplete} class="px-3 py-2 rounded-lg bg-blue-600 text-white disabled:opacity-50">
+                {"Save"}
+            </button>
+        </form>
+    }
+}
@@ -0,0 +1,93 @@
+use gloo_timers::callback::Interval;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlSelectElement;
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::services::idle;
+use crate::services::websocket::WebsocketService;
+
+/// How often we look whether we have been idle long enough to show as away.
+const IDLE_CHECK_MS: u32 = 15_000;
+/// What counts as being at the keyboard.
+const IDLE_EVENTS: [&str; 5] = ["keydown", "pointerdown", "pointermove", "wheel", "focus"];
+
+#[derive(Properties, PartialEq)]
+pub struct AwaySettingsProps {
+    pub username: String,
+}
+
+/// How long without input or focus before we show as away. Saved as soon as it is picked.
+#[function_component(AwaySettings)]
+pub fn away_settings(props: &AwaySettingsProps) -> Html {
+    let minutes = {
+        let username = props.username.clone();
+        use_state(move || idle::away_after(&username))
+    };
+    let onchange = {
+        let minutes = minutes.clone();
+        let username = props.username.clone();
+        Callback::from(move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            if let Ok(chosen) = select.value().parse() {
+                idle::set_away_after(&username, chosen);
+                minutes.set(chosen);
+            }
+        })
+    };
+
+    html! {
+        <label class="w-full px-4 py-2 border-b border-gray-200 bg-gray-50 text-sm text-gray-700 flex items-center gap-2">
+            {"Show me as away after"}
+            <select {onchange} class="rounded-lg px-2 py-1 border border-gray-200 bg-white">
+                {
+                    idle::CHOICES.into_iter().map(|choice| html! {
+                        <option value={choice.to_string()} selected={choice == *minutes}>
+                            {
+                                match choice {
+                                    0 => "never".to_string(),
+                                    _ => format!("{} minutes idle", choice),
+                                }
+                            }
+                        </option>
+                    }).collect::<Html>()
+                }
+            </select>
+        </label>
+    }
+}
+
+/// Shows `username` as away after a while without input or focus, back on the next one, for as
+/// long as they chose.
+pub fn use_away(wss: &WebsocketService, username: &str) {
+    let wss = wss.clone();
+    use_effect_with_deps(
+        move |username: &String| {
+            let username = username.clone();
+            let window = web_sys::window().unwrap();
+            idle::active(&wss);
+            let onactivity = {
+                let wss = wss.clone();
+                Closure::wrap(Box::new(move || idle::active(&wss)) as Box<dyn FnMut()>)
+            };
+            for event in IDLE_EVENTS {
+                let _ = window
+                    .add_event_listener_with_callback(event, onactivity.as_ref().unchecked_ref());
+            }
+            let check = Interval::new(IDLE_CHECK_MS, move || {
+                idle::check(&wss, idle::away_after(&username))
+            });
+            move || {
+                drop(check);
+                for event in IDLE_EVENTS {
+                    let _ = window.remove_event_listener_with_callback(
+                        event,
+                        onactivity.as_ref().unchecked_ref(),
+                    );
+                }
+            }
+        },
+        username.to_string(),
+    );
+}
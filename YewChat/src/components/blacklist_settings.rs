@@ -0,0 +1,38 @@
+use std::rc::Rc;
+
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::services::keywords;
+
+#[derive(Properties, PartialEq)]
+pub struct BlacklistSettingsProps {
+    pub words: Rc<Vec<String>>,
+    /// Called with the new list, which the server sends back to everyone once it has it.
+    pub on_change: Callback<Vec<String>>,
+}
+
+/// The words the server masks in the room's messages, for its moderators. Sent when the field is
+/// left.
+#[function_component(BlacklistSettings)]
+pub fn blacklist_settings(props: &BlacklistSettingsProps) -> Html {
+    let onchange = {
+        let on_change = props.on_change.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            on_change.emit(keywords::parse(&input.value()));
+        })
+    };
+
+    html! {
+        <label class="w-full px-4 py-2 border-b border-gray-200 bg-gray-50 text-sm text-gray-700 flex items-center gap-2">
+            <span class="flex-none">{"Mask the words"}</span>
+            <input
+                {onchange}
+                value={props.words.join(", ")}
+                class="grow rounded-lg px-2 py-1 border border-gray-200 bg-white"
+                placeholder="single words, separated by commas"
+            />
+        </label>
+    }
+}
@@ -0,0 +1,938 @@
+use std::rc::Rc;
+
+use gloo_timers::callback::Timeout;
+use web_sys::{HtmlInputElement, HtmlMediaElement, MediaStream};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::protocol::{IceCandidate, MsgTypes, WebSocketMessage};
+use crate::services::audio_devices;
+use crate::services::call::{self, Call, CallEvent, CallStatus};
+use crate::services::call_log::{self, CallRecord, Outcome};
+use crate::services::recorder::Recorder;
+use crate::services::ringtone::Ringtone;
+use crate::services::websocket::WebsocketService;
+use crate::store::{Action, Store};
+use crate::time;
+
+/// How long an outgoing call rings before we give up.
+const RING_TIMEOUT_MS: u32 = 30_000;
+
+/// Ready-made replies for declining a call with a message.
+const DECLINE_REPLIES: [&str; 3] = [
+    "Can't talk now, I'll call you back.",
+    "I'm in a meeting.",
+    "Can you message me instead?",
+];
+
+/// Someone calling us, waiting for us to pick up.
+#[derive(Clone)]
+struct IncomingCall {
+    peer: String,
+    offer: String,
+    /// When it started ringing.
+    since: f64,
+    /// ICE candidates they sent while we were still ringing.
+    candidates: Vec<IceCandidate>,
+}
+
+#[derive(Clone)]
+struct ActiveCall {
+    call: Rc<Call>,
+    outgoing: bool,
+    /// When it started ringing, on either side.
+    since: f64,
+    /// When it got through, if it has.
+    connected_at: Option<f64>,
+    status: CallStatus,
+    local: Option<MediaStream>,
+    remote: Option<MediaStream>,
+    /// Our recording of the call, while we make one.
+    recorder: Option<Rc<Recorder>>,
+    /// Whether the peer says they are recording it.
+    peer_recording: bool,
+}
+
+impl ActiveCall {
+    fn new(call: Call, outgoing: bool, since: f64, status: CallStatus) -> Self {
+        Self {
+            call: Rc::new(call),
+            outgoing,
+            since,
+            connected_at: None,
+            status,
+            local: None,
+            remote: None,
+            recorder: None,
+            peer_recording: false,
+        }
+    }
+}
+
+/// The call we are in, if any. At most one call exists at a time, either ringing on our side or
+/// set up by `Call`. Signaling goes out over `wss` as the call progresses.
+#[derive(Clone)]
+pub struct CallState {
+    wss: WebsocketService,
+    incoming: Option<IncomingCall>,
+    active: Option<ActiveCall>,
+    /// Cleared while push-to-talk holds our voice back.
+    transmitting: bool,
+    /// Told of each call once it is over, for the call log.
+    on_ended: Callback<CallRecord>,
+    /// Why the last call ended, until dismissed.
+    pub notice: Option<String>,
+}
+
+pub enum CallAction {
+    Dial {
+        peer: String,
+        video: bool,
+        on_event: Callback<CallEvent>,
+    },
+    Accept {
+        on_event: Callback<CallEvent>,
+    },
+    HangUp,
+    ToggleMute,
+    ToggleCamera,
+    /// Starts recording the call, or stops and saves the recording.
+    ToggleRecording,
+    /// Lets our voice out or holds it back, for push-to-talk.
+    Transmit(bool),
+    Event(CallEvent),
+    /// Nobody picked up in time: our call, or the one ringing here.
+    Timeout,
+    DismissNotice,
+    /// A `calloffer`, `callanswer`, `icecandidate`, `callhangup` or `callrecording` from the
+    /// server.
+    Signal {
+        message: Box<WebSocketMessage>,
+        on_event: Callback<CallEvent>,
+    },
+}
+
+impl CallState {
+    pub fn new(wss: WebsocketService, on_ended: Callback<CallRecord>) -> Self {
+        Self {
+            wss,
+            incoming: None,
+            active: None,
+            transmitting: true,
+            on_ended,
+            notice: None,
+        }
+    }
+
+    pub fn busy(&self) -> bool {
+        self.incoming.is_some() || self.active.is_some()
+    }
+
+    /// Whether we are in a call, rather than ringing or not in one at all.
+    pub fn in_call(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Whether someone is calling us, waiting for us to pick up.
+    pub fn ringing(&self) -> bool {
+        self.incoming.is_some()
+    }
+
+    /// Whether our outgoing call is still waiting to be answered.
+    pub fn dialing(&self) -> bool {
+        self.active
+            .as_ref()
+            .is_some_and(|a| a.status == CallStatus::Dialing)
+    }
+
+    pub fn peer(&self) -> Option<&str> {
+        match (&self.incoming, &self.active) {
+            (Some(incoming), _) => Some(&incoming.peer),
+            (None, Some(active)) => Some(&active.call.peer),
+            (None, None) => None,
+        }
+    }
+
+    fn hang_up_on(&self, peer: String, reason: &str) {
+        self.wss.send(&WebSocketMessage {
+            data: Some(reason.to_string()),
+            to: Some(peer),
+            ..WebSocketMessage::new(MsgTypes::CallHangup)
+        });
+    }
+
+    /// Tells the peer whether we are recording the call now.
+    fn announce_recording(&self, peer: &str, recording: bool) {
+        self.wss.send(&WebSocketMessage {
+            data: recording.then(|| "recording".to_string()),
+            to: Some(peer.to_string()),
+            ..WebSocketMessage::new(MsgTypes::CallRecording)
+        });
+    }
+
+    /// Logs the call in progress as over. Unless we got to talk, it went as `otherwise`; a call we
+    /// picked up that never got through failed.
+    fn log(&self, otherwise: Outcome) {
+        let record = match (&self.incoming, &self.active) {
+            (Some(incoming), _) => CallRecord {
+                peer: incoming.peer.clone(),
+                outgoing: false,
+                video: call::offers_video(&incoming.offer),
+                time: incoming.since,
+                outcome: otherwise,
+                duration: None,
+            },
+            (None, Some(active)) => CallRecord {
+                peer: active.call.peer.clone(),
+                outgoing: active.outgoing,
+                video: active.call.video,
+                time: active.since,
+                outcome: match (active.connected_at, active.outgoing) {
+                    (Some(_), _) => Outcome::Answered,
+                    (None, true) => otherwise,
+                    (None, false) => Outcome::Failed,
+                },
+                duration: active.connected_at.map(|at| time::now() - at),
+            },
+            (None, None) => return,
+        };
+        self.on_ended.emit(record);
+    }
+
+    /// Tells the peer we are gone, for when the whole chat is being torn down.
+    pub fn leave(&self) {
+        if let Some(peer) = self.peer() {
+            self.hang_up_on(peer.to_string(), "ended");
+        }
+    }
+
+    /// Tells the peer we are hanging up (with `reason`) and tears the call down.
+    pub fn end(&mut self, reason: &str) {
+        if let Some(peer) = self.peer() {
+            self.hang_up_on(peer.to_string(), reason);
+        }
+        self.incoming = None;
+        self.active = None;
+    }
+
+    fn on_signal(&mut self, message: WebSocketMessage, on_event: Callback<CallEvent>) {
+        let peer = message.from.unwrap_or_default();
+        match message.message_type {
+            MsgTypes::CallOffer => {
+                let Some(offer) = message.data else {
+                    return;
+                };
+                if self.busy() {
+                    self.on_ended.emit(CallRecord {
+                        peer: peer.clone(),
+                        outgoing: false,
+                        video: call::offers_video(&offer),
+                        time: time::now(),
+                        outcome: Outcome::Missed,
+                        duration: None,
+                    });
+                    self.hang_up_on(peer, "busy");
+                    return;
+                }
+                self.notice = None;
+                self.incoming = Some(IncomingCall {
+                    peer,
+                    offer,
+                    since: time::now(),
+                    candidates: vec![],
+                });
+            }
+            MsgTypes::CallAnswer => {
+                let Some(answer) = message.data else {
+                    return;
+                };
+                if let Some(active) = self.active.as_mut().filter(|a| a.call.peer == peer) {
+                    active.call.accept_answer(&answer, on_event);
+                    active.status = CallStatus::Connecting;
+                }
+            }
+            MsgTypes::IceCandidate => {
+                let Some(candidate) = message
+                    .data
+                    .and_then(|d| serde_json::from_str::<IceCandidate>(&d).ok())
+                else {
+                    return;
+                };
+                if let Some(active) = self.active.as_ref().filter(|a| a.call.peer == peer) {
+                    active.call.add_candidate(candidate);
+                } else if let Some(incoming) = self.incoming.as_mut().filter(|i| i.peer == peer) {
+                    incoming.candidates.push(candidate);
+                }
+            }
+            MsgTypes::CallHangup => {
+                if self.incoming.as_ref().is_some_and(|i| i.peer == peer) {
+                    self.log(Outcome::Missed);
+                    self.incoming = None;
+                    self.notice = Some(format!("Missed call from {}.", peer));
+                } else if self.active.as_ref().is_some_and(|a| a.call.peer == peer) {
+                    self.log(match message.data.as_deref() {
+                        Some("declined") => Outcome::Declined,
+                        _ => Outcome::Unanswered,
+                    });
+                    self.active = None;
+                    self.notice = Some(match message.data.as_deref() {
+                        Some("busy") => format!("{} is on another call.", peer),
+                        Some("declined") => format!("{} declined the call.", peer),
+                        _ => format!("Call with {} ended.", peer),
+                    });
+                }
+            }
+            MsgTypes::CallRecording => {
+                if let Some(active) = self.active.as_mut().filter(|a| a.call.peer == peer) {
+                    active.peer_recording = message.data.is_some();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl CallState {
+    /// The panel for the call in progress, if there is one. A call ringing here rings aloud unless
+    /// `quiet`. Declining it with a message sends it through `on_reply`, with who it is for.
+    pub fn view(
+        &self,
+        calls: &UseReducerDispatcher<CallState>,
+        on_event: Callback<CallEvent>,
+        avatar_of: impl Fn(&str) -> String,
+        quiet: bool,
+        on_reply: Callback<(String, String)>,
+    ) -> Html {
+        let dispatch = |action: fn() -> CallAction| {
+            let calls = calls.clone();
+            Callback::from(move |_| calls.dispatch(action()))
+        };
+        let on_accept = {
+            let calls = calls.clone();
+            Callback::from(move |_| {
+                calls.dispatch(CallAction::Accept {
+                    on_event: on_event.clone(),
+                })
+            })
+        };
+        let on_hang_up = dispatch(|| CallAction::HangUp);
+        let on_toggle_mute = dispatch(|| CallAction::ToggleMute);
+        let on_toggle_camera = dispatch(|| CallAction::ToggleCamera);
+        let on_toggle_recording = dispatch(|| CallAction::ToggleRecording);
+        match (&self.incoming, &self.active) {
+            (Some(incoming), _) => {
+                let on_decline_with = {
+                    let calls = calls.clone();
+                    let peer = incoming.peer.clone();
+                    Callback::from(move |text: String| {
+                        calls.dispatch(CallAction::HangUp);
+                        on_reply.emit((peer.clone(), text));
+                    })
+                };
+                html! {
+                    <CallPanel
+                        peer={incoming.peer.clone()}
+                        avatar={avatar_of(&incoming.peer)}
+                        status={CallStatus::Ringing}
+                        video={call::offers_video(&incoming.offer)}
+                        muted=false
+                        camera_on=false
+                        has_camera=false
+                        {quiet}
+                        {on_accept}
+                        {on_hang_up}
+                        {on_decline_with}
+                        {on_toggle_mute}
+                        {on_toggle_camera}
+                    />
+                }
+            }
+            (None, Some(active)) => html! {
+                <CallPanel
+                    peer={active.call.peer.clone()}
+                    avatar={avatar_of(&active.call.peer)}
+                    status={active.status}
+                    video={active.call.video}
+                    muted={active.call.muted()}
+                    camera_on={active.call.camera_on()}
+                    has_camera={active.call.has_camera()}
+                    local={active.local.clone()}
+                    remote={active.remote.clone()}
+                    recording={active.recorder.is_some()}
+                    peer_recording={active.peer_recording}
+                    {on_accept}
+                    {on_hang_up}
+                    {on_toggle_mute}
+                    {on_toggle_camera}
+                    {on_toggle_recording}
+                />
+            },
+            (None, None) => html! {},
+        }
+    }
+}
+
+impl Reducible for CallState {
+    type Action = CallAction;
+
+    fn reduce(self: Rc<Self>, action: CallAction) -> Rc<Self> {
+        let mut state = (*self).clone();
+        match action {
+            CallAction::Dial {
+                peer,
+                video,
+                on_event,
+            } => {
+                if state.busy() {
+                    return self;
+                }
+                state.notice = None;
+                match Call::dial(peer, video, on_event) {
+                    Ok(call) => {
+                        state.active = Some(ActiveCall::new(
+                            call,
+                            true,
+                            time::now(),
+                            CallStatus::Dialing,
+                        ))
+                    }
+                    Err(e) => state.notice = Some(format!("Could not start the call: {}", e)),
+                }
+            }
+            CallAction::Accept { on_event } => {
+                let Some(incoming) = state.incoming.take() else {
+                    return self;
+                };
+                match Call::answer(
+                    incoming.peer.clone(),
+                    &incoming.offer,
+                    incoming.candidates,
+                    on_event,
+                ) {
+                    Ok(call) => {
+                        state.active = Some(ActiveCall::new(
+                            call,
+                            false,
+                            incoming.since,
+                            CallStatus::Connecting,
+                        ))
+                    }
+                    Err(e) => {
+                        state.hang_up_on(incoming.peer, "failed");
+                        state.notice = Some(format!("Could not answer the call: {}", e));
+                    }
+                }
+            }
+            CallAction::HangUp => {
+                let ringing = state.incoming.is_some();
+                state.log(if ringing {
+                    Outcome::Declined
+                } else {
+                    Outcome::Unanswered
+                });
+                state.end(if ringing { "declined" } else { "ended" });
+            }
+            CallAction::ToggleMute => {
+                if let Some(active) = &state.active {
+                    active.call.set_muted(!active.call.muted());
+                }
+            }
+            CallAction::ToggleCamera => {
+                if let Some(active) = &state.active {
+                    active.call.set_camera(!active.call.camera_on());
+                }
+            }
+            CallAction::ToggleRecording => {
+                let Some(active) = state.active.as_mut() else {
+                    return self;
+                };
+                if active.recorder.take().is_none() {
+                    let streams = active.local.iter().chain(&active.remote);
+                    match Recorder::start(&active.call.peer, streams) {
+                        Ok(recorder) => active.recorder = Some(Rc::new(recorder)),
+                        Err(e) => {
+                            log::warn!("call: can't record: {:?}", e);
+                            state.notice =
+                                Some("Recording isn't supported by this browser.".into());
+                            return Rc::new(state);
+                        }
+                    }
+                }
+                let (peer, recording) = (active.call.peer.clone(), active.recorder.is_some());
+                state.announce_recording(&peer, recording);
+            }
+            CallAction::Transmit(transmitting) => {
+                state.transmitting = transmitting;
+                if let Some(active) = &state.active {
+                    active.call.set_transmitting(transmitting);
+                }
+            }
+            CallAction::Event(CallEvent::Signal(message)) => {
+                state.wss.send(&message);
+                return self;
+            }
+            CallAction::Event(CallEvent::LocalMedia(stream)) => {
+                if let Some(active) = state.active.as_mut() {
+                    if let Some(recorder) = &active.recorder {
+                        recorder.add(&stream);
+                    }
+                    active.local = Some(stream);
+                    active.call.set_transmitting(state.transmitting);
+                }
+            }
+            CallAction::Event(CallEvent::RemoteMedia(stream)) => {
+                if let Some(active) = state.active.as_mut() {
+                    if let Some(recorder) = &active.recorder {
+                        recorder.add(&stream);
+                    }
+                    active.remote = Some(stream);
+                }
+            }
+            CallAction::Event(CallEvent::Connected) => {
+                if let Some(active) = state.active.as_mut() {
+                    active.status = CallStatus::Connected;
+                    active.connected_at.get_or_insert_with(time::now);
+                }
+            }
+            CallAction::Event(CallEvent::Failed(reason)) => {
+                if state.active.is_none() {
+                    return self;
+                }
+                state.log(Outcome::Failed);
+                state.end("failed");
+                state.notice = Some(reason);
+            }
+            CallAction::Timeout => match state.incoming.clone() {
+                Some(incoming) => {
+                    state.log(Outcome::Missed);
+                    state.incoming = None;
+                    state.hang_up_on(incoming.peer.clone(), "missed");
+                    state.notice = Some(format!("Missed call from {}.", incoming.peer));
+                }
+                None if state.dialing() => {
+                    let peer = state.peer().unwrap_or_default().to_string();
+                    state.log(Outcome::Unanswered);
+                    state.end("missed");
+                    state.notice = Some(format!("{} didn't answer.", peer));
+                }
+                None => return self,
+            },
+            CallAction::DismissNotice => state.notice = None,
+            CallAction::Signal { message, on_event } => state.on_signal(*message, on_event),
+        }
+        Rc::new(state)
+    }
+}
+
+/// Our calls, and the log of those that are over.
+pub struct UseCalls {
+    pub state: UseReducerHandle<CallState>,
+    pub log: UseStateHandle<Rc<Vec<CallRecord>>>,
+    /// What the call reports, given to the calls we start or accept.
+    pub on_event: Callback<CallEvent>,
+    /// Calls the peer named, with video or not.
+    pub dial: Callback<(String, bool)>,
+}
+
+/// Calls over `wss` as `username`. Each call is left in the conversation with whoever was on the
+/// other end, to call them back from, and those of earlier visits are put back there. Ringing
+/// stops after a while.
+pub fn use_calls(wss: &WebsocketService, username: &str) -> UseCalls {
+    let store = use_context::<Store>().expect("No store found.");
+    let log = {
+        let username = username.to_string();
+        use_state(move || Rc::new(call_log::load(&username)))
+    };
+    let state = {
+        let wss = wss.clone();
+        let store = store.dispatcher();
+        let username = username.to_string();
+        let log = log.clone();
+        let on_ended = Callback::from(move |record: CallRecord| {
+            store.dispatch(Action::Direct {
+                peer: record.peer.clone(),
+                message: record.as_message(),
+            });
+            call_log::add(&username, record);
+            log.set(Rc::new(call_log::load(&username)));
+        });
+        use_reducer(move || CallState::new(wss, on_ended))
+    };
+    {
+        let store = store.dispatcher();
+        let log = log.clone();
+        use_effect_with_deps(
+            move |_| {
+                for record in log.iter() {
+                    store.dispatch(Action::Direct {
+                        peer: record.peer.clone(),
+                        message: record.as_message(),
+                    });
+                }
+                || ()
+            },
+            (),
+        );
+    }
+    {
+        let dispatcher = state.dispatcher();
+        use_effect_with_deps(
+            move |waiting| {
+                let timeout = waiting.then(|| {
+                    Timeout::new(RING_TIMEOUT_MS, move || {
+                        dispatcher.dispatch(CallAction::Timeout)
+                    })
+                });
+                move || drop(timeout)
+            },
+            state.dialing() || state.ringing(),
+        );
+    }
+
+    let on_event = {
+        let calls = state.dispatcher();
+        Callback::from(move |e| calls.dispatch(CallAction::Event(e)))
+    };
+    let dial = {
+        let calls = state.dispatcher();
+        let on_event = on_event.clone();
+        Callback::from(move |(peer, video)| {
+            calls.dispatch(CallAction::Dial {
+                peer,
+                video,
+                on_event: on_event.clone(),
+            })
+        })
+    };
+    UseCalls {
+        state,
+        log,
+        on_event,
+        dial,
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct CallPanelProps {
+    pub peer: String,
+    pub avatar: String,
+    pub status: CallStatus,
+    pub video: bool,
+    pub muted: bool,
+    pub camera_on: bool,
+    pub has_camera: bool,
+    #[prop_or_default]
+    pub local: Option<MediaStream>,
+    #[prop_or_default]
+    pub remote: Option<MediaStream>,
+    /// Whether we are recording the call.
+    #[prop_or_default]
+    pub recording: bool,
+    /// Whether the peer says they are.
+    #[prop_or_default]
+    pub peer_recording: bool,
+    /// Keeps a ringing call silent, as under Do Not Disturb.
+    #[prop_or_default]
+    pub quiet: bool,
+    pub on_accept: Callback<()>,
+    pub on_hang_up: Callback<()>,
+    /// Declines a ringing call, sending the caller this message.
+    #[prop_or_default]
+    pub on_decline_with: Callback<String>,
+    pub on_toggle_mute: Callback<()>,
+    pub on_toggle_camera: Callback<()>,
+    #[prop_or_default]
+    pub on_toggle_recording: Callback<()>,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct StreamVideoProps {
+    pub stream: Option<MediaStream>,
+    #[prop_or_default]
+    pub muted: bool,
+    #[prop_or_default]
+    pub class: Classes,
+}
+
+/// The speaker chosen in the settings for the [`StreamVideo`]s below to play through, or none for
+/// the browser's default.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Speaker(pub Option<String>);
+
+/// A `<video>` playing a media stream. Streams can only be attached as a DOM property.
+#[function_component(StreamVideo)]
+pub fn stream_video(props: &StreamVideoProps) -> Html {
+    let video = use_node_ref();
+    let speaker = use_context::<Speaker>().and_then(|speaker| speaker.0);
+    {
+        let video = video.clone();
+        use_effect_with_deps(
+            move |(stream, muted, speaker)| {
+                if let Some(element) = video.cast::<HtmlMediaElement>() {
+                    // The `muted` attribute only sets the default; our own voice must never play back.
+                    element.set_muted(*muted);
+                    element.set_src_object(stream.as_ref());
+                    if !*muted {
+                        audio_devices::route(&element, speaker.as_deref());
+                    }
+                }
+                || ()
+            },
+            (props.stream.clone(), props.muted, speaker),
+        );
+    }
+
+    html! {
+        <video ref={video} autoplay=true playsinline=true class={props.class.clone()}></video>
+    }
+}
+
+/// Floating card for the call in progress: who it is with, where it is at, and its controls. While
+/// a call rings here it rings aloud too, and can be declined with one of [`DECLINE_REPLIES`] or a
+/// message of our own. Once either side records, a badge on top says so.
+#[function_component(CallPanel)]
+pub fn call_panel(props: &CallPanelProps) -> Html {
+    let ringing = props.status == CallStatus::Ringing;
+    let replying = use_state_eq(|| false);
+    let reply = use_node_ref();
+    use_effect_with_deps(
+        |ring| {
+            let ringtone = ring.then(Ringtone::start).and_then(|started| {
+                started
+                    .map_err(|e| log::debug!("call: can't ring: {:?}", e))
+                    .ok()
+            });
+            move || drop(ringtone)
+        },
+        ringing && !props.quiet,
+    );
+    // Until the browser hands us the stream, its permission prompt is probably open.
+    let awaiting_devices = !ringing && props.local.is_none();
+    let status = match props.status {
+        _ if awaiting_devices && props.video => {
+            "Allow access to your camera and microphone to continue."
+        }
+        _ if awaiting_devices => "Allow access to your microphone to continue.",
+        CallStatus::Dialing => "Calling...",
+        CallStatus::Ringing if props.video => "Incoming video call",
+        CallStatus::Ringing => "Incoming call",
+        CallStatus::Connecting => "Connecting...",
+        CallStatus::Connected => "Connected",
+    };
+    let show_video = props.video && !ringing;
+    let on_accept = props.on_accept.reform(|_| ());
+    let on_hang_up = props.on_hang_up.reform(|_| ());
+    let on_toggle_mute = props.on_toggle_mute.reform(|_| ());
+    let on_toggle_camera = props.on_toggle_camera.reform(|_| ());
+    let on_toggle_recording = props.on_toggle_recording.reform(|_| ());
+    let recording_badge = match (props.recording, props.peer_recording) {
+        (false, false) => None,
+        (true, false) => Some("You are recording this call".to_string()),
+        (false, true) => Some(format!("{} is recording this call", props.peer)),
+        (true, true) => Some(format!("You and {} are recording this call", props.peer)),
+    };
+    let toggle_replying = {
+        let replying = replying.clone();
+        Callback::from(move |_| replying.set(!*replying))
+    };
+    let on_reply = {
+        let reply = reply.clone();
+        let on_decline_with = props.on_decline_with.clone();
+        Callback::from(move |e: FocusEvent| {
+            e.prevent_default();
+            let Some(input) = reply.cast::<HtmlInputElement>() else {
+                return;
+            };
+            let text = input.value().trim().to_string();
+            if !text.is_empty() {
+                on_decline_with.emit(text);
+            }
+        })
+    };
+
+    html! {
+        <div class={classes!(
+            "absolute", "top-20", "right-4", "z-30", "p-4", "rounded-2xl", "bg-gray-900", "text-white", "shadow-xl",
+            if show_video { "w-[28rem]" } else { "w-72" }
+        )}>
+            {
+                if let Some(badge) = recording_badge {
+                    html! {
+                        <div class="flex items-center gap-2 mb-3 px-3 py-1 rounded-full bg-red-600 text-xs font-semibold">
+                            <span class="w-2 h-2 rounded-full bg-white animate-pulse"></span>
+                            {badge}
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }
+            }
+            {
+                if show_video {
+                    html! {
+                        <div class="relative mb-3 aspect-video rounded-xl overflow-hidden bg-black flex items-center justify-center">
+                            {
+                                if props.remote.is_some() {
+                                    html! { <StreamVideo stream={props.remote.clone()} class="w-full h-full object-cover" /> }
+                                } else {
+                                    html! { <img class="w-20 h-20 rounded-full" src={props.avatar.clone()} alt="avatar"/> }
+                                }
+                            }
+                            {
+                                if props.camera_on {
+                                    html! {
+                                        <StreamVideo
+                                            stream={props.local.clone()}
+                                            muted=true
+                                            class="absolute bottom-2 right-2 w-28 rounded-lg shadow-lg border border-gray-700 -scale-x-100"
+                                        />
+                                    }
+                                } else {
+                                    html! {}
+                                }
+                            }
+                        </div>
+                    }
+                } else {
+                    // Audio only: the element stays hidden but still plays the peer's voice.
+                    html! { <StreamVideo stream={props.remote.clone()} class="hidden" /> }
+                }
+            }
+            <div class="flex items-center gap-3">
+                <img
+                    class={classes!("w-12", "h-12", "rounded-full", ringing.then_some("animate-pulse"))}
+                    src={props.avatar.clone()}
+                    alt="avatar"
+                />
+                <div class="min-w-0">
+                    <div class="font-semibold truncate">{props.peer.clone()}</div>
+                    <div class="text-sm text-gray-300">{status}</div>
+                </div>
+            </div>
+            <div class="flex justify-center gap-4 mt-4">
+                {
+                    if ringing {
+                        html! {
+                            <>
+                                <button
+                                    onclick={on_accept}
+                                    title="Accept"
+                                    class="p-3 rounded-full bg-green-500 hover:bg-green-600 transition-colors"
+                                >
+                                    <svg xmlns="http://www.w3.org/2000/svg" class="h-6 w-6" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                        <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M3 5a2 2 0 012-2h3.28a1 1 0 01.948.684l1.498 4.493a1 1 0 01-.502 1.21l-2.257 1.13a11.042 11.042 0 005.516 5.516l1.13-2.257a1 1 0 011.21-.502l4.493 1.498a1 1 0 01.684.949V19a2 2 0 01-2 2h-1C9.716 21 3 14.284 3 6V5z" />
+                                    </svg>
+                                </button>
+                                <button
+                                    onclick={toggle_replying}
+                                    title="Decline with a message"
+                                    class={classes!(
+                                        "p-3", "rounded-full", "transition-colors",
+                                        if *replying { "bg-white text-gray-900" } else { "bg-gray-700 hover:bg-gray-600" }
+                                    )}
+                                >
+                                    <svg xmlns="http://www.w3.org/2000/svg" class="h-6 w-6" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                        <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M8 10h.01M12 10h.01M16 10h.01M9 16H5a2 2 0 01-2-2V6a2 2 0 012-2h14a2 2 0 012 2v8a2 2 0 01-2 2h-5l-5 5v-5z" />
+                                    </svg>
+                                </button>
+                            </>
+                        }
+                    } else {
+                        html! {
+                            <button
+                                onclick={on_toggle_mute}
+                                title={if props.muted { "Unmute" } else { "Mute" }}
+                                class={classes!(
+                                    "p-3", "rounded-full", "transition-colors",
+                                    if props.muted { "bg-white text-gray-900" } else { "bg-gray-700 hover:bg-gray-600" }
+                                )}
+                            >
+                                <svg xmlns="http://www.w3.org/2000/svg" class="h-6 w-6" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M19 11a7 7 0 01-7 7m0 0a7 7 0 01-7-7m7 7v4m0 0H8m4 0h4m-4-8a3 3 0 01-3-3V5a3 3 0 116 0v6a3 3 0 01-3 3z" />
+                                </svg>
+                            </button>
+                        }
+                    }
+                }
+                {
+                    if show_video {
+                        html! {
+                            <button
+                                onclick={on_toggle_camera}
+                                disabled={!props.has_camera}
+                                title={if !props.has_camera { "No camera available" } else if props.camera_on { "Turn camera off" } else { "Turn camera on" }}
+                                class={classes!(
+                                    "p-3", "rounded-full", "transition-colors", "disabled:opacity-50",
+                                    if props.camera_on { "bg-gray-700 hover:bg-gray-600" } else { "bg-white text-gray-900" }
+                                )}
+                            >
+                                <svg xmlns="http://www.w3.org/2000/svg" class="h-6 w-6" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M15 10l4.553-2.276A1 1 0 0121 8.618v6.764a1 1 0 01-1.447.894L15 14M5 18h8a2 2 0 002-2V8a2 2 0 00-2-2H5a2 2 0 00-2 2v8a2 2 0 002 2z" />
+                                </svg>
+                            </button>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                {
+                    if ringing {
+                        html! {}
+                    } else {
+                        html! {
+                            <button
+                                onclick={on_toggle_recording}
+                                title={if props.recording { "Stop recording and save it" } else { "Record the call" }}
+                                class={classes!(
+                                    "p-3", "rounded-full", "transition-colors",
+                                    if props.recording { "bg-white text-red-600" } else { "bg-gray-700 hover:bg-gray-600" }
+                                )}
+                            >
+                                <svg xmlns="http://www.w3.org/2000/svg" class="h-6 w-6" viewBox="0 0 24 24" fill="currentColor">
+                                    <circle cx="12" cy="12" r="6" />
+                                </svg>
+                            </button>
+                        }
+                    }
+                }
+                <button
+                    onclick={on_hang_up}
+                    title={if ringing { "Decline" } else { "Hang up" }}
+                    class="p-3 rounded-full bg-red-500 hover:bg-red-600 transition-colors"
+                >
+                    <svg xmlns="http://www.w3.org/2000/svg" class="h-6 w-6 rotate-[135deg]" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                        <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M3 5a2 2 0 012-2h3.28a1 1 0 01.948.684l1.498 4.493a1 1 0 01-.502 1.21l-2.257 1.13a11.042 11.042 0 005.516 5.516l1.13-2.257a1 1 0 011.21-.502l4.493 1.498a1 1 0 01.684.949V19a2 2 0 01-2 2h-1C9.716 21 3 14.284 3 6V5z" />
+                    </svg>
+                </button>
+            </div>
+            {
+                if ringing && *replying {
+                    html! {
+                        <div class="mt-4 flex flex-col gap-1 text-sm">
+                            {
+                                for DECLINE_REPLIES.into_iter().map(|text| {
+                                    let onclick = props.on_decline_with.reform(move |_| text.to_string());
+                                    html! {
+                                        <button {onclick} class="text-left px-3 py-1.5 rounded-lg bg-gray-800 hover:bg-gray-700">
+                                            {text}
+                                        </button>
+                                    }
+                                })
+                            }
+                            <form onsubmit={on_reply} class="flex gap-1">
+                                <input
+                                    ref={reply}
+                                    placeholder="Write a message…"
+                                    class="min-w-0 grow px-3 py-1.5 rounded-lg bg-gray-800 placeholder-gray-400 outline-none"
+                                />
+                                <button type="submit" class="px-3 rounded-lg bg-gray-700 hover:bg-gray-600">{"Send"}</button>
+                            </form>
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }
+            }
+        </div>
+    }
+}
@@ -0,0 +1,94 @@
+use std::rc::Rc;
+
+use yew::prelude::*;
+
+use crate::services::call_log::{CallRecord, Outcome};
+use crate::store::Store;
+use crate::time;
+
+#[derive(Properties, PartialEq)]
+pub struct CallHistoryProps {
+    /// Oldest first, as logged.
+    pub calls: Rc<Vec<CallRecord>>,
+    /// Reference point for relative timestamps.
+    pub now: f64,
+    /// Whether a call can be placed now, rather than while in or ringing with another.
+    pub can_call: bool,
+    /// Opens the conversation with whoever is named.
+    pub on_message: Callback<String>,
+    /// Calls whoever is named back, with video if `true`.
+    pub on_call: Callback<(String, bool)>,
+    pub on_close: Callback<()>,
+}
+
+/// The calls made and got in this browser, newest first, each with a way to call back.
+#[function_component(CallHistory)]
+pub fn call_history(props: &CallHistoryProps) -> Html {
+    let store = use_context::<Store>().expect("No store found.");
+    let close = props.on_close.reform(|_| ());
+    let view_call = |call: &CallRecord| {
+        let message = {
+            let peer = call.peer.clone();
+            props.on_message.reform(move |_| peer.clone())
+        };
+        let call_back = {
+            let peer = call.peer.clone();
+            let video = call.video;
+            props.on_call.reform(move |_| (peer.clone(), video))
+        };
+        let missed = call.outcome == Outcome::Missed;
+
+        html! {
+            <div class="flex items-center gap-3 p-2 rounded-lg hover:bg-gray-50 text-sm">
+                <img class="w-10 h-10 rounded-full" src={store.avatar_of(&call.peer)} alt="avatar"/>
+                <div class="min-w-0 grow">
+                    <div class={classes!("font-medium", "truncate", missed.then_some("text-red-600"))}>
+                        {call.peer.clone()}
+                    </div>
+                    <div class="text-xs text-gray-500 truncate">
+                        { if call.outgoing { "↗ " } else { "↙ " } }
+                        {call.describe()}
+                        {" · "}
+                        <span title={time::absolute_label(call.time)}>{time::relative_label(call.time, props.now)}</span>
+                    </div>
+                </div>
+                <button onclick={message} title={format!("Message {}", call.peer)} class="px-2 py-1 rounded-lg hover:bg-gray-100">
+                    {"Message"}
+                </button>
+                <button
+                    onclick={call_back}
+                    disabled={!props.can_call}
+                    title={if props.can_call { format!("Call {} back", call.peer) } else { "Already in a call".to_string() }}
+                    class="px-2 py-1 rounded-lg bg-green-600 text-white hover:bg-green-700 disabled:opacity-50"
+                >
+                    {"Call"}
+                </button>
+            </div>
+        }
+    };
+
+    html! {
+        <div class="fixed inset-0 z-40 flex items-center justify-center bg-black/40" onclick={close.clone()}>
+            <div
+                class="w-[32rem] max-w-full max-h-[80vh] p-6 rounded-2xl bg-white shadow-xl flex flex-col gap-4"
+                onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}
+            >
+                <div class="text-lg font-semibold">{"Calls"}</div>
+                <div class="grow overflow-y-auto flex flex-col gap-1">
+                    {
+                        if props.calls.is_empty() {
+                            html! {
+                                <div class="text-sm text-gray-500">
+                                    {"No calls yet. Calls made and received in this browser are listed here."}
+                                </div>
+                            }
+                        } else {
+                            props.calls.iter().rev().map(view_call).collect::<Html>()
+                        }
+                    }
+                </div>
+                <button onclick={close} class="text-sm text-gray-500 hover:text-gray-800">{"Close"}</button>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::BTreeSet;
+
+use web_sys::MediaStream;
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::components::call::StreamVideo;
+
+/// How a group call is laid out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    /// Everyone the same size.
+    Grid,
+    /// Whoever is speaking large, the others in a strip below.
+    Speaker,
+}
+
+/// Someone in a group call, as shown in it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tile {
+    pub name: String,
+    pub avatar: String,
+    /// What they send, when it has their camera; otherwise their picture stands in.
+    pub video: Option<MediaStream>,
+    /// Our own tile, mirrored as people expect to see themselves.
+    pub own: bool,
+}
+
+/// Columns for a grid of `count` tiles, keeping it as square as can be.
+fn columns(count: usize) -> usize {
+    (1..)
+        .find(|columns| columns * columns >= count)
+        .unwrap_or(1)
+}
+
+/// Who the speaker view shows, with `shown` the one it showed last: them while they still speak,
+/// or else someone else who does. In a silence it stays on whoever it was on. We are only shown
+/// when alone.
+fn featured(tiles: &[Tile], speaking: &BTreeSet<String>, shown: Option<&str>) -> Option<String> {
+    let others = || tiles.iter().filter(|tile| !tile.own).map(|tile| &tile.name);
+    let present = |name: &&str| others().any(|other| other == name);
+    let shown = shown.filter(present);
+    shown
+        .filter(|name| speaking.contains(*name))
+        .map(str::to_string)
+        .or_else(|| others().find(|name| speaking.contains(*name)).cloned())
+        .or_else(|| shown.map(str::to_string))
+        .or_else(|| others().next().cloned())
+        .or_else(|| tiles.first().map(|tile| tile.name.clone()))
+}
+
+fn view_tile(tile: &Tile, speaks: bool, class: Classes) -> Html {
+    html! {
+        <div
+            title={if speaks { format!("{} is speaking", tile.name) } else { tile.name.clone() }}
+            class={classes!(
+                "relative", "aspect-video", "rounded-xl", "overflow-hidden", "bg-gray-800",
+                "flex", "items-center", "justify-center", "ring-4", "ring-inset", "transition-shadow",
+                if speaks { "ring-green-500" } else { "ring-transparent" },
+                class
+            )}
+        >
+            {
+                match &tile.video {
+                    // The voice channel already plays what they say.
+                    Some(stream) => html! {
+                        <StreamVideo
+                            stream={Some(stream.clone())}
+                            muted=true
+                            class={classes!("w-full", "h-full", "object-cover", tile.own.then_some("-scale-x-100"))}
+                        />
+                    },
+                    None => html! { <img class="w-1/4 max-w-[5rem] rounded-full" src={tile.avatar.clone()} alt="avatar"/> },
+                }
+            }
+            <span class="absolute bottom-1 left-2 px-1.5 rounded bg-black/50 text-xs truncate max-w-[90%]">
+                { if tile.own { format!("{} (you)", tile.name) } else { tile.name.clone() } }
+            </span>
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct CallLayoutProps {
+    pub tiles: Vec<Tile>,
+    /// Those of the tiles speaking now.
+    pub speaking: BTreeSet<String>,
+}
+
+/// Floating panel for a group call with cameras on: everyone in an adaptive grid, or whoever
+/// speaks large in the speaker view. A green ring shows who is speaking in either.
+#[function_component(CallLayout)]
+pub fn call_layout(props: &CallLayoutProps) -> Html {
+    let layout = use_state_eq(|| Layout::Grid);
+    // Who the speaker view showed last, so it stays on them through pauses.
+    let shown = use_mut_ref(|| Option::<String>::None);
+    let featured = featured(&props.tiles, &props.speaking, shown.borrow().as_deref());
+    *shown.borrow_mut() = featured.clone();
+
+    let toggle_layout = {
+        let layout = layout.clone();
+        Callback::from(move |_| {
+            layout.set(match *layout {
+                Layout::Grid => Layout::Speaker,
+                Layout::Speaker => Layout::Grid,
+            })
+        })
+    };
+    let speaks = |tile: &Tile| props.speaking.contains(&tile.name);
+
+    html! {
+        <div class="fixed top-20 left-1/2 -translate-x-1/2 z-30 w-[40rem] max-w-[calc(100vw-2rem)] p-3 rounded-2xl bg-gray-900 text-white shadow-xl">
+            <div class="flex items-center mb-2 text-sm">
+                <span class="font-semibold">{format!("Voice — {}", props.tiles.len())}</span>
+                <button
+                    onclick={toggle_layout}
+                    class="ml-auto px-2 py-0.5 rounded-full bg-gray-700 hover:bg-gray-600"
+                >
+                    { if *layout == Layout::Grid { "Speaker view" } else { "Grid view" } }
+                </button>
+            </div>
+            {
+                match *layout {
+                    Layout::Grid => html! {
+                        <div
+                            class="grid gap-2"
+                            style={format!("grid-template-columns: repeat({}, minmax(0, 1fr))", columns(props.tiles.len()))}
+                        >
+                            { for props.tiles.iter().map(|tile| view_tile(tile, speaks(tile), classes!())) }
+                        </div>
+                    },
+                    Layout::Speaker => html! {
+                        <>
+                            {
+                                for props.tiles.iter()
+                                    .filter(|tile| Some(&tile.name) == featured.as_ref())
+                                    .map(|tile| view_tile(tile, speaks(tile), classes!()))
+                            }
+                            <div class="mt-2 flex gap-2 overflow-x-auto">
+                                {
+                                    for props.tiles.iter()
+                                        .filter(|tile| Some(&tile.name) != featured.as_ref())
+                                        .map(|tile| view_tile(tile, speaks(tile), classes!("w-32", "shrink-0")))
+                                }
+                            </div>
+                        </>
+                    },
+                }
+            }
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    fn tile(name: &str, own: bool) -> Tile {
+        Tile {
+            name: name.into(),
+            avatar: String::new(),
+            video: None,
+            own,
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn the_speaker_view_follows_whoever_speaks() {
+        assert_eq!(
+            (1..=10).map(columns).collect::<Vec<_>>(),
+            vec![1, 2, 2, 2, 3, 3, 3, 3, 3, 4]
+        );
+
+        let tiles = vec![
+            tile("alice", true),
+            tile("bob", false),
+            tile("carol", false),
+        ];
+        let speaking = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+        let shown = featured(&tiles, &speaking(&[]), None);
+        assert_eq!(shown.as_deref(), Some("bob"));
+        let shown = featured(&tiles, &speaking(&["carol"]), shown.as_deref());
+        assert_eq!(shown.as_deref(), Some("carol"));
+        // Carol keeps it while both speak, and through the silence after.
+        let shown = featured(&tiles, &speaking(&["bob", "carol"]), shown.as_deref());
+        assert_eq!(shown.as_deref(), Some("carol"));
+        let shown = featured(&tiles, &speaking(&[]), shown.as_deref());
+        assert_eq!(shown.as_deref(), Some("carol"));
+        // We never take it from the others, however loud.
+        let shown = featured(&tiles, &speaking(&["alice"]), shown.as_deref());
+        assert_eq!(shown.as_deref(), Some("carol"));
+        assert_eq!(
+            featured(&[tile("alice", true)], &speaking(&[]), None).as_deref(),
+            Some("alice")
+        );
+    }
+}
@@ -1,304 +1,2103 @@
-use serde::{Deserialize, Serialize};
-use web_sys::HtmlInputElement;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gloo_timers::callback::{Interval, Timeout};
+use wasm_bindgen_futures::spawn_local;
+use yew::functional::*;
 use yew::prelude::*;
-use yew_agent::{Bridge, Bridged};
+use yew_agent::use_bridge;
+use yew_router::prelude::*;
 
+use crate::avatar::{self, AvatarSettings};
+use crate::components::activity_log::use_activity_log;
+use crate::components::attachments::use_attachments;
+use crate::components::away_settings::use_away;
+use crate::components::call::{use_calls, CallAction, CallState, Speaker, UseCalls};
+use crate::components::chat_header::ChatHeader;
+use crate::components::chat_history::ChatHistory;
+use crate::components::composer::Composer;
+use crate::components::frame_log::{use_frame_log_shortcut, FrameLog};
+use crate::components::live_share::use_live_share;
+use crate::components::message_list;
+use crate::components::print_view::PrintView;
+use crate::components::push_to_talk_settings::use_push_to_talk;
+use crate::components::room_icon::RoomIcon;
+use crate::components::room_password_dialog::RoomPasswordDialog;
+use crate::components::settings_panel::{use_preferences, SettingsPanel};
+use crate::components::sidebar::Sidebar;
+use crate::components::toast::{Toast, ToastAction, Toasts};
+use crate::components::voice::{use_voice, UseVoice, VoiceAction, VoiceStatus};
+use crate::protocol::{
+    Announcement, DirectMessage, JoinRefused, Kicked, MessageData, MsgTypes, Profile, Refusal,
+    Role, RoleChange, RoomDeleted, Sealed, UserPayload, WebSocketMessage,
+};
+use crate::sanitize;
+use crate::services::activity;
+use crate::services::api::ApiClient;
+use crate::services::auth::AuthService;
+use crate::services::call::CallEvent;
+use crate::services::contacts;
+use crate::services::crypto::{self, KeyPair, Trust};
+use crate::services::dnd;
 use crate::services::event_bus::EventBus;
-use crate::{services::websocket::WebsocketService, User};
+use crate::services::frame_log::{self, DecodeError, Direction};
+use crate::services::idle;
+use crate::services::irc;
+use crate::services::matrix;
+use crate::services::notes;
+use crate::services::push_to_talk;
+use crate::services::terms;
+use crate::services::websocket::{WebsocketService, WS_ENDPOINT};
+use crate::store::{Action, ChatState, Connection, Store};
+use crate::time;
+use crate::{LoginQuery, Route, User, DEFAULT_ROOM};
 
-pub enum Msg {
-    HandleMsg(String),
-    SubmitMessage,
+#[derive(Properties, PartialEq)]
+pub struct ChatProps {
+    pub room: String,
+    /// The code the link to the room had, which lets us into it when it is invite-only.
+    #[prop_or_default]
+    pub invite: Option<String>,
 }
 
-#[derive(Deserialize)]
-struct MessageData {
-    from: String,
-    message: String,
-}
+/// How often relative timestamps ("5m", "1h") are refreshed.
+const CLOCK_INTERVAL_MS: u32 = 30_000;
+
+/// How long an arriving message waits for others to be shown with, about one frame.
+const BATCH_MS: u32 = 16;
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "lowercase")]
-pub enum MsgTypes {
-    Users,
-    Register,
-    Message,
+/// How often the room directory is asked for again, for the unread counts of other rooms.
+const DIRECTORY_REFRESH_MS: u32 = 30_000;
+
+/// Messages received but not yet in the store. A burst, such as the history replayed on joining a
+/// room, is shown with one render instead of one per message.
+type MessageBatch = Rc<RefCell<Vec<MessageData>>>;
+
+fn queue_message(
+    batch: &MessageBatch,
+    store: &UseReducerDispatcher<ChatState>,
+    message: MessageData,
+) {
+    let first = {
+        let mut queued = batch.borrow_mut();
+        queued.push(message);
+        queued.len() == 1
+    };
+    if first {
+        let batch = batch.clone();
+        let store = store.clone();
+        Timeout::new(BATCH_MS, move || flush_messages(&batch, &store)).forget();
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct WebSocketMessage {
-    message_type: MsgTypes,
-    data_array: Option<Vec<String>>,
-    data: Option<String>,
+/// Hands the queued messages to the store, as must happen before anything else reaches it so
+/// that they keep their place.
+fn flush_messages(batch: &MessageBatch, store: &UseReducerDispatcher<ChatState>) {
+    let messages: Vec<MessageData> = batch.borrow_mut().drain(..).collect();
+    if !messages.is_empty() {
+        store.dispatch(Action::Messages(messages));
+    }
 }
 
-#[derive(Clone)]
-struct UserProfile {
-    name: String,
-    avatar: String,
+/// Fetches the pictures of a new user list, so redrawing it doesn't load them again.
+fn prefetch_avatars(store: UseReducerDispatcher<ChatState>, users: &[UserPayload]) {
+    let urls: Vec<String> = users
+        .iter()
+        .map(|u| avatar::url_for(&u.name, u.avatar.as_deref(), u.gravatar.as_deref()))
+        .collect();
+    spawn_local(async move {
+        if avatar::prefetch(urls).await {
+            store.dispatch(Action::AvatarsLoaded);
+        }
+    });
 }
 
-pub struct Chat {
-    users: Vec<UserProfile>,
-    chat_input: NodeRef,
-    _producer: Box<dyn Bridge<EventBus>>,
+/// Encrypts `text` for `peer`, whose key is `peer_key`, then sends it once that is done.
+fn send_direct(
     wss: WebsocketService,
-    messages: Vec<MessageData>,
+    keys: Rc<KeyPair>,
+    peer_key: String,
+    peer: String,
+    text: String,
+) {
+    spawn_local(async move {
+        match keys.seal(&peer_key, &text).await {
+            Ok(sealed) => wss.send(&WebSocketMessage {
+                data: Some(serde_json::to_string(&sealed).unwrap()),
+                to: Some(peer),
+                ..WebSocketMessage::new(MsgTypes::Direct)
+            }),
+            Err(e) => log::error!("crypto: failed to encrypt: {}", e),
+        }
+    });
 }
-impl Component for Chat {
-    type Message = Msg;
-    type Properties = ();
-
-    fn create(ctx: &Context<Self>) -> Self {
-        let (user, _) = ctx
-            .link()
-            .context::<User>(Callback::noop())
-            .expect("context to be set");
-        let wss = WebsocketService::new();
-        let username = user.username.borrow().clone();
-
-        let message = WebSocketMessage {
-            message_type: MsgTypes::Register,
-            data: Some(username.to_string()),
-            data_array: None,
+
+/// Decrypts a `direct` relayed by the server and files it under the conversation it belongs to.
+fn receive_direct(
+    store: UseReducerDispatcher<ChatState>,
+    keys: Rc<KeyPair>,
+    current_username: &str,
+    direct: DirectMessage,
+) {
+    // Both ends derive the same secret, so our own copy opens with their key.
+    let (peer, peer_key) = if direct.from == current_username {
+        (direct.to.clone(), direct.to_key.clone())
+    } else {
+        (direct.from.clone(), direct.from_key.clone())
+    };
+    store.dispatch(Action::PeerKey {
+        peer: peer.clone(),
+        key: peer_key.clone(),
+    });
+    spawn_local(async move {
+        let opened = match serde_json::from_str::<Sealed>(&direct.payload) {
+            Ok(sealed) => keys.open(&peer_key, &sealed).await,
+            Err(e) => Err(e.to_string()),
         };
+        let message = opened.unwrap_or_else(|e| {
+            log::error!("crypto: failed to decrypt: {}", e);
+            "Could not decrypt this message.".into()
+        });
+        store.dispatch(Action::Direct {
+            peer,
+            message: MessageData {
+                id: None,
+                from: direct.from,
+                message,
+                attachment: None,
+                time: direct.time,
+                bot: None,
+                to: None,
+                announcement: false,
+                pinned_until: None,
+                system: false,
+            },
+        });
+    });
+}
+
+/// Another connection under our name. The server only lets one of them chat.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Clash {
+    /// It was there first, so we weren't let in.
+    Conflict,
+    /// It took over from us.
+    Replaced,
+}
+
+/// Asks for what the room we are in has besides messages: its relay status, custom emoji,
+/// settings and the like.
+fn ask_about_room(wss: &WebsocketService) {
+    wss.send(&WebSocketMessage::new(MsgTypes::Relay));
+    wss.send(&WebSocketMessage::new(MsgTypes::Emoji));
+    wss.send(&WebSocketMessage::new(MsgTypes::SlowMode));
+    wss.send(&WebSocketMessage::new(MsgTypes::Reports));
+    wss.send(&WebSocketMessage::new(MsgTypes::Blacklist));
+    wss.send(&WebSocketMessage::new(MsgTypes::Restrictions));
+    wss.send(&WebSocketMessage::new(MsgTypes::Pins));
+    wss.send(&WebSocketMessage::new(MsgTypes::RoomUpdate));
+    wss.send(&WebSocketMessage::new(MsgTypes::RoomRoles));
+    wss.send(&WebSocketMessage::new(MsgTypes::ListRooms));
+}
+
+/// Enters `room` under our name, after saying which terms of service we accepted, announcing our
+/// key, Gravatar and whether we are away, and asking [about the room](ask_about_room) and whether
+/// we may see the admin view.
+/// With `take_over`, whoever is connected under the name already is disconnected instead of us.
+fn register(
+    wss: &WebsocketService,
+    username: &str,
+    room: &str,
+    invite: Option<&str>,
+    keys: Option<&KeyPair>,
+    take_over: bool,
+) {
+    if let Some(version) = terms::accepted(username) {
+        wss.send(&WebSocketMessage {
+            data: Some(version),
+            ..WebSocketMessage::new(MsgTypes::Terms)
+        });
+    }
+    let message_type = if take_over {
+        MsgTypes::Takeover
+    } else {
+        MsgTypes::Register
+    };
+    wss.send(&WebSocketMessage {
+        data: Some(username.to_string()),
+        room: Some(room.to_string()),
+        public_key: keys.map(|k| k.public_key().to_string()),
+        invite: invite.map(str::to_string),
+        ..WebSocketMessage::new(message_type)
+    });
+    ask_about_room(wss);
+    wss.send(&WebSocketMessage::new(MsgTypes::Admin));
+    let gravatar = AvatarSettings::load(username).gravatar();
+    if gravatar.is_some() {
+        wss.send(&WebSocketMessage {
+            data: gravatar,
+            ..WebSocketMessage::new(MsgTypes::Gravatar)
+        });
+    }
+    if idle::is_away() {
+        idle::announce(wss, true);
+    }
+}
+
+/// What a frame from the server asks of the chat.
+enum Incoming {
+    Store(Action),
+    /// Still sealed; it is opened with our key before it reaches the store.
+    Direct(DirectMessage),
+    Call(Box<WebSocketMessage>),
+    Voice(Box<WebSocketMessage>),
+    Clash(Clash),
+    SessionExpired,
+    /// We weren't registered, as the server has terms we haven't accepted.
+    TermsRequired,
+    RoomDeleted(RoomDeleted),
+    Kicked(Kicked),
+    JoinRefused(JoinRefused),
+    Ignored,
+}
+
+/// Decodes a frame from the server. Frames that aren't a [`WebSocketMessage`] at all are errors,
+/// while a well-formed frame with an unreadable payload is ignored.
+fn handle_msg(s: &str) -> Result<Incoming, serde_json::Error> {
+    let msg: WebSocketMessage = serde_json::from_str(s)?;
+    Ok(match msg.message_type {
+        MsgTypes::Users => {
+            let users: Vec<UserPayload> = msg
+                .data
+                .and_then(|d| serde_json::from_str(&d).ok())
+                .unwrap_or_else(|| {
+                    msg.data_array
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|name| UserPayload {
+                            name,
+                            avatar: None,
+                            key: None,
+                            gravatar: None,
+                            away: false,
+                            voice: false,
+                            role: Role::default(),
+                            room_role: None,
+                        })
+                        .collect()
+                });
+            Incoming::Store(Action::Users(users))
+        }
+        MsgTypes::Message => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, |m| Incoming::Store(Action::Message(m))),
+        // Whispers show in the room, marked as such.
+        MsgTypes::Whisper => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, |m| Incoming::Store(Action::Message(m))),
+        MsgTypes::Direct => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, Incoming::Direct),
+        MsgTypes::CallOffer
+        | MsgTypes::CallAnswer
+        | MsgTypes::IceCandidate
+        | MsgTypes::CallHangup
+        | MsgTypes::CallRecording => Incoming::Call(Box::new(msg)),
+        MsgTypes::VoiceOffer
+        | MsgTypes::VoiceAnswer
+        | MsgTypes::VoiceCandidate
+        | MsgTypes::VoiceRecording => Incoming::Voice(Box::new(msg)),
+        MsgTypes::Location => match msg.from {
+            Some(from) => Incoming::Store(Action::LiveLocation {
+                from,
+                position: msg.data.and_then(|d| serde_json::from_str(&d).ok()),
+            }),
+            None => Incoming::Ignored,
+        },
+        MsgTypes::Relay => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, |s| Incoming::Store(Action::Relay(s))),
+        MsgTypes::Emoji => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, |e| Incoming::Store(Action::Emoji(e))),
+        // Only administrators are told anything.
+        MsgTypes::Admin if msg.data.is_some() => Incoming::Store(Action::Admin),
+        MsgTypes::Welcome => msg
+            .data
+            .map_or(Incoming::Ignored, |w| Incoming::Store(Action::Welcome(w))),
+        MsgTypes::SlowMode => Incoming::Store(Action::SlowMode(
+            msg.data.and_then(|d| d.parse().ok()).filter(|&s| s > 0),
+        )),
+        // Only moderators are told anything.
+        MsgTypes::Reports => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, |r| Incoming::Store(Action::Reports(r))),
+        MsgTypes::Deleted => msg
+            .data
+            .map_or(Incoming::Ignored, |id| Incoming::Store(Action::Deleted(id))),
+        MsgTypes::Banned => msg.data.map_or(Incoming::Ignored, |name| {
+            Incoming::Store(Action::Banned(name))
+        }),
+        MsgTypes::Blacklist => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, |w| Incoming::Store(Action::Blacklist(w))),
+        MsgTypes::Pins => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, |p| Incoming::Store(Action::Pins(p))),
+        MsgTypes::Restrictions => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, |r| {
+                Incoming::Store(Action::Restrictions(r))
+            }),
+        MsgTypes::RoomUpdate => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, |u| {
+                Incoming::Store(Action::RoomUpdate(u))
+            }),
+        MsgTypes::Warning => msg
+            .data
+            .map_or(Incoming::Ignored, |w| Incoming::Store(Action::Warning(w))),
+        // The server no longer recognises our token; make the user log in again.
+        MsgTypes::AuthError => Incoming::SessionExpired,
+        MsgTypes::Terms => Incoming::TermsRequired,
+        MsgTypes::RoleChange => msg
+            .data
+            .and_then(|d| serde_json::from_str::<RoleChange>(&d).ok())
+            .map_or(Incoming::Ignored, |c| {
+                Incoming::Store(Action::RoleChange(c))
+            }),
+        MsgTypes::RoomDeleted => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, Incoming::RoomDeleted),
+        MsgTypes::Kicked => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, Incoming::Kicked),
+        MsgTypes::RoomRoles => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, |r| Incoming::Store(Action::RoomRoles(r))),
+        MsgTypes::JoinRefused => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, Incoming::JoinRefused),
+        MsgTypes::Invite => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, |i| Incoming::Store(Action::Invite(i))),
+        MsgTypes::ListRooms => msg
+            .data
+            .and_then(|d| serde_json::from_str(&d).ok())
+            .map_or(Incoming::Ignored, |r| Incoming::Store(Action::Rooms(r))),
+        MsgTypes::Conflict => Incoming::Clash(Clash::Conflict),
+        MsgTypes::Replaced => Incoming::Clash(Clash::Replaced),
+        _ => Incoming::Ignored,
+    })
+}
+
+/// What to tell someone a room didn't let in.
+fn refusal_reason(refused: &JoinRefused) -> String {
+    match refused.reason {
+        Refusal::Required => "The room is invite-only. Ask its owner for an invite link.".into(),
+        Refusal::Invalid => {
+            "The invite link isn't one of the room's. Check it was copied whole.".into()
+        }
+        Refusal::Expired => {
+            "The invite link has expired. Ask the room's owner for a new one.".into()
+        }
+        Refusal::Revoked => {
+            "The invite link was replaced by a newer one. Ask the room's owner for it.".into()
+        }
+        Refusal::Password => "The room has a password.".into(),
+        Refusal::WrongPassword => "That isn't the room's password.".into(),
+        Refusal::TooManyAttempts => match refused.retry_in {
+            Some(seconds) => format!(
+                "Too many wrong passwords. Try again in {} minute{}.",
+                seconds.div_ceil(60),
+                if seconds > 60 { "s" } else { "" }
+            ),
+            None => "Too many wrong passwords. Try again later.".into(),
+        },
+    }
+}
+
+/// The announcement pinned above the room. Clicking it scrolls to where it was posted.
+fn view_pinned(m: &MessageData) -> Html {
+    let onclick =
+        m.id.clone()
+            .map(|id| Callback::from(move |_| message_list::link_to(&id)));
+    let until = m.pinned_until.map(time::clock_label).unwrap_or_default();
+    html! {
+        <div
+            {onclick}
+            title={format!("Pinned until {}", until)}
+            class="w-full px-4 py-2 flex items-center gap-3 bg-amber-100 border-b border-amber-300 text-sm text-amber-900 cursor-pointer"
+        >
+            <span class="font-semibold">{format!("📌 {}", m.from)}</span>
+            <span class="grow truncate">{m.message.clone()}</span>
+        </div>
+    }
+}
+
+/// How long to pin it for and the text of an `/announce [30m|2h] text` message, if it is one.
+fn announce_command(text: &str) -> Option<(Option<u32>, &str)> {
+    let rest = text.trim().strip_prefix("/announce")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let (first, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let pin = first
+        .strip_suffix('m')
+        .and_then(|m| m.parse().ok())
+        .or_else(|| {
+            first
+                .strip_suffix('h')
+                .and_then(|h| h.parse::<u32>().ok())
+                .and_then(|h| h.checked_mul(60))
+        });
+    let text = if pin.is_some() { after } else { rest };
+    Some((pin, text.trim())).filter(|(_, text)| !text.is_empty())
+}
+
+/// The seconds of a `/slow 30` or `/slow off` message, if it is one, 0 being off.
+fn slow_command(text: &str) -> Option<u32> {
+    let rest = text.trim().strip_prefix("/slow")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    match rest.trim() {
+        "off" => Some(0),
+        seconds => seconds.strip_suffix('s').unwrap_or(seconds).parse().ok(),
+    }
+}
 
-        if let Ok(_) = wss
-            .tx
-            .clone()
-            .try_send(serde_json::to_string(&message).unwrap())
-        {
-            log::debug!("message sent successfully");
-        }
-
-        Self {
-            users: vec![],
-            messages: vec![],
-            chat_input: NodeRef::default(),
-            wss,
-            _producer: EventBus::bridge(ctx.link().callback(Msg::HandleMsg)),
-        }
-    }
-
-    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
-        match msg {
-            Msg::HandleMsg(s) => {
-                let msg: WebSocketMessage = serde_json::from_str(&s).unwrap();
-                match msg.message_type {
-                    MsgTypes::Users => {
-                        let users_from_message = msg.data_array.unwrap_or_default();
-                        self.users = users_from_message
-                            .iter()
-                            .map(|u| UserProfile {
-                                name: u.into(),
-                                avatar: format!(
-                                    "https://avatars.dicebear.com/api/adventurer-neutral/{}.svg",
-                                    u
-                                )
-                                .into(),
-                            })
-                            .collect();
-                        return true;
+/// The recipient and text of a `/whisper name text` message, if it is one.
+fn whisper_command(text: &str) -> Option<(&str, &str)> {
+    let rest = text.trim().strip_prefix("/whisper")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let (to, text) = rest.trim_start().split_once(char::is_whitespace)?;
+    Some((to.trim_start_matches('@'), text.trim())).filter(|(_, text)| !text.is_empty())
+}
+
+/// Sends what was typed to the open conversation as `current_username`: sealed with `keys` in a
+/// direct one, or else to the room, where `/announce`, `/slow` and `/whisper` are commands. What
+/// can't be sent is told with a toast.
+fn on_submit(
+    wss: &WebsocketService,
+    keys: Option<Rc<KeyPair>>,
+    store: &Store,
+    current_username: &str,
+    toasts: UseReducerDispatcher<Toasts>,
+) -> Callback<String> {
+    let wss = wss.clone();
+    let store = store.clone();
+    let current_username = current_username.to_string();
+    let conversation = store
+        .conversation
+        .clone()
+        .map(|peer| (store.peer_key(&peer).map(str::to_string), peer));
+    // Who can be whispered to: anyone else in the room.
+    let present: Vec<String> = store
+        .users
+        .iter()
+        .map(|u| u.name.clone())
+        .filter(|name| *name != current_username)
+        .collect();
+    let can_announce =
+        store.admin || store.room_owner.as_deref() == Some(current_username.as_str());
+    Callback::from(move |text: String| match &conversation {
+        // Notes to self stay here.
+        Some((_, peer)) if *peer == current_username => store.dispatch(Action::Direct {
+            peer: peer.clone(),
+            message: notes::add(peer, text),
+        }),
+        Some((peer_key, peer)) => {
+            activity::message_sent();
+            // Encrypt for the peer, then send once that is done.
+            let (Some(keys), Some(peer_key)) = (keys.clone(), peer_key.clone()) else {
+                return;
+            };
+            send_direct(wss.clone(), keys, peer_key, peer.clone(), text);
+        }
+        None => {
+            if let Some((pin_minutes, announced)) = announce_command(&text) {
+                if !can_announce {
+                    toasts.dispatch(ToastAction::Show {
+                        title: "Announcement not sent".into(),
+                        detail: "Only the room's owner or an administrator can announce.".into(),
+                    });
+                    return;
+                }
+                activity::message_sent();
+                let announcement = Announcement {
+                    text: announced.to_string(),
+                    pin_minutes,
+                };
+                wss.send(&WebSocketMessage {
+                    data: Some(serde_json::to_string(&announcement).unwrap()),
+                    ..WebSocketMessage::new(MsgTypes::Announce)
+                });
+                return;
+            }
+            if let Some(seconds) = slow_command(&text) {
+                if !can_announce {
+                    toasts.dispatch(ToastAction::Show {
+                        title: "Slow mode not changed".into(),
+                        detail: "Only the room's owner or an administrator can change it.".into(),
+                    });
+                    return;
+                }
+                wss.send(&WebSocketMessage {
+                    data: Some(seconds.to_string()),
+                    ..WebSocketMessage::new(MsgTypes::SlowMode)
+                });
+                return;
+            }
+            match whisper_command(&text) {
+                Some((to, _)) if !present.iter().any(|name| name == to) => {
+                    toasts.dispatch(ToastAction::Show {
+                        title: "Whisper not sent".into(),
+                        detail: format!("{} isn't in this room.", to),
+                    })
+                }
+                Some((to, whispered)) => {
+                    activity::message_sent();
+                    wss.send(&WebSocketMessage {
+                        data: Some(whispered.to_string()),
+                        to: Some(to.to_string()),
+                        ..WebSocketMessage::new(MsgTypes::Whisper)
+                    })
+                }
+                None => {
+                    activity::message_sent();
+                    wss.send(&WebSocketMessage {
+                        data: Some(text),
+                        ..WebSocketMessage::new(MsgTypes::Message)
+                    })
+                }
+            }
+        }
+    })
+}
+
+#[function_component(Chat)]
+pub fn chat(props: &ChatProps) -> Html {
+    let user = use_context::<User>().expect("No context found.");
+    let store = use_context::<Store>().expect("No store found.");
+    let history = use_history().expect("history to be available");
+    let current_username = user.username.borrow().clone();
+
+    let wss = {
+        let token = user
+            .tokens
+            .borrow()
+            .as_ref()
+            .map(|t| t.token.clone())
+            .unwrap_or_default();
+        let matrix = user.matrix.borrow().clone();
+        let irc = user.irc.borrow().clone();
+        let store = store.dispatcher();
+        use_state(move || match (matrix, irc) {
+            (Some(session), _) => matrix::connect(session),
+            (_, Some(session)) => irc::connect(session),
+            _ => WebsocketService::with_token(
+                &token,
+                Callback::from(move |status| store.dispatch(Action::Connection(status))),
+            ),
+        })
+    };
+    // Our direct message key, once loaded. Until then we haven't registered.
+    let keys = use_state(|| Option::<Rc<KeyPair>>::None);
+    let now = use_state(time::now);
+    let UseCalls {
+        state: calls,
+        log: call_log,
+        on_event: on_call_event,
+        dial: call_back,
+    } = use_calls(&wss, &current_username);
+    let UseVoice {
+        state: voice,
+        on_event: on_voice_event,
+    } = use_voice(&wss, &current_username);
+    let attachments = use_attachments(&wss, store.conversation.is_some(), store.restrictions);
+    let show_fingerprints = use_fingerprints(store.conversation.clone());
+    let print_view = use_state(|| false);
+    let show_avatar_settings = use_state(|| false);
+    let preferences = use_preferences(&current_username);
+    let talk_key = (*preferences.talk_key).clone();
+    let talking = {
+        let calls = calls.dispatcher();
+        let voice = voice.dispatcher();
+        use_push_to_talk(
+            talk_key.clone(),
+            Callback::from(move |transmit| {
+                calls.dispatch(CallAction::Transmit(transmit));
+                voice.dispatch(VoiceAction::Transmit(transmit));
+            }),
+        )
+    };
+    let clash = use_state(|| Option::<Clash>::None);
+    // The room that asked for its password, with what was wrong with the last one we gave.
+    let password_prompt = use_state(|| Option::<(String, Option<String>)>::None);
+    // Given with the next `join`, once typed in.
+    let pending_password = use_mut_ref(|| Option::<String>::None);
+    let toasts = use_reducer(Toasts::default);
+    let live_share = use_live_share(&wss, &current_username, toasts.dispatcher());
+    let show_frames = use_frame_log_shortcut();
+    let peer_profile = use_peer_profile(&user, store.conversation.clone());
+    // Mirroring is a feature of our server; Matrix and IRC have bridges of their own.
+    let can_relay = user.tokens.borrow().is_some();
+    // Others learn of our Gravatar through our server too.
+    let can_set_avatar = can_relay;
+    // And voice channels are signaled through it.
+    let has_voice = can_relay;
+
+    // Made once, so that the children given it don't render again for a new one.
+    let send = {
+        let wss = (*wss).clone();
+        use_state(move || Callback::from(move |message: WebSocketMessage| wss.send(&message)))
+    };
+    let session_expired = {
+        let user = user.clone();
+        let history = history.clone();
+        let room = props.room.clone();
+        let username = current_username.clone();
+        Callback::from(move |_| {
+            activity::record(&username, activity::Event::SessionExpired);
+            AuthService::sign_out(&user);
+            let query = LoginQuery {
+                room: Some(room.clone()),
+                expired: true,
+                ..Default::default()
+            };
+            if let Err(e) = history.push_with_query(Route::Login, query) {
+                log::error!("failed to redirect to login: {:?}", e);
+            }
+        })
+    };
+
+    // Registering announces our public key, so it waits for the key to be loaded.
+    {
+        let wss = (*wss).clone();
+        let keys = keys.clone();
+        let username = current_username.clone();
+        let room = props.room.clone();
+        let invite = props.invite.clone();
+        let user = user.clone();
+        let store = store.clone();
+        use_effect_with_deps(
+            move |_| {
+                fetch_room_info(&user, store.dispatcher(), room.clone());
+                spawn_local(async move {
+                    let loaded = KeyPair::load_or_create(&username)
+                        .await
+                        .map(Rc::new)
+                        .map_err(|e| log::error!("crypto: direct messages unavailable: {}", e))
+                        .ok();
+                    register(
+                        &wss,
+                        &username,
+                        &room,
+                        invite.as_deref(),
+                        loaded.as_deref(),
+                        false,
+                    );
+                    keys.set(loaded);
+                });
+                || ()
+            },
+            (),
+        );
+    }
+    // Our contacts, as we left them.
+    {
+        let store = store.dispatcher();
+        let username = current_username.clone();
+        use_effect_with_deps(
+            move |_| {
+                store.dispatch(Action::Contacts(contacts::load(&username)));
+                || ()
+            },
+            (),
+        );
+    }
+    use_activity_log(&current_username, &props.room, store.connection);
+    {
+        let wss = (*wss).clone();
+        let store = store.clone();
+        let user = user.clone();
+        let invite = props.invite.clone();
+        let pending_password = pending_password.clone();
+        let voice = voice.dispatcher();
+        let sharing = live_share.is_sharing();
+        let stop_live_share = live_share.stop.clone();
+        use_effect_with_deps(
+            move |room| {
+                if *room != store.room {
+                    voice.dispatch(VoiceAction::Leave);
+                    // Told to the room we are leaving, before we do.
+                    if sharing {
+                        stop_live_share.emit(());
+                    }
+                    wss.send(&WebSocketMessage {
+                        data: Some(room.clone()),
+                        invite,
+                        password: pending_password.borrow_mut().take(),
+                        ..WebSocketMessage::new(MsgTypes::Join)
+                    });
+                    ask_about_room(&wss);
+                    store.dispatch(Action::JoinRoom(room.clone()));
+                    fetch_room_info(&user, store.dispatcher(), room.clone());
+                }
+                || ()
+            },
+            props.room.clone(),
+        );
+    }
+    // Renamed by its owner while we are in it, or deleted and we were taken elsewhere: the address
+    // follows, without joining anew.
+    {
+        let history = history.clone();
+        let room = props.room.clone();
+        use_effect_with_deps(
+            move |renamed: &String| {
+                if *renamed != room {
+                    history.replace(Route::ChatRoom {
+                        room: renamed.clone(),
+                    });
+                }
+                || ()
+            },
+            store.room.clone(),
+        );
+    }
+    {
+        let now = now.clone();
+        use_effect_with_deps(
+            move |_| {
+                let clock = Interval::new(CLOCK_INTERVAL_MS, move || now.set(time::now()));
+                move || drop(clock)
+            },
+            (),
+        );
+    }
+    {
+        let user = user.clone();
+        let session_expired = session_expired.clone();
+        use_effect_with_deps(
+            move |_| {
+                let refresh = AuthService::keep_fresh(user, session_expired);
+                move || drop(refresh)
+            },
+            (),
+        );
+    }
+
+    // The sidebar's unread counts of other rooms come from the room directory, so keep asking.
+    {
+        let wss = (*wss).clone();
+        use_effect_with_deps(
+            move |_| {
+                let refresh = Interval::new(DIRECTORY_REFRESH_MS, move || {
+                    wss.send(&WebSocketMessage::new(MsgTypes::ListRooms))
+                });
+                move || drop(refresh)
+            },
+            (),
+        );
+    }
+    use_away(&wss, &current_username);
+
+    let batch = use_mut_ref(Vec::new);
+    let _bridge = {
+        let store = store.dispatcher();
+        let wss = (*wss).clone();
+        let user = user.clone();
+        let calls = calls.dispatcher();
+        let voice = voice.dispatcher();
+        let keys = (*keys).clone();
+        let current_username = current_username.clone();
+        let on_call_event = on_call_event.clone();
+        let on_voice_event = on_voice_event.clone();
+        let session_expired = session_expired.clone();
+        let clash = clash.clone();
+        let password_prompt = password_prompt.clone();
+        let toasts = toasts.dispatcher();
+        use_bridge::<EventBus, _>(move |s: String| {
+            let incoming = handle_msg(&s).map_err(|e| DecodeError::new(&s, &e));
+            frame_log::record(Direction::Received, &s, incoming.as_ref().err().cloned());
+            match incoming {
+                Ok(Incoming::Store(Action::Message(message))) => {
+                    queue_message(&batch, &store, message)
+                }
+                Ok(Incoming::Store(action)) => {
+                    if let Action::Users(users) = &action {
+                        prefetch_avatars(store.clone(), users);
                     }
-                    MsgTypes::Message => {
-                        let message_data: MessageData =
-                            serde_json::from_str(&msg.data.unwrap()).unwrap();
-                        self.messages.push(message_data);
-                        return true;
+                    // Reports are only for moderators, which we may have become or stopped being.
+                    if let Action::RoleChange(_) = &action {
+                        wss.send(&WebSocketMessage::new(MsgTypes::Reports));
                     }
-                    _ => {
-                        return false;
+                    flush_messages(&batch, &store);
+                    store.dispatch(action)
+                }
+                Ok(Incoming::Direct(direct)) => {
+                    if let Some(keys) = keys.clone() {
+                        receive_direct(store.clone(), keys, &current_username, direct);
                     }
                 }
-            }
-            Msg::SubmitMessage => {
-                let input = self.chat_input.cast::<HtmlInputElement>();
-                if let Some(input) = input {
-                    let message = WebSocketMessage {
-                        message_type: MsgTypes::Message,
-                        data: Some(input.value()),
-                        data_array: None,
+                Ok(Incoming::Call(message)) => calls.dispatch(CallAction::Signal {
+                    message,
+                    on_event: on_call_event.clone(),
+                }),
+                Ok(Incoming::Voice(message)) => voice.dispatch(VoiceAction::Signal {
+                    message,
+                    on_event: on_voice_event.clone(),
+                }),
+                Ok(Incoming::Clash(c)) => {
+                    let event = match c {
+                        Clash::Conflict => activity::Event::NameTaken,
+                        Clash::Replaced => activity::Event::Replaced,
                     };
-                    if let Err(e) = self
-                        .wss
-                        .tx
-                        .clone()
-                        .try_send(serde_json::to_string(&message).unwrap())
-                    {
-                        log::debug!("error sending to channel: {:?}", e);
+                    activity::record(&current_username, event);
+                    clash.set(Some(c));
+                }
+                // The server has taken us elsewhere already; catch up with it.
+                Ok(Incoming::RoomDeleted(deleted)) => {
+                    log::info!("#{} was deleted by {}", deleted.room, deleted.by);
+                    flush_messages(&batch, &store);
+                    store.dispatch(Action::JoinRoom(deleted.to.clone()));
+                    ask_about_room(&wss);
+                    fetch_room_info(&user, store.clone(), deleted.to);
+                }
+                Ok(Incoming::Kicked(kicked)) => {
+                    flush_messages(&batch, &store);
+                    store.dispatch(Action::JoinRoom(kicked.to.clone()));
+                    ask_about_room(&wss);
+                    fetch_room_info(&user, store.clone(), kicked.to.clone());
+                    toasts.dispatch(ToastAction::Show {
+                        title: format!("Sent out of #{}", kicked.room),
+                        detail: format!("{} sent you back to #{}.", kicked.by, kicked.to),
+                    });
+                }
+                // Left where we were, or put in the default room; go there, and say why, or ask for
+                // the password it wants.
+                Ok(Incoming::JoinRefused(refused)) => {
+                    flush_messages(&batch, &store);
+                    store.dispatch(Action::JoinRoom(refused.to.clone()));
+                    ask_about_room(&wss);
+                    fetch_room_info(&user, store.clone(), refused.to.clone());
+                    match refused.reason {
+                        Refusal::Password => password_prompt.set(Some((refused.room, None))),
+                        Refusal::WrongPassword => {
+                            let error = refusal_reason(&refused);
+                            password_prompt.set(Some((refused.room, Some(error))));
+                        }
+                        _ => toasts.dispatch(ToastAction::Show {
+                            title: format!("Couldn't enter #{}", refused.room),
+                            detail: refusal_reason(&refused),
+                        }),
                     }
-                    input.set_value("");
-                };
-                false
+                }
+                Ok(Incoming::SessionExpired) => session_expired.emit(()),
+                // They changed since we last looked; start over, which shows them.
+                Ok(Incoming::TermsRequired) => {
+                    terms::forget(&current_username);
+                    let _ = web_sys::window().unwrap().location().reload();
+                }
+                Ok(Incoming::Ignored) => {}
+                Err(e) => {
+                    log::error!("ws: unreadable message: {}", e.summary());
+                    toasts.dispatch(ToastAction::Show {
+                        title: "Couldn't read a frame from the server".into(),
+                        detail: e.summary(),
+                    });
+                }
             }
-        }
-    }    fn view(&self, ctx: &Context<Self>) -> Html {
-        let submit = ctx.link().callback(|_| Msg::SubmitMessage);
-        let (user, _) = ctx
-            .link()
-            .context::<User>(Callback::noop())
-            .expect("context to be set");
-        let current_username = user.username.borrow().clone();
+        })
+    };
 
-        html! {
+    let on_submit = on_submit(
+        &wss,
+        (*keys).clone(),
+        &store,
+        &current_username,
+        toasts.dispatcher(),
+    );
+    let logout = {
+        let wss = (*wss).clone();
+        let calls = calls.clone();
+        let user = user.clone();
+        let history = history.clone();
+        let username = current_username.clone();
+        Callback::from(move |_| {
+            activity::record(&username, activity::Event::SignedOut);
+            calls.leave();
+            wss.send(&WebSocketMessage::new(MsgTypes::Unregister));
+            AuthService::sign_out(&user);
+            // Leaving the route drops the socket's last handle, which flushes the queue and closes it.
+            history.push(Route::Login);
+        })
+    };
+    let take_over = {
+        let wss = (*wss).clone();
+        let keys = (*keys).clone();
+        let store = store.clone();
+        let username = current_username.clone();
+        let clash = clash.clone();
+        let invite = props.invite.clone();
+        Callback::from(move |_| {
+            activity::record(&username, activity::Event::TookOver);
+            // Registering replays the room's recent messages, so start over from those.
+            store.dispatch(Action::JoinRoom(store.room.clone()));
+            register(
+                &wss,
+                &username,
+                &store.room,
+                invite.as_deref(),
+                keys.as_deref(),
+                true,
+            );
+            clash.set(None);
+        })
+    };
+    let pick_new_name = {
+        let user = user.clone();
+        let history = history.clone();
+        let room = props.room.clone();
+        let username = current_username.clone();
+        Callback::from(move |_| {
+            activity::record(&username, activity::Event::Renamed);
+            AuthService::sign_out(&user);
+            let query = LoginQuery {
+                room: Some(room.clone()),
+                conflict: true,
+                ..Default::default()
+            };
+            if let Err(e) = history.push_with_query(Route::Login, query) {
+                log::error!("failed to redirect to login: {:?}", e);
+            }
+        })
+    };
+    let open_frames = {
+        let show_frames = show_frames.clone();
+        Callback::from(move |_| show_frames.set(true))
+    };
+    let close_frames = {
+        let show_frames = show_frames.clone();
+        Callback::from(move |_| show_frames.set(false))
+    };
+    let dismiss_toast = {
+        let toasts = toasts.dispatcher();
+        Callback::from(move |id| toasts.dispatch(ToastAction::Dismiss(id)))
+    };
+    let enter_with_password = {
+        let password_prompt = password_prompt.clone();
+        let history = history.clone();
+        Callback::from(move |password: String| {
+            if let Some((room, _)) = &*password_prompt {
+                *pending_password.borrow_mut() = Some(password);
+                history.push(Route::ChatRoom { room: room.clone() });
+            }
+            password_prompt.set(None);
+        })
+    };
+    let close_password_prompt = {
+        let password_prompt = password_prompt.clone();
+        Callback::from(move |_| password_prompt.set(None))
+    };
+    let toggle_avatar_settings = {
+        let show_avatar_settings = show_avatar_settings.clone();
+        Callback::from(move |_| show_avatar_settings.set(!*show_avatar_settings))
+    };
+    let on_avatar_change = {
+        let wss = (*wss).clone();
+        let show_avatar_settings = show_avatar_settings.clone();
+        Callback::from(move |settings: AvatarSettings| {
+            wss.send(&WebSocketMessage {
+                data: settings.gravatar(),
+                ..WebSocketMessage::new(MsgTypes::Gravatar)
+            });
+            show_avatar_settings.set(false);
+        })
+    };
+    let on_kick = {
+        let wss = (*wss).clone();
+        Callback::from(move |name: String| {
+            wss.send(&WebSocketMessage {
+                data: Some(name),
+                ..WebSocketMessage::new(MsgTypes::Kick)
+            })
+        })
+    };
+    let permissions = store.permissions(&current_username);
+    // No one is sent out of the default room, where everyone starts.
+    let can_kick = store.conversation.is_none() && permissions.kick && store.room != DEFAULT_ROOM;
+
+    let toggle_system = {
+        let preferences = preferences.clone();
+        Callback::from(move |_| preferences.toggle_system())
+    };
+    let open_print_view = {
+        let print_view = print_view.clone();
+        Callback::from(move |_| print_view.set(true))
+    };
+
+    // Whoever may post in the room may speak in its voice channel, from any conversation.
+    let can_speak = !store.banned.contains(&current_username) && permissions.post;
+    let can_send = match &store.conversation {
+        None => can_speak,
+        Some(peer) => *peer == current_username || keys.is_some() && store.peer_key(peer).is_some(),
+    };
+    let avatar_of = |name: &str| store.avatar_of(name);
+    let on_call_reply = {
+        let wss = (*wss).clone();
+        let keys = (*keys).clone();
+        let store = store.clone();
+        Callback::from(move |(peer, text): (String, String)| {
+            let (Some(keys), Some(peer_key)) = (keys.clone(), store.peer_key(&peer)) else {
+                log::warn!("call: can't message {}, without their key", peer);
+                return;
+            };
+            send_direct(wss.clone(), keys, peer_key.to_string(), peer, text);
+        })
+    };
+
+    if *print_view {
+        let title = match &store.conversation {
+            Some(peer) => format!("@{}", peer),
+            None => store.room.clone(),
+        };
+        let on_close = {
+            let print_view = print_view.clone();
+            Callback::from(move |_| print_view.set(false))
+        };
+        return html! {
+            <PrintView {title} messages={store.visible_messages().iter().filter(|m| !m.system).cloned().collect::<Vec<_>>()} {on_close} />
+        };
+    }
+
+    html! {
+        <ContextProvider<Speaker> context={Speaker((*preferences.speaker).clone())}>
             <div class="flex w-screen h-screen bg-gray-50">
-                // Sidebar with user list
-                <div class="flex-none w-72 h-screen bg-white shadow-md flex flex-col">
-                    <div class="text-xl p-4 font-bold border-b border-gray-200 bg-blue-600 text-white">
-                        <div class="flex items-center gap-2">
-                            <svg xmlns="http://www.w3.org/2000/svg" class="h-6 w-6" fill="none" viewBox="0 0 24 24" stroke="currentColor">
-                                <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M17 20h5v-2a3 3 0 00-5.356-1.857M17 20H7m10 0v-2c0-.656-.126-1.283-.356-1.857M7 20H2v-2a3 3 0 015.356-1.857M7 20v-2c0-.656.126-1.283.356-1.857m0 0a5.002 5.002 0 019.288 0M15 7a3 3 0 11-6 0 3 3 0 016 0zm6 3a2 2 0 11-4 0 2 2 0 014 0zM7 10a2 2 0 11-4 0 2 2 0 014 0z" />
-                            </svg>
-                            {"Online Users"}
-                        </div>
+                <Sidebar
+                    on_edit_avatar={can_set_avatar.then_some(toggle_avatar_settings)}
+                    on_kick={can_kick.then_some(on_kick)}
+                    on_share_contact={can_send.then(|| on_submit.reform(|name: String| contacts::card(&name)))}
+                >
+                    {
+                        if has_voice {
+                            let members = store.users.iter().filter(|u| u.voice).cloned().collect();
+                            let push_to_talk = talk_key.as_deref().map(push_to_talk::label);
+                            voice.view(&voice.dispatcher(), &current_username, &store.room, members, can_speak, push_to_talk)
+                        } else {
+                            html! {}
+                        }
+                    }
+                </Sidebar>
+
+                // Main chat area
+                <div
+                    class="relative grow h-screen flex flex-col bg-white shadow-lg"
+                    ondragenter={attachments.ondragenter.clone()}
+                    ondragover={Callback::from(|e: DragEvent| e.prevent_default())}
+                    ondragleave={attachments.ondragleave.clone()}
+                    ondrop={attachments.ondrop.clone()}
+                >
+                    { attachments.view_drop_overlay() }
+                    {
+                        if let Connection::Unreachable { attempts } = store.connection {
+                            let retry = {
+                                let wss = (*wss).clone();
+                                Callback::from(move |_| wss.retry())
+                            };
+                            view_unreachable(attempts, retry)
+                        } else {
+                            html! {}
+                        }
+                    }
+                    <div class="absolute bottom-24 right-4 z-30 flex flex-col gap-2">
+                        {
+                            toasts.items.iter().map(|toast| html! {
+                                <Toast
+                                    key={toast.id}
+                                    toast={toast.clone()}
+                                    on_dismiss={dismiss_toast.clone()}
+                                    on_details={open_frames.clone()}
+                                />
+                            }).collect::<Html>()
+                        }
                     </div>
-                    <div class="overflow-auto flex-grow">
                     {
-                        self.users.clone().iter().map(|u| {
-                            let is_current_user = u.name == current_username;
-                            html!{                                <div class={classes!(
-                                    "flex", "items-center", "m-3", "rounded-lg", "p-3", "transition-all", "hover:bg-blue-50", "cursor-pointer",
-                                    if is_current_user { vec!["bg-blue-100", "border-l-4", "border-blue-500"] } else { vec!["bg-white"] }
-                                )}>
-                                    <div class="relative">
-                                        <img class="w-12 h-12 rounded-full shadow-sm" src={u.avatar.clone()} alt="avatar"/>
-                                        <div class="absolute bottom-0 right-0 w-3 h-3 bg-green-500 rounded-full border-2 border-white"></div>
-                                    </div>
-                                    <div class="flex-grow ml-3">
-                                        <div class="flex text-sm font-medium justify-between">
-                                            <div class="flex items-center gap-1">
-                                                {u.name.clone()}
-                                                {
-                                                    if is_current_user {
-                                                        html! { <span class="text-xs bg-blue-600 text-white px-2 rounded-full">{"You"}</span> }
-                                                    } else {
-                                                        html! {}
-                                                    }
-                                                }
-                                            </div>
-                                        </div>
-                                        <div class="text-xs text-gray-500 mt-1">
-                                            {"Online"}
-                                        </div>
-                                    </div>
+                        if *show_frames {
+                            html! { <FrameLog on_close={close_frames} /> }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    {
+                        match *clash {
+                            Some(Clash::Conflict) => view_clash(Clash::Conflict, &current_username, take_over, pick_new_name),
+                            Some(Clash::Replaced) => view_clash(Clash::Replaced, &current_username, take_over, logout.clone()),
+                            None => html! {},
+                        }
+                    }
+                    {
+                        if let Some((room, error)) = &*password_prompt {
+                            html! {
+                                <RoomPasswordDialog
+                                    room={room.clone()}
+                                    error={error.clone()}
+                                    on_submit={enter_with_password}
+                                    on_close={close_password_prompt}
+                                />
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    {
+                        calls.view(
+                            &calls.dispatcher(),
+                            on_call_event.clone(),
+                            avatar_of,
+                            dnd::until(&current_username).is_some(),
+                            on_call_reply,
+                        )
+                    }
+                    {
+                        if talk_key.is_some() && talking && (calls.in_call() || voice.status() == VoiceStatus::Joined) {
+                            html! {
+                                <div
+                                    title="Push to talk: you are being heard"
+                                    class="absolute bottom-28 left-1/2 -translate-x-1/2 z-30 px-3 py-1 rounded-full bg-red-600 text-white text-xs font-bold tracking-wide shadow-lg animate-pulse"
+                                >
+                                    {"PTT"}
                                 </div>
                             }
-                        }).collect::<Html>()
+                        } else {
+                            html! {}
+                        }
                     }
-                    </div>
+                    <ChatHeader
+                        send={(*send).clone()}
+                        now={*now}
+                        {can_relay}
+                        hide_system={*preferences.hide_system}
+                        on_toggle_system={toggle_system}
+                        on_print={open_print_view}
+                        on_logout={logout.clone()}
+                        call_log={(*call_log).clone()}
+                        can_call={!calls.busy()}
+                        on_call={call_back}
+                    >
+                        {
+                            if store.conversation.as_ref() == Some(&current_username) {
+                                view_notes_title(&store)
+                            } else if let Some(peer) = &store.conversation {
+                                view_direct_title(&store, peer, (*peer_profile).as_ref(), &calls, &show_fingerprints, &on_call_event)
+                            } else {
+                                view_room_title(&store, &props.room)
+                            }
+                        }
+                    </ChatHeader>
+
+                    {
+                        match (&store.conversation, &*keys) {
+                            (Some(peer), Some(keys)) if *show_fingerprints => {
+                                view_fingerprints(&store, peer, keys, &show_fingerprints)
+                            }
+                            _ => html! {},
+                        }
+                    }
+                    {
+                        if *show_avatar_settings {
+                            html! {
+                                <SettingsPanel
+                                    username={current_username.clone()}
+                                    preferences={preferences.clone()}
+                                    {on_avatar_change}
+                                />
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+
+                    {
+                        match store.conversation {
+                            None => store
+                                .messages
+                                .iter()
+                                .rev()
+                                .find(|m| m.announcement && m.pinned_until.is_some_and(|t| t > *now))
+                                .map_or_else(|| html! {}, |m| view_pinned(m)),
+                            Some(_) => html! {},
+                        }
+                    }
+
+                    <ChatHistory
+                        room={props.room.clone()}
+                        send={(*send).clone()}
+                        toasts={toasts.dispatcher()}
+                        now={*now}
+                        hide_system={*preferences.hide_system}
+                        keywords={(*preferences.keywords).clone()}
+                        spam={*preferences.spam}
+                    />
+
+                    {
+                        match &store.conversation {
+                            Some(peer) if !can_send => html! {
+                                <div class="w-full px-4 pt-3 text-sm text-gray-600">
+                                    {format!("{} can't receive encrypted messages right now.", peer)}
+                                </div>
+                            },
+                            _ => html! {},
+                        }
+                    }
+                    {
+                        if let Some(notice) = &calls.notice {
+                            let dismiss = {
+                                let calls = calls.dispatcher();
+                                Callback::from(move |_| calls.dispatch(CallAction::DismissNotice))
+                            };
+                            html! {
+                                <div class="w-full px-4 pt-3 flex items-center gap-2 text-sm text-gray-600">
+                                    <span class="flex-grow">{notice.clone()}</span>
+                                    <button onclick={dismiss} class="px-2 rounded-full hover:bg-gray-100">
+                                        {"×"}
+                                    </button>
+                                </div>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    { live_share.view(&store.room) }
+                    { attachments.view_status() }
+                    <Composer
+                        // A fresh composer per conversation, so a half-typed mention doesn't follow along.
+                        key={store.conversation.clone().unwrap_or_default()}
+                        placeholder={match &store.conversation {
+                            Some(peer) if *peer == current_username => "Note to self, kept on this device".to_string(),
+                            Some(peer) => format!("Message {} (end-to-end encrypted)", peer),
+                            None if store.banned.contains(&current_username) => "You were banned from this room".to_string(),
+                            None if !can_send => "Your role in this room can't post".to_string(),
+                            None => match store.slow_mode {
+                                Some(seconds) => format!("Slow mode: one message every {}s", seconds),
+                                None => "Type your message here...".to_string(),
+                            },
+                        }}
+                        disabled={!can_send}
+                        {on_submit}
+                        on_file={attachments.on_file.clone()}
+                        // Live updates go out in the clear, so never in a direct conversation.
+                        on_share_live={store.conversation.is_none().then(|| live_share.start.clone())}
+                    />
                 </div>
-                
-                // Main chat area
-                <div class="grow h-screen flex flex-col bg-white shadow-lg">
-                    // Chat header
-                    <div class="w-full h-16 border-b border-gray-200 bg-white shadow-sm flex items-center px-4">
-                        <div class="flex items-center">
-                            <div class="text-xl font-semibold flex items-center gap-2">
-                                <svg xmlns="http://www.w3.org/2000/svg" class="h-6 w-6 text-blue-600" fill="none" viewBox="0 0 24 24" stroke="currentColor">
-                                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M8 12h.01M12 12h.01M16 12h.01M21 12c0 4.418-4.03 8-9 8a9.863 9.863 0 01-4.255-.949L3 20l1.395-3.72C3.512 15.042 3 13.574 3 12c0-4.418 4.03-8 9-8s9 3.582 9 8z" />
-                                </svg>
-                                {"Chat Room"}
-                            </div>
-                            <div class="ml-3 bg-green-100 text-green-800 text-xs px-2 py-1 rounded-full">
-                                {format!("{} users online", self.users.len())}
-                            </div>
+            </div>
+        </ContextProvider<Speaker>>
+    }
+}
+
+/// Shown instead of the chat while the server can't be reached. Retries go on in the background;
+/// the button only saves waiting for the next one.
+fn view_unreachable(attempts: u32, on_retry: Callback<MouseEvent>) -> Html {
+    let tries = if attempts == 1 {
+        "once".to_string()
+    } else {
+        format!("{} times", attempts)
+    };
+    html! {
+        <div class="absolute inset-0 z-30 flex items-center justify-center bg-white">
+            <div class="w-96 max-w-full p-6 flex flex-col items-center gap-3 text-center text-gray-800">
+                <svg xmlns="http://www.w3.org/2000/svg" class="h-12 w-12 text-red-500" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M18.364 5.636a9 9 0 010 12.728m-3.536-3.536a4 4 0 010-5.656m-7.072 7.072a9 9 0 010-12.728m3.536 3.536a4 4 0 010 5.656M12 12h.01M3 3l18 18" />
+                </svg>
+                <div class="text-lg font-semibold">{"Can't reach the chat server"}</div>
+                <code class="px-2 py-1 rounded bg-gray-100 text-sm">{WS_ENDPOINT}</code>
+                <div class="text-sm text-gray-600">
+                    {format!("Tried {}. We keep trying in the background and will carry on as soon as it answers.", tries)}
+                </div>
+                <button onclick={on_retry} class="px-4 py-2 rounded-lg bg-blue-600 text-white text-sm">
+                    {"Retry now"}
+                </button>
+            </div>
+        </div>
+    }
+}
+
+/// Asks what to do about another connection under our name: take over from it, or else leave.
+fn view_clash(
+    clash: Clash,
+    name: &str,
+    on_take_over: Callback<MouseEvent>,
+    on_leave: Callback<MouseEvent>,
+) -> Html {
+    let (title, explanation, leave_label, take_over_label) = match clash {
+        Clash::Conflict => (
+            format!("{} is already chatting elsewhere", name),
+            "Another window or device is connected under this name. Take over to chat here instead; the other one will be disconnected.",
+            "Pick a new name",
+            "Take over session",
+        ),
+        Clash::Replaced => (
+            "You're chatting elsewhere now".to_string(),
+            "Another window or device took over this session and this one was disconnected.",
+            "Log out",
+            "Use it here",
+        ),
+    };
+    html! {
+        <div class="absolute inset-0 z-30 flex items-center justify-center bg-black/40">
+            <div class="w-96 max-w-full p-6 rounded-2xl bg-white shadow-xl flex flex-col gap-4 text-gray-800">
+                <div class="text-lg font-semibold">{title}</div>
+                <div class="text-sm text-gray-600">{explanation}</div>
+                <div class="flex justify-end gap-2">
+                    <button onclick={on_leave} class="px-3 py-2 rounded-lg text-sm text-gray-600 hover:bg-gray-100">
+                        {leave_label}
+                    </button>
+                    <button onclick={on_take_over} class="px-3 py-2 rounded-lg bg-blue-600 text-white text-sm">
+                        {take_over_label}
+                    </button>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+/// Asks our server how much of the room it remembers, so older messages can be offered.
+fn fetch_room_info(user: &User, store: UseReducerDispatcher<ChatState>, room: String) {
+    let Some(api) = ApiClient::of(user) else {
+        return;
+    };
+    spawn_local(async move {
+        match api.room(&room).await {
+            Ok(info) => store.dispatch(Action::RoomInfo(info)),
+            Err(e) => log::warn!("api: no details of #{}: {}", room, e),
+        }
+    });
+}
+
+/// The room's icon and name, how many are in it and its topic.
+fn view_room_title(store: &Store, room: &str) -> Html {
+    html! {
+        <div class="flex items-center">
+            <div class="text-xl font-semibold flex items-center gap-2">
+                {
+                    if let Some(icon) = &store.room_icon {
+                        html! { <RoomIcon icon={icon.clone()} class={classes!("h-6", "w-6", "text-xl")} /> }
+                    } else {
+                        html! {
+                            <svg xmlns="http://www.w3.org/2000/svg" class="h-6 w-6 text-blue-600" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M8 12h.01M12 12h.01M16 12h.01M21 12c0 4.418-4.03 8-9 8a9.863 9.863 0 01-4.255-.949L3 20l1.395-3.72C3.512 15.042 3 13.574 3 12c0-4.418 4.03-8 9-8s9 3.582 9 8z" />
+                            </svg>
+                        }
+                    }
+                }
+                {format!("# {}", room)}
+                {
+                    if store.invite_only {
+                        html! {
+                            <span title="Invite-only" class="text-base text-gray-400">{"🔒"}</span>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                {
+                    if store.password_protected {
+                        html! {
+                            <span title="Password-protected" class="text-base text-gray-400">{"🔑"}</span>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+            </div>
+            {
+                match store.connection {
+                    Connection::Online => html! {
+                        <div class="ml-3 bg-green-100 text-green-800 text-xs px-2 py-1 rounded-full">
+                            {format!("{} users online", store.users.len())}
                         </div>
-                    </div>
-                    
-                    // Messages container with gradient background
-                    <div class="w-full flex-grow overflow-auto p-4 bg-gradient-to-b from-blue-50 to-gray-50">
-                        {
-                            self.messages.iter().map(|m| {
-                                let binding = UserProfile { 
-                                    name: m.from.clone(), 
-                                    avatar: format!("https://avatars.dicebear.com/api/adventurer-neutral/{}.svg", m.from) 
-                                };
-                                let user = self.users.iter().find(|u| u.name == m.from).unwrap_or(&binding);
-                                let is_current_user = m.from == current_username;
-                                
-                                html!{                                    <div class={classes!(
-                                        "flex", "mb-4", "transition-all", "duration-300", "ease-in",
-                                        if is_current_user { "justify-end" } else { "justify-start" }
-                                    )}>
-                                        {
-                                            if !is_current_user {
-                                                html! {
-                                                    <img class="w-10 h-10 rounded-full self-end mr-2 shadow-sm" src={user.avatar.clone()} alt="avatar"/>
-                                                }
-                                            } else {
-                                                html! {}
-                                            }
-                                        }
-                                        <div class={classes!(
-                                            "rounded-2xl", "p-4", "max-w-xl", "shadow-sm",                                            if is_current_user {
-                                                vec!["bg-blue-600", "text-white", "rounded-br-none"]
-                                            } else {
-                                                vec!["bg-white", "rounded-bl-none"]
-                                            }
-                                        )}>
-                                            <div class={classes!(
-                                                "font-medium", "mb-1",
-                                                if is_current_user { vec!["text-blue-100"] } else { vec!["text-gray-800"] }
-                                            )}>
-                                                {m.from.clone()}
-                                            </div>
-                                            <div class={classes!(
-                                                if is_current_user { vec!["text-white"] } else { vec!["text-gray-700"] }
-                                            )}>
-                                                {
-                                                    if m.message.ends_with(".gif") {
-                                                        html!{
-                                                            <div class="mt-2 rounded-lg overflow-hidden shadow-sm">
-                                                                <img class="w-full" src={m.message.clone()}/>
-                                                            </div>
-                                                        }
-                                                    } else {
-                                                        html!{
-                                                            <div class="whitespace-pre-wrap break-words">
-                                                                {m.message.clone()}
-                                                            </div>
-                                                        }
-                                                    }
-                                                }
-                                            </div>
-                                        </div>
-                                        {
-                                            if is_current_user {
-                                                html! {
-                                                    <img class="w-10 h-10 rounded-full self-end ml-2 shadow-sm" src={user.avatar.clone()} alt="avatar"/>
-                                                }
-                                            } else {
-                                                html! {}
-                                            }
+                    },
+                    Connection::Connecting => html! {
+                        <div class="ml-3 bg-yellow-100 text-yellow-800 text-xs px-2 py-1 rounded-full">
+                            {"Connecting..."}
+                        </div>
+                    },
+                    Connection::Unreachable { .. } => html! {
+                        <div class="ml-3 bg-red-100 text-red-800 text-xs px-2 py-1 rounded-full">
+                            {"Offline"}
+                        </div>
+                    },
+                }
+            }
+            {
+                if let Some(topic) = &store.topic {
+                    html! {
+                        <div class="ml-3 max-w-md truncate text-sm text-gray-500" title={topic.clone()}>{topic.clone()}</div>
+                    }
+                } else {
+                    html! {}
+                }
+            }
+        </div>
+    }
+}
+
+fn view_notes_title(store: &Store) -> Html {
+    let close = {
+        let store = store.clone();
+        Callback::from(move |_| store.dispatch(Action::CloseDirect))
+    };
+    html! {
+        <div class="flex items-center gap-3">
+            <button
+                onclick={close}
+                title={format!("Back to # {}", store.room)}
+                class="p-2 rounded-full text-gray-600 hover:bg-gray-100 transition-colors"
+            >
+                <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M15 19l-7-7 7-7" />
+                </svg>
+            </button>
+            <div class="text-xl font-semibold">{"Notes to self"}</div>
+            <div class="bg-gray-100 text-gray-700 text-xs px-2 py-1 rounded-full">{"Only on this device"}</div>
+        </div>
+    }
+}
+
+/// What the server says about the peer of the open direct `conversation`, once it answers.
+fn use_peer_profile(user: &User, conversation: Option<String>) -> UseStateHandle<Option<Profile>> {
+    let peer_profile = use_state(|| Option::<Profile>::None);
+    {
+        let user = user.clone();
+        let peer_profile = peer_profile.clone();
+        use_effect_with_deps(
+            move |conversation| {
+                peer_profile.set(None);
+                let others = conversation
+                    .clone()
+                    .filter(|peer| *peer != *user.username.borrow());
+                if let (Some(peer), Some(api)) = (others, ApiClient::of(&user)) {
+                    spawn_local(async move {
+                        match api.profile(&peer).await {
+                            Ok(profile) => peer_profile.set(profile),
+                            Err(e) => log::warn!("api: no profile of {}: {}", peer, e),
+                        }
+                    });
+                }
+                || ()
+            },
+            conversation,
+        );
+    }
+    peer_profile
+}
+
+/// Whether the fingerprints of the direct `conversation` are shown. They are per conversation, so
+/// they aren't carried over to the next one.
+fn use_fingerprints(conversation: Option<String>) -> UseStateHandle<bool> {
+    let show_fingerprints = use_state(|| false);
+    {
+        let show_fingerprints = show_fingerprints.clone();
+        use_effect_with_deps(
+            move |_| {
+                show_fingerprints.set(false);
+                || ()
+            },
+            conversation,
+        );
+    }
+    show_fingerprints
+}
+
+fn view_direct_title(
+    store: &Store,
+    peer: &str,
+    profile: Option<&Profile>,
+    calls: &UseReducerHandle<CallState>,
+    show_fingerprints: &UseStateHandle<bool>,
+    on_call_event: &Callback<CallEvent>,
+) -> Html {
+    let trust = store.peer_key(peer).map(|key| crypto::trust(peer, key));
+    let (label, badge) = match trust {
+        Some(Trust::Verified) => ("Verified", ["bg-green-100", "text-green-800"]),
+        Some(Trust::Changed) => ("Key changed", ["bg-red-100", "text-red-800"]),
+        Some(Trust::Unverified) => ("Not verified", ["bg-gray-100", "text-gray-700"]),
+        None => ("Not encrypted", ["bg-gray-100", "text-gray-700"]),
+    };
+    let close = {
+        let store = store.clone();
+        Callback::from(move |_| store.dispatch(Action::CloseDirect))
+    };
+    let toggle_fingerprints = {
+        let show_fingerprints = show_fingerprints.clone();
+        Callback::from(move |_| show_fingerprints.set(!*show_fingerprints))
+    };
+    let dial = |video: bool| {
+        let calls = calls.dispatcher();
+        let peer = peer.to_string();
+        let on_event = on_call_event.clone();
+        Callback::from(move |_| {
+            calls.dispatch(CallAction::Dial {
+                peer: peer.clone(),
+                video,
+                on_event: on_event.clone(),
+            })
+        })
+    };
+
+    html! {
+        <div class="flex items-center gap-3">
+            <button
+                onclick={close}
+                title={format!("Back to # {}", store.room)}
+                class="p-2 rounded-full text-gray-600 hover:bg-gray-100 transition-colors"
+            >
+                <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M15 19l-7-7 7-7" />
+                </svg>
+            </button>
+            <div class="text-xl font-semibold flex items-center gap-2">
+                <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5 text-blue-600" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M12 15v2m-6 4h12a2 2 0 002-2v-6a2 2 0 00-2-2H6a2 2 0 00-2 2v6a2 2 0 002 2zm10-10V7a4 4 0 00-8 0v4h8z" />
+                </svg>
+                {format!("@ {}", peer)}
+            </div>
+            {
+                // Only once it is theirs; the answer may be about the previous peer.
+                match profile.filter(|p| p.name == peer) {
+                    Some(profile) => html! {
+                        <div class="flex items-center gap-2 text-sm text-gray-500">
+                            <img
+                                class="w-6 h-6 rounded-full"
+                                src={profile.avatar.as_deref().and_then(sanitize::image).unwrap_or_else(|| store.avatar_of(peer))}
+                                alt="avatar"
+                            />
+                            {
+                                match (&profile.room, store.known.get(peer)) {
+                                    (Some(room), _) => format!("in # {}", room),
+                                    (None, Some(known)) => {
+                                        match time::relative_label(known.last_seen, time::now()).as_str() {
+                                            "just now" => "offline, seen just now".to_string(),
+                                            age => format!("offline, seen {} ago", age),
                                         }
-                                    </div>
+                                    }
+                                    (None, None) => "offline".to_string(),
                                 }
-                            }).collect::<Html>()
-                        }
-                    </div>
-                    
-                    // Input area
-                    <div class="w-full p-4 border-t border-gray-200 bg-white flex items-center gap-2">
-                        <input 
-                            ref={self.chat_input.clone()} 
-                            type="text" 
-                            placeholder="Type your message here..." 
-                            class="block w-full py-3 px-4 bg-gray-100 rounded-full outline-none focus:ring-2 focus:ring-blue-500 focus:bg-white transition-all" 
-                            name="message" 
-                            required=true 
-                        />
-                        <button 
-                            onclick={submit} 
-                            class="p-3 bg-blue-600 rounded-full flex justify-center items-center text-white hover:bg-blue-700 transition-colors focus:outline-none focus:ring-2 focus:ring-blue-500"
+                            }
+                        </div>
+                    },
+                    None => html! {},
+                }
+            }
+            <button
+                onclick={toggle_fingerprints}
+                disabled={trust.is_none()}
+                class={classes!("text-xs", "px-2", "py-1", "rounded-full", badge.to_vec())}
+            >
+                {label}
+            </button>
+            <button
+                onclick={dial(false)}
+                disabled={calls.busy()}
+                title={format!("Call {}", peer)}
+                class="p-2 rounded-full text-blue-600 hover:bg-blue-50 disabled:opacity-50 transition-colors"
+            >
+                <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M3 5a2 2 0 012-2h3.28a1 1 0 01.948.684l1.498 4.493a1 1 0 01-.502 1.21l-2.257 1.13a11.042 11.042 0 005.516 5.516l1.13-2.257a1 1 0 011.21-.502l4.493 1.498a1 1 0 01.684.949V19a2 2 0 01-2 2h-1C9.716 21 3 14.284 3 6V5z" />
+                </svg>
+            </button>
+            <button
+                onclick={dial(true)}
+                disabled={calls.busy()}
+                title={format!("Video call {}", peer)}
+                class="p-2 rounded-full text-blue-600 hover:bg-blue-50 disabled:opacity-50 transition-colors"
+            >
+                <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M15 10l4.553-2.276A1 1 0 0121 8.618v6.764a1 1 0 01-1.447.894L15 14M5 18h8a2 2 0 002-2V8a2 2 0 00-2-2H5a2 2 0 00-2 2v8a2 2 0 002 2z" />
+                </svg>
+            </button>
+        </div>
+    }
+}
+
+fn view_fingerprints(
+    store: &Store,
+    peer: &str,
+    keys: &KeyPair,
+    show_fingerprints: &UseStateHandle<bool>,
+) -> Html {
+    let Some(peer_key) = store.peer_key(peer) else {
+        return html! {};
+    };
+    let trust = crypto::trust(peer, peer_key);
+    let verify = {
+        let peer = peer.to_string();
+        let peer_key = peer_key.to_string();
+        let show_fingerprints = show_fingerprints.clone();
+        Callback::from(move |_| {
+            crypto::mark_verified(&peer, &peer_key);
+            // Closing the panel re-renders the header, whose badge now reads "Verified".
+            show_fingerprints.set(false);
+        })
+    };
+
+    html! {
+        <div class="w-full px-4 py-3 border-b border-gray-200 bg-gray-50 text-sm text-gray-700">
+            {
+                if trust == Trust::Changed {
+                    html! {
+                        <div class="mb-2 font-medium text-red-700">
+                            {format!("{}'s key has changed since you verified it. Compare fingerprints again before trusting this conversation.", peer)}
+                        </div>
+                    }
+                } else {
+                    html! {
+                        <div class="mb-2">
+                            {format!("Compare these fingerprints with {} in person or over another channel.", peer)}
+                        </div>
+                    }
+                }
+            }
+            <div class="grid grid-cols-[auto_1fr] gap-x-4 gap-y-1 font-mono">
+                <span class="font-sans text-gray-500">{"You"}</span>
+                <span>{crypto::fingerprint(keys.public_key())}</span>
+                <span class="font-sans text-gray-500">{peer.to_string()}</span>
+                <span>{crypto::fingerprint(peer_key)}</span>
+            </div>
+            {
+                if trust == Trust::Verified {
+                    html! {}
+                } else {
+                    html! {
+                        <button
+                            onclick={verify}
+                            class="mt-3 px-4 py-2 rounded-full bg-blue-600 text-white hover:bg-blue-700 transition-colors"
                         >
-                            <svg viewBox="0 0 24 24" xmlns="http://www.w3.org/2000/svg" class="w-6 h-6 fill-white">
-                                <path d="M0 0h24v24H0z" fill="none"></path><path d="M2.01 21L23 12 2.01 3 2 10l15 2-15 2z"></path>
-                            </svg>
+                            {"They match"}
                         </button>
-                    </div>
-                </div>
-            </div>
+                    }
+                }
+            }
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn malformed_frames_are_errors() {
+        assert!(handle_msg("").is_err());
+        assert!(handle_msg("not json").is_err());
+        assert!(handle_msg(r#"{"data":"no type"}"#).is_err());
+        assert!(handle_msg(r#"{"messageType":"shout"}"#).is_err());
+        assert!(handle_msg(r#"{"messageType":"message","data":42}"#).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn unreadable_payloads_are_ignored() {
+        for frame in [
+            r#"{"messageType":"message"}"#,
+            r#"{"messageType":"message","data":"{oops"}"#,
+            r#"{"messageType":"message","data":"{\"from\":\"alice\"}"}"#,
+            r#"{"messageType":"direct","data":"{\"from\":\"alice\"}"}"#,
+        ] {
+            assert!(
+                matches!(handle_msg(frame), Ok(Incoming::Ignored)),
+                "{}",
+                frame
+            );
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn messages_go_to_the_store() {
+        let frame = r#"{"messageType":"message","data":"{\"from\":\"alice\",\"message\":\"hi\",\"time\":1}"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::Store(Action::Message(m))) => {
+                assert_eq!(m.from, "alice");
+                assert_eq!(m.message, "hi");
+                assert_eq!(m.time, Some(1.0));
+            }
+            _ => panic!("expected a message"),
         }
     }
-}
\ No newline at end of file
+
+    #[wasm_bindgen_test]
+    fn relay_status_goes_to_the_store() {
+        let frame = r#"{"messageType":"relay","data":"{\"target\":\"Discord\",\"enabled\":true,\"error\":\"Discord answered 404\"}"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::Store(Action::Relay(status))) => {
+                assert_eq!(status.target.as_deref(), Some("Discord"));
+                assert!(status.enabled);
+                assert_eq!(status.error.as_deref(), Some("Discord answered 404"));
+            }
+            _ => panic!("expected a relay status"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn room_emoji_go_to_the_store() {
+        let frame = r#"{"messageType":"emoji","data":"{\"owner\":\"alice\",\"emoji\":[{\"name\":\"party\",\"image\":\"data:image/png;base64,x\"}]}"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::Store(Action::Emoji(room))) => {
+                assert_eq!(room.owner.as_deref(), Some("alice"));
+                assert_eq!(room.emoji[0].name, "party");
+            }
+            _ => panic!("expected the room's emoji"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn whisper_commands() {
+        assert_eq!(
+            whisper_command("/whisper @bob  meet me\nlater "),
+            Some(("bob", "meet me\nlater"))
+        );
+        assert_eq!(whisper_command("/whisper bob"), None);
+        assert_eq!(whisper_command("/whisperbob hi"), None);
+        assert_eq!(whisper_command("hi /whisper bob hi"), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn announce_commands() {
+        assert_eq!(
+            announce_command("/announce 2h Maintenance at noon"),
+            Some((Some(120), "Maintenance at noon"))
+        );
+        assert_eq!(
+            announce_command("/announce 30m  Back soon "),
+            Some((Some(30), "Back soon"))
+        );
+        assert_eq!(
+            announce_command("/announce 3 little pigs"),
+            Some((None, "3 little pigs"))
+        );
+        // Too many hours to count in minutes isn't a duration.
+        assert_eq!(
+            announce_command("/announce 100000000h hi"),
+            Some((None, "100000000h hi"))
+        );
+        assert_eq!(announce_command("/announce 2h"), None);
+        assert_eq!(announce_command("/announcements"), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn slow_commands() {
+        assert_eq!(slow_command("/slow 30"), Some(30));
+        assert_eq!(slow_command("/slow 45s "), Some(45));
+        assert_eq!(slow_command("/slow off"), Some(0));
+        assert_eq!(slow_command("/slow soon"), None);
+        assert_eq!(slow_command("/slowly 30"), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn only_admins_get_stats() {
+        let frame = r#"{"messageType":"admin","data":"{\"users\":[],\"rooms\":[],\"messages\":{\"total\":0,\"lastMinute\":0,\"lastHour\":0},\"startedAt\":0}"}"#;
+        assert!(matches!(
+            handle_msg(frame),
+            Ok(Incoming::Store(Action::Admin))
+        ));
+        assert!(matches!(
+            handle_msg(r#"{"messageType":"admin"}"#),
+            Ok(Incoming::Ignored)
+        ));
+    }
+
+    #[wasm_bindgen_test]
+    fn only_moderators_get_reports() {
+        let frame = r#"{"messageType":"reports","data":"[{\"message\":{\"id\":\"abc\",\"from\":\"eve\",\"message\":\"buy now\",\"time\":0},\"reporters\":[\"bob\"]}]"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::Store(Action::Reports(reports))) => {
+                assert_eq!(reports[0].message.from, "eve");
+                assert_eq!(reports[0].reporters, ["bob"]);
+            }
+            _ => panic!("expected the reports"),
+        }
+        assert!(matches!(
+            handle_msg(r#"{"messageType":"reports"}"#),
+            Ok(Incoming::Ignored)
+        ));
+        assert!(matches!(
+            handle_msg(r#"{"messageType":"deleted","data":"abc"}"#),
+            Ok(Incoming::Store(Action::Deleted(id))) if id == "abc"
+        ));
+        assert!(matches!(
+            handle_msg(r#"{"messageType":"banned","data":"eve"}"#),
+            Ok(Incoming::Store(Action::Banned(name))) if name == "eve"
+        ));
+    }
+
+    #[wasm_bindgen_test]
+    fn blacklists_and_warnings() {
+        match handle_msg(r#"{"messageType":"blacklist","data":"[\"darn\",\"heck\"]"}"#) {
+            Ok(Incoming::Store(Action::Blacklist(words))) => assert_eq!(words, ["darn", "heck"]),
+            _ => panic!("expected the blacklist"),
+        }
+        assert!(matches!(
+            handle_msg(r#"{"messageType":"warning","data":"Careful"}"#),
+            Ok(Incoming::Store(Action::Warning(text))) if text == "Careful"
+        ));
+    }
+
+    #[wasm_bindgen_test]
+    fn pins_of_the_room() {
+        let pins = r#"[{"message":{"id":"abc","from":"bob","message":"hi"},"pinnedBy":"alice","pinnedAt":5}]"#;
+        let frame = serde_json::to_string(&WebSocketMessage {
+            data: Some(pins.into()),
+            ..WebSocketMessage::new(MsgTypes::Pins)
+        })
+        .unwrap();
+        match handle_msg(&frame) {
+            Ok(Incoming::Store(Action::Pins(pins))) => {
+                assert_eq!(pins[0].message.id.as_deref(), Some("abc"));
+                assert_eq!(pins[0].pinned_by, "alice");
+            }
+            _ => panic!("expected the pins"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn room_restrictions() {
+        let frame = r#"{"messageType":"restrictions","data":"{\"links\":true}"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::Store(Action::Restrictions(r))) => {
+                assert!(r.links && !r.images && !r.attachments)
+            }
+            _ => panic!("expected the restrictions"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn room_updates() {
+        let frame = r#"{"messageType":"roomupdate","data":"{\"icon\":\"🐈\"}"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::Store(Action::RoomUpdate(u))) => assert_eq!(u.icon.as_deref(), Some("🐈")),
+            _ => panic!("expected the room update"),
+        }
+        match handle_msg(r#"{"messageType":"roomupdate","data":"{\"name\":\"kittens\"}"}"#) {
+            Ok(Incoming::Store(Action::RoomUpdate(u))) => {
+                assert_eq!(u.name.as_deref(), Some("kittens"));
+                assert_eq!(u.icon, None);
+            }
+            _ => panic!("expected the room update"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn deleted_rooms_take_us_back() {
+        let frame = r#"{"messageType":"roomdeleted","data":"{\"room\":\"cats\",\"by\":\"alice\",\"to\":\"general\"}"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::RoomDeleted(deleted)) => {
+                assert_eq!(deleted.room, "cats");
+                assert_eq!(deleted.by, "alice");
+                assert_eq!(deleted.to, "general");
+            }
+            _ => panic!("expected the room to be deleted"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn room_roles_say_who_may_do_what() {
+        let frame = r#"{"messageType":"roomroles","data":"{\"everyone\":{\"post\":false},\"roles\":[{\"name\":\"speaker\",\"permissions\":{\"pin\":true}}],\"members\":{\"bob\":\"speaker\"}}"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::Store(Action::RoomRoles(roles))) => {
+                assert!(!roles.permissions_of("alice").post);
+                let bob = roles.permissions_of("bob");
+                assert!(bob.post && bob.pin && !bob.kick);
+            }
+            _ => panic!("expected the room's roles"),
+        }
+        let frame =
+            r#"{"messageType":"users","data":"[{\"name\":\"bob\",\"roomRole\":\"speaker\"}]"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::Store(Action::Users(users))) => {
+                assert_eq!(users[0].room_role.as_deref(), Some("speaker"));
+            }
+            _ => panic!("expected the users"),
+        }
+        let frame = r#"{"messageType":"kicked","data":"{\"room\":\"cats\",\"by\":\"carol\",\"to\":\"general\"}"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::Kicked(kicked)) => {
+                assert_eq!(kicked.by, "carol");
+                assert_eq!(kicked.to, "general");
+            }
+            _ => panic!("expected to be sent out"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn invite_only_rooms_say_why_they_refuse() {
+        let frame = r#"{"messageType":"joinrefused","data":"{\"room\":\"club\",\"reason\":\"expired\",\"to\":\"general\"}"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::JoinRefused(refused)) => {
+                assert_eq!(refused.room, "club");
+                assert_eq!(refused.reason, Refusal::Expired);
+                assert_eq!(refused.to, "general");
+            }
+            _ => panic!("expected the refusal"),
+        }
+        let frame = r#"{"messageType":"invite","data":"{\"room\":\"club\",\"code\":\"c0de\",\"expiresAt\":1000}"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::Store(Action::Invite(invite))) => {
+                assert_eq!(invite.code, "c0de");
+                assert_eq!(invite.expires_at, 1000.0);
+            }
+            _ => panic!("expected the invite"),
+        }
+        match handle_msg(
+            r#"{"messageType":"roomupdate","data":"{\"name\":\"club\",\"inviteOnly\":true}"}"#,
+        ) {
+            Ok(Incoming::Store(Action::RoomUpdate(u))) => assert!(u.invite_only),
+            _ => panic!("expected the room update"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn password_rooms_count_down_the_lockout() {
+        let frame = r#"{"messageType":"joinrefused","data":"{\"room\":\"club\",\"reason\":\"tooManyAttempts\",\"to\":\"general\",\"retryIn\":540}"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::JoinRefused(refused)) => {
+                assert_eq!(refused.reason, Refusal::TooManyAttempts);
+                assert_eq!(
+                    refusal_reason(&refused),
+                    "Too many wrong passwords. Try again in 9 minutes."
+                );
+            }
+            _ => panic!("expected the refusal"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn directory_lists_rooms() {
+        let frame = r#"{"messageType":"listrooms","data":"[{\"name\":\"cats\",\"topic\":\"all about cats\",\"members\":2},{\"name\":\"vault\",\"members\":0,\"passwordProtected\":true}]"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::Store(Action::Rooms(rooms))) => {
+                assert_eq!(rooms.len(), 2);
+                assert_eq!(rooms[0].topic.as_deref(), Some("all about cats"));
+                assert_eq!(rooms[0].members, 2);
+                assert!(!rooms[0].password_protected);
+                assert!(rooms[1].password_protected);
+            }
+            _ => panic!("expected the rooms"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn owners_change() {
+        let frame =
+            r#"{"messageType":"rolechange","data":"{\"owner\":\"bob\",\"previous\":\"alice\"}"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::Store(Action::RoleChange(change))) => {
+                assert_eq!(change.owner, "bob");
+                assert_eq!(change.previous, "alice");
+            }
+            _ => panic!("expected the new owner"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn users_come_with_their_roles() {
+        let users = r#"[{"name":"alice","role":"owner"},{"name":"bob"},{"name":"carol","role":"moderator"}]"#;
+        let frame = serde_json::to_string(&WebSocketMessage {
+            data: Some(users.into()),
+            ..WebSocketMessage::new(MsgTypes::Users)
+        })
+        .unwrap();
+        match handle_msg(&frame) {
+            Ok(Incoming::Store(Action::Users(users))) => {
+                let roles: Vec<_> = users.iter().map(|u| u.role).collect();
+                assert_eq!(roles, [Role::Owner, Role::Member, Role::Moderator]);
+            }
+            _ => panic!("expected users"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn users_fall_back_to_bare_names() {
+        let frame = r#"{"messageType":"users","dataArray":["alice","bob"],"data":"garbage"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::Store(Action::Users(users))) => {
+                let names: Vec<_> = users.iter().map(|u| u.name.as_str()).collect();
+                assert_eq!(names, ["alice", "bob"]);
+                assert!(users.iter().all(|u| u.avatar.is_none() && u.key.is_none()));
+            }
+            _ => panic!("expected users"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn users_with_profiles() {
+        let frame = r#"{"messageType":"users","data":"[{\"name\":\"alice\",\"avatar\":\"a.png\",\"key\":\"k\"}]"}"#;
+        match handle_msg(frame) {
+            Ok(Incoming::Store(Action::Users(users))) => {
+                assert_eq!(users.len(), 1);
+                assert_eq!(users[0].avatar.as_deref(), Some("a.png"));
+                assert_eq!(users[0].key.as_deref(), Some("k"));
+            }
+            _ => panic!("expected users"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn empty_user_list() {
+        assert!(matches!(
+            handle_msg(r#"{"messageType":"users"}"#),
+            Ok(Incoming::Store(Action::Users(users))) if users.is_empty()
+        ));
+    }
+
+    #[wasm_bindgen_test]
+    fn call_signals_and_auth_errors() {
+        assert!(matches!(
+            handle_msg(r#"{"messageType":"calloffer","from":"bob","data":"sdp"}"#),
+            Ok(Incoming::Call(msg)) if msg.from.as_deref() == Some("bob")
+        ));
+        assert!(matches!(
+            handle_msg(r#"{"messageType":"voiceoffer","from":"bob","data":"sdp"}"#),
+            Ok(Incoming::Voice(msg)) if msg.from.as_deref() == Some("bob")
+        ));
+        assert!(matches!(
+            handle_msg(r#"{"messageType":"autherror","data":"invalid_token"}"#),
+            Ok(Incoming::SessionExpired)
+        ));
+        assert!(matches!(
+            handle_msg(r#"{"messageType":"register"}"#),
+            Ok(Incoming::Ignored)
+        ));
+    }
+
+    #[wasm_bindgen_test]
+    fn name_clashes() {
+        assert!(matches!(
+            handle_msg(r#"{"messageType":"conflict"}"#),
+            Ok(Incoming::Clash(Clash::Conflict))
+        ));
+        assert!(matches!(
+            handle_msg(r#"{"messageType":"replaced"}"#),
+            Ok(Incoming::Clash(Clash::Replaced))
+        ));
+    }
+}
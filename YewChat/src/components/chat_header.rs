@@ -0,0 +1,707 @@
+use std::rc::Rc;
+
+use serde::Serialize;
+use yew::functional::*;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::components::blacklist_settings::BlacklistSettings;
+use crate::components::call_history::CallHistory;
+use crate::components::emoji_settings::EmojiSettings;
+use crate::components::moderation_queue::ModerationQueue;
+use crate::components::pinned_messages::PinnedMessages;
+use crate::components::relay_settings::RelaySettings;
+use crate::components::restriction_settings::RestrictionSettings;
+use crate::components::room_directory::RoomDirectory;
+use crate::components::room_settings::RoomSettings;
+use crate::components::room_stats::RoomStats;
+use crate::components::saved_messages::SavedMessages;
+use crate::components::share_dialog::ShareDialog;
+use crate::protocol::{
+    EmojiChange, Moderation, MsgTypes, RelayConfig, Restrictions, RoomRoles, RoomUpdate,
+    WebSocketMessage,
+};
+use crate::services::call_log::{self, CallRecord};
+use crate::services::export::{self, ExportFormat};
+use crate::store::{Action, Store};
+use crate::{time, Route, User};
+
+#[derive(Properties, PartialEq)]
+pub struct ChatHeaderProps {
+    /// Who or what the conversation is with, at the start of the bar.
+    pub children: Children,
+    /// Sends what is changed from the panels to the server.
+    pub send: Callback<WebSocketMessage>,
+    pub now: f64,
+    /// Whether the room can be mirrored to Discord or Slack, which is up to our server.
+    #[prop_or_default]
+    pub can_relay: bool,
+    /// Whether joins, leaves and other system lines are hidden from the room.
+    #[prop_or_default]
+    pub hide_system: bool,
+    pub on_toggle_system: Callback<()>,
+    /// Opens the conversation laid out for printing.
+    pub on_print: Callback<()>,
+    pub on_logout: Callback<MouseEvent>,
+    /// Our calls, of which those missed since we last looked are counted on their button.
+    pub call_log: Rc<Vec<CallRecord>>,
+    /// Whether someone can be called back now, as we aren't in a call already.
+    #[prop_or_default]
+    pub can_call: bool,
+    /// Calls the peer named, with video or not.
+    pub on_call: Callback<(String, bool)>,
+}
+
+/// A frame of `message_type` carrying what is sent as JSON.
+fn sending<T: Serialize + 'static>(
+    send: &Callback<WebSocketMessage>,
+    message_type: MsgTypes,
+) -> Callback<T> {
+    send.reform(move |value: T| WebSocketMessage {
+        data: Some(serde_json::to_string(&value).unwrap()),
+        ..WebSocketMessage::new(message_type)
+    })
+}
+
+/// The bar above the conversation: its title, then the rooms anyone can enter, sharing the room,
+/// what its owner and moderators may change of it, our saved messages and calls, and exporting
+/// the conversation. The dialogs and panels these open are shown with it.
+#[function_component(ChatHeader)]
+pub fn chat_header(props: &ChatHeaderProps) -> Html {
+    let user = use_context::<User>().expect("No context found.");
+    let store = use_context::<Store>().expect("No store found.");
+    let history = use_history().expect("history to be available");
+    let current_username = user.username.borrow().clone();
+
+    let export_menu = use_state(|| false);
+    let stats_open = use_state(|| false);
+    let show_relay = use_state(|| false);
+    let show_emoji = use_state(|| false);
+    let show_reports = use_state(|| false);
+    let show_pins = use_state(|| false);
+    let show_share = use_state(|| false);
+    let show_directory = use_state(|| false);
+    let show_room_settings = use_state(|| false);
+    let show_saved = use_state(|| false);
+    let show_calls = use_state(|| false);
+    // When the calls were last looked at, so that only missed calls since are counted.
+    let calls_seen = {
+        let username = current_username.clone();
+        use_state(move || call_log::seen(&username))
+    };
+
+    let owns_room =
+        store.conversation.is_none() && store.room_owner.as_ref() == Some(&current_username);
+    let moderates_room = store.conversation.is_none()
+        && (store.admin || store.room_owner.as_ref() == Some(&current_username));
+    let permissions = store.permissions(&current_username);
+    let can_pin = store.conversation.is_none() && permissions.pin;
+    let can_invite = store.conversation.is_none() && permissions.invite;
+    // Only the owner, and roles that may invite, are sent the code of an invite-only room, when
+    // they ask.
+    {
+        let send = props.send.clone();
+        use_effect_with_deps(
+            move |(_, invite_only, can_invite)| {
+                if *invite_only && *can_invite {
+                    send.emit(WebSocketMessage::new(MsgTypes::Invite));
+                }
+                || ()
+            },
+            (store.room.clone(), store.invite_only, can_invite),
+        );
+    }
+
+    let open_share = {
+        let show_share = show_share.clone();
+        Callback::from(move |_| show_share.set(true))
+    };
+    let close_share = {
+        let show_share = show_share.clone();
+        Callback::from(move |_| show_share.set(false))
+    };
+    let open_directory = {
+        let show_directory = show_directory.clone();
+        let send = props.send.clone();
+        Callback::from(move |_| {
+            show_directory.set(true);
+            send.emit(WebSocketMessage::new(MsgTypes::ListRooms));
+        })
+    };
+    let close_directory = {
+        let show_directory = show_directory.clone();
+        Callback::from(move |_| show_directory.set(false))
+    };
+    let join_listed = {
+        let show_directory = show_directory.clone();
+        Callback::from(move |room: String| {
+            show_directory.set(false);
+            history.push(Route::ChatRoom { room });
+        })
+    };
+    let open_room_settings = {
+        let show_room_settings = show_room_settings.clone();
+        Callback::from(move |_| show_room_settings.set(true))
+    };
+    let close_room_settings = {
+        let show_room_settings = show_room_settings.clone();
+        Callback::from(move |_| show_room_settings.set(false))
+    };
+    let open_saved = {
+        let show_saved = show_saved.clone();
+        Callback::from(move |_| show_saved.set(true))
+    };
+    let close_saved = {
+        let show_saved = show_saved.clone();
+        Callback::from(move |_| show_saved.set(false))
+    };
+    let open_calls = {
+        let show_calls = show_calls.clone();
+        let calls_seen = calls_seen.clone();
+        let username = current_username.clone();
+        Callback::from(move |_| {
+            let now = time::now();
+            call_log::set_seen(&username, now);
+            calls_seen.set(now);
+            show_calls.set(true);
+        })
+    };
+    let close_calls = {
+        let show_calls = show_calls.clone();
+        Callback::from(move |_| show_calls.set(false))
+    };
+    let message_caller = {
+        let store = store.clone();
+        let show_calls = show_calls.clone();
+        Callback::from(move |peer| {
+            store.dispatch(Action::OpenDirect(peer));
+            show_calls.set(false);
+        })
+    };
+    let call_back = {
+        let on_call = props.on_call.clone();
+        let show_calls = show_calls.clone();
+        Callback::from(move |call| {
+            on_call.emit(call);
+            show_calls.set(false);
+        })
+    };
+    let missed_calls = call_log::missed_since(&props.call_log, *calls_seen);
+    let remove_bookmark = {
+        let store = store.clone();
+        Callback::from(move |bookmark| store.dispatch(Action::RemoveBookmark(bookmark)))
+    };
+    let toggle_relay = {
+        let show_relay = show_relay.clone();
+        Callback::from(move |_| show_relay.set(!*show_relay))
+    };
+    let toggle_emoji = {
+        let show_emoji = show_emoji.clone();
+        Callback::from(move |_| show_emoji.set(!*show_emoji))
+    };
+    let toggle_pins = {
+        let show_pins = show_pins.clone();
+        Callback::from(move |_| show_pins.set(!*show_pins))
+    };
+    let toggle_reports = {
+        let show_reports = show_reports.clone();
+        Callback::from(move |_| show_reports.set(!*show_reports))
+    };
+    let toggle_stats = {
+        let stats_open = stats_open.clone();
+        Callback::from(move |_| stats_open.set(!*stats_open))
+    };
+    let toggle_export_menu = {
+        let export_menu = export_menu.clone();
+        Callback::from(move |_| export_menu.set(!*export_menu))
+    };
+    let open_print_view = {
+        let export_menu = export_menu.clone();
+        let on_print = props.on_print.clone();
+        Callback::from(move |_| {
+            export_menu.set(false);
+            on_print.emit(());
+        })
+    };
+
+    let on_relay_change = sending::<RelayConfig>(&props.send, MsgTypes::Relay);
+    let on_emoji_change = sending::<EmojiChange>(&props.send, MsgTypes::Emoji);
+    let on_moderate = sending::<Moderation>(&props.send, MsgTypes::Moderate);
+    let on_blacklist_change = sending::<Vec<String>>(&props.send, MsgTypes::Blacklist);
+    let on_restrictions_change = sending::<Restrictions>(&props.send, MsgTypes::Restrictions);
+    let on_room_update = sending::<RoomUpdate>(&props.send, MsgTypes::RoomUpdate);
+    let on_room_roles = sending::<RoomRoles>(&props.send, MsgTypes::RoomRoles);
+    let on_arrange_pins = can_pin.then(|| sending::<Vec<String>>(&props.send, MsgTypes::Pins));
+    let on_transfer = props.send.reform(|to: String| WebSocketMessage {
+        data: Some(to),
+        ..WebSocketMessage::new(MsgTypes::Transfer)
+    });
+    let on_room_password = props
+        .send
+        .reform(|password: Option<String>| WebSocketMessage {
+            data: password,
+            ..WebSocketMessage::new(MsgTypes::RoomPassword)
+        });
+    let on_regenerate_invite = props
+        .send
+        .reform(|_| WebSocketMessage::new(MsgTypes::RegenerateInvite));
+    let on_delete_room = {
+        let send = props.send.clone();
+        let room = store.room.clone();
+        let show_room_settings = show_room_settings.clone();
+        Callback::from(move |_| {
+            send.emit(WebSocketMessage {
+                data: Some(room.clone()),
+                ..WebSocketMessage::new(MsgTypes::DeleteRoom)
+            });
+            show_room_settings.set(false);
+        })
+    };
+    let can_relay = props.can_relay;
+    let hide_system = props.hide_system;
+    let toggle_system = props.on_toggle_system.reform(|_| ());
+
+    html! {
+        <>
+            {
+                if *show_directory {
+                    html! {
+                        <RoomDirectory
+                            rooms={store.listed_rooms.clone()}
+                            current={store.room.clone()}
+                            on_join={join_listed}
+                            on_close={close_directory}
+                        />
+                    }
+                } else {
+                    html! {}
+                }
+            }
+            {
+                if *show_share {
+                    html! { <ShareDialog
+                            room={store.room.clone()}
+                            icon={store.room_icon.clone()}
+                            invite_only={store.invite_only}
+                            invite={store.invite.clone()}
+                            on_close={close_share}
+                        /> }
+                } else {
+                    html! {}
+                }
+            }
+            {
+                if *show_room_settings && owns_room {
+                    html! {
+                        <RoomSettings
+                            room={store.room.clone()}
+                            icon={store.room_icon.clone()}
+                            topic={store.topic.clone()}
+                            invite_only={store.invite_only}
+                            password_protected={store.password_protected}
+                            invite={store.invite.clone()}
+                            on_change={on_room_update}
+                            on_regenerate={on_regenerate_invite}
+                            on_password={on_room_password}
+                            roles={store.room_roles.clone()}
+                            {on_room_roles}
+                            members={store.users.iter().map(|u| u.name.clone()).filter(|name| *name != current_username).collect::<Vec<_>>()}
+                            {on_transfer}
+                            on_delete={on_delete_room}
+                            on_close={close_room_settings}
+                        />
+                    }
+                } else {
+                    html! {}
+                }
+            }
+            {
+                if *show_saved {
+                    html! {
+                        <SavedMessages
+                            bookmarks={store.bookmarks.clone()}
+                            now={props.now}
+                            on_remove={remove_bookmark}
+                            on_close={close_saved}
+                        />
+                    }
+                } else {
+                    html! {}
+                }
+            }
+            {
+                if *show_calls {
+                    html! {
+                        <CallHistory
+                            calls={props.call_log.clone()}
+                            now={props.now}
+                            can_call={props.can_call}
+                            on_message={message_caller}
+                            on_call={call_back}
+                            on_close={close_calls}
+                        />
+                    }
+                } else {
+                    html! {}
+                }
+            }
+            <div class="w-full h-16 border-b border-gray-200 bg-white shadow-sm flex items-center px-4">
+                { for props.children.iter() }
+                <div class="ml-auto"></div>
+                <button
+                    onclick={open_directory}
+                    title="Browse the rooms anyone can enter"
+                    class="flex items-center gap-2 px-3 py-2 rounded-full text-sm text-gray-600 hover:bg-gray-100 transition-colors"
+                >
+                    <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                        <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M4 6h16M4 10h16M4 14h16M4 18h16" />
+                    </svg>
+                    {"Rooms"}
+                </button>
+                {
+                    if store.conversation.is_none() {
+                        html! {
+                            <button
+                                onclick={open_share}
+                                title="Invite people to this room"
+                                class="flex items-center gap-2 px-3 py-2 rounded-full text-sm text-gray-600 hover:bg-gray-100 transition-colors"
+                            >
+                                <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M12 4v1m6 11h2m-6 0h-2v4m0-11v3m0 0h.01M12 12h4.01M16 20h4M4 12h4m12 0h.01M5 8h2a1 1 0 001-1V5a1 1 0 00-1-1H5a1 1 0 00-1 1v2a1 1 0 001 1zm12 0h2a1 1 0 001-1V5a1 1 0 00-1-1h-2a1 1 0 00-1 1v2a1 1 0 001 1zM5 20h2a1 1 0 001-1v-2a1 1 0 00-1-1H5a1 1 0 00-1 1v2a1 1 0 001 1z" />
+                                </svg>
+                                {"Share"}
+                            </button>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                {
+                    if can_relay && moderates_room {
+                        let failing = store.relay.as_ref().is_some_and(|r| r.error.is_some());
+                        html! {
+                            <button
+                                onclick={toggle_relay}
+                                title="Mirror this room to Discord or Slack"
+                                class="relative flex items-center gap-2 px-3 py-2 rounded-full text-sm text-gray-600 hover:bg-gray-100 transition-colors"
+                            >
+                                <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M8 7h12m0 0l-4-4m4 4l-4 4m0 6H4m0 0l4 4m-4-4l4-4" />
+                                </svg>
+                                {"Mirror"}
+                                {
+                                    if failing {
+                                        html! { <span class="absolute top-1 right-1 w-2 h-2 rounded-full bg-red-500"></span> }
+                                    } else {
+                                        html! {}
+                                    }
+                                }
+                            </button>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                {
+                    if owns_room {
+                        html! {
+                            <button
+                                onclick={open_room_settings}
+                                title="Rename this room or change its icon"
+                                class="flex items-center gap-2 px-3 py-2 rounded-full text-sm text-gray-600 hover:bg-gray-100 transition-colors"
+                            >
+                                <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M10.325 4.317c.426-1.756 2.924-1.756 3.35 0a1.724 1.724 0 002.573 1.066c1.543-.94 3.31.826 2.37 2.37a1.724 1.724 0 001.065 2.572c1.756.426 1.756 2.924 0 3.35a1.724 1.724 0 00-1.066 2.573c.94 1.543-.826 3.31-2.37 2.37a1.724 1.724 0 00-2.572 1.065c-.426 1.756-2.924 1.756-3.35 0a1.724 1.724 0 00-2.573-1.066c-1.543.94-3.31-.826-2.37-2.37a1.724 1.724 0 00-1.065-2.572c-1.756-.426-1.756-2.924 0-3.35a1.724 1.724 0 001.066-2.573c-.94-1.543.826-3.31 2.37-2.37.996.608 2.296.07 2.572-1.065z" />
+                                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M15 12a3 3 0 11-6 0 3 3 0 016 0z" />
+                                </svg>
+                                {"Room"}
+                            </button>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                {
+                    if owns_room {
+                        html! {
+                            <button
+                                onclick={toggle_emoji}
+                                title="Manage this room's custom emoji"
+                                class="flex items-center gap-2 px-3 py-2 rounded-full text-sm text-gray-600 hover:bg-gray-100 transition-colors"
+                            >
+                                <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M14.828 14.828a4 4 0 01-5.656 0M9 10h.01M15 10h.01M21 12a9 9 0 11-18 0 9 9 0 0118 0z" />
+                                </svg>
+                                {"Emoji"}
+                            </button>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                {
+                    if store.conversation.is_none() && (can_pin || !store.pins.is_empty()) {
+                        html! {
+                            <button
+                                onclick={toggle_pins}
+                                title="Pinned messages"
+                                class="flex items-center gap-2 px-3 py-2 rounded-full text-sm text-gray-600 hover:bg-gray-100 transition-colors"
+                            >
+                                {"📌 Pinned"}
+                                {
+                                    if store.pins.is_empty() {
+                                        html! {}
+                                    } else {
+                                        html! {
+                                            <span class="px-1.5 rounded-full bg-amber-500 text-xs text-white">
+                                                {store.pins.len()}
+                                            </span>
+                                        }
+                                    }
+                                }
+                            </button>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                {
+                    if moderates_room {
+                        html! {
+                            <button
+                                onclick={toggle_reports}
+                                title="Reported messages and what the room allows"
+                                class="flex items-center gap-2 px-3 py-2 rounded-full text-sm text-gray-600 hover:bg-gray-100 transition-colors"
+                            >
+                                <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M3 21v-4m0 0V5a2 2 0 012-2h6.5l1 1H21l-3 6 3 6h-8.5l-1-1H5a2 2 0 00-2 2zm9-13.5V9" />
+                                </svg>
+                                {"Reports"}
+                                {
+                                    if store.reports.is_empty() {
+                                        html! {}
+                                    } else {
+                                        html! {
+                                            <span class="px-1.5 rounded-full bg-red-600 text-xs text-white">
+                                                {store.reports.len()}
+                                            </span>
+                                        }
+                                    }
+                                }
+                            </button>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                <button
+                    onclick={open_saved}
+                    title="Saved messages"
+                    class="flex items-center gap-2 px-3 py-2 rounded-full text-sm text-gray-600 hover:bg-gray-100 transition-colors"
+                >
+                    <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                        <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M5 5a2 2 0 012-2h10a2 2 0 012 2v16l-7-3.5L5 21V5z" />
+                    </svg>
+                    {"Saved"}
+                </button>
+                <button
+                    onclick={open_calls}
+                    title={if missed_calls > 0 { format!("Calls — {} missed", missed_calls) } else { "Calls".to_string() }}
+                    class="relative flex items-center gap-2 px-3 py-2 rounded-full text-sm text-gray-600 hover:bg-gray-100 transition-colors"
+                >
+                    <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                        <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M3 5a2 2 0 012-2h3.28a1 1 0 01.948.684l1.498 4.493a1 1 0 01-.502 1.21l-2.257 1.13a11.042 11.042 0 005.516 5.516l1.13-2.257a1 1 0 011.21-.502l4.493 1.498a1 1 0 01.684.949V19a2 2 0 01-2 2h-1C9.716 21 3 14.284 3 6V5z" />
+                    </svg>
+                    {"Calls"}
+                    {
+                        if missed_calls > 0 {
+                            html! {
+                                <span class="absolute -top-1 -right-1 min-w-[1.25rem] h-5 px-1 rounded-full bg-red-500 text-white text-xs flex items-center justify-center">
+                                    {missed_calls}
+                                </span>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                </button>
+                {
+                    if store.admin {
+                        html! {
+                            <Link<Route> to={Route::Admin} classes="flex items-center gap-2 px-3 py-2 rounded-full text-sm text-gray-600 hover:bg-gray-100 transition-colors">
+                                <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M9 19v-6a2 2 0 00-2-2H5a2 2 0 00-2 2v6a2 2 0 002 2h2a2 2 0 002-2zm0 0V9a2 2 0 012-2h2a2 2 0 012 2v10m-6 0a2 2 0 002 2h2a2 2 0 002-2m0 0V5a2 2 0 012-2h2a2 2 0 012 2v14a2 2 0 01-2 2h-2a2 2 0 01-2-2z" />
+                                </svg>
+                                {"Admin"}
+                            </Link<Route>>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                {
+                    if store.conversation.is_none() {
+                        html! {
+                            <>
+                                <button
+                                    onclick={toggle_system}
+                                    title={if hide_system { "Show joins, leaves and other system lines" } else { "Hide joins, leaves and other system lines" }}
+                                    aria-pressed={(!hide_system).to_string()}
+                                    class={classes!(
+                                        "px-3", "py-2", "rounded-full", "text-sm", "hover:bg-gray-100", "transition-colors",
+                                        if hide_system { "text-gray-400 line-through" } else { "text-gray-600" }
+                                    )}
+                                >
+                                    {"System"}
+                                </button>
+                                <div class="relative">
+                                    <button
+                                        onclick={toggle_stats}
+                                        title="Room statistics"
+                                        class="flex items-center gap-2 px-3 py-2 rounded-full text-sm text-gray-600 hover:bg-gray-100 transition-colors"
+                                    >
+                                        <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                            <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M7 12l3-3 3 3 4-4M8 21l4-4 4 4M3 4h18M4 4h16v12a1 1 0 01-1 1H5a1 1 0 01-1-1V4z" />
+                                        </svg>
+                                        {"Stats"}
+                                    </button>
+                                    {
+                                        if *stats_open {
+                                            html! { <RoomStats room={store.room.clone()} messages={store.messages.iter().filter(|m| !m.system).cloned().collect::<Vec<_>>()} now={props.now} /> }
+                                        } else {
+                                            html! {}
+                                        }
+                                    }
+                                </div>
+                            </>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                <div class="relative">
+                    <button
+                        onclick={toggle_export_menu}
+                        title="Export this conversation"
+                        class="flex items-center gap-2 px-3 py-2 rounded-full text-sm text-gray-600 hover:bg-gray-100 transition-colors"
+                    >
+                        <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                            <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M4 16v1a3 3 0 003 3h10a3 3 0 003-3v-1m-4-4l-4 4m0 0l-4-4m4 4V4" />
+                        </svg>
+                        {"Export"}
+                    </button>
+                    {
+                        if *export_menu {
+                            html! {
+                                <div class="absolute right-0 mt-1 w-40 bg-white rounded-lg shadow-lg border border-gray-200 overflow-hidden z-20">
+                                    {
+                                        ExportFormat::ALL.into_iter().map(|format| {
+                                            let onclick = {
+                                                let store = store.clone();
+                                                let export_menu = export_menu.clone();
+                                                Callback::from(move |_| {
+                                                    export_menu.set(false);
+                                                    if let Err(e) = export::download(&store.transcript(), format) {
+                                                        log::error!("export failed: {}", e);
+                                                    }
+                                                })
+                                            };
+                                            html! {
+                                                <button {onclick} class="block w-full text-left px-4 py-2 text-sm hover:bg-blue-50">
+                                                    {format.label()}
+                                                </button>
+                                            }
+                                        }).collect::<Html>()
+                                    }
+                                    <div class="border-t border-gray-200"></div>
+                                    <button onclick={open_print_view} class="block w-full text-left px-4 py-2 text-sm hover:bg-blue-50">
+                                        {"Print view..."}
+                                    </button>
+                                    <Link<Route> to={Route::Archive} classes="block w-full px-4 py-2 text-sm hover:bg-blue-50">
+                                        {"Open transcript..."}
+                                    </Link<Route>>
+                                </div>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                </div>
+                <button
+                    onclick={props.on_logout.clone()}
+                    title="Log out"
+                    class="flex items-center gap-2 px-3 py-2 rounded-full text-sm text-gray-600 hover:bg-gray-100 transition-colors"
+                >
+                    <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                        <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M17 16l4-4m0 0l-4-4m4 4H7m6 4v1a3 3 0 01-3 3H6a3 3 0 01-3-3V7a3 3 0 013-3h4a3 3 0 013 3v1" />
+                    </svg>
+                    {"Log out"}
+                </button>
+            </div>
+            {
+                if *show_relay && can_relay && moderates_room {
+                    html! {
+                        <RelaySettings
+                            room={store.room.clone()}
+                            status={store.relay.clone()}
+                            on_change={on_relay_change}
+                        />
+                    }
+                } else {
+                    html! {}
+                }
+            }
+            {
+                if *show_pins && store.conversation.is_none() {
+                    html! {
+                        <PinnedMessages
+                            room={store.room.clone()}
+                            pins={store.pins.clone()}
+                            emoji={store.emoji.clone()}
+                            on_arrange={on_arrange_pins}
+                        />
+                    }
+                } else {
+                    html! {}
+                }
+            }
+            {
+                if *show_reports && moderates_room {
+                    html! {
+                        <>
+                            <ModerationQueue
+                                room={store.room.clone()}
+                                reports={store.reports.clone()}
+                                emoji={store.emoji.clone()}
+                                {on_moderate}
+                            />
+                            <BlacklistSettings
+                                words={store.blacklist.clone()}
+                                on_change={on_blacklist_change}
+                            />
+                            <RestrictionSettings
+                                restrictions={store.restrictions}
+                                on_change={on_restrictions_change}
+                            />
+                        </>
+                    }
+                } else {
+                    html! {}
+                }
+            }
+            {
+                if *show_emoji && owns_room {
+                    html! {
+                        <EmojiSettings
+                            room={store.room.clone()}
+                            emoji={store.emoji.clone()}
+                            on_change={on_emoji_change}
+                        />
+                    }
+                } else {
+                    html! {}
+                }
+            }
+        </>
+    }
+}
@@ -0,0 +1,211 @@
+use std::rc::Rc;
+
+use wasm_bindgen_futures::spawn_local;
+use web_sys::Element;
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::components::message_list::MessageList;
+use crate::components::toast::{ToastAction, Toasts};
+use crate::components::welcome::Welcome;
+use crate::protocol::{BotAction, HistoryQuery, MessageData, MsgTypes, WebSocketMessage};
+use crate::services::api::ApiClient;
+use crate::services::message_cache;
+use crate::services::spam::SpamSettings;
+use crate::store::{Action, Store};
+use crate::User;
+
+#[derive(Properties, PartialEq)]
+pub struct ChatHistoryProps {
+    pub room: String,
+    /// Sends what is done to messages, like reporting or pinning them, to the server.
+    pub send: Callback<WebSocketMessage>,
+    /// Told what came of it, where there is nothing else to see.
+    pub toasts: UseReducerDispatcher<Toasts>,
+    /// Reference point for relative timestamps.
+    pub now: f64,
+    /// Whether joins, leaves and other system lines are left out.
+    #[prop_or_default]
+    pub hide_system: bool,
+    /// Words that highlight others' messages, along with our name.
+    pub keywords: Rc<Vec<String>>,
+    /// When others' messages are folded away as possible spam.
+    pub spam: SpamSettings,
+}
+
+/// The messages of the open conversation under the room's welcome. Reaching the top of a room
+/// loads what came before, from this browser's cache and then from our server.
+#[function_component(ChatHistory)]
+pub fn chat_history(props: &ChatHistoryProps) -> Html {
+    let user = use_context::<User>().expect("No context found.");
+    let store = use_context::<Store>().expect("No store found.");
+    let current_username = user.username.borrow().clone();
+    let loading_earlier = use_state(|| false);
+
+    // Made once: a new callback on every render would make every message bubble render again.
+    let on_bot_action = {
+        let send = props.send.clone();
+        use_state(move || {
+            Callback::from(move |action: BotAction| {
+                send.emit(WebSocketMessage {
+                    data: Some(serde_json::to_string(&action).unwrap()),
+                    ..WebSocketMessage::new(MsgTypes::BotAction)
+                })
+            })
+        })
+    };
+    // Made once, like `on_bot_action`.
+    let on_report = {
+        let send = props.send.clone();
+        let toasts = props.toasts.clone();
+        use_state(move || {
+            Callback::from(move |message: Rc<MessageData>| {
+                send.emit(WebSocketMessage {
+                    data: message.id.clone(),
+                    ..WebSocketMessage::new(MsgTypes::Report)
+                });
+                toasts.dispatch(ToastAction::Show {
+                    title: "Message reported".into(),
+                    detail: format!(
+                        "The room's moderators will look at what {} said.",
+                        message.from
+                    ),
+                });
+            })
+        })
+    };
+    let on_bookmark = {
+        let store = store.clone();
+        Callback::from(move |message| store.dispatch(Action::ToggleBookmark(message)))
+    };
+    let can_pin = store.conversation.is_none() && store.permissions(&current_username).pin;
+    // Pins a message, or unpins it by arranging the pins without it.
+    let on_pin = can_pin.then(|| {
+        let send = props.send.clone();
+        let pins = store.pins.clone();
+        Callback::from(move |message: Rc<MessageData>| {
+            let pinned = pins.iter().any(|p| p.message.id == message.id);
+            send.emit(if pinned {
+                let ids: Vec<_> = pins
+                    .iter()
+                    .filter_map(|p| p.message.id.clone())
+                    .filter(|id| message.id.as_ref() != Some(id))
+                    .collect();
+                WebSocketMessage {
+                    data: Some(serde_json::to_string(&ids).unwrap()),
+                    ..WebSocketMessage::new(MsgTypes::Pins)
+                }
+            } else {
+                WebSocketMessage {
+                    data: message.id.clone(),
+                    ..WebSocketMessage::new(MsgTypes::Pin)
+                }
+            });
+        })
+    });
+
+    let load_earlier = {
+        let store = store.clone();
+        let loading_earlier = loading_earlier.clone();
+        Callback::from(move |_: ()| {
+            if *loading_earlier {
+                return;
+            }
+            if store.evicted > 0 {
+                let store = store.clone();
+                let room = store.room.clone();
+                let loading_earlier = loading_earlier.clone();
+                loading_earlier.set(true);
+                spawn_local(async move {
+                    let messages = message_cache::restore(&room, message_cache::PAGE)
+                        .await
+                        .unwrap_or_else(|e| {
+                            log::error!("failed to restore earlier messages: {}", e);
+                            vec![]
+                        });
+                    store.dispatch(Action::Restored { room, messages });
+                    loading_earlier.set(false);
+                });
+                return;
+            }
+            let before = store.first_kept().and_then(|m| m.id.clone());
+            let (Some(api), Some(before)) = (ApiClient::of(&user), before) else {
+                return;
+            };
+            let store = store.clone();
+            let room = store.room.clone();
+            let loading_earlier = loading_earlier.clone();
+            loading_earlier.set(true);
+            spawn_local(async move {
+                let query = HistoryQuery {
+                    before: Some(before),
+                    limit: None,
+                };
+                match api.history(&room, &query).await {
+                    Ok(page) => store.dispatch(Action::Earlier { room, page }),
+                    Err(e) => log::error!("api: failed to load earlier messages: {}", e),
+                }
+                loading_earlier.set(false);
+            });
+        })
+    };
+
+    // Reaching the top loads what is above it, as if the thread were all there.
+    let onscroll = {
+        let load_earlier = load_earlier.clone();
+        let more = store.conversation.is_none() && store.has_earlier();
+        Callback::from(move |e: Event| {
+            let container: Element = e.target_unchecked_into();
+            if more && container.scroll_top() == 0 {
+                load_earlier.emit(());
+            }
+        })
+    };
+
+    html! {
+        <div {onscroll} class="w-full flex-grow overflow-auto p-4 bg-gradient-to-b from-blue-50 to-gray-50">
+            {
+                match (&store.welcome, &store.conversation) {
+                    (Some(text), None) => html! {
+                        <Welcome text={text.clone()} username={current_username.clone()} emoji={store.emoji.clone()} />
+                    },
+                    _ => html! {},
+                }
+            }
+            {
+                if store.conversation.is_none() && store.has_earlier() {
+                    html! {
+                        <div class="flex justify-center mb-4">
+                            <button
+                                onclick={load_earlier.reform(|_| ())}
+                                disabled={*loading_earlier}
+                                class="px-3 py-1 rounded-full text-sm text-blue-700 bg-blue-100 hover:bg-blue-200 disabled:opacity-50 transition-colors"
+                            >
+                                { if *loading_earlier { "Loading..." } else { "Load earlier messages" } }
+                            </button>
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }
+            }
+            <MessageList
+                messages={store.visible_messages().iter().filter(|m| !(props.hide_system && m.system)).cloned().collect::<Vec<_>>()}
+                users={store.users.clone()}
+                known={store.known.clone()}
+                emoji={store.emoji.clone()}
+                current_username={current_username.clone()}
+                now={props.now}
+                on_bot_action={(*on_bot_action).clone()}
+                room={store.conversation.is_none().then(|| props.room.clone())}
+                bookmarks={store.bookmarks.clone()}
+                on_bookmark={on_bookmark}
+                keywords={props.keywords.clone()}
+                spam={props.spam}
+                on_report={(*on_report).clone()}
+                pins={store.pins.clone()}
+                {on_pin}
+            />
+        </div>
+    }
+}
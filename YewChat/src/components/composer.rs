@@ -0,0 +1,745 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use gloo_timers::callback::Interval;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{DataTransfer, File, HtmlInputElement, HtmlTextAreaElement};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::components::emoji_picker::EmojiPicker;
+use crate::protocol::Restrictions;
+use crate::sanitize;
+use crate::services::giphy::{self, Gif};
+use crate::services::location::{self, LIVE_MINUTES};
+use crate::store::{Store, UserProfile};
+use crate::{time, User};
+
+const MAX_MENTION_SUGGESTIONS: usize = 8;
+
+/// How many sent messages of each room can be recalled with the arrow keys.
+const MAX_RECALL: usize = 50;
+
+thread_local! {
+    /// What we sent in each room or direct conversation this session, latest last. Only kept in
+    /// memory, as direct messages are meant to exist in clear nowhere else.
+    static SENT: RefCell<HashMap<String, Vec<String>>> = RefCell::default();
+    /// When we last posted a message in each room, for its slow mode.
+    static POSTED: RefCell<HashMap<String, f64>> = RefCell::default();
+}
+
+fn remember_sent(thread: &str, text: &str) {
+    SENT.with(|sent| {
+        let mut sent = sent.borrow_mut();
+        let history = sent.entry(thread.to_string()).or_default();
+        if history.last().map(String::as_str) != Some(text) {
+            history.push(text.to_string());
+        }
+        let excess = history.len().saturating_sub(MAX_RECALL);
+        history.drain(..excess);
+    });
+}
+
+/// The message sent in `thread` `back` messages before the latest one.
+fn sent_before(thread: &str, back: usize) -> Option<String> {
+    SENT.with(|sent| {
+        let sent = sent.borrow();
+        let history = sent.get(thread)?;
+        history.iter().rev().nth(back).cloned()
+    })
+}
+
+/// Whole seconds still to wait before posting in a room with a slow mode of `slow_mode` seconds,
+/// having last posted at `posted`.
+fn seconds_left(posted: Option<f64>, slow_mode: u32, now: f64) -> u32 {
+    let Some(posted) = posted else {
+        return 0;
+    };
+    let left = posted + f64::from(slow_mode) * 1000.0 - now;
+    (left / 1000.0).ceil().max(0.0) as u32
+}
+
+/// Whole seconds still to wait before posting in `room`, under its slow mode.
+fn slow_mode_wait(room: &str, slow_mode: Option<u32>) -> u32 {
+    let posted = POSTED.with(|posted| posted.borrow().get(room).copied());
+    slow_mode.map_or(0, |seconds| seconds_left(posted, seconds, time::now()))
+}
+
+/// The composer grows with its text up to about six lines, and scrolls beyond that.
+const MAX_HEIGHT_PX: i32 = 160;
+
+/// An `@mention` being typed, spanning from the `@` up to the caret.
+#[derive(Clone)]
+struct Mention {
+    start: usize,
+    end: usize,
+    query: String,
+    selected: usize,
+}
+
+/// A `/giphy` search, offered to choose from instead of being sent.
+#[derive(Clone, PartialEq)]
+struct GifSearch {
+    query: String,
+    /// `None` while searching.
+    found: Option<Result<Vec<Gif>, String>>,
+}
+
+/// Finds the `@word` directly before the caret, if any.
+///
+/// `caret` is a UTF-16 offset as reported by the DOM; the returned offsets are byte offsets into `value`.
+fn find_mention(value: &str, caret: usize) -> Option<(usize, usize, String)> {
+    let mut units = 0;
+    let end = value
+        .char_indices()
+        .find(|(_, c)| {
+            let reached = units >= caret;
+            units += c.len_utf16();
+            reached
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(value.len());
+
+    let before = &value[..end];
+    let start = before.rfind('@')?;
+    let query = &before[start + 1..];
+    let at_word_start = before[..start]
+        .chars()
+        .next_back()
+        .is_none_or(char::is_whitespace);
+    if !at_word_start || query.chars().any(char::is_whitespace) {
+        return None;
+    }
+    Some((start, end, query.to_string()))
+}
+
+/// Replaces the mention being typed with `name` and puts the caret after it.
+fn insert_mention(input: &HtmlTextAreaElement, mention: &Mention, name: &str) {
+    let value = input.value();
+    let inserted = format!("@{} ", name);
+    let caret = value[..mention.start].encode_utf16().count() + inserted.encode_utf16().count();
+    input.set_value(&format!(
+        "{}{}{}",
+        &value[..mention.start],
+        inserted,
+        &value[mention.end..]
+    ));
+    let _ = input.focus();
+    let _ = input.set_selection_range(caret as u32, caret as u32);
+    fit_to_content(input);
+}
+
+/// Puts `text` where the caret is, over any selection, and the caret after it.
+fn insert_at_caret(input: &HtmlTextAreaElement, text: &str) {
+    let value: Vec<u16> = input.value().encode_utf16().collect();
+    let offset = |at: Result<Option<u32>, _>| at.ok().flatten().map_or(value.len(), |a| a as usize);
+    let start = offset(input.selection_start()).min(value.len());
+    let end = offset(input.selection_end()).clamp(start, value.len());
+    let inserted: Vec<u16> = text.encode_utf16().collect();
+    let caret = start + inserted.len();
+    input.set_value(&String::from_utf16_lossy(
+        &[&value[..start], &inserted, &value[end..]].concat(),
+    ));
+    let _ = input.focus();
+    let _ = input.set_selection_range(caret as u32, caret as u32);
+    fit_to_content(input);
+}
+
+/// Sizes `textarea` to its text, up to [`MAX_HEIGHT_PX`].
+fn fit_to_content(textarea: &HtmlTextAreaElement) {
+    // Shrunk first, so that deleted lines give their room back.
+    let _ = textarea.set_attribute("style", "height: auto");
+    let needed = textarea.scroll_height();
+    let overflow = if needed > MAX_HEIGHT_PX {
+        "auto"
+    } else {
+        "hidden"
+    };
+    let _ = textarea.set_attribute(
+        "style",
+        &format!(
+            "height: {}px; overflow-y: {}",
+            needed.min(MAX_HEIGHT_PX),
+            overflow
+        ),
+    );
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ComposerProps {
+    pub placeholder: String,
+    #[prop_or_default]
+    pub disabled: bool,
+    pub on_submit: Callback<String>,
+    /// An image pasted from the clipboard or a file picked with the paperclip, to be sent as an
+    /// attachment.
+    pub on_file: Callback<File>,
+    /// Shares where we are live for so many minutes, once the message saying so is sent. Only
+    /// offered where it is given.
+    #[prop_or_default]
+    pub on_share_live: Option<Callback<u32>>,
+}
+
+/// The message input, with `@mention` completion from the online users. It grows as lines are
+/// added, which are sent along as they are. `/giphy cats` offers GIFs to pick from instead, and
+/// the smiley opens a picker of emoji, the room's own first, and the pin sends where we are, once
+/// or live. In a room in slow mode, the send button counts down until the next message may go, and
+/// what the room doesn't allow is refused with a note before it is sent.
+#[function_component(Composer)]
+pub fn composer(props: &ComposerProps) -> Html {
+    let store = use_context::<Store>().expect("No store found.");
+    let user = use_context::<User>().expect("No context found.");
+    let current_username = user.username.borrow().clone();
+    let input = use_node_ref();
+    let mention = use_state(|| Option::<Mention>::None);
+    // How far back the message recalled with the arrow keys is, while it is left as it was.
+    let recalled = use_state(|| Option::<usize>::None);
+    let gifs = use_state(|| Option::<GifSearch>::None);
+    // Why the last thing we tried to send wasn't, until the text is changed.
+    let refused = use_state_eq(|| Option::<&'static str>::None);
+    let picker_open = use_state(|| false);
+    let location_open = use_state(|| false);
+    let thread = match &store.conversation {
+        Some(peer) => format!("@{}", peer),
+        None => store.room.clone(),
+    };
+    // Direct messages are never slowed down.
+    let slow_mode = store.slow_mode.filter(|_| store.conversation.is_none());
+    // Nor restricted.
+    let restrictions = match store.conversation {
+        Some(_) => Restrictions::default(),
+        None => store.restrictions,
+    };
+    // GIFs are images, linked to.
+    let gifs_refused = restrictions.links || restrictions.images || restrictions.attachments;
+    let wait = use_state_eq(|| slow_mode_wait(&store.room, slow_mode));
+
+    {
+        let wait = wait.clone();
+        let room = store.room.clone();
+        use_effect_with_deps(
+            move |_| {
+                wait.set(slow_mode_wait(&room, slow_mode));
+                let tick = Interval::new(1000, move || wait.set(slow_mode_wait(&room, slow_mode)));
+                move || drop(tick)
+            },
+            (store.room.clone(), slow_mode),
+        );
+    }
+
+    let candidates: Vec<UserProfile> = match &*mention {
+        Some(m) => store
+            .users_matching(&m.query, &current_username)
+            .take(MAX_MENTION_SUGGESTIONS)
+            .cloned()
+            .collect(),
+        None => vec![],
+    };
+
+    let submit = {
+        let input = input.clone();
+        let mention = mention.clone();
+        let on_submit = props.on_submit.clone();
+        let recalled = recalled.clone();
+        let gifs = gifs.clone();
+        let thread = thread.clone();
+        let room = store.room.clone();
+        let wait = wait.clone();
+        let refused = refused.clone();
+        Callback::from(move |_: ()| {
+            if let Some(input) = input.cast::<HtmlTextAreaElement>() {
+                // The command stays in the box, to be refined until a GIF is picked.
+                if let Some(query) = giphy::command(&input.value()) {
+                    if gifs_refused {
+                        refused.set(Some("GIFs aren't allowed in this room."));
+                        return;
+                    }
+                    let query = query.to_string();
+                    gifs.set(Some(GifSearch {
+                        query: query.clone(),
+                        found: None,
+                    }));
+                    let gifs = gifs.clone();
+                    spawn_local(async move {
+                        let found = giphy::search(&query).await;
+                        gifs.set(Some(GifSearch {
+                            query,
+                            found: Some(found),
+                        }));
+                    });
+                    mention.set(None);
+                    return;
+                }
+                let text = input.value();
+                // Commands aren't messages, so slow mode doesn't hold them back.
+                let posting = slow_mode.is_some() && !text.trim_start().starts_with('/');
+                if posting && slow_mode_wait(&room, slow_mode) > 0 {
+                    return;
+                }
+                // Left in the box, to be fixed.
+                if let Some(reason) = restrictions.refuse_text(&text) {
+                    refused.set(Some(reason));
+                    return;
+                }
+                if !text.trim().is_empty() {
+                    if posting {
+                        POSTED.with(|posted| posted.borrow_mut().insert(room.clone(), time::now()));
+                        wait.set(slow_mode_wait(&room, slow_mode));
+                    }
+                    remember_sent(&thread, &text);
+                    on_submit.emit(text);
+                }
+                input.set_value("");
+                fit_to_content(&input);
+            }
+            mention.set(None);
+            recalled.set(None);
+            gifs.set(None);
+        })
+    };
+
+    let oninput = {
+        let input = input.clone();
+        let mention = mention.clone();
+        let recalled = recalled.clone();
+        let refused = refused.clone();
+        Callback::from(move |_| {
+            recalled.set(None);
+            refused.set(None);
+            let Some(input) = input.cast::<HtmlTextAreaElement>() else {
+                return;
+            };
+            fit_to_content(&input);
+            let caret = input.selection_start().ok().flatten().unwrap_or_default() as usize;
+            mention.set(
+                find_mention(&input.value(), caret).map(|(start, end, query)| Mention {
+                    start,
+                    end,
+                    query,
+                    selected: 0,
+                }),
+            );
+        })
+    };
+
+    // Enter sends and Shift+Enter starts a new line, unless a mention is being picked. In an empty
+    // composer, Up and Down go through what was sent before.
+    let onkeydown = {
+        let input = input.clone();
+        let mention = mention.clone();
+        let recalled = recalled.clone();
+        let gifs = gifs.clone();
+        let submit = submit.clone();
+        let names: Vec<String> = candidates.iter().map(|u| u.name.clone()).collect();
+        Callback::from(move |e: KeyboardEvent| {
+            let picking = (*mention).clone().filter(|_| !names.is_empty());
+            let Some(current) = picking else {
+                let key = e.key();
+                if key == "Enter" && !e.shift_key() && !e.is_composing() {
+                    // Enter also confirms what an input method is composing; that isn't ours.
+                    e.prevent_default();
+                    submit.emit(());
+                } else if key == "ArrowUp" || key == "ArrowDown" {
+                    let Some(element) = input.cast::<HtmlTextAreaElement>() else {
+                        return;
+                    };
+                    let browsing = match *recalled {
+                        Some(back) => sent_before(&thread, back) == Some(element.value()),
+                        None => element.value().is_empty(),
+                    };
+                    if !browsing {
+                        return;
+                    }
+                    let back = match (*recalled, key.as_str()) {
+                        (None, "ArrowUp") => Some(0),
+                        (Some(back), "ArrowUp") => Some(back + 1),
+                        (Some(back), _) => back.checked_sub(1),
+                        (None, _) => return,
+                    };
+                    let text = match back {
+                        Some(back) => match sent_before(&thread, back) {
+                            Some(text) => text,
+                            // Already at the oldest.
+                            None => return,
+                        },
+                        None => String::new(),
+                    };
+                    e.prevent_default();
+                    element.set_value(&text);
+                    fit_to_content(&element);
+                    recalled.set(back);
+                } else if key == "Escape" && gifs.is_some() {
+                    gifs.set(None);
+                }
+                return;
+            };
+            match e.key().as_str() {
+                "ArrowDown" => mention.set(Some(Mention {
+                    selected: (current.selected + 1) % names.len(),
+                    ..current
+                })),
+                "ArrowUp" => mention.set(Some(Mention {
+                    selected: (current.selected + names.len() - 1) % names.len(),
+                    ..current
+                })),
+                "Enter" | "Tab" => {
+                    if let Some(element) = input.cast::<HtmlTextAreaElement>() {
+                        let name = &names[current.selected.min(names.len() - 1)];
+                        insert_mention(&element, &current, name);
+                    }
+                    mention.set(None);
+                }
+                "Escape" => mention.set(None),
+                _ => return,
+            }
+            e.prevent_default();
+        })
+    };
+
+    let onpaste = {
+        let on_file = props.on_file.clone();
+        let refused = refused.clone();
+        Callback::from(move |e: Event| {
+            // `ClipboardEvent` is still an unstable API in web-sys, so read the property directly.
+            let image = js_sys::Reflect::get(&e, &"clipboardData".into())
+                .ok()
+                .and_then(|dt| dt.dyn_into::<DataTransfer>().ok())
+                .and_then(|dt| dt.files())
+                .and_then(|files| {
+                    (0..files.length())
+                        .filter_map(|i| files.get(i))
+                        .find(|f| f.type_().starts_with("image/"))
+                });
+            if let Some(image) = image {
+                e.prevent_default();
+                match restrictions.refuse_file(&image.type_()) {
+                    Some(reason) => refused.set(Some(reason)),
+                    None => on_file.emit(image),
+                }
+            }
+        })
+    };
+
+    let selected = mention.as_ref().map_or(0, |m| m.selected);
+    let dropdown = if candidates.is_empty() {
+        html! {}
+    } else {
+        html! {
+            <div class="absolute bottom-full left-4 mb-2 w-64 bg-white rounded-lg shadow-lg border border-gray-200 overflow-hidden z-10">
+                {
+                    candidates.iter().enumerate().map(|(i, u)| {
+                        let onmousedown = {
+                            let input = input.clone();
+                            let mention = mention.clone();
+                            let name = u.name.clone();
+                            Callback::from(move |e: MouseEvent| {
+                                // Keep focus in the composer so the caret position survives the click.
+                                e.prevent_default();
+                                if let (Some(element), Some(current)) = (input.cast::<HtmlTextAreaElement>(), &*mention) {
+                                    insert_mention(&element, current, &name);
+                                }
+                                mention.set(None);
+                            })
+                        };
+                        html! {
+                            <div {onmousedown} class={classes!(
+                                "flex", "items-center", "gap-2", "px-3", "py-2", "cursor-pointer", "hover:bg-blue-50",
+                                if i == selected { vec!["bg-blue-100"] } else { vec![] }
+                            )}>
+                                <img class="w-6 h-6 rounded-full" src={u.avatar.clone()} alt="avatar"/>
+                                <span class="text-sm">{u.name.clone()}</span>
+                            </div>
+                        }
+                    }).collect::<Html>()
+                }
+            </div>
+        }
+    };
+
+    let gif_strip = match &*gifs {
+        Some(search) => {
+            let pick = |gif: &Gif| {
+                let input = input.clone();
+                let gifs = gifs.clone();
+                let on_submit = props.on_submit.clone();
+                let url = gif.url.clone();
+                Callback::from(move |_: MouseEvent| {
+                    on_submit.emit(url.clone());
+                    if let Some(input) = input.cast::<HtmlTextAreaElement>() {
+                        input.set_value("");
+                        fit_to_content(&input);
+                    }
+                    gifs.set(None);
+                })
+            };
+            let close = {
+                let gifs = gifs.clone();
+                Callback::from(move |_: MouseEvent| gifs.set(None))
+            };
+            html! {
+                <div class="absolute bottom-full inset-x-4 mb-2 p-2 bg-white rounded-lg shadow-lg border border-gray-200 z-10">
+                    <div class="flex items-center gap-2 mb-2 text-xs text-gray-500">
+                        <span class="grow truncate">{format!("GIFs for \u{201c}{}\u{201d}", search.query)}</span>
+                        <span>{"Powered by GIPHY"}</span>
+                        <button onclick={close} class="px-1 rounded hover:bg-gray-100">{"×"}</button>
+                    </div>
+                    {
+                        match &search.found {
+                            None => html! { <div class="p-2 text-sm text-gray-500">{"Searching…"}</div> },
+                            Some(Err(reason)) => html! { <div class="p-2 text-sm text-red-600">{reason.clone()}</div> },
+                            Some(Ok(found)) if found.is_empty() => html! {
+                                <div class="p-2 text-sm text-gray-500">{"No GIFs found"}</div>
+                            },
+                            Some(Ok(found)) => html! {
+                                <div class="flex gap-2 overflow-x-auto">
+                                    {
+                                        found.iter().filter_map(|gif| {
+                                            let preview = sanitize::image(&gif.preview)?;
+                                            Some(html! {
+                                                <button onclick={pick(gif)} title={gif.title.clone()} class="shrink-0 rounded overflow-hidden focus:ring-2 focus:ring-blue-500">
+                                                    <img class="h-24" src={preview} alt={gif.title.clone()} loading="lazy"/>
+                                                </button>
+                                            })
+                                        }).collect::<Html>()
+                                    }
+                                </div>
+                            },
+                        }
+                    }
+                </div>
+            }
+        }
+        None => html! {},
+    };
+
+    let toggle_picker = {
+        let picker_open = picker_open.clone();
+        Callback::from(move |_: MouseEvent| picker_open.set(!*picker_open))
+    };
+    let file_input = use_node_ref();
+    let pick_file = {
+        let file_input = file_input.clone();
+        Callback::from(move |_: MouseEvent| {
+            if let Some(input) = file_input.cast::<HtmlInputElement>() {
+                input.click();
+            }
+        })
+    };
+    let onchange = {
+        let on_file = props.on_file.clone();
+        let refused = refused.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let file = input.files().and_then(|files| files.get(0));
+            // So that picking the same file again is a change too.
+            input.set_value("");
+            if let Some(file) = file {
+                match restrictions.refuse_file(&file.type_()) {
+                    Some(reason) => refused.set(Some(reason)),
+                    None => on_file.emit(file),
+                }
+            }
+        })
+    };
+    let toggle_location = {
+        let location_open = location_open.clone();
+        Callback::from(move |_: MouseEvent| location_open.set(!*location_open))
+    };
+    // A location is a link, and takes its turn in slow mode like any message.
+    let location_refused = restrictions.links || *wait > 0;
+    let location_title = if restrictions.links {
+        "Links aren't allowed in this room"
+    } else {
+        "Share your location"
+    };
+    let share_location = |live: Option<u32>| {
+        let on_submit = props.on_submit.clone();
+        let on_share_live = props.on_share_live.clone();
+        let refused = refused.clone();
+        let location_open = location_open.clone();
+        let room = store.room.clone();
+        Callback::from(move |_: MouseEvent| {
+            location_open.set(false);
+            let on_submit = on_submit.clone();
+            let on_share_live = on_share_live.clone();
+            let refused = refused.clone();
+            let room = room.clone();
+            spawn_local(async move {
+                match location::current().await {
+                    Ok(position) => {
+                        if slow_mode.is_some() {
+                            POSTED.with(|posted| posted.borrow_mut().insert(room, time::now()));
+                        }
+                        on_submit.emit(position.message(live));
+                        if let (Some(minutes), Some(on_share_live)) = (live, on_share_live) {
+                            on_share_live.emit(minutes);
+                        }
+                    }
+                    Err(reason) => refused.set(Some(reason)),
+                }
+            });
+        })
+    };
+    let location_menu = if *location_open {
+        let live_choices = props.on_share_live.is_some().then(|| {
+            LIVE_MINUTES
+                .iter()
+                .map(|&minutes| {
+                    let label = match minutes {
+                        60 => "1 hour".to_string(),
+                        m if m % 60 == 0 => format!("{} hours", m / 60),
+                        m => format!("{} minutes", m),
+                    };
+                    html! {
+                        <button onclick={share_location(Some(minutes))} class="block w-full text-left px-3 py-2 text-sm hover:bg-gray-100">
+                            {format!("Share live for {}", label)}
+                        </button>
+                    }
+                })
+                .collect::<Html>()
+        });
+        html! {
+            <div class="absolute bottom-full left-4 mb-2 w-64 bg-white rounded-lg shadow-lg border border-gray-200 overflow-hidden z-10">
+                <button onclick={share_location(None)} class="block w-full text-left px-3 py-2 text-sm hover:bg-gray-100">
+                    {"Send my current location"}
+                </button>
+                { live_choices.unwrap_or_default() }
+            </div>
+        }
+    } else {
+        html! {}
+    };
+    let attach_title = if restrictions.attachments {
+        "Attachments aren't allowed in this room"
+    } else {
+        "Attach a file"
+    };
+
+    let picker = if *picker_open {
+        let on_pick = {
+            let input = input.clone();
+            let picker_open = picker_open.clone();
+            Callback::from(move |text: String| {
+                if let Some(input) = input.cast::<HtmlTextAreaElement>() {
+                    insert_at_caret(&input, &text);
+                }
+                picker_open.set(false);
+            })
+        };
+        html! { <EmojiPicker custom={store.emoji.clone()} {on_pick} /> }
+    } else {
+        html! {}
+    };
+
+    html! {
+        <div class="relative w-full p-4 border-t border-gray-200 bg-white flex items-end gap-2">
+            { gif_strip }
+            { dropdown }
+            { picker }
+            { location_menu }
+            if let Some(reason) = *refused {
+                <div class="absolute bottom-full left-4 mb-2 px-3 py-1 rounded-lg bg-red-50 border border-red-200 text-sm text-red-700">
+                    { reason }
+                </div>
+            }
+            <button
+                onclick={toggle_picker}
+                disabled={props.disabled}
+                title="Emoji"
+                class="p-3 rounded-full text-gray-500 hover:bg-gray-100 transition-colors focus:outline-none focus:ring-2 focus:ring-blue-500"
+            >
+                <svg xmlns="http://www.w3.org/2000/svg" class="w-6 h-6" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M14.828 14.828a4 4 0 01-5.656 0M9 10h.01M15 10h.01M21 12a9 9 0 11-18 0 9 9 0 0118 0z" />
+                </svg>
+            </button>
+            <input ref={file_input} type="file" class="hidden" {onchange} />
+            <button
+                onclick={pick_file}
+                disabled={props.disabled || restrictions.attachments}
+                title={attach_title}
+                class="p-3 rounded-full text-gray-500 hover:bg-gray-100 transition-colors focus:outline-none focus:ring-2 focus:ring-blue-500 disabled:opacity-40 disabled:cursor-not-allowed"
+            >
+                <svg xmlns="http://www.w3.org/2000/svg" class="w-6 h-6" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M15.172 7l-6.586 6.586a2 2 0 102.828 2.828l6.414-6.586a4 4 0 00-5.656-5.656l-6.415 6.585a6 6 0 108.486 8.486L20.5 13" />
+                </svg>
+            </button>
+            <button
+                onclick={toggle_location}
+                disabled={props.disabled || location_refused}
+                title={location_title}
+                class="p-3 rounded-full text-gray-500 hover:bg-gray-100 transition-colors focus:outline-none focus:ring-2 focus:ring-blue-500 disabled:opacity-40 disabled:cursor-not-allowed"
+            >
+                <svg xmlns="http://www.w3.org/2000/svg" class="w-6 h-6" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M17.657 16.657L13.414 20.9a2 2 0 01-2.827 0l-4.244-4.243a8 8 0 1111.314 0z" />
+                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M15 11a3 3 0 11-6 0 3 3 0 016 0z" />
+                </svg>
+            </button>
+            <textarea
+                ref={input}
+                {oninput}
+                {onkeydown}
+                {onpaste}
+                rows="1"
+                placeholder={props.placeholder.clone()}
+                disabled={props.disabled}
+                class="block w-full py-3 px-4 bg-gray-100 rounded-3xl resize-none outline-none focus:ring-2 focus:ring-blue-500 focus:bg-white transition-all"
+                name="message"
+                required=true
+            />
+            if *wait > 0 {
+                <button
+                    disabled=true
+                    title={format!("Slow mode: you can send again in {}s", *wait)}
+                    class="w-12 h-12 shrink-0 bg-gray-300 rounded-full flex justify-center items-center text-gray-700 font-semibold tabular-nums cursor-not-allowed"
+                >
+                    { *wait }
+                </button>
+            } else {
+                <button
+                    onclick={submit.reform(|_| ())}
+                    class="p-3 bg-blue-600 rounded-full flex justify-center items-center text-white hover:bg-blue-700 transition-colors focus:outline-none focus:ring-2 focus:ring-blue-500"
+                >
+                    <svg viewBox="0 0 24 24" xmlns="http://www.w3.org/2000/svg" class="w-6 h-6 fill-white">
+                        <path d="M0 0h24v24H0z" fill="none"></path><path d="M2.01 21L23 12 2.01 3 2 10l15 2-15 2z"></path>
+                    </svg>
+                </button>
+            }
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn sent_messages_are_recalled_per_thread() {
+        remember_sent("general", "one");
+        remember_sent("general", "two");
+        remember_sent("general", "two");
+        remember_sent("@bob", "psst");
+        assert_eq!(sent_before("general", 0).as_deref(), Some("two"));
+        assert_eq!(sent_before("general", 1).as_deref(), Some("one"));
+        assert_eq!(sent_before("general", 2), None);
+        assert_eq!(sent_before("@bob", 0).as_deref(), Some("psst"));
+        assert_eq!(sent_before("random", 0), None);
+
+        for i in 0..MAX_RECALL + 1 {
+            remember_sent("busy", &i.to_string());
+        }
+        assert_eq!(sent_before("busy", MAX_RECALL - 1).as_deref(), Some("1"));
+        assert_eq!(sent_before("busy", MAX_RECALL), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn slow_mode_counts_down_whole_seconds() {
+        assert_eq!(seconds_left(None, 30, 5_000.0), 0);
+        assert_eq!(seconds_left(Some(1_000.0), 30, 1_000.0), 30);
+        assert_eq!(seconds_left(Some(1_000.0), 30, 1_500.0), 30);
+        assert_eq!(seconds_left(Some(1_000.0), 30, 30_001.0), 1);
+        assert_eq!(seconds_left(Some(1_000.0), 30, 31_000.0), 0);
+        assert_eq!(seconds_left(Some(1_000.0), 30, 90_000.0), 0);
+    }
+}
@@ -0,0 +1,72 @@
+use yew::prelude::*;
+
+use crate::store::{Action, Store};
+use crate::User;
+
+#[derive(Properties, PartialEq)]
+pub struct ContactCardProps {
+    pub name: String,
+}
+
+/// Someone passed on in a message: their picture, whether they are in the room, and buttons to
+/// message them or add them to our contacts.
+#[function_component(ContactCard)]
+pub fn contact_card(props: &ContactCardProps) -> Html {
+    let store = use_context::<Store>().expect("No store found.");
+    let user = use_context::<User>().expect("No context found.");
+    let current_username = user.username.borrow().clone();
+    let name = props.name.clone();
+    let online = store.users.iter().any(|u| u.name == name);
+    let added = store.contacts.contains(&name);
+
+    let message = {
+        let store = store.clone();
+        let name = name.clone();
+        Callback::from(move |_: MouseEvent| store.dispatch(Action::OpenDirect(name.clone())))
+    };
+    let toggle_contact = {
+        let store = store.clone();
+        let name = name.clone();
+        let owner = current_username.clone();
+        Callback::from(move |_: MouseEvent| {
+            store.dispatch(Action::ToggleContact {
+                owner: owner.clone(),
+                name: name.clone(),
+            })
+        })
+    };
+
+    html! {
+        <div class="mt-1 w-64 max-w-full p-3 rounded-lg border border-gray-200 bg-white text-gray-800 flex flex-col gap-2">
+            <div class="flex items-center gap-3">
+                <img class="w-12 h-12 rounded-full shadow-sm" src={store.avatar_of(&name)} alt="avatar"/>
+                <div class="min-w-0">
+                    <div class="font-medium truncate">{name.clone()}</div>
+                    <div class="text-xs text-gray-500">
+                        { if online { format!("In #{}", store.room) } else { "Contact card".to_string() } }
+                    </div>
+                </div>
+            </div>
+            {
+                if name == current_username {
+                    html! { <div class="text-xs text-gray-500">{"That's you"}</div> }
+                } else {
+                    html! {
+                        <div class="flex gap-2 text-sm">
+                            <button onclick={message} class="flex-1 px-2 py-1 rounded-lg bg-blue-600 text-white hover:bg-blue-700">
+                                {"Message"}
+                            </button>
+                            <button
+                                onclick={toggle_contact}
+                                title={if added { format!("Remove {} from your contacts", name) } else { format!("Keep {} at hand in the sidebar", name) }}
+                                class="flex-1 px-2 py-1 rounded-lg border border-gray-300 hover:bg-gray-100"
+                            >
+                                { if added { "Added ✓" } else { "Add" } }
+                            </button>
+                        </div>
+                    }
+                }
+            }
+        </div>
+    }
+}
@@ -0,0 +1,60 @@
+use web_sys::HtmlInputElement;
+use yew::functional::*;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct DeleteRoomDialogProps {
+    pub room: String,
+    pub on_delete: Callback<()>,
+    pub on_close: Callback<()>,
+}
+
+/// Asks the room's owner to type its name before deleting it, as there is no undoing it.
+#[function_component(DeleteRoomDialog)]
+pub fn delete_room_dialog(props: &DeleteRoomDialogProps) -> Html {
+    let typed = use_state_eq(String::new);
+
+    let oninput = {
+        let typed = typed.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            typed.set(input.value().trim().to_string());
+        })
+    };
+    let confirmed = *typed == props.room;
+    let onsubmit = {
+        let on_delete = props.on_delete.clone();
+        Callback::from(move |e: FocusEvent| {
+            e.prevent_default();
+            if confirmed {
+                on_delete.emit(());
+            }
+        })
+    };
+    let close = props.on_close.reform(|_| ());
+
+    html! {
+        <div class="fixed inset-0 z-40 flex items-center justify-center bg-black/40" onclick={close.clone()}>
+            <form
+                {onsubmit}
+                class="w-80 p-6 rounded-2xl bg-white shadow-xl flex flex-col gap-4 text-sm text-gray-700"
+                onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}
+            >
+                <div class="text-lg font-semibold">{format!("Delete #{}?", props.room)}</div>
+                <div>
+                    {"Its messages, pins, emoji and settings go for good. Everyone in it is taken back to the room they were in before."}
+                </div>
+                <label class="flex flex-col gap-1">
+                    <span>{format!("Type {} to confirm", props.room)}</span>
+                    <input {oninput} value={(*typed).clone()} class="rounded-lg px-3 py-2 border border-gray-200" />
+                </label>
+                <div class="flex items-center justify-end gap-2">
+                    <button type="button" onclick={close} class="px-3 py-2 rounded-lg hover:bg-gray-100">{"Cancel"}</button>
+                    <button type="submit" disabled={!confirmed} class="px-3 py-2 rounded-lg bg-red-600 text-white disabled:opacity-50">
+                        {"Delete"}
+                    </button>
+                </div>
+            </form>
+        </div>
+    }
+}
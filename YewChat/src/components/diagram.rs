@@ -0,0 +1,74 @@
+use wasm_bindgen_futures::spawn_local;
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::diagram;
+
+#[derive(Clone, PartialEq)]
+enum Drawing {
+    Pending,
+    Drawn,
+    Failed(String),
+}
+
+#[derive(Properties, PartialEq)]
+pub struct DiagramProps {
+    pub source: String,
+}
+
+/// A Mermaid diagram from a message, which can be folded away. Its source is shown until it is
+/// drawn, and stays if it can't be.
+#[function_component(Diagram)]
+pub fn diagram_view(props: &DiagramProps) -> Html {
+    // Mermaid's drawing goes in here, so Yew never puts anything in it.
+    let target = use_node_ref();
+    let drawing = use_state(|| Drawing::Pending);
+
+    {
+        let target = target.clone();
+        let drawing = drawing.clone();
+        use_effect_with_deps(
+            move |source: &String| {
+                let source = source.clone();
+                spawn_local(async move {
+                    match diagram::render(&source).await {
+                        Ok(svg) => {
+                            if let Some(element) = target.cast::<web_sys::Element>() {
+                                element.set_inner_html(&svg);
+                                drawing.set(Drawing::Drawn);
+                            }
+                        }
+                        Err(reason) => drawing.set(Drawing::Failed(reason)),
+                    }
+                });
+                || ()
+            },
+            props.source.clone(),
+        );
+    }
+
+    html! {
+        <details open={true} class="my-1">
+            <summary class="cursor-pointer text-xs opacity-75 select-none">{"Diagram"}</summary>
+            <div ref={target} class="mt-1 overflow-x-auto rounded-lg bg-white"></div>
+            {
+                match &*drawing {
+                    Drawing::Drawn => html! {},
+                    Drawing::Pending => view_source(&props.source),
+                    Drawing::Failed(reason) => html! {
+                        <>
+                            <div class="mt-1 text-xs opacity-75">{format!("Couldn't draw this diagram: {}", reason)}</div>
+                            {view_source(&props.source)}
+                        </>
+                    },
+                }
+            }
+        </details>
+    }
+}
+
+fn view_source(source: &str) -> Html {
+    html! {
+        <pre class="mt-1 p-2 rounded-lg bg-black/5 font-mono text-xs overflow-x-auto">{source.to_string()}</pre>
+    }
+}
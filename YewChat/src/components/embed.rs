@@ -0,0 +1,526 @@
+//! Rich previews for links in messages. Each provider recognizes its own URLs in [`PROVIDERS`];
+//! the first link any of them claims becomes the message's embed.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use reqwasm::http::Request;
+use serde::Deserialize;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{DomParser, SupportedType, Url};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::components::external_link::ExternalLink;
+use crate::components::lazy_image::LazyImage;
+use crate::sanitize;
+use crate::services::location::{self, Live, Position};
+use crate::store::Store;
+use crate::time;
+
+/// Where tweets are looked up. It needs no API key, but answers without CORS headers in some
+/// browsers, in which case the tweet stays a plain link.
+const TWEET_OEMBED: &str = "https://publish.twitter.com/oembed";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Embed {
+    /// A picture linked directly, shown instead of the link itself.
+    Image { src: String },
+    /// A video player, loaded only once someone asks to play it.
+    Video {
+        provider: &'static str,
+        /// The player page to put in the iframe.
+        player: String,
+        thumbnail: Option<String>,
+    },
+    /// A post on X (Twitter), previewed as a card once its text has been fetched.
+    Tweet { url: String },
+    /// A place marked on OpenStreetMap, shown on a map.
+    Location(Position),
+}
+
+impl Embed {
+    /// Whether the embed stands in for the text when the message is nothing but its link.
+    pub fn replaces_link(&self) -> bool {
+        matches!(self, Embed::Image { .. })
+    }
+}
+
+/// Claims a link if it is one the provider knows how to embed.
+type Provider = fn(&Url) -> Option<Embed>;
+
+const PROVIDERS: &[Provider] = &[youtube, vimeo, tweet, openstreetmap, image];
+
+/// The embed for the first link in `text` that a provider recognizes.
+pub fn detect(text: &str) -> Option<(String, Embed)> {
+    text.split_whitespace()
+        .filter(|word| word.starts_with("https://") || word.starts_with("http://"))
+        .filter_map(|word| Some((word, Url::new(word).ok()?)))
+        .find_map(|(word, url)| {
+            PROVIDERS
+                .iter()
+                .find_map(|provider| provider(&url))
+                .map(|embed| (word.to_string(), embed))
+        })
+}
+
+fn youtube(url: &Url) -> Option<Embed> {
+    let host = url.hostname();
+    let path = url.pathname();
+    let id = match host.trim_start_matches("www.").trim_start_matches("m.") {
+        "youtu.be" => path.trim_start_matches('/').to_string(),
+        "youtube.com" | "youtube-nocookie.com" => match path.as_str() {
+            "/watch" => url.search_params().get("v")?,
+            _ => ["/shorts/", "/embed/", "/live/"]
+                .iter()
+                .find_map(|prefix| path.strip_prefix(prefix))?
+                .to_string(),
+        },
+        _ => return None,
+    };
+    let valid = id.len() == 11
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return None;
+    }
+    Some(Embed::Video {
+        provider: "YouTube",
+        player: format!("https://www.youtube-nocookie.com/embed/{}?autoplay=1", id),
+        thumbnail: Some(format!("https://i.ytimg.com/vi/{}/hqdefault.jpg", id)),
+    })
+}
+
+fn vimeo(url: &Url) -> Option<Embed> {
+    let path = url.pathname();
+    let id = match url.hostname().trim_start_matches("www.") {
+        "vimeo.com" => path.trim_start_matches('/'),
+        "player.vimeo.com" => path.strip_prefix("/video/")?,
+        _ => return None,
+    };
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(Embed::Video {
+        provider: "Vimeo",
+        player: format!("https://player.vimeo.com/video/{}?autoplay=1", id),
+        // Vimeo only hands out thumbnails through its API.
+        thumbnail: None,
+    })
+}
+
+fn tweet(url: &Url) -> Option<Embed> {
+    let host = url.hostname();
+    let host = host
+        .trim_start_matches("www.")
+        .trim_start_matches("mobile.");
+    if host != "twitter.com" && host != "x.com" {
+        return None;
+    }
+    // `/<user>/status/<id>`, possibly followed by `/photo/1` and the like.
+    let path = url.pathname();
+    let mut parts = path.trim_start_matches('/').split('/');
+    let (user, status, id) = (parts.next()?, parts.next()?, parts.next()?);
+    if user.is_empty()
+        || status != "status"
+        || id.is_empty()
+        || !id.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    // The oEmbed endpoint only knows the twitter.com spelling.
+    Some(Embed::Tweet {
+        url: format!("https://twitter.com/{}/status/{}", user, id),
+    })
+}
+
+fn openstreetmap(url: &Url) -> Option<Embed> {
+    Position::from_link(url).map(Embed::Location)
+}
+
+fn image(url: &Url) -> Option<Embed> {
+    if !url.pathname().ends_with(".gif") {
+        return None;
+    }
+    let src = sanitize::embeddable_image(&url.href())?;
+    Some(Embed::Image { src })
+}
+
+#[derive(Properties, PartialEq)]
+pub struct RichEmbedProps {
+    pub embed: Embed,
+    /// For a location shared live, whose updates move it while it lasts.
+    #[prop_or_default]
+    pub live: Option<Live>,
+}
+
+#[function_component(RichEmbed)]
+pub fn rich_embed(props: &RichEmbedProps) -> Html {
+    match &props.embed {
+        Embed::Image { src } => html! { <LazyImage src={src.clone()} /> },
+        Embed::Video {
+            provider,
+            player,
+            thumbnail,
+        } => html! {
+            <VideoEmbed provider={*provider} player={player.clone()} thumbnail={thumbnail.clone()} />
+        },
+        Embed::Tweet { url } => html! { <TweetEmbed url={url.clone()} /> },
+        Embed::Location(position) => html! {
+            <LocationEmbed position={*position} live={props.live.clone()} />
+        },
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct LocationEmbedProps {
+    position: Position,
+    live: Option<Live>,
+}
+
+/// The place on a small map, pinned in the middle, unless the build doesn't trust OpenStreetMap
+/// with pictures. Shared live, it follows the latest position we heard of until it ends.
+#[function_component(LocationEmbed)]
+fn location_embed(props: &LocationEmbedProps) -> Html {
+    const WIDTH: f64 = 320.0;
+    const HEIGHT: f64 = 180.0;
+    let store = use_context::<Store>().expect("No store found.");
+    let now = time::now();
+    let live = props.live.as_ref().filter(|live| now < live.until);
+    let update = live.and_then(|live| {
+        store
+            .live_locations
+            .get(&live.from)
+            .filter(|(_, heard)| *heard >= live.since)
+    });
+    let position = update.map_or(props.position, |(position, _)| *position);
+    let tiles: Option<Vec<(String, f64, f64)>> = location::tiles(&position, WIDTH, HEIGHT)
+        .into_iter()
+        .map(|(column, row, left, top)| {
+            let url = format!(
+                "https://tile.openstreetmap.org/{}/{}/{}.png",
+                location::ZOOM,
+                column,
+                row
+            );
+            Some((
+                sanitize::embeddable_image(&url)?,
+                left - WIDTH / 2.0,
+                top - HEIGHT / 2.0,
+            ))
+        })
+        .collect();
+    let label = match (&props.live, live, update) {
+        (None, ..) => format!("{:.5}, {:.5}", position.lat, position.lon),
+        (Some(_), None, _) => "Live location ended".to_string(),
+        (Some(_), Some(live), Some((_, heard))) => format!(
+            "Live until {} · updated {}",
+            time::clock_label(live.until),
+            time::relative_label(*heard, now)
+        ),
+        (Some(_), Some(live), None) => format!("Live until {}", time::clock_label(live.until)),
+    };
+
+    html! {
+        <ExternalLink
+            href={position.link()}
+            class="mt-2 block w-80 max-w-full rounded-lg overflow-hidden border border-gray-200 bg-white text-gray-800 hover:bg-gray-50"
+        >
+            {
+                match tiles {
+                    Some(tiles) => html! {
+                        <div class="relative h-[180px] bg-gray-100 overflow-hidden">
+                            {
+                                for tiles.into_iter().map(|(src, dx, dy)| html! {
+                                    <img
+                                        class="absolute max-w-none w-64 h-64"
+                                        style={format!("left: calc(50% + {}px); top: calc(50% + {}px)", dx, dy)}
+                                        src={src}
+                                        alt=""
+                                        loading="lazy"
+                                    />
+                                })
+                            }
+                            <svg xmlns="http://www.w3.org/2000/svg" class="absolute left-1/2 top-1/2 -translate-x-1/2 -translate-y-full h-8 w-8 text-red-600 drop-shadow" viewBox="0 0 24 24" fill="currentColor">
+                                <path fill-rule="evenodd" d="M12 2a7 7 0 00-7 7c0 5.25 7 13 7 13s7-7.75 7-13a7 7 0 00-7-7zm0 9.5a2.5 2.5 0 110-5 2.5 2.5 0 010 5z" clip-rule="evenodd" />
+                            </svg>
+                            <span class="absolute bottom-0 right-0 px-1 bg-white/80 text-[10px] text-gray-600">{"© OpenStreetMap"}</span>
+                        </div>
+                    },
+                    None => html! {},
+                }
+            }
+            <div class="px-3 py-2 text-sm">{"📍 "}{label}</div>
+        </ExternalLink>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct VideoEmbedProps {
+    provider: &'static str,
+    player: String,
+    thumbnail: Option<String>,
+}
+
+/// A thumbnail until clicked, so a busy room doesn't load a player for every link.
+#[function_component(VideoEmbed)]
+fn video_embed(props: &VideoEmbedProps) -> Html {
+    let playing = use_state(|| false);
+
+    if *playing {
+        return html! {
+            <div class="mt-2 w-80 max-w-full aspect-video rounded-lg overflow-hidden shadow-sm bg-black">
+                <iframe
+                    class="w-full h-full"
+                    src={props.player.clone()}
+                    title={format!("{} video", props.provider)}
+                    allow="autoplay; encrypted-media; fullscreen; picture-in-picture"
+                    allowfullscreen=true
+                    frameborder="0"
+                ></iframe>
+            </div>
+        };
+    }
+
+    let onclick = {
+        let playing = playing.clone();
+        Callback::from(move |_| playing.set(true))
+    };
+    html! {
+        <button
+            {onclick}
+            title={format!("Play on {}", props.provider)}
+            class="relative mt-2 w-80 max-w-full aspect-video rounded-lg overflow-hidden shadow-sm bg-gray-900 block group"
+        >
+            {
+                match &props.thumbnail {
+                    Some(src) => html! {
+                        <img class="absolute inset-0 w-full h-full object-cover" src={src.clone()} alt="" loading="lazy"/>
+                    },
+                    None => html! {},
+                }
+            }
+            <span class="absolute inset-0 flex items-center justify-center">
+                <span class="w-14 h-14 rounded-full bg-black/60 group-hover:bg-red-600 flex items-center justify-center transition-colors">
+                    <svg xmlns="http://www.w3.org/2000/svg" class="h-7 w-7 text-white" viewBox="0 0 24 24" fill="currentColor">
+                        <path d="M8 5v14l11-7z" />
+                    </svg>
+                </span>
+            </span>
+            <span class="absolute bottom-2 left-2 text-xs text-white bg-black/60 px-2 py-0.5 rounded">
+                {props.provider}
+            </span>
+        </button>
+    }
+}
+
+/// What the card shows of a tweet.
+#[derive(Clone, Debug, PartialEq)]
+struct Tweet {
+    author: String,
+    /// `@handle`, when the author URL gives it away.
+    handle: Option<String>,
+    text: String,
+    /// As the oEmbed HTML words it, like "March 1, 2021".
+    time: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OEmbed {
+    author_name: String,
+    author_url: String,
+    html: String,
+}
+
+impl OEmbed {
+    /// Pulls the text and date out of the `<blockquote>` Twitter gives us. Parsing it as a
+    /// detached document means none of its scripts or images load.
+    fn into_tweet(self) -> Option<Tweet> {
+        let parser = DomParser::new().ok()?;
+        let doc = parser
+            .parse_from_string(&self.html, SupportedType::TextHtml)
+            .ok()?;
+        let text = doc.query_selector("blockquote p").ok()??.text_content()?;
+        let links = doc.query_selector_all("blockquote > a").ok()?;
+        let time = links
+            .item(links.length().checked_sub(1)?)
+            .and_then(|a| a.text_content());
+        Some(Tweet {
+            handle: self
+                .author_url
+                .rsplit('/')
+                .next()
+                .filter(|h| !h.is_empty())
+                .map(|h| format!("@{}", h)),
+            author: self.author_name,
+            text,
+            time,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum TweetLoad {
+    Loading,
+    Loaded(Tweet),
+    Failed,
+}
+
+thread_local! {
+    /// Tweets already looked up, so scrolling or switching rooms doesn't fetch them again.
+    static TWEETS: RefCell<HashMap<String, TweetLoad>> = RefCell::new(HashMap::new());
+}
+
+async fn fetch_tweet(url: &str) -> Option<Tweet> {
+    let request = format!(
+        "{}?omit_script=true&dnt=true&url={}",
+        TWEET_OEMBED,
+        js_sys::encode_uri_component(url)
+    );
+    let response = Request::get(&request).send().await.ok()?;
+    if !response.ok() {
+        return None;
+    }
+    response.json::<OEmbed>().await.ok()?.into_tweet()
+}
+
+#[derive(Properties, PartialEq)]
+struct TweetEmbedProps {
+    url: String,
+}
+
+/// A compact card with the author, text and date of a tweet, or just its link if it can't be
+/// looked up.
+#[function_component(TweetEmbed)]
+fn tweet_embed(props: &TweetEmbedProps) -> Html {
+    let load = {
+        let url = props.url.clone();
+        use_state(move || {
+            TWEETS
+                .with(|t| t.borrow().get(&url).cloned())
+                .unwrap_or(TweetLoad::Loading)
+        })
+    };
+    {
+        let load = load.clone();
+        use_effect_with_deps(
+            move |url| {
+                if *load == TweetLoad::Loading {
+                    let url = url.clone();
+                    spawn_local(async move {
+                        let result = match fetch_tweet(&url).await {
+                            Some(tweet) => TweetLoad::Loaded(tweet),
+                            None => TweetLoad::Failed,
+                        };
+                        TWEETS.with(|t| t.borrow_mut().insert(url, result.clone()));
+                        load.set(result);
+                    });
+                }
+                || ()
+            },
+            props.url.clone(),
+        );
+    }
+
+    let tweet = match &*load {
+        TweetLoad::Loaded(tweet) => tweet,
+        TweetLoad::Loading => {
+            return html! {
+                <div class="mt-2 w-80 max-w-full h-20 rounded-lg border border-gray-200 bg-gray-50 animate-pulse"></div>
+            }
+        }
+        TweetLoad::Failed => {
+            return html! {
+                <ExternalLink href={props.url.clone()} class="mt-1 block text-sm text-blue-600 underline break-all">
+                    {props.url.clone()}
+                </ExternalLink>
+            }
+        }
+    };
+    html! {
+        <ExternalLink
+            href={props.url.clone()}
+            class="mt-2 block w-80 max-w-full p-3 rounded-lg border border-gray-200 bg-white text-gray-800 hover:bg-gray-50"
+        >
+            <div class="flex items-baseline gap-1 text-sm">
+                <span class="font-semibold">{tweet.author.clone()}</span>
+                {
+                    match &tweet.handle {
+                        Some(handle) => html! { <span class="text-gray-500 truncate">{handle.clone()}</span> },
+                        None => html! {},
+                    }
+                }
+            </div>
+            <div class="mt-1 text-sm whitespace-pre-wrap break-words">{tweet.text.clone()}</div>
+            {
+                match &tweet.time {
+                    Some(time) => html! { <div class="mt-1 text-xs text-gray-500">{time.clone()}</div> },
+                    None => html! {},
+                }
+            }
+        </ExternalLink>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    fn player(text: &str) -> Option<String> {
+        match detect(text)?.1 {
+            Embed::Video { player, .. } => Some(player),
+            _ => None,
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn recognizes_youtube_links() {
+        let expected = Some("https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ?autoplay=1".into());
+        for text in [
+            "look https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42",
+            "https://youtu.be/dQw4w9WgXcQ",
+            "https://m.youtube.com/shorts/dQw4w9WgXcQ",
+        ] {
+            assert_eq!(player(text), expected, "{}", text);
+        }
+        assert_eq!(player("https://www.youtube.com/watch?v=short"), None);
+        assert_eq!(player("https://www.youtube.com/channel/UC123"), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn recognizes_vimeo_links() {
+        assert_eq!(
+            player("https://vimeo.com/76979871"),
+            Some("https://player.vimeo.com/video/76979871?autoplay=1".into())
+        );
+        assert_eq!(player("https://vimeo.com/channels/staffpicks"), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn recognizes_tweets() {
+        let tweet = |text| match detect(text) {
+            Some((_, Embed::Tweet { url })) => Some(url),
+            _ => None,
+        };
+        let expected = Some("https://twitter.com/rustlang/status/1234567890".into());
+        assert_eq!(tweet("https://x.com/rustlang/status/1234567890"), expected);
+        assert_eq!(
+            tweet("https://mobile.twitter.com/rustlang/status/1234567890/photo/1"),
+            expected
+        );
+        assert_eq!(tweet("https://x.com/rustlang"), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn picks_the_first_embeddable_link() {
+        let (link, embed) =
+            detect("see https://example.com and https://example.com/cat.gif").unwrap();
+        assert_eq!(link, "https://example.com/cat.gif");
+        assert!(embed.replaces_link());
+        assert_eq!(detect("no links here"), None);
+        // Loaded without a click, so never over plain http.
+        assert_eq!(detect("http://example.com/cat.gif"), None);
+    }
+}
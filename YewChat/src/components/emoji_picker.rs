@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use yew::prelude::*;
+
+use crate::emoji;
+use crate::sanitize;
+
+#[derive(Properties, PartialEq)]
+pub struct EmojiPickerProps {
+    /// The room's custom emoji, offered first.
+    #[prop_or_default]
+    pub custom: Rc<HashMap<String, String>>,
+    /// Given the text to insert: the emoji itself, or `:name:` for a custom one.
+    pub on_pick: Callback<String>,
+}
+
+#[function_component(EmojiPicker)]
+pub fn emoji_picker(props: &EmojiPickerProps) -> Html {
+    let mut custom: Vec<(&String, String)> = props
+        .custom
+        .iter()
+        .filter_map(|(name, image)| Some((name, sanitize::image(image)?)))
+        .collect();
+    custom.sort();
+
+    // Pressed without taking the focus, so that the caret stays where the emoji goes.
+    let pick = |text: String| {
+        let on_pick = props.on_pick.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            on_pick.emit(text.clone());
+        })
+    };
+    html! {
+        <div class="absolute bottom-full left-4 mb-2 w-72 max-h-64 overflow-y-auto p-2 bg-white rounded-lg shadow-lg border border-gray-200 z-10">
+            {
+                if custom.is_empty() {
+                    html! {}
+                } else {
+                    html! {
+                        <div class="grid grid-cols-8 gap-1 pb-2 mb-2 border-b border-gray-200">
+                            {
+                                custom.into_iter().map(|(name, image)| {
+                                    let code = format!(":{}:", name);
+                                    html! {
+                                        <button onmousedown={pick(code.clone())} title={code.clone()} class="p-1 rounded hover:bg-blue-50">
+                                            <img class="w-6 h-6" src={image} alt={code}/>
+                                        </button>
+                                    }
+                                }).collect::<Html>()
+                            }
+                        </div>
+                    }
+                }
+            }
+            <div class="grid grid-cols-8 gap-1">
+                {
+                    emoji::COMMON.iter().map(|e| html! {
+                        <button onmousedown={pick(e.to_string())} class="p-1 rounded text-xl leading-none hover:bg-blue-50">{*e}</button>
+                    }).collect::<Html>()
+                }
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use web_sys::HtmlInputElement;
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::emoji;
+use crate::protocol::EmojiChange;
+use crate::sanitize;
+use crate::services::attachment::{format_size, AttachmentReader};
+
+#[derive(Properties, PartialEq)]
+pub struct EmojiSettingsProps {
+    pub room: String,
+    pub emoji: Rc<HashMap<String, String>>,
+    pub on_change: Callback<EmojiChange>,
+}
+
+/// The room's custom emoji, for its owner to add to or remove from.
+#[function_component(EmojiSettings)]
+pub fn emoji_settings(props: &EmojiSettingsProps) -> Html {
+    let name = use_state(String::new);
+    let image = use_state(|| Option::<String>::None);
+    let error = use_state(|| Option::<String>::None);
+    let reader = use_mut_ref(|| Option::<AttachmentReader>::None);
+
+    let oninput = {
+        let name = name.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            name.set(input.value().trim().to_lowercase());
+        })
+    };
+    let onchange = {
+        let image = image.clone();
+        let error = error.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            image.set(None);
+            if !file.type_().starts_with("image/") {
+                error.set(Some(format!("{} isn't a picture", file.name())));
+                return;
+            }
+            if file.size() > emoji::MAX_BYTES {
+                error.set(Some(format!(
+                    "{} is too large ({}, limit is {})",
+                    file.name(),
+                    format_size(file.size()),
+                    format_size(emoji::MAX_BYTES)
+                )));
+                return;
+            }
+            error.set(None);
+            let done = {
+                let image = image.clone();
+                let error = error.clone();
+                Callback::from(move |read: Result<_, String>| match read {
+                    Ok(attachment) => image.set(sanitize::attachment(&attachment)),
+                    Err(e) => error.set(Some(e)),
+                })
+            };
+            match AttachmentReader::read(file, Callback::noop(), done) {
+                Ok(started) => *reader.borrow_mut() = Some(started),
+                Err(e) => error.set(Some(e)),
+            }
+        })
+    };
+    let valid = emoji::valid_name(&name);
+    let onsubmit = {
+        let name = name.clone();
+        let image = image.clone();
+        let on_change = props.on_change.clone();
+        Callback::from(move |e: FocusEvent| {
+            e.prevent_default();
+            if let (true, Some(data)) = (valid, &*image) {
+                on_change.emit(EmojiChange {
+                    name: (*name).clone(),
+                    image: Some(data.clone()),
+                });
+                name.set(String::new());
+                image.set(None);
+            }
+        })
+    };
+
+    let mut names: Vec<&String> = props.emoji.keys().collect();
+    names.sort();
+
+    html! {
+        <div class="w-full px-4 py-3 border-b border-gray-200 bg-gray-50 text-sm text-gray-700">
+            <div>{format!("Custom emoji of #{}, used as :name: in messages.", props.room)}</div>
+            <div class="mt-2 flex flex-wrap gap-2">
+                {
+                    names.into_iter().map(|name| {
+                        let remove = {
+                            let on_change = props.on_change.clone();
+                            let name = name.clone();
+                            Callback::from(move |_| on_change.emit(EmojiChange {
+                                name: name.clone(),
+                                image: None,
+                            }))
+                        };
+                        html! {
+                            <span class="flex items-center gap-1 pl-1 pr-2 py-1 rounded-full bg-white border border-gray-200">
+                                <img class="w-6 h-6" src={sanitize::image(&props.emoji[name]).unwrap_or_default()} alt={format!(":{}:", name)}/>
+                                <span>{format!(":{}:", name)}</span>
+                                <button onclick={remove} title="Remove" class="px-1 rounded-full hover:bg-gray-100">{"×"}</button>
+                            </span>
+                        }
+                    }).collect::<Html>()
+                }
+            </div>
+            <form {onsubmit} class="mt-2 flex items-center gap-2">
+                <input
+                    {oninput}
+                    value={(*name).clone()}
+                    maxlength={emoji::NAME_MAX_LEN.to_string()}
+                    class="w-40 rounded-lg px-3 py-2 border border-gray-200 bg-white"
+                    placeholder="name"
+                />
+                <input {onchange} type="file" accept="image/*" class="grow text-xs" />
+                <button type="submit" disabled={!valid || image.is_none()} class="px-3 py-2 rounded-lg bg-blue-600 text-white disabled:opacity-50">
+                    {"Add"}
+                </button>
+            </form>
+            {
+                if let Some(error) = &*error {
+                    html! { <div class="mt-1 text-red-700">{error.clone()}</div> }
+                } else {
+                    html! {}
+                }
+            }
+            <div class="mt-1 text-xs text-gray-500">
+                {format!(
+                    "Names are lowercase letters, digits, _, - or +. Pictures can be up to {}.",
+                    format_size(emoji::MAX_BYTES)
+                )}
+            </div>
+        </div>
+    }
+}
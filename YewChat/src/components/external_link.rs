@@ -0,0 +1,87 @@
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::sanitize;
+
+#[derive(Properties, PartialEq)]
+pub struct ExternalLinkProps {
+    pub href: String,
+    #[prop_or_default]
+    pub class: Classes,
+    #[prop_or_default]
+    pub children: Children,
+}
+
+/// A link out of the chat, opened in a new tab. Unless it goes somewhere [`sanitize::trusted`],
+/// the user is told where it leads and asked first.
+#[function_component(ExternalLink)]
+pub fn external_link(props: &ExternalLinkProps) -> Html {
+    let confirming = use_state(|| false);
+
+    let onclick = (!sanitize::trusted(&props.href)).then(|| {
+        let confirming = confirming.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            confirming.set(true);
+        })
+    });
+    let cancel = {
+        let confirming = confirming.clone();
+        Callback::from(move |_: MouseEvent| confirming.set(false))
+    };
+    let proceed = {
+        let confirming = confirming.clone();
+        let href = props.href.clone();
+        Callback::from(move |_: MouseEvent| {
+            if let Some(window) = web_sys::window() {
+                let _ = window.open_with_url_and_target_and_features(
+                    &href,
+                    "_blank",
+                    "noopener,noreferrer",
+                );
+            }
+            confirming.set(false);
+        })
+    };
+
+    html! {
+        <>
+            <a
+                href={props.href.clone()}
+                target="_blank"
+                rel="noopener noreferrer"
+                class={props.class.clone()}
+                {onclick}
+            >
+                { for props.children.iter() }
+            </a>
+            {
+                if *confirming {
+                    html! {
+                        <div class="fixed inset-0 z-40 flex items-center justify-center bg-black/40" onclick={cancel.clone()}>
+                            <div
+                                class="w-96 max-w-full p-6 rounded-2xl bg-white shadow-xl flex flex-col gap-4 text-gray-800"
+                                onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}
+                            >
+                                <div class="text-lg font-semibold">
+                                    {format!("You're leaving to {}", sanitize::host(&props.href))}
+                                </div>
+                                <div class="text-sm text-gray-600 break-all">{props.href.clone()}</div>
+                                <div class="flex justify-end gap-2">
+                                    <button onclick={cancel} class="px-3 py-2 rounded-lg text-sm text-gray-600 hover:bg-gray-100">
+                                        {"Stay here"}
+                                    </button>
+                                    <button onclick={proceed} class="px-3 py-2 rounded-lg bg-blue-600 text-white text-sm">
+                                        {"Continue"}
+                                    </button>
+                                </div>
+                            </div>
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }
+            }
+        </>
+    }
+}
@@ -0,0 +1,119 @@
+use gloo_timers::callback::Interval;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::KeyboardEvent;
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::services::frame_log::{self, Direction};
+use crate::time;
+
+/// How often the open panel picks up new frames.
+const REFRESH_MS: u32 = 1_000;
+
+#[derive(Properties, PartialEq)]
+pub struct FrameLogProps {
+    pub on_close: Callback<()>,
+}
+
+/// The developer panel: the latest raw frames in both directions, with why any received one
+/// couldn't be read. Opened with Ctrl+Shift+F.
+#[function_component(FrameLog)]
+pub fn frame_log_panel(props: &FrameLogProps) -> Html {
+    let frames = use_state(frame_log::recent);
+
+    {
+        let frames = frames.clone();
+        use_effect_with_deps(
+            move |_| {
+                let refresh = Interval::new(REFRESH_MS, move || frames.set(frame_log::recent()));
+                move || drop(refresh)
+            },
+            (),
+        );
+    }
+
+    let clear = {
+        let frames = frames.clone();
+        Callback::from(move |_| {
+            frame_log::clear();
+            frames.set(vec![]);
+        })
+    };
+    let close = props.on_close.reform(|_| ());
+
+    html! {
+        <div class="absolute inset-x-0 bottom-0 z-30 h-1/2 flex flex-col bg-gray-900 text-gray-100 shadow-2xl">
+            <div class="flex items-center gap-2 px-4 py-2 border-b border-gray-700">
+                <div class="grow text-sm font-semibold">
+                    {format!("Frames · last {} kept", frame_log::CAPACITY)}
+                </div>
+                <button onclick={clear} class="px-2 py-1 rounded text-xs hover:bg-white/10">{"Clear"}</button>
+                <button onclick={close} class="px-2 py-1 rounded text-xs hover:bg-white/10">{"Close"}</button>
+            </div>
+            <div class="grow overflow-auto font-mono text-xs">
+                {
+                    frames.iter().map(|frame| {
+                        let (arrow, color) = match frame.direction {
+                            Direction::Received => ("↓", "text-green-400"),
+                            Direction::Sent => ("↑", "text-blue-400"),
+                        };
+                        let row = if frame.error.is_some() {
+                            "px-4 py-1 border-b border-gray-800 bg-red-900/40"
+                        } else {
+                            "px-4 py-1 border-b border-gray-800"
+                        };
+                        html! {
+                            <div class={row}>
+                                <div class="flex gap-2">
+                                    <span class={color}>{arrow}</span>
+                                    <span class="text-gray-500">{time::clock_label(frame.time)}</span>
+                                    <span class="break-all">{&frame.text}</span>
+                                </div>
+                                {
+                                    if let Some(error) = &frame.error {
+                                        html! { <div class="pl-6 text-red-300">{error.summary()}</div> }
+                                    } else {
+                                        html! {}
+                                    }
+                                }
+                            </div>
+                        }
+                    }).collect::<Html>()
+                }
+            </div>
+        </div>
+    }
+}
+
+/// Whether the frame log is open, which Ctrl+Shift+F toggles wherever the focus is.
+pub fn use_frame_log_shortcut() -> UseStateHandle<bool> {
+    let show_frames = use_state(|| false);
+    // The frame log is for developers, so it hides behind a shortcut rather than a button.
+    {
+        let show_frames = show_frames.clone();
+        use_effect_with_deps(
+            move |_| {
+                let window = web_sys::window().unwrap();
+                let onkeydown = Closure::wrap(Box::new(move |e: KeyboardEvent| {
+                    if e.ctrl_key() && e.shift_key() && e.key().eq_ignore_ascii_case("f") {
+                        e.prevent_default();
+                        show_frames.set(!*show_frames);
+                    }
+                }) as Box<dyn FnMut(KeyboardEvent)>);
+                let _ = window.add_event_listener_with_callback(
+                    "keydown",
+                    onkeydown.as_ref().unchecked_ref(),
+                );
+                move || {
+                    let _ = window.remove_event_listener_with_callback(
+                        "keydown",
+                        onkeydown.as_ref().unchecked_ref(),
+                    );
+                }
+            },
+            (),
+        );
+    }
+    show_frames
+}
@@ -0,0 +1,42 @@
+use std::rc::Rc;
+
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::services::keywords;
+
+#[derive(Properties, PartialEq)]
+pub struct KeywordSettingsProps {
+    pub username: String,
+    pub keywords: Rc<Vec<String>>,
+    /// Called with the saved keywords.
+    pub on_change: Callback<Vec<String>>,
+}
+
+/// The words that highlight a message as if it mentioned us. Saved when the field is left.
+#[function_component(KeywordSettings)]
+pub fn keyword_settings(props: &KeywordSettingsProps) -> Html {
+    let onchange = {
+        let username = props.username.clone();
+        let on_change = props.on_change.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let chosen = keywords::parse(&input.value());
+            input.set_value(&chosen.join(", "));
+            keywords::save(&username, &chosen);
+            on_change.emit(chosen);
+        })
+    };
+
+    html! {
+        <label class="w-full px-4 py-2 border-b border-gray-200 bg-gray-50 text-sm text-gray-700 flex items-center gap-2">
+            <span class="flex-none">{"Highlight messages with"}</span>
+            <input
+                {onchange}
+                value={props.keywords.join(", ")}
+                class="grow rounded-lg px-2 py-1 border border-gray-200 bg-white"
+                placeholder="deploy, your project's name, ..."
+            />
+        </label>
+    }
+}
@@ -0,0 +1,171 @@
+use js_sys::Array;
+use reqwasm::http::{Method, Request};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{Element, IntersectionObserver, IntersectionObserverEntry, IntersectionObserverInit};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::services::attachment::format_size;
+
+/// How far below the fold pictures start loading, so that scrolling doesn't outrun them.
+const PRELOAD_MARGIN: &str = "200px";
+
+#[derive(Properties, PartialEq)]
+pub struct LazyImageProps {
+    pub src: String,
+    #[prop_or_default]
+    pub alt: Option<String>,
+    /// In bytes, when known up front, as for attachments. Otherwise asked of the server.
+    #[prop_or_default]
+    pub size: Option<u64>,
+}
+
+/// A picture that is only loaded once it scrolls into view. Until then a blurred placeholder says
+/// what is coming and how big it is.
+#[function_component(LazyImage)]
+pub fn lazy_image(props: &LazyImageProps) -> Html {
+    let placeholder = use_node_ref();
+    let visible = use_state(|| false);
+    let size = use_state(|| props.size);
+
+    {
+        let placeholder = placeholder.clone();
+        let shown = *visible;
+        let visible = visible.clone();
+        use_effect_with_deps(
+            move |shown| {
+                let observed = (!*shown)
+                    .then(|| placeholder.cast::<Element>())
+                    .flatten()
+                    .and_then(|element| observe(&element, visible.clone()));
+                if !*shown && observed.is_none() {
+                    // Too old a browser to tell; load it right away as we used to.
+                    visible.set(true);
+                }
+                move || drop(observed)
+            },
+            shown,
+        );
+    }
+    {
+        let size = size.clone();
+        use_effect_with_deps(
+            move |src: &String| {
+                if size.is_none() && (src.starts_with("https://") || src.starts_with("http://")) {
+                    let src = src.clone();
+                    spawn_local(async move {
+                        if let Some(length) = content_length(&src).await {
+                            size.set(Some(length));
+                        }
+                    });
+                }
+                || ()
+            },
+            props.src.clone(),
+        );
+    }
+
+    if *visible {
+        return html! {
+            <div class="mt-2 rounded-lg overflow-hidden shadow-sm">
+                <img class="w-full" src={props.src.clone()} alt={props.alt.clone()} loading="lazy"/>
+            </div>
+        };
+    }
+
+    let kind = if is_gif(&props.src, props.alt.as_deref()) {
+        "GIF"
+    } else {
+        "Image"
+    };
+    let label = match *size {
+        Some(bytes) => format!("{} · {}", kind, format_size(bytes as f64)),
+        None => kind.to_string(),
+    };
+    html! {
+        <div
+            ref={placeholder}
+            role="img"
+            aria-label={props.alt.clone()}
+            data-src={props.src.clone()}
+            class="relative mt-2 w-64 max-w-full h-40 rounded-lg overflow-hidden shadow-sm"
+        >
+            <div class="absolute inset-0 bg-gradient-to-br from-gray-300 via-gray-200 to-gray-400 blur-md scale-110 animate-pulse"></div>
+            <span class="absolute bottom-2 left-2 px-2 py-0.5 rounded-full bg-black/50 text-xs text-white">
+                {label}
+            </span>
+        </div>
+    }
+}
+
+/// Watches an element until dropped.
+struct Watch {
+    observer: IntersectionObserver,
+    _onintersect: Closure<dyn FnMut(Array)>,
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.observer.disconnect();
+    }
+}
+
+/// Sets `visible` once `element` comes near the viewport. `None` where the browser can't watch
+/// for that.
+fn observe(element: &Element, visible: UseStateHandle<bool>) -> Option<Watch> {
+    let onintersect = Closure::wrap(Box::new(move |entries: Array| {
+        let entering = entries.iter().any(|entry| {
+            entry
+                .unchecked_into::<IntersectionObserverEntry>()
+                .is_intersecting()
+        });
+        if entering {
+            visible.set(true);
+        }
+    }) as Box<dyn FnMut(Array)>);
+    let mut options = IntersectionObserverInit::new();
+    options.root_margin(PRELOAD_MARGIN);
+    let observer =
+        IntersectionObserver::new_with_options(onintersect.as_ref().unchecked_ref(), &options)
+            .ok()?;
+    observer.observe(element);
+    Some(Watch {
+        observer,
+        _onintersect: onintersect,
+    })
+}
+
+/// The size the server gives for `src`, without downloading it.
+async fn content_length(src: &str) -> Option<u64> {
+    let response = Request::new(src).method(Method::HEAD).send().await.ok()?;
+    response
+        .headers()
+        .get("content-length")
+        .ok()
+        .flatten()?
+        .parse()
+        .ok()
+}
+
+fn is_gif(src: &str, alt: Option<&str>) -> bool {
+    let path = src.split(['?', '#']).next().unwrap_or_default();
+    path.to_lowercase().ends_with(".gif")
+        || src.starts_with("data:image/gif")
+        || alt.is_some_and(|a| a.to_lowercase().ends_with(".gif"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn recognizes_gifs() {
+        assert!(is_gif("https://example.com/cat.GIF?w=200", None));
+        assert!(is_gif("data:image/gif;base64,R0lGOD", None));
+        assert!(is_gif("data:,x", Some("dance.gif")));
+        assert!(!is_gif("https://example.com/cat.png", Some("cat.png")));
+    }
+}
@@ -0,0 +1,119 @@
+use std::rc::Rc;
+
+use gloo_timers::callback::Timeout;
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::components::toast::{ToastAction, Toasts};
+use crate::protocol::{MsgTypes, WebSocketMessage};
+use crate::services::location::{self, Position};
+use crate::services::websocket::WebsocketService;
+use crate::store::{Action, Store};
+use crate::time;
+
+/// Our location, while shared live with the room.
+struct LiveShare {
+    until: f64,
+    _watch: location::Watch,
+    _end: Timeout,
+}
+
+/// Our live location, and what starts and stops sharing it.
+pub struct UseLiveShare {
+    sharing: UseStateHandle<Option<Rc<LiveShare>>>,
+    /// Shares where we are with the room for so many minutes, as it changes.
+    pub start: Callback<u32>,
+    /// Tells the room we stopped sharing, as it is told when the time is up.
+    pub stop: Callback<()>,
+}
+
+/// Shares our location live with the room we are in, under `username`. What keeps it from being
+/// shared is told with a toast.
+pub fn use_live_share(
+    wss: &WebsocketService,
+    username: &str,
+    toasts: UseReducerDispatcher<Toasts>,
+) -> UseLiveShare {
+    let store = use_context::<Store>().expect("No store found.");
+    let live_share = use_state(|| Option::<Rc<LiveShare>>::None);
+    let stop_live_share = {
+        let wss = wss.clone();
+        let store = store.dispatcher();
+        let live_share = live_share.clone();
+        let me = username.to_string();
+        Callback::from(move |_: ()| {
+            wss.send(&WebSocketMessage::new(MsgTypes::Location));
+            store.dispatch(Action::LiveLocation {
+                from: me.clone(),
+                position: None,
+            });
+            live_share.set(None);
+        })
+    };
+    let start = {
+        let wss = wss.clone();
+        let live_share = live_share.clone();
+        let stop_live_share = stop_live_share.clone();
+        let me = username.to_string();
+        Callback::from(move |minutes: u32| {
+            let send = {
+                let wss = wss.clone();
+                let store = store.clone();
+                let me = me.clone();
+                Callback::from(move |position: Position| {
+                    wss.send(&WebSocketMessage {
+                        data: serde_json::to_string(&position).ok(),
+                        ..WebSocketMessage::new(MsgTypes::Location)
+                    });
+                    store.dispatch(Action::LiveLocation {
+                        from: me.clone(),
+                        position: Some(position),
+                    });
+                })
+            };
+            match location::Watch::start(send) {
+                Ok(watch) => {
+                    let stop_live_share = stop_live_share.clone();
+                    live_share.set(Some(Rc::new(LiveShare {
+                        until: time::now() + f64::from(minutes) * 60_000.0,
+                        _watch: watch,
+                        _end: Timeout::new(minutes * 60_000, move || stop_live_share.emit(())),
+                    })));
+                }
+                Err(reason) => toasts.dispatch(ToastAction::Show {
+                    title: "Couldn't share your live location".into(),
+                    detail: reason.into(),
+                }),
+            }
+        })
+    };
+
+    UseLiveShare {
+        sharing: live_share,
+        start,
+        stop: stop_live_share,
+    }
+}
+
+impl UseLiveShare {
+    pub fn is_sharing(&self) -> bool {
+        self.sharing.is_some()
+    }
+
+    /// Until when we share our location with `room`, with a button to stop early.
+    pub fn view(&self, room: &str) -> Html {
+        let Some(share) = &*self.sharing else {
+            return html! {};
+        };
+        html! {
+            <div class="w-full px-4 pt-3 flex items-center gap-2 text-sm text-gray-600">
+                <span class="flex-grow">
+                    {format!("📍 Sharing your live location with #{} until {}", room, time::clock_label(share.until))}
+                </span>
+                <button onclick={self.stop.reform(|_| ())} class="px-2 rounded-full text-red-600 hover:bg-red-50">
+                    {"Stop"}
+                </button>
+            </div>
+        }
+    }
+}
@@ -1,17 +1,79 @@
+use wasm_bindgen_futures::spawn_local;
 use web_sys::HtmlInputElement;
 use yew::functional::*;
 use yew::prelude::*;
 use yew_router::prelude::*;
 
-use crate::Route;
-use crate::User;
+use crate::avatar::{self, AvatarSettings};
+use crate::components::message_list;
+use crate::protocol::{
+    suggest_usernames, validate_username, AuthError, Credentials, USERNAME_MAX_LEN,
+};
+use crate::services::auth::AuthService;
+use crate::services::irc::{self, IrcSession};
+use crate::services::matrix::{self, DEFAULT_HOMESERVER};
+use crate::services::oauth::Provider;
+use crate::services::session::{Remembered, SignInMethod};
+use crate::{ChatQuery, LoginQuery, Route, User, DEFAULT_ROOM};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Login,
+    Signup,
+    /// Signing in to an account on a Matrix homeserver rather than on our server.
+    Matrix,
+    /// Picking a nick on an IRC network, reached through a WebSocket gateway.
+    Irc,
+}
 
 #[function_component(Login)]
 pub fn login() -> Html {
-    let username = use_state(|| String::new());
+    let query = use_location()
+        .and_then(|l| l.query::<LoginQuery>().ok())
+        .unwrap_or_default();
+    // Someone else is using the name they had; another account is what they need.
+    let remembered = use_state(|| Remembered::load().filter(|_| !query.conflict));
+    let remember = use_state(|| remembered.is_some());
+    let username = use_state(|| {
+        remembered
+            .as_ref()
+            .map(|r| r.username.clone())
+            .unwrap_or_default()
+    });
+    let password = use_state(String::new);
+    let email = use_state(String::new);
+    let homeserver = use_state(|| match remembered.as_ref().map(|r| &r.via) {
+        Some(SignInMethod::Matrix { homeserver }) => homeserver.clone(),
+        _ => DEFAULT_HOMESERVER.to_string(),
+    });
+    let gateway = use_state(|| match remembered.as_ref().map(|r| &r.via) {
+        Some(SignInMethod::Irc { gateway }) => gateway.clone(),
+        _ => String::new(),
+    });
+    let mode = use_state(|| match remembered.as_ref().map(|r| &r.via) {
+        _ if query.conflict => Mode::Signup,
+        Some(SignInMethod::Matrix { .. }) => Mode::Matrix,
+        Some(SignInMethod::Irc { .. }) => Mode::Irc,
+        Some(SignInMethod::Account) | None => Mode::Login,
+    });
+    let error = use_state(|| {
+        if query.conflict {
+            Some("That name is connected elsewhere. Log in as someone else or create another account.".to_string())
+        } else {
+            query
+                .expired
+                .then(|| AuthError::InvalidToken.describe().to_string())
+        }
+    });
+    let pending = use_state(|| false);
+    let suggestions = use_state(Vec::<String>::new);
     let user = use_context::<User>().expect("No context found.");
+    let history = use_history().expect("history to be available");
+    let room = query.room.unwrap_or_else(|| DEFAULT_ROOM.into());
+    let invite = query.invite;
+    let message = query.message;
 
-    let oninput = {
+    let oninput_username = {
         let current_username = username.clone();
 
         Callback::from(move |e: InputEvent| {
@@ -20,20 +82,466 @@ pub fn login() -> Html {
         })
     };
 
-    let onclick = {
+    let oninput_password = {
+        let current_password = password.clone();
+
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            current_password.set(input.value());
+        })
+    };
+
+    let oninput_email = {
+        let email = email.clone();
+
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            email.set(input.value());
+        })
+    };
+
+    let oninput_homeserver = {
+        let homeserver = homeserver.clone();
+
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            homeserver.set(input.value());
+        })
+    };
+
+    let oninput_gateway = {
+        let gateway = gateway.clone();
+
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            gateway.set(input.value());
+        })
+    };
+
+    let toggle_remember = {
+        let remember = remember.clone();
+        Callback::from(move |_| remember.set(!*remember))
+    };
+
+    // Back to the empty form, for someone other than who was remembered.
+    let not_me = {
+        let remembered = remembered.clone();
+        let username = username.clone();
+        let mode = mode.clone();
+        let remember = remember.clone();
+        let error = error.clone();
+        Callback::from(move |_| {
+            Remembered::forget();
+            remembered.set(None);
+            username.set(String::new());
+            mode.set(Mode::Login);
+            remember.set(false);
+            error.set(None);
+        })
+    };
+
+    let toggle_mode = {
+        let mode = mode.clone();
+        let error = error.clone();
+        let suggestions = suggestions.clone();
+        Callback::from(move |_| {
+            mode.set(match *mode {
+                Mode::Login => Mode::Signup,
+                Mode::Signup | Mode::Matrix | Mode::Irc => Mode::Login,
+            });
+            error.set(None);
+            suggestions.set(vec![]);
+        })
+    };
+
+    let switch_to = |target: Mode| {
+        let mode = mode.clone();
+        let error = error.clone();
+        let suggestions = suggestions.clone();
+        Callback::from(move |_| {
+            mode.set(target);
+            error.set(None);
+            suggestions.set(vec![]);
+        })
+    };
+
+    let onsubmit = {
+        let homeserver = homeserver.clone();
+        let gateway = gateway.clone();
         let username = username.clone();
-        let user = user.clone();
-        Callback::from(move |_| *user.username.borrow_mut() = (*username).clone())
+        let password = password.clone();
+        let email = email.clone();
+        let mode = mode.clone();
+        let error = error.clone();
+        let pending = pending.clone();
+        let suggestions = suggestions.clone();
+        let remember = remember.clone();
+        Callback::from(move |e: FocusEvent| {
+            e.prevent_default();
+            let via = match *mode {
+                Mode::Login | Mode::Signup => SignInMethod::Account,
+                Mode::Matrix => SignInMethod::Matrix {
+                    homeserver: (*homeserver).clone(),
+                },
+                Mode::Irc => SignInMethod::Irc {
+                    gateway: (*gateway).clone(),
+                },
+            };
+            if *mode == Mode::Irc {
+                // Nothing to check: the network tells us if the nick is taken once we connect.
+                if !username.is_empty() && !gateway.is_empty() {
+                    remember_sign_in(*remember, &username, via);
+                    irc::sign_in(
+                        &user,
+                        IrcSession {
+                            gateway: (*gateway).clone(),
+                            nick: (*username).clone(),
+                        },
+                    );
+                    enter_room(&history, room.clone(), invite.clone(), message.as_deref());
+                }
+                return;
+            }
+            if username.is_empty() || password.is_empty() || *pending {
+                return;
+            }
+            if *mode == Mode::Signup {
+                if let Err(reason) = validate_username(&username) {
+                    error.set(Some(reason.into()));
+                    return;
+                }
+            }
+            error.set(None);
+            suggestions.set(vec![]);
+            pending.set(true);
+
+            let credentials = Credentials::new(&username, &password);
+            let mode = *mode;
+            let homeserver = (*homeserver).clone();
+            let secret = (*password).clone();
+            let user = user.clone();
+            let history = history.clone();
+            let room = room.clone();
+            let invite = invite.clone();
+            let message = message.clone();
+            let error = error.clone();
+            let pending = pending.clone();
+            let suggestions = suggestions.clone();
+            let name = (*username).clone();
+            let email = email.trim().to_string();
+            let remember = *remember;
+            spawn_local(async move {
+                let result = match mode {
+                    Mode::Login => AuthService::login(&credentials).await,
+                    Mode::Signup => AuthService::signup(&credentials).await,
+                    // The homeserver checks the password itself, so it gets the real one.
+                    Mode::Matrix => match matrix::login(&homeserver, &name, &secret).await {
+                        Ok(session) => {
+                            remember_sign_in(remember, &name, via);
+                            matrix::sign_in(&user, session);
+                            enter_room(&history, room, invite, message.as_deref());
+                            return;
+                        }
+                        Err(e) => Err(e),
+                    },
+                    Mode::Irc => unreachable!("IRC has no login round trip"),
+                };
+                match result {
+                    Ok(identity) => {
+                        if !email.is_empty() {
+                            AvatarSettings {
+                                use_gravatar: true,
+                                email,
+                            }
+                            .save(&name);
+                        }
+                        remember_sign_in(remember, &name, via);
+                        AuthService::sign_in(&user, identity);
+                        enter_room(&history, room, invite, message.as_deref());
+                    }
+                    Err(e) => {
+                        if e == AuthError::NameTaken {
+                            let salt = (js_sys::Math::random() * 1e6) as u32;
+                            suggestions.set(suggest_usernames(&name, salt));
+                        }
+                        error.set(Some(e.describe().into()));
+                        pending.set(false);
+                    }
+                }
+            });
+        })
+    };
+
+    let (title, submit_label, toggle_label) = match *mode {
+        Mode::Login => ("Log in", "Go Chatting!", "New here? Create an account"),
+        Mode::Signup => ("Create an account", "Sign up", "Already registered? Log in"),
+        Mode::Matrix => (
+            "Log in with Matrix",
+            "Connect",
+            "Use a YewChat account instead",
+        ),
+        Mode::Irc => ("Join IRC", "Connect", "Use a YewChat account instead"),
+    };
+    let disabled = match *mode {
+        Mode::Irc => username.is_empty() || gateway.is_empty(),
+        _ => username.is_empty() || password.is_empty() || *pending,
+    };
+    // Only nag about the format while creating an account; existing names are whatever they are.
+    let username_hint = match *mode {
+        Mode::Signup if !username.is_empty() => validate_username(&username).err(),
+        _ => None,
+    };
+
+    if let Some(remembered) = &*remembered {
+        return view_remembered(
+            remembered,
+            onsubmit,
+            oninput_password,
+            (*error).clone(),
+            *pending,
+            disabled,
+            not_me,
+        );
+    }
+
+    html! {
+       <div class="bg-gray-800 flex w-screen">
+            <div class="container mx-auto flex flex-col justify-center items-center">
+                <form {onsubmit} class="m-4 flex flex-col w-80 gap-3">
+                    <h1 class="text-2xl font-bold text-white mb-2">{title}</h1>
+                    {
+                        if *mode == Mode::Matrix {
+                            html! {
+                                <input
+                                    oninput={oninput_homeserver}
+                                    value={(*homeserver).clone()}
+                                    type="url"
+                                    class="rounded-lg p-4 border text-gray-800 border-gray-200 bg-white"
+                                    placeholder="Homeserver"
+                                />
+                            }
+                        } else if *mode == Mode::Irc {
+                            html! {
+                                <input
+                                    oninput={oninput_gateway}
+                                    value={(*gateway).clone()}
+                                    type="url"
+                                    class="rounded-lg p-4 border text-gray-800 border-gray-200 bg-white"
+                                    placeholder="Gateway, like wss://irc.example.org/webirc"
+                                />
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    <input
+                        oninput={oninput_username}
+                        value={(*username).clone()}
+                        class={classes!(
+                            "rounded-lg", "p-4", "border", "text-gray-800", "bg-white",
+                            if username_hint.is_some() { "border-red-400" } else { "border-gray-200" }
+                        )}
+                        placeholder={match *mode {
+                            Mode::Matrix => "Matrix username",
+                            Mode::Irc => "Nickname",
+                            _ => "Username",
+                        }}
+                        autocomplete="username"
+                        maxlength={USERNAME_MAX_LEN.to_string()}
+                    />
+                    {
+                        if let Some(hint) = username_hint {
+                            html! { <div class="-mt-2 text-xs text-red-400">{hint}</div> }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    {
+                        if *mode == Mode::Irc {
+                            html! {}
+                        } else {
+                            html! {
+        <input oninput={oninput_password} type="password" class="rounded-lg p-4 border text-gray-800 border-gray-200 bg-white" placeholder="Password" autocomplete={if *mode == Mode::Signup { "new-password" } else { "current-password" }} />
+                            }
+                        }
+                    }
+                    {
+                        if matches!(*mode, Mode::Login | Mode::Signup) {
+                            html! {
+                                <input
+                                    oninput={oninput_email}
+                                    value={(*email).clone()}
+                                    type="email"
+                                    class="rounded-lg p-4 border text-gray-800 border-gray-200 bg-white"
+                                    placeholder="Email for your Gravatar (optional)"
+                                    autocomplete="email"
+                                />
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    {
+                        if let Some(error) = &*error {
+                            html! { <div class="text-sm text-red-400">{error.clone()}</div> }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    {
+                        if suggestions.is_empty() {
+                            html! {}
+                        } else {
+                            html! {
+                                <div class="flex flex-wrap items-center gap-2 text-sm text-gray-300">
+                                    {"Try:"}
+                                    {
+                                        suggestions.iter().map(|s| {
+                                            let username = username.clone();
+                                            let suggestions = suggestions.clone();
+                                            let error = error.clone();
+                                            let name = s.clone();
+                                            let onclick = Callback::from(move |_| {
+                                                username.set(name.clone());
+                                                suggestions.set(vec![]);
+                                                error.set(None);
+                                            });
+                                            html! {
+                                                <button type="button" {onclick} class="px-3 py-1 rounded-full bg-gray-700 hover:bg-gray-600 text-white">
+                                                    {s.clone()}
+                                                </button>
+                                            }
+                                        }).collect::<Html>()
+                                    }
+                                </div>
+                            }
+                        }
+                    }
+                    <label class="flex items-center gap-2 text-sm text-gray-300">
+                        <input type="checkbox" checked={*remember} onchange={toggle_remember} />
+                        {"Remember me"}
+                    </label>
+                    <button type="submit" {disabled} class="rounded-lg bg-violet-600 text-white font-bold p-4 uppercase border-violet-600 disabled:opacity-50" >
+                        { if *pending { "Please wait..." } else { submit_label } }
+                    </button>
+                    <button type="button" onclick={toggle_mode} class="text-sm text-gray-300 hover:text-white">{toggle_label}</button>
+                    {
+                        if matches!(*mode, Mode::Login | Mode::Signup) {
+                            html! {
+                                <>
+                                    <button type="button" onclick={switch_to(Mode::Matrix)} class="text-sm text-gray-300 hover:text-white">{"Have a Matrix account? Log in with it"}</button>
+                                    <button type="button" onclick={switch_to(Mode::Irc)} class="text-sm text-gray-300 hover:text-white">{"Chat on an IRC network instead"}</button>
+                                </>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    {
+                        Provider::configured().into_iter().map(|provider| html! {
+                            <button
+                                type="button"
+                                onclick={Callback::from(move |_| provider.begin())}
+                                class="rounded-lg bg-white text-gray-800 font-medium p-3 hover:bg-gray-100"
+                            >
+                                {format!("Continue with {}", provider.label())}
+                            </button>
+                        }).collect::<Html>()
+                    }
+                    <Link<Route> to={Route::Archive} classes="text-center text-sm text-gray-400 hover:text-white">
+                        {"Read an exported transcript"}
+                    </Link<Route>>
+                </form>
+            </div>
+        </div>
+    }
+}
+
+/// The login form for someone [`Remembered`]: their picture and a button to carry on, after a
+/// password if they need one.
+fn view_remembered(
+    remembered: &Remembered,
+    onsubmit: Callback<FocusEvent>,
+    oninput_password: Callback<InputEvent>,
+    error: Option<String>,
+    pending: bool,
+    disabled: bool,
+    not_me: Callback<MouseEvent>,
+) -> Html {
+    let name = &remembered.username;
+    let picture = avatar::url_for(name, None, AvatarSettings::load(name).gravatar().as_deref());
+    let (place, needs_password) = match &remembered.via {
+        SignInMethod::Account => ("YewChat account".to_string(), true),
+        SignInMethod::Matrix { homeserver } => (format!("Matrix on {}", homeserver), true),
+        SignInMethod::Irc { gateway } => (format!("IRC through {}", gateway), false),
     };
 
     html! {
        <div class="bg-gray-800 flex w-screen">
             <div class="container mx-auto flex flex-col justify-center items-center">
-                <form class="m-4 flex">
-                    <input {oninput} class="rounded-l-lg p-4 border-t mr-0 border-b border-l text-gray-800 border-gray-200 bg-white" placeholder="Username" />
-                    <Link<Route> to={Route::Chat}> <button {onclick} disabled={username.len()<1} class="px-8 rounded-r-lg bg-violet-600	  text-white font-bold p-4 uppercase border-violet-600 border-t border-b border-r" >{"Go Chatting!"}</button></Link<Route>>
+                <form {onsubmit} class="m-4 flex flex-col items-center w-80 gap-3">
+                    <img class="w-20 h-20 rounded-full" src={picture} alt="" />
+                    <h1 class="text-2xl font-bold text-white">{format!("Welcome back, {}", name)}</h1>
+                    <div class="-mt-2 text-sm text-gray-400">{place}</div>
+                    {
+                        if needs_password {
+                            html! {
+                                <input
+                                    oninput={oninput_password}
+                                    type="password"
+                                    class="w-full rounded-lg p-4 border text-gray-800 border-gray-200 bg-white"
+                                    placeholder="Password"
+                                    autocomplete="current-password"
+                                />
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    {
+                        if let Some(error) = error {
+                            html! { <div class="text-sm text-red-400">{error}</div> }
+                        } else {
+                            html! {}
+                        }
+                    }
+                    <button type="submit" {disabled} class="w-full rounded-lg bg-violet-600 text-white font-bold p-4 border-violet-600 disabled:opacity-50">
+                        { if pending { "Please wait...".to_string() } else { format!("Continue as {}", name) } }
+                    </button>
+                    <button type="button" onclick={not_me} class="text-sm text-gray-300 hover:text-white">
+                        {format!("Not {}? Use another account", name)}
+                    </button>
                 </form>
             </div>
         </div>
     }
 }
+
+/// Keeps who signed in for next time, or forgets whoever was kept, as the checkbox says.
+fn remember_sign_in(remember: bool, username: &str, via: SignInMethod) {
+    if remember {
+        Remembered {
+            username: username.to_string(),
+            via,
+        }
+        .save();
+    } else {
+        Remembered::forget();
+    }
+}
+
+/// Opens the room we were sent here from, with the invite code its link had, at the linked message
+/// if there was one.
+fn enter_room(history: &AnyHistory, room: String, invite: Option<String>, message: Option<&str>) {
+    let route = Route::ChatRoom { room };
+    if invite.is_some() {
+        if let Err(e) = history.push_with_query(route, ChatQuery { invite }) {
+            log::error!("failed to open the room: {:?}", e);
+        }
+    } else {
+        history.push(route);
+    }
+    if let Some(id) = message {
+        message_list::link_to(id);
+    }
+}
@@ -0,0 +1,59 @@
+use wasm_bindgen_futures::spawn_local;
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::math;
+
+#[derive(Properties, PartialEq)]
+pub struct MathProps {
+    pub tex: String,
+    #[prop_or_default]
+    pub display: bool,
+}
+
+/// A formula from a message. Until KaTeX has typeset it, or if it can't be loaded, it is shown as
+/// it was written.
+#[function_component(Math)]
+pub fn math_view(props: &MathProps) -> Html {
+    // KaTeX fills this one itself, so Yew never puts anything in it.
+    let target = use_node_ref();
+    let typeset = use_state(|| false);
+
+    {
+        let target = target.clone();
+        let typeset = typeset.clone();
+        use_effect_with_deps(
+            move |(tex, display)| {
+                let (tex, display) = (tex.clone(), *display);
+                spawn_local(async move {
+                    if math::load().await.is_err() {
+                        return;
+                    }
+                    if let Some(element) = target.cast::<web_sys::Element>() {
+                        typeset.set(math::render(&tex, &element, display).is_ok());
+                    }
+                });
+                || ()
+            },
+            (props.tex.clone(), props.display),
+        );
+    }
+
+    let source = if props.display {
+        format!("$${}$$", props.tex)
+    } else {
+        format!("${}$", props.tex)
+    };
+    html! {
+        <span class={classes!(props.display.then_some("block my-1 overflow-x-auto"))}>
+            <span ref={target}></span>
+            {
+                if *typeset {
+                    html! {}
+                } else {
+                    html! { <code class="font-mono text-sm">{source}</code> }
+                }
+            }
+        </span>
+    }
+}
@@ -0,0 +1,1232 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gloo_timers::callback::Timeout;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{ScrollBehavior, ScrollIntoViewOptions, ScrollLogicalPosition};
+use yew::prelude::*;
+
+use crate::avatar;
+use crate::blocks::{self, Align, Block, Table};
+use crate::components::contact_card::ContactCard;
+use crate::components::diagram::Diagram;
+use crate::components::embed::{self, RichEmbed};
+use crate::components::external_link::ExternalLink;
+use crate::components::lazy_image::LazyImage;
+#[cfg(feature = "math")]
+use crate::components::math::Math;
+use crate::components::share_dialog;
+use crate::emoji::{self, Piece};
+use crate::protocol::{BotAction, BotCard, MessageData, PinnedMessage};
+use crate::sanitize;
+use crate::services::attachment::{format_size, Attachment};
+use crate::services::clipboard;
+use crate::services::contacts;
+use crate::services::keywords;
+use crate::services::location;
+use crate::services::spam::{self, SpamSettings};
+use crate::store::{Bookmark, KnownProfile, UserProfile};
+use crate::time;
+
+#[derive(Properties, PartialEq)]
+pub struct MessageListProps {
+    pub messages: Vec<Rc<MessageData>>,
+    /// Where avatars are looked up; senders missing from it get a generated one.
+    #[prop_or_default]
+    pub users: Vec<UserProfile>,
+    /// Looked in for senders who have gone offline.
+    #[prop_or_default]
+    pub known: Rc<HashMap<String, KnownProfile>>,
+    /// The room's custom emoji, by name.
+    #[prop_or_default]
+    pub emoji: Rc<HashMap<String, String>>,
+    pub current_username: String,
+    /// Reference point for relative timestamps.
+    pub now: f64,
+    /// Where presses of bot card buttons go. Without it the buttons are disabled.
+    #[prop_or_default]
+    pub on_bot_action: Option<Callback<BotAction>>,
+    /// The room shown, which messages can be linked in. `None` for direct messages.
+    #[prop_or_default]
+    pub room: Option<String>,
+    /// Saved messages, marked as such in the room.
+    #[prop_or_default]
+    pub bookmarks: Rc<Vec<Bookmark>>,
+    /// Where saving or unsaving a message goes. Offered in the menu of linkable messages when set.
+    #[prop_or_default]
+    pub on_bookmark: Option<Callback<Rc<MessageData>>>,
+    /// Words that highlight others' messages, along with our name.
+    #[prop_or_default]
+    pub keywords: Rc<Vec<String>>,
+    /// When others' messages are folded away as possible spam.
+    #[prop_or_default]
+    pub spam: SpamSettings,
+    /// Where reports of others' messages go. Offered in the menu of linkable messages when set.
+    #[prop_or_default]
+    pub on_report: Option<Callback<Rc<MessageData>>>,
+    /// Messages pinned above the room, marked as such.
+    #[prop_or_default]
+    pub pins: Rc<Vec<PinnedMessage>>,
+    /// Where pinning or unpinning a message goes. Offered in the menu of linkable messages when
+    /// set, which it is for the room's moderators.
+    #[prop_or_default]
+    pub on_pin: Option<Callback<Rc<MessageData>>>,
+}
+
+/// How long a linked message stays highlighted after scrolling to it.
+const HIGHLIGHT_MS: u32 = 2_000;
+
+/// The element id of a message, which is also the fragment of links to it.
+pub fn anchor(id: &str) -> String {
+    format!("msg-{}", id)
+}
+
+/// The message the address points at, if any.
+pub fn linked_message() -> Option<String> {
+    let hash = web_sys::window()?.location().hash().ok()?;
+    hash.strip_prefix("#msg-")
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+}
+
+/// Points the address at a message; the open thread scrolls to it once it has arrived.
+pub fn link_to(id: &str) {
+    if let Some(window) = web_sys::window() {
+        if let Err(e) = window.location().set_hash(&anchor(id)) {
+            log::error!("failed to link to message: {:?}", e);
+        }
+    }
+}
+
+/// The message thread, grouped by sender and day.
+#[function_component(MessageList)]
+pub fn message_list(props: &MessageListProps) -> Html {
+    let messages = &props.messages;
+    let highlighted = use_state(|| None::<String>);
+    // The message named in the address, until we have scrolled to it.
+    let target = use_state(linked_message);
+
+    {
+        let target = target.clone();
+        use_effect_with_deps(
+            move |_| {
+                let window = web_sys::window().unwrap();
+                let onhashchange = Closure::wrap(
+                    Box::new(move || target.set(linked_message())) as Box<dyn FnMut()>
+                );
+                let _ = window.add_event_listener_with_callback(
+                    "hashchange",
+                    onhashchange.as_ref().unchecked_ref(),
+                );
+                move || {
+                    let _ = window.remove_event_listener_with_callback(
+                        "hashchange",
+                        onhashchange.as_ref().unchecked_ref(),
+                    );
+                }
+            },
+            (),
+        );
+    }
+    {
+        let highlighted = highlighted.clone();
+        let target = target.clone();
+        // The history may still be on its way when the page opens.
+        let arrived = (*target)
+            .as_ref()
+            .is_some_and(|id| messages.iter().any(|m| m.id.as_ref() == Some(id)));
+        let deps = ((*target).clone(), arrived);
+        use_effect_with_deps(
+            move |(id, arrived)| {
+                if let (Some(id), true) = (id, *arrived) {
+                    let element = web_sys::window()
+                        .and_then(|w| w.document())
+                        .and_then(|d| d.get_element_by_id(&anchor(id)));
+                    if let Some(element) = element {
+                        element.scroll_into_view_with_scroll_into_view_options(
+                            ScrollIntoViewOptions::new()
+                                .block(ScrollLogicalPosition::Center)
+                                .behavior(ScrollBehavior::Smooth),
+                        );
+                    }
+                    highlighted.set(Some(id.clone()));
+                    target.set(None);
+                    // Outlives this effect, which ends as soon as the target is cleared.
+                    Timeout::new(HIGHLIGHT_MS, move || highlighted.set(None)).forget();
+                }
+                || ()
+            },
+            deps,
+        );
+    }
+
+    let avatar_of = |name: &str| {
+        props
+            .users
+            .iter()
+            .find(|u| u.name == name)
+            .map(|u| u.avatar.clone())
+            .or_else(|| props.known.get(name).map(|k| k.avatar_url(name)))
+            .unwrap_or_else(|| avatar::url_for(name, None, None))
+    };
+
+    let watched: Vec<String> = props
+        .keywords
+        .iter()
+        .cloned()
+        .chain(std::iter::once(props.current_username.clone()))
+        .collect();
+
+    let spam = spam::flag(messages, &props.current_username, &props.spam);
+
+    // Keyed, so that a new message or user list only renders the bubbles whose props changed.
+    messages
+        .iter()
+        .enumerate()
+        .flat_map(|(i, m)| {
+            let day = m.time.map(time::day_of);
+            let prev = i.checked_sub(1).and_then(|p| messages.get(p));
+            let next = messages.get(i + 1);
+            let new_day = day.is_some() && prev.is_none_or(|p| p.time.map(time::day_of) != day);
+            // Consecutive messages from one sender on the same day share a single name and avatar.
+            let starts_group = new_day || prev.is_none_or(|p| !same_sender(p, m));
+            let ends_group =
+                next.is_none_or(|n| !same_sender(n, m) || n.time.map(time::day_of) != day);
+
+            let separator = match m.time {
+                Some(t) if new_day => Some(view_day_separator(&time::day_label(t))),
+                _ => None,
+            };
+            let bubble = html! {
+                <MessageBubble
+                    key={m.id.clone().unwrap_or_else(|| format!("#{}", i))}
+                    message={m.clone()}
+                    avatar={m.bot.as_ref().and_then(|b| b.avatar.as_deref()).and_then(sanitize::image).unwrap_or_else(|| avatar_of(&m.from))}
+                    is_current_user={m.from == props.current_username && m.bot.is_none()}
+                    {starts_group}
+                    {ends_group}
+                    now={props.now}
+                    emoji={props.emoji.clone()}
+                    on_bot_action={props.on_bot_action.clone()}
+                    // Whispers aren't kept, so there is nothing to link to.
+                    link={props.room.as_ref().zip(m.id.as_ref()).filter(|_| m.to.is_none()).map(|(room, id)| {
+                        format!("{}#{}", share_dialog::invite_link(room), anchor(id))
+                    })}
+                    highlighted={m.id.is_some() && *highlighted == m.id}
+                    bookmarked={props.room.as_ref().is_some_and(|room| props.bookmarks.iter().any(|b| b.is_of(room, m)))}
+                    on_bookmark={props.on_bookmark.clone()}
+                    mentions_us={m.from != props.current_username && keywords::matches(&m.message, &watched)}
+                    spam={spam[i]}
+                    on_report={props.on_report.clone()}
+                    pinned={m.id.is_some() && props.pins.iter().any(|p| p.message.id == m.id)}
+                    on_pin={props.on_pin.clone()}
+                />
+            };
+            separator.into_iter().chain(std::iter::once(bubble))
+        })
+        .collect::<Html>()
+}
+
+#[derive(Properties, PartialEq)]
+pub struct MessageBubbleProps {
+    pub message: Rc<MessageData>,
+    pub avatar: String,
+    pub is_current_user: bool,
+    /// First of a run from one sender: shows their name.
+    pub starts_group: bool,
+    /// Last of a run from one sender: shows their avatar.
+    pub ends_group: bool,
+    pub now: f64,
+    #[prop_or_default]
+    pub emoji: Rc<HashMap<String, String>>,
+    #[prop_or_default]
+    pub on_bot_action: Option<Callback<BotAction>>,
+    /// Where the message can be found again. Offered in its menu when set.
+    #[prop_or_default]
+    pub link: Option<String>,
+    /// Drawn with a ring, having just been opened from a link.
+    #[prop_or_default]
+    pub highlighted: bool,
+    #[prop_or_default]
+    pub bookmarked: bool,
+    /// Given the message to save or unsave, from its menu.
+    #[prop_or_default]
+    pub on_bookmark: Option<Callback<Rc<MessageData>>>,
+    /// Mentions our name or one of our keywords.
+    #[prop_or_default]
+    pub mentions_us: bool,
+    /// Why this looks like spam, if it does. Folded until asked for.
+    #[prop_or_default]
+    pub spam: Option<&'static str>,
+    /// Given the message to report to the room's moderators, from its menu.
+    #[prop_or_default]
+    pub on_report: Option<Callback<Rc<MessageData>>>,
+    #[prop_or_default]
+    pub pinned: bool,
+    /// Given the message to pin or unpin, from its menu.
+    #[prop_or_default]
+    pub on_pin: Option<Callback<Rc<MessageData>>>,
+}
+
+#[function_component(MessageBubble)]
+pub fn message_bubble(props: &MessageBubbleProps) -> Html {
+    let m = &props.message;
+    let is_current_user = props.is_current_user;
+    // Our own messages are drawn on blue, except whispers, which all look alike.
+    let on_blue = is_current_user && m.to.is_none();
+    let (starts_group, ends_group) = (props.starts_group, props.ends_group);
+    let bot = m.bot.as_ref();
+    let embed = embed::detect(&m.message);
+    let menu_open = use_state(|| false);
+    let unfolded = use_state(|| false);
+
+    let open_menu = props.link.as_ref().map(|_| {
+        let menu_open = menu_open.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            menu_open.set(true);
+        })
+    });
+    let close_menu = {
+        let menu_open = menu_open.clone();
+        Callback::from(move |_: MouseEvent| menu_open.set(false))
+    };
+    let bookmark = props.on_bookmark.as_ref().map(|on_bookmark| {
+        let message = m.clone();
+        let on_bookmark = on_bookmark.clone();
+        let menu_open = menu_open.clone();
+        let toggle = Callback::from(move |_: MouseEvent| {
+            on_bookmark.emit(message.clone());
+            menu_open.set(false);
+        });
+        (props.bookmarked, toggle)
+    });
+    let pin = props.on_pin.as_ref().map(|on_pin| {
+        let message = m.clone();
+        let on_pin = on_pin.clone();
+        let menu_open = menu_open.clone();
+        let toggle = Callback::from(move |_: MouseEvent| {
+            on_pin.emit(message.clone());
+            menu_open.set(false);
+        });
+        (props.pinned, toggle)
+    });
+    let report = props
+        .on_report
+        .as_ref()
+        .filter(|_| !is_current_user)
+        .map(|on_report| {
+            let message = m.clone();
+            let on_report = on_report.clone();
+            let menu_open = menu_open.clone();
+            Callback::from(move |_: MouseEvent| {
+                on_report.emit(message.clone());
+                menu_open.set(false);
+            })
+        });
+
+    if m.announcement {
+        return view_announcement(m, props.now);
+    }
+    if m.system {
+        return view_system(m);
+    }
+    if let (Some(reason), false) = (props.spam, *unfolded) {
+        let onclick = Callback::from(move |_| unfolded.set(true));
+        return html! {
+            <div class="flex justify-start mb-4 ml-12 text-xs text-gray-500">
+                <button {onclick} class="px-3 py-1 rounded-full border border-dashed border-gray-300 hover:bg-gray-100">
+                    {format!("Possible spam from {} ({}) · show", m.from, reason)}
+                </button>
+            </div>
+        };
+    }
+
+    html! {
+        <div
+            id={m.id.as_deref().map(anchor)}
+            onmouseleave={close_menu.clone()}
+            class={classes!(
+            "group", "flex", "transition-all", "duration-300", "ease-in",
+            if ends_group { "mb-4" } else { "mb-1" },
+            if is_current_user { "justify-end" } else { "justify-start" }
+        )}>
+            {
+                if is_current_user {
+                    html! {}
+                } else if ends_group {
+                    html! {
+                        <img class="w-10 h-10 rounded-full self-end mr-2 shadow-sm" src={props.avatar.clone()} alt="avatar"/>
+                    }
+                } else {
+                    html! { <div class="flex-none w-10 mr-2"></div> }
+                }
+            }
+            <div oncontextmenu={open_menu.clone()} class={classes!(
+                "relative", "rounded-2xl", "max-w-xl", "shadow-sm", "transition-shadow",
+                props.highlighted.then(|| vec!["ring-4", "ring-yellow-300"]),
+                if starts_group { "p-4" } else { "px-4 py-2" },
+                match (is_current_user, bot.is_some()) {
+                    _ if m.to.is_some() => vec!["bg-gray-100", "border", "border-dashed", "border-gray-400"],
+                    (true, _) => vec!["bg-blue-600", "text-white"],
+                    (false, true) => vec!["bg-purple-50", "border", "border-purple-200"],
+                    (false, false) if props.mentions_us => vec!["bg-yellow-50", "border", "border-yellow-300"],
+                    (false, false) => vec!["bg-white"],
+                },
+                match (is_current_user, ends_group) {
+                    (true, true) => Some("rounded-br-none"),
+                    (false, true) => Some("rounded-bl-none"),
+                    (_, false) => None,
+                }
+            )}>
+                {
+                    match (&props.link, open_menu) {
+                        (Some(link), Some(open_menu)) => view_menu(link, *menu_open, open_menu, close_menu, bookmark, pin, report),
+                        _ => html! {},
+                    }
+                }
+                {
+                    if starts_group {
+                        html! {
+                            <div class={classes!(
+                                "font-medium", "mb-1",
+                                if on_blue { vec!["text-blue-100"] } else { vec!["text-gray-800"] }
+                            )}>
+                                {m.from.clone()}
+                                {
+                                    if bot.is_some() {
+                                        html! { <span class="ml-2 text-xs bg-purple-600 text-white px-2 rounded-full">{"BOT"}</span> }
+                                    } else {
+                                        html! {}
+                                    }
+                                }
+                            </div>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                {
+                    match &m.to {
+                        Some(to) => html! {
+                            <div class="mb-1 text-xs italic text-gray-500">{
+                                if is_current_user {
+                                    format!("Whispered to {} · only they can see this", to)
+                                } else {
+                                    "Whispered · only you can see this".to_string()
+                                }
+                            }</div>
+                        },
+                        None => html! {},
+                    }
+                }
+                <div class={classes!(
+                    if on_blue { vec!["text-white"] } else { vec!["text-gray-700"] }
+                )}>
+                    {
+                        match bot.and_then(|b| b.title.clone()) {
+                            Some(title) => html! { <div class="font-semibold text-gray-900 mb-1">{title}</div> },
+                            None => html! {},
+                        }
+                    }
+                    {
+                        if let Some(attachment) = &m.attachment {
+                            view_attachment(attachment)
+                        } else if let Some(name) = contacts::card_of(&m.message) {
+                            html! { <ContactCard name={name.to_string()} /> }
+                        } else {
+                            let text_shown = match &embed {
+                                Some((link, embed)) => !(embed.replaces_link() && m.message.trim() == link),
+                                None => true,
+                            };
+                            html!{
+                                <>
+                                    {
+                                        if text_shown {
+                                            html! {
+                                                <div class="whitespace-pre-wrap break-words">
+                                                    {view_text(&m.message, &props.emoji)}
+                                                </div>
+                                            }
+                                        } else {
+                                            html! {}
+                                        }
+                                    }
+                                    {
+                                        match &embed {
+                                            Some((_, embed)) => html! { <RichEmbed embed={embed.clone()} live={location::live(m)} /> },
+                                            None => html! {},
+                                        }
+                                    }
+                                </>
+                            }
+                        }
+                    }
+                    {
+                        match bot {
+                            Some(card) => view_bot_buttons(&m.from, card, props.on_bot_action.as_ref()),
+                            None => html! {},
+                        }
+                    }
+                </div>
+                {
+                    match m.time {
+                        Some(t) => html! {
+                            <div
+                                title={time::absolute_label(t)}
+                                class={classes!(
+                                    "mt-1", "text-xs", "text-right",
+                                    if on_blue { "text-blue-200" } else { "text-gray-400" }
+                                )}
+                            >
+                                {
+                                    if props.bookmarked {
+                                        html! { <span title="Saved" class="mr-1">{"★"}</span> }
+                                    } else {
+                                        html! {}
+                                    }
+                                }
+                                {
+                                    if props.pinned {
+                                        html! { <span title="Pinned" class="mr-1">{"📌"}</span> }
+                                    } else {
+                                        html! {}
+                                    }
+                                }
+                                {time::relative_label(t, props.now)}
+                            </div>
+                        },
+                        None => html! {},
+                    }
+                }
+            </div>
+            {
+                if !is_current_user {
+                    html! {}
+                } else if ends_group {
+                    html! {
+                        <img class="w-10 h-10 rounded-full self-end ml-2 shadow-sm" src={props.avatar.clone()} alt="avatar"/>
+                    }
+                } else {
+                    html! { <div class="flex-none w-10 ml-2"></div> }
+                }
+            }
+        </div>
+    }
+}
+
+/// An announcement, across the whole thread rather than in a bubble.
+fn view_announcement(m: &MessageData, now: f64) -> Html {
+    html! {
+        <div id={m.id.as_deref().map(anchor)} class="w-full my-4 px-4 py-3 rounded-lg bg-amber-50 border-l-4 border-amber-500 shadow-sm">
+            <div class="flex items-center gap-2 text-xs font-semibold uppercase tracking-wide text-amber-700">
+                <svg xmlns="http://www.w3.org/2000/svg" class="h-4 w-4" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M11 5.882V19.24a1.76 1.76 0 01-3.417.592l-2.147-6.15M18 13a3 3 0 100-6M5.436 13.683A4.001 4.001 0 017 6h1.832c4.1 0 7.625-1.234 9.168-3v14c-1.543-1.766-5.067-3-9.168-3H7a3.988 3.988 0 01-1.564-.317z" />
+                </svg>
+                {format!("Announcement from {}", m.from)}
+                <span class="grow"></span>
+                {
+                    match m.time {
+                        Some(t) => html! {
+                            <span title={time::absolute_label(t)} class="font-normal normal-case tracking-normal">
+                                {time::relative_label(t, now)}
+                            </span>
+                        },
+                        None => html! {},
+                    }
+                }
+            </div>
+            <div class="mt-1 whitespace-pre-wrap break-words text-gray-900">{m.message.clone()}</div>
+        </div>
+    }
+}
+
+/// Someone joining or leaving, a pin, a lost connection: a muted line across the middle.
+fn view_system(m: &MessageData) -> Html {
+    html! {
+        <div class="flex justify-center my-2">
+            <span
+                title={m.time.map(time::absolute_label)}
+                class="px-3 py-0.5 rounded-full bg-gray-100 text-xs text-gray-500 italic"
+            >
+                {m.message.clone()}
+            </span>
+        </div>
+    }
+}
+
+/// The "⋯" button in the corner of a bubble and the menu it opens, also reachable by right-click.
+fn view_menu(
+    link: &str,
+    open: bool,
+    on_open: Callback<MouseEvent>,
+    on_close: Callback<MouseEvent>,
+    bookmark: Option<(bool, Callback<MouseEvent>)>,
+    pin: Option<(bool, Callback<MouseEvent>)>,
+    report: Option<Callback<MouseEvent>>,
+) -> Html {
+    let copy_link = {
+        let link = link.to_string();
+        Callback::from(move |e| {
+            if let Err(e) = clipboard::write_text(&link) {
+                log::error!("copy failed: {:?}", e);
+            }
+            on_close.emit(e);
+        })
+    };
+
+    html! {
+        <>
+            <button
+                onclick={on_open}
+                title="More"
+                class="absolute top-1 right-2 px-1 rounded text-gray-400 bg-white/80 opacity-0 group-hover:opacity-100 hover:text-gray-700"
+            >
+                {"⋯"}
+            </button>
+            {
+                if open {
+                    html! {
+                        <div class="absolute top-7 right-2 z-10 py-1 min-w-max rounded-lg bg-white shadow-lg border border-gray-200 text-sm text-gray-700">
+                            <button onclick={copy_link} class="block w-full px-4 py-1 text-left hover:bg-gray-100">
+                                {"Copy link"}
+                            </button>
+                            {
+                                match bookmark {
+                                    Some((bookmarked, toggle)) => html! {
+                                        <button onclick={toggle} class="block w-full px-4 py-1 text-left hover:bg-gray-100">
+                                            { if bookmarked { "Remove from saved" } else { "Save" } }
+                                        </button>
+                                    },
+                                    None => html! {},
+                                }
+                            }
+                            {
+                                match pin {
+                                    Some((pinned, toggle)) => html! {
+                                        <button onclick={toggle} class="block w-full px-4 py-1 text-left hover:bg-gray-100">
+                                            { if pinned { "Unpin" } else { "Pin" } }
+                                        </button>
+                                    },
+                                    None => html! {},
+                                }
+                            }
+                            {
+                                match report {
+                                    Some(report) => html! {
+                                        <button onclick={report} class="block w-full px-4 py-1 text-left hover:bg-gray-100">
+                                            {"Report"}
+                                        </button>
+                                    },
+                                    None => html! {},
+                                }
+                            }
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }
+            }
+        </>
+    }
+}
+
+/// Whether two neighbouring messages belong in one group. A bot never shares one with a user.
+fn same_sender(a: &MessageData, b: &MessageData) -> bool {
+    a.from == b.from && a.bot.is_some() == b.bot.is_some() && !a.system && !b.system
+}
+
+fn view_bot_buttons(
+    bot: &str,
+    card: &BotCard,
+    on_bot_action: Option<&Callback<BotAction>>,
+) -> Html {
+    if card.buttons.is_empty() {
+        return html! {};
+    }
+
+    html! {
+        <div class="flex flex-wrap gap-2 mt-3">
+            {
+                card.buttons.iter().map(|button| {
+                    let onclick = on_bot_action.map(|on_bot_action| {
+                        let action = BotAction {
+                            bot: bot.to_string(),
+                            card: card.id.clone(),
+                            action: button.action.clone(),
+                        };
+                        on_bot_action.reform(move |_| action.clone())
+                    });
+                    html! {
+                        <button
+                            disabled={onclick.is_none()}
+                            {onclick}
+                            class="px-3 py-1 rounded-full text-sm border border-purple-300 text-purple-700 bg-white hover:bg-purple-100 disabled:opacity-50 disabled:hover:bg-white transition-colors"
+                        >
+                            {button.label.clone()}
+                        </button>
+                    }
+                }).collect::<Html>()
+            }
+        </div>
+    }
+}
+
+fn view_day_separator(label: &str) -> Html {
+    html! {
+        <div key={format!("day {}", label)} class="flex items-center gap-3 my-4 text-xs font-medium text-gray-500">
+            <div class="flex-grow border-t border-gray-200"></div>
+            <span class="px-3 py-1 rounded-full bg-white shadow-sm">{label.to_string()}</span>
+            <div class="flex-grow border-t border-gray-200"></div>
+        </div>
+    }
+}
+
+/// The text of a message, with its diagrams drawn and its tables laid out.
+pub fn view_text(text: &str, emoji: &HashMap<String, String>) -> Html {
+    blocks::split(text)
+        .into_iter()
+        .map(|block| match block {
+            Block::Text(text) => view_inline(&text, emoji),
+            Block::Diagram(source) => html! { <Diagram {source} /> },
+            Block::Table(table) => view_table(&table, emoji),
+        })
+        .collect()
+}
+
+/// A table that scrolls sideways when too wide, and down past a point with its header kept.
+fn view_table(table: &Table, emoji: &HashMap<String, String>) -> Html {
+    let align = |column: usize| match table.align.get(column) {
+        Some(Align::Center) => "text-center",
+        Some(Align::Right) => "text-right",
+        _ => "text-left",
+    };
+    html! {
+        <div class="my-1 max-h-80 overflow-auto rounded-lg border border-black/10 whitespace-normal">
+            <table class="min-w-full text-sm border-collapse">
+                <thead>
+                    <tr>
+                        {
+                            table.header.iter().enumerate().map(|(column, cell)| html! {
+                                <th class={classes!("sticky", "top-0", "px-2", "py-1", "bg-gray-100", "text-gray-700", "font-semibold", "whitespace-nowrap", align(column))}>
+                                    {view_inline(cell, emoji)}
+                                </th>
+                            }).collect::<Html>()
+                        }
+                    </tr>
+                </thead>
+                <tbody>
+                    {
+                        table.rows.iter().map(|row| html! {
+                            <tr class="border-t border-black/10">
+                                {
+                                    row.iter().enumerate().map(|(column, cell)| html! {
+                                        <td class={classes!("px-2", "py-1", align(column))}>{view_inline(cell, emoji)}</td>
+                                    }).collect::<Html>()
+                                }
+                            </tr>
+                        }).collect::<Html>()
+                    }
+                </tbody>
+            </table>
+        </div>
+    }
+}
+
+/// Running text, with its math typeset.
+#[cfg(feature = "math")]
+fn view_inline(text: &str, emoji: &HashMap<String, String>) -> Html {
+    crate::math::split(text)
+        .into_iter()
+        .map(|part| match part {
+            crate::math::Part::Text(text) => view_links(&text, emoji),
+            crate::math::Part::Math { tex, display } => html! { <Math {tex} {display} /> },
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "math"))]
+fn view_inline(text: &str, emoji: &HashMap<String, String>) -> Html {
+    view_links(text, emoji)
+}
+
+/// Text with its web addresses made into links.
+fn view_links(text: &str, emoji: &HashMap<String, String>) -> Html {
+    sanitize::linkify(text)
+        .into_iter()
+        .map(|segment| match segment {
+            sanitize::Segment::Text(text) => view_emoji(&text, emoji),
+            sanitize::Segment::Link { text, href } => html! {
+                <ExternalLink {href} class="underline break-all">{text}</ExternalLink>
+            },
+        })
+        .collect()
+}
+
+/// Text with the room's `:emoji:` shown as their pictures.
+fn view_emoji(text: &str, custom: &HashMap<String, String>) -> Html {
+    emoji::split(text, custom)
+        .into_iter()
+        .map(|piece| match piece {
+            Piece::Text(text) => html! { {text} },
+            Piece::Emoji { name, image } => {
+                let code = format!(":{}:", name);
+                html! {
+                    <img class="inline h-6 w-6 align-text-bottom" src={image} alt={code.clone()} title={code}/>
+                }
+            }
+        })
+        .collect()
+}
+
+fn view_attachment(attachment: &Attachment) -> Html {
+    // Without its contents, only the name and size are shown.
+    let data = sanitize::attachment(attachment);
+    if let (true, Some(data)) = (attachment.is_image(), &data) {
+        html! {
+            <LazyImage
+                src={data.clone()}
+                alt={attachment.name.clone()}
+                size={attachment.size}
+            />
+        }
+    } else {
+        html! {
+            <a
+                href={data}
+                download={attachment.name.clone()}
+                class="flex items-center gap-3 mt-1 p-3 rounded-lg bg-gray-100 text-gray-800 hover:bg-gray-200"
+            >
+                <svg xmlns="http://www.w3.org/2000/svg" class="h-8 w-8 text-blue-600" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M7 21h10a2 2 0 002-2V9.414a1 1 0 00-.293-.707l-5.414-5.414A1 1 0 0012.586 3H7a2 2 0 00-2 2v14a2 2 0 002 2z" />
+                </svg>
+                <div class="min-w-0">
+                    <div class="font-medium truncate">{attachment.name.clone()}</div>
+                    <div class="text-xs text-gray-500">{format_size(attachment.size as f64)}</div>
+                </div>
+            </a>
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{BotButton, Role};
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_test::*;
+    use web_sys::Element;
+
+    /// Mounts `COMP` into a fresh element on the page. Yew 0.19 renders synchronously on mount.
+    fn render<COMP: Component>(props: COMP::Properties) -> Element {
+        let document = web_sys::window().unwrap().document().unwrap();
+        let root = document.create_element("div").unwrap();
+        document.body().unwrap().append_child(&root).unwrap();
+        yew::start_app_with_props_in_element::<COMP>(root.clone(), props);
+        root
+    }
+
+    fn message(from: &str, text: &str) -> Rc<MessageData> {
+        Rc::new(MessageData {
+            id: None,
+            from: from.into(),
+            message: text.into(),
+            attachment: None,
+            time: None,
+            bot: None,
+            to: None,
+            announcement: false,
+            pinned_until: None,
+            system: false,
+        })
+    }
+
+    fn bubble(message: Rc<MessageData>) -> MessageBubbleProps {
+        MessageBubbleProps {
+            message,
+            avatar: "avatar.svg".into(),
+            is_current_user: false,
+            starts_group: true,
+            ends_group: true,
+            now: 0.0,
+            emoji: Rc::default(),
+            on_bot_action: None,
+            link: None,
+            highlighted: false,
+            bookmarked: false,
+            on_bookmark: None,
+            mentions_us: false,
+            spam: None,
+            on_report: None,
+            pinned: false,
+            on_pin: None,
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn text_message() {
+        let root = render::<MessageBubble>(bubble(message("alice", "<b>hi</b>")));
+        let text = root.text_content().unwrap();
+        assert!(text.contains("alice"));
+        assert!(
+            text.contains("<b>hi</b>"),
+            "message text must not be parsed as HTML"
+        );
+        assert!(root.query_selector("b").unwrap().is_none());
+        assert!(root.query_selector(".justify-start").unwrap().is_some());
+        assert!(root
+            .query_selector("img[src='avatar.svg']")
+            .unwrap()
+            .is_some());
+    }
+
+    #[wasm_bindgen_test]
+    fn links_in_text() {
+        let root = render::<MessageBubble>(bubble(message(
+            "alice",
+            "see https://example.com/a. not javascript:alert(1)",
+        )));
+        let link = root
+            .query_selector("a[href='https://example.com/a']")
+            .unwrap()
+            .unwrap();
+        assert_eq!(link.text_content().unwrap(), "https://example.com/a");
+        assert_eq!(root.query_selector_all("a").unwrap().length(), 1);
+        assert!(root
+            .text_content()
+            .unwrap()
+            .contains("a. not javascript:alert(1)"));
+    }
+
+    #[wasm_bindgen_test]
+    fn own_message_continuing_a_group() {
+        let root = render::<MessageBubble>(MessageBubbleProps {
+            is_current_user: true,
+            starts_group: false,
+            ends_group: false,
+            ..bubble(message("me", "again"))
+        });
+        assert!(root.query_selector(".justify-end").unwrap().is_some());
+        assert!(!root.text_content().unwrap().contains("me"));
+        assert!(root.query_selector("img").unwrap().is_none());
+    }
+
+    #[wasm_bindgen_test]
+    fn custom_emoji() {
+        let root = render::<MessageBubble>(MessageBubbleProps {
+            emoji: Rc::new(HashMap::from([(
+                "party".to_string(),
+                "data:image/png;base64,x".to_string(),
+            )])),
+            ..bubble(message("alice", "done :party: :nope:"))
+        });
+        let img = root.query_selector("img[alt=':party:']").unwrap().unwrap();
+        assert_eq!(img.get_attribute("src").unwrap(), "data:image/png;base64,x");
+        assert!(root.text_content().unwrap().contains(":nope:"));
+    }
+
+    #[wasm_bindgen_test]
+    fn gif_link() {
+        let root = render::<MessageBubble>(bubble(message("alice", "https://example.com/cat.gif")));
+        assert!(root
+            .query_selector("[data-src='https://example.com/cat.gif']")
+            .unwrap()
+            .is_some());
+    }
+
+    #[wasm_bindgen_test]
+    fn video_link_waits_for_a_click() {
+        let root = render::<MessageBubble>(bubble(message(
+            "alice",
+            "watch this https://youtu.be/dQw4w9WgXcQ",
+        )));
+        assert!(root.text_content().unwrap().contains("watch this"));
+        assert!(root.query_selector("iframe").unwrap().is_none());
+        assert!(root
+            .query_selector("button[title='Play on YouTube']")
+            .unwrap()
+            .is_some());
+    }
+
+    #[wasm_bindgen_test]
+    fn linkable_message() {
+        let root = render::<MessageBubble>(MessageBubbleProps {
+            link: Some("https://chat.example.org/chat/general#msg-abc".into()),
+            ..bubble(Rc::new(MessageData {
+                id: Some("abc".into()),
+                ..(*message("alice", "hi")).clone()
+            }))
+        });
+        assert!(root.query_selector("#msg-abc").unwrap().is_some());
+        assert!(!root.text_content().unwrap().contains("Copy link"));
+        root.query_selector("button[title='More']")
+            .unwrap()
+            .unwrap()
+            .dyn_into::<web_sys::HtmlElement>()
+            .unwrap()
+            .click();
+        assert!(root.text_content().unwrap().contains("Copy link"));
+
+        // Nowhere to link to, so no menu.
+        let root = render::<MessageBubble>(bubble(message("alice", "hi")));
+        assert!(root
+            .query_selector("button[title='More']")
+            .unwrap()
+            .is_none());
+    }
+
+    #[wasm_bindgen_test]
+    fn saving_from_the_menu() {
+        let saved = Rc::new(std::cell::RefCell::new(None));
+        let on_bookmark = {
+            let saved = saved.clone();
+            Callback::from(move |m: Rc<MessageData>| *saved.borrow_mut() = m.id.clone())
+        };
+        let root = render::<MessageBubble>(MessageBubbleProps {
+            link: Some("https://chat.example.org/chat/general#msg-abc".into()),
+            on_bookmark: Some(on_bookmark),
+            ..bubble(Rc::new(MessageData {
+                id: Some("abc".into()),
+                time: Some(0.0),
+                ..(*message("alice", "hi")).clone()
+            }))
+        });
+        assert!(root.query_selector("[title='Saved']").unwrap().is_none());
+        let click = |selector: &str| {
+            root.query_selector(selector)
+                .unwrap()
+                .unwrap()
+                .dyn_into::<web_sys::HtmlElement>()
+                .unwrap()
+                .click()
+        };
+        click("button[title='More']");
+        click(".absolute.top-7 button:last-child");
+        assert_eq!(saved.borrow().as_deref(), Some("abc"));
+    }
+
+    #[wasm_bindgen_test]
+    fn reporting_from_the_menu() {
+        let reported = Rc::new(std::cell::RefCell::new(None));
+        let on_report = {
+            let reported = reported.clone();
+            Callback::from(move |m: Rc<MessageData>| *reported.borrow_mut() = m.id.clone())
+        };
+        let props = |is_current_user| MessageBubbleProps {
+            link: Some("https://chat.example.org/chat/general#msg-abc".into()),
+            is_current_user,
+            on_report: Some(on_report.clone()),
+            ..bubble(Rc::new(MessageData {
+                id: Some("abc".into()),
+                ..(*message("alice", "hi")).clone()
+            }))
+        };
+        for (is_current_user, offered) in [(true, false), (false, true)] {
+            let root = render::<MessageBubble>(props(is_current_user));
+            let more: web_sys::HtmlElement = root
+                .query_selector("button[title='More']")
+                .unwrap()
+                .unwrap()
+                .dyn_into()
+                .unwrap();
+            more.click();
+            let last: web_sys::HtmlElement = root
+                .query_selector(".absolute.top-7 button:last-child")
+                .unwrap()
+                .unwrap()
+                .dyn_into()
+                .unwrap();
+            assert_eq!(last.text_content().unwrap() == "Report", offered);
+            if offered {
+                last.click();
+            }
+        }
+        assert_eq!(reported.borrow().as_deref(), Some("abc"));
+    }
+
+    #[wasm_bindgen_test]
+    fn reads_the_linked_message_from_the_address() {
+        link_to("abc");
+        assert_eq!(linked_message(), Some("abc".into()));
+        web_sys::window().unwrap().location().set_hash("").unwrap();
+        assert_eq!(linked_message(), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn attachments() {
+        let attach = |name: &str, mime: &str| {
+            Rc::new(MessageData {
+                attachment: Some(Attachment {
+                    name: name.into(),
+                    mime: mime.into(),
+                    size: 2048,
+                    data: format!("data:{};base64,eA==", mime),
+                }),
+                ..(*message("alice", "")).clone()
+            })
+        };
+
+        let root = render::<MessageBubble>(bubble(attach("cat.png", "image/png")));
+        let placeholder = root
+            .query_selector("[role='img'][aria-label='cat.png']")
+            .unwrap()
+            .unwrap();
+        assert!(placeholder.text_content().unwrap().contains("2.0 KB"));
+
+        let root = render::<MessageBubble>(bubble(attach("notes.pdf", "application/pdf")));
+        let link = root
+            .query_selector("a[download='notes.pdf']")
+            .unwrap()
+            .unwrap();
+        assert!(link.text_content().unwrap().contains("notes.pdf"));
+    }
+
+    #[wasm_bindgen_test]
+    fn list_groups_by_sender_and_day() {
+        let now = time::now();
+        let at = |from: &str, time: f64| {
+            Rc::new(MessageData {
+                time: Some(time),
+                ..(*message(from, "hello")).clone()
+            })
+        };
+        let root = render::<MessageList>(MessageListProps {
+            messages: vec![at("alice", now), at("alice", now), at("bob", now)],
+            users: vec![UserProfile {
+                name: "alice".into(),
+                avatar: "alice.png".into(),
+                away: false,
+                voice: false,
+                role: Role::Member,
+                room_role: None,
+            }],
+            // Offline, but seen before.
+            known: Rc::new(HashMap::from([(
+                "bob".to_string(),
+                KnownProfile {
+                    avatar: Some("https://example.com/bob.png".into()),
+                    gravatar: None,
+                    last_seen: now,
+                },
+            )])),
+            emoji: Rc::default(),
+            current_username: "bob".into(),
+            now,
+            on_bot_action: None,
+            room: None,
+            bookmarks: Rc::default(),
+            on_bookmark: None,
+            keywords: Rc::default(),
+            spam: SpamSettings::default(),
+            on_report: None,
+            pins: Rc::default(),
+            on_pin: None,
+        });
+        let text = root.text_content().unwrap();
+        assert_eq!(text.matches("Today").count(), 1);
+        // One name per run of messages from the same sender.
+        assert_eq!(text.matches("alice").count(), 1);
+        assert_eq!(
+            root.query_selector_all("img[src='alice.png']")
+                .unwrap()
+                .length(),
+            1
+        );
+        assert!(root
+            .query_selector("img[src='https://example.com/bob.png']")
+            .unwrap()
+            .is_some());
+        assert_eq!(root.query_selector_all(".justify-end").unwrap().length(), 1);
+    }
+
+    #[wasm_bindgen_test]
+    fn announcement() {
+        let announcement = Rc::new(MessageData {
+            announcement: true,
+            ..(*message("alice", "Maintenance at noon")).clone()
+        });
+        let root = render::<MessageBubble>(bubble(announcement));
+        let text = root.text_content().unwrap();
+        assert!(text.contains("Announcement from alice") && text.contains("Maintenance at noon"));
+    }
+
+    #[wasm_bindgen_test]
+    fn system_line() {
+        let joined = Rc::new(MessageData::system("carol joined"));
+        let root = render::<MessageBubble>(bubble(joined));
+        assert_eq!(root.text_content().unwrap().trim(), "carol joined");
+        assert!(root.query_selector("img").unwrap().is_none());
+    }
+
+    #[wasm_bindgen_test]
+    fn spam_is_folded() {
+        let root = render::<MessageBubble>(MessageBubbleProps {
+            spam: Some("shouting"),
+            ..bubble(message("eve", "BUY NOW BUY NOW"))
+        });
+        let text = root.text_content().unwrap();
+        assert!(text.contains("Possible spam from eve (shouting)"));
+        assert!(!text.contains("BUY NOW"));
+    }
+
+    #[wasm_bindgen_test]
+    fn whisper() {
+        let whisper = Rc::new(MessageData {
+            to: Some("bob".into()),
+            ..(*message("alice", "psst")).clone()
+        });
+        let root = render::<MessageBubble>(bubble(whisper));
+        let text = root.text_content().unwrap();
+        assert!(text.contains("only you can see this") && text.contains("psst"));
+    }
+
+    #[wasm_bindgen_test]
+    fn bot_card_with_buttons() {
+        let card = Rc::new(MessageData {
+            bot: Some(Box::new(BotCard {
+                id: Some("build-42".into()),
+                title: Some("CI".into()),
+                avatar: None,
+                buttons: vec![BotButton {
+                    action: "retry".into(),
+                    label: "Retry".into(),
+                }],
+            })),
+            ..(*message("ci", "Build failed")).clone()
+        });
+
+        let root = render::<MessageBubble>(bubble(card.clone()));
+        let text = root.text_content().unwrap();
+        assert!(text.contains("BOT") && text.contains("CI") && text.contains("Build failed"));
+        let button = root.query_selector("button").unwrap().unwrap();
+        assert!(
+            button.has_attribute("disabled"),
+            "no callback, nothing to press"
+        );
+
+        let pressed = Rc::new(std::cell::RefCell::new(None));
+        let on_bot_action = {
+            let pressed = pressed.clone();
+            Callback::from(move |action| *pressed.borrow_mut() = Some(action))
+        };
+        let root = render::<MessageBubble>(MessageBubbleProps {
+            on_bot_action: Some(on_bot_action),
+            ..bubble(card)
+        });
+        root.query_selector("button")
+            .unwrap()
+            .unwrap()
+            .dyn_into::<web_sys::HtmlElement>()
+            .unwrap()
+            .click();
+        assert_eq!(
+            *pressed.borrow(),
+            Some(BotAction {
+                bot: "ci".into(),
+                card: Some("build-42".into()),
+                action: "retry".into(),
+            })
+        );
+    }
+}
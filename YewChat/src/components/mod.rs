@@ -1,2 +1,52 @@
+pub mod activity_log;
+pub mod admin;
+pub mod archive;
+pub mod attachments;
+pub mod audio_settings;
+pub mod avatar_settings;
+pub mod away_settings;
+pub mod blacklist_settings;
+pub mod call;
+pub mod call_history;
+pub mod call_layout;
 pub mod chat;
+pub mod chat_header;
+pub mod chat_history;
+pub mod composer;
+pub mod contact_card;
+pub mod delete_room_dialog;
+pub mod diagram;
+pub mod embed;
+pub mod emoji_picker;
+pub mod emoji_settings;
+pub mod external_link;
+pub mod frame_log;
+pub mod keyword_settings;
+pub mod lazy_image;
+pub mod live_share;
 pub mod login;
+#[cfg(feature = "math")]
+pub mod math;
+pub mod message_list;
+pub mod moderation_queue;
+pub mod oauth_callback;
+pub mod pinned_messages;
+pub mod print_view;
+pub mod push_to_talk_settings;
+pub mod relay_settings;
+pub mod restriction_settings;
+pub mod room_directory;
+pub mod room_icon;
+pub mod room_password_dialog;
+pub mod room_roles;
+pub mod room_settings;
+pub mod room_stats;
+pub mod saved_messages;
+pub mod settings_panel;
+pub mod share_dialog;
+pub mod sidebar;
+pub mod spam_settings;
+pub mod terms_gate;
+pub mod toast;
+pub mod voice;
+pub mod welcome;
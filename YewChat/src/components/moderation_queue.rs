@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use yew::prelude::*;
+
+use crate::components::message_list::view_text;
+use crate::protocol::{Moderation, ModerationAction, ReportedMessage};
+use crate::time;
+
+#[derive(Properties, PartialEq)]
+pub struct ModerationQueueProps {
+    pub room: String,
+    pub reports: Rc<Vec<ReportedMessage>>,
+    /// The room's custom emoji, by name.
+    pub emoji: Rc<HashMap<String, String>>,
+    pub on_moderate: Callback<Moderation>,
+}
+
+/// The reported messages of the room, for its owner and the administrators to keep, delete, or
+/// delete and ban their sender. Whatever is decided reaches everyone in the room.
+#[function_component(ModerationQueue)]
+pub fn moderation_queue(props: &ModerationQueueProps) -> Html {
+    html! {
+        <div class="w-full max-h-80 overflow-auto px-4 py-3 border-b border-gray-200 bg-gray-50 text-sm text-gray-700">
+            <div>{format!("Reported messages of #{}", props.room)}</div>
+            {
+                if props.reports.is_empty() {
+                    html! { <div class="mt-2 text-gray-500">{"Nothing has been reported."}</div> }
+                } else {
+                    props.reports.iter().map(|report| {
+                        let m = &report.message;
+                        let act = |action| {
+                            let on_moderate = props.on_moderate.clone();
+                            let id = m.id.clone().unwrap_or_default();
+                            Callback::from(move |_| on_moderate.emit(Moderation {
+                                id: id.clone(),
+                                action,
+                            }))
+                        };
+                        html! {
+                            <div class="mt-2 p-3 rounded-lg bg-white border border-gray-200">
+                                <div class="flex items-baseline gap-2">
+                                    <span class="font-semibold">{m.from.clone()}</span>
+                                    <span class="text-xs text-gray-500">
+                                        {m.time.map(time::absolute_label).unwrap_or_default()}
+                                    </span>
+                                </div>
+                                <div class="mt-1 whitespace-pre-wrap break-words">{view_text(&m.message, &props.emoji)}</div>
+                                <div class="mt-2 flex items-center gap-2">
+                                    <span class="grow text-xs text-gray-500">
+                                        {format!("Reported by {}", report.reporters.join(", "))}
+                                    </span>
+                                    <button onclick={act(ModerationAction::Approve)} class="px-3 py-1 rounded-lg hover:bg-gray-100">
+                                        {"Approve"}
+                                    </button>
+                                    <button onclick={act(ModerationAction::Delete)} class="px-3 py-1 rounded-lg text-red-700 hover:bg-red-50">
+                                        {"Delete"}
+                                    </button>
+                                    <button
+                                        onclick={act(ModerationAction::Ban)}
+                                        title={format!("Delete and stop {} posting in #{}", m.from, props.room)}
+                                        class="px-3 py-1 rounded-lg bg-red-600 text-white hover:bg-red-700"
+                                    >
+                                        {"Ban"}
+                                    </button>
+                                </div>
+                            </div>
+                        }
+                    }).collect::<Html>()
+                }
+            }
+        </div>
+    }
+}
@@ -0,0 +1,106 @@
+use serde::Deserialize;
+use wasm_bindgen_futures::spawn_local;
+use yew::functional::*;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::protocol::{AuthError, OAuthCode};
+use crate::services::auth::AuthService;
+use crate::services::oauth::{self, Provider};
+use crate::{Route, User, DEFAULT_ROOM};
+
+#[derive(Deserialize)]
+struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct OAuthCallbackProps {
+    pub provider: String,
+}
+
+/// Landing page for the provider redirect: hands the code to the server and signs in with the
+/// identity it returns.
+#[function_component(OAuthCallback)]
+pub fn oauth_callback(props: &OAuthCallbackProps) -> Html {
+    let user = use_context::<User>().expect("No context found.");
+    let history = use_history().expect("history to be available");
+    let query = use_location().and_then(|l| l.query::<CallbackQuery>().ok());
+    let error = use_state(|| Option::<String>::None);
+
+    {
+        let error = error.clone();
+        use_effect_with_deps(
+            move |provider: &String| {
+                let exchange = match (Provider::from_id(provider), query) {
+                    (None, _) => Err(format!("Unknown sign-in provider \"{}\".", provider)),
+                    (_, None) => Err("The sign-in response was malformed.".into()),
+                    (_, Some(CallbackQuery { error: Some(e), .. })) => {
+                        Err(format!("Sign-in was cancelled ({}).", e))
+                    }
+                    (
+                        Some(provider),
+                        Some(CallbackQuery {
+                            code: Some(code),
+                            state: Some(state),
+                            ..
+                        }),
+                    ) => {
+                        if oauth::verify_state(&state) {
+                            Ok(OAuthCode {
+                                provider: provider.id().into(),
+                                code,
+                                redirect_uri: provider.redirect_uri(),
+                            })
+                        } else {
+                            Err("The sign-in response did not match this browser session.".into())
+                        }
+                    }
+                    (Some(_), Some(_)) => Err("The sign-in response was malformed.".into()),
+                };
+                match exchange {
+                    Ok(code) => spawn_local(async move {
+                        match AuthService::oauth(&code).await {
+                            Ok(identity) => {
+                                AuthService::sign_in(&user, identity);
+                                history.replace(Route::ChatRoom {
+                                    room: DEFAULT_ROOM.into(),
+                                });
+                            }
+                            Err(AuthError::Unavailable) => {
+                                error.set(Some(AuthError::Unavailable.describe().into()))
+                            }
+                            Err(_) => error.set(Some(AuthError::OAuthFailed.describe().into())),
+                        }
+                    }),
+                    Err(e) => error.set(Some(e)),
+                }
+                || ()
+            },
+            props.provider.clone(),
+        );
+    }
+
+    html! {
+       <div class="bg-gray-800 flex w-screen">
+            <div class="container mx-auto flex flex-col justify-center items-center gap-4 text-white">
+                {
+                    if let Some(error) = &*error {
+                        html! {
+                            <>
+                                <div class="text-red-400">{error.clone()}</div>
+                                <Link<Route> to={Route::Login} classes="underline text-gray-300 hover:text-white">
+                                    {"Back to login"}
+                                </Link<Route>>
+                            </>
+                        }
+                    } else {
+                        html! { <div class="text-gray-300">{"Signing you in..."}</div> }
+                    }
+                }
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use yew::prelude::*;
+
+use crate::components::message_list::{self, view_text};
+use crate::protocol::PinnedMessage;
+use crate::time;
+
+#[derive(Properties, PartialEq)]
+pub struct PinnedMessagesProps {
+    pub room: String,
+    pub pins: Rc<Vec<PinnedMessage>>,
+    /// The room's custom emoji, by name.
+    pub emoji: Rc<HashMap<String, String>>,
+    /// Given the ids of the pins in their new order, without those unpinned. Only set for the
+    /// room's moderators; everyone else just reads.
+    #[prop_or_default]
+    pub on_arrange: Option<Callback<Vec<String>>>,
+}
+
+/// The ids of `pins` in order, with the one at `from` moved to `to`, or left out without a `to`.
+fn arranged(pins: &[PinnedMessage], from: usize, to: Option<usize>) -> Vec<String> {
+    let mut ids: Vec<String> = pins
+        .iter()
+        .map(|p| p.message.id.clone().unwrap_or_default())
+        .collect();
+    let id = ids.remove(from);
+    if let Some(to) = to {
+        ids.insert(to.min(ids.len()), id);
+    }
+    ids
+}
+
+/// The messages pinned in the room, in order. Clicking one jumps to it; the room's owner and the
+/// administrators can also move pins up and down, or unpin them.
+#[function_component(PinnedMessages)]
+pub fn pinned_messages(props: &PinnedMessagesProps) -> Html {
+    let count = props.pins.len();
+    html! {
+        <div class="w-full max-h-80 overflow-auto px-4 py-3 border-b border-gray-200 bg-amber-50 text-sm text-gray-700">
+            <div>{format!("Pinned in #{}", props.room)}</div>
+            {
+                if props.pins.is_empty() {
+                    html! { <div class="mt-2 text-gray-500">{"Nothing is pinned."}</div> }
+                } else {
+                    props.pins.iter().enumerate().map(|(at, pin)| {
+                        let m = &pin.message;
+                        let onclick = m.id.clone().map(|id| Callback::from(move |_| message_list::link_to(&id)));
+                        let controls = match &props.on_arrange {
+                            Some(on_arrange) => {
+                                let button = |label: &str, title: &str, to: Option<usize>, disabled: bool| {
+                                    let on_arrange = on_arrange.clone();
+                                    let pins = props.pins.clone();
+                                    let onclick = Callback::from(move |_| on_arrange.emit(arranged(&pins, at, to)));
+                                    html! {
+                                        <button {onclick} {disabled} title={title.to_string()} class="px-2 py-1 rounded-lg hover:bg-amber-100 disabled:opacity-30">
+                                            {label.to_string()}
+                                        </button>
+                                    }
+                                };
+                                html! {
+                                    <div class="flex-none flex items-center">
+                                        {button("↑", "Move up", at.checked_sub(1), at == 0)}
+                                        {button("↓", "Move down", Some(at + 1), at + 1 == count)}
+                                        {button("Unpin", "Unpin", None, false)}
+                                    </div>
+                                }
+                            }
+                            None => html! {},
+                        };
+                        html! {
+                            <div class="mt-2 p-3 flex items-start gap-2 rounded-lg bg-white border border-amber-200">
+                                <div {onclick} class="grow min-w-0 cursor-pointer">
+                                    <div class="flex items-baseline gap-2">
+                                        <span class="font-semibold">{m.from.clone()}</span>
+                                        <span class="text-xs text-gray-500">
+                                            {m.time.map(time::absolute_label).unwrap_or_default()}
+                                        </span>
+                                    </div>
+                                    <div class="mt-1 whitespace-pre-wrap break-words">{view_text(&m.message, &props.emoji)}</div>
+                                    <div class="mt-1 text-xs text-gray-500">
+                                        {format!("Pinned by {}", pin.pinned_by)}
+                                    </div>
+                                </div>
+                                {controls}
+                            </div>
+                        }
+                    }).collect::<Html>()
+                }
+            }
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::MessageData;
+    use wasm_bindgen_test::*;
+
+    fn pin(id: &str) -> PinnedMessage {
+        PinnedMessage {
+            message: MessageData {
+                id: Some(id.into()),
+                ..MessageData::system(id)
+            },
+            pinned_by: "alice".into(),
+            pinned_at: 0.0,
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn pins_move_and_go() {
+        let pins = [pin("a"), pin("b"), pin("c")];
+        assert_eq!(arranged(&pins, 1, Some(0)), ["b", "a", "c"]);
+        assert_eq!(arranged(&pins, 1, Some(2)), ["a", "c", "b"]);
+        assert_eq!(arranged(&pins, 2, Some(3)), ["a", "b", "c"]);
+        assert_eq!(arranged(&pins, 0, None), ["b", "c"]);
+    }
+}
@@ -0,0 +1,114 @@
+use std::rc::Rc;
+
+use yew::prelude::*;
+
+use crate::protocol::MessageData;
+use crate::sanitize;
+use crate::services::attachment::format_size;
+use crate::time;
+
+#[derive(Properties, PartialEq)]
+pub struct PrintViewProps {
+    /// The room, or `@peer` for a direct conversation.
+    pub title: String,
+    pub messages: Vec<Rc<MessageData>>,
+    pub on_close: Callback<()>,
+}
+
+/// The conversation as a plain black-on-white document, for the browser to print or save as a
+/// PDF. Only the toolbar above it is interactive, and it is left off the printout.
+#[function_component(PrintView)]
+pub fn print_view(props: &PrintViewProps) -> Html {
+    let print = Callback::from(|_| {
+        if let Some(window) = web_sys::window() {
+            if let Err(e) = window.print() {
+                log::error!("failed to print: {:?}", e);
+            }
+        }
+    });
+    let close = props.on_close.reform(|_| ());
+    let printed_at = time::absolute_label(time::now());
+
+    html! {
+        <div class="min-h-screen w-screen bg-gray-100 print:bg-white">
+            <style>{"@page { margin: 2cm; }"}</style>
+            <div class="sticky top-0 flex items-center gap-3 px-4 h-14 bg-white border-b border-gray-200 shadow-sm print:hidden">
+                <div class="grow text-sm text-gray-500">{"Print view"}</div>
+                <button onclick={print} class="px-4 py-1.5 rounded-full bg-blue-600 text-white text-sm hover:bg-blue-700">{"Print"}</button>
+                <button onclick={close} class="px-4 py-1.5 rounded-full text-sm text-gray-600 hover:bg-gray-100">{"Close"}</button>
+            </div>
+            <article class="max-w-3xl mx-auto my-6 p-12 bg-white text-black font-serif shadow print:m-0 print:p-0 print:max-w-none print:shadow-none">
+                <header class="mb-6 pb-3 border-b border-black">
+                    <h1 class="text-2xl font-bold">{
+                        if props.title.starts_with('@') {
+                            props.title.clone()
+                        } else {
+                            format!("# {}", props.title)
+                        }
+                    }</h1>
+                    <div class="text-sm">
+                        {format!("{} messages · printed {}", props.messages.len(), printed_at)}
+                    </div>
+                </header>
+                { view_messages(&props.messages) }
+            </article>
+        </div>
+    }
+}
+
+fn view_messages(messages: &[Rc<MessageData>]) -> Html {
+    let mut day = None;
+    messages
+        .iter()
+        .map(|m| {
+            // A heading wherever the day changes, kept on the page of the message after it.
+            let heading = match m.time {
+                Some(t) if day != Some(time::day_of(t)) => {
+                    day = Some(time::day_of(t));
+                    html! {
+                        <h2 class="mt-6 mb-2 text-sm font-bold uppercase tracking-wide break-after-avoid">
+                            {time::day_label(t)}
+                        </h2>
+                    }
+                }
+                _ => html! {},
+            };
+            html! {
+                <>
+                    { heading }
+                    { view_message(m) }
+                </>
+            }
+        })
+        .collect()
+}
+
+fn view_message(m: &MessageData) -> Html {
+    let attachment = match &m.attachment {
+        Some(a) => match sanitize::attachment(a) {
+            Some(data) if a.is_image() => html! {
+                <img src={data} alt={a.name.clone()} class="mt-1 max-w-full max-h-96" />
+            },
+            _ => html! {
+                <div class="mt-1 italic">{format!("[attachment: {}, {}]", a.name, format_size(a.size as f64))}</div>
+            },
+        },
+        None => html! {},
+    };
+
+    html! {
+        <section class="mb-3 break-inside-avoid">
+            <div class="text-sm">
+                <span class="font-bold">{m.from.clone()}</span>
+                {
+                    match m.time {
+                        Some(t) => html! { <span class="ml-2">{time::clock_label(t)}</span> },
+                        None => html! {},
+                    }
+                }
+            </div>
+            <div class="whitespace-pre-wrap break-words">{m.message.clone()}</div>
+            { attachment }
+        </section>
+    }
+}
@@ -0,0 +1,162 @@
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::services::push_to_talk;
+
+#[derive(Properties, PartialEq)]
+pub struct PushToTalkSettingsProps {
+    pub username: String,
+    /// The key held to talk, as a `KeyboardEvent.code`, or none to always be heard.
+    pub binding: Option<String>,
+    pub on_change: Callback<Option<String>>,
+}
+
+/// The key to hold for our voice to go out in calls and voice channels. Clicking the button waits
+/// for the next key, Escape or clicking away cancels. Saved as soon as it is pressed.
+#[function_component(PushToTalkSettings)]
+pub fn push_to_talk_settings(props: &PushToTalkSettingsProps) -> Html {
+    let listening = use_state_eq(|| false);
+
+    let listen = {
+        let listening = listening.clone();
+        Callback::from(move |_| listening.set(true))
+    };
+    let stop = {
+        let listening = listening.clone();
+        Callback::from(move |_| listening.set(false))
+    };
+    let onkeydown = {
+        let listening = listening.clone();
+        let username = props.username.clone();
+        let on_change = props.on_change.clone();
+        Callback::from(move |e: KeyboardEvent| {
+            if !*listening {
+                return;
+            }
+            e.prevent_default();
+            listening.set(false);
+            if e.key() != "Escape" {
+                let code = e.code();
+                push_to_talk::set_key(&username, Some(&code));
+                on_change.emit(Some(code));
+            }
+        })
+    };
+    let turn_off = {
+        let username = props.username.clone();
+        let on_change = props.on_change.clone();
+        Callback::from(move |_| {
+            push_to_talk::set_key(&username, None);
+            on_change.emit(None);
+        })
+    };
+
+    html! {
+        <div class="w-full px-4 py-2 border-b border-gray-200 bg-gray-50 text-sm text-gray-700 flex items-center gap-2">
+            {"Push to talk"}
+            <button
+                onclick={listen}
+                {onkeydown}
+                onblur={stop}
+                title="Hold this key for your voice to go out in calls and voice channels"
+                class={classes!(
+                    "min-w-[6rem]", "rounded-lg", "px-2", "py-1", "border",
+                    if *listening { vec!["border-blue-500", "bg-blue-50"] } else { vec!["border-gray-200", "bg-white"] }
+                )}
+            >
+                {
+                    match (&props.binding, *listening) {
+                        (_, true) => "Press a key…".to_string(),
+                        (Some(code), false) => push_to_talk::label(code),
+                        (None, false) => "Off".to_string(),
+                    }
+                }
+            </button>
+            {
+                if props.binding.is_some() {
+                    html! {
+                        <button onclick={turn_off} class="px-2 py-1 rounded-lg hover:bg-gray-100">{"Turn off"}</button>
+                    }
+                } else {
+                    html! {}
+                }
+            }
+        </div>
+    }
+}
+
+/// Whether the push-to-talk `key` is held. Without a key, it never is. `on_transmit` is told
+/// whether our voice should go out: while it is held, or always without a key.
+pub fn use_push_to_talk(key: Option<String>, on_transmit: Callback<bool>) -> bool {
+    let talking = use_state_eq(|| false);
+    let transmit = key.is_none() || *talking;
+    // Push-to-talk follows its key wherever the focus is, except while typing the key into a field.
+    // Leaving the window lets go of it, as its keyup would go elsewhere.
+    {
+        let talking = talking.clone();
+        use_effect_with_deps(
+            move |talk_key: &Option<String>| {
+                let window = web_sys::window().unwrap();
+                talking.set(false);
+                let listeners = talk_key.clone().map(|code| {
+                    let pressed = move |e: &Event| {
+                        e.dyn_ref::<KeyboardEvent>()
+                            .filter(|e| e.code() == code)
+                            .cloned()
+                    };
+                    let onkeydown = {
+                        let talking = talking.clone();
+                        let pressed = pressed.clone();
+                        Closure::wrap(Box::new(move |e: Event| {
+                            if pressed(&e).is_some_and(|e| !e.repeat() && !push_to_talk::typing(&e))
+                            {
+                                talking.set(true);
+                            }
+                        }) as Box<dyn FnMut(Event)>)
+                    };
+                    let onkeyup = {
+                        let talking = talking.clone();
+                        Closure::wrap(Box::new(move |e: Event| {
+                            if pressed(&e).is_some() {
+                                talking.set(false);
+                            }
+                        }) as Box<dyn FnMut(Event)>)
+                    };
+                    let onblur = {
+                        let talking = talking.clone();
+                        Closure::wrap(
+                            Box::new(move |_: Event| talking.set(false)) as Box<dyn FnMut(Event)>
+                        )
+                    };
+                    let listeners = [("keydown", onkeydown), ("keyup", onkeyup), ("blur", onblur)];
+                    for (event, listener) in &listeners {
+                        let _ = window.add_event_listener_with_callback(
+                            event,
+                            listener.as_ref().unchecked_ref(),
+                        );
+                    }
+                    listeners
+                });
+                move || {
+                    for (event, listener) in listeners.iter().flatten() {
+                        let _ = window.remove_event_listener_with_callback(
+                            event,
+                            listener.as_ref().unchecked_ref(),
+                        );
+                    }
+                }
+            },
+            key,
+        );
+    }
+    use_effect_with_deps(
+        move |transmit: &bool| {
+            on_transmit.emit(*transmit);
+            || ()
+        },
+        transmit,
+    );
+    *talking
+}
@@ -0,0 +1,117 @@
+use web_sys::HtmlInputElement;
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::protocol::{RelayConfig, RelayStatus};
+
+#[derive(Properties, PartialEq)]
+pub struct RelaySettingsProps {
+    pub room: String,
+    /// `None` until the server has answered.
+    pub status: Option<RelayStatus>,
+    pub on_change: Callback<RelayConfig>,
+}
+
+/// Mirroring the room to a Discord or Slack channel through an incoming webhook.
+#[function_component(RelaySettings)]
+pub fn relay_settings(props: &RelaySettingsProps) -> Html {
+    let url = use_state(String::new);
+    let status = props.status.clone().unwrap_or_default();
+
+    let oninput = {
+        let url = url.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            url.set(input.value());
+        })
+    };
+    let onsubmit = {
+        let url = url.clone();
+        let on_change = props.on_change.clone();
+        Callback::from(move |e: FocusEvent| {
+            e.prevent_default();
+            if url.trim().is_empty() {
+                return;
+            }
+            on_change.emit(RelayConfig {
+                url: Some(url.trim().to_string()),
+                enabled: true,
+            });
+            url.set(String::new());
+        })
+    };
+    let toggle = {
+        let on_change = props.on_change.clone();
+        let enabled = status.enabled;
+        Callback::from(move |_| {
+            on_change.emit(RelayConfig {
+                url: None,
+                enabled: !enabled,
+            })
+        })
+    };
+    let remove = {
+        let on_change = props.on_change.clone();
+        Callback::from(move |_| {
+            on_change.emit(RelayConfig {
+                url: Some(String::new()),
+                enabled: false,
+            })
+        })
+    };
+
+    let summary = match (&props.status, &status.target) {
+        (None, _) => "Loading...".to_string(),
+        (_, None) => format!("#{} isn't mirrored anywhere.", props.room),
+        (_, Some(target)) if status.enabled => {
+            format!("Messages in #{} are mirrored to {}.", props.room, target)
+        }
+        (_, Some(target)) => format!("Mirroring #{} to {} is paused.", props.room, target),
+    };
+
+    html! {
+        <div class="w-full px-4 py-3 border-b border-gray-200 bg-gray-50 text-sm text-gray-700">
+            <div class="flex items-center gap-2">
+                <span>{summary}</span>
+                {
+                    if status.target.is_some() {
+                        html! {
+                            <>
+                                <button onclick={toggle} class="px-2 py-1 rounded-full text-xs bg-blue-100 text-blue-800 hover:bg-blue-200">
+                                    { if status.enabled { "Pause" } else { "Resume" } }
+                                </button>
+                                <button onclick={remove} class="px-2 py-1 rounded-full text-xs bg-gray-200 text-gray-700 hover:bg-gray-300">
+                                    {"Remove"}
+                                </button>
+                            </>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+            </div>
+            {
+                if let Some(error) = &status.error {
+                    html! { <div class="mt-1 text-red-700">{error.clone()}</div> }
+                } else {
+                    html! {}
+                }
+            }
+            <form {onsubmit} class="mt-2 flex gap-2">
+                <input
+                    {oninput}
+                    value={(*url).clone()}
+                    type="url"
+                    class="grow rounded-lg px-3 py-2 border border-gray-200 bg-white"
+                    placeholder={if status.target.is_some() { "New webhook URL" } else { "Discord or Slack webhook URL" }}
+                />
+                <button type="submit" disabled={url.trim().is_empty()} class="px-3 py-2 rounded-lg bg-blue-600 text-white disabled:opacity-50">
+                    {"Save"}
+                </button>
+            </form>
+            <div class="mt-1 text-xs text-gray-500">
+                {"Anyone in the room can see whether it is mirrored, but not the URL."}
+            </div>
+        </div>
+    }
+}
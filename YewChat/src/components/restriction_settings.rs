@@ -0,0 +1,42 @@
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::protocol::Restrictions;
+
+#[derive(Properties, PartialEq)]
+pub struct RestrictionSettingsProps {
+    pub restrictions: Restrictions,
+    /// Called with the new restrictions, which the server sends back to everyone once it has them.
+    pub on_change: Callback<Restrictions>,
+}
+
+/// What the room doesn't allow in messages, for its moderators. Sent as soon as a box is ticked.
+#[function_component(RestrictionSettings)]
+pub fn restriction_settings(props: &RestrictionSettingsProps) -> Html {
+    let field = |label: &str, refused: bool, set: fn(&mut Restrictions, bool)| {
+        let restrictions = props.restrictions;
+        let on_change = props.on_change.clone();
+        let onchange = Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let mut restrictions = restrictions;
+            set(&mut restrictions, input.checked());
+            on_change.emit(restrictions);
+        });
+        html! {
+            <label class="flex items-center gap-1">
+                <input type="checkbox" checked={refused} {onchange} />
+                {label.to_string()}
+            </label>
+        }
+    };
+    let r = props.restrictions;
+
+    html! {
+        <div class="w-full px-4 py-2 border-b border-gray-200 bg-gray-50 text-sm text-gray-700 flex flex-wrap items-center gap-x-4 gap-y-2">
+            <span>{"Refuse messages with:"}</span>
+            {field("links", r.links, |r, on| r.links = on)}
+            {field("images", r.images, |r, on| r.images = on)}
+            {field("attachments", r.attachments, |r, on| r.attachments = on)}
+        </div>
+    }
+}
@@ -0,0 +1,130 @@
+use std::rc::Rc;
+
+use web_sys::HtmlInputElement;
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::components::room_icon::RoomIcon;
+use crate::protocol::ListedRoom;
+
+#[derive(Properties, PartialEq)]
+pub struct RoomDirectoryProps {
+    /// The rooms anyone can enter, busiest first, or none while the server hasn't answered.
+    pub rooms: Option<Rc<Vec<ListedRoom>>>,
+    /// The room we are in, which has nothing to join.
+    pub current: String,
+    pub on_join: Callback<String>,
+    pub on_close: Callback<()>,
+}
+
+/// Whether `room` is one of those looked for, by its name or topic.
+fn matches(room: &ListedRoom, filter: &str) -> bool {
+    let filter = filter.to_lowercase();
+    room.name.to_lowercase().contains(&filter)
+        || room
+            .topic
+            .as_deref()
+            .is_some_and(|topic| topic.to_lowercase().contains(&filter))
+}
+
+/// The rooms anyone can enter, with what they are about and how many are in them, to pick one to
+/// go to. Invite-only rooms aren't listed; password-protected ones are, and ask for it on joining.
+#[function_component(RoomDirectory)]
+pub fn room_directory(props: &RoomDirectoryProps) -> Html {
+    let filter = use_state_eq(String::new);
+
+    let oninput = {
+        let filter = filter.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            filter.set(input.value().trim().to_string());
+        })
+    };
+    let close = props.on_close.reform(|_| ());
+    let view_room = |room: &ListedRoom| {
+        let join = {
+            let name = room.name.clone();
+            props.on_join.reform(move |_| name.clone())
+        };
+        html! {
+            <li class="flex items-center gap-3 py-2">
+                {
+                    match &room.icon {
+                        Some(icon) => html! { <RoomIcon icon={icon.clone()} class={classes!("w-8", "h-8", "text-2xl")} /> },
+                        None => html! {
+                            <span class="w-8 h-8 flex items-center justify-center rounded-md bg-gray-100 text-gray-400">{"#"}</span>
+                        },
+                    }
+                }
+                <div class="grow min-w-0">
+                    <div class="font-semibold flex items-center gap-1">
+                        {format!("#{}", room.name)}
+                        {
+                            if room.password_protected {
+                                html! { <span title="Password-protected" class="text-xs">{"🔑"}</span> }
+                            } else {
+                                html! {}
+                            }
+                        }
+                    </div>
+                    {
+                        if let Some(topic) = &room.topic {
+                            html! { <div class="text-xs text-gray-500 truncate" title={topic.clone()}>{topic.clone()}</div> }
+                        } else {
+                            html! {}
+                        }
+                    }
+                </div>
+                <span class="text-xs text-gray-500 whitespace-nowrap">{format!("{} online", room.members)}</span>
+                {
+                    if room.name == props.current {
+                        html! { <span class="w-14 text-center text-xs text-gray-400">{"Here"}</span> }
+                    } else {
+                        html! {
+                            <button onclick={join} class="w-14 py-1 rounded-lg bg-blue-600 text-white hover:bg-blue-700">
+                                {"Join"}
+                            </button>
+                        }
+                    }
+                }
+            </li>
+        }
+    };
+
+    html! {
+        <div class="fixed inset-0 z-40 flex items-center justify-center bg-black/40" onclick={close.clone()}>
+            <div
+                class="w-96 max-h-[80vh] p-6 rounded-2xl bg-white shadow-xl flex flex-col gap-4 text-sm text-gray-700"
+                onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}
+            >
+                <div class="flex items-center">
+                    <div class="text-lg font-semibold">{"Rooms"}</div>
+                    <button onclick={close} title="Close" class="ml-auto px-2 py-1 rounded-lg hover:bg-gray-100">{"✕"}</button>
+                </div>
+                <input
+                    {oninput}
+                    value={(*filter).clone()}
+                    placeholder="Search by name or topic"
+                    class="rounded-lg px-3 py-2 border border-gray-200"
+                />
+                {
+                    match &props.rooms {
+                        None => html! { <div class="text-gray-500">{"Loading…"}</div> },
+                        Some(rooms) => {
+                            let shown: Vec<_> = rooms.iter().filter(|room| matches(room, &filter)).collect();
+                            if shown.is_empty() {
+                                html! { <div class="text-gray-500">{"No room matches."}</div> }
+                            } else {
+                                html! {
+                                    <ul class="overflow-y-auto divide-y divide-gray-100">
+                                        { for shown.into_iter().map(view_room) }
+                                    </ul>
+                                }
+                            }
+                        }
+                    }
+                }
+            </div>
+        </div>
+    }
+}
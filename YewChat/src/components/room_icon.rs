@@ -0,0 +1,30 @@
+use yew::prelude::*;
+
+use crate::sanitize;
+
+#[derive(Properties, PartialEq)]
+pub struct RoomIconProps {
+    /// An emoji or other short text, or a `data:image/...` URL.
+    pub icon: String,
+    /// Size of the icon, as classes.
+    #[prop_or(classes!("w-6", "h-6"))]
+    pub class: Classes,
+}
+
+/// What a room's owner chose to show beside its name. Addresses that aren't pictures show nothing.
+#[function_component(RoomIcon)]
+pub fn room_icon(props: &RoomIconProps) -> Html {
+    if !props.icon.starts_with("data:") {
+        return html! {
+            <span class={classes!(props.class.clone(), "flex-none", "inline-flex", "items-center", "justify-center", "leading-none")}>
+                {props.icon.clone()}
+            </span>
+        };
+    }
+    match sanitize::image(&props.icon) {
+        Some(src) => html! {
+            <img {src} alt="" class={classes!(props.class.clone(), "flex-none", "rounded-md", "object-cover")} />
+        },
+        None => html! {},
+    }
+}
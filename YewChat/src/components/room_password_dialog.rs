@@ -0,0 +1,87 @@
+use web_sys::HtmlInputElement;
+use yew::functional::*;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct RoomPasswordDialogProps {
+    pub room: String,
+    /// What went wrong with the last password given, if one was.
+    #[prop_or_default]
+    pub error: Option<String>,
+    pub on_submit: Callback<String>,
+    pub on_close: Callback<()>,
+}
+
+/// Asks for the password of a room that wouldn't let us in without it.
+#[function_component(RoomPasswordDialog)]
+pub fn room_password_dialog(props: &RoomPasswordDialogProps) -> Html {
+    let password = use_state_eq(String::new);
+    let input = use_node_ref();
+
+    {
+        let input = input.clone();
+        use_effect_with_deps(
+            move |_| {
+                if let Some(input) = input.cast::<HtmlInputElement>() {
+                    let _ = input.focus();
+                }
+                || ()
+            },
+            props.error.clone(),
+        );
+    }
+    let oninput = {
+        let password = password.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            password.set(input.value());
+        })
+    };
+    let onsubmit = {
+        let password = password.clone();
+        let on_submit = props.on_submit.clone();
+        Callback::from(move |e: FocusEvent| {
+            e.prevent_default();
+            if !password.is_empty() {
+                on_submit.emit((*password).clone());
+                password.set(String::new());
+            }
+        })
+    };
+    let close = props.on_close.reform(|_| ());
+
+    html! {
+        <div class="fixed inset-0 z-40 flex items-center justify-center bg-black/40" onclick={close.clone()}>
+            <form
+                {onsubmit}
+                class="w-80 p-6 rounded-2xl bg-white shadow-xl flex flex-col gap-4 text-sm text-gray-700"
+                onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}
+            >
+                <div class="text-lg font-semibold">{format!("#{} has a password", props.room)}</div>
+                <div>{"Its owner can tell you what it is. Once you are in, you won't be asked again."}</div>
+                <input
+                    ref={input}
+                    type="password"
+                    autocomplete="off"
+                    {oninput}
+                    value={(*password).clone()}
+                    placeholder="Password"
+                    class="rounded-lg px-3 py-2 border border-gray-200"
+                />
+                {
+                    if let Some(error) = &props.error {
+                        html! { <div class="text-red-700">{error.clone()}</div> }
+                    } else {
+                        html! {}
+                    }
+                }
+                <div class="flex items-center justify-end gap-2">
+                    <button type="button" onclick={close} class="px-3 py-2 rounded-lg hover:bg-gray-100">{"Cancel"}</button>
+                    <button type="submit" disabled={password.is_empty()} class="px-3 py-2 rounded-lg bg-blue-600 text-white disabled:opacity-50">
+                        {"Enter"}
+                    </button>
+                </div>
+            </form>
+        </div>
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::protocol::{CustomRole, Permissions, RoomRoles};
+
+/// How many roles of its own a room can have.
+const MAX_ROOM_ROLES: usize = 10;
+/// Longest name a room's role can have.
+const MAX_ROLE_NAME_LEN: usize = 32;
+
+/// One column of the permission matrix.
+#[derive(Clone, Copy, PartialEq)]
+enum Permission {
+    Post,
+    Pin,
+    Invite,
+    Kick,
+}
+
+impl Permission {
+    const ALL: [Permission; 4] = [
+        Permission::Post,
+        Permission::Pin,
+        Permission::Invite,
+        Permission::Kick,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Permission::Post => "Post",
+            Permission::Pin => "Pin",
+            Permission::Invite => "Invite",
+            Permission::Kick => "Kick",
+        }
+    }
+
+    fn hint(self) -> &'static str {
+        match self {
+            Permission::Post => "Send messages",
+            Permission::Pin => "Pin messages and arrange the pins",
+            Permission::Invite => "Have the invite link, when the room is invite-only",
+            Permission::Kick => "Send members back to the default room",
+        }
+    }
+
+    fn of(self, permissions: &mut Permissions) -> &mut bool {
+        match self {
+            Permission::Post => &mut permissions.post,
+            Permission::Pin => &mut permissions.pin,
+            Permission::Invite => &mut permissions.invite,
+            Permission::Kick => &mut permissions.kick,
+        }
+    }
+}
+
+/// Adds a role that may do what everyone may, unless the room has as many as it can, one of that
+/// name already, or the name is blank or too long.
+fn add_role(roles: &mut RoomRoles, name: &str) -> bool {
+    let name = name.trim();
+    if name.is_empty()
+        || name.chars().count() > MAX_ROLE_NAME_LEN
+        || roles.roles.len() >= MAX_ROOM_ROLES
+        || roles.roles.iter().any(|r| r.name == name)
+    {
+        return false;
+    }
+    roles.roles.push(CustomRole {
+        name: name.to_string(),
+        permissions: roles.everyone,
+    });
+    true
+}
+
+/// Removes the role named, and with it the members', who go back to what everyone may do.
+fn remove_role(roles: &mut RoomRoles, name: &str) {
+    roles.roles.retain(|r| r.name != name);
+    roles.members.retain(|_, role| role != name);
+}
+
+#[derive(Properties, PartialEq)]
+pub struct RoomRolesEditorProps {
+    pub roles: Rc<RoomRoles>,
+    /// Everyone else in the room, who can be given a role.
+    pub members: Vec<String>,
+    pub on_save: Callback<RoomRoles>,
+    pub on_close: Callback<()>,
+}
+
+/// The room's own roles, for its owner: what each may do, as a matrix with a row for everyone
+/// else, and who has which. Nothing changes until saved.
+#[function_component(RoomRolesEditor)]
+pub fn room_roles_editor(props: &RoomRolesEditorProps) -> Html {
+    let draft = use_state_eq(|| (*props.roles).clone());
+    let new_role = use_state_eq(String::new);
+
+    let toggle = |row: Option<usize>, permission: Permission| {
+        let draft = draft.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let mut roles = (*draft).clone();
+            let permissions = match row {
+                Some(at) => &mut roles.roles[at].permissions,
+                None => &mut roles.everyone,
+            };
+            *permission.of(permissions) = input.checked();
+            draft.set(roles);
+        })
+    };
+    let view_row = |row: Option<usize>, name: &str, mut permissions: Permissions| {
+        let remove = row.map(|_| {
+            let draft = draft.clone();
+            let name = name.to_string();
+            Callback::from(move |_| {
+                let mut roles = (*draft).clone();
+                remove_role(&mut roles, &name);
+                draft.set(roles);
+            })
+        });
+        html! {
+            <tr class="border-t border-gray-100">
+                <td class={classes!("py-1", "pr-2", "truncate", row.is_none().then_some("text-gray-500"))}>{name.to_string()}</td>
+                {
+                    for Permission::ALL.into_iter().map(|permission| html! {
+                        <td class="text-center">
+                            <input
+                                type="checkbox"
+                                checked={*permission.of(&mut permissions)}
+                                onchange={toggle(row, permission)}
+                                title={format!("{}: {}", name, permission.hint())}
+                            />
+                        </td>
+                    })
+                }
+                <td class="text-right">
+                    {
+                        match remove {
+                            Some(remove) => html! {
+                                <button type="button" onclick={remove} title="Remove the role" class="px-1 text-gray-400 hover:text-red-700">{"✕"}</button>
+                            },
+                            None => html! {},
+                        }
+                    }
+                </td>
+            </tr>
+        }
+    };
+    let type_role = {
+        let new_role = new_role.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            new_role.set(input.value());
+        })
+    };
+    let add = {
+        let draft = draft.clone();
+        let new_role = new_role.clone();
+        Callback::from(move |_| {
+            let mut roles = (*draft).clone();
+            if add_role(&mut roles, &new_role) {
+                draft.set(roles);
+                new_role.set(String::new());
+            }
+        })
+    };
+    // Those given a role who aren't here now keep it, and can be seen to.
+    let members: BTreeSet<&String> = props.members.iter().chain(draft.members.keys()).collect();
+    let view_member = |member: &String| {
+        let onchange = {
+            let draft = draft.clone();
+            let member = member.clone();
+            Callback::from(move |e: Event| {
+                let select: HtmlSelectElement = e.target_unchecked_into();
+                let mut roles = (*draft).clone();
+                match select.value() {
+                    role if role.is_empty() => roles.members.remove(&member),
+                    role => roles.members.insert(member.clone(), role),
+                };
+                draft.set(roles);
+            })
+        };
+        let current = draft.members.get(member);
+        html! {
+            <label class="flex items-center gap-2">
+                <span class="grow truncate">{member.clone()}</span>
+                <select {onchange} class="w-32 rounded-lg px-2 py-1 border border-gray-200 bg-white">
+                    <option value="" selected={current.is_none()}>{"Everyone else"}</option>
+                    {
+                        for draft.roles.iter().map(|role| html! {
+                            <option value={role.name.clone()} selected={current == Some(&role.name)}>{role.name.clone()}</option>
+                        })
+                    }
+                </select>
+            </label>
+        }
+    };
+    let save = {
+        let draft = draft.clone();
+        let on_save = props.on_save.clone();
+        Callback::from(move |_| on_save.emit((*draft).clone()))
+    };
+    let close = props.on_close.reform(|_| ());
+
+    html! {
+        <div class="flex flex-col gap-4">
+            <table class="w-full table-fixed">
+                <thead>
+                    <tr class="text-xs text-gray-500">
+                        <th class="w-24 text-left font-normal">{"Role"}</th>
+                        {
+                            for Permission::ALL.into_iter().map(|permission| html! {
+                                <th class="font-normal" title={permission.hint()}>{permission.label()}</th>
+                            })
+                        }
+                        <th class="w-6" />
+                    </tr>
+                </thead>
+                <tbody>
+                    { view_row(None, "Everyone else", draft.everyone) }
+                    { for draft.roles.iter().enumerate().map(|(at, role)| view_row(Some(at), &role.name, role.permissions)) }
+                </tbody>
+            </table>
+            <div class="flex gap-2">
+                <input
+                    oninput={type_role}
+                    value={(*new_role).clone()}
+                    maxlength={MAX_ROLE_NAME_LEN.to_string()}
+                    placeholder="A new role"
+                    class="grow min-w-0 rounded-lg px-3 py-2 border border-gray-200"
+                />
+                <button
+                    type="button"
+                    onclick={add}
+                    disabled={new_role.trim().is_empty() || draft.roles.len() >= MAX_ROOM_ROLES}
+                    class="px-3 py-2 rounded-lg hover:bg-gray-100 disabled:opacity-50"
+                >
+                    {"Add"}
+                </button>
+            </div>
+            <div class="text-xs text-gray-500">
+                {"A member's role replaces what everyone else may do, so it can take a permission away as well as grant one. You and the administrators may do it all."}
+            </div>
+            {
+                if draft.roles.is_empty() || members.is_empty() {
+                    html! {}
+                } else {
+                    html! {
+                        <div class="pt-4 border-t border-gray-200 flex flex-col gap-2 max-h-48 overflow-y-auto">
+                            { for members.into_iter().map(view_member) }
+                        </div>
+                    }
+                }
+            }
+            <div class="flex items-center justify-end gap-2">
+                <button type="button" onclick={close} class="px-3 py-2 rounded-lg hover:bg-gray-100">{"Cancel"}</button>
+                <button
+                    type="button"
+                    onclick={save}
+                    disabled={*draft == *props.roles}
+                    class="px-3 py-2 rounded-lg bg-blue-600 text-white disabled:opacity-50"
+                >
+                    {"Save"}
+                </button>
+            </div>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn roles_start_from_everyone_and_leave_no_one_behind() {
+        let mut roles = RoomRoles::default();
+        roles.everyone.post = false;
+        assert!(add_role(&mut roles, " speaker "));
+        assert!(!add_role(&mut roles, "speaker"));
+        assert!(!add_role(&mut roles, "  "));
+        assert!(!add_role(&mut roles, &"x".repeat(MAX_ROLE_NAME_LEN + 1)));
+        assert_eq!(roles.roles[0].name, "speaker");
+        assert!(!roles.roles[0].permissions.post);
+
+        roles.members.insert("bob".into(), "speaker".into());
+        remove_role(&mut roles, "speaker");
+        assert!(roles.roles.is_empty());
+        assert!(roles.members.is_empty());
+
+        for at in 0..MAX_ROOM_ROLES {
+            assert!(add_role(&mut roles, &format!("role {}", at)));
+        }
+        assert!(!add_role(&mut roles, "one too many"));
+    }
+}
@@ -0,0 +1,559 @@
+use std::rc::Rc;
+
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::components::delete_room_dialog::DeleteRoomDialog;
+use crate::components::room_icon::RoomIcon;
+use crate::components::room_roles::RoomRolesEditor;
+use crate::components::share_dialog::invite_code_link;
+use crate::emoji;
+use crate::protocol::{Invite, RoomRoles, RoomUpdate};
+use crate::sanitize;
+use crate::services::attachment::{format_size, AttachmentReader};
+use crate::services::clipboard;
+use crate::time;
+use crate::DEFAULT_ROOM;
+
+/// Longest an icon can be when it is text rather than a picture, enough for any emoji.
+const MAX_ICON_TEXT_BYTES: usize = 32;
+/// Longest name a room can be renamed to.
+const MAX_NAME_LEN: usize = 32;
+/// Longest a room's topic can be.
+const MAX_TOPIC_LEN: usize = 200;
+
+#[derive(Properties, PartialEq)]
+pub struct RoomSettingsProps {
+    pub room: String,
+    pub icon: Option<String>,
+    pub topic: Option<String>,
+    pub invite_only: bool,
+    pub password_protected: bool,
+    /// The code that lets people into the room, once it is invite-only.
+    pub invite: Option<Invite>,
+    /// Everyone else in the room, who it can be handed to.
+    pub members: Vec<String>,
+    /// Called with the new settings, which the server sends back to everyone once it has them.
+    pub on_change: Callback<RoomUpdate>,
+    /// Hands the room to the member named.
+    pub on_transfer: Callback<String>,
+    /// Revokes the room's code, for a new one.
+    pub on_regenerate: Callback<()>,
+    /// Sets the room's password, or removes it.
+    pub on_password: Callback<Option<String>>,
+    pub roles: Rc<RoomRoles>,
+    /// Called with the room's new roles, which the server sends back to everyone once it has them.
+    pub on_room_roles: Callback<RoomRoles>,
+    /// Deletes the room, once its name was typed in to confirm.
+    pub on_delete: Callback<()>,
+    pub on_close: Callback<()>,
+}
+
+/// The parts of the settings, shown one at a time.
+#[derive(Clone, Copy, PartialEq)]
+enum Tab {
+    General,
+    Roles,
+}
+
+/// Whether the server takes `text` as a room's icon: an emoji or a few letters, in one piece.
+fn valid_text(text: &str) -> bool {
+    !text.is_empty()
+        && text.len() <= MAX_ICON_TEXT_BYTES
+        && !text.contains(char::is_whitespace)
+        && !text.starts_with("data:")
+}
+
+/// Whether the server takes `name` as a room's new name.
+fn valid_name(name: &str) -> bool {
+    (1..=MAX_NAME_LEN).contains(&name.chars().count())
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// The room's settings, for its owner: its name and topic, and the icon shown beside it, typed in
+/// as an emoji or uploaded as a picture. Renaming takes everyone in the room along, as does
+/// deleting it; the default room can be neither renamed nor deleted. An invite-only room lets in
+/// those it already has and whoever opens its invite link, whose code can be replaced when it got
+/// around too far; a password-protected one, whoever types its password. The owner can also hand
+/// the room to someone else in it, and with it these settings. The roles tab sets who else may do
+/// what in the room.
+#[function_component(RoomSettings)]
+pub fn room_settings(props: &RoomSettingsProps) -> Html {
+    let tab = use_state_eq(|| Tab::General);
+    let name = use_state_eq(|| props.room.clone());
+    let icon = use_state_eq(|| props.icon.clone());
+    let topic = use_state_eq(|| props.topic.clone().unwrap_or_default());
+    let invite_only = use_state_eq(|| props.invite_only);
+    let copied = use_state_eq(|| false);
+    let password = use_state_eq(String::new);
+    let error = use_state_eq(|| Option::<String>::None);
+    let reader = use_mut_ref(|| Option::<AttachmentReader>::None);
+    let deleting = use_state_eq(|| false);
+    let heir = use_state_eq(String::new);
+
+    let rename = {
+        let name = name.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            name.set(input.value().trim().to_string());
+        })
+    };
+    let retopic = {
+        let topic = topic.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            topic.set(input.value());
+        })
+    };
+    let oninput = {
+        let icon = icon.clone();
+        let error = error.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let text = input.value().trim().to_string();
+            if text.is_empty() || valid_text(&text) {
+                error.set(None);
+            } else {
+                error.set(Some(format!(
+                    "Icons are one emoji or a few letters, up to {} bytes.",
+                    MAX_ICON_TEXT_BYTES
+                )));
+            }
+            icon.set((!text.is_empty()).then_some(text));
+        })
+    };
+    let onchange = {
+        let icon = icon.clone();
+        let error = error.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            if !file.type_().starts_with("image/") {
+                error.set(Some(format!("{} isn't a picture", file.name())));
+                return;
+            }
+            if file.size() > emoji::MAX_BYTES {
+                error.set(Some(format!(
+                    "{} is too large ({}, limit is {})",
+                    file.name(),
+                    format_size(file.size()),
+                    format_size(emoji::MAX_BYTES)
+                )));
+                return;
+            }
+            error.set(None);
+            let done = {
+                let icon = icon.clone();
+                let error = error.clone();
+                Callback::from(move |read: Result<_, String>| match read {
+                    Ok(attachment) => icon.set(sanitize::attachment(&attachment)),
+                    Err(e) => error.set(Some(e)),
+                })
+            };
+            match AttachmentReader::read(file, Callback::noop(), done) {
+                Ok(started) => *reader.borrow_mut() = Some(started),
+                Err(e) => error.set(Some(e)),
+            }
+        })
+    };
+    let remove = {
+        let icon = icon.clone();
+        let error = error.clone();
+        Callback::from(move |_| {
+            icon.set(None);
+            error.set(None);
+        })
+    };
+    let close = props.on_close.reform(|_| ());
+    let name_valid = valid_name(&name);
+    let onsubmit = {
+        let name = name.clone();
+        let icon = icon.clone();
+        let topic = topic.clone();
+        let invite_only = invite_only.clone();
+        let on_change = props.on_change.clone();
+        let on_close = props.on_close.clone();
+        Callback::from(move |e: FocusEvent| {
+            e.prevent_default();
+            on_change.emit(RoomUpdate {
+                name: Some((*name).clone()),
+                icon: (*icon).clone(),
+                topic: Some(topic.trim().to_string()).filter(|topic| !topic.is_empty()),
+                invite_only: *invite_only,
+                // Set with its own message, so the password never goes out with the rest.
+                password_protected: false,
+            });
+            on_close.emit(());
+        })
+    };
+    if *tab == Tab::Roles {
+        let on_save = {
+            let on_room_roles = props.on_room_roles.clone();
+            let on_close = props.on_close.clone();
+            Callback::from(move |roles| {
+                on_room_roles.emit(roles);
+                on_close.emit(());
+            })
+        };
+        return html! {
+            <div class="fixed inset-0 z-40 flex items-center justify-center bg-black/40" onclick={close}>
+                <div
+                    class="w-80 p-6 rounded-2xl bg-white shadow-xl flex flex-col gap-4 text-sm text-gray-700"
+                    onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}
+                >
+                    <div class="text-lg font-semibold">{format!("Settings of #{}", props.room)}</div>
+                    { view_tabs(&tab) }
+                    <RoomRolesEditor
+                        roles={props.roles.clone()}
+                        members={props.members.clone()}
+                        {on_save}
+                        on_close={props.on_close.clone()}
+                    />
+                </div>
+            </div>
+        };
+    }
+    if *deleting {
+        let back = Callback::from(move |_| deleting.set(false));
+        return html! {
+            <DeleteRoomDialog room={props.room.clone()} on_delete={props.on_delete.clone()} on_close={back} />
+        };
+    }
+    let choose_heir = {
+        let heir = heir.clone();
+        Callback::from(move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            heir.set(select.value());
+        })
+    };
+    let transfer = {
+        let heir = heir.clone();
+        let on_transfer = props.on_transfer.clone();
+        Callback::from(move |_| {
+            if !heir.is_empty() {
+                on_transfer.emit((*heir).clone());
+            }
+        })
+    };
+    let toggle_invite_only = {
+        let invite_only = invite_only.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            invite_only.set(input.checked());
+        })
+    };
+    let link = props
+        .invite
+        .as_ref()
+        .map(|invite| invite_code_link(&props.room, &invite.code));
+    let copy = {
+        let link = link.clone().unwrap_or_default();
+        let copied = copied.clone();
+        Callback::from(move |_| match clipboard::write_text(&link) {
+            Ok(()) => copied.set(true),
+            Err(e) => log::error!("copy failed: {:?}", e),
+        })
+    };
+    let regenerate = {
+        let copied = copied.clone();
+        let on_regenerate = props.on_regenerate.clone();
+        Callback::from(move |_| {
+            copied.set(false);
+            on_regenerate.emit(());
+        })
+    };
+    let type_password = {
+        let password = password.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            password.set(input.value());
+        })
+    };
+    let set_password = {
+        let password = password.clone();
+        let on_password = props.on_password.clone();
+        Callback::from(move |_| {
+            if !password.is_empty() {
+                on_password.emit(Some((*password).clone()));
+                password.set(String::new());
+            }
+        })
+    };
+    let remove_password = props.on_password.reform(|_| None);
+    let confirm_delete = {
+        let deleting = deleting.clone();
+        Callback::from(move |_| deleting.set(true))
+    };
+    let picture = icon.as_deref().is_some_and(|i| i.starts_with("data:"));
+    let text = if picture {
+        String::new()
+    } else {
+        icon.as_deref().unwrap_or_default().to_string()
+    };
+
+    html! {
+        <div class="fixed inset-0 z-40 flex items-center justify-center bg-black/40" onclick={close.clone()}>
+            <form
+                {onsubmit}
+                class="w-80 p-6 rounded-2xl bg-white shadow-xl flex flex-col gap-4 text-sm text-gray-700"
+                onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}
+            >
+                <div class="text-lg font-semibold">{format!("Settings of #{}", props.room)}</div>
+                { view_tabs(&tab) }
+                <label class="flex flex-col gap-1">
+                    <span>{"Name"}</span>
+                    <input
+                        oninput={rename}
+                        value={(*name).clone()}
+                        disabled={props.room == DEFAULT_ROOM}
+                        maxlength={MAX_NAME_LEN.to_string()}
+                        class="rounded-lg px-3 py-2 border border-gray-200 disabled:bg-gray-50"
+                    />
+                    <span class="text-xs text-gray-500">
+                        {
+                            if props.room == DEFAULT_ROOM {
+                                "Everyone starts here, so it keeps its name."
+                            } else if name_valid {
+                                "Renaming takes everyone in the room along, and is noted in it."
+                            } else {
+                                "Names are letters, digits, - or _."
+                            }
+                        }
+                    </span>
+                </label>
+                <label class="flex flex-col gap-1">
+                    <span>{"Topic"}</span>
+                    <input
+                        oninput={retopic}
+                        value={(*topic).clone()}
+                        maxlength={MAX_TOPIC_LEN.to_string()}
+                        placeholder="What the room is about"
+                        class="rounded-lg px-3 py-2 border border-gray-200"
+                    />
+                    <span class="text-xs text-gray-500">{"Shown under the room's name and in the list of rooms."}</span>
+                </label>
+                <div class="flex items-center gap-3">
+                    {
+                        match &*icon {
+                            Some(icon) => html! { <RoomIcon icon={icon.clone()} class={classes!("w-12", "h-12", "text-4xl")} /> },
+                            None => html! {
+                                <span class="w-12 h-12 flex items-center justify-center rounded-md bg-gray-100 text-2xl text-gray-400">{"#"}</span>
+                            },
+                        }
+                    }
+                    <div class="grow flex flex-col gap-2">
+                        <input
+                            {oninput}
+                            value={text}
+                            class="rounded-lg px-3 py-2 border border-gray-200"
+                            placeholder={if picture { "a picture is set" } else { "an emoji" }}
+                        />
+                        <input {onchange} type="file" accept="image/*" class="text-xs" />
+                    </div>
+                </div>
+                {
+                    if let Some(error) = &*error {
+                        html! { <div class="text-red-700">{error.clone()}</div> }
+                    } else {
+                        html! {}
+                    }
+                }
+                <div class="text-xs text-gray-500">
+                    {format!(
+                        "The icon is shown beside the room's name, to everyone. Pictures can be up to {}.",
+                        format_size(emoji::MAX_BYTES)
+                    )}
+                </div>
+                <label class="flex items-start gap-2">
+                    <input
+                        type="checkbox"
+                        checked={*invite_only}
+                        disabled={props.room == DEFAULT_ROOM}
+                        onchange={toggle_invite_only}
+                        class="mt-1"
+                    />
+                    <span class="flex flex-col">
+                        <span>{"Invite-only"}</span>
+                        <span class="text-xs text-gray-500">
+                            {
+                                if props.room == DEFAULT_ROOM {
+                                    "Everyone starts here, so it is open to everyone."
+                                } else {
+                                    "Only those already here, and whoever opens the invite link, can come in."
+                                }
+                            }
+                        </span>
+                    </span>
+                </label>
+                <div class="flex items-center gap-2">
+                    <button type="button" onclick={remove} disabled={icon.is_none()} class="px-3 py-2 rounded-lg hover:bg-gray-100 disabled:opacity-50">
+                        {"Remove icon"}
+                    </button>
+                    <span class="grow" />
+                    <button type="button" onclick={close} class="px-3 py-2 rounded-lg hover:bg-gray-100">{"Cancel"}</button>
+                    <button type="submit" disabled={error.is_some() || !name_valid} class="px-3 py-2 rounded-lg bg-blue-600 text-white disabled:opacity-50">
+                        {"Save"}
+                    </button>
+                </div>
+                {
+                    if props.room == DEFAULT_ROOM {
+                        html! {}
+                    } else {
+                        html! {
+                            <div class="pt-4 border-t border-gray-200 flex flex-col gap-2">
+                                <div class="flex gap-2">
+                                    <input
+                                        type="password"
+                                        autocomplete="new-password"
+                                        oninput={type_password}
+                                        value={(*password).clone()}
+                                        placeholder={if props.password_protected { "A new password" } else { "A password to enter" }}
+                                        class="grow min-w-0 rounded-lg px-3 py-2 border border-gray-200"
+                                    />
+                                    <button type="button" onclick={set_password} disabled={password.is_empty()} class="px-3 py-2 rounded-lg hover:bg-gray-100 disabled:opacity-50">
+                                        {"Set"}
+                                    </button>
+                                </div>
+                                <div class="flex items-center gap-2 text-xs text-gray-500">
+                                    <span class="grow">
+                                        {
+                                            if props.password_protected {
+                                                "Those not here yet are asked for the password."
+                                            } else {
+                                                "No password: anyone can come in."
+                                            }
+                                        }
+                                    </span>
+                                    {
+                                        if props.password_protected {
+                                            html! {
+                                                <button type="button" onclick={remove_password} class="px-2 py-1 rounded-lg text-sm text-gray-700 hover:bg-gray-100">
+                                                    {"Remove"}
+                                                </button>
+                                            }
+                                        } else {
+                                            html! {}
+                                        }
+                                    }
+                                </div>
+                            </div>
+                        }
+                    }
+                }
+                {
+                    match (&props.invite, link) {
+                        (Some(invite), Some(link)) if props.invite_only => html! {
+                            <div class="pt-4 border-t border-gray-200 flex flex-col gap-2">
+                                <div class="flex gap-2">
+                                    <input readonly=true value={link} class="grow min-w-0 rounded-lg px-3 py-2 border border-gray-200 bg-gray-50 text-xs" />
+                                    <button type="button" onclick={copy} class="px-3 py-2 rounded-lg hover:bg-gray-100">
+                                        { if *copied { "Copied" } else { "Copy" } }
+                                    </button>
+                                </div>
+                                <div class="flex items-center gap-2 text-xs text-gray-500">
+                                    <span class="grow">{format!("Works until {}.", time::absolute_label(invite.expires_at))}</span>
+                                    <button
+                                        type="button"
+                                        onclick={regenerate}
+                                        title="The current link stops working; those it let in stay"
+                                        class="px-2 py-1 rounded-lg text-sm text-gray-700 hover:bg-gray-100"
+                                    >
+                                        {"New link"}
+                                    </button>
+                                </div>
+                            </div>
+                        },
+                        _ => html! {},
+                    }
+                }
+                <div class="pt-4 border-t border-gray-200 flex items-center gap-2">
+                    <select onchange={choose_heir} disabled={props.members.is_empty()} class="grow min-w-0 rounded-lg px-2 py-2 border border-gray-200 bg-white">
+                        <option value="" selected={heir.is_empty()}>
+                            { if props.members.is_empty() { "No one else is here" } else { "Hand the room to…" } }
+                        </option>
+                        {
+                            props.members.iter().map(|name| html! {
+                                <option value={name.clone()} selected={*name == *heir}>{name.clone()}</option>
+                            }).collect::<Html>()
+                        }
+                    </select>
+                    <button
+                        type="button"
+                        onclick={transfer}
+                        disabled={heir.is_empty()}
+                        title="They become the owner, and you an ordinary member"
+                        class="px-3 py-2 rounded-lg hover:bg-gray-100 disabled:opacity-50"
+                    >
+                        {"Hand over"}
+                    </button>
+                </div>
+                {
+                    if props.room == DEFAULT_ROOM {
+                        html! {}
+                    } else {
+                        html! {
+                            <button type="button" onclick={confirm_delete} class="self-start px-3 py-2 rounded-lg text-red-700 hover:bg-red-50">
+                                {"Delete room…"}
+                            </button>
+                        }
+                    }
+                }
+            </form>
+        </div>
+    }
+}
+
+fn view_tabs(tab: &UseStateHandle<Tab>) -> Html {
+    let view_tab = |which: Tab, label: &str| {
+        let onclick = {
+            let tab = tab.clone();
+            Callback::from(move |_| tab.set(which))
+        };
+        html! {
+            <button type="button" {onclick} class={classes!(
+                "px-3", "py-1", "border-b-2", "-mb-px",
+                if **tab == which { vec!["border-blue-600", "text-blue-700"] } else { vec!["border-transparent", "text-gray-500", "hover:text-gray-700"] }
+            )}>
+                {label.to_string()}
+            </button>
+        }
+    };
+    html! {
+        <div class="flex gap-2 border-b border-gray-200">
+            { view_tab(Tab::General, "General") }
+            { view_tab(Tab::Roles, "Roles") }
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn names_are_one_short_word() {
+        assert!(valid_name("kittens"));
+        assert!(valid_name("café_2-b"));
+        assert!(!valid_name(""));
+        assert!(!valid_name("cat pictures"));
+        assert!(!valid_name("a/b"));
+        assert!(!valid_name(&"x".repeat(MAX_NAME_LEN + 1)));
+    }
+
+    #[wasm_bindgen_test]
+    fn icons_are_one_short_piece_of_text() {
+        assert!(valid_text("🐈"));
+        assert!(valid_text("👩‍💻"));
+        assert!(valid_text("JS"));
+        assert!(!valid_text(""));
+        assert!(!valid_text("two words"));
+        assert!(!valid_text(&"x".repeat(MAX_ICON_TEXT_BYTES + 1)));
+        assert!(!valid_text("data:text/html,<b>"));
+    }
+}
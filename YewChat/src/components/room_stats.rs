@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use yew::prelude::*;
+
+use crate::protocol::MessageData;
+
+const HOUR_MS: f64 = 3_600_000.0;
+/// How many hours back the chart goes.
+pub const HOURS: usize = 24;
+/// How many of the most active users are listed.
+const TOP: usize = 5;
+
+/// Messages sent in each of the last [`HOURS`] hours, oldest hour first. Messages without a time
+/// or older than that are left out.
+pub fn per_hour(messages: &[Rc<MessageData>], now: f64) -> [usize; HOURS] {
+    let mut counts = [0; HOURS];
+    for time in messages.iter().filter_map(|m| m.time) {
+        let ago = ((now - time) / HOUR_MS).floor();
+        if (0.0..HOURS as f64).contains(&ago) {
+            counts[HOURS - 1 - ago as usize] += 1;
+        }
+    }
+    counts
+}
+
+/// Who sent the most messages, most first; ties go by name.
+pub fn most_active(messages: &[Rc<MessageData>]) -> Vec<(String, usize)> {
+    let mut counts = HashMap::<&str, usize>::new();
+    for m in messages {
+        *counts.entry(&m.from).or_default() += 1;
+    }
+    let mut ranked: Vec<_> = counts
+        .into_iter()
+        .map(|(name, count)| (name.to_string(), count))
+        .collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked.truncate(TOP);
+    ranked
+}
+
+#[derive(Properties, PartialEq)]
+pub struct RoomStatsProps {
+    pub room: String,
+    pub messages: Vec<Rc<MessageData>>,
+    pub now: f64,
+}
+
+/// Popover charting the room's messages per hour and its most active users. Only counts the
+/// messages loaded in this tab.
+#[function_component(RoomStats)]
+pub fn room_stats(props: &RoomStatsProps) -> Html {
+    let hours = per_hour(&props.messages, props.now);
+    let busiest = hours.iter().copied().max().unwrap_or(0).max(1);
+    let ranked = most_active(&props.messages);
+
+    html! {
+        <div class="absolute right-0 mt-1 w-80 p-4 bg-white rounded-lg shadow-lg border border-gray-200 z-20 flex flex-col gap-3 text-sm">
+            <div class="font-semibold">{format!("# {} · {} messages loaded", props.room, props.messages.len())}</div>
+            <div>
+                <div class="text-xs text-gray-500 mb-1">{"Messages per hour, last 24 hours"}</div>
+                <div class="flex items-end gap-px h-20 border-b border-gray-200">
+                    {
+                        hours.iter().enumerate().map(|(i, &count)| {
+                            let ago = HOURS - 1 - i;
+                            let title = match ago {
+                                0 => format!("{} this hour", count),
+                                1 => format!("{} an hour ago", count),
+                                _ => format!("{} {} hours ago", count, ago),
+                            };
+                            html! {
+                                <div
+                                    {title}
+                                    class="grow bg-blue-400 hover:bg-blue-600 rounded-t-sm"
+                                    style={format!("height: {}%", count * 100 / busiest)}
+                                ></div>
+                            }
+                        }).collect::<Html>()
+                    }
+                </div>
+                <div class="flex justify-between text-xs text-gray-400">
+                    <span>{"24h ago"}</span>
+                    <span>{"now"}</span>
+                </div>
+            </div>
+            <div>
+                <div class="text-xs text-gray-500 mb-1">{"Most active"}</div>
+                {
+                    if ranked.is_empty() {
+                        html! { <div class="text-gray-500">{"Nobody has said anything yet."}</div> }
+                    } else {
+                        ranked.iter().map(|(name, count)| html! {
+                            <div class="flex justify-between py-0.5">
+                                <span>{name.clone()}</span>
+                                <span class="text-gray-500 tabular-nums">{count}</span>
+                            </div>
+                        }).collect::<Html>()
+                    }
+                }
+            </div>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    fn message(from: &str, time: Option<f64>) -> Rc<MessageData> {
+        Rc::new(MessageData {
+            id: None,
+            from: from.into(),
+            message: "hi".into(),
+            attachment: None,
+            time,
+            bot: None,
+            to: None,
+            announcement: false,
+            pinned_until: None,
+            system: false,
+        })
+    }
+
+    #[wasm_bindgen_test]
+    fn counts_by_hour_and_sender() {
+        let now = 100.0 * HOUR_MS;
+        let messages = [
+            message("bob", Some(now - 10.0)),
+            message("alice", Some(now - 1.5 * HOUR_MS)),
+            message("alice", Some(now - 30.0 * HOUR_MS)),
+            message("carol", None),
+            message("alice", Some(now - 10.0)),
+        ];
+        let hours = per_hour(&messages, now);
+        assert_eq!(hours[HOURS - 1], 2);
+        assert_eq!(hours[HOURS - 2], 1);
+        assert_eq!(hours.iter().sum::<usize>(), 3);
+
+        let ranked = most_active(&messages);
+        assert_eq!(
+            ranked,
+            [("alice".into(), 3), ("bob".into(), 1), ("carol".into(), 1)]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::rc::Rc;
+
+use yew::prelude::*;
+
+use crate::components::message_list::anchor;
+use crate::components::share_dialog::invite_link;
+use crate::services::attachment::format_size;
+use crate::store::Bookmark;
+use crate::time;
+
+#[derive(Properties, PartialEq)]
+pub struct SavedMessagesProps {
+    pub bookmarks: Rc<Vec<Bookmark>>,
+    /// Reference point for relative timestamps.
+    pub now: f64,
+    pub on_remove: Callback<Bookmark>,
+    pub on_close: Callback<()>,
+}
+
+/// The messages saved from any room, each linking back to where it was said.
+#[function_component(SavedMessages)]
+pub fn saved_messages(props: &SavedMessagesProps) -> Html {
+    let close = props.on_close.reform(|_| ());
+
+    html! {
+        <div class="fixed inset-0 z-40 flex items-center justify-center bg-black/40" onclick={close.clone()}>
+            <div
+                class="w-[32rem] max-w-full max-h-[80vh] p-6 rounded-2xl bg-white shadow-xl flex flex-col gap-4"
+                onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}
+            >
+                <div class="text-lg font-semibold">{"Saved messages"}</div>
+                <div class="grow overflow-y-auto flex flex-col gap-3">
+                    {
+                        if props.bookmarks.is_empty() {
+                            html! {
+                                <div class="text-sm text-gray-500">
+                                    {"Nothing saved yet. Save a message from its ⋯ menu to find it here later."}
+                                </div>
+                            }
+                        } else {
+                            props.bookmarks.iter().map(|b| view_bookmark(b, props)).collect::<Html>()
+                        }
+                    }
+                </div>
+                <button onclick={close} class="text-sm text-gray-500 hover:text-gray-800">{"Close"}</button>
+            </div>
+        </div>
+    }
+}
+
+fn view_bookmark(bookmark: &Bookmark, props: &SavedMessagesProps) -> Html {
+    let m = &bookmark.message;
+    let link =
+        m.id.as_deref()
+            .map(|id| format!("{}#{}", invite_link(&bookmark.room), anchor(id)));
+    let remove = {
+        let on_remove = props.on_remove.clone();
+        let bookmark = bookmark.clone();
+        Callback::from(move |_| on_remove.emit(bookmark.clone()))
+    };
+
+    html! {
+        <div class="p-3 rounded-lg border border-gray-200 text-sm">
+            <div class="flex items-center gap-2 text-xs text-gray-500">
+                <span class="font-medium text-gray-800">{m.from.clone()}</span>
+                <span>{format!("in #{}", bookmark.room)}</span>
+                {
+                    match m.time {
+                        Some(t) => html! { <span title={time::absolute_label(t)}>{time::relative_label(t, props.now)}</span> },
+                        None => html! {},
+                    }
+                }
+                <span class="grow"></span>
+                {
+                    match link {
+                        Some(href) => html! {
+                            <a {href} onclick={props.on_close.reform(|_| ())} class="text-blue-700 hover:underline">{"Open"}</a>
+                        },
+                        None => html! {},
+                    }
+                }
+                <button onclick={remove} title="Remove from saved" class="px-1 rounded hover:bg-gray-100">{"×"}</button>
+            </div>
+            <div class="mt-1 max-h-24 overflow-hidden whitespace-pre-wrap break-words text-gray-700">
+                {
+                    match &m.attachment {
+                        Some(a) => format!("{} ({})", a.name, format_size(a.size as f64)),
+                        None => m.message.clone(),
+                    }
+                }
+            </div>
+        </div>
+    }
+}
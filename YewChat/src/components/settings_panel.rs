@@ -0,0 +1,124 @@
+use std::rc::Rc;
+
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::avatar::AvatarSettings;
+use crate::components::activity_log::ActivityLog;
+use crate::components::audio_settings::AudioSettings;
+use crate::components::avatar_settings::AvatarSettingsPanel;
+use crate::components::away_settings::AwaySettings;
+use crate::components::keyword_settings::KeywordSettings;
+use crate::components::push_to_talk_settings::PushToTalkSettings;
+use crate::components::spam_settings::SpamSettingsPanel;
+use crate::services::spam::SpamSettings;
+use crate::services::{audio_devices, keywords, push_to_talk, system_lines};
+
+/// What we chose for how the chat looks and sounds, kept in this browser for each account.
+#[derive(Clone, PartialEq)]
+pub struct Preferences {
+    username: String,
+    pub keywords: UseStateHandle<Rc<Vec<String>>>,
+    pub spam: UseStateHandle<SpamSettings>,
+    /// The key held to talk, as a `KeyboardEvent.code`, or none to always be heard.
+    pub talk_key: UseStateHandle<Option<String>>,
+    pub speaker: UseStateHandle<Option<String>>,
+    /// Whether joins, leaves and other system lines are left out of the room.
+    pub hide_system: UseStateHandle<bool>,
+}
+
+impl Preferences {
+    /// Shows the system lines if they were hidden, or else hides them.
+    pub fn toggle_system(&self) {
+        system_lines::set_hidden(&self.username, !*self.hide_system);
+        self.hide_system.set(!*self.hide_system);
+    }
+}
+
+/// The preferences of `username`, as they were left.
+pub fn use_preferences(username: &str) -> Preferences {
+    let keywords = {
+        let username = username.to_string();
+        use_state(move || Rc::new(keywords::load(&username)))
+    };
+    let spam = {
+        let username = username.to_string();
+        use_state(move || SpamSettings::load(&username))
+    };
+    let talk_key = {
+        let username = username.to_string();
+        use_state(move || push_to_talk::key(&username))
+    };
+    let speaker = use_state(audio_devices::speaker);
+    let hide_system = {
+        let username = username.to_string();
+        use_state(move || system_lines::hidden(&username))
+    };
+    Preferences {
+        username: username.to_string(),
+        keywords,
+        spam,
+        talk_key,
+        speaker,
+        hide_system,
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct SettingsPanelProps {
+    pub username: String,
+    pub preferences: Preferences,
+    /// Our new picture, once saved.
+    pub on_avatar_change: Callback<AvatarSettings>,
+}
+
+/// Our own settings, opened from our entry in the sidebar: our picture, when to show as away,
+/// push-to-talk, where calls are heard, the keywords to highlight, what counts as spam, and the
+/// activity log. Each is saved as soon as it is changed.
+#[function_component(SettingsPanel)]
+pub fn settings_panel(props: &SettingsPanelProps) -> Html {
+    let preferences = &props.preferences;
+    let on_talk_key_change = {
+        let talk_key = preferences.talk_key.clone();
+        Callback::from(move |chosen: Option<String>| talk_key.set(chosen))
+    };
+    let on_speaker_change = {
+        let speaker = preferences.speaker.clone();
+        Callback::from(move |chosen: Option<String>| speaker.set(chosen))
+    };
+    let on_keywords_change = {
+        let keywords = preferences.keywords.clone();
+        Callback::from(move |chosen: Vec<String>| keywords.set(Rc::new(chosen)))
+    };
+    let on_spam_settings_change = {
+        let spam = preferences.spam.clone();
+        Callback::from(move |chosen: SpamSettings| spam.set(chosen))
+    };
+
+    html! {
+        <>
+            <AvatarSettingsPanel
+                username={props.username.clone()}
+                on_change={props.on_avatar_change.clone()}
+            />
+            <AwaySettings username={props.username.clone()} />
+            <PushToTalkSettings
+                username={props.username.clone()}
+                binding={(*preferences.talk_key).clone()}
+                on_change={on_talk_key_change}
+            />
+            <AudioSettings speaker={(*preferences.speaker).clone()} {on_speaker_change} />
+            <KeywordSettings
+                username={props.username.clone()}
+                keywords={(*preferences.keywords).clone()}
+                on_change={on_keywords_change}
+            />
+            <SpamSettingsPanel
+                username={props.username.clone()}
+                settings={*preferences.spam}
+                on_change={on_spam_settings_change}
+            />
+            <ActivityLog username={props.username.clone()} />
+        </>
+    }
+}
@@ -0,0 +1,188 @@
+use qrcode::{Color, QrCode};
+use yew::functional::*;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::components::room_icon::RoomIcon;
+use crate::protocol::Invite;
+use crate::services::clipboard;
+use crate::time;
+use crate::Route;
+
+/// Blank modules around the code, which scanners need to find its edges.
+const QUIET_ZONE: usize = 4;
+
+#[derive(Properties, PartialEq)]
+pub struct ShareDialogProps {
+    pub room: String,
+    /// The room's icon, shown above the invite.
+    #[prop_or_default]
+    pub icon: Option<String>,
+    /// Whether the room only lets in those with its code.
+    #[prop_or_default]
+    pub invite_only: bool,
+    /// The room's code, which only its owner has.
+    #[prop_or_default]
+    pub invite: Option<Invite>,
+    pub on_close: Callback<()>,
+}
+
+/// The room's invite link, with a QR code to open it on a phone. The link to an invite-only room
+/// has its code, so only its owner can share one.
+#[function_component(ShareDialog)]
+pub fn share_dialog(props: &ShareDialogProps) -> Html {
+    let copied = use_state(|| false);
+    let link = match &props.invite {
+        Some(invite) => invite_code_link(&props.room, &invite.code),
+        None => invite_link(&props.room),
+    };
+
+    let copy = {
+        let link = link.clone();
+        let copied = copied.clone();
+        Callback::from(move |_| match clipboard::write_text(&link) {
+            Ok(()) => copied.set(true),
+            Err(e) => log::error!("copy failed: {:?}", e),
+        })
+    };
+    let close = props.on_close.reform(|_| ());
+    let icon = match &props.icon {
+        Some(icon) => {
+            html! { <RoomIcon icon={icon.clone()} class={classes!("w-12", "h-12", "text-4xl")} /> }
+        }
+        None => html! {},
+    };
+    if props.invite_only && props.invite.is_none() {
+        return html! {
+            <div class="fixed inset-0 z-40 flex items-center justify-center bg-black/40" onclick={close.clone()}>
+                <div
+                    class="w-80 p-6 rounded-2xl bg-white shadow-xl flex flex-col items-center gap-4 text-center"
+                    onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}
+                >
+                    {icon}
+                    <div class="text-lg font-semibold">{format!("Invite to #{}", props.room)}</div>
+                    <div class="text-sm text-gray-600">
+                        {"The room is invite-only, so only its owner can share a link that lets people in."}
+                    </div>
+                    <button onclick={close} class="text-sm text-gray-500 hover:text-gray-800">{"Close"}</button>
+                </div>
+            </div>
+        };
+    }
+
+    html! {
+        <div class="fixed inset-0 z-40 flex items-center justify-center bg-black/40" onclick={close.clone()}>
+            <div
+                class="w-80 p-6 rounded-2xl bg-white shadow-xl flex flex-col items-center gap-4"
+                onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}
+            >
+                {icon}
+                <div class="text-lg font-semibold">{format!("Invite to #{}", props.room)}</div>
+                {
+                    match qr_path(&link) {
+                        Some((size, path)) => html! {
+                            <svg
+                                class="w-56 h-56"
+                                viewBox={format!("0 0 {0} {0}", size)}
+                                shape-rendering="crispEdges"
+                                role="img"
+                                aria-label="QR code of the invite link"
+                            >
+                                <rect width="100%" height="100%" fill="white" />
+                                <path d={path} fill="black" />
+                            </svg>
+                        },
+                        None => html! {
+                            <div class="text-sm text-gray-500">{"This link is too long for a QR code."}</div>
+                        },
+                    }
+                }
+                <div class="w-full flex gap-2">
+                    <input
+                        readonly=true
+                        value={link}
+                        class="grow min-w-0 rounded-lg px-3 py-2 border border-gray-200 bg-gray-50 text-sm"
+                    />
+                    <button onclick={copy} class="px-3 py-2 rounded-lg bg-blue-600 text-white text-sm">
+                        { if *copied { "Copied" } else { "Copy" } }
+                    </button>
+                </div>
+                {
+                    if let Some(invite) = &props.invite {
+                        html! {
+                            <div class="text-xs text-gray-500 text-center">
+                                {format!("Anyone with this link can join until {}.", time::absolute_label(invite.expires_at))}
+                            </div>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                <button onclick={close} class="text-sm text-gray-500 hover:text-gray-800">{"Close"}</button>
+            </div>
+        </div>
+    }
+}
+
+/// The address of the room on this site. Whoever opens it is asked to log in first.
+pub fn invite_link(room: &str) -> String {
+    let origin = web_sys::window()
+        .and_then(|w| w.location().origin().ok())
+        .unwrap_or_default();
+    format!(
+        "{}{}",
+        origin,
+        Route::ChatRoom {
+            room: room.to_string()
+        }
+        .to_path()
+    )
+}
+
+/// The address of an invite-only room with its code, which lets whoever opens it in.
+pub fn invite_code_link(room: &str, code: &str) -> String {
+    format!(
+        "{}?invite={}",
+        invite_link(room),
+        js_sys::encode_uri_component(code)
+    )
+}
+
+/// Encodes `data` as a QR code, drawn as one SVG path of unit squares. Returns the side of the
+/// image in modules, quiet zone included.
+fn qr_path(data: &str) -> Option<(usize, String)> {
+    let code = QrCode::new(data).ok()?;
+    let width = code.width();
+    let path = code
+        .to_colors()
+        .iter()
+        .enumerate()
+        .filter(|(_, color)| **color == Color::Dark)
+        .map(|(i, _)| {
+            let (x, y) = (i % width + QUIET_ZONE, i / width + QUIET_ZONE);
+            format!("M{} {}h1v1h-1z", x, y)
+        })
+        .collect();
+    Some((width + 2 * QUIET_ZONE, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn draws_one_square_per_dark_module() {
+        let (size, path) = qr_path("https://chat.example.org/chat/general").unwrap();
+        let code = QrCode::new("https://chat.example.org/chat/general").unwrap();
+        assert_eq!(size, code.width() + 2 * QUIET_ZONE);
+        let dark = code
+            .to_colors()
+            .iter()
+            .filter(|c| **c == Color::Dark)
+            .count();
+        assert_eq!(path.matches('M').count(), dark);
+        // The top-left finder pattern starts right after the quiet zone.
+        assert!(path.starts_with("M4 4h1v1h-1z"));
+    }
+}
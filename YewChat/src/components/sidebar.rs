@@ -0,0 +1,1000 @@
+use gloo_timers::callback::Timeout;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::functional::*;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::components::room_icon::RoomIcon;
+use crate::protocol::{ListedRoom, Role};
+use crate::services::room_folders::{Dragged, Folder, Place, RoomFolders, MAX_FOLDER_NAME_LEN};
+use crate::services::{dnd, notes};
+use crate::store::{Action, Store, UserProfile};
+use crate::{time, Route, User, DEFAULT_ROOM};
+
+/// A change to our room folders, which are saved once it is made.
+type FolderChange = Box<dyn FnOnce(&mut RoomFolders)>;
+
+/// What is being dragged about the room list, and where it would land.
+#[derive(Clone)]
+struct Dragging {
+    item: UseStateHandle<Option<Dragged>>,
+    over: UseStateHandle<Option<Place>>,
+}
+
+/// What makes a row of the room list draggable, and something to drop on.
+struct DragHandlers {
+    ondragstart: Callback<DragEvent>,
+    ondragover: Callback<DragEvent>,
+    ondrop: Callback<DragEvent>,
+    ondragend: Callback<DragEvent>,
+}
+
+impl Dragging {
+    /// Whether something dragged would land at `place` if dropped now.
+    fn is_over(&self, place: &Place) -> bool {
+        self.over.as_ref() == Some(place)
+    }
+
+    /// Handlers for the row of `item`, or of nothing to drag without one, that is `place` to drop
+    /// on. The drop is made through `change_folders`.
+    fn handlers(
+        &self,
+        item: Option<Dragged>,
+        place: Place,
+        change_folders: &Callback<FolderChange>,
+    ) -> DragHandlers {
+        let ondragstart = {
+            let dragged = self.item.clone();
+            Callback::from(move |e: DragEvent| {
+                let Some(item) = item.clone() else {
+                    return;
+                };
+                // Firefox only drags what carries some data.
+                if let Some(transfer) = e.data_transfer() {
+                    let (Dragged::Room(name) | Dragged::Folder(name)) = &item;
+                    let _ = transfer.set_data("text/plain", name);
+                    transfer.set_effect_allowed("move");
+                }
+                dragged.set(Some(item));
+            })
+        };
+        let ondragover = {
+            let dragged = self.item.clone();
+            let over = self.over.clone();
+            let place = place.clone();
+            Callback::from(move |e: DragEvent| {
+                if dragged.as_ref().is_some_and(|item| item.fits(&place)) {
+                    e.prevent_default();
+                    over.set(Some(place.clone()));
+                }
+            })
+        };
+        let ondrop = {
+            let dragging = self.clone();
+            let change_folders = change_folders.clone();
+            Callback::from(move |e: DragEvent| {
+                e.prevent_default();
+                if let Some(item) = (*dragging.item).clone() {
+                    let place = place.clone();
+                    change_folders.emit(Box::new(move |folders| folders.arrange(&item, &place)));
+                }
+                dragging.item.set(None);
+                dragging.over.set(None);
+            })
+        };
+        let ondragend = {
+            let dragging = self.clone();
+            Callback::from(move |_| {
+                dragging.item.set(None);
+                dragging.over.set(None);
+            })
+        };
+        DragHandlers {
+            ondragstart,
+            ondragover,
+            ondrop,
+            ondragend,
+        }
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct SidebarProps {
+    /// Where clicks on our own entry go. Without it, that entry does nothing.
+    #[prop_or_default]
+    pub on_edit_avatar: Option<Callback<()>>,
+    /// Sends the member named out of the room, offered beside everyone but its moderators.
+    /// Without it, we may not.
+    #[prop_or_default]
+    pub on_kick: Option<Callback<String>>,
+    /// Sends a contact card of whoever is named where we are, offered in the menu of everyone's
+    /// entry. Without it, we may not post here.
+    #[prop_or_default]
+    pub on_share_contact: Option<Callback<String>>,
+    /// What else the room has, like its voice channel, shown above its members.
+    #[prop_or_default]
+    pub children: Children,
+}
+
+/// Whose entry has its menu open, and what that menu can do.
+struct UserMenu<'a> {
+    open: &'a UseStateHandle<Option<String>>,
+    on_share_contact: Option<&'a Callback<String>>,
+}
+
+/// The rooms we have been in, sorted into our folders and in the order we dragged them, above our
+/// notes to self, our contacts and the people of the room: those online by role, and folded away
+/// those who spoke in it but aren't here now. Clicking someone else opens a direct conversation
+/// with them, and right-clicking anyone opens a menu to add them to our contacts or pass them on
+/// as a contact card.
+#[function_component(Sidebar)]
+pub fn sidebar(props: &SidebarProps) -> Html {
+    let store = use_context::<Store>().expect("No store found.");
+    let user = use_context::<User>().expect("No context found.");
+    let current_username = user.username.borrow().clone();
+    let dnd_until = {
+        let username = current_username.clone();
+        use_state(move || dnd::until(&username))
+    };
+    let dnd_menu = use_state(|| false);
+    let history = use_history().expect("history to be available");
+    let folders = {
+        let username = current_username.clone();
+        use_state(move || RoomFolders::load(&username))
+    };
+    // The name of the folder being added, while it is typed in.
+    let new_folder = use_state_eq(|| Option::<String>::None);
+    let dragging = Dragging {
+        item: use_state_eq(|| None),
+        over: use_state_eq(|| None),
+    };
+    let show_offline = use_state(|| false);
+    let menu_open = use_state_eq(|| Option::<String>::None);
+    let menu = UserMenu {
+        open: &menu_open,
+        on_share_contact: props.on_share_contact.as_ref(),
+    };
+    let offline = store.offline_members();
+    let toggle_offline = {
+        let show_offline = show_offline.clone();
+        Callback::from(move |_| show_offline.set(!*show_offline))
+    };
+    let change_folders = {
+        let folders = folders.clone();
+        let username = current_username.clone();
+        Callback::from(move |change: FolderChange| {
+            let mut changed = (*folders).clone();
+            change(&mut changed);
+            changed.save(&username);
+            folders.set(changed);
+        })
+    };
+    {
+        // The room we are in goes on the list, and is read as far as the directory last said.
+        let change_folders = change_folders.clone();
+        let folders = folders.clone();
+        use_effect_with_deps(
+            move |(room, listed)| {
+                let mut changed = (*folders).clone();
+                let visited = changed.visit(room);
+                let caught_up = listed
+                    .as_ref()
+                    .is_some_and(|listed| changed.update_seen(listed, room));
+                if visited || caught_up {
+                    change_folders.emit(Box::new(move |folders| *folders = changed));
+                }
+                || ()
+            },
+            (store.room.clone(), store.listed_rooms.clone()),
+        );
+    }
+    {
+        // Ends Do Not Disturb when its time is up.
+        let expire = dnd_until.setter();
+        let username = current_username.clone();
+        use_effect_with_deps(
+            move |until| {
+                let expiry = until.map(|until| {
+                    let wait = (until - time::now()).max(0.0) as u32;
+                    Timeout::new(wait, move || {
+                        dnd::clear(&username);
+                        expire.set(None);
+                    })
+                });
+                move || drop(expiry)
+            },
+            *dnd_until,
+        );
+    }
+
+    html! {
+        <div class="flex-none w-72 h-screen bg-white shadow-md flex flex-col">
+            <div class="text-xl p-4 font-bold border-b border-gray-200 bg-blue-600 text-white">
+                <div class="flex items-center gap-2">
+                    <svg xmlns="http://www.w3.org/2000/svg" class="h-6 w-6" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                        <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M17 20h5v-2a3 3 0 00-5.356-1.857M17 20H7m10 0v-2c0-.656-.126-1.283-.356-1.857M7 20H2v-2a3 3 0 015.356-1.857M7 20v-2c0-.656.126-1.283.356-1.857m0 0a5.002 5.002 0 019.288 0M15 7a3 3 0 11-6 0 3 3 0 016 0zm6 3a2 2 0 11-4 0 2 2 0 014 0zM7 10a2 2 0 11-4 0 2 2 0 014 0z" />
+                    </svg>
+                    {"Members"}
+                    <div class="relative ml-auto">
+                        { view_dnd_button(&dnd_until, &dnd_menu) }
+                        {
+                            if *dnd_menu {
+                                view_dnd_menu(&current_username, &dnd_until, &dnd_menu)
+                            } else {
+                                html! {}
+                            }
+                        }
+                    </div>
+                </div>
+            </div>
+            <div class="overflow-auto flex-grow">
+            { view_rooms(&store, &history, &folders, &new_folder, &dragging, &change_folders) }
+            { view_notes_entry(&store, &current_username) }
+            {
+                if store.contacts.is_empty() {
+                    html! {}
+                } else {
+                    html! {
+                        <>
+                            { view_section_title("Contacts", store.contacts.len()) }
+                            { for store.contacts.iter().map(|name| view_contact(&store, name, &current_username, &menu)) }
+                        </>
+                    }
+                }
+            }
+            <div class="px-4 pt-1 flex items-center gap-1 text-xs text-gray-500">
+                {
+                    if let Some(icon) = &store.room_icon {
+                        html! { <RoomIcon icon={icon.clone()} class={classes!("w-4", "h-4")} /> }
+                    } else {
+                        html! {}
+                    }
+                }
+                {format!("#{} · {} online, {} offline", store.room, store.users.len(), offline.len())}
+            </div>
+            { for props.children.iter() }
+            {
+                SECTIONS.iter().map(|&(role, title)| {
+                    let members: Vec<&UserProfile> = store.users.iter().filter(|u| u.role == role).collect();
+                    if members.is_empty() {
+                        return html! {};
+                    }
+                    html! {
+                        <>
+                            { view_section_title(title, members.len()) }
+                            { for members.into_iter().map(|u| view_user(&store, u, &current_username, props.on_edit_avatar.as_ref(), props.on_kick.as_ref(), *dnd_until, &menu)) }
+                        </>
+                    }
+                }).collect::<Html>()
+            }
+            {
+                if offline.is_empty() {
+                    html! {}
+                } else {
+                    html! {
+                        <>
+                            <button
+                                onclick={toggle_offline}
+                                class="w-full px-4 pt-3 pb-1 flex items-center gap-1 text-xs font-semibold uppercase tracking-wide text-gray-500 hover:text-gray-700"
+                            >
+                                { if *show_offline { "▾" } else { "▸" } }
+                                {format!("Offline members — {}", offline.len())}
+                            </button>
+                            {
+                                if *show_offline {
+                                    offline.iter().map(|name| view_offline(&store, name, &current_username, &menu)).collect::<Html>()
+                                } else {
+                                    html! {}
+                                }
+                            }
+                        </>
+                    }
+                }
+            }
+            </div>
+        </div>
+    }
+}
+
+/// The rooms we have been in: those in each of our folders, under its name, then the rest. Each
+/// shows how many messages were posted in it since we were last there, and a folder how many in
+/// all of its rooms, even folded away. Rooms and folders can be dragged into another order, and
+/// rooms into and out of folders.
+fn view_rooms(
+    store: &Store,
+    history: &AnyHistory,
+    folders: &RoomFolders,
+    new_folder: &UseStateHandle<Option<String>>,
+    dragging: &Dragging,
+    change_folders: &Callback<FolderChange>,
+) -> Html {
+    let listed: &[ListedRoom] = store.listed_rooms.as_deref().map_or(&[], Vec::as_slice);
+    let start_folder = {
+        let new_folder = new_folder.clone();
+        Callback::from(move |_| new_folder.set(Some(String::new())))
+    };
+    let name_folder = {
+        let new_folder = new_folder.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            new_folder.set(Some(input.value()));
+        })
+    };
+    let add_folder = {
+        let new_folder = new_folder.clone();
+        let change_folders = change_folders.clone();
+        Callback::from(move |e: FocusEvent| {
+            e.prevent_default();
+            if let Some(name) = (*new_folder).clone() {
+                change_folders.emit(Box::new(move |folders| {
+                    folders.add_folder(&name);
+                }));
+            }
+            new_folder.set(None);
+        })
+    };
+    let cancel_folder = {
+        let new_folder = new_folder.clone();
+        Callback::from(move |_| new_folder.set(None))
+    };
+    let view_folder = |folder: &Folder| {
+        let unread = folders.folder_unread(&folder.name, listed);
+        let unread_muted = folders.folder_unread_muted(&folder.name, listed);
+        let toggle = {
+            let name = folder.name.clone();
+            change_folders.reform(move |_| {
+                let name = name.clone();
+                Box::new(move |folders: &mut RoomFolders| folders.toggle_folder(&name))
+                    as FolderChange
+            })
+        };
+        let remove = {
+            let name = folder.name.clone();
+            change_folders.reform(move |_| {
+                let name = name.clone();
+                Box::new(move |folders: &mut RoomFolders| folders.remove_folder(&name))
+                    as FolderChange
+            })
+        };
+        let place = Place::Folder(folder.name.clone());
+        let over = dragging.is_over(&place);
+        let drag = dragging.handlers(
+            Some(Dragged::Folder(folder.name.clone())),
+            place,
+            change_folders,
+        );
+        html! {
+            <>
+                <div
+                    draggable="true"
+                    ondragstart={drag.ondragstart}
+                    ondragover={drag.ondragover}
+                    ondrop={drag.ondrop}
+                    ondragend={drag.ondragend}
+                    class={classes!(
+                        "group", "mx-2", "px-2", "pt-2", "rounded-lg", "flex", "items-center", "gap-1", "text-xs", "font-semibold", "text-gray-600",
+                        if over { vec!["bg-blue-50", "ring-2", "ring-blue-300"] } else { vec![] }
+                    )}
+                >
+                    <button onclick={toggle} class="flex items-center gap-1 hover:text-gray-800">
+                        { if folder.collapsed { "▸" } else { "▾" } }
+                        {folder.name.clone()}
+                    </button>
+                    { view_unread(unread, unread_muted) }
+                    <button
+                        onclick={remove}
+                        title="Remove the folder, keeping its rooms"
+                        class="ml-auto hidden group-hover:block px-1 text-gray-400 hover:text-gray-700"
+                    >
+                        {"✕"}
+                    </button>
+                </div>
+                {
+                    if folder.collapsed {
+                        html! {}
+                    } else if folders.in_folder(folder).next().is_none() {
+                        html! { <div class="pl-8 py-1 text-xs text-gray-400">{"Drag rooms here, or move them from their menu."}</div> }
+                    } else {
+                        folders.in_folder(folder).map(|room| view_room(store, history, folders, listed, room, dragging, change_folders)).collect::<Html>()
+                    }
+                }
+            </>
+        }
+    };
+
+    html! {
+        <div class="pb-2 border-b border-gray-200">
+            <div class="px-4 pt-3 pb-1 flex items-center text-xs font-semibold uppercase tracking-wide text-gray-500">
+                {"Rooms"}
+                <button onclick={start_folder} title="New folder" class="ml-auto px-1 text-base leading-none hover:text-gray-700">{"+"}</button>
+            </div>
+            {
+                if let Some(name) = &**new_folder {
+                    html! {
+                        <form onsubmit={add_folder} class="px-4 py-1 flex items-center gap-1">
+                            <input
+                                oninput={name_folder}
+                                value={name.clone()}
+                                maxlength={MAX_FOLDER_NAME_LEN.to_string()}
+                                placeholder="Folder name, like Work"
+                                class="grow min-w-0 rounded px-2 py-1 text-sm border border-gray-200"
+                            />
+                            <button type="submit" class="px-2 py-1 text-sm rounded bg-blue-600 text-white">{"Add"}</button>
+                            <button type="button" onclick={cancel_folder} class="px-1 text-gray-400 hover:text-gray-700">{"✕"}</button>
+                        </form>
+                    }
+                } else {
+                    html! {}
+                }
+            }
+            {
+                if folders.favorites.is_empty() {
+                    html! {}
+                } else {
+                    html! {
+                        <>
+                            <div class="px-4 pt-2 text-xs font-semibold text-gray-600">{"★ Favorites"}</div>
+                            { for folders.favorites.iter().map(|room| view_room(store, history, folders, listed, room, dragging, change_folders)) }
+                        </>
+                    }
+                }
+            }
+            { for folders.folders.iter().map(view_folder) }
+            { for folders.unfiled().map(|room| view_room(store, history, folders, listed, room, dragging, change_folders)) }
+            {
+                if dragging.item.is_some() {
+                    let over = dragging.is_over(&Place::End);
+                    let drag = dragging.handlers(None, Place::End, change_folders);
+                    html! {
+                        <div
+                            ondragover={drag.ondragover}
+                            ondrop={drag.ondrop}
+                            class={classes!(
+                                "mx-2", "mt-1", "px-2", "py-1", "rounded-lg", "border-2", "border-dashed", "text-xs", "text-gray-400",
+                                if over { "border-blue-300" } else { "border-gray-200" }
+                            )}
+                        >
+                            {"Drop here to put it last"}
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }
+            }
+        </div>
+    }
+}
+
+fn view_room(
+    store: &Store,
+    history: &AnyHistory,
+    folders: &RoomFolders,
+    listed: &[ListedRoom],
+    room: &str,
+    dragging: &Dragging,
+    change_folders: &Callback<FolderChange>,
+) -> Html {
+    let here = store.room == room;
+    let unread = if here {
+        0
+    } else {
+        folders.unread(room, listed)
+    };
+    let go = {
+        let history = history.clone();
+        let room = room.to_string();
+        Callback::from(move |_| history.push(Route::ChatRoom { room: room.clone() }))
+    };
+    let move_to = {
+        let room = room.to_string();
+        change_folders.reform(move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            let folder = select.value();
+            let room = room.clone();
+            Box::new(move |folders: &mut RoomFolders| {
+                folders.file(&room, (!folder.is_empty()).then_some(folder.as_str()))
+            }) as FolderChange
+        })
+    };
+    let forget = {
+        let room = room.to_string();
+        change_folders.reform(move |_| {
+            let room = room.clone();
+            Box::new(move |folders: &mut RoomFolders| folders.forget(&room)) as FolderChange
+        })
+    };
+    let star = {
+        let room = room.to_string();
+        change_folders.reform(move |_| {
+            let room = room.clone();
+            Box::new(move |folders: &mut RoomFolders| folders.toggle_favorite(&room))
+                as FolderChange
+        })
+    };
+    let mute = {
+        let room = room.to_string();
+        change_folders.reform(move |_| {
+            let room = room.clone();
+            Box::new(move |folders: &mut RoomFolders| folders.toggle_muted(&room)) as FolderChange
+        })
+    };
+    let favorite = folders.is_favorite(room);
+    let muted = folders.is_muted(room);
+    let folder = folders.folder_of(room);
+    let place = Place::Room(room.to_string());
+    let over = dragging.is_over(&place);
+    // Favorites stay in the order they were starred.
+    let (ondragstart, ondragover, ondrop, ondragend) = if favorite {
+        (None, None, None, None)
+    } else {
+        let drag = dragging.handlers(Some(Dragged::Room(room.to_string())), place, change_folders);
+        (
+            Some(drag.ondragstart),
+            Some(drag.ondragover),
+            Some(drag.ondrop),
+            Some(drag.ondragend),
+        )
+    };
+    html! {
+        <div
+            draggable={(!favorite).to_string()}
+            {ondragstart}
+            {ondragover}
+            {ondrop}
+            {ondragend}
+            class={classes!(
+                "group", "mx-2", "px-2", "py-1", "rounded-lg", "flex", "items-center", "gap-1", "text-sm", "border-t-2",
+                if here { "bg-blue-100" } else { "hover:bg-blue-50" },
+                if over { "border-blue-400" } else { "border-transparent" }
+            )}
+        >
+            <button onclick={go} disabled={here} class={classes!(
+                "grow", "min-w-0", "truncate", "text-left",
+                if muted { "text-gray-400" } else if unread > 0 { "font-semibold" } else { "" }
+            )}>
+                {format!("# {}", room)}
+            </button>
+            {
+                if muted {
+                    view_unread(0, unread > 0)
+                } else {
+                    view_unread(unread, false)
+                }
+            }
+            <button
+                onclick={mute}
+                title={if muted { "Unmute" } else { "Mute: no count, sounds or notifications" }}
+                class={classes!(
+                    "px-1", "text-xs",
+                    if muted { vec!["text-gray-400"] } else { vec!["hidden", "group-hover:block", "text-gray-400", "hover:text-gray-700"] }
+                )}
+            >
+                { if muted { "🔕" } else { "🔔" } }
+            </button>
+            <button
+                onclick={star}
+                title={if favorite { "Remove from favorites" } else { "Add to favorites" }}
+                class={classes!(
+                    "px-1",
+                    if favorite { vec!["text-yellow-500"] } else { vec!["hidden", "group-hover:block", "text-gray-400", "hover:text-yellow-500"] }
+                )}
+            >
+                { if favorite { "★" } else { "☆" } }
+            </button>
+            {
+                if folders.folders.is_empty() || favorite {
+                    html! {}
+                } else {
+                    html! {
+                        <select onchange={move_to} title="Move to a folder" class="hidden group-hover:block w-20 text-xs bg-transparent">
+                            <option value="" selected={folder.is_none()}>{"No folder"}</option>
+                            {
+                                for folders.folders.iter().map(|f| html! {
+                                    <option value={f.name.clone()} selected={folder == Some(f.name.as_str())}>{f.name.clone()}</option>
+                                })
+                            }
+                        </select>
+                    }
+                }
+            }
+            {
+                if here {
+                    html! {}
+                } else {
+                    html! {
+                        <button onclick={forget} title="Take off the list" class="hidden group-hover:block px-1 text-gray-400 hover:text-gray-700">{"✕"}</button>
+                    }
+                }
+            }
+        </div>
+    }
+}
+
+/// How many messages are unread, or with `muted_unread`, only that some of muted rooms are.
+fn view_unread(unread: u64, muted_unread: bool) -> Html {
+    if unread > 0 {
+        html! { <span class="text-xs bg-red-500 text-white px-2 rounded-full">{unread}</span> }
+    } else if muted_unread {
+        html! { <span title="New messages, muted" class="w-2 h-2 rounded-full bg-gray-300"></span> }
+    } else {
+        html! {}
+    }
+}
+
+/// Sections of the online members, in order, by role.
+const SECTIONS: [(Role, &str); 3] = [
+    (Role::Owner, "Owner"),
+    (Role::Moderator, "Moderators"),
+    (Role::Member, "Members"),
+];
+
+fn view_section_title(title: &str, count: usize) -> Html {
+    html! {
+        <div class="px-4 pt-3 pb-1 text-xs font-semibold uppercase tracking-wide text-gray-500">
+            {format!("{} — {}", title, count)}
+        </div>
+    }
+}
+
+fn view_user(
+    store: &Store,
+    u: &UserProfile,
+    current_username: &str,
+    on_edit_avatar: Option<&Callback<()>>,
+    on_kick: Option<&Callback<String>>,
+    dnd_until: Option<f64>,
+    menu: &UserMenu,
+) -> Html {
+    let is_current_user = u.name == current_username;
+    let kick = on_kick
+        .filter(|_| !is_current_user && u.role == Role::Member)
+        .map(|on_kick| {
+            let on_kick = on_kick.clone();
+            let name = u.name.clone();
+            Callback::from(move |e: MouseEvent| {
+                e.stop_propagation();
+                on_kick.emit(name.clone());
+            })
+        });
+    let is_open = store.conversation.as_ref() == Some(&u.name);
+    let unread = store.unread.get(&u.name).copied().unwrap_or_default();
+    let onclick = if is_current_user {
+        on_edit_avatar.map(|edit| edit.reform(|_| ()))
+    } else {
+        let store = store.clone();
+        let name = u.name.clone();
+        Some(Callback::from(move |_| {
+            store.dispatch(Action::OpenDirect(name.clone()))
+        }))
+    };
+    let (oncontextmenu, onmouseleave) = menu_handlers(menu, &u.name);
+    html! {
+        <div {onclick} {oncontextmenu} {onmouseleave} title={match (is_current_user, on_edit_avatar) {
+            (false, _) => Some(format!("Message {} privately", u.name)),
+            (true, Some(_)) => Some("Change your picture".to_string()),
+            (true, None) => None,
+        }} class={classes!(
+            "relative", "group", "flex", "items-center", "m-3", "rounded-lg", "p-3", "transition-all", "hover:bg-blue-50", "cursor-pointer",
+            if is_current_user || is_open { vec!["bg-blue-100", "border-l-4", "border-blue-500"] } else { vec!["bg-white"] }
+        )}>
+            <div class="relative">
+                <img class="w-12 h-12 rounded-full shadow-sm" src={u.avatar.clone()} alt="avatar"/>
+                {
+                    match dnd_until {
+                        Some(until) if is_current_user => html! {
+                            <div
+                                title={format!("Do not disturb until {}", time::absolute_label(until))}
+                                class="absolute bottom-0 right-0 w-3 h-3 bg-red-500 rounded-full border-2 border-white flex items-center justify-center"
+                            >
+                                <div class="w-1.5 h-px bg-white"></div>
+                            </div>
+                        },
+                        _ if u.away => html! {
+                            <div title="Away" class="absolute bottom-0 right-0 w-3 h-3 bg-yellow-400 rounded-full border-2 border-white"></div>
+                        },
+                        _ => html! {
+                            <div class="absolute bottom-0 right-0 w-3 h-3 bg-green-500 rounded-full border-2 border-white"></div>
+                        },
+                    }
+                }
+            </div>
+            <div class="flex-grow ml-3">
+                <div class="flex text-sm font-medium justify-between">
+                    <div class="flex items-center gap-1">
+                        {u.name.clone()}
+                        {
+                            if is_current_user {
+                                html! { <span class="text-xs bg-blue-600 text-white px-2 rounded-full">{"You"}</span> }
+                            } else {
+                                html! {}
+                            }
+                        }
+                        {
+                            match &u.room_role {
+                                Some(role) => html! { <span class="text-xs bg-gray-200 text-gray-700 px-2 rounded-full">{role.clone()}</span> },
+                                None => html! {},
+                            }
+                        }
+                    </div>
+                    {
+                        match kick {
+                            Some(kick) => html! {
+                                <button
+                                    onclick={kick}
+                                    title={format!("Send {} back to #{}", u.name, DEFAULT_ROOM)}
+                                    class="hidden group-hover:block px-2 rounded-full text-xs text-red-700 hover:bg-red-50"
+                                >
+                                    {"Kick"}
+                                </button>
+                            },
+                            None => html! {},
+                        }
+                    }
+                    {
+                        if unread > 0 {
+                            html! { <span class="text-xs bg-red-500 text-white px-2 rounded-full">{unread}</span> }
+                        } else {
+                            html! {}
+                        }
+                    }
+                </div>
+                <div class="text-xs text-gray-500 mt-1">
+                    {
+                        match dnd_until {
+                            Some(until) if is_current_user => format!("Do not disturb · until {}", time::clock_label(until)),
+                            _ if u.away => "Away".to_string(),
+                            _ => "Online".to_string(),
+                        }
+                    }
+                </div>
+            </div>
+            { view_user_menu(store, &u.name, current_username, menu) }
+        </div>
+    }
+}
+
+/// Someone who spoke in the room but isn't in it now.
+fn view_offline(store: &Store, name: &str, current_username: &str, menu: &UserMenu) -> Html {
+    let onclick = {
+        let store = store.clone();
+        let name = name.to_string();
+        Callback::from(move |_| store.dispatch(Action::OpenDirect(name.clone())))
+    };
+    let is_open = store.conversation.as_deref() == Some(name);
+    let (oncontextmenu, onmouseleave) = menu_handlers(menu, name);
+    html! {
+        <div {onclick} {oncontextmenu} {onmouseleave} title={format!("Message {} privately", name)} class={classes!(
+            "relative", "flex", "items-center", "m-3", "rounded-lg", "p-3", "transition-all", "hover:bg-blue-50", "cursor-pointer",
+            if is_open { vec!["bg-blue-100", "border-l-4", "border-blue-500"] } else { vec!["bg-white"] }
+        )}>
+            <img class="w-12 h-12 rounded-full shadow-sm grayscale opacity-60" src={store.avatar_of(name)} alt="avatar"/>
+            <div class="flex-grow ml-3 opacity-60">
+                <div class="text-sm font-medium">{name.to_string()}</div>
+                <div class="text-xs text-gray-500 mt-1">{"Offline"}</div>
+            </div>
+            { view_user_menu(store, name, current_username, menu) }
+        </div>
+    }
+}
+
+/// Someone we added to our contacts, whether or not they are in the room.
+fn view_contact(store: &Store, name: &str, current_username: &str, menu: &UserMenu) -> Html {
+    let onclick = {
+        let store = store.clone();
+        let name = name.to_string();
+        Callback::from(move |_| store.dispatch(Action::OpenDirect(name.clone())))
+    };
+    let is_open = store.conversation.as_deref() == Some(name);
+    let here = store.users.iter().any(|u| u.name == name);
+    let unread = store.unread.get(name).copied().unwrap_or_default();
+    let (oncontextmenu, onmouseleave) = menu_handlers(menu, name);
+    html! {
+        <div {onclick} {oncontextmenu} {onmouseleave} title={format!("Message {} privately", name)} class={classes!(
+            "relative", "flex", "items-center", "m-3", "rounded-lg", "p-3", "transition-all", "hover:bg-blue-50", "cursor-pointer",
+            if is_open { vec!["bg-blue-100", "border-l-4", "border-blue-500"] } else { vec!["bg-white"] }
+        )}>
+            <img class={classes!("w-12", "h-12", "rounded-full", "shadow-sm", (!here).then_some("grayscale"))} src={store.avatar_of(name)} alt="avatar"/>
+            <div class="flex-grow ml-3">
+                <div class="flex text-sm font-medium justify-between">
+                    {name.to_string()}
+                    {
+                        if unread > 0 {
+                            html! { <span class="text-xs bg-red-500 text-white px-2 rounded-full">{unread}</span> }
+                        } else {
+                            html! {}
+                        }
+                    }
+                </div>
+                <div class="text-xs text-gray-500 mt-1">
+                    { if here { format!("In #{}", store.room) } else { "Not in this room".to_string() } }
+                </div>
+            </div>
+            { view_user_menu(store, name, current_username, menu) }
+        </div>
+    }
+}
+
+/// What opens the menu of `name`'s entry on a right click, and closes it once the pointer leaves.
+fn menu_handlers(menu: &UserMenu, name: &str) -> (Callback<MouseEvent>, Callback<MouseEvent>) {
+    let open = {
+        let menu_open = menu.open.clone();
+        let name = name.to_string();
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            menu_open.set(Some(name.clone()));
+        })
+    };
+    let close = {
+        let menu_open = menu.open.clone();
+        Callback::from(move |_: MouseEvent| menu_open.set(None))
+    };
+    (open, close)
+}
+
+/// The menu of `name`'s entry, if it is open: messaging them, adding them to our contacts or taking
+/// them off, and sending their contact card where we are.
+fn view_user_menu(store: &Store, name: &str, current_username: &str, menu: &UserMenu) -> Html {
+    if menu.open.as_deref() != Some(name) {
+        return html! {};
+    }
+    let item = "block w-full text-left px-4 py-1 hover:bg-gray-100";
+    // Each item closes the menu, and doesn't fall through to the entry it is on.
+    let act = |action: Box<dyn Fn()>| {
+        let menu_open = menu.open.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.stop_propagation();
+            action();
+            menu_open.set(None);
+        })
+    };
+    let is_us = name == current_username;
+    if is_us && menu.on_share_contact.is_none() {
+        return html! {};
+    }
+    let message = {
+        let store = store.clone();
+        let name = name.to_string();
+        act(Box::new(move || {
+            store.dispatch(Action::OpenDirect(name.clone()))
+        }))
+    };
+    let toggle_contact = {
+        let store = store.clone();
+        let name = name.to_string();
+        let owner = current_username.to_string();
+        act(Box::new(move || {
+            store.dispatch(Action::ToggleContact {
+                owner: owner.clone(),
+                name: name.clone(),
+            })
+        }))
+    };
+    let share = menu.on_share_contact.map(|on_share_contact| {
+        let on_share_contact = on_share_contact.clone();
+        let name = name.to_string();
+        act(Box::new(move || on_share_contact.emit(name.clone())))
+    });
+    let share_label = match &store.conversation {
+        Some(peer) if peer == current_username => "Share contact in your notes".to_string(),
+        Some(peer) => format!("Share contact with {}", peer),
+        None => format!("Share contact in #{}", store.room),
+    };
+
+    html! {
+        <div class="absolute top-full right-2 -mt-2 z-20 py-1 min-w-max rounded-lg bg-white shadow-lg border border-gray-200 text-sm text-gray-700">
+            if !is_us {
+                <button onclick={message} class={item}>{"Message privately"}</button>
+                <button onclick={toggle_contact} class={item}>
+                    { if store.contacts.iter().any(|c| c == name) { "Remove from contacts" } else { "Add to contacts" } }
+                </button>
+            }
+            if let Some(share) = share {
+                <button onclick={share} class={item}>{share_label}</button>
+            }
+        </div>
+    }
+}
+
+fn view_notes_entry(store: &Store, current_username: &str) -> Html {
+    let is_open = store.conversation.as_deref() == Some(current_username);
+    let onclick = {
+        let store = store.clone();
+        let owner = current_username.to_string();
+        Callback::from(move |_| {
+            store.dispatch(Action::OpenNotes {
+                notes: notes::load(&owner),
+                owner: owner.clone(),
+            })
+        })
+    };
+    html! {
+        <div {onclick} title="Jot down links and reminders, kept in this browser" class={classes!(
+            "flex", "items-center", "m-3", "rounded-lg", "p-3", "transition-all", "hover:bg-blue-50", "cursor-pointer",
+            if is_open { vec!["bg-blue-100", "border-l-4", "border-blue-500"] } else { vec!["bg-white"] }
+        )}>
+            <div class="w-12 h-12 rounded-full bg-yellow-100 text-yellow-700 flex items-center justify-center shadow-sm">
+                <svg xmlns="http://www.w3.org/2000/svg" class="h-6 w-6" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M11 5H6a2 2 0 00-2 2v11a2 2 0 002 2h11a2 2 0 002-2v-5m-1.414-9.414a2 2 0 112.828 2.828L11.828 15H9v-2.828l8.586-8.586z" />
+                </svg>
+            </div>
+            <div class="flex-grow ml-3">
+                <div class="text-sm font-medium">{"Notes to self"}</div>
+                <div class="text-xs text-gray-500 mt-1">{"Only on this device"}</div>
+            </div>
+        </div>
+    }
+}
+
+fn view_dnd_button(
+    dnd_until: &UseStateHandle<Option<f64>>,
+    dnd_menu: &UseStateHandle<bool>,
+) -> Html {
+    let onclick = {
+        let dnd_menu = dnd_menu.clone();
+        Callback::from(move |_| dnd_menu.set(!*dnd_menu))
+    };
+    let (title, shade) = match **dnd_until {
+        Some(_) => ("Do not disturb is on", "bg-white/30"),
+        None => ("Do not disturb", "hover:bg-white/20"),
+    };
+    html! {
+        <button {onclick} {title} class={classes!("p-1.5", "rounded-full", "transition-colors", shade)}>
+            <svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M20.354 15.354A9 9 0 018.646 3.646 9.003 9.003 0 0012 21a9.003 9.003 0 008.354-5.646z" />
+            </svg>
+        </button>
+    }
+}
+
+fn view_dnd_menu(
+    username: &str,
+    dnd_until: &UseStateHandle<Option<f64>>,
+    dnd_menu: &UseStateHandle<bool>,
+) -> Html {
+    let item = "block w-full text-left px-4 py-2 text-sm hover:bg-blue-50";
+    let choices = dnd::DURATIONS.into_iter().map(|(label, duration)| {
+        let onclick = {
+            let dnd_until = dnd_until.clone();
+            let dnd_menu = dnd_menu.clone();
+            let username = username.to_string();
+            Callback::from(move |_| {
+                dnd_until.set(Some(dnd::start(&username, duration)));
+                dnd_menu.set(false);
+            })
+        };
+        html! { <button {onclick} class={item}>{format!("For {}", label)}</button> }
+    });
+    let turn_off = {
+        let dnd_until = dnd_until.clone();
+        let dnd_menu = dnd_menu.clone();
+        let username = username.to_string();
+        Callback::from(move |_| {
+            dnd::clear(&username);
+            dnd_until.set(None);
+            dnd_menu.set(false);
+        })
+    };
+
+    html! {
+        <div class="absolute right-0 mt-2 w-52 bg-white text-gray-800 font-normal rounded-lg shadow-lg border border-gray-200 overflow-hidden z-20">
+            <div class="px-4 py-2 text-xs text-gray-500 border-b border-gray-200">
+                {
+                    match **dnd_until {
+                        Some(until) => format!("Do not disturb until {}", time::clock_label(until)),
+                        None => "Pause sounds and notifications".to_string(),
+                    }
+                }
+            </div>
+            { for choices }
+            {
+                if dnd_until.is_some() {
+                    html! {
+                        <button onclick={turn_off} class={classes!(item, "border-t", "border-gray-200")}>{"Turn off"}</button>
+                    }
+                } else {
+                    html! {}
+                }
+            }
+        </div>
+    }
+}
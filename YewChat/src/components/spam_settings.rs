@@ -0,0 +1,54 @@
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::services::spam::SpamSettings;
+
+#[derive(Properties, PartialEq)]
+pub struct SpamSettingsProps {
+    pub username: String,
+    pub settings: SpamSettings,
+    /// Called with the saved settings.
+    pub on_change: Callback<SpamSettings>,
+}
+
+/// When others' messages are folded away as possible spam. Saved as soon as a number is changed.
+#[function_component(SpamSettingsPanel)]
+pub fn spam_settings_panel(props: &SpamSettingsProps) -> Html {
+    let field = |label: &str, value: u32, max: u32, set: fn(&mut SpamSettings, u32)| {
+        let username = props.username.clone();
+        let settings = props.settings;
+        let on_change = props.on_change.clone();
+        let onchange = Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            if let Ok(chosen) = input.value().parse::<u32>() {
+                let mut settings = settings;
+                set(&mut settings, chosen.min(max));
+                settings.save(&username);
+                on_change.emit(settings);
+            }
+        });
+        html! {
+            <label class="flex items-center gap-1">
+                {label.to_string()}
+                <input
+                    {onchange}
+                    type="number"
+                    min="0"
+                    max={max.to_string()}
+                    value={value.to_string()}
+                    class="w-16 rounded-lg px-2 py-1 border border-gray-200 bg-white"
+                />
+            </label>
+        }
+    };
+    let s = props.settings;
+
+    html! {
+        <div class="w-full px-4 py-2 border-b border-gray-200 bg-gray-50 text-sm text-gray-700 flex flex-wrap items-center gap-x-4 gap-y-2">
+            <span title="0 turns a check off">{"Fold possible spam:"}</span>
+            {field("same message more than", s.repeats as u32, 20, |s, n| s.repeats = n as usize)}
+            {field("capitals from %", s.caps_percent, 100, |s, n| s.caps_percent = n)}
+            {field("bare links more than", s.links as u32, 20, |s, n| s.links = n as usize)}
+        </div>
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use wasm_bindgen_futures::spawn_local;
+use yew::functional::*;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::components::message_list::view_text;
+use crate::protocol::Terms;
+use crate::services::api::ApiClient;
+use crate::services::auth::AuthService;
+use crate::services::terms;
+use crate::{Route, User};
+
+#[derive(Properties, PartialEq)]
+pub struct TermsGateProps {
+    #[prop_or_default]
+    pub children: Children,
+}
+
+enum Check {
+    Checking,
+    Failed(String),
+    /// These haven't been accepted yet.
+    Needed(Terms),
+    Passed,
+}
+
+/// Shows the server's terms of service until they are accepted, and only then what it wraps, so
+/// that the chat doesn't register before. Matrix and IRC have no terms of ours.
+#[function_component(TermsGate)]
+pub fn terms_gate(props: &TermsGateProps) -> Html {
+    let user = use_context::<User>().expect("No context found.");
+    let history = use_history().expect("history to be available");
+    let username = user.username.borrow().clone();
+    let check = use_state(|| Check::Checking);
+    // Bumped to ask again after a failure.
+    let attempt = use_state(|| 0_u32);
+
+    {
+        let check = check.clone();
+        let user = user.clone();
+        let username = username.clone();
+        use_effect_with_deps(
+            move |_| {
+                match ApiClient::of(&user) {
+                    None => check.set(Check::Passed),
+                    Some(api) => spawn_local(async move {
+                        check.set(match api.terms().await {
+                            Ok(None) => Check::Passed,
+                            Ok(Some(t))
+                                if terms::accepted(&username).as_ref() == Some(&t.version) =>
+                            {
+                                Check::Passed
+                            }
+                            Ok(Some(t)) => Check::Needed(t),
+                            Err(e) => Check::Failed(e),
+                        })
+                    }),
+                }
+                || ()
+            },
+            *attempt,
+        );
+    }
+
+    let retry = {
+        let check = check.clone();
+        let attempt = attempt.clone();
+        Callback::from(move |_| {
+            check.set(Check::Checking);
+            attempt.set(*attempt + 1);
+        })
+    };
+    let decline = {
+        let user = user.clone();
+        Callback::from(move |_| {
+            AuthService::sign_out(&user);
+            history.push(Route::Login);
+        })
+    };
+
+    match &*check {
+        Check::Passed => html! { <>{ for props.children.iter() }</> },
+        Check::Checking => html! {
+            <div class="w-screen h-screen flex items-center justify-center bg-gray-50 text-gray-500">
+                {"Loading..."}
+            </div>
+        },
+        Check::Failed(e) => html! {
+            <div class="w-screen h-screen flex flex-col gap-3 items-center justify-center bg-gray-50 text-gray-700">
+                <div>{"Couldn't load the server's terms of service."}</div>
+                <div class="text-sm text-gray-500">{e.clone()}</div>
+                <button onclick={retry} class="px-4 py-2 rounded-lg bg-blue-600 text-white hover:bg-blue-700">
+                    {"Try again"}
+                </button>
+            </div>
+        },
+        Check::Needed(t) => {
+            let accept = {
+                let check = check.clone();
+                let version = t.version.clone();
+                Callback::from(move |_| {
+                    terms::accept(&username, &version);
+                    check.set(Check::Passed);
+                })
+            };
+            html! {
+                <div class="w-screen h-screen flex items-center justify-center bg-gray-800 p-4">
+                    <div class="w-full max-w-xl max-h-full flex flex-col bg-white rounded-lg shadow-lg">
+                        <div class="px-6 pt-6 pb-2 text-xl font-semibold">{"Before you join"}</div>
+                        <div class="px-6 text-sm text-gray-500">{"Please read and accept this server's terms of service."}</div>
+                        <div class="m-6 p-4 overflow-auto rounded-lg border border-gray-200 bg-gray-50 whitespace-pre-wrap break-words text-gray-800">
+                            {view_text(&t.text, &HashMap::new())}
+                        </div>
+                        <div class="px-6 pb-6 flex justify-end gap-2">
+                            <button onclick={decline} class="px-4 py-2 rounded-lg text-gray-600 hover:bg-gray-100">
+                                {"Decline and sign out"}
+                            </button>
+                            <button onclick={accept} class="px-4 py-2 rounded-lg bg-blue-600 text-white hover:bg-blue-700">
+                                {"I accept"}
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::rc::Rc;
+
+use gloo_timers::callback::Timeout;
+use yew::functional::*;
+use yew::prelude::*;
+
+/// How long a toast stays up unless dismissed. The same one arriving again starts it over.
+const TOAST_MS: u32 = 8_000;
+
+/// At most this many are shown at once; older ones make way.
+const MAX_TOASTS: usize = 3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ToastItem {
+    pub id: u32,
+    pub title: String,
+    pub detail: String,
+    /// How many times it was shown, as repeats are folded into one.
+    pub count: usize,
+}
+
+/// Passing notifications, newest last.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Toasts {
+    pub items: Vec<ToastItem>,
+    next_id: u32,
+}
+
+pub enum ToastAction {
+    Show { title: String, detail: String },
+    Dismiss(u32),
+}
+
+impl Reducible for Toasts {
+    type Action = ToastAction;
+
+    fn reduce(self: Rc<Self>, action: ToastAction) -> Rc<Self> {
+        let mut state = (*self).clone();
+        match action {
+            ToastAction::Show { title, detail } => {
+                let repeat = state
+                    .items
+                    .iter()
+                    .position(|t| t.title == title && t.detail == detail);
+                match repeat {
+                    Some(at) => {
+                        let mut toast = state.items.remove(at);
+                        toast.count += 1;
+                        state.items.push(toast);
+                    }
+                    None => {
+                        state.items.push(ToastItem {
+                            id: state.next_id,
+                            title,
+                            detail,
+                            count: 1,
+                        });
+                        state.next_id += 1;
+                    }
+                }
+                let excess = state.items.len().saturating_sub(MAX_TOASTS);
+                state.items.drain(..excess);
+            }
+            ToastAction::Dismiss(id) => state.items.retain(|t| t.id != id),
+        }
+        Rc::new(state)
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ToastProps {
+    pub toast: ToastItem,
+    pub on_dismiss: Callback<u32>,
+    /// Offered as a "Details" button, if given.
+    #[prop_or_default]
+    pub on_details: Option<Callback<()>>,
+}
+
+#[function_component(Toast)]
+pub fn toast(props: &ToastProps) -> Html {
+    {
+        let id = props.toast.id;
+        let on_dismiss = props.on_dismiss.clone();
+        use_effect_with_deps(
+            move |_| {
+                let timeout = Timeout::new(TOAST_MS, move || on_dismiss.emit(id));
+                move || drop(timeout)
+            },
+            props.toast.count,
+        );
+    }
+
+    let dismiss = {
+        let id = props.toast.id;
+        props.on_dismiss.reform(move |_: MouseEvent| id)
+    };
+    html! {
+        <div role="alert" class="w-80 p-3 rounded-lg bg-gray-900/90 text-white shadow-lg flex gap-2">
+            <div class="grow min-w-0">
+                <div class="text-sm font-semibold">
+                    {&props.toast.title}
+                    {
+                        if props.toast.count > 1 {
+                            html! { <span class="ml-1 text-gray-400">{format!("×{}", props.toast.count)}</span> }
+                        } else {
+                            html! {}
+                        }
+                    }
+                </div>
+                <div class="text-xs text-gray-300 break-words">{&props.toast.detail}</div>
+                {
+                    if let Some(on_details) = &props.on_details {
+                        html! {
+                            <button onclick={on_details.reform(|_| ())} class="mt-1 text-xs text-blue-300 hover:underline">
+                                {"Details"}
+                            </button>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+            </div>
+            <button onclick={dismiss} class="self-start px-1 rounded hover:bg-white/10">{"×"}</button>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    fn show(toasts: Rc<Toasts>, title: &str) -> Rc<Toasts> {
+        toasts.reduce(ToastAction::Show {
+            title: title.into(),
+            detail: "detail".into(),
+        })
+    }
+
+    #[wasm_bindgen_test]
+    fn repeats_are_folded_and_the_oldest_make_way() {
+        let mut toasts = Rc::new(Toasts::default());
+        for title in ["a", "b", "a", "c", "d"] {
+            toasts = show(toasts, title);
+        }
+        let shown: Vec<(&str, usize)> = toasts
+            .items
+            .iter()
+            .map(|t| (t.title.as_str(), t.count))
+            .collect();
+        assert_eq!(shown, [("a", 2), ("c", 1), ("d", 1)]);
+
+        let id = toasts.items[0].id;
+        let toasts = toasts.reduce(ToastAction::Dismiss(id));
+        assert_eq!(toasts.items.len(), 2);
+    }
+}
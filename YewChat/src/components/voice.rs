@@ -0,0 +1,787 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+
+use gloo_timers::callback::Interval;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{MediaStream, MediaStreamTrack};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::components::call::StreamVideo;
+use crate::components::call_layout::{CallLayout, Tile};
+use crate::protocol::{IceCandidate, MsgTypes, WebSocketMessage};
+use crate::services::audio_devices::Microphone;
+use crate::services::call::{Call, CallEvent};
+use crate::services::recorder::Recorder;
+use crate::services::voice_activity::{Meters, TICK_MS};
+use crate::services::websocket::WebsocketService;
+use crate::store::{Store, UserProfile};
+
+/// Where we are with the voice channel of our room.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VoiceStatus {
+    Off,
+    /// Waiting for the microphone, then for the server to put us in.
+    Joining,
+    Joined,
+}
+
+#[derive(Clone)]
+struct VoicePeer {
+    call: Rc<Call>,
+    /// What they send us, once it arrives.
+    remote: Option<MediaStream>,
+}
+
+/// The voice channel of the room we are in, once we join it: our microphone, and camera if we came
+/// with it, and a connection to each of the others in it. Of two people, whoever's name sorts first offers to connect, so that
+/// two who join at once don't both do.
+#[derive(Clone)]
+pub struct VoiceState {
+    wss: WebsocketService,
+    status: VoiceStatus,
+    microphone: Option<Rc<Microphone>>,
+    muted: bool,
+    /// Cleared while push-to-talk holds our voice back.
+    transmitting: bool,
+    camera_off: bool,
+    peers: BTreeMap<String, VoicePeer>,
+    /// Our recording of the channel, while we make one.
+    recorder: Option<Rc<Recorder>>,
+    /// Those of the others who say they are recording it.
+    recording: BTreeSet<String>,
+    /// Why we couldn't join or record, until we try again.
+    notice: Option<String>,
+}
+
+pub enum VoiceAction {
+    Join,
+    /// What the browser said when asked for the microphone, and maybe camera, on joining.
+    Microphone(Result<Microphone, String>),
+    Leave,
+    ToggleMute,
+    /// Pauses or resumes our camera. The others see black meanwhile, as in a call.
+    ToggleCamera,
+    /// Starts recording the channel of `room`, or stops and saves the recording.
+    ToggleRecording {
+        room: String,
+    },
+    /// Lets our voice out or holds it back, for push-to-talk.
+    Transmit(bool),
+    /// Those of the room in its voice channel, as its `users` frame says, with us as `me`.
+    Members {
+        me: String,
+        voice: Vec<String>,
+        on_event: Callback<(String, CallEvent)>,
+    },
+    /// A `voiceoffer`, `voiceanswer`, `voicecandidate` or `voicerecording` from the server.
+    Signal {
+        message: Box<WebSocketMessage>,
+        on_event: Callback<(String, CallEvent)>,
+    },
+    /// What the connection to `peer` reports.
+    Event {
+        peer: String,
+        event: CallEvent,
+    },
+}
+
+/// Those of `voice` we should offer to connect to, as `me`: the others in it whose names sort
+/// after ours, unless we are `connected` to them already.
+fn to_dial<'a>(me: &str, voice: &'a [String], connected: impl Fn(&str) -> bool) -> Vec<&'a String> {
+    voice
+        .iter()
+        .filter(|peer| me < peer.as_str() && !connected(peer))
+        .collect()
+}
+
+/// What the badge of a channel being recorded says, with `ourselves` whether we are and `others`
+/// those of the others who are.
+fn recording_label(ourselves: bool, others: &BTreeSet<String>) -> Option<String> {
+    let mut names: Vec<&str> = ourselves.then_some("You").into_iter().collect();
+    names.extend(others.iter().map(String::as_str));
+    let (last, first) = names.split_last()?;
+    let who = match first {
+        [] => last.to_string(),
+        _ => format!("{} and {}", first.join(", "), last),
+    };
+    let verb = if names.len() == 1 && !ourselves {
+        "is"
+    } else {
+        "are"
+    };
+    Some(format!("{} {} recording", who, verb))
+}
+
+/// Reports what the connection to `peer` says as said about them.
+fn events_of(peer: &str, on_event: &Callback<(String, CallEvent)>) -> Callback<CallEvent> {
+    let peer = peer.to_string();
+    on_event.reform(move |event| (peer.clone(), event))
+}
+
+impl VoiceState {
+    pub fn new(wss: WebsocketService) -> Self {
+        Self {
+            wss,
+            status: VoiceStatus::Off,
+            microphone: None,
+            muted: false,
+            transmitting: true,
+            camera_off: false,
+            peers: BTreeMap::new(),
+            recorder: None,
+            recording: BTreeSet::new(),
+            notice: None,
+        }
+    }
+
+    /// Tells `peer` whether we are recording the channel now.
+    fn announce_recording(&self, peer: &str) {
+        self.wss.send(&WebSocketMessage {
+            data: self.recorder.is_some().then(|| "recording".to_string()),
+            to: Some(peer.to_string()),
+            ..WebSocketMessage::new(MsgTypes::VoiceRecording)
+        });
+    }
+
+    /// Keeps track of a new connection to `peer`, who is told if we are recording.
+    fn connected(&mut self, peer: String, call: Call) {
+        if self.recorder.is_some() {
+            self.announce_recording(&peer);
+        }
+        let call = Rc::new(call);
+        self.peers.insert(peer, VoicePeer { call, remote: None });
+    }
+
+    fn announce(&self, joined: bool) {
+        self.wss.send(&WebSocketMessage {
+            data: joined.then(|| "join".to_string()),
+            ..WebSocketMessage::new(MsgTypes::Voice)
+        });
+    }
+
+    pub fn status(&self) -> VoiceStatus {
+        self.status
+    }
+
+    /// Lets our voice out unless muted or held back by push-to-talk.
+    fn gate_audio(&self) {
+        let Some(microphone) = &self.microphone else {
+            return;
+        };
+        let on = !self.muted && self.transmitting;
+        for track in microphone.stream().get_audio_tracks().iter() {
+            track.unchecked_into::<MediaStreamTrack>().set_enabled(on);
+        }
+    }
+
+    /// Whether we came with a camera, on or not.
+    fn has_camera(&self) -> bool {
+        self.microphone
+            .as_ref()
+            .is_some_and(|microphone| microphone.stream().get_video_tracks().length() > 0)
+    }
+
+    fn gate_video(&self) {
+        let Some(microphone) = &self.microphone else {
+            return;
+        };
+        for track in microphone.stream().get_video_tracks().iter() {
+            track
+                .unchecked_into::<MediaStreamTrack>()
+                .set_enabled(!self.camera_off);
+        }
+    }
+
+    /// Hangs up on everyone and lets go of the microphone, saving our recording if we made one.
+    fn hang_up(&mut self) {
+        self.status = VoiceStatus::Off;
+        self.peers.clear();
+        self.microphone = None;
+        self.recorder = None;
+        self.recording.clear();
+    }
+
+    fn on_members(&mut self, me: &str, voice: &[String], on_event: Callback<(String, CallEvent)>) {
+        let inside = voice.iter().any(|name| name == me);
+        match (self.status, inside) {
+            (VoiceStatus::Joining, true) => self.status = VoiceStatus::Joined,
+            // Taken out by the server, as when we reconnect or are sent to another room.
+            (VoiceStatus::Joined, false) => {
+                self.hang_up();
+                return;
+            }
+            (VoiceStatus::Joined, true) => {}
+            _ => return,
+        }
+        let Some(microphone) = &self.microphone else {
+            return;
+        };
+        let microphone = microphone.stream().clone();
+        self.peers.retain(|name, _| voice.contains(name));
+        self.recording.retain(|name| voice.contains(name));
+        let dial = to_dial(me, voice, |peer| self.peers.contains_key(peer));
+        for peer in dial {
+            let events = events_of(peer, &on_event);
+            match Call::dial_voice(peer.clone(), microphone.clone(), events) {
+                Ok(call) => self.connected(peer.clone(), call),
+                Err(e) => log::warn!("voice: can't connect to {}: {}", peer, e),
+            }
+        }
+    }
+
+    fn on_signal(&mut self, message: WebSocketMessage, on_event: Callback<(String, CallEvent)>) {
+        let peer = message.from.unwrap_or_default();
+        let events = events_of(&peer, &on_event);
+        match message.message_type {
+            MsgTypes::VoiceOffer => {
+                let (VoiceStatus::Joined, Some(microphone), Some(offer)) =
+                    (self.status, &self.microphone, message.data)
+                else {
+                    return;
+                };
+                let microphone = microphone.stream().clone();
+                match Call::answer_voice(peer.clone(), &offer, vec![], microphone, events) {
+                    Ok(call) => self.connected(peer, call),
+                    Err(e) => log::warn!("voice: can't answer {}: {}", peer, e),
+                }
+            }
+            MsgTypes::VoiceAnswer => {
+                if let (Some(connection), Some(answer)) = (self.peers.get(&peer), message.data) {
+                    connection.call.accept_answer(&answer, events);
+                }
+            }
+            MsgTypes::VoiceCandidate => {
+                let candidate = message
+                    .data
+                    .and_then(|d| serde_json::from_str::<IceCandidate>(&d).ok());
+                if let (Some(connection), Some(candidate)) = (self.peers.get(&peer), candidate) {
+                    connection.call.add_candidate(candidate);
+                }
+            }
+            MsgTypes::VoiceRecording => {
+                if !self.peers.contains_key(&peer) {
+                    return;
+                }
+                if message.data.is_some() {
+                    self.recording.insert(peer);
+                } else {
+                    self.recording.remove(&peer);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The channel of `room`, for the sidebar, with `members` the people of the room in it.
+    /// Without `may_speak`, it can't be joined. `push_to_talk` is the key held to talk, if there
+    /// is one. Once some of us have their camera on, everyone is shown in a [`CallLayout`] too.
+    pub fn view(
+        &self,
+        voice: &UseReducerDispatcher<VoiceState>,
+        me: &str,
+        room: &str,
+        members: Vec<UserProfile>,
+        may_speak: bool,
+        push_to_talk: Option<String>,
+    ) -> Html {
+        let on_join = may_speak.then(|| {
+            let voice = voice.clone();
+            Callback::from(move |video: bool| {
+                voice.dispatch(VoiceAction::Join);
+                let voice = voice.clone();
+                spawn_local(async move {
+                    voice.dispatch(VoiceAction::Microphone(Microphone::open(video).await));
+                });
+            })
+        });
+        let on_leave = {
+            let voice = voice.clone();
+            Callback::from(move |_| voice.dispatch(VoiceAction::Leave))
+        };
+        let on_toggle_mute = {
+            let voice = voice.clone();
+            Callback::from(move |_| voice.dispatch(VoiceAction::ToggleMute))
+        };
+        let on_toggle_camera = {
+            let voice = voice.clone();
+            Callback::from(move |_| voice.dispatch(VoiceAction::ToggleCamera))
+        };
+        let on_toggle_recording = {
+            let voice = voice.clone();
+            let room = room.to_string();
+            Callback::from(move |_| {
+                voice.dispatch(VoiceAction::ToggleRecording { room: room.clone() })
+            })
+        };
+        let recording = self.recorder.is_some();
+        let recording_label = recording_label(recording, &self.recording);
+        let mut recorders = self.recording.clone();
+        if recording {
+            recorders.insert(me.to_string());
+        }
+        let remote: BTreeMap<String, MediaStream> = self
+            .peers
+            .iter()
+            .filter_map(|(name, peer)| Some((name.clone(), peer.remote.clone()?)))
+            .collect();
+        let mut streams = remote.clone();
+        if let (VoiceStatus::Joined, Some(microphone), false, true) =
+            (self.status, &self.microphone, self.muted, self.transmitting)
+        {
+            streams.insert(me.to_string(), microphone.stream().clone());
+        }
+        let camera = self.has_camera().then_some(!self.camera_off);
+        let own_video = (camera == Some(true))
+            .then(|| self.microphone.as_ref().map(|m| m.stream().clone()))
+            .flatten();
+        let tiles: Vec<Tile> = members
+            .iter()
+            .map(|member| Tile {
+                name: member.name.clone(),
+                avatar: member.avatar.clone(),
+                video: if member.name == me {
+                    own_video.clone()
+                } else {
+                    remote
+                        .get(&member.name)
+                        .filter(|stream| stream.get_video_tracks().length() > 0)
+                        .cloned()
+                },
+                own: member.name == me,
+            })
+            .collect();
+        let tiles = match self.status {
+            VoiceStatus::Joined if tiles.iter().any(|tile| tile.video.is_some()) => tiles,
+            _ => vec![],
+        };
+
+        html! {
+            <VoiceChannel
+                {members}
+                status={self.status}
+                muted={self.muted}
+                remote={Rc::new(remote)}
+                streams={Rc::new(streams)}
+                notice={self.notice.clone()}
+                {push_to_talk}
+                {camera}
+                {tiles}
+                {recording}
+                {recorders}
+                {recording_label}
+                {on_join}
+                {on_leave}
+                {on_toggle_mute}
+                {on_toggle_camera}
+                {on_toggle_recording}
+            />
+        }
+    }
+}
+
+impl Reducible for VoiceState {
+    type Action = VoiceAction;
+
+    fn reduce(self: Rc<Self>, action: VoiceAction) -> Rc<Self> {
+        let mut state = (*self).clone();
+        match action {
+            VoiceAction::Join => {
+                if state.status != VoiceStatus::Off {
+                    return self;
+                }
+                state.status = VoiceStatus::Joining;
+                state.camera_off = false;
+                state.notice = None;
+            }
+            VoiceAction::Microphone(Ok(microphone)) => {
+                let microphone = Rc::new(microphone);
+                // We left while the browser was asking.
+                if state.status != VoiceStatus::Joining || state.microphone.is_some() {
+                    return self;
+                }
+                state.microphone = Some(microphone);
+                state.gate_audio();
+                state.announce(true);
+            }
+            VoiceAction::Microphone(Err(e)) => {
+                if state.status != VoiceStatus::Joining {
+                    return self;
+                }
+                log::debug!("voice: no microphone: {}", e);
+                state.hang_up();
+                state.notice = Some("Microphone access was denied.".into());
+            }
+            VoiceAction::Leave => {
+                if state.status == VoiceStatus::Off {
+                    return self;
+                }
+                state.announce(false);
+                state.hang_up();
+            }
+            VoiceAction::ToggleMute => {
+                state.muted = !state.muted;
+                state.gate_audio();
+            }
+            VoiceAction::ToggleCamera => {
+                state.camera_off = !state.camera_off;
+                state.gate_video();
+            }
+            VoiceAction::ToggleRecording { room } => {
+                if state.status != VoiceStatus::Joined {
+                    return self;
+                }
+                if state.recorder.take().is_none() {
+                    let streams = state
+                        .microphone
+                        .iter()
+                        .map(|microphone| microphone.stream())
+                        .chain(state.peers.values().filter_map(|peer| peer.remote.as_ref()));
+                    match Recorder::start(&format!("voice-{}", room), streams) {
+                        Ok(recorder) => state.recorder = Some(Rc::new(recorder)),
+                        Err(e) => {
+                            log::warn!("voice: can't record: {:?}", e);
+                            state.notice =
+                                Some("Recording isn't supported by this browser.".into());
+                            return Rc::new(state);
+                        }
+                    }
+                }
+                for peer in state.peers.keys() {
+                    state.announce_recording(peer);
+                }
+            }
+            VoiceAction::Transmit(transmitting) => {
+                state.transmitting = transmitting;
+                state.gate_audio();
+            }
+            VoiceAction::Members {
+                me,
+                voice,
+                on_event,
+            } => {
+                if state.status == VoiceStatus::Off {
+                    return self;
+                }
+                state.on_members(&me, &voice, on_event);
+            }
+            VoiceAction::Signal { message, on_event } => state.on_signal(*message, on_event),
+            VoiceAction::Event { peer, event } => match event {
+                CallEvent::Signal(message) => {
+                    state.wss.send(&message);
+                    return self;
+                }
+                CallEvent::RemoteMedia(stream) => {
+                    let Some(connection) = state.peers.get_mut(&peer) else {
+                        return self;
+                    };
+                    if let Some(recorder) = &state.recorder {
+                        recorder.add(&stream);
+                    }
+                    connection.remote = Some(stream);
+                }
+                CallEvent::Failed(reason) => {
+                    log::warn!("voice: lost {}: {}", peer, reason);
+                    if state.peers.remove(&peer).is_none() {
+                        return self;
+                    }
+                    state.recording.remove(&peer);
+                }
+                CallEvent::Connected | CallEvent::LocalMedia(_) => return self,
+            },
+        }
+        Rc::new(state)
+    }
+}
+
+/// The voice channel of the room we are in.
+pub struct UseVoice {
+    pub state: UseReducerHandle<VoiceState>,
+    /// What the connection to each peer reports.
+    pub on_event: Callback<(String, CallEvent)>,
+}
+
+/// Voice channels over `wss` as `username`, connecting to whoever the server says is in the room's.
+pub fn use_voice(wss: &WebsocketService, username: &str) -> UseVoice {
+    let store = use_context::<Store>().expect("No store found.");
+    let state = {
+        let wss = wss.clone();
+        use_reducer(move || VoiceState::new(wss))
+    };
+    let on_event = {
+        let voice = state.dispatcher();
+        Callback::from(move |(peer, event)| voice.dispatch(VoiceAction::Event { peer, event }))
+    };
+    {
+        let voice = state.dispatcher();
+        let me = username.to_string();
+        let on_event = on_event.clone();
+        use_effect_with_deps(
+            move |users: &Vec<UserProfile>| {
+                voice.dispatch(VoiceAction::Members {
+                    me,
+                    voice: users
+                        .iter()
+                        .filter(|u| u.voice)
+                        .map(|u| u.name.clone())
+                        .collect(),
+                    on_event,
+                });
+                || ()
+            },
+            store.users.clone(),
+        );
+    }
+    UseVoice { state, on_event }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct VoiceChannelProps {
+    /// Those of the room in its voice channel.
+    pub members: Vec<UserProfile>,
+    pub status: VoiceStatus,
+    pub muted: bool,
+    /// What we hear of each of the others, to be played.
+    pub remote: Rc<BTreeMap<String, MediaStream>>,
+    /// Those, and our own microphone while it is on, to tell who is speaking.
+    pub streams: Rc<BTreeMap<String, MediaStream>>,
+    pub notice: Option<String>,
+    /// The key held to talk, as it reads, if push-to-talk is on.
+    pub push_to_talk: Option<String>,
+    /// Whether our camera is on, if we came with one.
+    pub camera: Option<bool>,
+    /// Everyone, for the [`CallLayout`], once some of us have their camera on.
+    pub tiles: Vec<Tile>,
+    /// Whether we are recording the channel.
+    pub recording: bool,
+    /// Those recording it, ourselves included, and as a badge says so.
+    pub recorders: BTreeSet<String>,
+    pub recording_label: Option<String>,
+    /// Joins the channel, with the camera too if given `true`. Without it, our role in the room
+    /// may not speak.
+    pub on_join: Option<Callback<bool>>,
+    pub on_leave: Callback<()>,
+    pub on_toggle_mute: Callback<()>,
+    pub on_toggle_camera: Callback<()>,
+    pub on_toggle_recording: Callback<()>,
+}
+
+/// The voice channel of the room, always open: who is in it, ringed while they speak, and the
+/// buttons to join, mute, record and leave. It plays what the others say, and marks whoever is
+/// recording it.
+#[function_component(VoiceChannel)]
+pub fn voice_channel(props: &VoiceChannelProps) -> Html {
+    let meters = use_mut_ref(Meters::default);
+    let speaking = use_state_eq(BTreeSet::<String>::new);
+    {
+        let meters = meters.clone();
+        let speaking = speaking.setter();
+        use_effect_with_deps(
+            move |streams: &Rc<BTreeMap<String, MediaStream>>| {
+                meters.borrow_mut().follow(streams);
+                let tick = move || speaking.set(meters.borrow_mut().speaking());
+                tick();
+                // With nobody to listen to, there is nothing to read.
+                let interval = (!streams.is_empty()).then(|| Interval::new(TICK_MS, tick));
+                move || drop(interval)
+            },
+            props.streams.clone(),
+        );
+    }
+    let on_join = props.on_join.as_ref().map(|join| join.reform(|_| false));
+    let on_join_with_video = props.on_join.as_ref().map(|join| join.reform(|_| true));
+    let on_leave = props.on_leave.reform(|_| ());
+    let on_toggle_mute = props.on_toggle_mute.reform(|_| ());
+    let on_toggle_camera = props.on_toggle_camera.reform(|_| ());
+    let on_toggle_recording = props.on_toggle_recording.reform(|_| ());
+    let view_member = |u: &UserProfile| {
+        let speaks = speaking.contains(&u.name);
+        let records = props.recorders.contains(&u.name);
+        html! {
+            <div class="flex items-center gap-2 py-1" title={if speaks { format!("{} is speaking", u.name) } else { u.name.clone() }}>
+                <img
+                    class={classes!(
+                        "w-8", "h-8", "rounded-full", "ring-2", "transition-shadow",
+                        if speaks { "ring-green-500" } else { "ring-transparent" }
+                    )}
+                    src={u.avatar.clone()}
+                    alt="avatar"
+                />
+                <span class={classes!("text-sm", "truncate", speaks.then_some("font-semibold"))}>{u.name.clone()}</span>
+                {
+                    if records {
+                        html! {
+                            <span title={format!("{} is recording the channel", u.name)} class="ml-auto px-1.5 rounded bg-red-600 text-white text-[10px] font-semibold">
+                                {"REC"}
+                            </span>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+            </div>
+        }
+    };
+
+    html! {
+        <div class="px-4 pt-3 pb-1">
+            <div class="flex items-center gap-2 text-xs font-semibold uppercase tracking-wide text-gray-500">
+                {format!("Voice — {}", props.members.len())}
+                <div class="ml-auto flex gap-1 normal-case tracking-normal font-normal">
+                    {
+                        match props.status {
+                            VoiceStatus::Off => html! {
+                                <>
+                                    <button
+                                        onclick={on_join_with_video.clone()}
+                                        disabled={on_join_with_video.is_none()}
+                                        title={if on_join_with_video.is_some() { "Join with your camera on" } else { "Your role in this room can't speak" }}
+                                        class="px-2 py-0.5 rounded-full bg-gray-100 hover:bg-gray-200 disabled:opacity-50"
+                                    >
+                                        {"📹"}
+                                    </button>
+                                    <button
+                                        onclick={on_join.clone()}
+                                        disabled={on_join.is_none()}
+                                        title={if on_join.is_some() { "Join the room's voice channel" } else { "Your role in this room can't speak" }}
+                                        class="px-2 py-0.5 rounded-full bg-green-600 text-white hover:bg-green-700 disabled:opacity-50"
+                                    >
+                                        {"Join"}
+                                    </button>
+                                </>
+                            },
+                            VoiceStatus::Joining => html! { <span>{"Connecting…"}</span> },
+                            VoiceStatus::Joined => html! {
+                                <>
+                                    <button
+                                        onclick={on_toggle_mute}
+                                        title={if props.muted { "Unmute" } else { "Mute" }}
+                                        class={classes!(
+                                            "px-2", "py-0.5", "rounded-full",
+                                            if props.muted { vec!["bg-gray-700", "text-white"] } else { vec!["bg-gray-100", "hover:bg-gray-200"] }
+                                        )}
+                                    >
+                                        { if props.muted { "🔇" } else { "🎙️" } }
+                                    </button>
+                                    {
+                                        if let Some(on) = props.camera {
+                                            html! {
+                                                <button
+                                                    onclick={on_toggle_camera}
+                                                    title={if on { "Turn camera off" } else { "Turn camera on" }}
+                                                    class={classes!(
+                                                        "px-2", "py-0.5", "rounded-full",
+                                                        if on { vec!["bg-gray-100", "hover:bg-gray-200"] } else { vec!["bg-gray-700", "text-white"] }
+                                                    )}
+                                                >
+                                                    {"📷"}
+                                                </button>
+                                            }
+                                        } else {
+                                            html! {}
+                                        }
+                                    }
+                                    <button
+                                        onclick={on_toggle_recording}
+                                        title={if props.recording { "Stop recording and save it" } else { "Record the channel" }}
+                                        class={classes!(
+                                            "px-2", "py-0.5", "rounded-full",
+                                            if props.recording { vec!["bg-red-600", "text-white"] } else { vec!["bg-gray-100", "hover:bg-gray-200"] }
+                                        )}
+                                    >
+                                        {"⏺"}
+                                    </button>
+                                    <button onclick={on_leave} class="px-2 py-0.5 rounded-full bg-red-500 text-white hover:bg-red-600">
+                                        {"Leave"}
+                                    </button>
+                                </>
+                            },
+                        }
+                    }
+                </div>
+            </div>
+            {
+                if props.members.is_empty() {
+                    html! { <div class="py-1 text-xs text-gray-400">{"Nobody is talking."}</div> }
+                } else {
+                    props.members.iter().map(view_member).collect::<Html>()
+                }
+            }
+            {
+                if let Some(label) = &props.recording_label {
+                    html! {
+                        <div class="flex items-center gap-2 my-1 px-2 py-1 rounded-lg bg-red-600 text-white text-xs font-semibold">
+                            <span class="w-2 h-2 rounded-full bg-white animate-pulse"></span>
+                            {label.clone()}
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }
+            }
+            {
+                match (&props.push_to_talk, props.status) {
+                    (Some(key), VoiceStatus::Joined) => html! {
+                        <div class="py-1 text-xs text-gray-400">{format!("Hold {} to talk", key)}</div>
+                    },
+                    _ => html! {},
+                }
+            }
+            {
+                if let Some(notice) = &props.notice {
+                    html! { <div class="py-1 text-xs text-red-600">{notice.clone()}</div> }
+                } else {
+                    html! {}
+                }
+            }
+            {
+                for props.remote.values().map(|stream| html! {
+                    <StreamVideo stream={Some(stream.clone())} class="hidden" />
+                })
+            }
+            {
+                if props.tiles.is_empty() {
+                    html! {}
+                } else {
+                    html! { <CallLayout tiles={props.tiles.clone()} speaking={(*speaking).clone()} /> }
+                }
+            }
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn only_the_first_of_two_names_dials() {
+        let voice: Vec<String> = vec!["alice".into(), "bob".into(), "carol".into()];
+        assert_eq!(to_dial("bob", &voice, |_| false), vec!["carol"]);
+        assert_eq!(
+            to_dial("alice", &voice, |peer| peer == "bob"),
+            vec!["carol"]
+        );
+        assert!(to_dial("carol", &voice, |_| false).is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn the_badge_names_whoever_records() {
+        let others = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+        assert_eq!(recording_label(false, &others(&[])), None);
+        assert_eq!(
+            recording_label(true, &others(&[])).as_deref(),
+            Some("You are recording")
+        );
+        assert_eq!(
+            recording_label(false, &others(&["bob"])).as_deref(),
+            Some("bob is recording")
+        );
+        assert_eq!(
+            recording_label(true, &others(&["bob", "carol"])).as_deref(),
+            Some("You, bob and carol are recording")
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::components::message_list::view_text;
+use crate::services::welcome;
+
+#[derive(Properties, PartialEq)]
+pub struct WelcomeProps {
+    /// The server's message of the day, formatted like a message.
+    pub text: String,
+    pub username: String,
+    #[prop_or_default]
+    pub emoji: Rc<HashMap<String, String>>,
+}
+
+/// The message of the day, at the top of the room until closed.
+#[function_component(Welcome)]
+pub fn welcome_card(props: &WelcomeProps) -> Html {
+    let dismissed = use_state(|| welcome::dismissed(&props.username, &props.text));
+    // A different message, after a restart of the server, shows again.
+    if *dismissed && welcome::dismissed(&props.username, &props.text) {
+        return html! {};
+    }
+    let onclick = {
+        let dismissed = dismissed.clone();
+        let username = props.username.clone();
+        let text = props.text.clone();
+        Callback::from(move |_| {
+            welcome::dismiss(&username, &text);
+            dismissed.set(true);
+        })
+    };
+
+    html! {
+        <div class="relative mb-4 p-4 pr-10 rounded-lg bg-white border border-blue-200 shadow-sm text-gray-800">
+            <div class="text-xs font-semibold uppercase tracking-wide text-blue-700 mb-1">{"Welcome"}</div>
+            <div class="whitespace-pre-wrap break-words">{view_text(&props.text, &props.emoji)}</div>
+            <button
+                {onclick}
+                title="Close"
+                class="absolute top-2 right-2 p-1 rounded-full text-gray-400 hover:text-gray-700 hover:bg-gray-100"
+            >
+                <svg xmlns="http://www.w3.org/2000/svg" class="h-4 w-4" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                    <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M6 18L18 6M6 6l12 12" />
+                </svg>
+            </button>
+        </div>
+    }
+}
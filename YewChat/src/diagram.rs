@@ -0,0 +1,77 @@
+//! Drawing the diagrams found by [`crate::blocks`] with Mermaid, which is fetched from its CDN the
+//! first time a message has one.
+
+use std::cell::Cell;
+
+use js_sys::{Function, Object, Promise, Reflect};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use crate::services::script;
+
+// Not pinned yet: its hash has to be taken from the published file, with
+// `openssl dgst -sha384 -binary mermaid.min.js | openssl base64 -A`.
+const MERMAID: script::Asset = script::Asset {
+    url: "https://cdn.jsdelivr.net/npm/mermaid@10.9.1/dist/mermaid.min.js",
+    integrity: None,
+};
+
+thread_local! {
+    static INITIALIZED: Cell<bool> = const { Cell::new(false) };
+    /// Mermaid needs an id for every diagram it draws.
+    static NEXT_ID: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Waits for Mermaid to be loaded and set up.
+async fn load() -> Result<JsValue, JsValue> {
+    script::load(&MERMAID, None).await?;
+    let window = web_sys::window().ok_or("no window")?;
+    let mermaid = Reflect::get(&window, &"mermaid".into())?;
+    if !INITIALIZED.with(Cell::get) {
+        // Drawn when asked, with the labels in diagrams kept from running scripts or linking out.
+        let config = Object::new();
+        Reflect::set(&config, &"startOnLoad".into(), &false.into())?;
+        Reflect::set(&config, &"securityLevel".into(), &"strict".into())?;
+        let initialize: Function = Reflect::get(&mermaid, &"initialize".into())?.dyn_into()?;
+        initialize.call1(&mermaid, &config)?;
+        INITIALIZED.with(|i| i.set(true));
+    }
+    Ok(mermaid)
+}
+
+/// Draws `source` as SVG markup, or says why it can't be drawn.
+pub async fn render(source: &str) -> Result<String, String> {
+    let mermaid = load()
+        .await
+        .map_err(|_| "Mermaid could not be loaded".to_string())?;
+    let id = NEXT_ID.with(|next| {
+        let id = next.get();
+        next.set(id + 1);
+        format!("yewchat-diagram-{}", id)
+    });
+    let drawn = async {
+        let render: Function = Reflect::get(&mermaid, &"render".into())?.dyn_into()?;
+        let promise: Promise = render
+            .call2(&mermaid, &id.as_str().into(), &source.into())?
+            .dyn_into()?;
+        let result = JsFuture::from(promise).await?;
+        Reflect::get(&result, &"svg".into())?
+            .as_string()
+            .ok_or_else(|| JsValue::from("no svg"))
+    }
+    .await;
+    drawn.map_err(|e| {
+        // A failed diagram leaves its error drawing behind on the page.
+        if let Some(left) = web_sys::window()
+            .and_then(|w| w.document())
+            .and_then(|d| d.get_element_by_id(&format!("d{}", id)))
+        {
+            left.remove();
+        }
+        Reflect::get(&e, &"message".into())
+            .ok()
+            .and_then(|m| m.as_string())
+            .or_else(|| e.as_string())
+            .unwrap_or_else(|| "it isn't a diagram Mermaid knows".to_string())
+    })
+}
@@ -0,0 +1,99 @@
+//! A room's custom emoji, written `:name:` in messages and shown as the picture its owner gave.
+
+use std::collections::HashMap;
+
+use crate::sanitize;
+
+pub const NAME_MAX_LEN: usize = 32;
+
+/// Largest picture for a custom emoji. Everyone in the room is sent all of them.
+pub const MAX_BYTES: f64 = 64.0 * 1024.0;
+
+/// Everyday emoji, offered in the picker after the room's own.
+pub const COMMON: [&str; 16] = [
+    "😀", "😂", "😊", "😍", "🤔", "😢", "😮", "😡", "👍", "👎", "👏", "🙏", "🎉", "❤️", "🔥", "👀",
+];
+
+/// A piece of a message, split by [`split`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Piece {
+    Text(String),
+    Emoji { name: String, image: String },
+}
+
+/// Whether `name` can name a custom emoji: lowercase letters, digits, `_`, `-` or `+`.
+pub fn valid_name(name: &str) -> bool {
+    (1..=NAME_MAX_LEN).contains(&name.len())
+        && name
+            .bytes()
+            .all(|b| matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' | b'+'))
+}
+
+/// Splits text into plain text and the room's emoji in it. `:names:` the room doesn't have stay
+/// as they were written.
+pub fn split(text: &str, custom: &HashMap<String, String>) -> Vec<Piece> {
+    let mut pieces = vec![];
+    let mut plain = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        let after = &rest[start + 1..];
+        let found = after.find(':').and_then(|end| {
+            let name = &after[..end];
+            Some((name, sanitize::image(custom.get(name)?)?))
+        });
+        match found {
+            Some((name, image)) => {
+                plain.push_str(&rest[..start]);
+                if !plain.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut plain)));
+                }
+                pieces.push(Piece::Emoji {
+                    name: name.to_string(),
+                    image,
+                });
+                rest = &after[name.len() + 1..];
+            }
+            None => {
+                // The closing colon may open the next one.
+                plain.push_str(&rest[..=start]);
+                rest = after;
+            }
+        }
+    }
+    plain.push_str(rest);
+    if !plain.is_empty() {
+        pieces.push(Piece::Text(plain));
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn finds_the_rooms_emoji() {
+        let image = "data:image/png;base64,x";
+        let custom = HashMap::from([
+            ("party".to_string(), image.to_string()),
+            ("evil".to_string(), "javascript:alert(1)".to_string()),
+        ]);
+        let emoji = Piece::Emoji {
+            name: "party".into(),
+            image: image.into(),
+        };
+        assert_eq!(
+            split("at 12:30 :party::party: :nope: :evil:", &custom),
+            [
+                Piece::Text("at 12:30 ".into()),
+                emoji.clone(),
+                emoji,
+                Piece::Text(" :nope: :evil:".into()),
+            ]
+        );
+        assert!(valid_name("thumbs_up+1"));
+        assert!(!valid_name("Party"));
+        assert!(!valid_name(""));
+    }
+}
@@ -1,18 +1,40 @@
 #![recursion_limit = "512"]
 
+mod avatar;
+mod blocks;
 mod components;
+mod diagram;
+mod emoji;
+#[cfg(feature = "math")]
+mod math;
+mod protocol;
+mod sanitize;
 mod services;
+mod store;
+mod time;
 
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 use yew::functional::*;
 use yew::prelude::*;
 use yew_router::prelude::*;
 
+use components::admin::Admin;
+use components::archive::Archive;
 use components::chat::Chat;
 use components::login::Login;
+use components::message_list;
+use components::oauth_callback::OAuthCallback;
+use components::terms_gate::TermsGate;
+use protocol::Tokens;
+use services::irc::IrcSession;
+use services::matrix::MatrixSession;
+use services::service_worker::{self, PendingUpdate};
+use services::session::Session;
+use store::StoreProvider;
 
 // When the `wee_alloc` feature is enabled, this uses `wee_alloc` as the global
 // allocator.
@@ -22,31 +44,94 @@ use components::login::Login;
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
-#[derive(Debug, Clone, Copy, PartialEq, Routable)]
+// The component tests render into a real DOM, so they have to run in a browser.
+#[cfg(test)]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+#[derive(Debug, Clone, PartialEq, Routable)]
 pub enum Route {
     #[at("/")]
+    Home,
+    #[at("/login")]
     Login,
     #[at("/chat")]
     Chat,
+    #[at("/chat/:room")]
+    ChatRoom { room: String },
+    #[at("/archive")]
+    Archive,
+    #[at("/admin")]
+    Admin,
+    #[at("/oauth/:provider")]
+    OAuthCallback { provider: String },
     #[not_found]
     #[at("/404")]
     NotFound,
 }
 
+pub const DEFAULT_ROOM: &str = "general";
+
+/// Query string for `/login`, remembering where to go once the user has signed in.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LoginQuery {
+    pub room: Option<String>,
+    /// The invite code the link to `room` had, for when it is invite-only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite: Option<String>,
+    /// A message in `room` that a link pointed at, to go back to after logging in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Set when we were sent back here because the session could not be refreshed.
+    #[serde(default)]
+    pub expired: bool,
+    /// Set when the user chose not to take over a session already connected under their name.
+    #[serde(default)]
+    pub conflict: bool,
+}
+
+/// Query string for `/chat/:room`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChatQuery {
+    /// The code that lets us into the room, when it is invite-only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite: Option<String>,
+}
+
 pub type User = Rc<UserInner>;
 
 #[derive(Debug, PartialEq)]
 pub struct UserInner {
     pub username: RefCell<String>,
+    /// Issued by the server on login; proves `username` belongs to us when connecting.
+    pub tokens: RefCell<Option<Tokens>>,
+    /// Set instead of `tokens` when chatting through a Matrix homeserver.
+    pub matrix: RefCell<Option<MatrixSession>>,
+    /// Set instead of `tokens` when chatting on an IRC network.
+    pub irc: RefCell<Option<IrcSession>>,
 }
 
 #[function_component(Main)]
 fn main() -> Html {
     let ctx = use_state(|| {
+        let session = Session::load().unwrap_or_default();
         Rc::new(UserInner {
-            username: RefCell::new("initial".into()),
+            username: RefCell::new(session.username),
+            tokens: RefCell::new(session.tokens),
+            matrix: RefCell::new(session.matrix),
+            irc: RefCell::new(session.irc),
         })
     });
+    let update = use_state(|| Option::<PendingUpdate>::None);
+    {
+        let update = update.clone();
+        use_effect_with_deps(
+            move |_| {
+                service_worker::register(Callback::from(move |pending| update.set(Some(pending))));
+                || ()
+            },
+            (),
+        );
+    }
 
     html! {
         <ContextProvider<User> context={(*ctx).clone()}>
@@ -55,14 +140,95 @@ fn main() -> Html {
                     <Switch<Route> render={Switch::render(switch)}/>
                 </div>
             </BrowserRouter>
+            {
+                if let Some(pending) = (*update).clone() {
+                    let dismiss = {
+                        let update = update.clone();
+                        Callback::from(move |_| update.set(None))
+                    };
+                    html! {
+                        <div class="fixed bottom-4 left-1/2 -translate-x-1/2 z-50 flex items-center gap-3 pl-4 pr-2 py-2 rounded-full bg-gray-900 text-white text-sm shadow-xl">
+                            <span>{"A new version of YewChat is available."}</span>
+                            <button
+                                onclick={Callback::from(move |_| pending.apply())}
+                                class="px-3 py-1 rounded-full bg-violet-500 hover:bg-violet-600 font-semibold transition-colors"
+                            >
+                                {"Refresh"}
+                            </button>
+                            <button onclick={dismiss} title="Later" class="px-2 py-1 rounded-full text-gray-400 hover:text-white">{"✕"}</button>
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }
+            }
         </ContextProvider<User>>
     }
 }
 
+#[derive(Properties, PartialEq)]
+struct ChatRoomProps {
+    room: String,
+}
+
+/// Renders the chat for `room`, sending visitors without a username to the login form first,
+/// along with the invite code the link had.
+#[function_component(ChatRoom)]
+fn chat_room(props: &ChatRoomProps) -> Html {
+    let user = use_context::<User>().expect("No context found.");
+    let history = use_history().expect("history to be available");
+    let invite = use_location()
+        .and_then(|l| l.query::<ChatQuery>().ok())
+        .and_then(|q| q.invite);
+    let logged_in = user.tokens.borrow().is_some()
+        || user.matrix.borrow().is_some()
+        || user.irc.borrow().is_some();
+
+    {
+        let room = props.room.clone();
+        let invite = invite.clone();
+        use_effect_with_deps(
+            move |logged_in| {
+                if !*logged_in {
+                    let query = LoginQuery {
+                        room: Some(room),
+                        invite,
+                        message: message_list::linked_message(),
+                        ..Default::default()
+                    };
+                    if let Err(e) = history.replace_with_query(Route::Login, query) {
+                        log::error!("failed to redirect to login: {:?}", e);
+                    }
+                }
+                || ()
+            },
+            logged_in,
+        );
+    }
+
+    if logged_in {
+        html! {
+            <TermsGate>
+                <StoreProvider room={props.room.clone()}>
+                    <Chat room={props.room.clone()} {invite} />
+                </StoreProvider>
+            </TermsGate>
+        }
+    } else {
+        html! {}
+    }
+}
+
 fn switch(selected_route: &Route) -> Html {
     match selected_route {
+        Route::Home | Route::Chat => html! {
+            <Redirect<Route> to={Route::ChatRoom { room: DEFAULT_ROOM.into() }} />
+        },
         Route::Login => html! {<Login />},
-        Route::Chat => html! {<Chat/>},
+        Route::ChatRoom { room } => html! {<ChatRoom room={room.clone()} />},
+        Route::Archive => html! {<Archive />},
+        Route::Admin => html! {<Admin />},
+        Route::OAuthCallback { provider } => html! {<OAuthCallback provider={provider.clone()} />},
         Route::NotFound => html! {<h1>{"404 baby"}</h1>},
     }
 }
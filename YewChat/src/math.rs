@@ -0,0 +1,163 @@
+//! `$...$` and `$$...$$` math in messages, typeset by KaTeX. Only built with the `math` feature;
+//! KaTeX is fetched from its CDN the first time a message needs it.
+
+use js_sys::{Function, Object, Reflect};
+use wasm_bindgen::{JsCast, JsValue};
+
+use crate::services::script;
+
+// The hashes KaTeX publishes with the release.
+const KATEX: script::Asset = script::Asset {
+    url: "https://cdn.jsdelivr.net/npm/katex@0.16.9/dist/katex.min.js",
+    integrity: Some("sha384-XjKyOOlGwcjNTAIQHIpgOno0Hl1YQqzUOEleOLALmuqehneUG+vnGctmUb0ZY0l8"),
+};
+const KATEX_CSS: script::Asset = script::Asset {
+    url: "https://cdn.jsdelivr.net/npm/katex@0.16.9/dist/katex.min.css",
+    integrity: Some("sha384-n8MVd4RsNIU0tAv4ct0nTaAbDJwPJzDEaqSD1odI+WdtXRGWt2kTvGFasHpSy3SV"),
+};
+
+/// A piece of a message, split by [`split`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Part {
+    Text(String),
+    /// `display` for `$$...$$`, set on a line of its own.
+    Math {
+        tex: String,
+        display: bool,
+    },
+}
+
+/// Splits a message into text and math. Inline math can't start or end on a space or span lines,
+/// and can't be followed by a digit, so that "$5 and $10" stay prices. `\$` is a dollar sign.
+pub fn split(text: &str) -> Vec<Part> {
+    let mut parts = vec![];
+    let mut plain = String::new();
+    let mut rest = text;
+    while let Some(at) = rest.find(['$', '\\']) {
+        plain.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if let Some(after) = rest.strip_prefix("\\$") {
+            plain.push('$');
+            rest = after;
+            continue;
+        }
+        if rest.starts_with('\\') {
+            plain.push('\\');
+            rest = &rest[1..];
+            continue;
+        }
+        let found = match rest.strip_prefix("$$") {
+            Some(inner) => display_math(inner),
+            None => inline_math(&rest[1..]),
+        };
+        match found {
+            Some((tex, display, len)) => {
+                if !plain.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut plain)));
+                }
+                parts.push(Part::Math {
+                    tex: tex.to_string(),
+                    display,
+                });
+                rest = &rest[len..];
+            }
+            None => {
+                plain.push('$');
+                rest = &rest[1..];
+            }
+        }
+    }
+    plain.push_str(rest);
+    if !plain.is_empty() {
+        parts.push(Part::Text(plain));
+    }
+    parts
+}
+
+/// The formula after an opening `$$`, and how long it is with its delimiters.
+fn display_math(inner: &str) -> Option<(&str, bool, usize)> {
+    let end = inner.find("$$")?;
+    let tex = inner[..end].trim();
+    (!tex.is_empty()).then_some((tex, true, end + 4))
+}
+
+/// The formula after an opening `$`, and how long it is with its delimiters.
+fn inline_math(inner: &str) -> Option<(&str, bool, usize)> {
+    if inner.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let line = &inner[..inner.find('\n').unwrap_or(inner.len())];
+    let mut from = 0;
+    while let Some(at) = line[from..].find('$').map(|at| from + at) {
+        let tex = &line[..at];
+        let closes = !tex.is_empty()
+            && !tex.ends_with(char::is_whitespace)
+            && !tex.ends_with('\\')
+            && !line[at + 1..].starts_with(|c: char| c.is_ascii_digit());
+        if closes {
+            return Some((tex, false, at + 2));
+        }
+        from = at + 1;
+    }
+    None
+}
+
+/// Waits for KaTeX to be loaded.
+pub async fn load() -> Result<(), JsValue> {
+    script::load(&KATEX, Some(&KATEX_CSS)).await
+}
+
+/// Typesets `tex` into `element`, replacing what it holds. Mistakes in the formula are shown in
+/// place rather than failing; commands that could add links or styles are left off, as KaTeX
+/// does by default.
+pub fn render(tex: &str, element: &web_sys::Element, display: bool) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or("no window")?;
+    let katex = Reflect::get(&window, &"katex".into())?;
+    let render: Function = Reflect::get(&katex, &"render".into())?.dyn_into()?;
+    let options = Object::new();
+    Reflect::set(&options, &"displayMode".into(), &display.into())?;
+    Reflect::set(&options, &"throwOnError".into(), &false.into())?;
+    render.call3(&katex, &tex.into(), element, &options)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    fn text(text: &str) -> Part {
+        Part::Text(text.into())
+    }
+
+    fn math(tex: &str, display: bool) -> Part {
+        Part::Math {
+            tex: tex.into(),
+            display,
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn finds_inline_and_display_math() {
+        assert_eq!(
+            split("so $x^2$ then $$ \\int_0^1 f $$!"),
+            [
+                text("so "),
+                math("x^2", false),
+                text(" then "),
+                math("\\int_0^1 f", true),
+                text("!"),
+            ]
+        );
+        assert_eq!(split("$\\$5$"), [math("\\$5", false)]);
+    }
+
+    #[wasm_bindgen_test]
+    fn leaves_prices_and_escapes_alone() {
+        assert_eq!(split("it was $5 and $10"), [text("it was $5 and $10")]);
+        assert_eq!(split("$ x $"), [text("$ x $")]);
+        assert_eq!(split("$a\nb$"), [text("$a\nb$")]);
+        assert_eq!(split("\\$x$ and $$"), [text("$x$ and $$")]);
+        assert_eq!(split("C:\\dir"), [text("C:\\dir")]);
+    }
+}
@@ -0,0 +1,974 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::sanitize::{self, Segment};
+use crate::services::attachment::Attachment;
+use crate::time;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageData {
+    /// Stamped by the server; links point at the message as `#msg-<id>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub from: String,
+    pub message: String,
+    #[serde(default)]
+    pub attachment: Option<Attachment>,
+    /// Milliseconds since the Unix epoch, stamped by the server.
+    #[serde(default)]
+    pub time: Option<f64>,
+    /// Set when an integration posted this through a webhook; `from` is then the bot's name.
+    /// Boxed, as few messages have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bot: Option<Box<BotCard>>,
+    /// Set on whispers: the only one besides the sender who sees it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Posted with `announce` by the room's owner or an administrator.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub announcement: bool,
+    /// Until when an announcement stays pinned above the room, in milliseconds since the epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned_until: Option<f64>,
+    /// Written by this client rather than anyone in the room, such as someone joining. Never sent.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub system: bool,
+}
+
+impl MessageData {
+    /// A line about the room itself, stamped now.
+    pub fn system(text: impl Into<String>) -> Self {
+        Self {
+            id: None,
+            from: String::new(),
+            message: text.into(),
+            attachment: None,
+            time: Some(time::now()),
+            bot: None,
+            to: None,
+            announcement: false,
+            pinned_until: None,
+            system: true,
+        }
+    }
+}
+
+/// The extras of a bot message, as given in the webhook request.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BotCard {
+    /// Identifies the card in responses to its buttons.
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub avatar: Option<String>,
+    #[serde(default)]
+    pub buttons: Vec<BotButton>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BotButton {
+    /// Sent back to the bot when the button is pressed.
+    pub action: String,
+    pub label: String,
+}
+
+/// Payload of `botaction`: someone pressed a button on a bot's card. The server tells the bot.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BotAction {
+    pub bot: String,
+    pub card: Option<String>,
+    pub action: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MsgTypes {
+    Users,
+    Register,
+    Unregister,
+    Join,
+    Message,
+    Signup,
+    Login,
+    AuthOk,
+    AuthError,
+    OAuth,
+    Refresh,
+    Direct,
+    CallOffer,
+    CallAnswer,
+    IceCandidate,
+    CallHangup,
+    BotAction,
+    Relay,
+    /// Sets the Gravatar hash others see us with; without data, goes back to a generated picture.
+    Gravatar,
+    /// Registers like `register`, disconnecting whoever is already chatting under our name.
+    Takeover,
+    /// Our name is already connected elsewhere, so we weren't registered.
+    Conflict,
+    /// Someone took over our name from elsewhere; we are no longer registered.
+    Replaced,
+    /// The room's custom emoji. Sent without data to ask for them, or as an [`EmojiChange`] by the
+    /// room's owner.
+    Emoji,
+    /// Sent without data to ask for [`AdminStats`]. Answered without data to non-administrators.
+    Admin,
+    /// Tells the room we are away from the keyboard, or back when sent without data.
+    Away,
+    /// A message for `to` alone. The server sends it to them and back to us as [`MessageData`],
+    /// and doesn't keep it.
+    Whisper,
+    /// An [`Announcement`], which comes back to the room as a `message`. Only the room's owner and
+    /// administrators may send one.
+    Announce,
+    /// The server's message of the day, sent on registering if it has one.
+    Welcome,
+    /// Accepts the [`Terms`] of the version in `data`. The server answers registering with this,
+    /// carrying the terms, while we haven't.
+    Terms,
+    /// Seconds everyone in the room has to wait between messages, or no data when there is no
+    /// slow mode. Sent without data to ask; with seconds, by the room's owner or an administrator,
+    /// to set it, 0 turning it off.
+    SlowMode,
+    /// Reports the message of the room whose id is in `data` to its moderators.
+    Report,
+    /// The room's [`ReportedMessage`]s, sent to its owner and administrators whenever they change.
+    /// Sent without data to ask; answered without data to anyone else.
+    Reports,
+    /// A [`Moderation`] of a reported message, by the room's owner or an administrator.
+    Moderate,
+    /// A moderator deleted the message whose id is in `data`.
+    Deleted,
+    /// Whoever is named in `data` may no longer post in the room.
+    Banned,
+    /// The words masked in the room's messages, as a list. Sent without data to ask; with a list,
+    /// by the room's owner or an administrator, to replace them.
+    Blacklist,
+    /// Something we should know about what we just sent, in `data`.
+    Warning,
+    /// Pins the message of the room whose id is in `data`. Only the room's owner, administrators
+    /// and those whose role may pin may.
+    Pin,
+    /// The room's [`PinnedMessage`]s, in the order shown, sent whenever they change. Sent without
+    /// data to ask; with a list of ids, by whoever may pin, to put the pins in that order and
+    /// unpin the rest.
+    Pins,
+    /// What the room doesn't allow in messages, as [`Restrictions`]. Sent without data to ask;
+    /// with them, by the room's owner or an administrator, to replace them.
+    Restrictions,
+    /// The room's settings, as a [`RoomUpdate`], sent whenever they change. Sent without data to
+    /// ask; with them, by the room's owner, to replace them, which can rename the room.
+    RoomUpdate,
+    /// Deletes our room, which only its owner may, with the room's name in `data` to confirm.
+    DeleteRoom,
+    /// The room we were in was deleted, as a [`RoomDeleted`]. The server has already taken us to
+    /// the one it names.
+    RoomDeleted,
+    /// Hands our room to the member of it named in `data`. Only its owner may.
+    Transfer,
+    /// The room has a new owner, as a [`RoleChange`]. The `users` frame that follows has the
+    /// roles.
+    RoleChange,
+    /// The code that lets people into our invite-only room, as an [`Invite`], sent to its owner
+    /// and those whose role may invite. Sent without data to ask.
+    Invite,
+    /// Revokes the code of our invite-only room and makes a new one, which is sent back as
+    /// `invite`. Only its owner may.
+    RegenerateInvite,
+    /// The room we asked to enter wouldn't let us in, as a [`JoinRefused`]. The server has left
+    /// us in the one it names.
+    JoinRefused,
+    /// Sets the password of our room to `data`, or removes it without. Only its owner may.
+    RoomPassword,
+    /// Asks for the rooms anyone can enter, answered with a list of [`ListedRoom`]s.
+    ListRooms,
+    /// The room's own roles and what they may do, as [`RoomRoles`], sent whenever they change.
+    /// Sent without data to ask; with them, by the room's owner, to replace them.
+    RoomRoles,
+    /// Sends the member of our room named in `data` back to the default room. Only those whose
+    /// role may kick may.
+    Kick,
+    /// We were sent out of the room, as a [`Kicked`]. The server has already taken us to the one
+    /// it names.
+    Kicked,
+    /// Joins the voice channel of our room, or leaves it when sent without data. The `users`
+    /// frame says who is in it, and going to another room leaves it.
+    Voice,
+    /// Voice channel signaling between two of those in it, relayed like a call's.
+    VoiceOffer,
+    VoiceAnswer,
+    VoiceCandidate,
+    /// Tells the peer of a call that we started recording it, or stopped when sent without data.
+    /// Relayed like the call's signaling, so each side shows who is recording.
+    CallRecording,
+    /// The same for one of those in the voice channel with us.
+    VoiceRecording,
+    /// Where we are, as a [`Position`](crate::services::location::Position), while we share it
+    /// live with the room, or that we stopped when sent without data. The server passes it on to
+    /// the rest of the room with whose it is.
+    Location,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSocketMessage {
+    pub message_type: MsgTypes,
+    pub data_array: Option<Vec<String>>,
+    pub data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<Attachment>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room: Option<String>,
+    /// Recipient of a `direct` message or call signal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Sender of a relayed call signal, filled in by the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// Our key for direct messages, announced on `register`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    /// The invite code we were given for the room we `register` or `join` in, if it is
+    /// invite-only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite: Option<String>,
+    /// The password typed in for the room we `join`, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+impl WebSocketMessage {
+    pub fn new(message_type: MsgTypes) -> Self {
+        Self {
+            message_type,
+            data_array: None,
+            data: None,
+            attachment: None,
+            room: None,
+            to: None,
+            from: None,
+            public_key: None,
+            invite: None,
+            password: None,
+        }
+    }
+}
+
+pub const USERNAME_MIN_LEN: usize = 3;
+pub const USERNAME_MAX_LEN: usize = 20;
+
+/// Checks a username chosen at signup: 3-20 letters, digits, `_` or `-`.
+pub fn validate_username(name: &str) -> Result<(), &'static str> {
+    let len = name.chars().count();
+    if len < USERNAME_MIN_LEN {
+        Err("Usernames need at least 3 characters.")
+    } else if len > USERNAME_MAX_LEN {
+        Err("Usernames can be at most 20 characters.")
+    } else if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        Err("Only letters, digits, _ and - are allowed.")
+    } else {
+        Ok(())
+    }
+}
+
+/// Alternatives to offer when `name` is taken. `salt` varies the numeric suffixes.
+pub fn suggest_usernames(name: &str, salt: u32) -> Vec<String> {
+    let base: String = name.chars().take(USERNAME_MAX_LEN - 4).collect();
+    vec![
+        format!("{}_{}", base, 10 + salt % 90),
+        format!("{}{}", base, 100 + (salt / 90) % 900),
+        format!("the_{}", base),
+    ]
+    .into_iter()
+    .filter(|s| validate_username(s).is_ok())
+    .collect()
+}
+
+/// Payload of `signup` and `login`. The password never leaves the browser in plain text.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Credentials {
+    pub username: String,
+    pub password_hash: String,
+}
+
+impl Credentials {
+    pub fn new(username: &str, password: &str) -> Self {
+        // Salt with the (case-insensitive) username so equal passwords don't share a hash.
+        let digest = Sha256::digest(format!("{}:{}", username.to_lowercase(), password));
+        Self {
+            username: username.to_string(),
+            password_hash: digest.iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+}
+
+/// Payload of `oauth`: the authorization code the provider redirected back with, for the server
+/// to exchange (it holds the client secret).
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OAuthCode {
+    pub provider: String,
+    pub code: String,
+    pub redirect_uri: String,
+}
+
+/// One entry of the `users` payload. Older servers only send bare names in `dataArray`.
+#[derive(Serialize, Deserialize)]
+pub struct UserPayload {
+    pub name: String,
+    #[serde(default)]
+    pub avatar: Option<String>,
+    /// Public key for direct messages; missing for clients that can't receive them.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Hash of the email whose Gravatar pictures them, if they chose one.
+    #[serde(default)]
+    pub gravatar: Option<String>,
+    /// Idle for a while; missing while they are around.
+    #[serde(default)]
+    pub away: bool,
+    /// In the room's voice channel.
+    #[serde(default)]
+    pub voice: bool,
+    #[serde(default)]
+    pub role: Role,
+    /// The role the room's owner gave them, as named in the room's [`RoomRoles`].
+    #[serde(default, rename = "roomRole")]
+    pub room_role: Option<String>,
+}
+
+/// What someone is in the room they are in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Entered the room first, and moderates it.
+    Owner,
+    /// An administrator, who moderates every room.
+    Moderator,
+    #[default]
+    Member,
+}
+
+/// Payload of an outgoing `direct`: AES-GCM ciphertext only the two parties can open.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sealed {
+    pub iv: String,
+    pub ciphertext: String,
+}
+
+/// Payload of an incoming `direct`, delivered to both the sender and the recipient. The server
+/// relays `payload` (a serialized [`Sealed`]) untouched, together with the keys on record.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectMessage {
+    pub from: String,
+    pub to: String,
+    pub payload: String,
+    pub from_key: String,
+    pub to_key: String,
+    #[serde(default)]
+    pub time: Option<f64>,
+}
+
+/// Payload of `icecandidate`: a network path the sender's browser found for a call.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IceCandidate {
+    pub candidate: String,
+    pub sdp_mid: Option<String>,
+    pub sdp_m_line_index: Option<u16>,
+}
+
+/// Payload of `authok`: who the server says we are, and the tokens proving it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Identity {
+    pub username: String,
+    #[serde(flatten)]
+    pub tokens: Tokens,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tokens {
+    /// Short-lived JWT presented in the WebSocket handshake.
+    pub token: String,
+    /// Long-lived opaque token traded for a fresh JWT via `refresh`.
+    pub refresh_token: String,
+    /// When `token` stops being accepted, in milliseconds since the Unix epoch.
+    pub expires_at: f64,
+}
+
+/// Payload of `autherror`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthError {
+    WrongPassword,
+    UnknownUser,
+    NameTaken,
+    InvalidName,
+    InvalidToken,
+    OAuthFailed,
+    /// Never sent by the server: we could not get an answer from it at all.
+    Unavailable,
+}
+
+impl AuthError {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            AuthError::WrongPassword => "Wrong password.",
+            AuthError::UnknownUser => "No account with that name. Sign up instead?",
+            AuthError::NameTaken => "That name is already taken.",
+            AuthError::InvalidName => "That name is not allowed.",
+            AuthError::InvalidToken => "Your session has expired, please log in again.",
+            AuthError::OAuthFailed => "Signing in with that provider failed.",
+            AuthError::Unavailable => "Could not reach the chat server.",
+        }
+    }
+}
+
+/// Payload of `relay` we send to change where the room is mirrored to. Sending `relay` without a
+/// payload asks for the current [`RelayStatus`] instead.
+#[derive(Debug, Serialize)]
+pub struct RelayConfig {
+    /// A Discord or Slack incoming webhook URL. `None` keeps the current one; empty removes it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub enabled: bool,
+}
+
+/// Payload of `relay` from the server: whether the room is mirrored, and how that is going.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct RelayStatus {
+    /// `Discord` or `Slack`; `None` when the room isn't mirrored anywhere.
+    #[serde(default)]
+    pub target: Option<String>,
+    pub enabled: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Payload of `emoji` we send, as the room's owner, to add or replace one of its custom emoji.
+#[derive(Debug, Serialize)]
+pub struct EmojiChange {
+    pub name: String,
+    /// A `data:image/...` URL; `None` removes the emoji.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+/// Payload of an outgoing `announce`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Announcement {
+    pub text: String,
+    /// How long to pin it above the room for, if at all.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pin_minutes: Option<u32>,
+}
+
+/// A message of the room that was reported, and who reported it.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ReportedMessage {
+    pub message: MessageData,
+    pub reporters: Vec<String>,
+}
+
+/// A message pinned above the room, and by whom.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinnedMessage {
+    pub message: MessageData,
+    pub pinned_by: String,
+    /// Milliseconds since the epoch.
+    pub pinned_at: f64,
+}
+
+/// Payload of an outgoing `moderate`.
+#[derive(Debug, Serialize)]
+pub struct Moderation {
+    /// Id of the reported message.
+    pub id: String,
+    pub action: ModerationAction,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationAction {
+    /// Leaves the message be and drops the report.
+    Approve,
+    /// Deletes the message for everyone.
+    Delete,
+    /// Deletes the message and stops its sender posting in the room.
+    Ban,
+}
+
+/// The settings of a room its owner can change.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RoomUpdate {
+    /// The room's name. Sent by its owner, a different one renames the room; left out, it stays.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Shown beside the room's name: an emoji or other short text, or a `data:image/...` URL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    /// A line on what the room is about, shown under its name and in the room directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    /// Whether only those with an invite code, and those already let in, can enter the room.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub invite_only: bool,
+    /// Whether entering the room takes its password, which only the server knows.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub password_protected: bool,
+}
+
+/// A room of the `listrooms` reply: one that isn't invite-only.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListedRoom {
+    pub name: String,
+    #[serde(default)]
+    pub topic: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// How many are in it now.
+    pub members: usize,
+    /// How many messages were posted in it since the server started.
+    #[serde(default)]
+    pub posted: u64,
+    #[serde(default)]
+    pub password_protected: bool,
+}
+
+/// Payload of `roomdeleted`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct RoomDeleted {
+    pub room: String,
+    /// The owner who deleted it.
+    pub by: String,
+    /// Where we were taken.
+    pub to: String,
+}
+
+/// Payload of `invite`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Invite {
+    pub room: String,
+    pub code: String,
+    /// When the code stops letting anyone in, in milliseconds since the epoch.
+    pub expires_at: f64,
+}
+
+/// Why an invite-only or password-protected room didn't let us in.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Refusal {
+    /// We had no code.
+    Required,
+    /// The code isn't one of the room's.
+    Invalid,
+    Expired,
+    /// The owner made a new code since, or opened the room to everyone.
+    Revoked,
+    /// We gave no password.
+    Password,
+    WrongPassword,
+    /// Too many wrong passwords lately; `retry_in` says when to try again.
+    TooManyAttempts,
+}
+
+/// Payload of `joinrefused`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinRefused {
+    pub room: String,
+    pub reason: Refusal,
+    /// Where we are instead.
+    pub to: String,
+    /// Seconds until we may try another password.
+    #[serde(default)]
+    pub retry_in: Option<u64>,
+}
+
+/// Payload of `kicked`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Kicked {
+    pub room: String,
+    /// Who sent us out.
+    pub by: String,
+    /// Where we are now.
+    pub to: String,
+}
+
+/// What a member may do in the room. Its owner and the administrators may do it all; the server
+/// holds everyone else to it, this is so that we only offer what it would accept.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Permissions {
+    pub post: bool,
+    /// Pin messages and arrange the pins.
+    pub pin: bool,
+    /// Have the invite code of an invite-only room.
+    pub invite: bool,
+    /// Send members back to the default room.
+    pub kick: bool,
+}
+
+impl Permissions {
+    pub const ALL: Self = Self {
+        post: true,
+        pin: true,
+        invite: true,
+        kick: true,
+    };
+}
+
+/// Everyone may post, and nothing more.
+impl Default for Permissions {
+    fn default() -> Self {
+        Self {
+            post: true,
+            pin: false,
+            invite: false,
+            kick: false,
+        }
+    }
+}
+
+/// A role of the room's own, which its owner gives to some of its members.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CustomRole {
+    pub name: String,
+    #[serde(default)]
+    pub permissions: Permissions,
+}
+
+/// Payload of `roomroles`: who may do what in the room, besides its moderators.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomRoles {
+    /// What the members without a role may do.
+    pub everyone: Permissions,
+    pub roles: Vec<CustomRole>,
+    /// The role of each member given one, by name. It replaces what everyone may do, so a role
+    /// can take a permission away as well as grant one.
+    pub members: BTreeMap<String, String>,
+}
+
+impl RoomRoles {
+    pub fn role_of(&self, name: &str) -> Option<&CustomRole> {
+        let role = self.members.get(name)?;
+        self.roles.iter().find(|r| r.name == *role)
+    }
+
+    pub fn permissions_of(&self, name: &str) -> Permissions {
+        self.role_of(name).map_or(self.everyone, |r| r.permissions)
+    }
+}
+
+/// Payload of `rolechange`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct RoleChange {
+    pub owner: String,
+    /// Who owned the room until now.
+    pub previous: String,
+}
+
+/// What a room's moderators don't allow in its messages. Each is true when refused; the server
+/// refuses such messages too, this is so that we can say so before sending.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Restrictions {
+    /// http(s) addresses in the text.
+    pub links: bool,
+    /// Attached images.
+    pub images: bool,
+    /// Attached files of any kind, images included.
+    pub attachments: bool,
+}
+
+impl Restrictions {
+    /// Why the room won't take this text, if it won't.
+    pub fn refuse_text(&self, text: &str) -> Option<&'static str> {
+        let linked = sanitize::linkify(text)
+            .iter()
+            .any(|s| matches!(s, Segment::Link { .. }));
+        (self.links && linked).then_some("Links aren't allowed in this room.")
+    }
+
+    /// Why the room won't take an attached file of this type, if it won't.
+    pub fn refuse_file(&self, mime: &str) -> Option<&'static str> {
+        if self.attachments {
+            Some("Attachments aren't allowed in this room.")
+        } else if self.images && mime.starts_with("image/") {
+            Some("Images aren't allowed in this room.")
+        } else {
+            None
+        }
+    }
+}
+
+/// The server's terms of service, from `/api/terms`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Terms {
+    /// Changes whenever the text does.
+    pub version: String,
+    pub text: String,
+}
+
+/// Payload of `emoji` from the server: all of the room's custom emoji.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct RoomEmoji {
+    /// Who may change them, having entered the room first.
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub emoji: Vec<CustomEmoji>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct CustomEmoji {
+    pub name: String,
+    pub image: String,
+}
+
+/// Payload of `admin` from the server: the whole server at a glance.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminStats {
+    /// Everyone connected, in order of registration.
+    pub users: Vec<AdminUser>,
+    /// Rooms with someone in them or something kept, by name.
+    pub rooms: Vec<AdminRoom>,
+    pub messages: Throughput,
+    /// Milliseconds since the Unix epoch.
+    pub started_at: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct AdminUser {
+    pub name: String,
+    pub room: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct AdminRoom {
+    pub name: String,
+    pub members: usize,
+    pub kept: usize,
+    #[serde(default)]
+    pub icon: Option<String>,
+}
+
+/// How many messages were posted, in all rooms.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Throughput {
+    pub total: u64,
+    pub last_minute: usize,
+    pub last_hour: usize,
+}
+
+/// Query of `GET /api/rooms/{room}/messages`.
+#[derive(Debug, Default, Serialize)]
+pub struct HistoryQuery {
+    /// Id of the oldest message we have; the page ends just before it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+/// Body of `GET /api/rooms/{room}/messages`, oldest first.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct HistoryPage {
+    pub messages: Vec<MessageData>,
+    /// Whether there are older messages still.
+    #[serde(default)]
+    pub more: bool,
+}
+
+/// Body of `GET /api/rooms/{room}`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct RoomInfo {
+    pub name: String,
+    /// How many of its messages the server remembers.
+    #[serde(default)]
+    pub kept: usize,
+    #[serde(default)]
+    pub icon: Option<String>,
+}
+
+/// Body of `GET /api/users/{name}`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub avatar: Option<String>,
+    /// Set while they are connected.
+    #[serde(default)]
+    pub room: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn serializes_camel_case_and_skips_empty_fields() {
+        let msg = WebSocketMessage {
+            data: Some("alice".into()),
+            public_key: Some("key".into()),
+            ..WebSocketMessage::new(MsgTypes::Register)
+        };
+        let json: serde_json::Value = serde_json::to_value(&msg).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "messageType": "register",
+                "dataArray": null,
+                "data": "alice",
+                "publicKey": "key",
+            })
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn deserializes_a_minimal_server_frame() {
+        let msg: WebSocketMessage =
+            serde_json::from_str(r#"{"messageType":"users","dataArray":["alice","bob"]}"#).unwrap();
+        assert!(matches!(msg.message_type, MsgTypes::Users));
+        assert_eq!(msg.data_array, Some(vec!["alice".into(), "bob".into()]));
+        assert_eq!(msg.data, None);
+        assert_eq!(msg.attachment, None);
+    }
+
+    #[wasm_bindgen_test]
+    fn round_trips_every_message_type() {
+        for (message_type, name) in [
+            (MsgTypes::Users, "users"),
+            (MsgTypes::AuthOk, "authok"),
+            (MsgTypes::OAuth, "oauth"),
+            (MsgTypes::CallOffer, "calloffer"),
+            (MsgTypes::IceCandidate, "icecandidate"),
+            (MsgTypes::CallHangup, "callhangup"),
+            (MsgTypes::BotAction, "botaction"),
+            (MsgTypes::Takeover, "takeover"),
+            (MsgTypes::Emoji, "emoji"),
+            (MsgTypes::Admin, "admin"),
+            (MsgTypes::Away, "away"),
+            (MsgTypes::Whisper, "whisper"),
+            (MsgTypes::Announce, "announce"),
+            (MsgTypes::Welcome, "welcome"),
+            (MsgTypes::Terms, "terms"),
+            (MsgTypes::SlowMode, "slowmode"),
+            (MsgTypes::Reports, "reports"),
+            (MsgTypes::Moderate, "moderate"),
+            (MsgTypes::Blacklist, "blacklist"),
+            (MsgTypes::Pin, "pin"),
+            (MsgTypes::Pins, "pins"),
+            (MsgTypes::Restrictions, "restrictions"),
+            (MsgTypes::RoomUpdate, "roomupdate"),
+            (MsgTypes::DeleteRoom, "deleteroom"),
+            (MsgTypes::RoomDeleted, "roomdeleted"),
+            (MsgTypes::Transfer, "transfer"),
+            (MsgTypes::RoleChange, "rolechange"),
+            (MsgTypes::Invite, "invite"),
+            (MsgTypes::RegenerateInvite, "regenerateinvite"),
+            (MsgTypes::JoinRefused, "joinrefused"),
+            (MsgTypes::RoomPassword, "roompassword"),
+            (MsgTypes::ListRooms, "listrooms"),
+            (MsgTypes::RoomRoles, "roomroles"),
+            (MsgTypes::Kick, "kick"),
+            (MsgTypes::Kicked, "kicked"),
+            (MsgTypes::Voice, "voice"),
+            (MsgTypes::VoiceOffer, "voiceoffer"),
+            (MsgTypes::VoiceAnswer, "voiceanswer"),
+            (MsgTypes::VoiceCandidate, "voicecandidate"),
+            (MsgTypes::CallRecording, "callrecording"),
+            (MsgTypes::VoiceRecording, "voicerecording"),
+            (MsgTypes::Location, "location"),
+        ] {
+            let json = serde_json::to_string(&WebSocketMessage::new(message_type)).unwrap();
+            assert!(
+                json.contains(&format!(r#""messageType":"{}""#, name)),
+                "{}",
+                json
+            );
+            let back: WebSocketMessage = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&back).unwrap(), json);
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn restrictions_say_what_they_refuse() {
+        let none = Restrictions::default();
+        assert_eq!(none.refuse_text("see https://example.com"), None);
+        assert_eq!(none.refuse_file("image/png"), None);
+
+        let images: Restrictions = serde_json::from_str(r#"{"images":true}"#).unwrap();
+        assert_eq!(
+            images.refuse_file("image/png"),
+            Some("Images aren't allowed in this room.")
+        );
+        assert_eq!(images.refuse_file("text/plain"), None);
+
+        let strict = Restrictions {
+            links: true,
+            images: false,
+            attachments: true,
+        };
+        assert!(strict.refuse_text("see https://example.com").is_some());
+        assert_eq!(strict.refuse_text("see example.com"), None);
+        assert!(strict.refuse_file("text/plain").is_some());
+    }
+
+    #[wasm_bindgen_test]
+    fn rejects_unknown_message_types() {
+        assert!(serde_json::from_str::<WebSocketMessage>(r#"{"messageType":"shout"}"#).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn message_data_from_older_servers_has_no_time_or_attachment() {
+        let m: MessageData = serde_json::from_str(r#"{"from":"alice","message":"hi"}"#).unwrap();
+        assert_eq!(m.time, None);
+        assert_eq!(m.attachment, None);
+        assert_eq!(m.bot, None);
+    }
+
+    #[wasm_bindgen_test]
+    fn bot_cards_only_need_what_they_use() {
+        let m: MessageData = serde_json::from_str(
+            r#"{"from":"ci","message":"Build failed","bot":{"buttons":[{"action":"retry","label":"Retry"}]}}"#,
+        )
+        .unwrap();
+        let bot = m.bot.unwrap();
+        assert_eq!(bot.id, None);
+        assert_eq!(bot.buttons[0].action, "retry");
+    }
+
+    #[wasm_bindgen_test]
+    fn auth_errors_use_snake_case() {
+        assert_eq!(
+            serde_json::to_string(&AuthError::InvalidToken).unwrap(),
+            r#""invalid_token""#
+        );
+        assert_eq!(
+            serde_json::from_str::<AuthError>(r#""o_auth_failed""#).unwrap(),
+            AuthError::OAuthFailed
+        );
+    }
+}
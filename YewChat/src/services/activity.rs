@@ -0,0 +1,146 @@
+//! What happened in our sessions: joins, lost connections, name clashes and how much was sent.
+//! Kept per user in this browser, to make sense of a flaky connection after the fact.
+
+use std::cell::Cell;
+
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+
+use crate::time;
+
+/// How many entries are kept, dropping the oldest.
+pub const CAPACITY: usize = 200;
+
+thread_local! {
+    /// Messages sent since the session started.
+    static SENT: Cell<u32> = const { Cell::new(0) };
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Event {
+    /// The chat was opened, signed in.
+    Started,
+    Joined {
+        room: String,
+    },
+    /// The server acknowledged us, at first or after losing it.
+    Connected,
+    ConnectionLost,
+    /// Someone else was already connected under our name.
+    NameTaken,
+    /// We disconnected whoever else was connected under our name.
+    TookOver,
+    /// Someone connected under our name disconnected us.
+    Replaced,
+    /// We left to sign in under another name.
+    Renamed,
+    /// The server no longer took our sign-in.
+    SessionExpired,
+    SignedOut,
+}
+
+impl Event {
+    pub fn label(&self) -> String {
+        match self {
+            Event::Started => "Session started".to_string(),
+            Event::Joined { room } => format!("Joined #{}", room),
+            Event::Connected => "Connected".to_string(),
+            Event::ConnectionLost => "Lost the connection".to_string(),
+            Event::NameTaken => "Name already in use elsewhere".to_string(),
+            Event::TookOver => "Took over the name".to_string(),
+            Event::Replaced => "Taken over from another tab or device".to_string(),
+            Event::Renamed => "Left to pick another name".to_string(),
+            Event::SessionExpired => "Session expired".to_string(),
+            Event::SignedOut => "Signed out".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub time: f64,
+    pub event: Event,
+    /// Messages sent in the session up to then.
+    pub sent: u32,
+}
+
+fn storage_key(username: &str) -> String {
+    format!("yewchat.activity.{}", username)
+}
+
+/// The entries of `username`, newest first.
+pub fn load(username: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = LocalStorage::get(storage_key(username)).unwrap_or_default();
+    entries.reverse();
+    entries
+}
+
+/// Notes `event` down for `username`. Starting a session sets the count of sent messages back.
+pub fn record(username: &str, event: Event) {
+    if event == Event::Started {
+        SENT.with(|sent| sent.set(0));
+    }
+    let mut entries: Vec<Entry> = LocalStorage::get(storage_key(username)).unwrap_or_default();
+    append(
+        &mut entries,
+        Entry {
+            time: time::now(),
+            event,
+            sent: sent(),
+        },
+    );
+    if let Err(e) = LocalStorage::set(storage_key(username), &entries) {
+        log::error!("failed to save the activity log: {:?}", e);
+    }
+}
+
+fn append(entries: &mut Vec<Entry>, entry: Entry) {
+    entries.push(entry);
+    if entries.len() > CAPACITY {
+        entries.drain(..entries.len() - CAPACITY);
+    }
+}
+
+pub fn message_sent() {
+    SENT.with(|sent| sent.set(sent.get() + 1));
+}
+
+/// Messages sent since the session started.
+pub fn sent() -> u32 {
+    SENT.with(Cell::get)
+}
+
+pub fn clear(username: &str) {
+    LocalStorage::delete(storage_key(username));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn keeps_the_latest_entries() {
+        let mut entries = vec![];
+        for sent in 0..CAPACITY as u32 + 3 {
+            let event = Event::Joined {
+                room: "general".into(),
+            };
+            append(
+                &mut entries,
+                Entry {
+                    time: 0.0,
+                    event,
+                    sent,
+                },
+            );
+        }
+        assert_eq!(entries.len(), CAPACITY);
+        assert_eq!(entries[0].sent, 3);
+
+        let saved = serde_json::to_string(&entries[0]).unwrap();
+        assert!(saved.contains(r#""kind":"joined","room":"general""#));
+        assert_eq!(serde_json::from_str::<Entry>(&saved).unwrap(), entries[0]);
+    }
+}
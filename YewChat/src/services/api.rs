@@ -0,0 +1,113 @@
+//! Our server's HTTP API, for what the chat asks for rather than gets pushed over the WebSocket:
+//! older history, someone's profile, a room's details.
+
+use reqwasm::http::Request;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use web_sys::UrlSearchParams;
+
+use crate::protocol::{HistoryPage, HistoryQuery, Profile, RoomInfo, Terms};
+use crate::services::websocket::WS_ENDPOINT;
+use crate::User;
+
+/// Makes requests with the session token, so it only exists while we are signed in to our server.
+#[derive(Clone)]
+pub struct ApiClient {
+    token: String,
+}
+
+impl ApiClient {
+    /// `None` without a session on our server, as when chatting through Matrix or IRC.
+    pub fn of(user: &User) -> Option<Self> {
+        user.tokens.borrow().as_ref().map(|t| Self {
+            token: t.token.clone(),
+        })
+    }
+
+    pub async fn room(&self, room: &str) -> Result<RoomInfo, String> {
+        self.get(&format!("/api/rooms/{}", encode(room)))
+            .await?
+            .ok_or_else(|| format!("no room {}", room))
+    }
+
+    pub async fn history(&self, room: &str, query: &HistoryQuery) -> Result<HistoryPage, String> {
+        self.get(&format!(
+            "/api/rooms/{}/messages?{}",
+            encode(room),
+            query_string(query)
+        ))
+        .await?
+        .ok_or_else(|| format!("no room {}", room))
+    }
+
+    /// `None` for a name that is neither connected nor registered.
+    pub async fn profile(&self, name: &str) -> Result<Option<Profile>, String> {
+        self.get(&format!("/api/users/{}", encode(name))).await
+    }
+
+    /// `None` when the server has no terms to accept.
+    pub async fn terms(&self) -> Result<Option<Terms>, String> {
+        self.get("/api/terms").await
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>, String> {
+        let response = Request::get(&format!("{}{}", endpoint(), path))
+            .header("Authorization", &format!("Bearer {}", self.token))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status() == 404 {
+            return Ok(None);
+        }
+        if !response.ok() {
+            return Err(format!("{} {}", response.status(), response.status_text()));
+        }
+        response.json().await.map(Some).map_err(|e| e.to_string())
+    }
+}
+
+/// The server's HTTP address, from [`WS_ENDPOINT`]: `ws://` becomes `http://`, `wss://` `https://`.
+fn endpoint() -> String {
+    WS_ENDPOINT.replacen("ws", "http", 1)
+}
+
+/// `params` as a query string, leaving out fields that are `None`.
+fn query_string(params: &impl Serialize) -> String {
+    let search = UrlSearchParams::new().unwrap();
+    if let Ok(Value::Object(fields)) = serde_json::to_value(params) {
+        for (name, value) in fields {
+            match value {
+                Value::Null => {}
+                Value::String(s) => search.append(&name, &s),
+                other => search.append(&name, &other.to_string()),
+            }
+        }
+    }
+    search.to_string().into()
+}
+
+fn encode(s: &str) -> String {
+    js_sys::encode_uri_component(s).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn builds_history_queries() {
+        assert_eq!(query_string(&HistoryQuery::default()), "");
+        let query = HistoryQuery {
+            before: Some("a b".into()),
+            limit: Some(20),
+        };
+        assert_eq!(query_string(&query), "before=a+b&limit=20");
+    }
+
+    #[wasm_bindgen_test]
+    fn http_side_of_the_websocket() {
+        assert_eq!(endpoint(), "http://127.0.0.1:8080");
+    }
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{File, FileReader, ProgressEvent};
+use yew::Callback;
+
+/// Largest file we are willing to push through the socket as a data URL.
+pub const MAX_ATTACHMENT_BYTES: f64 = 10.0 * 1024.0 * 1024.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    pub mime: String,
+    pub size: u64,
+    /// The file contents encoded as a `data:` URL.
+    pub data: String,
+}
+
+impl Attachment {
+    pub fn is_image(&self) -> bool {
+        self.mime.starts_with("image/")
+    }
+}
+
+/// Human readable file size, e.g. `1.4 MB`.
+pub fn format_size(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// An in-flight file read. Dropping it aborts the read.
+pub struct AttachmentReader {
+    reader: FileReader,
+    _onprogress: Closure<dyn FnMut(ProgressEvent)>,
+    _onloadend: Closure<dyn FnMut(ProgressEvent)>,
+}
+
+impl AttachmentReader {
+    /// Starts reading `file`, reporting `(loaded, total)` byte counts to `on_progress` and the
+    /// finished attachment (or an error message) to `on_done`.
+    pub fn read(
+        file: File,
+        on_progress: Callback<(f64, f64)>,
+        on_done: Callback<Result<Attachment, String>>,
+    ) -> Result<Self, String> {
+        if file.size() > MAX_ATTACHMENT_BYTES {
+            return Err(format!(
+                "{} is too large ({}, limit is {})",
+                file.name(),
+                format_size(file.size()),
+                format_size(MAX_ATTACHMENT_BYTES)
+            ));
+        }
+
+        let reader = FileReader::new().map_err(|e| format!("{:?}", e))?;
+
+        let onprogress = Closure::wrap(Box::new(move |e: ProgressEvent| {
+            if e.length_computable() {
+                on_progress.emit((e.loaded(), e.total()));
+            }
+        }) as Box<dyn FnMut(ProgressEvent)>);
+
+        let onloadend = {
+            let reader = reader.clone();
+            let file = file.clone();
+            Closure::wrap(Box::new(move |_: ProgressEvent| {
+                let result = reader
+                    .result()
+                    .ok()
+                    .and_then(|r| r.as_string())
+                    .map(|data| Attachment {
+                        name: file.name(),
+                        mime: file.type_(),
+                        size: file.size() as u64,
+                        data,
+                    })
+                    .ok_or_else(|| format!("could not read {}", file.name()));
+                on_done.emit(result);
+            }) as Box<dyn FnMut(ProgressEvent)>)
+        };
+
+        reader.set_onprogress(Some(onprogress.as_ref().unchecked_ref()));
+        reader.set_onloadend(Some(onloadend.as_ref().unchecked_ref()));
+        reader
+            .read_as_data_url(&file)
+            .map_err(|e| format!("{:?}", e))?;
+
+        Ok(Self {
+            reader,
+            _onprogress: onprogress,
+            _onloadend: onloadend,
+        })
+    }
+}
+
+impl Drop for AttachmentReader {
+    fn drop(&mut self) {
+        self.reader.set_onprogress(None);
+        self.reader.set_onloadend(None);
+        if self.reader.ready_state() == FileReader::LOADING {
+            self.reader.abort();
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Which microphone and speaker calls and voice channels use, for computers that have more than
+//! one. The choice is kept in this browser rather than for an account, as the devices are.
+
+use gloo_storage::{LocalStorage, Storage};
+use js_sys::{Array, Function, Reflect};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    HtmlMediaElement, MediaDeviceInfo, MediaDeviceKind, MediaStream, MediaStreamTrack,
+    MediaTrackConstraints,
+};
+
+use crate::services::call;
+
+const MICROPHONE_KEY: &str = "yewchat.microphone";
+const SPEAKER_KEY: &str = "yewchat.speaker";
+
+/// A microphone or speaker, as the browser lists it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Device {
+    pub id: String,
+    pub label: String,
+}
+
+/// Our microphone, and camera if asked for, released once nothing holds them any more.
+pub struct Microphone(MediaStream);
+
+impl Microphone {
+    /// Opens the chosen microphone, or the browser's default one, with the camera too for `video`
+    /// unless there is none to be had.
+    pub async fn open(video: bool) -> Result<Self, String> {
+        let stream = match call::user_media(video).await {
+            Err(e) if video => {
+                log::debug!("no camera, falling back to audio: {}", e);
+                call::user_media(false).await
+            }
+            result => result,
+        };
+        stream.map(Microphone)
+    }
+
+    pub fn stream(&self) -> &MediaStream {
+        &self.0
+    }
+}
+
+impl Drop for Microphone {
+    fn drop(&mut self) {
+        for track in self.0.get_tracks().iter() {
+            track.unchecked_into::<MediaStreamTrack>().stop();
+        }
+    }
+}
+
+fn choice(key: &str) -> Option<String> {
+    LocalStorage::get(key).ok()
+}
+
+fn choose(key: &str, id: Option<&str>) {
+    let Some(id) = id else {
+        LocalStorage::delete(key);
+        return;
+    };
+    if let Err(e) = LocalStorage::set(key, id) {
+        log::error!("failed to save the chosen device: {:?}", e);
+    }
+}
+
+/// The microphone chosen, or none for the browser's default.
+pub fn microphone() -> Option<String> {
+    choice(MICROPHONE_KEY)
+}
+
+pub fn set_microphone(id: Option<&str>) {
+    choose(MICROPHONE_KEY, id);
+}
+
+/// The speaker chosen, or none for the browser's default.
+pub fn speaker() -> Option<String> {
+    choice(SPEAKER_KEY)
+}
+
+pub fn set_speaker(id: Option<&str>) {
+    choose(SPEAKER_KEY, id);
+}
+
+/// What to ask `getUserMedia` for as audio: the chosen microphone while it is there, or else
+/// whichever the browser picks.
+pub fn audio_constraint() -> JsValue {
+    let Some(id) = microphone() else {
+        return JsValue::TRUE;
+    };
+    let mut constraints = MediaTrackConstraints::new();
+    constraints.device_id(&JsValue::from_str(&id));
+    constraints.into()
+}
+
+/// Numbers the devices the browser hasn't named, as it doesn't until allowed to use the
+/// microphone.
+fn named(devices: Vec<Device>, kind: &str) -> Vec<Device> {
+    devices
+        .into_iter()
+        .enumerate()
+        .map(|(at, device)| Device {
+            label: match device.label {
+                label if label.is_empty() => format!("{} {}", kind, at + 1),
+                label => label,
+            },
+            ..device
+        })
+        .collect()
+}
+
+/// The microphones and the speakers there are, in that order.
+pub async fn list() -> Result<(Vec<Device>, Vec<Device>), String> {
+    let devices = web_sys::window()
+        .expect("no global `window`")
+        .navigator()
+        .media_devices()
+        .map_err(|e| format!("{:?}", e))?;
+    let promise = devices
+        .enumerate_devices()
+        .map_err(|e| format!("{:?}", e))?;
+    let all: Array = JsFuture::from(promise)
+        .await
+        .map_err(|e| format!("{:?}", e))?
+        .unchecked_into();
+    let (mut microphones, mut speakers) = (vec![], vec![]);
+    for info in all.iter().map(JsCast::unchecked_into::<MediaDeviceInfo>) {
+        let device = Device {
+            id: info.device_id(),
+            label: info.label(),
+        };
+        match info.kind() {
+            MediaDeviceKind::Audioinput => microphones.push(device),
+            MediaDeviceKind::Audiooutput => speakers.push(device),
+            _ => {}
+        }
+    }
+    Ok((named(microphones, "Microphone"), named(speakers, "Speaker")))
+}
+
+fn set_sink_id(element: &JsValue) -> Option<Function> {
+    Reflect::get(element, &"setSinkId".into())
+        .ok()
+        .and_then(|f| f.dyn_into().ok())
+}
+
+/// Whether this browser can play through a speaker other than its default one.
+pub fn can_choose_speaker() -> bool {
+    web_sys::window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.create_element("audio").ok())
+        .is_some_and(|audio| set_sink_id(&audio).is_some())
+}
+
+/// Plays `element` through the speaker `id`, or the default one, where the browser can.
+pub fn route(element: &HtmlMediaElement, id: Option<&str>) {
+    let Some(set_sink_id) = set_sink_id(element) else {
+        return;
+    };
+    let Ok(promise) = set_sink_id.call1(element, &JsValue::from_str(id.unwrap_or_default())) else {
+        return;
+    };
+    spawn_local(async move {
+        if let Err(e) = JsFuture::from(js_sys::Promise::from(promise)).await {
+            log::debug!("can't play through the chosen speaker: {:?}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn unnamed_devices_are_numbered() {
+        let device = |id: &str, label: &str| Device {
+            id: id.into(),
+            label: label.into(),
+        };
+        let named = named(
+            vec![device("a", ""), device("b", "USB headset"), device("c", "")],
+            "Microphone",
+        );
+        assert_eq!(
+            named,
+            vec![
+                device("a", "Microphone 1"),
+                device("b", "USB headset"),
+                device("c", "Microphone 3"),
+            ]
+        );
+    }
+}
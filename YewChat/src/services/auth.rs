@@ -0,0 +1,150 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use futures::{SinkExt, StreamExt};
+use gloo_timers::future::TimeoutFuture;
+use reqwasm::websocket::{futures::WebSocket, Message};
+use wasm_bindgen_futures::spawn_local;
+use yew::Callback;
+
+use crate::protocol::{AuthError, Credentials, Identity, MsgTypes, OAuthCode, WebSocketMessage};
+use crate::services::session::Session;
+use crate::services::websocket::WS_ENDPOINT;
+use crate::time;
+use crate::User;
+
+/// Refresh this long before the JWT expires, to leave room for a slow round trip.
+const REFRESH_MARGIN_MS: f64 = 60_000.0;
+
+/// Talks to the server about who we are: logging in, and keeping the session's JWT fresh.
+pub struct AuthService;
+
+impl AuthService {
+    pub async fn login(credentials: &Credentials) -> Result<Identity, AuthError> {
+        Self::exchange(MsgTypes::Login, serde_json::to_string(credentials).unwrap()).await
+    }
+
+    pub async fn signup(credentials: &Credentials) -> Result<Identity, AuthError> {
+        Self::exchange(
+            MsgTypes::Signup,
+            serde_json::to_string(credentials).unwrap(),
+        )
+        .await
+    }
+
+    pub async fn oauth(code: &OAuthCode) -> Result<Identity, AuthError> {
+        Self::exchange(MsgTypes::OAuth, serde_json::to_string(code).unwrap()).await
+    }
+
+    pub async fn refresh(refresh_token: &str) -> Result<Identity, AuthError> {
+        Self::exchange(MsgTypes::Refresh, refresh_token.to_string()).await
+    }
+
+    /// Stores the identity the server vouched for, both in the `User` context and the session.
+    pub fn sign_in(user: &User, identity: Identity) {
+        *user.username.borrow_mut() = identity.username.clone();
+        *user.tokens.borrow_mut() = Some(identity.tokens.clone());
+        Session {
+            username: identity.username,
+            tokens: Some(identity.tokens),
+            matrix: None,
+            irc: None,
+        }
+        .save();
+    }
+
+    pub fn sign_out(user: &User) {
+        user.username.borrow_mut().clear();
+        user.tokens.borrow_mut().take();
+        user.matrix.borrow_mut().take();
+        user.irc.borrow_mut().take();
+        Session::clear();
+    }
+
+    /// Silently refreshes the JWT shortly before it expires, for as long as the returned handle
+    /// lives. `on_expired` fires if a refresh is rejected and the user has to log in again.
+    pub fn keep_fresh(user: User, on_expired: Callback<()>) -> RefreshHandle {
+        let active = Rc::new(Cell::new(true));
+        let handle = RefreshHandle {
+            active: active.clone(),
+        };
+
+        spawn_local(async move {
+            loop {
+                let Some(tokens) = user.tokens.borrow().clone() else {
+                    return;
+                };
+                let wait = (tokens.expires_at - REFRESH_MARGIN_MS - time::now()).max(0.0);
+                TimeoutFuture::new(wait.min(u32::MAX as f64) as u32).await;
+                if !active.get() {
+                    return;
+                }
+                match Self::refresh(&tokens.refresh_token).await {
+                    Ok(identity) => Self::sign_in(&user, identity),
+                    // A network hiccup shouldn't log anyone out; try again on the next round.
+                    Err(AuthError::Unavailable) => TimeoutFuture::new(5_000).await,
+                    Err(e) => {
+                        log::debug!("auth: refresh rejected: {:?}", e);
+                        if active.get() {
+                            on_expired.emit(());
+                        }
+                        return;
+                    }
+                }
+            }
+        });
+
+        handle
+    }
+
+    /// Sends one auth request on a throwaway connection and waits for the verdict.
+    async fn exchange(message_type: MsgTypes, data: String) -> Result<Identity, AuthError> {
+        let message = WebSocketMessage {
+            data: Some(data),
+            ..WebSocketMessage::new(message_type)
+        };
+        let ws = WebSocket::open(WS_ENDPOINT).map_err(|_| AuthError::Unavailable)?;
+        let (mut write, mut read) = ws.split();
+        write
+            .send(Message::Text(serde_json::to_string(&message).unwrap()))
+            .await
+            .map_err(|_| AuthError::Unavailable)?;
+
+        let mut verdict = Err(AuthError::Unavailable);
+        while let Some(Ok(Message::Text(data))) = read.next().await {
+            let Ok(reply) = serde_json::from_str::<WebSocketMessage>(&data) else {
+                continue;
+            };
+            match reply.message_type {
+                MsgTypes::AuthOk => {
+                    verdict = reply
+                        .data
+                        .and_then(|d| serde_json::from_str(&d).ok())
+                        .ok_or(AuthError::Unavailable);
+                    break;
+                }
+                MsgTypes::AuthError => {
+                    verdict = Err(reply
+                        .data
+                        .and_then(|d| serde_json::from_value(d.into()).ok())
+                        .unwrap_or(AuthError::Unavailable));
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let _ = write.close().await;
+        verdict
+    }
+}
+
+/// Stops the refresh loop started by [`AuthService::keep_fresh`] when dropped.
+pub struct RefreshHandle {
+    active: Rc<Cell<bool>>,
+}
+
+impl Drop for RefreshHandle {
+    fn drop(&mut self) {
+        self.active.set(false);
+    }
+}
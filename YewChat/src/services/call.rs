@@ -0,0 +1,519 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use js_sys::{Array, Reflect};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    Event, MediaStream, MediaStreamConstraints, MediaStreamTrack, RtcConfiguration,
+    RtcIceCandidateInit, RtcIceConnectionState, RtcIceServer, RtcOfferOptions, RtcPeerConnection,
+    RtcPeerConnectionIceEvent, RtcSdpType, RtcSessionDescriptionInit, RtcTrackEvent,
+};
+use yew::Callback;
+
+use crate::protocol::{IceCandidate, MsgTypes, WebSocketMessage};
+use crate::services::audio_devices;
+
+/// Public STUN server used to discover our address; there is no TURN relay.
+const STUN_SERVER: &str = "stun:stun.l.google.com:19302";
+
+/// Where a call is at, as far as the UI is concerned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CallStatus {
+    /// We called and are waiting for the peer to pick up.
+    Dialing,
+    /// The peer is calling us and waits for us to accept or decline.
+    Ringing,
+    /// Both sides agreed; the media connection is being set up.
+    Connecting,
+    Connected,
+}
+
+/// The message types a [`Call`] signals with, so that a voice channel's connections and a direct
+/// call never take each other's offers for their own.
+#[derive(Clone, Copy)]
+struct Signaling {
+    offer: MsgTypes,
+    answer: MsgTypes,
+    candidate: MsgTypes,
+}
+
+impl Signaling {
+    const CALL: Signaling = Signaling {
+        offer: MsgTypes::CallOffer,
+        answer: MsgTypes::CallAnswer,
+        candidate: MsgTypes::IceCandidate,
+    };
+    const VOICE: Signaling = Signaling {
+        offer: MsgTypes::VoiceOffer,
+        answer: MsgTypes::VoiceAnswer,
+        candidate: MsgTypes::VoiceCandidate,
+    };
+}
+
+/// Where a call's microphone comes from.
+#[derive(Clone)]
+enum Media {
+    /// Asked of the browser for this call, with the camera too for `video`, and released with it.
+    Own { video: bool },
+    /// A stream its owner shares between calls, and releases once done with all of them.
+    Shared(MediaStream),
+}
+
+/// What a [`Call`] reports back to its owner.
+pub enum CallEvent {
+    /// A signaling message to deliver to the peer through the chat server.
+    Signal(Box<WebSocketMessage>),
+    Connected,
+    /// Our microphone (and camera) stream, once the browser granted access.
+    LocalMedia(MediaStream),
+    /// What the peer sends us, to be played back.
+    RemoteMedia(MediaStream),
+    /// The call can't go on; the owner should hang up and show the reason.
+    Failed(String),
+}
+
+/// One end of a one-to-one voice or video call, or of one of a voice channel's connections.
+/// Dropping it hangs up locally: the peer connection is closed, and the microphone and camera are
+/// released unless shared. Telling the peer is up to the owner.
+pub struct Call {
+    pub peer: String,
+    /// Whether the call was placed as a video call; the camera may still be off or unavailable.
+    pub video: bool,
+    connection: RtcPeerConnection,
+    local: Rc<RefCell<Option<MediaStream>>>,
+    /// Candidates that arrived before the remote description was set.
+    pending: Rc<RefCell<Vec<IceCandidate>>>,
+    remote_ready: Rc<Cell<bool>>,
+    closed: Rc<Cell<bool>>,
+    muted: Cell<bool>,
+    /// Cleared while push-to-talk holds our voice back.
+    transmitting: Cell<bool>,
+    camera_off: Cell<bool>,
+    _onicecandidate: Closure<dyn FnMut(RtcPeerConnectionIceEvent)>,
+    _ontrack: Closure<dyn FnMut(RtcTrackEvent)>,
+    _onstatechange: Closure<dyn FnMut(Event)>,
+}
+
+impl Call {
+    fn new(
+        peer: String,
+        video: bool,
+        signaling: Signaling,
+        on_event: Callback<CallEvent>,
+    ) -> Result<Self, String> {
+        let mut server = RtcIceServer::new();
+        server.urls(&JsValue::from_str(STUN_SERVER));
+        let mut config = RtcConfiguration::new();
+        config.ice_servers(&Array::of1(&server));
+        let connection = RtcPeerConnection::new_with_configuration(&config).map_err(describe)?;
+
+        let onicecandidate = {
+            let on_event = on_event.clone();
+            let peer = peer.clone();
+            Closure::wrap(Box::new(move |e: RtcPeerConnectionIceEvent| {
+                // A `None` candidate only marks the end of gathering.
+                if let Some(candidate) = e.candidate() {
+                    let candidate = IceCandidate {
+                        candidate: candidate.candidate(),
+                        sdp_mid: candidate.sdp_mid(),
+                        sdp_m_line_index: candidate.sdp_m_line_index(),
+                    };
+                    on_event.emit(CallEvent::Signal(Box::new(WebSocketMessage {
+                        data: Some(serde_json::to_string(&candidate).unwrap()),
+                        to: Some(peer.clone()),
+                        ..WebSocketMessage::new(signaling.candidate)
+                    })));
+                }
+            }) as Box<dyn FnMut(RtcPeerConnectionIceEvent)>)
+        };
+
+        let ontrack = {
+            let on_event = on_event.clone();
+            Closure::wrap(Box::new(move |e: RtcTrackEvent| {
+                if let Ok(stream) = e.streams().get(0).dyn_into::<MediaStream>() {
+                    on_event.emit(CallEvent::RemoteMedia(stream));
+                }
+            }) as Box<dyn FnMut(RtcTrackEvent)>)
+        };
+
+        let onstatechange = {
+            let connection = connection.clone();
+            Closure::wrap(
+                Box::new(move |_: Event| match connection.ice_connection_state() {
+                    RtcIceConnectionState::Connected => on_event.emit(CallEvent::Connected),
+                    RtcIceConnectionState::Failed => {
+                        on_event.emit(CallEvent::Failed("The connection failed.".into()))
+                    }
+                    _ => {}
+                }) as Box<dyn FnMut(Event)>,
+            )
+        };
+
+        connection.set_onicecandidate(Some(onicecandidate.as_ref().unchecked_ref()));
+        connection.set_ontrack(Some(ontrack.as_ref().unchecked_ref()));
+        connection.set_oniceconnectionstatechange(Some(onstatechange.as_ref().unchecked_ref()));
+
+        Ok(Self {
+            peer,
+            video,
+            connection,
+            local: Rc::new(RefCell::new(None)),
+            pending: Rc::new(RefCell::new(vec![])),
+            remote_ready: Rc::new(Cell::new(false)),
+            closed: Rc::new(Cell::new(false)),
+            muted: Cell::new(false),
+            transmitting: Cell::new(true),
+            camera_off: Cell::new(false),
+            _onicecandidate: onicecandidate,
+            _ontrack: ontrack,
+            _onstatechange: onstatechange,
+        })
+    }
+
+    /// Calls `peer`: asks for the microphone (and camera, for a `video` call), then sends them
+    /// an offer.
+    pub fn dial(peer: String, video: bool, on_event: Callback<CallEvent>) -> Result<Self, String> {
+        Self::offer(peer, Media::Own { video }, Signaling::CALL, on_event)
+    }
+
+    /// Connects to `peer` in a voice channel, sending them our `microphone`, and camera if it
+    /// comes with one.
+    pub fn dial_voice(
+        peer: String,
+        microphone: MediaStream,
+        on_event: Callback<CallEvent>,
+    ) -> Result<Self, String> {
+        let media = Media::Shared(microphone);
+        Self::offer(peer, media, Signaling::VOICE, on_event)
+    }
+
+    fn offer(
+        peer: String,
+        media: Media,
+        signaling: Signaling,
+        on_event: Callback<CallEvent>,
+    ) -> Result<Self, String> {
+        let video = matches!(media, Media::Own { video: true });
+        // Anyone in a voice channel may have come with their camera, so leave room for it even
+        // when we came without. Left unset otherwise, as `false` would make our own video one-way.
+        let mut options = RtcOfferOptions::new();
+        if let Media::Shared(_) = media {
+            options.offer_to_receive_video(true);
+        }
+        let call = Self::new(peer, video, signaling, on_event.clone())?;
+        let setup = call.setup();
+        let connection = call.connection.clone();
+        let peer = call.peer.clone();
+        spawn_local(async move {
+            let offer = async {
+                setup.attach_media(media, &on_event).await?;
+                let offer =
+                    resolve(connection.create_offer_with_rtc_offer_options(&options)).await?;
+                let sdp = sdp_of(&offer)?;
+                set_description(&connection, RtcSdpType::Offer, &sdp, true).await?;
+                Ok::<_, String>(sdp)
+            };
+            match offer.await {
+                Ok(sdp) => on_event.emit(CallEvent::Signal(Box::new(WebSocketMessage {
+                    data: Some(sdp),
+                    to: Some(peer),
+                    ..WebSocketMessage::new(signaling.offer)
+                }))),
+                Err(e) => on_event.emit(CallEvent::Failed(e)),
+            }
+        });
+        Ok(call)
+    }
+
+    /// Picks up `peer`'s call, replying to their `offer` with an answer. `candidates` are the
+    /// ones they sent while we were still ringing. We send video back if they offered it.
+    pub fn answer(
+        peer: String,
+        offer: &str,
+        candidates: Vec<IceCandidate>,
+        on_event: Callback<CallEvent>,
+    ) -> Result<Self, String> {
+        let media = Media::Own {
+            video: offers_video(offer),
+        };
+        Self::reply(peer, offer, candidates, media, Signaling::CALL, on_event)
+    }
+
+    /// Takes up `peer`'s offer to connect in a voice channel, sending them our `microphone`, and
+    /// camera if it comes with one.
+    pub fn answer_voice(
+        peer: String,
+        offer: &str,
+        candidates: Vec<IceCandidate>,
+        microphone: MediaStream,
+        on_event: Callback<CallEvent>,
+    ) -> Result<Self, String> {
+        let media = Media::Shared(microphone);
+        Self::reply(peer, offer, candidates, media, Signaling::VOICE, on_event)
+    }
+
+    fn reply(
+        peer: String,
+        offer: &str,
+        candidates: Vec<IceCandidate>,
+        media: Media,
+        signaling: Signaling,
+        on_event: Callback<CallEvent>,
+    ) -> Result<Self, String> {
+        let video = matches!(media, Media::Own { video: true });
+        let call = Self::new(peer, video, signaling, on_event.clone())?;
+        call.pending.borrow_mut().extend(candidates);
+        let setup = call.setup();
+        let connection = call.connection.clone();
+        let peer = call.peer.clone();
+        let offer = offer.to_string();
+        spawn_local(async move {
+            let answer = async {
+                set_description(&connection, RtcSdpType::Offer, &offer, false).await?;
+                setup.remote_described();
+                setup.attach_media(media, &on_event).await?;
+                let answer = resolve(connection.create_answer()).await?;
+                let sdp = sdp_of(&answer)?;
+                set_description(&connection, RtcSdpType::Answer, &sdp, true).await?;
+                Ok::<_, String>(sdp)
+            };
+            match answer.await {
+                Ok(sdp) => on_event.emit(CallEvent::Signal(Box::new(WebSocketMessage {
+                    data: Some(sdp),
+                    to: Some(peer),
+                    ..WebSocketMessage::new(signaling.answer)
+                }))),
+                Err(e) => on_event.emit(CallEvent::Failed(e)),
+            }
+        });
+        Ok(call)
+    }
+
+    /// Applies the peer's reply to our offer.
+    pub fn accept_answer(&self, answer: &str, on_event: Callback<CallEvent>) {
+        let setup = self.setup();
+        let connection = self.connection.clone();
+        let answer = answer.to_string();
+        spawn_local(async move {
+            match set_description(&connection, RtcSdpType::Answer, &answer, false).await {
+                Ok(()) => setup.remote_described(),
+                Err(e) => on_event.emit(CallEvent::Failed(e)),
+            }
+        });
+    }
+
+    pub fn add_candidate(&self, candidate: IceCandidate) {
+        if self.remote_ready.get() {
+            add_candidate(&self.connection, &candidate);
+        } else {
+            self.pending.borrow_mut().push(candidate);
+        }
+    }
+
+    pub fn muted(&self) -> bool {
+        self.muted.get()
+    }
+
+    pub fn set_muted(&self, muted: bool) {
+        self.muted.set(muted);
+        self.gate_audio();
+    }
+
+    /// Lets our voice out or holds it back, for push-to-talk. Muting still wins.
+    pub fn set_transmitting(&self, transmitting: bool) {
+        self.transmitting.set(transmitting);
+        self.gate_audio();
+    }
+
+    fn gate_audio(&self) {
+        let on = !self.muted.get() && self.transmitting.get();
+        if let Some(stream) = &*self.local.borrow() {
+            for track in tracks(&stream.get_audio_tracks()) {
+                track.set_enabled(on);
+            }
+        }
+    }
+
+    /// Whether we are sending video: the camera was granted and is not switched off.
+    pub fn camera_on(&self) -> bool {
+        !self.camera_off.get() && self.has_camera()
+    }
+
+    pub fn has_camera(&self) -> bool {
+        self.local
+            .borrow()
+            .as_ref()
+            .is_some_and(|s| s.get_video_tracks().length() > 0)
+    }
+
+    /// Pauses or resumes our video. The track stays negotiated, the peer just sees black.
+    pub fn set_camera(&self, on: bool) {
+        self.camera_off.set(!on);
+        if let Some(stream) = &*self.local.borrow() {
+            for track in tracks(&stream.get_video_tracks()) {
+                track.set_enabled(on);
+            }
+        }
+    }
+
+    fn setup(&self) -> Setup {
+        Setup {
+            connection: self.connection.clone(),
+            local: self.local.clone(),
+            pending: self.pending.clone(),
+            remote_ready: self.remote_ready.clone(),
+            closed: self.closed.clone(),
+        }
+    }
+}
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        self.closed.set(true);
+        self.connection.set_onicecandidate(None);
+        self.connection.set_ontrack(None);
+        self.connection.set_oniceconnectionstatechange(None);
+        self.connection.close();
+        if let Some(stream) = self.local.borrow_mut().take() {
+            tracks(&stream.get_tracks())
+                .iter()
+                .for_each(MediaStreamTrack::stop);
+        }
+    }
+}
+
+/// The parts of a [`Call`] its async setup steps need, without keeping the call itself alive.
+struct Setup {
+    connection: RtcPeerConnection,
+    local: Rc<RefCell<Option<MediaStream>>>,
+    pending: Rc<RefCell<Vec<IceCandidate>>>,
+    remote_ready: Rc<Cell<bool>>,
+    closed: Rc<Cell<bool>>,
+}
+
+impl Setup {
+    /// Asks for the microphone, and the camera too for `video`, unless `media` is shared. Without a
+    /// camera (or permission to use it) the call goes ahead with audio only.
+    async fn attach_media(
+        &self,
+        media: Media,
+        on_event: &Callback<CallEvent>,
+    ) -> Result<(), String> {
+        let video = match media {
+            Media::Own { video } => video,
+            Media::Shared(stream) => {
+                if self.closed.get() {
+                    return Err("The call was cancelled.".into());
+                }
+                for track in tracks(&stream.get_tracks()) {
+                    self.connection.add_track_0(&track, &stream);
+                }
+                return Ok(());
+            }
+        };
+        let stream = match user_media(video).await {
+            Err(e) if video => {
+                log::debug!("call: no camera, falling back to audio: {}", e);
+                user_media(false).await
+            }
+            result => result,
+        }
+        .map_err(|_| "Microphone access was denied.".to_string())?;
+
+        // The call may have been hung up while the permission prompt was open.
+        if self.closed.get() {
+            tracks(&stream.get_tracks())
+                .iter()
+                .for_each(MediaStreamTrack::stop);
+            return Err("The call was cancelled.".into());
+        }
+        for track in tracks(&stream.get_tracks()) {
+            self.connection.add_track_0(&track, &stream);
+        }
+        *self.local.borrow_mut() = Some(stream.clone());
+        on_event.emit(CallEvent::LocalMedia(stream));
+        Ok(())
+    }
+
+    fn remote_described(&self) {
+        self.remote_ready.set(true);
+        for candidate in self.pending.borrow_mut().drain(..) {
+            add_candidate(&self.connection, &candidate);
+        }
+    }
+}
+
+/// Asks the browser for the microphone, the one chosen in the settings if any, and the camera too
+/// for `video`.
+pub async fn user_media(video: bool) -> Result<MediaStream, String> {
+    let mut constraints = MediaStreamConstraints::new();
+    constraints
+        .audio(&audio_devices::audio_constraint())
+        .video(&JsValue::from_bool(video));
+    let devices = web_sys::window()
+        .expect("no global `window`")
+        .navigator()
+        .media_devices()
+        .map_err(describe)?;
+    let stream = resolve(
+        devices
+            .get_user_media_with_constraints(&constraints)
+            .map_err(describe)?,
+    )
+    .await?;
+    Ok(stream.unchecked_into())
+}
+
+/// Whether an SDP offer carries a video section, i.e. the caller wants a video call.
+pub fn offers_video(sdp: &str) -> bool {
+    sdp.lines().any(|line| line.starts_with("m=video"))
+}
+
+fn add_candidate(connection: &RtcPeerConnection, candidate: &IceCandidate) {
+    let mut init = RtcIceCandidateInit::new(&candidate.candidate);
+    init.sdp_mid(candidate.sdp_mid.as_deref());
+    init.sdp_m_line_index(candidate.sdp_m_line_index);
+    let promise = connection.add_ice_candidate_with_opt_rtc_ice_candidate_init(Some(&init));
+    spawn_local(async move {
+        if let Err(e) = JsFuture::from(promise).await {
+            log::debug!("call: rejected ICE candidate: {:?}", e);
+        }
+    });
+}
+
+async fn set_description(
+    connection: &RtcPeerConnection,
+    kind: RtcSdpType,
+    sdp: &str,
+    local: bool,
+) -> Result<(), String> {
+    let mut description = RtcSessionDescriptionInit::new(kind);
+    description.sdp(sdp);
+    let promise = if local {
+        connection.set_local_description(&description)
+    } else {
+        connection.set_remote_description(&description)
+    };
+    resolve(promise).await.map(|_| ())
+}
+
+fn tracks(list: &Array) -> Vec<MediaStreamTrack> {
+    list.iter().map(JsCast::unchecked_into).collect()
+}
+
+fn sdp_of(description: &JsValue) -> Result<String, String> {
+    Reflect::get(description, &"sdp".into())
+        .ok()
+        .and_then(|sdp| sdp.as_string())
+        .ok_or_else(|| "no SDP in session description".to_string())
+}
+
+async fn resolve(promise: js_sys::Promise) -> Result<JsValue, String> {
+    JsFuture::from(promise).await.map_err(describe)
+}
+
+fn describe(e: JsValue) -> String {
+    format!("{:?}", e)
+}
@@ -0,0 +1,170 @@
+//! The calls we made and got, kept in this browser for each account so that a missed call can
+//! still be found once its ringing panel is gone. They show in the conversation with whoever was
+//! on the other end, and all together under Calls.
+
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::MessageData;
+
+/// How many calls are kept, forgetting the oldest.
+const MAX_CALLS: usize = 200;
+
+fn storage_key(username: &str) -> String {
+    format!("yewchat.calls.{}", username)
+}
+
+fn seen_key(username: &str) -> String {
+    format!("yewchat.calls_seen.{}", username)
+}
+
+/// How a call went, as far as we are concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Outcome {
+    /// We got to talk.
+    Answered,
+    /// It rang here without us picking up, or while we were on another call.
+    Missed,
+    /// Turned down, by either side.
+    Declined,
+    /// It rang there without them picking up, or we gave up first.
+    Unanswered,
+    /// Picked up, but never got through.
+    Failed,
+}
+
+/// A call between us and `peer`, over.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CallRecord {
+    pub peer: String,
+    pub outgoing: bool,
+    pub video: bool,
+    /// When it started ringing, in milliseconds since the epoch.
+    pub time: f64,
+    pub outcome: Outcome,
+    /// How long we talked, in milliseconds, if we did.
+    pub duration: Option<f64>,
+}
+
+/// `ms` as a clock shows it: minutes and seconds, with hours in front once there are some.
+pub fn duration_label(ms: f64) -> String {
+    let seconds = (ms / 1000.0).round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    match hours {
+        0 => format!("{}:{:02}", minutes, seconds),
+        _ => format!("{}:{:02}:{:02}", hours, minutes, seconds),
+    }
+}
+
+/// How many of `calls` were missed after `seen`.
+pub fn missed_since(calls: &[CallRecord], seen: f64) -> usize {
+    calls
+        .iter()
+        .filter(|call| call.outcome == Outcome::Missed && call.time > seen)
+        .count()
+}
+
+impl CallRecord {
+    /// How the call reads, both in the conversation and under Calls.
+    pub fn describe(&self) -> String {
+        let kind = if self.video { "video call" } else { "call" };
+        match (self.outcome, self.duration) {
+            (Outcome::Answered, Some(duration)) => format!(
+                "{} {}, {}",
+                if self.outgoing {
+                    "Outgoing"
+                } else {
+                    "Incoming"
+                },
+                kind,
+                duration_label(duration)
+            ),
+            (Outcome::Answered, None) => {
+                format!(
+                    "{} {}",
+                    if self.outgoing {
+                        "Outgoing"
+                    } else {
+                        "Incoming"
+                    },
+                    kind
+                )
+            }
+            (Outcome::Missed, _) => format!("Missed {}", kind),
+            (Outcome::Declined, _) => format!("Declined {}", kind),
+            (Outcome::Unanswered, _) => format!("Unanswered {}", kind),
+            (Outcome::Failed, _) => format!("Failed {}", kind),
+        }
+    }
+
+    /// The call as a line of the conversation with its peer, placed when it started ringing.
+    pub fn as_message(&self) -> MessageData {
+        MessageData {
+            time: Some(self.time),
+            ..MessageData::system(format!("📞 {}", self.describe()))
+        }
+    }
+}
+
+/// The calls of `username`, oldest first.
+pub fn load(username: &str) -> Vec<CallRecord> {
+    LocalStorage::get(storage_key(username)).unwrap_or_default()
+}
+
+/// Writes `record` down among the calls of `username`.
+pub fn add(username: &str, record: CallRecord) {
+    let mut calls = load(username);
+    calls.push(record);
+    if calls.len() > MAX_CALLS {
+        calls.drain(..calls.len() - MAX_CALLS);
+    }
+    if let Err(e) = LocalStorage::set(storage_key(username), &calls) {
+        log::error!("failed to save the call log: {:?}", e);
+    }
+}
+
+/// When `username` last looked at their calls.
+pub fn seen(username: &str) -> f64 {
+    LocalStorage::get(seen_key(username)).unwrap_or_default()
+}
+
+pub fn set_seen(username: &str, now: f64) {
+    if let Err(e) = LocalStorage::set(seen_key(username), now) {
+        log::error!("failed to save when calls were seen: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn calls_read_with_their_outcome_and_length() {
+        assert_eq!(duration_label(0.0), "0:00");
+        assert_eq!(duration_label(42_400.0), "0:42");
+        assert_eq!(duration_label(725_000.0), "12:05");
+        assert_eq!(duration_label(3_723_000.0), "1:02:03");
+
+        let call = CallRecord {
+            peer: "bob".into(),
+            outgoing: true,
+            video: false,
+            time: 0.0,
+            outcome: Outcome::Answered,
+            duration: Some(185_000.0),
+        };
+        assert_eq!(call.describe(), "Outgoing call, 3:05");
+        let missed = CallRecord {
+            outgoing: false,
+            video: true,
+            outcome: Outcome::Missed,
+            duration: None,
+            ..call.clone()
+        };
+        assert_eq!(missed.describe(), "Missed video call");
+        assert!(missed.as_message().system);
+        assert_eq!(missed_since(&[call, missed.clone()], -1.0), 1);
+        assert_eq!(missed_since(&[missed], 0.0), 0);
+    }
+}
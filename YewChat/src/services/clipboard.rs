@@ -0,0 +1,12 @@
+use js_sys::{Function, Reflect};
+use wasm_bindgen::{JsCast, JsValue};
+
+/// Puts `text` on the clipboard. `navigator.clipboard` is still an unstable API in web-sys, so it
+/// is called through reflection. Browsers only allow this in response to a click or key press.
+pub fn write_text(text: &str) -> Result<(), JsValue> {
+    let navigator = web_sys::window().ok_or("no window")?.navigator();
+    let clipboard = Reflect::get(&navigator, &"clipboard".into())?;
+    let write: Function = Reflect::get(&clipboard, &"writeText".into())?.dyn_into()?;
+    write.call1(&clipboard, &text.into())?;
+    Ok(())
+}
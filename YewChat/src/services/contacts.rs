@@ -0,0 +1,49 @@
+//! Contacts: people we added to keep at hand in the sidebar, kept in this browser for each account.
+//! Someone can be passed on to others as a contact card, an ordinary message naming them that
+//! every client can read and ours shows with their picture and buttons to message or add them.
+
+use gloo_storage::{LocalStorage, Storage};
+
+use crate::protocol::validate_username;
+
+const CARD_PREFIX: &str = "👤 Contact: ";
+
+fn storage_key(username: &str) -> String {
+    format!("yewchat.contacts.{}", username)
+}
+
+/// The contacts of `username`, in the order they were added.
+pub fn load(username: &str) -> Vec<String> {
+    LocalStorage::get(storage_key(username)).unwrap_or_default()
+}
+
+pub fn save(username: &str, contacts: &[String]) {
+    if let Err(e) = LocalStorage::set(storage_key(username), contacts) {
+        log::error!("failed to save contacts: {:?}", e);
+    }
+}
+
+/// The message passing `name` on as a contact card.
+pub fn card(name: &str) -> String {
+    format!("{}{}", CARD_PREFIX, name)
+}
+
+/// Who the contact card `text` is of, if it is one.
+pub fn card_of(text: &str) -> Option<&str> {
+    let name = text.strip_prefix(CARD_PREFIX)?;
+    validate_username(name).is_ok().then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn cards_name_a_valid_user() {
+        assert_eq!(card_of(&card("bob")), Some("bob"));
+        assert_eq!(card_of("bob"), None);
+        assert_eq!(card_of("👤 Contact: bob and alice"), None);
+        assert_eq!(card_of("👤 Contact: "), None);
+    }
+}
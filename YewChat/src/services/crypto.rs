@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use gloo_storage::{LocalStorage, Storage};
+use js_sys::{Array, Object, Promise, Reflect, Uint8Array, JSON};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    AesGcmParams, AesKeyGenParams, CryptoKey, EcKeyGenParams, EcKeyImportParams,
+    EcdhKeyDeriveParams, SubtleCrypto,
+};
+
+use crate::protocol::Sealed;
+
+const KEYS_PREFIX: &str = "yewchat.keys.";
+const TRUSTED_KEY: &str = "yewchat.trusted";
+const CURVE: &str = "P-256";
+const IV_BYTES: usize = 12;
+
+/// Our long-lived ECDH key for direct messages. The private half never leaves this browser; the
+/// public half is announced on `register` and handed to peers by the server.
+pub struct KeyPair {
+    private: CryptoKey,
+    public: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredKeys {
+    /// The private key as a JWK, serialized to a string.
+    private: String,
+    /// The public key in raw form, base64 encoded.
+    public: String,
+}
+
+impl KeyPair {
+    /// Loads `username`'s key pair from localStorage, creating (and saving) one the first time.
+    pub async fn load_or_create(username: &str) -> Result<Self, String> {
+        let storage_key = format!("{}{}", KEYS_PREFIX, username.to_lowercase());
+        if let Ok(stored) = LocalStorage::get::<StoredKeys>(&storage_key) {
+            match Self::import(&stored).await {
+                Ok(keys) => return Ok(keys),
+                Err(e) => log::error!("crypto: stored key is unusable, replacing it: {}", e),
+            }
+        }
+        let (keys, stored) = Self::generate().await?;
+        if let Err(e) = LocalStorage::set(&storage_key, &stored) {
+            log::error!("crypto: failed to persist key: {:?}", e);
+        }
+        Ok(keys)
+    }
+
+    async fn generate() -> Result<(Self, StoredKeys), String> {
+        let pair = resolve(subtle().generate_key_with_object(
+            &EcKeyGenParams::new("ECDH", CURVE),
+            true,
+            &usages(&["deriveKey"]),
+        ))
+        .await?;
+        let private: CryptoKey = Reflect::get(&pair, &"privateKey".into())
+            .map_err(describe)?
+            .unchecked_into();
+        let public: CryptoKey = Reflect::get(&pair, &"publicKey".into())
+            .map_err(describe)?
+            .unchecked_into();
+
+        let jwk = resolve(subtle().export_key("jwk", &private)).await?;
+        let raw = resolve(subtle().export_key("raw", &public)).await?;
+        let stored = StoredKeys {
+            private: JSON::stringify(&jwk).map_err(describe)?.into(),
+            public: encode(&Uint8Array::new(&raw).to_vec()),
+        };
+        Ok((
+            Self {
+                private,
+                public: stored.public.clone(),
+            },
+            stored,
+        ))
+    }
+
+    async fn import(stored: &StoredKeys) -> Result<Self, String> {
+        let jwk: Object = JSON::parse(&stored.private)
+            .map_err(describe)?
+            .unchecked_into();
+        let private = resolve(subtle().import_key_with_object(
+            "jwk",
+            &jwk,
+            &ecdh_params(),
+            true,
+            &usages(&["deriveKey"]),
+        ))
+        .await?
+        .unchecked_into();
+        Ok(Self {
+            private,
+            public: stored.public.clone(),
+        })
+    }
+
+    pub fn public_key(&self) -> &str {
+        &self.public
+    }
+
+    /// The AES key shared with the owner of `peer_public`. Both sides derive the same one, so it
+    /// also opens the messages we sent them.
+    async fn shared_key(&self, peer_public: &str) -> Result<CryptoKey, String> {
+        let raw = Uint8Array::from(decode(peer_public)?.as_slice());
+        let peer: CryptoKey = resolve(subtle().import_key_with_object(
+            "raw",
+            &raw,
+            &ecdh_params(),
+            true,
+            &usages(&[]),
+        ))
+        .await?
+        .unchecked_into();
+        let key = resolve(subtle().derive_key_with_object_and_object(
+            &EcdhKeyDeriveParams::new("ECDH", &peer),
+            &self.private,
+            &AesKeyGenParams::new("AES-GCM", 256),
+            false,
+            &usages(&["encrypt", "decrypt"]),
+        ))
+        .await?;
+        Ok(key.unchecked_into())
+    }
+
+    /// Encrypts `plaintext` for the owner of `peer_public`.
+    pub async fn seal(&self, peer_public: &str, plaintext: &str) -> Result<Sealed, String> {
+        let key = self.shared_key(peer_public).await?;
+        let mut iv = [0u8; IV_BYTES];
+        window()
+            .crypto()
+            .map_err(describe)?
+            .get_random_values_with_u8_array(&mut iv)
+            .map_err(describe)?;
+        let ciphertext = resolve(subtle().encrypt_with_object_and_u8_array(
+            &AesGcmParams::new("AES-GCM", &Uint8Array::from(&iv[..])),
+            &key,
+            &mut plaintext.as_bytes().to_vec(),
+        ))
+        .await?;
+        Ok(Sealed {
+            iv: encode(&iv),
+            ciphertext: encode(&Uint8Array::new(&ciphertext).to_vec()),
+        })
+    }
+
+    /// Decrypts a message exchanged with the owner of `peer_public`.
+    pub async fn open(&self, peer_public: &str, sealed: &Sealed) -> Result<String, String> {
+        let key = self.shared_key(peer_public).await?;
+        let iv = Uint8Array::from(decode(&sealed.iv)?.as_slice());
+        let plaintext = resolve(subtle().decrypt_with_object_and_u8_array(
+            &AesGcmParams::new("AES-GCM", &iv),
+            &key,
+            &mut decode(&sealed.ciphertext)?,
+        ))
+        .await?;
+        String::from_utf8(Uint8Array::new(&plaintext).to_vec()).map_err(|e| e.to_string())
+    }
+}
+
+/// A short digest of a public key for people to compare out of band, e.g. `3f2a 9c01 …`.
+pub fn fingerprint(public_key: &str) -> String {
+    let digest = Sha256::digest(public_key.as_bytes());
+    digest[..16]
+        .chunks(2)
+        .map(|pair| format!("{:02x}{:02x}", pair[0], pair[1]))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trust {
+    Unverified,
+    Verified,
+    /// The user verified a different key for this peer before: someone may be in the middle.
+    Changed,
+}
+
+/// How far we trust `public_key` to really belong to `peer`.
+pub fn trust(peer: &str, public_key: &str) -> Trust {
+    match trusted().get(&peer.to_lowercase()) {
+        None => Trust::Unverified,
+        Some(verified) if *verified == fingerprint(public_key) => Trust::Verified,
+        Some(_) => Trust::Changed,
+    }
+}
+
+/// Remembers that the user compared `peer`'s fingerprint and found it matching.
+pub fn mark_verified(peer: &str, public_key: &str) {
+    let mut trusted = trusted();
+    trusted.insert(peer.to_lowercase(), fingerprint(public_key));
+    if let Err(e) = LocalStorage::set(TRUSTED_KEY, trusted) {
+        log::error!("crypto: failed to persist verification: {:?}", e);
+    }
+}
+
+fn trusted() -> HashMap<String, String> {
+    LocalStorage::get(TRUSTED_KEY).unwrap_or_default()
+}
+
+fn window() -> web_sys::Window {
+    web_sys::window().expect("no global `window`")
+}
+
+fn subtle() -> SubtleCrypto {
+    window()
+        .crypto()
+        .expect("WebCrypto to be available")
+        .subtle()
+}
+
+fn ecdh_params() -> EcKeyImportParams {
+    let mut params = EcKeyImportParams::new("ECDH");
+    params.named_curve(CURVE);
+    params
+}
+
+fn usages(usages: &[&str]) -> JsValue {
+    usages
+        .iter()
+        .map(|u| JsValue::from_str(u))
+        .collect::<Array>()
+        .into()
+}
+
+async fn resolve(promise: Result<Promise, JsValue>) -> Result<JsValue, String> {
+    JsFuture::from(promise.map_err(describe)?)
+        .await
+        .map_err(describe)
+}
+
+fn describe(e: JsValue) -> String {
+    format!("{:?}", e)
+}
+
+// `btoa`/`atob` work on "binary strings" with one char per byte.
+fn encode(bytes: &[u8]) -> String {
+    let binary: String = bytes.iter().map(|&b| b as char).collect();
+    window().btoa(&binary).unwrap_or_default()
+}
+
+fn decode(encoded: &str) -> Result<Vec<u8>, String> {
+    let binary = window().atob(encoded).map_err(describe)?;
+    Ok(binary.chars().map(|c| c as u8).collect())
+}
@@ -0,0 +1,45 @@
+//! Do Not Disturb: until when we don't want to be drawn back to the chat. Kept per user in this
+//! browser, so it survives a reload and ends on its own.
+
+use gloo_storage::{LocalStorage, Storage};
+
+use crate::time;
+
+const MINUTE_MS: f64 = 60_000.0;
+
+/// The lengths offered, by label.
+pub const DURATIONS: [(&str, f64); 4] = [
+    ("30 minutes", 30.0 * MINUTE_MS),
+    ("1 hour", 60.0 * MINUTE_MS),
+    ("4 hours", 4.0 * 60.0 * MINUTE_MS),
+    ("24 hours", 24.0 * 60.0 * MINUTE_MS),
+];
+
+fn storage_key(username: &str) -> String {
+    format!("yewchat.dnd.{}", username)
+}
+
+/// When Do Not Disturb ends for `username`, if it is on. Sounds, desktop notifications and title
+/// badges are to be held back until then.
+pub fn until(username: &str) -> Option<f64> {
+    let until: f64 = LocalStorage::get(storage_key(username)).ok()?;
+    if until > time::now() {
+        Some(until)
+    } else {
+        clear(username);
+        None
+    }
+}
+
+/// Turns Do Not Disturb on for `username` for `duration` milliseconds, and returns when it ends.
+pub fn start(username: &str, duration: f64) -> f64 {
+    let until = time::now() + duration;
+    if let Err(e) = LocalStorage::set(storage_key(username), until) {
+        log::error!("failed to save Do Not Disturb: {:?}", e);
+    }
+    until
+}
+
+pub fn clear(username: &str) {
+    LocalStorage::delete(storage_key(username));
+}
@@ -0,0 +1,190 @@
+use js_sys::Array;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Blob, BlobPropertyBag, File, HtmlAnchorElement, Url};
+
+use crate::protocol::MessageData;
+use crate::sanitize;
+use crate::services::attachment::format_size;
+use crate::time;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Json,
+    Text,
+    Html,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [ExportFormat::Json, ExportFormat::Text, ExportFormat::Html];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "JSON",
+            ExportFormat::Text => "Plain text",
+            ExportFormat::Html => "HTML",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Text => "txt",
+            ExportFormat::Html => "html",
+        }
+    }
+
+    fn mime(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Text => "text/plain;charset=utf-8",
+            ExportFormat::Html => "text/html;charset=utf-8",
+        }
+    }
+}
+
+/// A conversation as written to a JSON export.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transcript {
+    /// The room (or `@peer` for a direct conversation) the messages were taken from.
+    pub room: String,
+    pub exported_at: f64,
+    pub messages: Vec<MessageData>,
+}
+
+impl Transcript {
+    /// Leaves out the system lines, which only this client wrote.
+    pub fn new<'a>(room: &str, messages: impl IntoIterator<Item = &'a MessageData>) -> Self {
+        Self {
+            room: room.to_string(),
+            exported_at: time::now(),
+            messages: messages
+                .into_iter()
+                .filter(|m| !m.system)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    fn render(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Json => serde_json::to_string_pretty(self).unwrap(),
+            ExportFormat::Text => self.to_text(),
+            ExportFormat::Html => self.to_html(),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = format!("# {}\n\n", self.room);
+        for m in &self.messages {
+            let when = m.time.map(time::absolute_label).unwrap_or_default();
+            // Later lines of a message are indented, so that each message still starts a line.
+            let text = m.message.replace('\n', "\n    ");
+            out.push_str(&format!("[{}] {}: {}", when, m.from, text));
+            if let Some(attachment) = &m.attachment {
+                out.push_str(&format!(
+                    " [attachment: {}, {}]",
+                    attachment.name,
+                    format_size(attachment.size as f64)
+                ));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn to_html(&self) -> String {
+        let mut body = String::new();
+        for m in &self.messages {
+            let when = m.time.map(time::absolute_label).unwrap_or_default();
+            // The page is opened from disk, where a crafted link would run with fewer limits.
+            let attachment = match &m.attachment {
+                Some(a) => match sanitize::attachment(a) {
+                    Some(data) if a.is_image() => {
+                        format!(r#"<img src="{}" alt="{}">"#, escape(&data), escape(&a.name))
+                    }
+                    Some(data) => format!(
+                        r#"<a href="{}" download="{}">{} ({})</a>"#,
+                        escape(&data),
+                        escape(&a.name),
+                        escape(&a.name),
+                        format_size(a.size as f64)
+                    ),
+                    None => format!(
+                        "<p>[attachment: {}, {}]</p>",
+                        escape(&a.name),
+                        format_size(a.size as f64)
+                    ),
+                },
+                None => String::new(),
+            };
+            body.push_str(&format!(
+                "<div class=\"message\"><span class=\"from\">{}</span> <time>{}</time><p>{}</p>{}</div>\n",
+                escape(&m.from),
+                escape(&when),
+                escape(&m.message),
+                attachment
+            ));
+        }
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title># {room}</title>\n\
+             <style>body{{font-family:sans-serif;max-width:48rem;margin:2rem auto;color:#1f2937}}\
+             .message{{margin-bottom:1rem}}.from{{font-weight:600}}time{{color:#9ca3af;font-size:.75rem}}\
+             p{{margin:.25rem 0;white-space:pre-wrap}}img{{max-width:100%}}</style>\n</head>\n<body>\n\
+             <h1># {room}</h1>\n{body}</body>\n</html>\n",
+            room = escape(&self.room),
+            body = body
+        )
+    }
+}
+
+/// Reads back a transcript previously exported as JSON.
+pub async fn read_transcript(file: &File) -> Result<Transcript, String> {
+    let text = JsFuture::from(file.text()).await.map_err(describe)?;
+    serde_json::from_str(&text.as_string().unwrap_or_default())
+        .map_err(|_| format!("{} is not a YewChat JSON export.", file.name()))
+}
+
+/// Saves the transcript in `format` through a browser download.
+pub fn download(transcript: &Transcript, format: ExportFormat) -> Result<(), String> {
+    let filename = format!(
+        "yewchat-{}.{}",
+        transcript.room.trim_start_matches('@'),
+        format.extension()
+    );
+    let contents = transcript.render(format);
+
+    let mut options = BlobPropertyBag::new();
+    options.type_(format.mime());
+    let blob = Blob::new_with_str_sequence_and_options(
+        &Array::of1(&JsValue::from_str(&contents)),
+        &options,
+    )
+    .map_err(describe)?;
+    let url = Url::create_object_url_with_blob(&blob).map_err(describe)?;
+
+    let document = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or("no document")?;
+    let link: HtmlAnchorElement = document
+        .create_element("a")
+        .map_err(describe)?
+        .unchecked_into();
+    link.set_href(&url);
+    link.set_download(&filename);
+    link.click();
+    Url::revoke_object_url(&url).map_err(describe)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn describe(e: JsValue) -> String {
+    format!("{:?}", e)
+}
@@ -0,0 +1,118 @@
+//! The latest raw frames to and from the server, for the developer panel. Meant for whoever is
+//! writing a server of their own and wants to see what the client makes of it.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+use serde_json::Value;
+
+use crate::time;
+
+/// How many frames are kept, dropping the oldest.
+pub const CAPACITY: usize = 200;
+
+thread_local! {
+    static FRAMES: RefCell<VecDeque<Frame>> = RefCell::default();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Received,
+    Sent,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub direction: Direction,
+    pub time: f64,
+    pub text: String,
+    /// Why a received frame couldn't be read, if it couldn't.
+    pub error: Option<DecodeError>,
+}
+
+/// A frame from the server that isn't a [`crate::protocol::WebSocketMessage`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodeError {
+    /// Its `messageType`, when there is one to be read.
+    pub message_type: Option<String>,
+    pub reason: String,
+}
+
+impl DecodeError {
+    pub fn new(frame: &str, error: &serde_json::Error) -> Self {
+        let message_type = serde_json::from_str::<Value>(frame)
+            .ok()
+            .and_then(|v| v.get("messageType")?.as_str().map(str::to_string));
+        Self {
+            message_type,
+            reason: error.to_string(),
+        }
+    }
+
+    /// What went wrong, in a line.
+    pub fn summary(&self) -> String {
+        match &self.message_type {
+            Some(message_type) => format!("`{}` frame: {}", message_type, self.reason),
+            None => self.reason.clone(),
+        }
+    }
+}
+
+pub fn record(direction: Direction, text: &str, error: Option<DecodeError>) {
+    FRAMES.with(|frames| {
+        let mut frames = frames.borrow_mut();
+        if frames.len() == CAPACITY {
+            frames.pop_front();
+        }
+        frames.push_back(Frame {
+            direction,
+            time: time::now(),
+            text: text.to_string(),
+            error,
+        });
+    });
+}
+
+/// The frames kept, newest first.
+pub fn recent() -> Vec<Frame> {
+    FRAMES.with(|frames| frames.borrow().iter().rev().cloned().collect())
+}
+
+pub fn clear() {
+    FRAMES.with(|frames| frames.borrow_mut().clear());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn keeps_the_latest_frames() {
+        clear();
+        for i in 0..CAPACITY + 5 {
+            record(Direction::Received, &i.to_string(), None);
+        }
+        let frames = recent();
+        assert_eq!(frames.len(), CAPACITY);
+        assert_eq!(frames[0].text, (CAPACITY + 4).to_string());
+        assert_eq!(frames[CAPACITY - 1].text, "5");
+        clear();
+    }
+
+    #[wasm_bindgen_test]
+    fn decode_errors_name_the_frame_type() {
+        let frame = r#"{"messageType":"shout"}"#;
+        let Err(e) = serde_json::from_str::<crate::protocol::WebSocketMessage>(frame) else {
+            panic!("an unknown type was accepted");
+        };
+        let error = DecodeError::new(frame, &e);
+        assert_eq!(error.message_type.as_deref(), Some("shout"));
+        assert!(error
+            .summary()
+            .starts_with("`shout` frame: unknown variant"));
+
+        let e = serde_json::from_str::<Value>("oops").unwrap_err();
+        assert_eq!(DecodeError::new("oops", &e).message_type, None);
+    }
+}
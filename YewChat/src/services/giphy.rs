@@ -0,0 +1,98 @@
+//! GIF search for the `/giphy` command. Only offered when an API key was provided at build time,
+//! as `YEWCHAT_GIPHY_API_KEY=... npm run build`.
+
+use js_sys::encode_uri_component;
+use reqwasm::http::Request;
+use serde::Deserialize;
+
+const SEARCH_URL: &str = "https://api.giphy.com/v1/gifs/search";
+
+/// How many results are offered to choose from.
+const RESULTS: usize = 12;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gif {
+    pub title: String,
+    /// A small version for the results.
+    pub preview: String,
+    /// What is sent, so that everyone sees it in place.
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    data: Vec<Found>,
+}
+
+#[derive(Deserialize)]
+struct Found {
+    #[serde(default)]
+    title: String,
+    images: Images,
+}
+
+#[derive(Deserialize)]
+struct Images {
+    fixed_height_small: Rendition,
+    downsized: Rendition,
+}
+
+#[derive(Deserialize)]
+struct Rendition {
+    url: String,
+}
+
+fn api_key() -> Option<&'static str> {
+    option_env!("YEWCHAT_GIPHY_API_KEY")
+}
+
+/// The keywords of a `/giphy keywords` message, if it is one.
+pub fn command(text: &str) -> Option<&str> {
+    let rest = text.trim().strip_prefix("/giphy")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(rest.trim()).filter(|q| !q.is_empty())
+}
+
+/// GIFs for `query`, rated for general audiences.
+pub async fn search(query: &str) -> Result<Vec<Gif>, String> {
+    let key = api_key().ok_or("GIF search isn't set up for this client")?;
+    let url = format!(
+        "{}?api_key={}&q={}&limit={}&rating=g",
+        SEARCH_URL,
+        encode_uri_component(key),
+        encode_uri_component(query),
+        RESULTS
+    );
+    let response = Request::get(&url).send().await.map_err(|e| e.to_string())?;
+    if !response.ok() {
+        return Err(format!("{} {}", response.status(), response.status_text()));
+    }
+    let found: SearchResponse = response.json().await.map_err(|e| e.to_string())?;
+    Ok(found
+        .data
+        .into_iter()
+        .map(|f| Gif {
+            title: f.title,
+            preview: f.images.fixed_height_small.url,
+            url: f.images.downsized.url,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn commands() {
+        assert_eq!(command("/giphy happy cat"), Some("happy cat"));
+        assert_eq!(command("  /giphy   dance \n"), Some("dance"));
+        assert_eq!(command("/giphy"), None);
+        assert_eq!(command("/giphy   "), None);
+        assert_eq!(command("/giphyfoo"), None);
+        assert_eq!(command("try /giphy cats"), None);
+    }
+}
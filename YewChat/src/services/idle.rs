@@ -0,0 +1,86 @@
+//! Whether we are at the keyboard. After some minutes without input or focus we show as away, and
+//! as back on the next keypress or click. Only changes are announced, so moving the mouse about
+//! doesn't send anything.
+
+use std::cell::Cell;
+
+use gloo_storage::{LocalStorage, Storage};
+
+use crate::protocol::{MsgTypes, WebSocketMessage};
+use crate::services::websocket::WebsocketService;
+use crate::time;
+
+const MINUTE_MS: f64 = 60_000.0;
+/// Minutes without activity before we show as away, unless chosen otherwise.
+pub const DEFAULT_MINUTES: u32 = 5;
+/// The thresholds offered in the settings. 0 stands for never.
+pub const CHOICES: [u32; 6] = [0, 2, 5, 10, 15, 30];
+
+thread_local! {
+    static LAST_ACTIVE: Cell<f64> = const { Cell::new(0.0) };
+    static AWAY: Cell<bool> = const { Cell::new(false) };
+}
+
+fn storage_key(username: &str) -> String {
+    format!("yewchat.away_after.{}", username)
+}
+
+/// Minutes without activity before `username` shows as away; 0 for never.
+pub fn away_after(username: &str) -> u32 {
+    LocalStorage::get(storage_key(username)).unwrap_or(DEFAULT_MINUTES)
+}
+
+pub fn set_away_after(username: &str, minutes: u32) {
+    if let Err(e) = LocalStorage::set(storage_key(username), minutes) {
+        log::error!("failed to save the away threshold: {:?}", e);
+    }
+}
+
+/// Whether we last announced ourselves as away.
+pub fn is_away() -> bool {
+    AWAY.with(Cell::get)
+}
+
+/// Notes that we are active, announcing that we are back if we were away.
+pub fn active(wss: &WebsocketService) {
+    LAST_ACTIVE.with(|last| last.set(time::now()));
+    if AWAY.with(|away| away.replace(false)) {
+        announce(wss, false);
+    }
+}
+
+/// Announces that we are away once we have been idle for `minutes` (never, for 0).
+pub fn check(wss: &WebsocketService, minutes: u32) {
+    let last = LAST_ACTIVE.with(Cell::get);
+    if !is_away() && idle(last, time::now(), minutes) {
+        AWAY.with(|away| away.set(true));
+        announce(wss, true);
+    }
+}
+
+fn idle(last_active: f64, now: f64, minutes: u32) -> bool {
+    minutes > 0 && now - last_active >= minutes as f64 * MINUTE_MS
+}
+
+/// Tells the room whether we are away. Registering again resets it on the server, so this is
+/// repeated after every registration.
+pub fn announce(wss: &WebsocketService, away: bool) {
+    wss.send(&WebSocketMessage {
+        data: away.then(|| "away".to_string()),
+        ..WebSocketMessage::new(MsgTypes::Away)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn idle_after_the_threshold() {
+        assert!(!idle(0.0, 4.0 * MINUTE_MS, 5));
+        assert!(idle(0.0, 5.0 * MINUTE_MS, 5));
+        // Never away.
+        assert!(!idle(0.0, 1e12, 0));
+    }
+}
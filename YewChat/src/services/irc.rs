@@ -0,0 +1,429 @@
+//! Talks to an IRC network through a WebSocket gateway that passes raw IRC lines back and forth
+//! (KiwiIRC's webircgateway, or any server following the IRCv3 WebSocket spec).
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use futures::channel::mpsc::{self, Sender};
+use futures::{SinkExt, StreamExt};
+use reqwasm::websocket::{futures::WebSocket, Message};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen_futures::spawn_local;
+use yew_agent::{Dispatched, Dispatcher};
+
+use crate::protocol::{MessageData, MsgTypes, Role, UserPayload, WebSocketMessage};
+use crate::services::event_bus::EventBus;
+use crate::services::session::Session;
+use crate::services::websocket::{emit, WebsocketService};
+use crate::User;
+
+/// Where to connect and as whom. IRC has no accounts of its own, so this is all we keep.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IrcSession {
+    pub gateway: String,
+    pub nick: String,
+}
+
+/// Stores the IRC connection details in the `User` context and the session.
+pub fn sign_in(user: &User, session: IrcSession) {
+    *user.username.borrow_mut() = session.nick.clone();
+    *user.irc.borrow_mut() = Some(session.clone());
+    Session {
+        username: session.nick.clone(),
+        tokens: None,
+        matrix: None,
+        irc: Some(session),
+    }
+    .save();
+}
+
+/// Opens a chat connection through the gateway. Frames sent through the service become IRC
+/// commands, and what the network tells us comes back through the [`EventBus`] as frames.
+pub fn connect(session: IrcSession) -> WebsocketService {
+    let (tx, mut rx) = mpsc::channel::<String>(1000);
+    let ws = match WebSocket::open(&session.gateway) {
+        Ok(ws) => ws,
+        Err(e) => {
+            log::error!("irc: can't open {}: {:?}", session.gateway, e);
+            return WebsocketService::from_channel(tx);
+        }
+    };
+    let (mut write, mut read) = ws.split();
+    let (lines, mut outgoing) = mpsc::channel::<String>(1000);
+    let client = Rc::new(Client {
+        nick: RefCell::new(session.nick),
+        channel: RefCell::new(None),
+        wanted: RefCell::new(None),
+        names: RefCell::new(vec![]),
+        incoming_names: RefCell::new(vec![]),
+        welcomed: Cell::new(false),
+        lines,
+    });
+
+    spawn_local(async move {
+        while let Some(line) = outgoing.next().await {
+            log::debug!("to irc: {}", line);
+            if write.send(Message::Text(line)).await.is_err() {
+                break;
+            }
+        }
+        if let Err(e) = write.close().await {
+            log::error!("irc: failed to close: {:?}", e);
+        }
+    });
+
+    {
+        let client = client.clone();
+        spawn_local(async move {
+            let mut bus = EventBus::dispatcher();
+            while let Some(s) = rx.next().await {
+                if let Ok(msg) = serde_json::from_str::<WebSocketMessage>(&s) {
+                    client.handle(&mut bus, msg);
+                }
+            }
+            // Every handle was dropped: say goodbye, which also ends the writer.
+            client.send("QUIT :Leaving".into());
+            client.lines.clone().close_channel();
+        });
+    }
+
+    spawn_local(async move {
+        let mut bus = EventBus::dispatcher();
+        while let Some(msg) = read.next().await {
+            let data = match msg {
+                Ok(Message::Text(data)) => data,
+                Ok(Message::Bytes(b)) => String::from_utf8_lossy(&b).into_owned(),
+                Err(e) => {
+                    log::error!("irc: {:?}", e);
+                    continue;
+                }
+            };
+            // Gateways usually send one line per frame, but some batch them.
+            for line in data.lines().filter(|l| !l.is_empty()) {
+                log::debug!("from irc: {}", line);
+                match parse(line) {
+                    Some(line) => client.receive(&mut bus, line),
+                    None => log::warn!("irc: unreadable line: {}", line),
+                }
+            }
+        }
+        log::debug!("IRC gateway closed");
+    });
+
+    WebsocketService::from_channel(tx)
+}
+
+struct Client {
+    /// Our current nick; the network may make us pick another one.
+    nick: RefCell<String>,
+    /// The channel the chat shows, once joined.
+    channel: RefCell<Option<String>>,
+    /// A channel to join once the network has welcomed us.
+    wanted: RefCell<Option<String>>,
+    names: RefCell<Vec<String>>,
+    /// `NAMES` replies come in several lines; this collects them until the last one.
+    incoming_names: RefCell<Vec<String>>,
+    welcomed: Cell<bool>,
+    lines: Sender<String>,
+}
+
+impl Client {
+    fn send(&self, line: String) {
+        if let Err(e) = self.lines.clone().try_send(line) {
+            log::debug!("irc: error sending to channel: {:?}", e);
+        }
+    }
+
+    /// Translates a frame from the chat into IRC commands.
+    fn handle(&self, bus: &mut Dispatcher<EventBus>, msg: WebSocketMessage) {
+        match msg.message_type {
+            MsgTypes::Register => {
+                let nick = self.nick.borrow().clone();
+                self.send(format!("NICK {}", nick));
+                self.send(format!("USER {} 0 * :YewChat", nick));
+                *self.wanted.borrow_mut() =
+                    Some(channel_for(msg.room.as_deref().unwrap_or("general")));
+            }
+            MsgTypes::Join => {
+                let Some(room) = msg.data else {
+                    return;
+                };
+                let wanted = channel_for(&room);
+                if self.welcomed.get() {
+                    if let Some(current) = self.channel.borrow_mut().take() {
+                        self.send(format!("PART {}", current));
+                    }
+                    self.send(format!("JOIN {}", wanted));
+                } else {
+                    *self.wanted.borrow_mut() = Some(wanted);
+                }
+            }
+            MsgTypes::Message if msg.attachment.is_some() => {
+                log::error!("irc: attachments aren't supported on IRC")
+            }
+            MsgTypes::Message => {
+                let Some(channel) = self.channel.borrow().clone() else {
+                    return;
+                };
+                let text = msg.data.unwrap_or_default();
+                for line in text.lines().filter(|l| !l.is_empty()) {
+                    self.send(format!("PRIVMSG {} :{}", channel, line));
+                }
+                // Networks don't echo our own messages back, so show it ourselves.
+                emit(
+                    bus,
+                    MsgTypes::Message,
+                    &MessageData {
+                        id: None,
+                        from: self.nick.borrow().clone(),
+                        message: text,
+                        attachment: None,
+                        time: Some(js_sys::Date::now()),
+                        bot: None,
+                        to: None,
+                        announcement: false,
+                        pinned_until: None,
+                        system: false,
+                    },
+                );
+            }
+            MsgTypes::Unregister => self.send("QUIT :Leaving".into()),
+            // Encrypted direct messages, calls and bots only exist on our own server.
+            _ => {}
+        }
+    }
+
+    /// Acts on a line from the network.
+    fn receive(&self, bus: &mut Dispatcher<EventBus>, line: Line) {
+        let nick = line.nick().unwrap_or_default();
+        let is_me = nick == *self.nick.borrow();
+        let in_channel = |name: Option<&&str>| {
+            name.is_some_and(|c| {
+                self.channel
+                    .borrow()
+                    .as_deref()
+                    .is_some_and(|own| own.eq_ignore_ascii_case(c))
+            })
+        };
+
+        match line.command {
+            "PING" => self.send(format!("PONG :{}", line.params.first().unwrap_or(&""))),
+            // Welcome: registration went through, and the network tells us our nick.
+            "001" => {
+                self.welcomed.set(true);
+                if let Some(nick) = line.params.first() {
+                    *self.nick.borrow_mut() = nick.to_string();
+                }
+                if let Some(channel) = self.wanted.borrow_mut().take() {
+                    self.send(format!("JOIN {}", channel));
+                }
+            }
+            // Nick in use. Before we're welcomed, retry with another one.
+            "433" if !self.welcomed.get() => {
+                let nick = format!("{}_", self.nick.borrow());
+                self.send(format!("NICK {}", nick));
+                *self.nick.borrow_mut() = nick;
+            }
+            // A page of the names in a channel.
+            "353" if in_channel(line.params.get(2)) => {
+                let names = line.params.get(3).copied().unwrap_or_default();
+                self.incoming_names
+                    .borrow_mut()
+                    .extend(names.split(' ').filter(|n| !n.is_empty()).map(strip_prefix));
+            }
+            // End of the names.
+            "366" if in_channel(line.params.get(1)) => {
+                *self.names.borrow_mut() = self.incoming_names.take();
+                self.send_users(bus);
+            }
+            "JOIN" if is_me => {
+                let channel = line.params.first().copied().unwrap_or_default();
+                *self.channel.borrow_mut() = Some(channel.to_string());
+                self.incoming_names.borrow_mut().clear();
+            }
+            "JOIN" if in_channel(line.params.first()) => {
+                self.names.borrow_mut().push(nick.to_string());
+                self.send_users(bus);
+            }
+            "PART" if in_channel(line.params.first()) => self.forget(bus, nick),
+            "KICK" if in_channel(line.params.first()) => {
+                let kicked = line.params.get(1).copied().unwrap_or_default();
+                if kicked == *self.nick.borrow() {
+                    self.channel.borrow_mut().take();
+                    self.names.borrow_mut().clear();
+                    self.send_users(bus);
+                } else {
+                    self.forget(bus, kicked);
+                }
+            }
+            "QUIT" => self.forget(bus, nick),
+            "NICK" => {
+                let new = line.params.first().copied().unwrap_or_default();
+                if is_me {
+                    *self.nick.borrow_mut() = new.to_string();
+                }
+                let renamed = {
+                    let mut names = self.names.borrow_mut();
+                    names
+                        .iter_mut()
+                        .find(|n| **n == nick)
+                        .map(|n| *n = new.to_string())
+                };
+                if renamed.is_some() {
+                    self.send_users(bus);
+                }
+            }
+            "PRIVMSG" if in_channel(line.params.first()) => {
+                let text = line.params.get(1).copied().unwrap_or_default();
+                let message = match text.strip_prefix("\u{1}ACTION ") {
+                    Some(action) => format!("* {} {}", nick, action.trim_end_matches('\u{1}')),
+                    // Other CTCP requests aren't meant to be shown.
+                    None if text.starts_with('\u{1}') => return,
+                    None => text.to_string(),
+                };
+                emit(
+                    bus,
+                    MsgTypes::Message,
+                    &MessageData {
+                        id: None,
+                        from: nick.to_string(),
+                        message,
+                        attachment: None,
+                        time: Some(js_sys::Date::now()),
+                        bot: None,
+                        to: None,
+                        announcement: false,
+                        pinned_until: None,
+                        system: false,
+                    },
+                );
+            }
+            "ERROR" => log::error!("irc: {}", line.params.join(" ")),
+            _ => {}
+        }
+    }
+
+    fn forget(&self, bus: &mut Dispatcher<EventBus>, nick: &str) {
+        let before = self.names.borrow().len();
+        self.names.borrow_mut().retain(|n| n != nick);
+        if self.names.borrow().len() != before {
+            self.send_users(bus);
+        }
+    }
+
+    fn send_users(&self, bus: &mut Dispatcher<EventBus>) {
+        let mut users: Vec<UserPayload> = self
+            .names
+            .borrow()
+            .iter()
+            .map(|name| UserPayload {
+                name: name.clone(),
+                avatar: None,
+                key: None,
+                gravatar: None,
+                away: false,
+                voice: false,
+                role: Role::default(),
+                room_role: None,
+            })
+            .collect();
+        users.sort_by_key(|u| u.name.to_lowercase());
+        emit(bus, MsgTypes::Users, &users);
+    }
+}
+
+/// Chat room names map to channels of the same name: `general` is `#general`.
+fn channel_for(room: &str) -> String {
+    if room.starts_with('#') || room.starts_with('&') {
+        room.to_string()
+    } else {
+        format!("#{}", room)
+    }
+}
+
+/// Drops the `@` of operators, `+` of voiced users and the like from a `NAMES` entry.
+fn strip_prefix(name: &str) -> String {
+    name.trim_start_matches(['~', '&', '@', '%', '+'])
+        .to_string()
+}
+
+/// One line of the IRC protocol, split into its parts.
+#[derive(Debug, PartialEq)]
+struct Line<'a> {
+    /// Who sent it, like `nick!user@host` or a server name.
+    source: Option<&'a str>,
+    command: &'a str,
+    /// Parameters, with the trailing one (after ` :`) kept whole.
+    params: Vec<&'a str>,
+}
+
+impl<'a> Line<'a> {
+    fn nick(&self) -> Option<&'a str> {
+        self.source.map(|s| s.split('!').next().unwrap_or(s))
+    }
+}
+
+fn parse(line: &str) -> Option<Line<'_>> {
+    let mut rest = line.trim_end_matches(['\r', '\n']);
+    // IRCv3 message tags carry nothing we use.
+    if rest.starts_with('@') {
+        rest = rest.split_once(' ')?.1.trim_start();
+    }
+    let source = match rest.strip_prefix(':') {
+        Some(prefixed) => {
+            let (source, after) = prefixed.split_once(' ')?;
+            rest = after.trim_start();
+            Some(source)
+        }
+        None => None,
+    };
+    let (middle, trailing) = match rest.split_once(" :") {
+        Some((middle, trailing)) => (middle, Some(trailing)),
+        None => (rest, None),
+    };
+    let mut words = middle.split(' ').filter(|w| !w.is_empty());
+    let command = words.next()?;
+    let mut params: Vec<&str> = words.collect();
+    params.extend(trailing);
+    Some(Line {
+        source,
+        command,
+        params,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn parses_source_command_and_trailing_parameter() {
+        let line = parse(":alice!a@example.org PRIVMSG #general :hi there").unwrap();
+        assert_eq!(line.source, Some("alice!a@example.org"));
+        assert_eq!(line.nick(), Some("alice"));
+        assert_eq!(line.command, "PRIVMSG");
+        assert_eq!(line.params, vec!["#general", "hi there"]);
+    }
+
+    #[wasm_bindgen_test]
+    fn parses_lines_without_source_and_with_tags() {
+        let ping = parse("PING :irc.example.org\r\n").unwrap();
+        assert_eq!(ping.source, None);
+        assert_eq!(ping.params, vec!["irc.example.org"]);
+
+        let tagged = parse("@time=2021-01-01T00:00:00Z :bob JOIN #general").unwrap();
+        assert_eq!(tagged.nick(), Some("bob"));
+        assert_eq!(tagged.params, vec!["#general"]);
+
+        assert_eq!(parse(""), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn maps_rooms_to_channels_and_cleans_names() {
+        assert_eq!(channel_for("general"), "#general");
+        assert_eq!(channel_for("#rust"), "#rust");
+        assert_eq!(strip_prefix("@alice"), "alice");
+        assert_eq!(strip_prefix("+bob"), "bob");
+    }
+}
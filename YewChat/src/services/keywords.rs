@@ -0,0 +1,62 @@
+//! Words we want to be pointed at, like our project's name: messages containing them are
+//! highlighted like those mentioning us. Kept per user in this browser.
+
+use gloo_storage::{LocalStorage, Storage};
+
+fn storage_key(username: &str) -> String {
+    format!("yewchat.keywords.{}", username)
+}
+
+pub fn load(username: &str) -> Vec<String> {
+    LocalStorage::get(storage_key(username)).unwrap_or_default()
+}
+
+pub fn save(username: &str, keywords: &[String]) {
+    if let Err(e) = LocalStorage::set(storage_key(username), keywords) {
+        log::error!("failed to save keywords: {:?}", e);
+    }
+}
+
+/// The keywords of a comma-separated list, lowercased and without repeats.
+pub fn parse(list: &str) -> Vec<String> {
+    let mut keywords: Vec<String> = vec![];
+    for keyword in list.split(',').map(|k| k.trim().to_lowercase()) {
+        if !keyword.is_empty() && !keywords.contains(&keyword) {
+            keywords.push(keyword);
+        }
+    }
+    keywords
+}
+
+/// Whether `text` contains one of `watched` as a whole word, ignoring case. A name also matches
+/// as an `@mention`.
+pub fn matches(text: &str, watched: &[String]) -> bool {
+    let text = text.to_lowercase();
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    watched.iter().any(|word| {
+        let word = word.to_lowercase();
+        !word.is_empty()
+            && text.match_indices(&word).any(|(at, _)| {
+                !is_word(text[..at].chars().next_back())
+                    && !is_word(text[at + word.len()..].chars().next())
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn whole_words_in_any_case() {
+        let watched = parse(" Deploy, alice,,deploy ");
+        assert_eq!(watched, ["deploy", "alice"]);
+
+        assert!(matches("Ready to DEPLOY?", &watched));
+        assert!(matches("thanks @alice!", &watched));
+        assert!(!matches("redeployed it", &watched));
+        assert!(!matches("alice_bot says hi", &watched));
+        assert!(!matches("anything", &[]));
+    }
+}
@@ -0,0 +1,278 @@
+//! Sharing where we are. A location goes out as an ordinary message linking to OpenStreetMap,
+//! which every client can follow and ours shows as a map. Shared live, the message says for how
+//! long, and our position follows it to the rest of the room in `location` frames as we move.
+
+use std::cell::Cell;
+
+use js_sys::Promise;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Geolocation, PositionError, PositionOptions, Url};
+use yew::Callback;
+
+use crate::protocol::MessageData;
+use crate::time;
+
+/// How long a location can be shared live for, in minutes.
+pub const LIVE_MINUTES: [u32; 3] = [15, 60, 480];
+/// At most how often a live location is sent on, in milliseconds.
+const UPDATE_MS: f64 = 10_000.0;
+/// How long the browser may take to find us, in milliseconds.
+const TIMEOUT_MS: u32 = 20_000;
+/// The zoom of the map shown for a location, about a neighbourhood.
+pub const ZOOM: u32 = 15;
+/// The side of a map tile, in pixels.
+pub const TILE: f64 = 256.0;
+
+const LIVE_PREFIX: &str = "📍 Live location for ";
+
+/// A place, in degrees. Sent as the payload of `location`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Position {
+    fn of(position: &web_sys::Position) -> Self {
+        let coords = position.coords();
+        // About a metre is close enough, and no closer than the browser can tell.
+        let round = |degrees: f64| (degrees * 100_000.0).round() / 100_000.0;
+        Self {
+            lat: round(coords.latitude()),
+            lon: round(coords.longitude()),
+        }
+    }
+
+    /// The place on OpenStreetMap, with a marker.
+    pub fn link(&self) -> String {
+        format!(
+            "https://www.openstreetmap.org/?mlat={lat}&mlon={lon}#map={zoom}/{lat}/{lon}",
+            lat = self.lat,
+            lon = self.lon,
+            zoom = ZOOM
+        )
+    }
+
+    /// The place a link made by [`link`](Self::link) marks, if `url` is one.
+    pub fn from_link(url: &Url) -> Option<Self> {
+        let host = url.hostname();
+        if host.trim_start_matches("www.") != "openstreetmap.org" {
+            return None;
+        }
+        let params = url.search_params();
+        let position = Self {
+            lat: params.get("mlat")?.parse().ok()?,
+            lon: params.get("mlon")?.parse().ok()?,
+        };
+        let valid =
+            (-90.0..=90.0).contains(&position.lat) && (-180.0..=180.0).contains(&position.lon);
+        valid.then_some(position)
+    }
+
+    /// The message sharing the place, live for `live` minutes if given.
+    pub fn message(&self, live: Option<u32>) -> String {
+        match live {
+            Some(minutes) => format!("{}{} minutes: {}", LIVE_PREFIX, minutes, self.link()),
+            None => format!("📍 {}", self.link()),
+        }
+    }
+
+    /// Where the place falls on the world map at `zoom`, in pixels from its top left corner.
+    pub fn pixel(&self, zoom: u32) -> (f64, f64) {
+        let size = TILE * f64::from(1u32 << zoom);
+        let lat = self.lat.to_radians();
+        let x = (self.lon + 180.0) / 360.0 * size;
+        let y = (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / std::f64::consts::PI) / 2.0 * size;
+        (x, y)
+    }
+}
+
+/// For how many minutes the location in a message made by [`Position::message`] is shared live,
+/// if it is.
+pub fn live_minutes(text: &str) -> Option<u32> {
+    let rest = text.strip_prefix(LIVE_PREFIX)?;
+    rest.split(' ').next()?.parse().ok()
+}
+
+/// A location shared live: by whom, and from when until when, in milliseconds since the epoch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Live {
+    pub from: String,
+    pub since: f64,
+    pub until: f64,
+}
+
+/// How `message` shares a location live, if it does.
+pub fn live(message: &MessageData) -> Option<Live> {
+    let minutes = live_minutes(&message.message)?;
+    let since = message.time?;
+    Some(Live {
+        from: message.from.clone(),
+        since,
+        until: since + f64::from(minutes) * 60_000.0,
+    })
+}
+
+/// The tiles of the map around `position` that cover `width` by `height` pixels centred on it: the
+/// column and row of each, and where its top left corner goes.
+pub fn tiles(position: &Position, width: f64, height: f64) -> Vec<(u32, u32, f64, f64)> {
+    let (x, y) = position.pixel(ZOOM);
+    let count = 1i64 << ZOOM;
+    let (column, row) = ((x / TILE).floor() as i64, (y / TILE).floor() as i64);
+    let reach = |side: f64| (side / 2.0 / TILE).ceil() as i64;
+    let (columns, rows) = (reach(width), reach(height));
+    let mut tiles = vec![];
+    for r in row - rows..=row + rows {
+        if !(0..count).contains(&r) {
+            continue;
+        }
+        for c in column - columns..=column + columns {
+            let left = c as f64 * TILE - x + width / 2.0;
+            let top = r as f64 * TILE - y + height / 2.0;
+            if left >= width || top >= height || left + TILE <= 0.0 || top + TILE <= 0.0 {
+                continue;
+            }
+            // Past the date line, the map starts over.
+            tiles.push((c.rem_euclid(count) as u32, r as u32, left, top));
+        }
+    }
+    tiles
+}
+
+fn geolocation() -> Result<Geolocation, &'static str> {
+    web_sys::window()
+        .and_then(|w| w.navigator().geolocation().ok())
+        .ok_or("This browser can't tell where you are.")
+}
+
+fn options() -> PositionOptions {
+    let mut options = PositionOptions::new();
+    options.enable_high_accuracy(true).timeout(TIMEOUT_MS);
+    options
+}
+
+fn describe(error: &PositionError) -> &'static str {
+    match error.code() {
+        PositionError::PERMISSION_DENIED => "Location access was denied.",
+        PositionError::TIMEOUT => "Finding where you are took too long.",
+        _ => "Your location couldn't be found.",
+    }
+}
+
+/// Where we are, once the browser has asked for permission and found out.
+pub async fn current() -> Result<Position, &'static str> {
+    let geolocation = geolocation()?;
+    let found = Promise::new(&mut |resolve, reject| {
+        if let Err(e) = geolocation.get_current_position_with_error_callback_and_options(
+            &resolve,
+            Some(&reject),
+            &options(),
+        ) {
+            let _ = reject.call1(&JsValue::NULL, &e);
+        }
+    });
+    match JsFuture::from(found).await {
+        Ok(position) => Ok(Position::of(position.unchecked_ref())),
+        Err(e) => Err(e
+            .dyn_ref::<PositionError>()
+            .map_or("Your location couldn't be found.", describe)),
+    }
+}
+
+/// Our position as we move, until dropped.
+pub struct Watch {
+    geolocation: Geolocation,
+    id: i32,
+    _on_position: Closure<dyn FnMut(web_sys::Position)>,
+    _on_error: Closure<dyn FnMut(PositionError)>,
+}
+
+impl Watch {
+    /// Tells `on_position` where we are whenever it changes, at most every [`UPDATE_MS`].
+    pub fn start(on_position: Callback<Position>) -> Result<Self, &'static str> {
+        let geolocation = geolocation()?;
+        let sent = Cell::new(f64::NEG_INFINITY);
+        let on_position = Closure::wrap(Box::new(move |position: web_sys::Position| {
+            let now = time::now();
+            if now - sent.get() >= UPDATE_MS {
+                sent.set(now);
+                on_position.emit(Position::of(&position));
+            }
+        }) as Box<dyn FnMut(web_sys::Position)>);
+        let on_error = Closure::wrap(Box::new(|e: PositionError| {
+            log::warn!("location: {}", describe(&e));
+        }) as Box<dyn FnMut(PositionError)>);
+        let id = geolocation
+            .watch_position_with_error_callback_and_options(
+                on_position.as_ref().unchecked_ref(),
+                Some(on_error.as_ref().unchecked_ref()),
+                &options(),
+            )
+            .map_err(|_| "Your location couldn't be followed.")?;
+        Ok(Self {
+            geolocation,
+            id,
+            _on_position: on_position,
+            _on_error: on_error,
+        })
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.geolocation.clear_watch(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn locations_go_out_as_map_links() {
+        let tower = Position {
+            lat: 48.85837,
+            lon: 2.29448,
+        };
+        assert_eq!(
+            tower.message(None),
+            "📍 https://www.openstreetmap.org/?mlat=48.85837&mlon=2.29448#map=15/48.85837/2.29448"
+        );
+        let url = Url::new(&tower.link()).unwrap();
+        assert_eq!(Position::from_link(&url), Some(tower));
+        let elsewhere = Url::new("https://example.com/?mlat=1&mlon=2").unwrap();
+        assert_eq!(Position::from_link(&elsewhere), None);
+        let off_the_map = Url::new("https://openstreetmap.org/?mlat=95&mlon=2").unwrap();
+        assert_eq!(Position::from_link(&off_the_map), None);
+
+        assert_eq!(live_minutes(&tower.message(Some(60))), Some(60));
+        assert_eq!(live_minutes(&tower.message(None)), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn the_map_is_centred_on_the_place() {
+        let origin = Position { lat: 0.0, lon: 0.0 };
+        let middle = TILE * f64::from(1u32 << (ZOOM - 1));
+        assert_eq!(origin.pixel(ZOOM), (middle, middle));
+
+        // The place is where four tiles meet, so each shows a quarter of the map.
+        let around = tiles(&origin, 320.0, 180.0);
+        assert_eq!(around.len(), 4);
+        let half = 1u32 << (ZOOM - 1);
+        assert!(around.contains(&(half - 1, half - 1, 160.0 - TILE, 90.0 - TILE)));
+        assert!(around.contains(&(half, half, 160.0, 90.0)));
+
+        // Across the date line, the columns wrap around.
+        let date_line = Position {
+            lat: 0.0,
+            lon: 180.0,
+        };
+        assert!(tiles(&date_line, 320.0, 180.0)
+            .iter()
+            .any(|&(column, ..)| column == 0));
+    }
+}
@@ -0,0 +1,427 @@
+//! Talks to a Matrix homeserver instead of our own server, translating between the
+//! client-server API and the frames the chat already understands.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use futures::channel::mpsc;
+use futures::StreamExt;
+use reqwasm::http::Request;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use wasm_bindgen_futures::spawn_local;
+use yew_agent::{Dispatched, Dispatcher};
+
+use crate::protocol::{AuthError, MessageData, MsgTypes, Role, UserPayload, WebSocketMessage};
+use crate::services::event_bus::EventBus;
+use crate::services::session::Session;
+use crate::services::websocket::{emit, WebsocketService};
+use crate::User;
+
+/// How long the homeserver may hold a `/sync` request open when nothing happens.
+const SYNC_TIMEOUT_MS: u32 = 30_000;
+
+/// Messages loaded from before we entered a room.
+const HISTORY_LIMIT: u32 = 50;
+
+pub const DEFAULT_HOMESERVER: &str = "https://matrix.org";
+
+/// An access token for a Matrix account, kept in the session instead of our own [`Tokens`].
+///
+/// [`Tokens`]: crate::protocol::Tokens
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MatrixSession {
+    pub homeserver: String,
+    /// Full user ID, like `@alice:matrix.org`. Doubles as our username in the chat.
+    pub user_id: String,
+    pub access_token: String,
+}
+
+#[derive(Deserialize)]
+struct LoginResponse {
+    user_id: String,
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct JoinResponse {
+    room_id: String,
+}
+
+#[derive(Deserialize)]
+struct JoinedMembers {
+    joined: HashMap<String, Member>,
+}
+
+#[derive(Deserialize)]
+struct Member {
+    avatar_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Messages {
+    chunk: Vec<RoomEvent>,
+}
+
+#[derive(Deserialize)]
+struct SyncResponse {
+    next_batch: String,
+    #[serde(default)]
+    rooms: SyncRooms,
+}
+
+#[derive(Default, Deserialize)]
+struct SyncRooms {
+    #[serde(default)]
+    join: HashMap<String, JoinedRoom>,
+}
+
+#[derive(Deserialize)]
+struct JoinedRoom {
+    #[serde(default)]
+    timeline: Timeline,
+}
+
+#[derive(Default, Deserialize)]
+struct Timeline {
+    #[serde(default)]
+    events: Vec<RoomEvent>,
+}
+
+#[derive(Deserialize)]
+struct RoomEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    event_id: Option<String>,
+    sender: String,
+    #[serde(default)]
+    origin_server_ts: Option<f64>,
+    #[serde(default)]
+    content: Value,
+}
+
+impl RoomEvent {
+    /// The event as a chat message, if it is one we can show.
+    fn to_message(&self) -> Option<MessageData> {
+        if self.kind != "m.room.message" {
+            return None;
+        }
+        let body = self.content.get("body")?.as_str()?;
+        let message = match self.content.get("msgtype").and_then(Value::as_str) {
+            Some("m.emote") => format!("* {} {}", self.sender, body),
+            _ => body.to_string(),
+        };
+        Some(MessageData {
+            id: self.event_id.clone(),
+            from: self.sender.clone(),
+            message,
+            attachment: None,
+            time: self.origin_server_ts,
+            bot: None,
+            to: None,
+            announcement: false,
+            pinned_until: None,
+            system: false,
+        })
+    }
+}
+
+/// Logs in with a password, as Element and most other clients do.
+pub async fn login(
+    homeserver: &str,
+    username: &str,
+    password: &str,
+) -> Result<MatrixSession, AuthError> {
+    let homeserver = homeserver.trim_end_matches('/').to_string();
+    let body = json!({
+        "type": "m.login.password",
+        "identifier": { "type": "m.id.user", "user": username },
+        "password": password,
+        "initial_device_display_name": "YewChat",
+    });
+    let response = Request::post(&format!("{}/_matrix/client/v3/login", homeserver))
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|_| AuthError::Unavailable)?;
+    match response.status() {
+        200 => {
+            let login: LoginResponse = response.json().await.map_err(|_| AuthError::Unavailable)?;
+            Ok(MatrixSession {
+                homeserver,
+                user_id: login.user_id,
+                access_token: login.access_token,
+            })
+        }
+        403 => Err(AuthError::WrongPassword),
+        _ => Err(AuthError::Unavailable),
+    }
+}
+
+/// Stores the Matrix account in the `User` context and the session.
+pub fn sign_in(user: &User, session: MatrixSession) {
+    *user.username.borrow_mut() = session.user_id.clone();
+    *user.matrix.borrow_mut() = Some(session.clone());
+    Session {
+        username: session.user_id.clone(),
+        tokens: None,
+        matrix: Some(session),
+        irc: None,
+    }
+    .save();
+}
+
+/// Opens a chat connection backed by the homeserver. It is driven exactly like a WebSocket to our
+/// own server: frames sent through the service are translated into API calls, and what `/sync`
+/// reports comes back through the [`EventBus`] as frames.
+pub fn connect(session: MatrixSession) -> WebsocketService {
+    let (tx, mut rx) = mpsc::channel::<String>(1000);
+    let client = Rc::new(Client {
+        session,
+        room_id: RefCell::new(None),
+        active: Cell::new(true),
+        syncing: Cell::new(false),
+    });
+
+    spawn_local(async move {
+        let mut bus = EventBus::dispatcher();
+        while let Some(s) = rx.next().await {
+            let Ok(msg) = serde_json::from_str::<WebSocketMessage>(&s) else {
+                continue;
+            };
+            if let Err(e) = client.handle(&mut bus, msg).await {
+                log::error!("matrix: {}", e);
+            }
+            if !client.active.get() {
+                break;
+            }
+        }
+        // Every handle was dropped or we unregistered: let the sync loop wind down.
+        client.active.set(false);
+    });
+
+    WebsocketService::from_channel(tx)
+}
+
+struct Client {
+    session: MatrixSession,
+    /// The room the chat shows, once joined.
+    room_id: RefCell<Option<String>>,
+    active: Cell<bool>,
+    syncing: Cell<bool>,
+}
+
+impl Client {
+    async fn handle(
+        self: &Rc<Self>,
+        bus: &mut Dispatcher<EventBus>,
+        msg: WebSocketMessage,
+    ) -> Result<(), String> {
+        match msg.message_type {
+            MsgTypes::Register => {
+                self.enter(bus, msg.room.as_deref().unwrap_or("general"))
+                    .await
+            }
+            MsgTypes::Join => match msg.data {
+                Some(room) => self.enter(bus, &room).await,
+                None => Ok(()),
+            },
+            MsgTypes::Message if msg.attachment.is_some() => {
+                Err("attachments aren't supported on Matrix".into())
+            }
+            MsgTypes::Message => {
+                let Some(room_id) = self.room_id.borrow().clone() else {
+                    return Ok(());
+                };
+                let body = msg.data.unwrap_or_default();
+                let txn = format!("yewchat{}", js_sys::Date::now());
+                self.call::<Value>(
+                    Request::put(&self.url(&format!(
+                        "/rooms/{}/send/m.room.message/{}",
+                        encode(&room_id),
+                        txn
+                    )))
+                    .header("Content-Type", "application/json")
+                    .body(json!({ "msgtype": "m.text", "body": body }).to_string()),
+                )
+                .await
+                .map(drop)
+            }
+            MsgTypes::Unregister => {
+                self.active.set(false);
+                Ok(())
+            }
+            // Encrypted direct messages, calls and bots only exist on our own server.
+            _ => Ok(()),
+        }
+    }
+
+    /// Joins the Matrix room behind a chat room name, then loads its members and recent history.
+    async fn enter(
+        self: &Rc<Self>,
+        bus: &mut Dispatcher<EventBus>,
+        room: &str,
+    ) -> Result<(), String> {
+        let joined: JoinResponse = self
+            .call(
+                Request::post(&self.url(&format!("/join/{}", encode(&self.alias(room)))))
+                    .header("Content-Type", "application/json")
+                    .body("{}"),
+            )
+            .await?;
+        *self.room_id.borrow_mut() = Some(joined.room_id.clone());
+
+        self.send_members(bus, &joined.room_id).await?;
+        let history: Messages = self
+            .call(Request::get(&self.url(&format!(
+                "/rooms/{}/messages?dir=b&limit={}",
+                encode(&joined.room_id),
+                HISTORY_LIMIT
+            ))))
+            .await?;
+        for message in history.chunk.iter().rev().filter_map(RoomEvent::to_message) {
+            emit(bus, MsgTypes::Message, &message);
+        }
+
+        if !self.syncing.replace(true) {
+            spawn_local(self.clone().sync());
+        }
+        Ok(())
+    }
+
+    /// Long-polls `/sync`, passing on what happens in the current room until we're shut down.
+    async fn sync(self: Rc<Self>) {
+        let mut bus = EventBus::dispatcher();
+        // The first sync only tells us where "now" is; history was loaded when joining.
+        let filter = encode(r#"{"room":{"timeline":{"limit":0}}}"#);
+        let mut since = match self
+            .call::<SyncResponse>(Request::get(&self.url(&format!("/sync?filter={}", filter))))
+            .await
+        {
+            Ok(sync) => sync.next_batch,
+            Err(e) => {
+                log::error!("matrix: initial sync failed: {}", e);
+                self.syncing.set(false);
+                return;
+            }
+        };
+
+        while self.active.get() {
+            let url = self.url(&format!(
+                "/sync?since={}&timeout={}",
+                encode(&since),
+                SYNC_TIMEOUT_MS
+            ));
+            let sync = match self.call::<SyncResponse>(Request::get(&url)).await {
+                Ok(sync) => sync,
+                Err(e) => {
+                    log::error!("matrix: sync failed: {}", e);
+                    gloo_timers::future::TimeoutFuture::new(5_000).await;
+                    continue;
+                }
+            };
+            since = sync.next_batch;
+            if !self.active.get() {
+                break;
+            }
+
+            let current = self.room_id.borrow().clone();
+            let Some(room) = current.and_then(|id| sync.rooms.join.get(&id).map(|r| (id, r)))
+            else {
+                continue;
+            };
+            let (room_id, room) = room;
+            for event in &room.timeline.events {
+                if let Some(message) = event.to_message() {
+                    emit(&mut bus, MsgTypes::Message, &message);
+                }
+            }
+            if room
+                .timeline
+                .events
+                .iter()
+                .any(|e| e.kind == "m.room.member")
+            {
+                if let Err(e) = self.send_members(&mut bus, &room_id).await {
+                    log::error!("matrix: {}", e);
+                }
+            }
+        }
+        self.syncing.set(false);
+    }
+
+    async fn send_members(
+        &self,
+        bus: &mut Dispatcher<EventBus>,
+        room_id: &str,
+    ) -> Result<(), String> {
+        let members: JoinedMembers = self
+            .call(Request::get(
+                &self.url(&format!("/rooms/{}/joined_members", encode(room_id))),
+            ))
+            .await?;
+        let mut users: Vec<UserPayload> = members
+            .joined
+            .into_iter()
+            .map(|(name, member)| UserPayload {
+                avatar: member.avatar_url.and_then(|mxc| self.thumbnail(&mxc)),
+                name,
+                key: None,
+                gravatar: None,
+                away: false,
+                voice: false,
+                role: Role::default(),
+                room_role: None,
+            })
+            .collect();
+        users.sort_by(|a, b| a.name.cmp(&b.name));
+        emit(bus, MsgTypes::Users, &users);
+        Ok(())
+    }
+
+    /// Chat room names map to aliases on our homeserver: `general` is `#general:matrix.org`.
+    /// Full aliases and room IDs are used as they are.
+    fn alias(&self, room: &str) -> String {
+        if room.starts_with('#') || room.starts_with('!') {
+            return room.to_string();
+        }
+        let server = self.session.user_id.split_once(':').map_or("", |(_, s)| s);
+        format!("#{}:{}", room, server)
+    }
+
+    /// An HTTP URL for a small version of an `mxc://` avatar.
+    fn thumbnail(&self, mxc: &str) -> Option<String> {
+        let (server, media) = mxc.strip_prefix("mxc://")?.split_once('/')?;
+        Some(format!(
+            "{}/_matrix/media/v3/thumbnail/{}/{}?width=96&height=96&method=crop",
+            self.session.homeserver, server, media
+        ))
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/_matrix/client/v3{}", self.session.homeserver, path)
+    }
+
+    async fn call<T: DeserializeOwned>(&self, request: Request) -> Result<T, String> {
+        let response = request
+            .header(
+                "Authorization",
+                &format!("Bearer {}", self.session.access_token),
+            )
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.ok() {
+            return Err(format!("{} {}", response.status(), response.status_text()));
+        }
+        response.json().await.map_err(|e| e.to_string())
+    }
+}
+
+fn encode(s: &str) -> String {
+    js_sys::encode_uri_component(s).into()
+}
@@ -0,0 +1,196 @@
+//! Room messages pushed out of memory by [`crate::store::MAX_MESSAGES`], kept in IndexedDB until
+//! the user scrolls back to them. Each tab keeps its own, for the room it is in.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use js_sys::{Array, Promise};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    IdbCursorDirection, IdbCursorWithValue, IdbDatabase, IdbKeyRange, IdbObjectStore, IdbRequest,
+    IdbTransactionMode,
+};
+
+use crate::protocol::MessageData;
+use crate::time;
+
+/// How many messages scrolling back restores at a time.
+pub const PAGE: usize = 50;
+
+const DB_NAME: &str = "yewchat";
+const DB_VERSION: u32 = 1;
+const STORE: &str = "evicted";
+
+/// Entries of tabs that were closed are swept away once they are this old.
+const STALE_MS: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+
+thread_local! {
+    /// Tells this tab's entries from those of other tabs sharing the database.
+    static TAB: String = format!("{:x}", (js_sys::Math::random() * 1e12) as u64);
+    /// Orders the entries: later evictions hold later messages.
+    static SEQ: Cell<u32> = const { Cell::new(0) };
+    static DB: RefCell<Option<IdbDatabase>> = RefCell::default();
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    room: String,
+    saved: f64,
+    message: MessageData,
+}
+
+/// Stores `messages`, oldest first, which were just dropped from the room's buffer.
+pub fn evict(room: &str, messages: Vec<Rc<MessageData>>) {
+    let saved = time::now();
+    let entries: Vec<(JsValue, String)> = messages
+        .iter()
+        .map(|message| {
+            let entry = Entry {
+                room: room.to_string(),
+                saved,
+                message: (**message).clone(),
+            };
+            let seq = SEQ.with(|seq| seq.replace(seq.get() + 1));
+            (key(seq as f64), serde_json::to_string(&entry).unwrap())
+        })
+        .collect();
+    spawn_local(async move {
+        let put = async {
+            let store = object_store(IdbTransactionMode::Readwrite).await?;
+            for (key, entry) in entries {
+                store.put_with_key(&JsValue::from_str(&entry), &key)?;
+            }
+            Ok::<_, JsValue>(())
+        };
+        if let Err(e) = put.await {
+            log::error!("message cache: failed to evict: {:?}", e);
+        }
+    });
+}
+
+/// Takes back the latest `count` evicted messages of `room`, oldest first. Fewer, possibly none,
+/// if the cache has lost them.
+pub async fn restore(room: &str, count: usize) -> Result<Vec<MessageData>, String> {
+    let restore = async {
+        let store = object_store(IdbTransactionMode::Readwrite).await?;
+        let request = store
+            .open_cursor_with_range_and_direction(&this_tab()?.into(), IdbCursorDirection::Prev)?;
+        let mut restored = vec![];
+        while restored.len() < count {
+            let Ok(cursor) = done(&request).await?.dyn_into::<IdbCursorWithValue>() else {
+                break;
+            };
+            let entry = cursor
+                .value()?
+                .as_string()
+                .and_then(|s| serde_json::from_str::<Entry>(&s).ok());
+            match entry {
+                Some(entry) if entry.room == room => restored.push(entry.message),
+                // Left over from a room we have since left.
+                _ => {}
+            }
+            cursor.delete()?;
+            cursor.continue_()?;
+        }
+        restored.reverse();
+        Ok::<_, JsValue>(restored)
+    };
+    restore.await.map_err(|e| format!("{:?}", e))
+}
+
+/// Drops this tab's entries, as when it leaves the room they belong to.
+pub fn forget() {
+    spawn_local(async {
+        let clear = async {
+            let store = object_store(IdbTransactionMode::Readwrite).await?;
+            store.delete(&this_tab()?.into())?;
+            Ok::<_, JsValue>(())
+        };
+        if let Err(e) = clear.await {
+            log::error!("message cache: failed to clear: {:?}", e);
+        }
+    });
+}
+
+fn key(seq: f64) -> JsValue {
+    TAB.with(|tab| Array::of2(&JsValue::from_str(tab), &JsValue::from_f64(seq)).into())
+}
+
+fn this_tab() -> Result<IdbKeyRange, JsValue> {
+    let first = TAB.with(|tab| Array::of1(&JsValue::from_str(tab)));
+    IdbKeyRange::bound(&first, &key(f64::INFINITY))
+}
+
+async fn object_store(mode: IdbTransactionMode) -> Result<IdbObjectStore, JsValue> {
+    database()
+        .await?
+        .transaction_with_str_and_mode(STORE, mode)?
+        .object_store(STORE)
+}
+
+async fn database() -> Result<IdbDatabase, JsValue> {
+    if let Some(db) = DB.with(|db| db.borrow().clone()) {
+        return Ok(db);
+    }
+    let factory = web_sys::window()
+        .ok_or("no window")?
+        .indexed_db()?
+        .ok_or("IndexedDB is not available")?;
+    let open = factory.open_with_u32(DB_NAME, DB_VERSION)?;
+    let onupgradeneeded = {
+        let open = open.clone();
+        wasm_bindgen::closure::Closure::once(move || {
+            if let Ok(db) = open.result() {
+                let _ = db
+                    .unchecked_into::<IdbDatabase>()
+                    .create_object_store(STORE);
+            }
+        })
+    };
+    open.set_onupgradeneeded(Some(onupgradeneeded.as_ref().unchecked_ref()));
+    let db: IdbDatabase = done(&open).await?.unchecked_into();
+    DB.with(|cached| *cached.borrow_mut() = Some(db.clone()));
+    spawn_local(sweep(db.clone()));
+    Ok(db)
+}
+
+/// Deletes what tabs closed long ago left behind. Ours may be just as old, but is still wanted.
+async fn sweep(db: IdbDatabase) {
+    let sweep = async {
+        let store = db
+            .transaction_with_str_and_mode(STORE, IdbTransactionMode::Readwrite)?
+            .object_store(STORE)?;
+        let request = store.open_cursor()?;
+        let cutoff = time::now() - STALE_MS;
+        let tab = TAB.with(|tab| JsValue::from_str(tab));
+        while let Ok(cursor) = done(&request).await?.dyn_into::<IdbCursorWithValue>() {
+            let ours = Array::from(&cursor.key()?).get(0) == tab;
+            let stale = !ours
+                && cursor
+                    .value()?
+                    .as_string()
+                    .and_then(|s| serde_json::from_str::<Entry>(&s).ok())
+                    .is_none_or(|entry| entry.saved < cutoff);
+            if stale {
+                cursor.delete()?;
+            }
+            cursor.continue_()?;
+        }
+        Ok::<_, JsValue>(())
+    };
+    if let Err(e) = sweep.await {
+        log::error!("message cache: failed to sweep: {:?}", e);
+    }
+}
+
+/// Waits for the next success of `request`, which for a cursor is each step.
+async fn done(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+    JsFuture::from(promise).await?;
+    request.result()
+}
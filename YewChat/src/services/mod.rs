@@ -1,2 +1,36 @@
-pub mod websocket;
+pub mod activity;
+pub mod api;
+pub mod attachment;
+pub mod audio_devices;
+pub mod auth;
+pub mod call;
+pub mod call_log;
+pub mod clipboard;
+pub mod contacts;
+pub mod crypto;
+pub mod dnd;
 pub mod event_bus;
+pub mod export;
+pub mod frame_log;
+pub mod giphy;
+pub mod idle;
+pub mod irc;
+pub mod keywords;
+pub mod location;
+pub mod matrix;
+pub mod message_cache;
+pub mod notes;
+pub mod oauth;
+pub mod push_to_talk;
+pub mod recorder;
+pub mod ringtone;
+pub mod room_folders;
+pub mod script;
+pub mod service_worker;
+pub mod session;
+pub mod spam;
+pub mod system_lines;
+pub mod terms;
+pub mod voice_activity;
+pub mod websocket;
+pub mod welcome;
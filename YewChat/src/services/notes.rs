@@ -0,0 +1,43 @@
+//! Notes to self: a conversation with ourselves that never leaves this browser.
+
+use gloo_storage::{LocalStorage, Storage};
+
+use crate::protocol::MessageData;
+use crate::time;
+
+/// How many notes are kept, forgetting the oldest.
+const MAX_NOTES: usize = 500;
+
+fn storage_key(username: &str) -> String {
+    format!("yewchat.notes.{}", username)
+}
+
+/// The notes of `username`, oldest first.
+pub fn load(username: &str) -> Vec<MessageData> {
+    LocalStorage::get(storage_key(username)).unwrap_or_default()
+}
+
+/// Writes down `text` as a note of `username`, and returns it to be shown.
+pub fn add(username: &str, text: String) -> MessageData {
+    let note = MessageData {
+        id: None,
+        from: username.to_string(),
+        message: text,
+        attachment: None,
+        time: Some(time::now()),
+        bot: None,
+        to: None,
+        announcement: false,
+        pinned_until: None,
+        system: false,
+    };
+    let mut notes = load(username);
+    notes.push(note.clone());
+    if notes.len() > MAX_NOTES {
+        notes.drain(..notes.len() - MAX_NOTES);
+    }
+    if let Err(e) = LocalStorage::set(storage_key(username), &notes) {
+        log::error!("failed to save notes: {:?}", e);
+    }
+    note
+}
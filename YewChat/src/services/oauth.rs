@@ -0,0 +1,113 @@
+use gloo_storage::{SessionStorage, Storage};
+use js_sys::encode_uri_component;
+
+const STATE_KEY: &str = "yewchat.oauth.state";
+
+/// Identity providers we can sign in with. Each one is only offered when its client id was
+/// provided at build time, e.g. `YEWCHAT_GITHUB_CLIENT_ID=... npm run build`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Provider {
+    GitHub,
+    Google,
+}
+
+impl Provider {
+    pub const ALL: [Provider; 2] = [Provider::GitHub, Provider::Google];
+
+    /// The id used in the callback route and on the wire.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Provider::GitHub => "github",
+            Provider::Google => "google",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.id() == id)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Provider::GitHub => "GitHub",
+            Provider::Google => "Google",
+        }
+    }
+
+    fn client_id(&self) -> Option<&'static str> {
+        match self {
+            Provider::GitHub => option_env!("YEWCHAT_GITHUB_CLIENT_ID"),
+            Provider::Google => option_env!("YEWCHAT_GOOGLE_CLIENT_ID"),
+        }
+    }
+
+    pub fn configured() -> Vec<Provider> {
+        Self::ALL
+            .into_iter()
+            .filter(|p| p.client_id().is_some())
+            .collect()
+    }
+
+    /// Where the provider sends the browser back to; must match the app registration.
+    pub fn redirect_uri(&self) -> String {
+        let origin = window().location().origin().unwrap_or_default();
+        format!("{}/oauth/{}", origin, self.id())
+    }
+
+    fn authorize_url(&self, client_id: &str, state: &str) -> String {
+        let (base, scope, extra) = match self {
+            Provider::GitHub => ("https://github.com/login/oauth/authorize", "read:user", ""),
+            Provider::Google => (
+                "https://accounts.google.com/o/oauth2/v2/auth",
+                "openid profile",
+                "&response_type=code",
+            ),
+        };
+        format!(
+            "{}?client_id={}&redirect_uri={}&scope={}&state={}{}",
+            base,
+            encode_uri_component(client_id),
+            encode_uri_component(&self.redirect_uri()),
+            encode_uri_component(scope),
+            encode_uri_component(state),
+            extra
+        )
+    }
+
+    /// Leaves the app for the provider's consent screen.
+    pub fn begin(&self) {
+        let Some(client_id) = self.client_id() else {
+            log::error!("oauth: {} is not configured", self.label());
+            return;
+        };
+        let state = random_state();
+        if let Err(e) = SessionStorage::set(STATE_KEY, &state) {
+            log::error!("oauth: failed to store state: {:?}", e);
+            return;
+        }
+        if let Err(e) = window()
+            .location()
+            .assign(&self.authorize_url(client_id, &state))
+        {
+            log::error!("oauth: failed to redirect: {:?}", e);
+        }
+    }
+}
+
+/// Checks the `state` echoed back by the provider against the one we sent, consuming it.
+pub fn verify_state(state: &str) -> bool {
+    let expected: Option<String> = SessionStorage::get(STATE_KEY).ok();
+    SessionStorage::delete(STATE_KEY);
+    expected.is_some_and(|expected| expected == state)
+}
+
+fn window() -> web_sys::Window {
+    web_sys::window().expect("no global `window`")
+}
+
+fn random_state() -> String {
+    let mut bytes = [0u8; 16];
+    if let Ok(crypto) = window().crypto() {
+        let _ = crypto.get_random_values_with_u8_array(&mut bytes);
+    }
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
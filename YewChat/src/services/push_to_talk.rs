@@ -0,0 +1,91 @@
+//! Push-to-talk: with a key chosen, our microphone only goes out to calls and voice channels while
+//! that key is held. The key is kept in this browser, for each account.
+
+use gloo_storage::{LocalStorage, Storage};
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlElement, KeyboardEvent};
+
+fn storage_key(username: &str) -> String {
+    format!("yewchat.push_to_talk.{}", username)
+}
+
+/// The key `username` holds to talk, as a `KeyboardEvent.code`, or none to always be heard.
+pub fn key(username: &str) -> Option<String> {
+    LocalStorage::get(storage_key(username)).ok()
+}
+
+pub fn set_key(username: &str, key: Option<&str>) {
+    let Some(key) = key else {
+        LocalStorage::delete(storage_key(username));
+        return;
+    };
+    if let Err(e) = LocalStorage::set(storage_key(username), key) {
+        log::error!("failed to save the push-to-talk key: {:?}", e);
+    }
+}
+
+/// How the key of `code` reads: a letter or digit alone, or its name in words.
+pub fn label(code: &str) -> String {
+    if let Some(single) = code
+        .strip_prefix("Key")
+        .or_else(|| code.strip_prefix("Digit"))
+    {
+        return single.to_string();
+    }
+    let mut words = String::new();
+    for c in code.chars() {
+        if c.is_ascii_uppercase() && !words.is_empty() {
+            words.push(' ');
+        }
+        words.push(c);
+    }
+    words
+}
+
+/// Whether the key of `code` types something into a text field.
+fn types(code: &str) -> bool {
+    code.starts_with("Key")
+        || code.starts_with("Digit")
+        || matches!(
+            code,
+            "Space"
+                | "Minus"
+                | "Equal"
+                | "BracketLeft"
+                | "BracketRight"
+                | "Backslash"
+                | "Semicolon"
+                | "Quote"
+                | "Backquote"
+                | "Comma"
+                | "Period"
+                | "Slash"
+        )
+}
+
+/// Whether `e` is someone typing rather than talking: a key that types, pressed in a text field.
+pub fn typing(e: &KeyboardEvent) -> bool {
+    let Some(target) = e.target().and_then(|t| t.dyn_into::<HtmlElement>().ok()) else {
+        return false;
+    };
+    let field = matches!(target.tag_name().as_str(), "INPUT" | "TEXTAREA" | "SELECT")
+        || target.is_content_editable();
+    field && types(&e.code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn keys_read_as_on_the_keyboard() {
+        assert_eq!(label("KeyV"), "V");
+        assert_eq!(label("Digit5"), "5");
+        assert_eq!(label("Space"), "Space");
+        assert_eq!(label("ControlLeft"), "Control Left");
+        assert_eq!(label("F13"), "F13");
+        assert!(types("KeyV") && types("Space") && types("Backquote"));
+        assert!(!types("ControlLeft") && !types("F13") && !types("CapsLock"));
+    }
+}
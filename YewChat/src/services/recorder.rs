@@ -0,0 +1,167 @@
+//! Recording a call or voice channel in this browser. Everything heard in it, our own voice
+//! included, is mixed into one audio stream through an audio context and recorded from there; once
+//! the recording stops, it is saved as a download. Nothing goes through the server but the word
+//! that we are recording, which the others are shown.
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+use js_sys::{Array, Date};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    AudioContext, Blob, BlobEvent, BlobPropertyBag, HtmlAnchorElement, MediaRecorder, MediaStream,
+    MediaStreamAudioDestinationNode, Url,
+};
+
+/// The file extension for recordings of `mime`, which is up to the browser.
+fn extension(mime: &str) -> &'static str {
+    match mime.split(';').next().unwrap_or_default() {
+        "audio/ogg" => "ogg",
+        "audio/mp4" => "m4a",
+        _ => "webm",
+    }
+}
+
+/// What a recording of the call with `subject` is saved as, started `at`.
+fn file_name(subject: &str, at: &Date, mime: &str) -> String {
+    let subject: String = subject
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!(
+        "yewchat-{}-{}-{:02}-{:02}-{:02}{:02}.{}",
+        subject,
+        at.get_full_year(),
+        at.get_month() + 1,
+        at.get_date(),
+        at.get_hours(),
+        at.get_minutes(),
+        extension(mime)
+    )
+}
+
+fn save(blob: &Blob, filename: &str) -> Result<(), JsValue> {
+    let url = Url::create_object_url_with_blob(blob)?;
+    let document = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or("no document")?;
+    let link: HtmlAnchorElement = document.create_element("a")?.unchecked_into();
+    link.set_href(&url);
+    link.set_download(filename);
+    link.click();
+    Url::revoke_object_url(&url)
+}
+
+/// A recording in progress, saved as a download once dropped.
+pub struct Recorder {
+    context: AudioContext,
+    destination: MediaStreamAudioDestinationNode,
+    recorder: MediaRecorder,
+    /// The ids of the streams mixed in so far.
+    mixed: RefCell<BTreeSet<String>>,
+}
+
+impl Recorder {
+    /// Starts recording `streams`, and any [`add`](Self::add)ed later, as the call with
+    /// `subject`.
+    pub fn start<'a>(
+        subject: &str,
+        streams: impl IntoIterator<Item = &'a MediaStream>,
+    ) -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+        let _ = context.resume();
+        let destination = context.create_media_stream_destination()?;
+        let recorder = MediaRecorder::new_with_media_stream(&destination.stream())?;
+        let this = Self {
+            context,
+            destination,
+            recorder,
+            mixed: RefCell::default(),
+        };
+        for stream in streams {
+            this.add(stream);
+        }
+
+        let chunks = Array::new();
+        let on_data = {
+            let chunks = chunks.clone();
+            Closure::wrap(Box::new(move |e: BlobEvent| {
+                if let Some(data) = e.data() {
+                    chunks.push(&data);
+                }
+            }) as Box<dyn FnMut(BlobEvent)>)
+        };
+        this.recorder
+            .set_ondataavailable(Some(on_data.as_ref().unchecked_ref()));
+        // The last of the data arrives after the recorder is told to stop, and us long gone.
+        let on_stop = {
+            let recorder = this.recorder.clone();
+            let context = this.context.clone();
+            let started = Date::new_0();
+            let subject = subject.to_string();
+            Closure::once_into_js(move || {
+                recorder.set_ondataavailable(None);
+                drop(on_data);
+                let _ = context.close();
+                let mime = recorder.mime_type();
+                let mut options = BlobPropertyBag::new();
+                options.type_(&mime);
+                let saved = Blob::new_with_blob_sequence_and_options(&chunks, &options)
+                    .and_then(|blob| save(&blob, &file_name(&subject, &started, &mime)));
+                if let Err(e) = saved {
+                    log::error!("recording: not saved: {:?}", e);
+                }
+            })
+        };
+        this.recorder.set_onstop(Some(on_stop.unchecked_ref()));
+        this.recorder.start()?;
+        Ok(this)
+    }
+
+    /// Mixes `stream` into the recording, unless it already is or has no sound.
+    pub fn add(&self, stream: &MediaStream) {
+        if stream.get_audio_tracks().length() == 0 || !self.mixed.borrow_mut().insert(stream.id()) {
+            return;
+        }
+        let mixed = self
+            .context
+            .create_media_stream_source(stream)
+            .and_then(|source| source.connect_with_audio_node(&self.destination));
+        if let Err(e) = mixed {
+            log::warn!("recording: can't mix in a stream: {:?}", e);
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.recorder.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn recordings_are_named_after_the_call() {
+        let at = Date::new_with_year_month_day_hr_min(2026, 9, 15, 9, 5);
+        assert_eq!(
+            file_name("bob", &at, "audio/webm;codecs=opus"),
+            "yewchat-bob-2026-10-15-0905.webm"
+        );
+        assert_eq!(
+            file_name("voice #general", &at, "audio/mp4"),
+            "yewchat-voice--general-2026-10-15-0905.m4a"
+        );
+        assert_eq!(extension(""), "webm");
+    }
+}
@@ -0,0 +1,55 @@
+//! The ring of an incoming call, made up on the spot so that there is no sound file to ship: two
+//! tones together, on for a moment and off for longer, like a telephone.
+
+use gloo_timers::callback::Interval;
+use wasm_bindgen::JsValue;
+use web_sys::AudioContext;
+
+/// The tones, in hertz.
+const TONES: [f32; 2] = [440.0, 480.0];
+/// How long each ring sounds, in seconds.
+const RING_SECONDS: f64 = 1.5;
+/// From one ring to the next, in milliseconds.
+const CYCLE_MS: u32 = 4_000;
+const VOLUME: f32 = 0.1;
+
+/// A phone ringing until dropped. Browsers may keep it silent until the page has been clicked.
+pub struct Ringtone {
+    context: AudioContext,
+    _cycle: Interval,
+}
+
+impl Ringtone {
+    pub fn start() -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+        let _ = context.resume();
+        let gain = context.create_gain()?;
+        gain.gain().set_value(0.0);
+        gain.connect_with_audio_node(&context.destination())?;
+        for tone in TONES {
+            let oscillator = context.create_oscillator()?;
+            oscillator.frequency().set_value(tone);
+            oscillator.connect_with_audio_node(&gain)?;
+            oscillator.start()?;
+        }
+        let ring = {
+            let context = context.clone();
+            move || {
+                let now = context.current_time();
+                let _ = gain.gain().set_value_at_time(VOLUME, now);
+                let _ = gain.gain().set_value_at_time(0.0, now + RING_SECONDS);
+            }
+        };
+        ring();
+        Ok(Self {
+            context,
+            _cycle: Interval::new(CYCLE_MS, ring),
+        })
+    }
+}
+
+impl Drop for Ringtone {
+    fn drop(&mut self) {
+        let _ = self.context.close();
+    }
+}
@@ -0,0 +1,440 @@
+//! The rooms we have been in, listed in the sidebar and sorted into folders of our own, like
+//! "Work" or "Friends", with our favorites above them all. Kept per user in this browser, along
+//! with how far we read each room.
+
+use std::collections::HashMap;
+
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::ListedRoom;
+
+/// Longest a folder's name can be.
+pub const MAX_FOLDER_NAME_LEN: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Folder {
+    pub name: String,
+    /// In the order they were put in.
+    pub rooms: Vec<String>,
+    /// Folded away in the sidebar.
+    #[serde(default)]
+    pub collapsed: bool,
+}
+
+/// A room or folder being dragged about the sidebar.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Dragged {
+    Room(String),
+    Folder(String),
+}
+
+/// Where something dragged about the sidebar can be dropped.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Place {
+    /// Just before this room, in its folder if it is in one. Only rooms go there.
+    Room(String),
+    /// A room goes at the end of this folder's rooms; a folder, just before it.
+    Folder(String),
+    /// After the rooms in no folder, for a room, or after the other folders, for a folder.
+    End,
+}
+
+impl Dragged {
+    /// Whether dropping it at `place` would do anything.
+    pub fn fits(&self, place: &Place) -> bool {
+        match (self, place) {
+            (Dragged::Room(room), Place::Room(other)) => room != other,
+            (Dragged::Folder(folder), Place::Folder(other)) => folder != other,
+            (Dragged::Folder(_), Place::Room(_)) => false,
+            _ => true,
+        }
+    }
+}
+
+/// Takes the item `moved` picks out of `list` and puts it back just before the one `before`
+/// picks, or at the end when there is none.
+fn place_before<T>(list: &mut Vec<T>, moved: impl Fn(&T) -> bool, before: impl Fn(&T) -> bool) {
+    let Some(from) = list.iter().position(moved) else {
+        return;
+    };
+    let item = list.remove(from);
+    let to = list.iter().position(before).unwrap_or(list.len());
+    list.insert(to, item);
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomFolders {
+    /// Every room we have been in and not forgotten, whether or not it is in a folder, in the
+    /// order we put them in: that of the rooms in no folder.
+    pub rooms: Vec<String>,
+    pub folders: Vec<Folder>,
+    /// Rooms starred to the top of the list, in the order they were starred. They keep their
+    /// place in their folder for when they are unstarred.
+    pub favorites: Vec<String>,
+    /// Rooms that shouldn't draw us back: their unread messages show as a dot rather than a count,
+    /// left out of their folder's, and, like while Do Not Disturb is on, they are to make no sound
+    /// or notification. We stay in them all the same.
+    pub muted: Vec<String>,
+    /// How many messages had been posted in each room when we last saw it, as the room directory
+    /// counts them.
+    pub seen: HashMap<String, u64>,
+}
+
+impl RoomFolders {
+    fn storage_key(username: &str) -> String {
+        format!("yewchat.folders.{}", username)
+    }
+
+    pub fn load(username: &str) -> Self {
+        LocalStorage::get(Self::storage_key(username)).unwrap_or_default()
+    }
+
+    pub fn save(&self, username: &str) {
+        if let Err(e) = LocalStorage::set(Self::storage_key(username), self) {
+            log::error!("failed to save room folders: {:?}", e);
+        }
+    }
+
+    /// Remembers that we are in `room`, returning whether it is new to us.
+    pub fn visit(&mut self, room: &str) -> bool {
+        if self.rooms.iter().any(|r| r == room) {
+            return false;
+        }
+        self.rooms.push(room.to_string());
+        true
+    }
+
+    /// Takes `room` off the list, and out of its folder.
+    pub fn forget(&mut self, room: &str) {
+        self.rooms.retain(|r| r != room);
+        self.favorites.retain(|r| r != room);
+        self.muted.retain(|r| r != room);
+        self.seen.remove(room);
+        for folder in &mut self.folders {
+            folder.rooms.retain(|r| r != room);
+        }
+    }
+
+    pub fn is_favorite(&self, room: &str) -> bool {
+        self.favorites.iter().any(|r| r == room)
+    }
+
+    /// Stars `room`, or unstars it if it was starred.
+    pub fn toggle_favorite(&mut self, room: &str) {
+        if self.is_favorite(room) {
+            self.favorites.retain(|r| r != room);
+        } else if self.rooms.iter().any(|r| r == room) {
+            self.favorites.push(room.to_string());
+        }
+    }
+
+    pub fn is_muted(&self, room: &str) -> bool {
+        self.muted.iter().any(|r| r == room)
+    }
+
+    /// Mutes `room`, or unmutes it if it was muted.
+    pub fn toggle_muted(&mut self, room: &str) {
+        if self.is_muted(room) {
+            self.muted.retain(|r| r != room);
+        } else if self.rooms.iter().any(|r| r == room) {
+            self.muted.push(room.to_string());
+        }
+    }
+
+    /// Adds an empty folder, unless there is one of that name already or the name is blank.
+    pub fn add_folder(&mut self, name: &str) -> bool {
+        let name = name.trim();
+        if name.is_empty()
+            || name.chars().count() > MAX_FOLDER_NAME_LEN
+            || self.folders.iter().any(|f| f.name == name)
+        {
+            return false;
+        }
+        self.folders.push(Folder {
+            name: name.to_string(),
+            rooms: vec![],
+            collapsed: false,
+        });
+        true
+    }
+
+    /// Removes a folder. Its rooms stay on the list, outside any folder.
+    pub fn remove_folder(&mut self, name: &str) {
+        self.folders.retain(|f| f.name != name);
+    }
+
+    pub fn toggle_folder(&mut self, name: &str) {
+        if let Some(folder) = self.folders.iter_mut().find(|f| f.name == name) {
+            folder.collapsed = !folder.collapsed;
+        }
+    }
+
+    /// Puts `room` in the folder named, or takes it out of any with `None`. A room is in one
+    /// folder at most.
+    pub fn file(&mut self, room: &str, folder: Option<&str>) {
+        for f in &mut self.folders {
+            f.rooms.retain(|r| r != room);
+        }
+        if let Some(f) = folder.and_then(|name| self.folders.iter_mut().find(|f| f.name == name)) {
+            f.rooms.push(room.to_string());
+        }
+    }
+
+    /// Moves what was dragged to where it was dropped, as [`Place`] says.
+    pub fn arrange(&mut self, dragged: &Dragged, place: &Place) {
+        if !dragged.fits(place) {
+            return;
+        }
+        match (dragged, place) {
+            (Dragged::Room(room), _) if !self.rooms.contains(room) => {}
+            (Dragged::Room(room), Place::Room(before)) => {
+                let folder = self.folder_of(before).map(str::to_string);
+                self.file(room, folder.as_deref());
+                place_before(&mut self.rooms, |r| r == room, |r| r == before);
+                if let Some(f) =
+                    folder.and_then(|name| self.folders.iter_mut().find(|f| f.name == name))
+                {
+                    place_before(&mut f.rooms, |r| r == room, |r| r == before);
+                }
+            }
+            (Dragged::Room(room), Place::Folder(name)) => self.file(room, Some(name)),
+            (Dragged::Room(room), Place::End) => {
+                self.file(room, None);
+                place_before(&mut self.rooms, |r| r == room, |_| false);
+            }
+            (Dragged::Folder(name), Place::Folder(before)) => place_before(
+                &mut self.folders,
+                |f| f.name == *name,
+                |f| f.name == *before,
+            ),
+            (Dragged::Folder(name), Place::End) => {
+                place_before(&mut self.folders, |f| f.name == *name, |_| false)
+            }
+            (Dragged::Folder(_), Place::Room(_)) => {}
+        }
+    }
+
+    pub fn folder_of(&self, room: &str) -> Option<&str> {
+        self.folders
+            .iter()
+            .find(|f| f.rooms.iter().any(|r| r == room))
+            .map(|f| f.name.as_str())
+    }
+
+    /// The rooms of the folder named, in order, save the favorites, which are shown above.
+    pub fn in_folder<'a>(&'a self, folder: &'a Folder) -> impl Iterator<Item = &'a String> {
+        folder.rooms.iter().filter(|r| !self.is_favorite(r))
+    }
+
+    /// The rooms of the list in no folder and not favorites, in order.
+    pub fn unfiled(&self) -> impl Iterator<Item = &String> {
+        self.rooms
+            .iter()
+            .filter(|r| self.folder_of(r).is_none() && !self.is_favorite(r))
+    }
+
+    /// Catches up with the room directory: `current` is read up to now, and rooms we haven't
+    /// counted yet start from now. Counts go back to zero when the server restarts, and so do
+    /// ours. Returns whether anything changed.
+    pub fn update_seen(&mut self, listed: &[ListedRoom], current: &str) -> bool {
+        let mut changed = false;
+        for room in listed.iter().filter(|l| self.rooms.contains(&l.name)) {
+            let seen = self.seen.get(&room.name).copied();
+            let caught_up = seen.is_none_or(|seen| room.name == current || seen > room.posted);
+            if caught_up && seen != Some(room.posted) {
+                self.seen.insert(room.name.clone(), room.posted);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// How many messages were posted in `room` since we last saw it, as far as the room directory
+    /// says. Invite-only rooms aren't listed, so they never have any.
+    pub fn unread(&self, room: &str, listed: &[ListedRoom]) -> u64 {
+        match (self.seen.get(room), listed.iter().find(|l| l.name == room)) {
+            (Some(seen), Some(listed)) => listed.posted.saturating_sub(*seen),
+            _ => 0,
+        }
+    }
+
+    /// Messages unread in the rooms shown in the folder named, all told, save those of muted
+    /// rooms.
+    pub fn folder_unread(&self, name: &str, listed: &[ListedRoom]) -> u64 {
+        self.folders.iter().find(|f| f.name == name).map_or(0, |f| {
+            self.in_folder(f)
+                .filter(|r| !self.is_muted(r))
+                .map(|r| self.unread(r, listed))
+                .sum()
+        })
+    }
+
+    /// Whether a muted room shown in the folder named has messages we haven't seen.
+    pub fn folder_unread_muted(&self, name: &str, listed: &[ListedRoom]) -> bool {
+        self.folders
+            .iter()
+            .find(|f| f.name == name)
+            .is_some_and(|f| {
+                self.in_folder(f)
+                    .any(|r| self.is_muted(r) && self.unread(r, listed) > 0)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    fn listed(name: &str, posted: u64) -> ListedRoom {
+        ListedRoom {
+            name: name.into(),
+            topic: None,
+            icon: None,
+            members: 0,
+            posted,
+            password_protected: false,
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn folders_add_up_their_rooms() {
+        let mut folders = RoomFolders::default();
+        assert!(folders.visit("general"));
+        assert!(folders.visit("deploys"));
+        assert!(folders.visit("standup"));
+        assert!(!folders.visit("general"));
+        assert!(folders.add_folder(" Work "));
+        assert!(!folders.add_folder("Work"));
+        assert!(!folders.add_folder("  "));
+        folders.file("deploys", Some("Work"));
+        folders.file("standup", Some("Work"));
+        assert_eq!(folders.folder_of("deploys"), Some("Work"));
+        assert_eq!(folders.unfiled().collect::<Vec<_>>(), ["general"]);
+
+        // Counting starts when a room is first listed.
+        let before = [
+            listed("general", 3),
+            listed("deploys", 10),
+            listed("standup", 0),
+        ];
+        assert!(folders.update_seen(&before, "general"));
+        let after = [
+            listed("general", 5),
+            listed("deploys", 12),
+            listed("standup", 1),
+        ];
+        assert_eq!(folders.unread("deploys", &after), 2);
+        assert_eq!(folders.folder_unread("Work", &after), 3);
+        // The room we are in is read as it goes.
+        assert!(folders.update_seen(&after, "general"));
+        assert_eq!(folders.unread("general", &after), 0);
+        // A restarted server counts from zero again.
+        folders.update_seen(&[listed("deploys", 1)], "general");
+        assert_eq!(folders.unread("deploys", &[listed("deploys", 1)]), 0);
+
+        folders.remove_folder("Work");
+        assert_eq!(folders.unfiled().count(), 3);
+        folders.forget("standup");
+        assert_eq!(folders.rooms, ["general", "deploys"]);
+    }
+
+    #[wasm_bindgen_test]
+    fn dragging_rearranges() {
+        let room = |name: &str| Dragged::Room(name.into());
+        let mut folders = RoomFolders::default();
+        for name in ["general", "deploys", "standup", "games"] {
+            folders.visit(name);
+        }
+        folders.add_folder("Work");
+        folders.add_folder("Friends");
+
+        folders.arrange(&room("games"), &Place::Room("general".into()));
+        assert_eq!(folders.rooms, ["games", "general", "deploys", "standup"]);
+        folders.arrange(&room("standup"), &Place::Folder("Work".into()));
+        // Next to a room in a folder goes in that folder too.
+        folders.arrange(&room("deploys"), &Place::Room("standup".into()));
+        assert_eq!(folders.folders[0].rooms, ["deploys", "standup"]);
+        folders.arrange(&room("deploys"), &Place::End);
+        assert_eq!(folders.folders[0].rooms, ["standup"]);
+        assert_eq!(
+            folders.unfiled().collect::<Vec<_>>(),
+            ["games", "general", "deploys"]
+        );
+        // Rooms we aren't keeping, and folders dropped on rooms, go nowhere.
+        folders.arrange(&room("elsewhere"), &Place::Folder("Work".into()));
+        assert_eq!(folders.folders[0].rooms, ["standup"]);
+        let before = folders.clone();
+        folders.arrange(
+            &Dragged::Folder("Work".into()),
+            &Place::Room("games".into()),
+        );
+        assert_eq!(folders, before);
+
+        folders.arrange(
+            &Dragged::Folder("Friends".into()),
+            &Place::Folder("Work".into()),
+        );
+        assert_eq!(folders.folders[0].name, "Friends");
+        folders.arrange(&Dragged::Folder("Friends".into()), &Place::End);
+        assert_eq!(folders.folders[1].name, "Friends");
+    }
+
+    #[wasm_bindgen_test]
+    fn favorites_leave_their_folder_for_a_while() {
+        let mut folders = RoomFolders::default();
+        for name in ["general", "deploys", "standup"] {
+            folders.visit(name);
+        }
+        folders.add_folder("Work");
+        folders.file("deploys", Some("Work"));
+        folders.file("standup", Some("Work"));
+        folders.update_seen(&[listed("deploys", 0), listed("standup", 0)], "general");
+
+        folders.toggle_favorite("standup");
+        folders.toggle_favorite("general");
+        folders.toggle_favorite("elsewhere");
+        assert_eq!(folders.favorites, ["standup", "general"]);
+        assert_eq!(folders.unfiled().count(), 0);
+        let work = &folders.folders[0];
+        assert_eq!(folders.in_folder(work).collect::<Vec<_>>(), ["deploys"]);
+        // Counted where they are shown.
+        let listed = [listed("deploys", 1), listed("standup", 2)];
+        assert_eq!(folders.folder_unread("Work", &listed), 1);
+
+        folders.toggle_favorite("standup");
+        let work = &folders.folders[0];
+        assert_eq!(
+            folders.in_folder(work).collect::<Vec<_>>(),
+            ["deploys", "standup"]
+        );
+        folders.forget("general");
+        assert!(folders.favorites.is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn muted_rooms_only_show_a_dot() {
+        let mut folders = RoomFolders::default();
+        for name in ["deploys", "standup"] {
+            folders.visit(name);
+        }
+        folders.add_folder("Work");
+        folders.file("deploys", Some("Work"));
+        folders.file("standup", Some("Work"));
+        folders.update_seen(&[listed("deploys", 0), listed("standup", 0)], "general");
+
+        folders.toggle_muted("standup");
+        folders.toggle_muted("elsewhere");
+        assert_eq!(folders.muted, ["standup"]);
+        let after = [listed("deploys", 1), listed("standup", 4)];
+        assert_eq!(folders.unread("standup", &after), 4);
+        assert_eq!(folders.folder_unread("Work", &after), 1);
+        assert!(folders.folder_unread_muted("Work", &after));
+
+        folders.toggle_muted("standup");
+        assert_eq!(folders.folder_unread("Work", &after), 5);
+        assert!(!folders.folder_unread_muted("Work", &after));
+    }
+}
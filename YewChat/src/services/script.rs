@@ -0,0 +1,75 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use js_sys::{Function, Promise};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::JsFuture;
+
+thread_local! {
+    /// Settles once each script is loaded, or failed to load; everyone waiting on it shares it.
+    static LOADED: RefCell<HashMap<String, Promise>> = RefCell::default();
+}
+
+/// A file of a library on a CDN, with the Subresource Integrity hash it has to match, so the
+/// browser refuses a copy that was changed there rather than running it.
+pub struct Asset<'a> {
+    pub url: &'a str,
+    pub integrity: Option<&'a str>,
+}
+
+/// Waits for a library from a CDN, adding its script, and its style sheet if it has one, to the
+/// page the first time it is asked for. The libraries are only needed by some messages, so they
+/// aren't loaded with the app.
+pub async fn load(src: &Asset<'_>, stylesheet: Option<&Asset<'_>>) -> Result<(), JsValue> {
+    let loaded = LOADED.with(|loaded| {
+        loaded
+            .borrow_mut()
+            .entry(src.url.to_string())
+            .or_insert_with(|| {
+                Promise::new(&mut |resolve, reject| {
+                    if let Err(e) = add_to_page(src, stylesheet, resolve, &reject) {
+                        let _ = reject.call1(&JsValue::NULL, &e);
+                    }
+                })
+            })
+            .clone()
+    });
+    JsFuture::from(loaded).await.map(|_| ())
+}
+
+fn add_to_page(
+    src: &Asset,
+    stylesheet: Option<&Asset>,
+    on_load: Function,
+    on_error: &Function,
+) -> Result<(), JsValue> {
+    let document = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or("no document")?;
+    let body = document.body().ok_or("no body")?;
+
+    if let Some(href) = stylesheet {
+        let style = document.create_element("link")?;
+        style.set_attribute("rel", "stylesheet")?;
+        style.set_attribute("href", href.url)?;
+        pin(&style, href)?;
+        body.append_child(&style)?;
+    }
+
+    let script = document.create_element("script")?;
+    script.set_attribute("src", src.url)?;
+    pin(&script, src)?;
+    script.add_event_listener_with_callback("load", &on_load)?;
+    script.add_event_listener_with_callback("error", on_error)?;
+    body.append_child(&script)?;
+    Ok(())
+}
+
+/// Fetched without cookies, which the browser needs to check the hash of a file from elsewhere.
+fn pin(element: &web_sys::Element, asset: &Asset) -> Result<(), JsValue> {
+    element.set_attribute("crossorigin", "anonymous")?;
+    if let Some(integrity) = asset.integrity {
+        element.set_attribute("integrity", integrity)?;
+    }
+    Ok(())
+}
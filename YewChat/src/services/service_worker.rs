@@ -0,0 +1,109 @@
+use gloo_timers::callback::Interval;
+use js_sys::{Object, Reflect};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    ServiceWorker, ServiceWorkerContainer, ServiceWorkerRegistration, ServiceWorkerState,
+};
+use yew::Callback;
+
+const SCRIPT_URL: &str = "/sw.js";
+/// How often an open tab asks whether a new build has been deployed.
+const UPDATE_CHECK_MS: u32 = 60 * 60 * 1000;
+
+/// A new build, installed and waiting for the page to let it take over.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingUpdate(ServiceWorker);
+
+impl PendingUpdate {
+    /// Activates the new build and reloads the page once it controls it.
+    pub fn apply(&self) {
+        let Some(container) = container() else {
+            return;
+        };
+        let reload = Closure::once_into_js(|| {
+            if let Some(window) = web_sys::window() {
+                let _ = window.location().reload();
+            }
+        });
+        container.set_oncontrollerchange(Some(reload.unchecked_ref()));
+
+        let message = Object::new();
+        let _ = Reflect::set(&message, &"type".into(), &"SKIP_WAITING".into());
+        if let Err(e) = self.0.post_message(&message) {
+            log::error!("failed to activate update: {:?}", e);
+        }
+    }
+}
+
+/// Registers the worker that caches the app for offline startup, reporting through
+/// `on_update` whenever a newly deployed build is ready.
+pub fn register(on_update: Callback<PendingUpdate>) {
+    // Debug builds change constantly; a cache in front of them only gets in the way.
+    if cfg!(debug_assertions) {
+        return;
+    }
+    let Some(container) = container() else {
+        return;
+    };
+    let promise = container.register(SCRIPT_URL);
+    spawn_local(async move {
+        let registration: ServiceWorkerRegistration = match JsFuture::from(promise).await {
+            Ok(registration) => registration.unchecked_into(),
+            Err(e) => {
+                log::error!("failed to register service worker: {:?}", e);
+                return;
+            }
+        };
+
+        // A build deployed while no tab was open may already be waiting.
+        if let Some(waiting) = registration.waiting() {
+            if container.controller().is_some() {
+                on_update.emit(PendingUpdate(waiting));
+            }
+        }
+
+        let onupdatefound = {
+            let registration = registration.clone();
+            let container = container.clone();
+            Closure::wrap(Box::new(move || {
+                let Some(installing) = registration.installing() else {
+                    return;
+                };
+                let worker = installing.clone();
+                let on_update = on_update.clone();
+                let container = container.clone();
+                let onstatechange = Closure::wrap(Box::new(move || {
+                    // Without a controller this is the first install, not an update.
+                    let has_controller = container.controller().is_some();
+                    if worker.state() == ServiceWorkerState::Installed && has_controller {
+                        on_update.emit(PendingUpdate(worker.clone()));
+                    }
+                }) as Box<dyn FnMut()>);
+                installing.set_onstatechange(Some(onstatechange.as_ref().unchecked_ref()));
+                onstatechange.forget();
+            }) as Box<dyn FnMut()>)
+        };
+        registration.set_onupdatefound(Some(onupdatefound.as_ref().unchecked_ref()));
+        // The registration outlives every component, so its listener lives as long as the page.
+        onupdatefound.forget();
+
+        Interval::new(UPDATE_CHECK_MS, move || {
+            if let Ok(promise) = registration.update() {
+                spawn_local(async move {
+                    let _ = JsFuture::from(promise).await;
+                });
+            }
+        })
+        .forget();
+    });
+}
+
+/// `navigator.serviceWorker`, which is missing outside secure contexts.
+fn container() -> Option<ServiceWorkerContainer> {
+    let navigator = web_sys::window()?.navigator();
+    Reflect::has(&navigator, &JsValue::from_str("serviceWorker"))
+        .unwrap_or(false)
+        .then(|| navigator.service_worker())
+}
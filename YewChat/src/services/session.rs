@@ -0,0 +1,79 @@
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::Tokens;
+use crate::services::irc::IrcSession;
+use crate::services::matrix::MatrixSession;
+
+const SESSION_KEY: &str = "yewchat.session";
+const REMEMBERED_KEY: &str = "yewchat.remembered";
+
+/// What we keep in localStorage so a refresh doesn't log the user out.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub username: String,
+    #[serde(default)]
+    pub tokens: Option<Tokens>,
+    /// Set instead of `tokens` when chatting through a Matrix homeserver.
+    #[serde(default)]
+    pub matrix: Option<MatrixSession>,
+    /// Set instead of `tokens` when chatting on an IRC network.
+    #[serde(default)]
+    pub irc: Option<IrcSession>,
+}
+
+impl Session {
+    pub fn load() -> Option<Self> {
+        LocalStorage::get(SESSION_KEY)
+            .ok()
+            .filter(|s: &Self| !s.username.is_empty())
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = LocalStorage::set(SESSION_KEY, self) {
+            log::error!("failed to persist session: {:?}", e);
+        }
+    }
+
+    pub fn clear() {
+        LocalStorage::delete(SESSION_KEY);
+    }
+}
+
+/// Who last signed in with "Remember me" ticked, offered on the login page so they needn't type
+/// their name again. It outlives logging out, so it holds no secrets; their avatar settings are
+/// kept under their name already.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Remembered {
+    pub username: String,
+    pub via: SignInMethod,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", tag = "kind")]
+pub enum SignInMethod {
+    /// An account on our server.
+    Account,
+    Matrix {
+        homeserver: String,
+    },
+    Irc {
+        gateway: String,
+    },
+}
+
+impl Remembered {
+    pub fn load() -> Option<Self> {
+        LocalStorage::get(REMEMBERED_KEY).ok()
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = LocalStorage::set(REMEMBERED_KEY, self) {
+            log::error!("failed to remember the user: {:?}", e);
+        }
+    }
+
+    pub fn forget() {
+        LocalStorage::delete(REMEMBERED_KEY);
+    }
+}
@@ -0,0 +1,175 @@
+//! Guesses at spam among others' messages: the same text over and over, shouting, and floods of
+//! bare links. Flagged messages are folded away rather than hidden, as the guess can be wrong. The
+//! thresholds are kept per user in this browser.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::MessageData;
+use crate::sanitize::{self, Segment};
+
+/// Repeats and link floods only count within this long of each other.
+const WINDOW_MS: f64 = 10.0 * 60_000.0;
+/// Shorter messages are never shouting, so "OK" and "LOL" pass.
+const MIN_CAPS_LETTERS: usize = 10;
+
+/// Each threshold is off at 0.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpamSettings {
+    /// How many times one sender may say the same thing; further copies are folded.
+    pub repeats: usize,
+    /// Share of capital letters, in percent, from which a message counts as shouting.
+    pub caps_percent: u32,
+    /// How many messages of nothing but links one sender may post; further ones are folded.
+    pub links: usize,
+}
+
+impl Default for SpamSettings {
+    fn default() -> Self {
+        Self {
+            repeats: 2,
+            caps_percent: 80,
+            links: 3,
+        }
+    }
+}
+
+impl SpamSettings {
+    fn storage_key(username: &str) -> String {
+        format!("yewchat.spam.{}", username)
+    }
+
+    pub fn load(username: &str) -> Self {
+        LocalStorage::get(Self::storage_key(username)).unwrap_or_default()
+    }
+
+    pub fn save(&self, username: &str) {
+        if let Err(e) = LocalStorage::set(Self::storage_key(username), self) {
+            log::error!("failed to save spam settings: {:?}", e);
+        }
+    }
+}
+
+/// Why each of `messages` looks like spam, if it does. Our own messages, announcements and system
+/// lines never do.
+pub fn flag(
+    messages: &[Rc<MessageData>],
+    us: &str,
+    settings: &SpamSettings,
+) -> Vec<Option<&'static str>> {
+    // For each sender, when they said each text, and when they posted bare links.
+    let mut said: HashMap<(&str, String), Vec<f64>> = HashMap::new();
+    let mut linked: HashMap<&str, Vec<f64>> = HashMap::new();
+    messages
+        .iter()
+        .map(|m| {
+            if m.from == us || m.announcement || m.system {
+                return None;
+            }
+            let time = m.time.unwrap_or_default();
+            let recent = |times: &mut Vec<f64>| {
+                times.retain(|&t| time - t < WINDOW_MS);
+                times.push(time);
+                times.len()
+            };
+            let text = m.message.trim().to_lowercase();
+            let repeated = !text.is_empty()
+                && recent(said.entry((m.from.as_str(), text)).or_default()) > settings.repeats;
+            let flooding = links_only(&m.message)
+                && recent(linked.entry(m.from.as_str()).or_default()) > settings.links;
+            if settings.repeats > 0 && repeated {
+                Some("repeated")
+            } else if settings.links > 0 && flooding {
+                Some("link flood")
+            } else if settings.caps_percent > 0 && shouting(&m.message, settings.caps_percent) {
+                Some("shouting")
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn shouting(text: &str, caps_percent: u32) -> bool {
+    let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+    let caps = letters.iter().filter(|c| c.is_uppercase()).count();
+    letters.len() >= MIN_CAPS_LETTERS && caps * 100 >= letters.len() * caps_percent as usize
+}
+
+/// Whether the message is one or more links and nothing else.
+fn links_only(text: &str) -> bool {
+    let segments = sanitize::linkify(text);
+    segments.iter().any(|s| matches!(s, Segment::Link { .. }))
+        && segments.iter().all(|s| match s {
+            Segment::Text(t) => t.trim().is_empty(),
+            Segment::Link { .. } => true,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    fn message(from: &str, text: &str, minute: f64) -> Rc<MessageData> {
+        Rc::new(MessageData {
+            from: from.into(),
+            time: Some(minute * 60_000.0),
+            system: false,
+            ..MessageData::system(text)
+        })
+    }
+
+    #[wasm_bindgen_test]
+    fn flags_repeats_shouting_and_link_floods() {
+        let settings = SpamSettings::default();
+        let messages = [
+            message("eve", "buy now", 0.0),
+            message("eve", "Buy now ", 1.0),
+            message("eve", "buy now", 2.0),
+            message("bob", "buy now", 2.0),
+            message("eve", "buy now", 30.0),
+            message("eve", "THIS IS THE BEST DEAL EVER", 31.0),
+            message("bob", "OK", 31.0),
+            message("eve", "https://a.example https://b.example", 32.0),
+            message("eve", "https://a.example", 32.0),
+            message("eve", "https://c.example", 33.0),
+            message("eve", "https://d.example", 33.0),
+            message("bob", "see https://a.example", 33.0),
+        ];
+        let flags = flag(&messages, "alice", &settings);
+        assert_eq!(
+            flags,
+            [
+                None,
+                None,
+                Some("repeated"),
+                None,
+                // Long enough after the others.
+                None,
+                Some("shouting"),
+                None,
+                None,
+                None,
+                None,
+                Some("link flood"),
+                None,
+            ]
+        );
+
+        // Our own messages are left alone, and 0 turns a check off.
+        assert!(flag(&messages, "eve", &settings)
+            .iter()
+            .all(Option::is_none));
+        let off = SpamSettings {
+            repeats: 0,
+            caps_percent: 0,
+            links: 0,
+        };
+        assert!(flag(&messages, "alice", &off).iter().all(Option::is_none));
+    }
+}
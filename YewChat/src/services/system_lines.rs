@@ -0,0 +1,18 @@
+//! Whether the lines this client writes about the room, like someone joining, are shown among the
+//! messages. Kept per user in this browser.
+
+use gloo_storage::{LocalStorage, Storage};
+
+fn storage_key(username: &str) -> String {
+    format!("yewchat.hide_system.{}", username)
+}
+
+pub fn hidden(username: &str) -> bool {
+    LocalStorage::get(storage_key(username)).unwrap_or(false)
+}
+
+pub fn set_hidden(username: &str, hidden: bool) {
+    if let Err(e) = LocalStorage::set(storage_key(username), hidden) {
+        log::error!("failed to save whether to hide system lines: {:?}", e);
+    }
+}
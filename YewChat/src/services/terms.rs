@@ -0,0 +1,23 @@
+//! Which version of the server's terms we accepted. Told to the server before every registration,
+//! as it doesn't keep it. Kept per user in this browser.
+
+use gloo_storage::{LocalStorage, Storage};
+
+fn storage_key(username: &str) -> String {
+    format!("yewchat.terms.{}", username)
+}
+
+pub fn accepted(username: &str) -> Option<String> {
+    LocalStorage::get(storage_key(username)).ok()
+}
+
+pub fn accept(username: &str, version: &str) {
+    if let Err(e) = LocalStorage::set(storage_key(username), version) {
+        log::error!("failed to save the accepted terms: {:?}", e);
+    }
+}
+
+/// Forgets what we accepted, so that the terms are asked for again.
+pub fn forget(username: &str) {
+    LocalStorage::delete(storage_key(username));
+}
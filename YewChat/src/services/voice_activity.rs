@@ -0,0 +1,154 @@
+//! Who in a voice channel is speaking, and how loud the microphone being tried in the settings is.
+//! Each stream goes through an analyser of one audio context, whose loudness is read a few times a
+//! second. The streams still play through their own element; the analysers only listen.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use web_sys::{AnalyserNode, AudioContext, MediaStream, MediaStreamAudioSourceNode};
+
+/// How often the levels are read, in milliseconds.
+pub const TICK_MS: u32 = 100;
+/// How loud someone has to be to count as speaking, as the root mean square of their samples.
+const SPEAKING_LEVEL: f32 = 0.02;
+/// How many ticks someone still counts as speaking once quiet, so pauses between words don't
+/// make them flicker.
+const HOLD_TICKS: u8 = 3;
+/// Samples read at each tick, about a hundredth of a second's.
+const WINDOW: u32 = 512;
+
+/// How loud `samples` are, from 0 to 1. They are bytes centred on 128, as analysers give them.
+pub fn level(samples: &[u8]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum: f32 = samples
+        .iter()
+        .map(|&s| (f32::from(s) - 128.0) / 128.0)
+        .map(|s| s * s)
+        .sum();
+    (sum / samples.len() as f32).sqrt()
+}
+
+/// Whether someone is speaking, tick after tick.
+#[derive(Default)]
+pub struct Activity {
+    /// Ticks left before they count as quiet.
+    hold: u8,
+}
+
+impl Activity {
+    /// Takes in the `level` of this tick, and says whether they are speaking.
+    pub fn update(&mut self, level: f32) -> bool {
+        self.hold = if level >= SPEAKING_LEVEL {
+            HOLD_TICKS
+        } else {
+            self.hold.saturating_sub(1)
+        };
+        self.hold > 0
+    }
+}
+
+struct Meter {
+    stream: MediaStream,
+    _source: MediaStreamAudioSourceNode,
+    analyser: AnalyserNode,
+    samples: Vec<u8>,
+    activity: Activity,
+}
+
+/// Listens to the streams of a voice channel, by whose they are. Dropping it stops listening.
+#[derive(Default)]
+pub struct Meters {
+    context: Option<AudioContext>,
+    meters: BTreeMap<String, Meter>,
+}
+
+impl Meters {
+    /// Listens to `streams` from now on, and no longer to those not among them.
+    pub fn follow(&mut self, streams: &BTreeMap<String, MediaStream>) {
+        self.meters
+            .retain(|name, meter| streams.get(name) == Some(&meter.stream));
+        for (name, stream) in streams {
+            if self.meters.contains_key(name) {
+                continue;
+            }
+            match self.meter(stream) {
+                Ok(meter) => {
+                    self.meters.insert(name.clone(), meter);
+                }
+                Err(e) => log::debug!("voice: can't listen to {}: {:?}", name, e),
+            }
+        }
+    }
+
+    /// Who is speaking, as of now.
+    pub fn speaking(&mut self) -> BTreeSet<String> {
+        self.meters
+            .iter_mut()
+            .filter_map(|(name, meter)| {
+                meter.analyser.get_byte_time_domain_data(&mut meter.samples);
+                meter
+                    .activity
+                    .update(level(&meter.samples))
+                    .then(|| name.clone())
+            })
+            .collect()
+    }
+
+    /// How loud the stream of `name` is, as of now, from 0 to 1.
+    pub fn level(&mut self, name: &str) -> Option<f32> {
+        let meter = self.meters.get_mut(name)?;
+        meter.analyser.get_byte_time_domain_data(&mut meter.samples);
+        Some(level(&meter.samples))
+    }
+
+    fn meter(&mut self, stream: &MediaStream) -> Result<Meter, wasm_bindgen::JsValue> {
+        let context = match &self.context {
+            Some(context) => context,
+            None => self.context.insert(AudioContext::new()?),
+        };
+        // Made after the click that joined the channel, but browsers may hold it back regardless.
+        let _ = context.resume();
+        let source = context.create_media_stream_source(stream)?;
+        let analyser = context.create_analyser()?;
+        analyser.set_fft_size(WINDOW);
+        source.connect_with_audio_node(&analyser)?;
+        Ok(Meter {
+            stream: stream.clone(),
+            _source: source,
+            analyser,
+            samples: vec![128; WINDOW as usize],
+            activity: Activity::default(),
+        })
+    }
+}
+
+impl Drop for Meters {
+    fn drop(&mut self) {
+        if let Some(context) = &self.context {
+            let _ = context.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn speaking_outlasts_short_pauses() {
+        assert_eq!(level(&[]), 0.0);
+        assert_eq!(level(&[128; 64]), 0.0);
+        assert!((level(&[0, 255, 0, 255]) - 0.996).abs() < 0.01);
+        let speech = [100, 160, 96, 150, 128];
+        assert!(level(&speech) >= SPEAKING_LEVEL);
+
+        let mut activity = Activity::default();
+        assert!(!activity.update(0.0));
+        assert!(activity.update(level(&speech)));
+        assert!(activity.update(0.0));
+        assert!(activity.update(0.0));
+        assert!(!activity.update(0.0));
+    }
+}
@@ -1,32 +1,97 @@
-use futures::{channel::mpsc::Sender, SinkExt, StreamExt};
-use reqwasm::websocket::{futures::WebSocket, Message};
+use std::pin::Pin;
+use std::task::Poll;
+
+use futures::channel::mpsc::{Sender, UnboundedSender};
+use futures::future::{poll_fn, select, Either};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use gloo_timers::future::TimeoutFuture;
+use reqwasm::websocket::{futures::WebSocket, Message, State};
+use serde::Serialize;
 
 use wasm_bindgen_futures::spawn_local;
-use yew_agent::Dispatched;
+use yew::Callback;
+use yew_agent::{Dispatched, Dispatcher};
 
+use crate::protocol::{MsgTypes, WebSocketMessage};
 use crate::services::event_bus::{EventBus, Request};
+use crate::services::frame_log::{self, Direction};
+use crate::store::Connection;
+
+pub const WS_ENDPOINT: &str = "ws://127.0.0.1:8080";
 
+/// The first retry of a connection that failed comes after this long.
+const RETRY_MIN_MS: u32 = 1_000;
+const RETRY_MAX_MS: u32 = 30_000;
+
+/// Handle to the chat connection. Clones share the socket, which closes once all are dropped.
+#[derive(Clone)]
 pub struct WebsocketService {
     pub tx: Sender<String>,
+    /// Cuts short the wait before the next attempt to connect, while the server can't be reached.
+    retry: Option<UnboundedSender<()>>,
 }
 
 impl WebsocketService {
-    pub fn new() -> Self {
-        let ws = WebSocket::open("ws://127.0.0.1:8080").unwrap();
+    /// A handle on frames that `tx` delivers some other way, for transports that speak another
+    /// protocol.
+    pub fn from_channel(tx: Sender<String>) -> Self {
+        Self { tx, retry: None }
+    }
 
-        let (mut write, mut read) = ws.split();
+    /// Opens a connection authenticated with `token`. Browsers can't set headers on a WebSocket
+    /// handshake, so the token travels in the query string.
+    ///
+    /// Until the server can be reached, it is tried again and again, and `on_status` hears of
+    /// every failure. Frames sent meanwhile wait for the connection.
+    pub fn with_token(token: &str, on_status: Callback<Connection>) -> Self {
+        Self::connect(
+            format!(
+                "{}/?token={}",
+                WS_ENDPOINT,
+                js_sys::encode_uri_component(token)
+            ),
+            on_status,
+        )
+    }
 
+    fn connect(url: String, on_status: Callback<Connection>) -> Self {
         let (in_tx, mut in_rx) = futures::channel::mpsc::channel::<String>(1000);
-        let mut event_bus = EventBus::dispatcher();
+        let (retry_tx, mut retry_rx) = futures::channel::mpsc::unbounded::<()>();
 
         spawn_local(async move {
-            while let Some(s) = in_rx.next().await {
-                log::debug!("got event from channel! {}", s);
-                write.send(Message::Text(s)).await.unwrap();
+            let mut attempts = 0;
+            let ws = loop {
+                if let Some(ws) = open(&url).await {
+                    break ws;
+                }
+                attempts += 1;
+                on_status.emit(Connection::Unreachable { attempts });
+                let wait = TimeoutFuture::new(retry_delay(attempts));
+                if let Either::Right((None, _)) = select(wait, retry_rx.next()).await {
+                    // Every handle is gone; nobody is waiting for this connection any more.
+                    return;
+                }
+            };
+            if attempts > 0 {
+                on_status.emit(Connection::Connecting);
             }
-        });
+            let (mut write, mut read) = ws.split();
+            let mut event_bus = EventBus::dispatcher();
+
+            spawn_local(async move {
+                while let Some(s) = in_rx.next().await {
+                    log::debug!("got event from channel! {}", s);
+                    if let Err(e) = write.send(Message::Text(s)).await {
+                        log::error!("ws: failed to send: {:?}", e);
+                        break;
+                    }
+                }
+                // Every sender is gone, so the service was dropped: close the socket properly.
+                if let Err(e) = write.close().await {
+                    log::error!("ws: failed to close: {:?}", e);
+                }
+            });
 
-        spawn_local(async move {
             while let Some(msg) = read.next().await {
                 match msg {
                     Ok(Message::Text(data)) => {
@@ -48,6 +113,70 @@ impl WebsocketService {
             log::debug!("WebSocket Closed");
         });
 
-        Self { tx: in_tx }
+        Self {
+            tx: in_tx,
+            retry: Some(retry_tx),
+        }
+    }
+
+    /// Tries to connect again right away, if we are waiting to.
+    pub fn retry(&self) {
+        if let Some(retry) = &self.retry {
+            let _ = retry.unbounded_send(());
+        }
+    }
+
+    pub fn send(&self, message: &WebSocketMessage) {
+        let frame = serde_json::to_string(message).unwrap();
+        frame_log::record(Direction::Sent, &frame, None);
+        if let Err(e) = self.tx.clone().try_send(frame) {
+            log::debug!("error sending to channel: {:?}", e);
+        }
+    }
+}
+
+/// Opens a socket to `url` and waits for the handshake. `None` if the server can't be reached.
+async fn open(url: &str) -> Option<WebSocket> {
+    let mut ws = WebSocket::open(url)
+        .map_err(|e| log::error!("ws: can't open {}: {:?}", url, e))
+        .ok()?;
+    // Ready once the handshake is over either way; a failure is also announced on the stream.
+    let failed = poll_fn(|cx| {
+        if Pin::new(&mut ws).poll_ready(cx).is_ready() {
+            return Poll::Ready(false);
+        }
+        Pin::new(&mut ws).poll_next(cx).map(|_| true)
+    })
+    .await;
+    (!failed && matches!(ws.state(), State::Open)).then_some(ws)
+}
+
+/// How long to wait after the `attempts`th failure to connect: twice as long each time, up to
+/// [`RETRY_MAX_MS`].
+fn retry_delay(attempts: u32) -> u32 {
+    RETRY_MIN_MS
+        .saturating_mul(1 << attempts.saturating_sub(1).min(16))
+        .min(RETRY_MAX_MS)
+}
+
+/// Hands the chat a frame as if our server had sent it, for transports that speak another
+/// protocol.
+pub fn emit(bus: &mut Dispatcher<EventBus>, message_type: MsgTypes, payload: &impl Serialize) {
+    let frame = WebSocketMessage {
+        data: Some(serde_json::to_string(payload).unwrap()),
+        ..WebSocketMessage::new(message_type)
+    };
+    bus.send(Request::EventBusMsg(serde_json::to_string(&frame).unwrap()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn retries_back_off() {
+        let delays: Vec<u32> = [1, 2, 3, 5, 6, 100].map(retry_delay).to_vec();
+        assert_eq!(delays, [1_000, 2_000, 4_000, 16_000, 30_000, 30_000]);
     }
 }
@@ -0,0 +1,18 @@
+//! Which message of the day we closed. Only that one stays closed: a new one shows again. Kept
+//! per user in this browser.
+
+use gloo_storage::{LocalStorage, Storage};
+
+fn storage_key(username: &str) -> String {
+    format!("yewchat.welcome_dismissed.{}", username)
+}
+
+pub fn dismissed(username: &str, welcome: &str) -> bool {
+    LocalStorage::get::<String>(storage_key(username)).is_ok_and(|closed| closed == welcome)
+}
+
+pub fn dismiss(username: &str, welcome: &str) {
+    if let Err(e) = LocalStorage::set(storage_key(username), welcome) {
+        log::error!("failed to save the closed welcome message: {:?}", e);
+    }
+}
@@ -0,0 +1,673 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+use yew::functional::*;
+use yew::prelude::*;
+
+use crate::avatar;
+use crate::protocol::{
+    HistoryPage, Invite, ListedRoom, MessageData, Permissions, PinnedMessage, RelayStatus,
+    ReportedMessage, Restrictions, Role, RoleChange, RoomEmoji, RoomInfo, RoomRoles, RoomUpdate,
+    UserPayload,
+};
+use crate::services::contacts;
+use crate::services::export::Transcript;
+use crate::services::location::Position;
+use crate::services::message_cache;
+use crate::time;
+
+const PROFILES_KEY: &str = "yewchat.profiles";
+const BOOKMARKS_KEY: &str = "yewchat.bookmarks";
+
+/// How many people [`KnownProfile`]s are kept for, forgetting those seen longest ago.
+const MAX_PROFILES: usize = 500;
+
+/// How many of the room's messages are kept in memory. Older ones move to the
+/// [`message_cache`] until scrolled back to. Direct conversations are left alone, so that their
+/// decrypted text never lands on disk.
+pub const MAX_MESSAGES: usize = 500;
+
+/// Handle to the shared chat state, available as a context under [`StoreProvider`].
+pub type Store = UseReducerHandle<ChatState>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserProfile {
+    pub name: String,
+    pub avatar: String,
+    pub away: bool,
+    /// In the room's voice channel.
+    pub voice: bool,
+    pub role: Role,
+    /// The role of the room's own they were given, if any.
+    pub room_role: Option<String>,
+}
+
+/// What we last knew of someone who was online, remembered across sessions so that their messages
+/// keep their picture once they have left.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KnownProfile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gravatar: Option<String>,
+    pub last_seen: f64,
+}
+
+impl KnownProfile {
+    pub fn avatar_url(&self, name: &str) -> String {
+        avatar::url_for(name, self.avatar.as_deref(), self.gravatar.as_deref())
+    }
+}
+
+/// A room message saved to come back to, kept across sessions.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub room: String,
+    /// Attachments are saved without their contents, which would soon fill the storage.
+    pub message: MessageData,
+    pub saved: f64,
+}
+
+impl Bookmark {
+    pub fn is_of(&self, room: &str, message: &MessageData) -> bool {
+        self.room == room && message.id.is_some() && self.message.id == message.id
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Connection {
+    /// Waiting for the server to acknowledge our registration.
+    Connecting,
+    Online,
+    /// The server couldn't be reached, `attempts` times so far. We keep trying.
+    Unreachable {
+        attempts: u32,
+    },
+}
+
+/// Everything the server tells us about the room and our conversations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChatState {
+    pub room: String,
+    pub connection: Connection,
+    pub users: Vec<UserProfile>,
+    /// Everyone we have seen online, here or in earlier sessions, including those in `users`.
+    pub known: Rc<HashMap<String, KnownProfile>>,
+    pub messages: Vec<Rc<MessageData>>,
+    /// The peer whose direct messages are shown instead of the room, if any. Our own name stands
+    /// for our notes to self.
+    pub conversation: Option<String>,
+    pub direct: HashMap<String, Vec<Rc<MessageData>>>,
+    /// Latest public key the server gave us for each user.
+    pub peer_keys: HashMap<String, String>,
+    pub unread: HashMap<String, usize>,
+    /// Where the room is mirrored to, once the server has told us.
+    pub relay: Option<RelayStatus>,
+    /// How many messages of the room the server remembers, once it has told us. Only the latest
+    /// are replayed when entering; the rest are loaded on request.
+    pub kept: Option<usize>,
+    /// How many of the room's messages are in the [`message_cache`] rather than in `messages`.
+    pub evicted: usize,
+    /// The room's custom emoji, pictures by name.
+    pub emoji: Rc<HashMap<String, String>>,
+    /// Who may change the room's emoji, once the server has told us.
+    pub room_owner: Option<String>,
+    /// Saved messages of every room, latest first.
+    pub bookmarks: Rc<Vec<Bookmark>>,
+    /// Whether the server lets us see its admin view.
+    pub admin: bool,
+    /// The server's message of the day, if it sent one.
+    pub welcome: Option<String>,
+    /// Seconds to wait between our messages in the room, if it is in slow mode.
+    pub slow_mode: Option<u32>,
+    /// Reported messages of the room, which the server only tells its moderators about.
+    pub reports: Rc<Vec<ReportedMessage>>,
+    /// Who may no longer post in the room.
+    pub banned: HashSet<String>,
+    /// Words the server masks in the room's messages.
+    pub blacklist: Rc<Vec<String>>,
+    /// What the room doesn't allow in messages.
+    pub restrictions: Restrictions,
+    /// Messages pinned above the room, in the order its moderators chose.
+    pub pins: Rc<Vec<PinnedMessage>>,
+    /// What the room's owner chose to show beside its name: an emoji or a `data:image/...` URL.
+    pub room_icon: Option<String>,
+    /// What the room's owner says it is about.
+    pub topic: Option<String>,
+    /// Whether only those with an invite code, and those already let in, can enter the room.
+    pub invite_only: bool,
+    /// Whether entering the room takes its password.
+    pub password_protected: bool,
+    /// The code that lets people into the room, which only its owner is sent.
+    pub invite: Option<Invite>,
+    /// The rooms anyone can enter, busiest first, once the server told us.
+    pub listed_rooms: Option<Rc<Vec<ListedRoom>>>,
+    /// The room's own roles, and what everyone else may do there.
+    pub room_roles: Rc<RoomRoles>,
+    /// Where those of the room sharing their location live last were, and when we heard.
+    pub live_locations: Rc<HashMap<String, (Position, f64)>>,
+    /// Whom we added to our contacts, in that order.
+    pub contacts: Rc<Vec<String>>,
+}
+
+pub enum Action {
+    /// Switches to another room, forgetting what was said in the previous one.
+    JoinRoom(String),
+    Users(Vec<UserPayload>),
+    Message(MessageData),
+    /// Several messages at once, in the order they arrived.
+    Messages(Vec<MessageData>),
+    PeerKey {
+        peer: String,
+        key: String,
+    },
+    /// A decrypted message from the conversation with `peer`, sent by either side.
+    Direct {
+        peer: String,
+        message: MessageData,
+    },
+    OpenDirect(String),
+    /// Shows the notes to self of `owner`, as they were saved.
+    OpenNotes {
+        owner: String,
+        notes: Vec<MessageData>,
+    },
+    CloseDirect,
+    Relay(RelayStatus),
+    Emoji(RoomEmoji),
+    /// The server answered our question about its admin view with the view itself.
+    Admin,
+    Welcome(String),
+    SlowMode(Option<u32>),
+    Reports(Vec<ReportedMessage>),
+    /// A moderator deleted the message with this id.
+    Deleted(String),
+    /// A moderator banned someone from the room.
+    Banned(String),
+    Blacklist(Vec<String>),
+    Restrictions(Restrictions),
+    Pins(Vec<PinnedMessage>),
+    RoomUpdate(RoomUpdate),
+    /// The room was handed to someone else. Permissions follow `room_owner`.
+    RoleChange(RoleChange),
+    Invite(Invite),
+    Rooms(Vec<ListedRoom>),
+    RoomRoles(RoomRoles),
+    /// Where someone of the room sharing their location live is now, or that they stopped.
+    LiveLocation {
+        from: String,
+        position: Option<Position>,
+    },
+    /// Our contacts, as saved for us.
+    Contacts(Vec<String>),
+    /// Adds `name` to the contacts of `owner`, or takes them off if they were there.
+    ToggleContact {
+        owner: String,
+        name: String,
+    },
+    /// The server has something to say about what we sent, shown as a system line.
+    Warning(String),
+    /// Saves a message of the room, or forgets it if it was saved already.
+    ToggleBookmark(Rc<MessageData>),
+    /// Forgets a saved message, of whichever room.
+    RemoveBookmark(Bookmark),
+    RoomInfo(RoomInfo),
+    /// How the connection is doing, until the server acknowledges us with a user list.
+    Connection(Connection),
+    /// Some avatars were [`avatar::prefetch`]ed; show the copies in memory.
+    AvatarsLoaded,
+    /// Messages of `room` taken back from the [`message_cache`], to go before those we have.
+    Restored {
+        room: String,
+        messages: Vec<MessageData>,
+    },
+    /// Older messages of `room`, to go before those we have.
+    Earlier {
+        room: String,
+        page: HistoryPage,
+    },
+}
+
+impl ChatState {
+    pub fn new(room: String) -> Self {
+        Self {
+            room,
+            connection: Connection::Connecting,
+            users: vec![],
+            known: Rc::new(LocalStorage::get(PROFILES_KEY).unwrap_or_default()),
+            messages: vec![],
+            conversation: None,
+            direct: HashMap::new(),
+            peer_keys: HashMap::new(),
+            unread: HashMap::new(),
+            relay: None,
+            kept: None,
+            evicted: 0,
+            emoji: Rc::default(),
+            room_owner: None,
+            bookmarks: Rc::new(LocalStorage::get(BOOKMARKS_KEY).unwrap_or_default()),
+            admin: false,
+            welcome: None,
+            slow_mode: None,
+            reports: Rc::default(),
+            banned: HashSet::new(),
+            blacklist: Rc::default(),
+            restrictions: Restrictions::default(),
+            room_icon: None,
+            topic: None,
+            invite_only: false,
+            password_protected: false,
+            invite: None,
+            listed_rooms: None,
+            pins: Rc::default(),
+            room_roles: Rc::default(),
+            live_locations: Rc::default(),
+            contacts: Rc::default(),
+        }
+    }
+
+    /// The thread currently on screen: the open direct conversation, or else the room.
+    pub fn visible_messages(&self) -> &[Rc<MessageData>] {
+        match &self.conversation {
+            Some(peer) => self.direct.get(peer).map_or(&[], Vec::as_slice),
+            None => &self.messages,
+        }
+    }
+
+    pub fn avatar_of(&self, name: &str) -> String {
+        self.users
+            .iter()
+            .find(|u| u.name == name)
+            .map(|u| u.avatar.clone())
+            .or_else(|| self.known.get(name).map(|k| k.avatar_url(name)))
+            .unwrap_or_else(|| avatar::url_for(name, None, None))
+    }
+
+    /// Who has spoken in the room as far back as we have its messages but isn't in it now, by
+    /// name.
+    pub fn offline_members(&self) -> Vec<String> {
+        let mut offline: Vec<String> = self
+            .messages
+            .iter()
+            .filter(|m| !m.system && m.bot.is_none() && !m.from.is_empty())
+            .filter(|m| self.users.iter().all(|u| u.name != m.from))
+            .map(|m| m.from.clone())
+            .collect();
+        offline.sort_by_key(|name| name.to_lowercase());
+        offline.dedup();
+        offline
+    }
+
+    /// Whether the cache or the server has older messages of the room than the first one shown.
+    pub fn has_earlier(&self) -> bool {
+        self.evicted > 0
+            || self.kept.is_some_and(|kept| kept > self.kept_count())
+                && self.first_kept().is_some_and(|m| m.id.is_some())
+    }
+
+    /// How many of the messages shown the server has too.
+    fn kept_count(&self) -> usize {
+        self.messages.iter().filter(|m| !m.system).count()
+    }
+
+    /// The earliest message shown that the server has too, unlike our own system lines.
+    pub fn first_kept(&self) -> Option<&Rc<MessageData>> {
+        self.messages.iter().find(|m| !m.system)
+    }
+
+    /// Adds messages that just arrived, each followed by a system line if it pins something.
+    fn arrive(&mut self, messages: impl IntoIterator<Item = MessageData>) {
+        let before = self.messages.len();
+        let now = time::now();
+        for message in messages {
+            let pin = match message.pinned_until {
+                Some(until) if message.announcement && until > now => Some(MessageData {
+                    time: message.time,
+                    ..MessageData::system(format!(
+                        "{} pinned an announcement until {}",
+                        message.from,
+                        time::absolute_label(until)
+                    ))
+                }),
+                _ => None,
+            };
+            self.messages.push(Rc::new(message));
+            self.messages.extend(pin.map(Rc::new));
+        }
+        self.evict(self.messages.len() - before);
+    }
+
+    /// Moves the oldest messages to the cache once there are too many. Messages scrolled back to
+    /// stay until as many new ones have arrived.
+    fn evict(&mut self, arrived: usize) {
+        let limit = MAX_MESSAGES.max(self.messages.len() - arrived);
+        if self.messages.len() > limit {
+            let evicted: Vec<_> = self.messages.drain(..self.messages.len() - limit).collect();
+            self.evicted += evicted.len();
+            message_cache::evict(&self.room, evicted);
+        }
+    }
+
+    /// What we, as `username`, may do in the room: everything if we own it or administer the
+    /// server, else what our role there allows.
+    pub fn permissions(&self, username: &str) -> Permissions {
+        if self.admin || self.room_owner.as_deref() == Some(username) {
+            Permissions::ALL
+        } else {
+            self.room_roles.permissions_of(username)
+        }
+    }
+
+    pub fn peer_key(&self, peer: &str) -> Option<&str> {
+        self.peer_keys.get(peer).map(String::as_str)
+    }
+
+    /// Users other than `except` whose name starts with `prefix`, ignoring case.
+    pub fn users_matching<'a>(
+        &'a self,
+        prefix: &str,
+        except: &'a str,
+    ) -> impl Iterator<Item = &'a UserProfile> {
+        let prefix = prefix.to_lowercase();
+        self.users
+            .iter()
+            .filter(move |u| u.name != except && u.name.to_lowercase().starts_with(&prefix))
+    }
+
+    /// The thread on screen, ready to be exported.
+    pub fn transcript(&self) -> Transcript {
+        let room = match &self.conversation {
+            Some(peer) => format!("@{}", peer),
+            None => self.room.clone(),
+        };
+        Transcript::new(&room, self.visible_messages().iter().map(Rc::as_ref))
+    }
+}
+
+impl Reducible for ChatState {
+    type Action = Action;
+
+    fn reduce(self: Rc<Self>, action: Action) -> Rc<Self> {
+        let mut state = (*self).clone();
+        match action {
+            Action::JoinRoom(room) => {
+                state.room = room;
+                state.messages.clear();
+                state.users.clear();
+                state.relay = None;
+                state.kept = None;
+                state.evicted = 0;
+                state.emoji = Rc::default();
+                state.room_owner = None;
+                state.slow_mode = None;
+                state.reports = Rc::default();
+                state.banned.clear();
+                state.blacklist = Rc::default();
+                state.restrictions = Restrictions::default();
+                state.pins = Rc::default();
+                state.room_icon = None;
+                state.topic = None;
+                state.invite_only = false;
+                state.password_protected = false;
+                state.invite = None;
+                state.room_roles = Rc::default();
+                state.live_locations = Rc::default();
+                message_cache::forget();
+            }
+            Action::Users(users) => {
+                for u in &users {
+                    if let Some(key) = &u.key {
+                        state.peer_keys.insert(u.name.clone(), key.clone());
+                    }
+                }
+                remember(&mut state.known, &users);
+                // The first list of a room is everyone already there, not news.
+                if !state.users.is_empty() {
+                    if state.connection != Connection::Online {
+                        state
+                            .messages
+                            .push(Rc::new(MessageData::system("Reconnected")));
+                    }
+                    for line in arrivals(&state.users, &users) {
+                        state.messages.push(Rc::new(MessageData::system(line)));
+                    }
+                }
+                state.users = users
+                    .into_iter()
+                    .map(|u| UserProfile {
+                        avatar: avatar::url_for(
+                            &u.name,
+                            u.avatar.as_deref(),
+                            u.gravatar.as_deref(),
+                        ),
+                        name: u.name,
+                        away: u.away,
+                        voice: u.voice,
+                        role: u.role,
+                        room_role: u.room_role,
+                    })
+                    .collect();
+                // The user list is the server's reply to registering.
+                state.connection = Connection::Online;
+            }
+            Action::Message(message) => state.arrive([message]),
+            Action::Messages(messages) => state.arrive(messages),
+            Action::PeerKey { peer, key } => {
+                state.peer_keys.insert(peer, key);
+            }
+            Action::Direct { peer, message } => {
+                if state.conversation.as_ref() != Some(&peer) && message.from == peer {
+                    *state.unread.entry(peer.clone()).or_default() += 1;
+                }
+                // Decryption finishes out of order, so keep the thread sorted by server time.
+                let thread = state.direct.entry(peer).or_default();
+                let at = thread.partition_point(|m| m.time <= message.time);
+                thread.insert(at, Rc::new(message));
+            }
+            Action::OpenDirect(peer) => {
+                state.unread.remove(&peer);
+                state.conversation = Some(peer);
+            }
+            Action::OpenNotes { owner, notes } => {
+                state
+                    .direct
+                    .insert(owner.clone(), notes.into_iter().map(Rc::new).collect());
+                state.conversation = Some(owner);
+            }
+            Action::CloseDirect => state.conversation = None,
+            Action::Connection(connection) => {
+                if state.connection == Connection::Online && connection != Connection::Online {
+                    state.messages.push(Rc::new(MessageData::system(
+                        "Lost the connection to the server. Reconnecting...",
+                    )));
+                }
+                state.connection = connection;
+            }
+            Action::Relay(status) => state.relay = Some(status),
+            Action::Emoji(room) => {
+                state.emoji = Rc::new(room.emoji.into_iter().map(|e| (e.name, e.image)).collect());
+                state.room_owner = room.owner;
+            }
+            Action::Admin => state.admin = true,
+            Action::Welcome(text) => state.welcome = Some(text),
+            Action::SlowMode(seconds) => state.slow_mode = seconds,
+            Action::Reports(reports) => state.reports = Rc::new(reports),
+            Action::Blacklist(words) => state.blacklist = Rc::new(words),
+            Action::Restrictions(restrictions) => state.restrictions = restrictions,
+            // The code is the owner's; a new owner asks for it anew.
+            Action::RoleChange(change) => {
+                state.room_owner = Some(change.owner);
+                state.invite = None;
+            }
+            Action::Invite(invite) => state.invite = Some(invite),
+            Action::Rooms(rooms) => state.listed_rooms = Some(Rc::new(rooms)),
+            Action::RoomRoles(roles) => state.room_roles = Rc::new(roles),
+            Action::LiveLocation { from, position } => {
+                let live = Rc::make_mut(&mut state.live_locations);
+                match position {
+                    Some(position) => live.insert(from, (position, time::now())),
+                    None => live.remove(&from),
+                };
+            }
+            Action::Contacts(list) => state.contacts = Rc::new(list),
+            Action::ToggleContact { owner, name } => {
+                let list = Rc::make_mut(&mut state.contacts);
+                let before = list.len();
+                list.retain(|c| *c != name);
+                if list.len() == before {
+                    list.push(name);
+                }
+                contacts::save(&owner, list);
+            }
+            Action::RoomUpdate(update) => {
+                // Renamed: everything stays, only the name changes.
+                if let Some(name) = update.name {
+                    state.room = name;
+                }
+                state.room_icon = update.icon;
+                state.topic = update.topic;
+                state.invite_only = update.invite_only;
+                state.password_protected = update.password_protected;
+                if !update.invite_only {
+                    state.invite = None;
+                }
+            }
+            Action::Pins(pins) => state.pins = Rc::new(pins),
+            Action::Warning(text) => state.messages.push(Rc::new(MessageData::system(text))),
+            Action::Deleted(id) => state.messages.retain(|m| m.id.as_ref() != Some(&id)),
+            Action::Banned(name) => {
+                if state.banned.insert(name.clone()) {
+                    state.messages.push(Rc::new(MessageData::system(format!(
+                        "{} was banned from the room",
+                        name
+                    ))));
+                }
+            }
+            Action::ToggleBookmark(message) => {
+                let bookmarks = Rc::make_mut(&mut state.bookmarks);
+                let before = bookmarks.len();
+                bookmarks.retain(|b| !b.is_of(&state.room, &message));
+                if bookmarks.len() == before {
+                    let mut message = (*message).clone();
+                    if let Some(attachment) = &mut message.attachment {
+                        attachment.data.clear();
+                    }
+                    bookmarks.insert(
+                        0,
+                        Bookmark {
+                            room: state.room.clone(),
+                            message,
+                            saved: time::now(),
+                        },
+                    );
+                }
+                save_bookmarks(bookmarks);
+            }
+            Action::RemoveBookmark(bookmark) => {
+                let bookmarks = Rc::make_mut(&mut state.bookmarks);
+                bookmarks.retain(|b| *b != bookmark);
+                save_bookmarks(bookmarks);
+            }
+            // Answers can arrive after we have moved on to another room.
+            Action::RoomInfo(info) if info.name == state.room => {
+                state.kept = Some(info.kept);
+                state.room_icon = info.icon;
+            }
+            Action::RoomInfo(_) => {}
+            Action::AvatarsLoaded => {
+                for user in &mut state.users {
+                    user.avatar = avatar::loaded(std::mem::take(&mut user.avatar));
+                }
+            }
+            Action::Earlier { room, page } if room == state.room => {
+                if !page.more {
+                    // That was all of it, whatever the count said.
+                    state.kept = Some(state.kept_count() + page.messages.len());
+                }
+                state
+                    .messages
+                    .splice(0..0, page.messages.into_iter().map(Rc::new));
+            }
+            Action::Earlier { .. } => {}
+            Action::Restored { room, messages } if room == state.room => {
+                // Whatever the cache lost, the server may still have.
+                state.evicted = if messages.is_empty() {
+                    0
+                } else {
+                    state.evicted.saturating_sub(messages.len())
+                };
+                state
+                    .messages
+                    .splice(0..0, messages.into_iter().map(Rc::new));
+            }
+            Action::Restored { .. } => {}
+        }
+        Rc::new(state)
+    }
+}
+
+/// "Joined" and "left" lines for the difference between two user lists.
+fn arrivals(before: &[UserProfile], after: &[UserPayload]) -> Vec<String> {
+    let joined = after
+        .iter()
+        .filter(|u| !before.iter().any(|b| b.name == u.name))
+        .map(|u| format!("{} joined", u.name));
+    let left = before
+        .iter()
+        .filter(|b| !after.iter().any(|u| u.name == b.name))
+        .map(|b| format!("{} left", b.name));
+    joined.chain(left).collect()
+}
+
+/// Notes everyone in a user list as seen now, and saves the lot.
+fn remember(known: &mut Rc<HashMap<String, KnownProfile>>, users: &[UserPayload]) {
+    let known = Rc::make_mut(known);
+    let now = time::now();
+    for u in users {
+        known.insert(
+            u.name.clone(),
+            KnownProfile {
+                avatar: u.avatar.clone(),
+                gravatar: u.gravatar.clone(),
+                last_seen: now,
+            },
+        );
+    }
+    if known.len() > MAX_PROFILES {
+        let mut seen: Vec<f64> = known.values().map(|k| k.last_seen).collect();
+        seen.sort_by(|a, b| b.total_cmp(a));
+        let cutoff = seen[MAX_PROFILES - 1];
+        known.retain(|_, k| k.last_seen >= cutoff);
+    }
+    if let Err(e) = LocalStorage::set(PROFILES_KEY, &*known) {
+        log::error!("failed to save known profiles: {:?}", e);
+    }
+}
+
+fn save_bookmarks(bookmarks: &[Bookmark]) {
+    if let Err(e) = LocalStorage::set(BOOKMARKS_KEY, bookmarks) {
+        log::error!("failed to save bookmarks: {:?}", e);
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct StoreProviderProps {
+    pub room: String,
+    #[prop_or_default]
+    pub children: Children,
+}
+
+/// Owns the [`Store`] for everything rendered inside it.
+#[function_component(StoreProvider)]
+pub fn store_provider(props: &StoreProviderProps) -> Html {
+    let room = props.room.clone();
+    let store = use_reducer_eq(move || ChatState::new(room));
+
+    html! {
+        <ContextProvider<Store> context={store}>
+            { for props.children.iter() }
+        </ContextProvider<Store>>
+    }
+}
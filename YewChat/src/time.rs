@@ -0,0 +1,82 @@
+use js_sys::Date;
+use wasm_bindgen::JsValue;
+
+/// A calendar day in the browser's local time zone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Day {
+    year: u32,
+    month: u32,
+    date: u32,
+}
+
+impl Day {
+    fn of(date: &Date) -> Self {
+        Self {
+            year: date.get_full_year(),
+            month: date.get_month(),
+            date: date.get_date(),
+        }
+    }
+}
+
+/// The local day a millisecond Unix timestamp (as sent by the server) falls on.
+pub fn day_of(timestamp: f64) -> Day {
+    Day::of(&Date::new(&JsValue::from_f64(timestamp)))
+}
+
+/// Label for a day separator: "Today", "Yesterday", or a full date.
+pub fn day_label(timestamp: f64) -> String {
+    let today = Date::new_0();
+    let yesterday = Date::new_0();
+    yesterday.set_date(today.get_date() - 1);
+
+    let day = day_of(timestamp);
+    if day == Day::of(&today) {
+        "Today".into()
+    } else if day == Day::of(&yesterday) {
+        "Yesterday".into()
+    } else {
+        let options = js_sys::Object::new();
+        for (key, value) in [
+            ("weekday", "long"),
+            ("year", "numeric"),
+            ("month", "long"),
+            ("day", "numeric"),
+        ] {
+            let _ = js_sys::Reflect::set(&options, &key.into(), &value.into());
+        }
+        Date::new(&JsValue::from_f64(timestamp))
+            .to_locale_date_string("default", &options)
+            .into()
+    }
+}
+
+/// Current time as milliseconds since the Unix epoch.
+pub fn now() -> f64 {
+    Date::now()
+}
+
+/// Short relative age of a timestamp: "just now", "5m", "3h", "2d".
+pub fn relative_label(timestamp: f64, now: f64) -> String {
+    let seconds = ((now - timestamp) / 1000.0).max(0.0) as u64;
+    match seconds {
+        0..=59 => "just now".into(),
+        60..=3_599 => format!("{}m", seconds / 60),
+        3_600..=86_399 => format!("{}h", seconds / 3_600),
+        _ => format!("{}d", seconds / 86_400),
+    }
+}
+
+/// Full local date and time, for tooltips.
+pub fn absolute_label(timestamp: f64) -> String {
+    Date::new(&JsValue::from_f64(timestamp))
+        .to_locale_string("default", &JsValue::UNDEFINED)
+        .into()
+}
+
+/// Local time of day down to the second, for logs.
+pub fn clock_label(timestamp: f64) -> String {
+    Date::new(&JsValue::from_f64(timestamp))
+        .to_locale_time_string("default")
+        .into()
+}
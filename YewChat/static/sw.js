@@ -0,0 +1,67 @@
+// Caches the app shell so YewChat starts without a network connection.
+// `__BUILD_ID__` is replaced on every build; the changed bytes are what makes
+// browsers install a new worker, and the page then offers to refresh.
+const BUILD_ID = '__BUILD_ID__';
+const CACHE = `yewchat-${BUILD_ID}`;
+const APP_SHELL = [
+    '/',
+    '/index.html',
+    '/yewchat.js',
+    '/yewchat_bg.wasm',
+    '/manifest.webmanifest',
+    '/icon.svg',
+];
+// Styles come from the Tailwind CDN; keep a copy so an offline start is not unstyled.
+const RUNTIME_ORIGINS = ['https://cdn.tailwindcss.com'];
+
+self.addEventListener('install', (event) => {
+    event.waitUntil(caches.open(CACHE).then((cache) => cache.addAll(APP_SHELL)));
+});
+
+self.addEventListener('activate', (event) => {
+    event.waitUntil(
+        caches
+            .keys()
+            .then((keys) => Promise.all(keys.filter((key) => key !== CACHE).map((key) => caches.delete(key))))
+            .then(() => self.clients.claim())
+    );
+});
+
+// The page asks the waiting worker to take over once the user accepts the update.
+self.addEventListener('message', (event) => {
+    if (event.data && event.data.type === 'SKIP_WAITING') {
+        self.skipWaiting();
+    }
+});
+
+self.addEventListener('fetch', (event) => {
+    const request = event.request;
+    if (request.method !== 'GET') {
+        return;
+    }
+    const url = new URL(request.url);
+
+    if (request.mode === 'navigate') {
+        // Every route is rendered by the app, so offline navigations get the cached index.
+        event.respondWith(fetch(request).catch(() => caches.match('/index.html')));
+        return;
+    }
+
+    if (url.origin === self.location.origin) {
+        event.respondWith(caches.match(request).then((cached) => cached || fetch(request)));
+        return;
+    }
+
+    if (RUNTIME_ORIGINS.includes(url.origin)) {
+        event.respondWith(
+            caches.open(CACHE).then((cache) =>
+                fetch(request)
+                    .then((response) => {
+                        cache.put(request, response.clone());
+                        return response;
+                    })
+                    .catch(() => cache.match(request))
+            )
+        );
+    }
+});
@@ -8,20 +8,34 @@ module.exports = {
     mode: 'production',
     devServer: {
         port: 8000,
+        historyApiFallback: true,
     },
     entry: './bootstrap.js',
     output: {
         path: distPath,
         filename: 'yewchat.js',
+        publicPath: '/',
         webassemblyModuleFilename: 'yewchat_bg.wasm',
     },
     plugins: [
         new CopyWebpackPlugin({
-            patterns: [{ from: './static', to: distPath }],
+            patterns: [
+                {
+                    from: './static',
+                    to: distPath,
+                    // A fresh id per build makes browsers pick up the new service worker.
+                    transform(content, absoluteFrom) {
+                        if (!absoluteFrom.endsWith('sw.js')) {
+                            return content;
+                        }
+                        return content.toString().replace('__BUILD_ID__', Date.now().toString(36));
+                    },
+                },
+            ],
         }),
         new WasmPackPlugin({
             crateDirectory: '.',
-            extraArgs: '-- --features wee_alloc',
+            extraArgs: `-- --features wee_alloc${process.env.YEWCHAT_MATH ? ',math' : ''}`,
             outName: 'yewchat',
         }),
     ],
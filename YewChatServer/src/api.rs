@@ -0,0 +1,115 @@
+//! Read-only HTTP endpoints beside the WebSocket, for what a client asks for rather than gets
+//! pushed: older history, someone's profile, a room's details. All of them want a session token,
+//! and a room's only answer those it would let in, invite and password alike.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, Request, State};
+use axum::http::header::{ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_ORIGIN};
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::Value;
+
+use crate::auth::Identity;
+use crate::protocol::{HistoryPage, HistoryQuery, Profile};
+use crate::{bearer_token, AppState};
+
+const DEFAULT_PAGE: usize = 50;
+const MAX_PAGE: usize = 100;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/rooms/:room", get(room))
+        .route("/api/rooms/:room/messages", get(messages))
+        .route("/api/users/:name", get(user))
+        .route("/api/terms", get(terms))
+        .layer(middleware::from_fn(cors))
+}
+
+async fn room(
+    Path(room): Path<String>,
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, StatusCode> {
+    let reader = authorize(&state, &headers)?;
+    let hub = state.hub.lock().unwrap();
+    let info = hub
+        .room_info(&reader.username, &room)
+        .ok_or(StatusCode::FORBIDDEN)?;
+    Ok(Json(serde_json::to_value(info).unwrap()))
+}
+
+async fn messages(
+    Path(room): Path<String>,
+    Query(query): Query<HistoryQuery>,
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<HistoryPage>, StatusCode> {
+    let reader = authorize(&state, &headers)?;
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE).clamp(1, MAX_PAGE);
+    let hub = state.hub.lock().unwrap();
+    hub.page(&reader.username, &room, query.before.as_deref(), limit)
+        .map(Json)
+        .ok_or(StatusCode::FORBIDDEN)
+}
+
+/// Whoever is connected under `name`, or else has an account by that name.
+async fn user(
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, StatusCode> {
+    authorize(&state, &headers)?;
+    if let Some(profile) = state.hub.lock().unwrap().profile(&name) {
+        return Ok(Json(serde_json::to_value(profile).unwrap()));
+    }
+    let name = state
+        .auth
+        .account_name(&name)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let profile = Profile {
+        name: &name,
+        avatar: None,
+        room: None,
+    };
+    Ok(Json(serde_json::to_value(profile).unwrap()))
+}
+
+/// The terms to accept before registering, if the server has any.
+async fn terms(
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, StatusCode> {
+    authorize(&state, &headers)?;
+    let terms = state.terms.lock().unwrap();
+    let terms = terms.terms().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(serde_json::to_value(terms).unwrap()))
+}
+
+/// Whose token came with the request, if a valid one did.
+fn authorize(state: &AppState, headers: &HeaderMap) -> Result<Identity, StatusCode> {
+    state
+        .auth
+        .verify(bearer_token(headers))
+        .ok_or(StatusCode::UNAUTHORIZED)
+}
+
+/// The client is served from another origin and sends its token in a header, so browsers check
+/// with us first.
+async fn cors(request: Request, next: Next) -> Response {
+    let mut response = if request.method() == Method::OPTIONS {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        next.run(request).await
+    };
+    let headers = response.headers_mut();
+    headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    headers.insert(
+        ACCESS_CONTROL_ALLOW_HEADERS,
+        HeaderValue::from_static("authorization"),
+    );
+    response
+}
@@ -0,0 +1,300 @@
+//! Accounts and session tokens: a short-lived JWT for the WebSocket handshake, plus a single-use
+//! refresh token to get the next one.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use crate::now_ms;
+use crate::protocol::{AuthError, Credentials, Tokens};
+
+const JWT_TTL_MS: u64 = 15 * 60 * 1000;
+const REFRESH_TTL_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+
+/// Who a token was issued to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Identity {
+    pub username: String,
+    pub avatar: Option<String>,
+}
+
+struct Account {
+    username: String,
+    salt: [u8; 16],
+    hash: [u8; 32],
+}
+
+struct RefreshEntry {
+    identity: Identity,
+    expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    avatar: Option<String>,
+    /// Seconds since the Unix epoch.
+    exp: u64,
+}
+
+/// In-memory account store. Everything is forgotten on restart.
+pub struct Auth {
+    secret: Vec<u8>,
+    /// Keyed by lowercased username, so names are unique regardless of case.
+    accounts: Mutex<HashMap<String, Account>>,
+    refresh_tokens: Mutex<HashMap<String, RefreshEntry>>,
+}
+
+impl Auth {
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self {
+            secret,
+            accounts: Mutex::new(HashMap::new()),
+            refresh_tokens: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn signup(&self, credentials: &Credentials) -> Result<Tokens, AuthError> {
+        if !valid_username(&credentials.username) {
+            return Err(AuthError::InvalidName);
+        }
+        let key = credentials.username.to_lowercase();
+        if self.accounts.lock().unwrap().contains_key(&key) {
+            return Err(AuthError::NameTaken);
+        }
+        let mut salt = [0; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let hash = hash_password(&credentials.password_hash, salt).await;
+        // Someone may have taken the name while we were hashing.
+        match self.accounts.lock().unwrap().entry(key) {
+            Entry::Occupied(_) => return Err(AuthError::NameTaken),
+            Entry::Vacant(entry) => {
+                entry.insert(Account {
+                    username: credentials.username.clone(),
+                    salt,
+                    hash,
+                });
+            }
+        }
+        Ok(self.issue(Identity {
+            username: credentials.username.clone(),
+            avatar: None,
+        }))
+    }
+
+    pub async fn login(&self, credentials: &Credentials) -> Result<Tokens, AuthError> {
+        let (username, salt, expected) = {
+            let accounts = self.accounts.lock().unwrap();
+            let account = accounts
+                .get(&credentials.username.to_lowercase())
+                .ok_or(AuthError::UnknownUser)?;
+            (account.username.clone(), account.salt, account.hash)
+        };
+        let hash = hash_password(&credentials.password_hash, salt).await;
+        if !bool::from(hash.ct_eq(&expected)) {
+            return Err(AuthError::WrongPassword);
+        }
+        Ok(self.issue(Identity {
+            username,
+            avatar: None,
+        }))
+    }
+
+    /// The name `username` was registered with, if it was.
+    pub fn account_name(&self, username: &str) -> Option<String> {
+        let accounts = self.accounts.lock().unwrap();
+        accounts
+            .get(&username.to_lowercase())
+            .map(|a| a.username.clone())
+    }
+
+    /// Trades in a refresh token. They are single use, so this also revokes it.
+    pub fn refresh(&self, refresh_token: &str) -> Result<Tokens, AuthError> {
+        let entry = self.refresh_tokens.lock().unwrap().remove(refresh_token);
+        match entry {
+            Some(entry) if entry.expires_at >= now_ms() => Ok(self.issue(entry.identity)),
+            _ => Err(AuthError::InvalidToken),
+        }
+    }
+
+    /// Tokens for `identity`. Refresh tokens that ran out unused are forgotten meanwhile.
+    pub fn issue(&self, identity: Identity) -> Tokens {
+        let now = now_ms();
+        let expires_at = now + JWT_TTL_MS;
+        let token = self.create_jwt(&identity, expires_at);
+        let refresh_token = random_hex(24);
+        let username = identity.username.clone();
+        let mut refresh_tokens = self.refresh_tokens.lock().unwrap();
+        refresh_tokens.retain(|_, entry| entry.expires_at >= now);
+        refresh_tokens.insert(
+            refresh_token.clone(),
+            RefreshEntry {
+                identity,
+                expires_at: now + REFRESH_TTL_MS,
+            },
+        );
+        Tokens {
+            username,
+            token,
+            refresh_token,
+            expires_at,
+        }
+    }
+
+    /// The identity a JWT vouches for, if it is ours and hasn't expired.
+    pub fn verify(&self, token: &str) -> Option<Identity> {
+        let mut parts = token.split('.');
+        let (header, claims, signature) = (parts.next()?, parts.next()?, parts.next()?);
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        self.mac(header, claims).verify_slice(&signature).ok()?;
+        let claims: Claims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims).ok()?).ok()?;
+        if claims.exp * 1000 < now_ms() {
+            return None;
+        }
+        Some(Identity {
+            username: claims.sub,
+            avatar: claims.avatar,
+        })
+    }
+
+    fn create_jwt(&self, identity: &Identity, expires_at: u64) -> String {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(
+            serde_json::to_string(&Claims {
+                sub: identity.username.clone(),
+                avatar: identity.avatar.clone(),
+                exp: expires_at / 1000,
+            })
+            .unwrap(),
+        );
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&header, &claims).finalize().into_bytes());
+        format!("{}.{}.{}", header, claims, signature)
+    }
+
+    fn mac(&self, header: &str, claims: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).unwrap();
+        mac.update(format!("{}.{}", header, claims).as_bytes());
+        mac
+    }
+}
+
+/// Mirrors `validate_username` in the client: 3-20 letters, digits, `_` or `-`.
+fn valid_username(name: &str) -> bool {
+    (3..=20).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Takes tens of milliseconds on purpose, so it runs on a thread of its own rather than holding up
+/// the connections served by this one. Room passwords are hashed the same way.
+pub async fn hash_password(password_hash: &str, salt: [u8; 16]) -> [u8; 32] {
+    let password_hash = password_hash.to_string();
+    tokio::task::spawn_blocking(move || {
+        // The same cost as Node's `scryptSync` defaults, which the JS server uses.
+        let params = scrypt::Params::new(14, 8, 1, 32).unwrap();
+        let mut hash = [0; 32];
+        scrypt::scrypt(password_hash.as_bytes(), &salt, &params, &mut hash).unwrap();
+        hash
+    })
+    .await
+    .expect("hashing a password panicked")
+}
+
+pub fn random_hex(bytes: usize) -> String {
+    let mut buf = vec![0; bytes];
+    rand::thread_rng().fill_bytes(&mut buf);
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials(username: &str, password_hash: &str) -> Credentials {
+        Credentials {
+            username: username.into(),
+            password_hash: password_hash.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn signed_tokens_verify() {
+        let auth = Auth::new(b"secret".to_vec());
+        let tokens = auth.signup(&credentials("alice", "h")).await.unwrap();
+        assert_eq!(
+            auth.verify(&tokens.token).map(|i| i.username).as_deref(),
+            Some("alice")
+        );
+        assert_eq!(Auth::new(b"other".to_vec()).verify(&tokens.token), None);
+        assert_eq!(auth.verify(&tokens.token.replace('.', "")), None);
+    }
+
+    #[tokio::test]
+    async fn login_checks_the_password() {
+        let auth = Auth::new(b"secret".to_vec());
+        auth.signup(&credentials("Alice", "h")).await.unwrap();
+        assert_eq!(
+            auth.signup(&credentials("alice", "x")).await.unwrap_err(),
+            AuthError::NameTaken
+        );
+        assert_eq!(
+            auth.login(&credentials("alice", "x")).await.unwrap_err(),
+            AuthError::WrongPassword
+        );
+        assert_eq!(
+            auth.login(&credentials("ALICE", "h"))
+                .await
+                .unwrap()
+                .username,
+            "Alice"
+        );
+        assert_eq!(
+            auth.login(&credentials("bob", "h")).await.unwrap_err(),
+            AuthError::UnknownUser
+        );
+        assert_eq!(
+            auth.signup(&credentials("no spaces", "h"))
+                .await
+                .unwrap_err(),
+            AuthError::InvalidName
+        );
+    }
+
+    #[tokio::test]
+    async fn refresh_tokens_are_single_use() {
+        let auth = Auth::new(b"secret".to_vec());
+        let tokens = auth.signup(&credentials("alice", "h")).await.unwrap();
+        let refreshed = auth.refresh(&tokens.refresh_token).unwrap();
+        assert_eq!(refreshed.username, "alice");
+        assert_eq!(
+            auth.refresh(&tokens.refresh_token).unwrap_err(),
+            AuthError::InvalidToken
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_refresh_tokens_are_forgotten() {
+        let auth = Auth::new(b"secret".to_vec());
+        let stale = auth.signup(&credentials("alice", "h")).await.unwrap();
+        auth.refresh_tokens
+            .lock()
+            .unwrap()
+            .get_mut(&stale.refresh_token)
+            .unwrap()
+            .expires_at = 0;
+        let fresh = auth.login(&credentials("alice", "h")).await.unwrap();
+        let kept = auth.refresh_tokens.lock().unwrap();
+        assert!(!kept.contains_key(&stale.refresh_token));
+        assert!(kept.contains_key(&fresh.refresh_token));
+    }
+}
@@ -0,0 +1,72 @@
+//! Words a room's moderators don't want said there. They are starred out of messages before
+//! anyone sees them, so the history never has them either.
+
+/// Most words a room's blacklist can have.
+const MAX_WORDS: usize = 100;
+const MAX_WORD_LEN: usize = 32;
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The words of a blacklist as a moderator gave them, lowercased and without repeats. Anything
+/// that isn't a single word is dropped, as it could never match.
+pub fn normalize(words: Vec<String>) -> Vec<String> {
+    let mut kept: Vec<String> = vec![];
+    for word in words.into_iter().map(|w| w.trim().to_lowercase()) {
+        let valid = !word.is_empty()
+            && word.chars().count() <= MAX_WORD_LEN
+            && word.chars().all(is_word_char);
+        if valid && !kept.contains(&word) && kept.len() < MAX_WORDS {
+            kept.push(word);
+        }
+    }
+    kept
+}
+
+/// `text` with each whole word of `words` replaced by as many stars, ignoring case, or `None`
+/// when there was nothing to mask.
+pub fn mask(text: &str, words: &[String]) -> Option<String> {
+    let mut masked = String::with_capacity(text.len());
+    let mut changed = false;
+    let mut rest = text;
+    while let Some(start) = rest.find(is_word_char) {
+        masked.push_str(&rest[..start]);
+        let from = &rest[start..];
+        let end = from.find(|c| !is_word_char(c)).unwrap_or(from.len());
+        let word = &from[..end];
+        if words.contains(&word.to_lowercase()) {
+            masked.push_str(&"*".repeat(word.chars().count()));
+            changed = true;
+        } else {
+            masked.push_str(word);
+        }
+        rest = &from[end..];
+    }
+    masked.push_str(rest);
+    changed.then_some(masked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_whole_words_only() {
+        let words = normalize(vec![
+            " Darn ".into(),
+            "heck".into(),
+            "two words".into(),
+            "darn".into(),
+        ]);
+        assert_eq!(words, ["darn", "heck"]);
+
+        assert_eq!(
+            mask("Darn it, what the HECK!", &words).as_deref(),
+            Some("**** it, what the ****!")
+        );
+        assert_eq!(mask("darned heckler", &words), None);
+        assert_eq!(mask("", &words), None);
+        assert_eq!(mask("ça darn ça", &words).as_deref(), Some("ça **** ça"));
+    }
+}
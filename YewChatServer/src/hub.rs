@@ -0,0 +1,3186 @@
+//! Who is connected, which room they're in, and what was said there recently.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use rand::RngCore;
+use serde_json::Value;
+use subtle::ConstantTimeEq;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::auth::{self, Identity};
+use crate::blacklist;
+use crate::now_ms;
+use crate::protocol::{
+    AdminRoom, AdminStats, AdminUser, Announcement, CustomEmoji, DirectMessage, EmojiChange,
+    HistoryPage, Invite, JoinRefused, Kicked, ListedRoom, Location, MessageData, Moderation,
+    ModerationAction, MsgTypes, Permissions, PinnedMessage, Profile, Refusal, ReportedMessage,
+    Restrictions, Role, RoleChange, RoomDeleted, RoomEmoji, RoomInfo, RoomRoles, RoomUpdate,
+    Throughput, UserPayload, WebSocketMessage, Webhook,
+};
+
+pub const DEFAULT_ROOM: &str = "general";
+
+/// How many of the kept messages are replayed on entering a room. Older ones are paged in over
+/// HTTP on request.
+const REPLAY_LEN: usize = 50;
+
+/// How many custom emoji a room can have.
+const MAX_EMOJI: usize = 50;
+
+/// Largest custom emoji image, as a data URL. Every client in the room is sent all of them.
+const MAX_EMOJI_BYTES: usize = 64 * 1024;
+/// Longest a room's icon can be when it is text rather than an image, enough for any emoji.
+const MAX_ICON_TEXT_BYTES: usize = 32;
+/// Longest name a room can be renamed to.
+const MAX_ROOM_NAME_LEN: usize = 32;
+/// Longest a room's topic can be.
+const MAX_TOPIC_LEN: usize = 200;
+/// How many roles of its own a room can have.
+const MAX_ROOM_ROLES: usize = 10;
+/// Longest name a room's role can have.
+const MAX_ROLE_NAME_LEN: usize = 32;
+
+const MINUTE_MS: u64 = 60 * 1000;
+const HOUR_MS: u64 = 60 * MINUTE_MS;
+
+/// How long an invite code lets people in, unless the owner makes a new one first.
+const INVITE_TTL_MS: u64 = 7 * 24 * HOUR_MS;
+/// Longest a room's password can be.
+const MAX_ROOM_PASSWORD_LEN: usize = 128;
+/// How many wrong passwords someone can give for a room before they have to wait.
+const MAX_PASSWORD_ATTEMPTS: u32 = 5;
+/// How long they wait, counted from the first wrong one.
+const PASSWORD_LOCKOUT_MS: u64 = 10 * MINUTE_MS;
+
+/// Longest an announcement can stay pinned.
+const MAX_PIN_MINUTES: u64 = 24 * 60;
+/// How many messages a room can have pinned, unless the server is told otherwise.
+const DEFAULT_PIN_LIMIT: usize = 5;
+/// Longest wait slow mode can impose between someone's messages.
+const MAX_SLOW_MODE_SECONDS: u64 = 60 * 60;
+
+/// Identifies one WebSocket connection.
+pub type ConnId = u64;
+
+/// Outgoing frames for a connection, written to its socket by a task of its own.
+pub type Outbox = UnboundedSender<String>;
+
+struct Member {
+    conn: ConnId,
+    nick: String,
+    avatar: Option<String>,
+    gravatar: Option<String>,
+    /// Public ECDH key for end-to-end encrypted direct messages.
+    public_key: Option<String>,
+    /// Idle for a while, by their client's reckoning.
+    away: bool,
+    /// In the voice channel of their room.
+    voice: bool,
+    room: String,
+    /// The room they were in before this one, where they go back to if this one is deleted.
+    previous: Option<String>,
+    outbox: Outbox,
+    /// When they last posted a message, for slow mode.
+    last_posted: u64,
+}
+
+impl Member {
+    fn payload<'a>(&'a self, role: Option<Role>, room_role: Option<&'a str>) -> UserPayload<'a> {
+        UserPayload {
+            name: &self.nick,
+            avatar: self.avatar.as_deref(),
+            gravatar: self.gravatar.as_deref(),
+            key: self.public_key.as_deref(),
+            away: self.away.then_some(true),
+            voice: self.voice.then_some(true),
+            role,
+            room_role,
+        }
+    }
+}
+
+/// Where someone asks to be on registering: a room, or else the default one, with the invite code
+/// they were given for it and the password they typed for it.
+#[derive(Default)]
+pub struct Entry {
+    pub room: Option<String>,
+    pub invite: Option<String>,
+    pub password: Option<RoomKey>,
+}
+
+/// An invite code of a room. Codes are kept once revoked or expired, so whoever still has one can
+/// be told why it no longer works.
+struct InviteCode {
+    room: String,
+    expires_at: u64,
+    revoked: bool,
+}
+
+/// A room password as given, hashed with scrypt as account passwords are. That takes a while, so
+/// it is done before the hub is locked, with the salt of the room's password at the time.
+#[derive(Clone, Copy)]
+pub struct RoomKey {
+    salt: [u8; 16],
+    hash: [u8; 32],
+}
+
+impl RoomKey {
+    /// Hashes `password` with `salt`, or a new one for a password being set.
+    pub async fn new(password: &str, salt: Option<[u8; 16]>) -> Result<Self, &'static str> {
+        if !(1..=MAX_ROOM_PASSWORD_LEN).contains(&password.chars().count()) {
+            return Err("room passwords are 1-128 characters");
+        }
+        let salt = salt.unwrap_or_else(|| {
+            let mut salt = [0; 16];
+            rand::thread_rng().fill_bytes(&mut salt);
+            salt
+        });
+        let hash = auth::hash_password(password, salt).await;
+        Ok(Self { salt, hash })
+    }
+}
+
+/// A room's password, salted and hashed, with whom it let in, who needn't give it again until it
+/// changes.
+struct RoomPassword {
+    key: RoomKey,
+    admitted: HashSet<String>,
+}
+
+impl RoomPassword {
+    /// Keys hashed with an older password's salt don't match, even if they were right then.
+    fn matches(&self, given: &RoomKey) -> bool {
+        given.salt == self.key.salt && bool::from(given.hash.ct_eq(&self.key.hash))
+    }
+}
+
+/// Wrong passwords someone gave for a room, since the first of them.
+struct Attempts {
+    failures: u32,
+    since: u64,
+}
+
+/// A kept message that someone reported, waiting for a moderator.
+struct Report {
+    id: String,
+    /// In the order they reported it.
+    reporters: Vec<String>,
+}
+
+pub struct Hub {
+    /// In order of registration, which is also the order of the user list.
+    members: Vec<Member>,
+    /// The last `history_len` `message` frames of each room, replayed to whoever enters it.
+    history: HashMap<String, VecDeque<String>>,
+    history_len: usize,
+    /// Who first entered each room, the only one who may change its custom emoji.
+    owners: HashMap<String, String>,
+    /// Each room's custom emoji, as image data URLs by name.
+    emoji: HashMap<String, BTreeMap<String, String>>,
+    started_at: u64,
+    /// When each message of the last hour was posted, for the admin view.
+    posted: VecDeque<u64>,
+    posted_total: u64,
+    /// How many messages were posted in each room, for the room directory.
+    posted_in: HashMap<String, u64>,
+    /// Sent to everyone who registers: the server's rules, links and the like.
+    welcome: Option<String>,
+    /// Seconds each member of a room has to wait between messages, for rooms in slow mode.
+    slow_mode: HashMap<String, u64>,
+    /// Accounts that moderate every room, besides each room's owner.
+    admins: HashSet<String>,
+    /// Each room's reported messages, oldest report first.
+    reports: HashMap<String, Vec<Report>>,
+    /// Who may no longer post in each room.
+    banned: HashMap<String, HashSet<String>>,
+    /// Words starred out of each room's messages.
+    blacklists: HashMap<String, Vec<String>>,
+    /// What each room doesn't allow in messages, for rooms that refuse anything.
+    restrictions: HashMap<String, Restrictions>,
+    /// Each room's pinned messages, in the order shown.
+    pins: HashMap<String, Vec<PinnedMessage>>,
+    /// How many messages a room can have pinned; pinning more drops the one pinned longest ago.
+    pin_limit: usize,
+    /// Each room's icon, for rooms that have one.
+    icons: HashMap<String, String>,
+    /// What each room is about, for rooms that say.
+    topics: HashMap<String, String>,
+    /// The invite-only rooms, with whom each let in, who may come back without a code.
+    invite_only: HashMap<String, HashSet<String>>,
+    /// The invite codes of the invite-only rooms, by code.
+    invites: HashMap<String, InviteCode>,
+    /// The passwords of the password-protected rooms.
+    passwords: HashMap<String, RoomPassword>,
+    /// Wrong passwords given lately, by name and room.
+    attempts: HashMap<(String, String), Attempts>,
+    /// Each room's own roles, for rooms whose owner set some up or changed what everyone may do.
+    room_roles: HashMap<String, RoomRoles>,
+}
+
+impl Hub {
+    pub fn new(history_len: usize) -> Self {
+        Self {
+            members: vec![],
+            history: HashMap::new(),
+            history_len,
+            owners: HashMap::new(),
+            emoji: HashMap::new(),
+            started_at: now_ms(),
+            posted: VecDeque::new(),
+            posted_total: 0,
+            posted_in: HashMap::new(),
+            welcome: None,
+            slow_mode: HashMap::new(),
+            admins: HashSet::new(),
+            reports: HashMap::new(),
+            banned: HashMap::new(),
+            blacklists: HashMap::new(),
+            restrictions: HashMap::new(),
+            pins: HashMap::new(),
+            pin_limit: DEFAULT_PIN_LIMIT,
+            icons: HashMap::new(),
+            topics: HashMap::new(),
+            invite_only: HashMap::new(),
+            invites: HashMap::new(),
+            passwords: HashMap::new(),
+            attempts: HashMap::new(),
+            room_roles: HashMap::new(),
+        }
+    }
+
+    /// Sets the message of the day sent on registering, or stops sending one.
+    pub fn set_welcome(&mut self, welcome: Option<String>) {
+        self.welcome = welcome;
+    }
+
+    /// Sets who moderates every room, who get reports wherever they are.
+    pub fn set_admins(&mut self, admins: HashSet<String>) {
+        self.admins = admins;
+    }
+
+    /// Sets how many messages each room can have pinned, at least one.
+    pub fn set_pin_limit(&mut self, limit: usize) {
+        self.pin_limit = limit.max(1);
+    }
+
+    pub fn register(
+        &mut self,
+        conn: ConnId,
+        identity: Identity,
+        public_key: Option<String>,
+        entry: Entry,
+        take_over: bool,
+        outbox: Outbox,
+    ) {
+        let mut room = entry.room.unwrap_or_else(|| DEFAULT_ROOM.to_string());
+        // One connection per name: a second one is refused unless it asks to take over.
+        let elsewhere = self
+            .members
+            .iter()
+            .position(|m| m.nick == identity.username && m.conn != conn);
+        if let Some(at) = elsewhere {
+            if !take_over {
+                let _ = outbox.send(WebSocketMessage::new(MsgTypes::Conflict).to_text());
+                return;
+            }
+            let replaced = self.members.remove(at);
+            let _ = replaced
+                .outbox
+                .send(WebSocketMessage::new(MsgTypes::Replaced).to_text());
+            if replaced.room != room {
+                self.broadcast_users(&replaced.room);
+            }
+        }
+        // Rooms that won't let them in leave them in the default one, which is open to everyone.
+        let (invite, password) = (entry.invite.as_deref(), entry.password.as_ref());
+        if let Err(reason) = self.let_in(&identity.username, &room, invite, password) {
+            let _ =
+                outbox.send(self.refused_frame(&identity.username, &room, reason, DEFAULT_ROOM));
+            room = DEFAULT_ROOM.to_string();
+        }
+        // Registering twice on one connection just updates the entry.
+        self.members.retain(|m| m.conn != conn);
+        self.owners
+            .entry(room.clone())
+            .or_insert_with(|| identity.username.clone());
+        self.members.push(Member {
+            conn,
+            nick: identity.username,
+            avatar: identity.avatar,
+            gravatar: None,
+            public_key,
+            away: false,
+            voice: false,
+            room: room.clone(),
+            previous: None,
+            outbox: outbox.clone(),
+            last_posted: 0,
+        });
+        if let Some(welcome) = &self.welcome {
+            let _ = outbox.send(
+                WebSocketMessage {
+                    data: Some(welcome.clone()),
+                    ..WebSocketMessage::new(MsgTypes::Welcome)
+                }
+                .to_text(),
+            );
+        }
+        self.broadcast_users(&room);
+        self.replay_history(conn);
+        self.tell_if_banned(conn);
+    }
+
+    /// Forgets the connection's member, if it registered. Called on `unregister` and disconnect.
+    pub fn leave(&mut self, conn: ConnId) {
+        if let Some(at) = self.members.iter().position(|m| m.conn == conn) {
+            let member = self.members.remove(at);
+            self.broadcast_users(&member.room);
+        }
+    }
+
+    /// Moves the member to `room`, unless it is invite-only or password-protected and won't let
+    /// them in, in which case they are told why and sent the room they stay in again, as their
+    /// client already left it.
+    pub fn join(
+        &mut self,
+        conn: ConnId,
+        room: String,
+        invite: Option<&str>,
+        password: Option<&RoomKey>,
+    ) {
+        let Some(member) = self.member(conn) else {
+            return;
+        };
+        if member.room == room {
+            return;
+        }
+        let (nick, current) = (member.nick.clone(), member.room.clone());
+        if let Err(reason) = self.let_in(&nick, &room, invite, password) {
+            let frame = self.refused_frame(&nick, &room, reason, &current);
+            if let Some(member) = self.member(conn) {
+                let _ = member.outbox.send(frame);
+            }
+            self.broadcast_users(&current);
+            self.replay_history(conn);
+            self.tell_if_banned(conn);
+            return;
+        }
+        let Some(member) = self.member_mut(conn) else {
+            return;
+        };
+        let previous = std::mem::replace(&mut member.room, room.clone());
+        member.previous = Some(previous.clone());
+        member.voice = false;
+        self.owners.entry(room.clone()).or_insert(nick);
+        self.broadcast_users(&previous);
+        self.broadcast_users(&room);
+        self.replay_history(conn);
+        self.tell_if_banned(conn);
+    }
+
+    /// Shows the member's Gravatar to their room. Anything but a hash clears it.
+    pub fn set_gravatar(&mut self, conn: ConnId, hash: Option<String>) {
+        let Some(member) = self.member_mut(conn) else {
+            return;
+        };
+        member.gravatar = hash
+            .filter(|h| h.len() == 64 && h.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')));
+        let room = member.room.clone();
+        self.broadcast_users(&room);
+    }
+
+    /// Shows the member as away or back. Only changes reach the room, so a client announcing the
+    /// same thing twice doesn't make the user list flicker.
+    pub fn set_away(&mut self, conn: ConnId, away: bool) {
+        let Some(member) = self.member_mut(conn) else {
+            return;
+        };
+        if member.away == away {
+            return;
+        }
+        member.away = away;
+        let room = member.room.clone();
+        self.broadcast_users(&room);
+    }
+
+    /// Puts the member in their room's voice channel or takes them out of it, which the room sees
+    /// in its user list. Speaking takes a role that may post, and not being banned from posting.
+    pub fn set_voice(&mut self, conn: ConnId, voice: bool) -> Result<(), &'static str> {
+        let member = self.member(conn).ok_or("not registered")?;
+        if voice && (!self.permissions(member).post || self.is_banned(member)) {
+            return Err("they may not post in the room, so can't speak");
+        }
+        let member = self.member_mut(conn).ok_or("not registered")?;
+        if member.voice == voice {
+            return Ok(());
+        }
+        member.voice = voice;
+        let room = member.room.clone();
+        self.broadcast_users(&room);
+        Ok(())
+    }
+
+    /// The custom emoji of the connection's room, as an `emoji` frame.
+    pub fn room_emoji(&self, conn: ConnId) -> Option<WebSocketMessage> {
+        self.member(conn).map(|m| self.emoji_frame(&m.room))
+    }
+
+    /// Adds, replaces or removes one of the room's custom emoji, which only its owner may do.
+    /// Everyone in the room is sent the new set.
+    pub fn set_emoji(&mut self, conn: ConnId, change: EmojiChange) -> Result<(), &'static str> {
+        let member = self.member(conn).ok_or("not registered")?;
+        let room = member.room.clone();
+        if self.owners.get(&room) != Some(&member.nick) {
+            return Err("only the room's owner can change its emoji");
+        }
+        let valid_name = (1..=32).contains(&change.name.len())
+            && change
+                .name
+                .bytes()
+                .all(|b| matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' | b'+'));
+        if !valid_name {
+            return Err("emoji names are 1-32 lowercase letters, digits, _, - or +");
+        }
+        let emoji = self.emoji.entry(room.clone()).or_default();
+        match change.image {
+            None => {
+                emoji.remove(&change.name);
+            }
+            Some(image) => {
+                if !image.starts_with("data:image/") || image.len() > MAX_EMOJI_BYTES {
+                    return Err("emoji must be images of at most 64 KB");
+                }
+                if !emoji.contains_key(&change.name) && emoji.len() >= MAX_EMOJI {
+                    return Err("the room has as many emoji as it can");
+                }
+                emoji.insert(change.name, image);
+            }
+        }
+        self.broadcast(&room, &self.emoji_frame(&room).to_text());
+        Ok(())
+    }
+
+    /// Posts a message to the sender's room, unless they were banned from it, their role there
+    /// can't post, slow mode says it is too soon, or it has something the room doesn't allow.
+    /// Returns the text as posted, with the room's blacklisted words masked.
+    pub fn message(
+        &mut self,
+        conn: ConnId,
+        text: &str,
+        attachment: Option<&Value>,
+    ) -> Result<String, &'static str> {
+        let sender = self.member(conn).ok_or("not registered")?;
+        if self.is_banned(sender) {
+            return Err("banned from the room");
+        }
+        if !self.permissions(sender).post {
+            return Err("their role in the room can't post");
+        }
+        let now = now_ms();
+        let wait = self.slow_mode.get(&sender.room).copied().unwrap_or(0) * 1000;
+        if sender.last_posted > 0 && now < sender.last_posted + wait {
+            return Err("slow mode: too soon after the last message");
+        }
+        self.check_restrictions(sender, text, attachment)?;
+        let text = self.mask(sender, text);
+        let frame = WebSocketMessage::with_payload(
+            MsgTypes::Message,
+            &MessageData {
+                id: &auth::random_hex(8),
+                from: &sender.nick,
+                message: &text,
+                attachment,
+                time: now,
+                bot: None,
+                to: None,
+                announcement: false,
+                pinned_until: None,
+                system: false,
+            },
+        )
+        .to_text();
+        let room = sender.room.clone();
+        self.publish(&room, frame);
+        if let Some(sender) = self.member_mut(conn) {
+            sender.last_posted = now;
+        }
+        Ok(text)
+    }
+
+    /// The slow mode of the connection's room, as a `slowmode` frame.
+    pub fn slow_mode(&self, conn: ConnId) -> Option<WebSocketMessage> {
+        self.member(conn).map(|m| self.slow_mode_frame(&m.room))
+    }
+
+    /// Makes everyone in the sender's room wait `seconds` between messages, or no longer for 0.
+    /// Only the room's owner and administrators, told by `admin`, may.
+    pub fn set_slow_mode(
+        &mut self,
+        conn: ConnId,
+        admin: bool,
+        seconds: u64,
+    ) -> Result<(), &'static str> {
+        let sender = self.member(conn).ok_or("not registered")?;
+        if !admin && self.owners.get(&sender.room) != Some(&sender.nick) {
+            return Err("only the room's owner or an administrator can change slow mode");
+        }
+        let room = sender.room.clone();
+        match seconds.min(MAX_SLOW_MODE_SECONDS) {
+            0 => self.slow_mode.remove(&room),
+            seconds => self.slow_mode.insert(room.clone(), seconds),
+        };
+        self.broadcast(&room, &self.slow_mode_frame(&room).to_text());
+        Ok(())
+    }
+
+    /// The blacklist of the connection's room, as a `blacklist` frame.
+    pub fn blacklist(&self, conn: ConnId) -> Option<WebSocketMessage> {
+        self.member(conn).map(|m| self.blacklist_frame(&m.room))
+    }
+
+    /// Replaces the blacklist of the sender's room, which only its owner and administrators may.
+    /// Everyone in the room is sent the new one.
+    pub fn set_blacklist(&mut self, conn: ConnId, words: Vec<String>) -> Result<(), &'static str> {
+        let sender = self.member(conn).ok_or("not registered")?;
+        if !self.is_moderator(sender) {
+            return Err("only the room's owner or an administrator can change the blacklist");
+        }
+        let room = sender.room.clone();
+        let words = blacklist::normalize(words);
+        if words.is_empty() {
+            self.blacklists.remove(&room);
+        } else {
+            self.blacklists.insert(room.clone(), words);
+        }
+        self.broadcast(&room, &self.blacklist_frame(&room).to_text());
+        Ok(())
+    }
+
+    /// What the connection's room doesn't allow, as a `restrictions` frame.
+    pub fn restrictions(&self, conn: ConnId) -> Option<WebSocketMessage> {
+        self.member(conn).map(|m| self.restrictions_frame(&m.room))
+    }
+
+    /// Replaces what the sender's room doesn't allow, which only its owner and administrators
+    /// may. Everyone in the room is sent the new restrictions.
+    pub fn set_restrictions(
+        &mut self,
+        conn: ConnId,
+        restrictions: Restrictions,
+    ) -> Result<(), &'static str> {
+        let sender = self.member(conn).ok_or("not registered")?;
+        if !self.is_moderator(sender) {
+            return Err("only the room's owner or an administrator can change the restrictions");
+        }
+        let room = sender.room.clone();
+        if restrictions == Restrictions::default() {
+            self.restrictions.remove(&room);
+        } else {
+            self.restrictions.insert(room.clone(), restrictions);
+        }
+        self.broadcast(&room, &self.restrictions_frame(&room).to_text());
+        Ok(())
+    }
+
+    /// The settings of the connection's room, as a `roomupdate` frame.
+    pub fn room_update(&self, conn: ConnId) -> Option<WebSocketMessage> {
+        self.member(conn).map(|m| self.room_update_frame(&m.room))
+    }
+
+    /// Replaces the settings of the sender's room, which only its owner may do. Everyone in the
+    /// room is sent the new settings. A new name moves the room, with all it has, and is recorded
+    /// in it as a system message; administrators are sent the new state of the server. Making the
+    /// room invite-only lets in everyone already in it, and sends the owner its code; opening it
+    /// again revokes its codes.
+    pub fn update_room(&mut self, conn: ConnId, update: RoomUpdate) -> Result<(), &'static str> {
+        let member = self.member(conn).ok_or("not registered")?;
+        let mut room = member.room.clone();
+        let nick = member.nick.clone();
+        if self.owners.get(&room) != Some(&nick) {
+            return Err("only the room's owner can change its settings");
+        }
+        let icon = update.icon.filter(|icon| !icon.is_empty());
+        if let Some(icon) = &icon {
+            let valid = if icon.starts_with("data:") {
+                icon.starts_with("data:image/") && icon.len() <= MAX_EMOJI_BYTES
+            } else {
+                icon.len() <= MAX_ICON_TEXT_BYTES && !icon.contains(char::is_whitespace)
+            };
+            if !valid {
+                return Err("room icons are an emoji or an image of at most 64 KB");
+            }
+        }
+        let topic = update
+            .topic
+            .map(|topic| topic.trim().to_string())
+            .filter(|topic| !topic.is_empty());
+        if topic
+            .as_ref()
+            .is_some_and(|topic| topic.chars().count() > MAX_TOPIC_LEN || topic.contains('\n'))
+        {
+            return Err("room topics are a line of at most 200 characters");
+        }
+        let renamed = update.name.filter(|name| *name != room);
+        if let Some(name) = &renamed {
+            let valid = (1..=MAX_ROOM_NAME_LEN).contains(&name.chars().count())
+                && name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                return Err("room names are 1-32 letters, digits, - or _");
+            }
+            if room == DEFAULT_ROOM {
+                return Err("the default room can't be renamed");
+            }
+            if self.in_use(name) {
+                return Err("there is a room of that name already");
+            }
+        }
+        if update.invite_only && room == DEFAULT_ROOM {
+            return Err("the default room is open to everyone");
+        }
+        match icon {
+            Some(icon) => self.icons.insert(room.clone(), icon),
+            None => self.icons.remove(&room),
+        };
+        match topic {
+            Some(topic) => self.topics.insert(room.clone(), topic),
+            None => self.topics.remove(&room),
+        };
+        let closing = update.invite_only && !self.invite_only.contains_key(&room);
+        if closing {
+            let inside = self
+                .members
+                .iter()
+                .filter(|m| m.room == room)
+                .map(|m| m.nick.clone())
+                .collect();
+            self.invite_only.insert(room.clone(), inside);
+        } else if !update.invite_only && self.invite_only.remove(&room).is_some() {
+            self.revoke_invites(&room);
+        }
+        if let Some(name) = renamed {
+            self.move_room(&room, Some(&name));
+            let text = format!("{} renamed #{} to #{}", nick, room, name);
+            room = name;
+            self.broadcast(&room, &self.room_update_frame(&room).to_text());
+            self.publish(&room, system_frame(&text));
+            self.send_stats_to_admins();
+        } else {
+            self.broadcast(&room, &self.room_update_frame(&room).to_text());
+        }
+        if closing {
+            let frame = self.invite(conn)?;
+            if let Some(member) = self.member(conn) {
+                let _ = member.outbox.send(frame.to_text());
+            }
+        }
+        Ok(())
+    }
+
+    /// The invite code of the sender's invite-only room, as an `invite` frame, which only its
+    /// owner and those whose role may invite may have. A new one is made when the last one
+    /// expired.
+    pub fn invite(&mut self, conn: ConnId) -> Result<WebSocketMessage, &'static str> {
+        let member = self.member(conn).ok_or("not registered")?;
+        if !self.permissions(member).invite {
+            return Err("their role in the room can't have its invite code");
+        }
+        if !self.invite_only.contains_key(&member.room) {
+            return Err("the room is open to everyone");
+        }
+        let room = member.room.clone();
+        let now = now_ms();
+        let live = self
+            .invites
+            .iter()
+            .find(|(_, i)| i.room == room && !i.revoked && i.expires_at > now)
+            .map(|(code, _)| code.clone());
+        let code = match live {
+            Some(code) => code,
+            None => self.new_invite(&room),
+        };
+        Ok(self.invite_frame(&code))
+    }
+
+    /// Revokes the invite codes of the sender's invite-only room, which only its owner may do, and
+    /// makes a new one, as an `invite` frame. Those let in with the old codes stay let in.
+    pub fn regenerate_invite(&mut self, conn: ConnId) -> Result<WebSocketMessage, &'static str> {
+        let room = self.owned_invite_only_room(conn)?;
+        self.revoke_invites(&room);
+        let code = self.new_invite(&room);
+        Ok(self.invite_frame(&code))
+    }
+
+    /// Sets the password of the sender's room, or removes it, which only its owner may do.
+    /// Everyone already in the room is let in under the new one; everyone else has to give it.
+    /// The room is sent its settings, which say there is a password but not what it is.
+    pub fn set_room_password(
+        &mut self,
+        conn: ConnId,
+        password: Option<RoomKey>,
+    ) -> Result<(), &'static str> {
+        let member = self.member(conn).ok_or("not registered")?;
+        let room = member.room.clone();
+        if self.owners.get(&room) != Some(&member.nick) {
+            return Err("only the room's owner can change its password");
+        }
+        match password {
+            Some(key) => {
+                if room == DEFAULT_ROOM {
+                    return Err("the default room is open to everyone");
+                }
+                let inside = self
+                    .members
+                    .iter()
+                    .filter(|m| m.room == room)
+                    .map(|m| m.nick.clone())
+                    .collect();
+                let lock = RoomPassword {
+                    key,
+                    admitted: inside,
+                };
+                self.passwords.insert(room.clone(), lock);
+            }
+            None => {
+                self.passwords.remove(&room);
+            }
+        }
+        self.attempts.retain(|(_, r), _| *r != room);
+        self.broadcast(&room, &self.room_update_frame(&room).to_text());
+        Ok(())
+    }
+
+    /// The salt of `room`'s password, if it has one, to hash a password given for it with.
+    pub fn password_salt(&self, room: &str) -> Option<[u8; 16]> {
+        self.passwords.get(room).map(|lock| lock.key.salt)
+    }
+
+    /// Deletes the sender's room, which only its owner may do, once they confirm by giving its
+    /// name. Everything it had is forgotten. Everyone in it is told, and taken back to the room
+    /// they were in before, if it would still let them in, or else the default one.
+    pub fn delete_room(&mut self, conn: ConnId, confirmation: &str) -> Result<(), &'static str> {
+        let member = self.member(conn).ok_or("not registered")?;
+        let room = member.room.clone();
+        let owner = member.nick.clone();
+        if self.owners.get(&room) != Some(&owner) {
+            return Err("only the room's owner can delete it");
+        }
+        if confirmation != room {
+            return Err("the name given isn't the room's");
+        }
+        if room == DEFAULT_ROOM {
+            return Err("the default room can't be deleted");
+        }
+        self.move_room(&room, None);
+        let inside: Vec<ConnId> = self
+            .members
+            .iter()
+            .filter(|m| m.room == room)
+            .map(|m| m.conn)
+            .collect();
+        for conn in inside {
+            let Some(member) = self.member_mut(conn) else {
+                continue;
+            };
+            let previous = member.previous.take();
+            let nick = member.nick.clone();
+            let to = previous
+                .filter(|to| self.let_in(&nick, to, None, None).is_ok())
+                .unwrap_or_else(|| DEFAULT_ROOM.to_string());
+            let Some(member) = self.member_mut(conn) else {
+                continue;
+            };
+            let deleted = RoomDeleted {
+                room: &room,
+                by: &owner,
+                to: &to,
+            };
+            let _ = member
+                .outbox
+                .send(WebSocketMessage::with_payload(MsgTypes::RoomDeleted, &deleted).to_text());
+            member.room = to.clone();
+            member.voice = false;
+            self.owners.entry(to.clone()).or_insert(nick);
+            self.broadcast_users(&to);
+            self.replay_history(conn);
+            self.tell_if_banned(conn);
+            // Only for them, after the history: it isn't news to the room they land in.
+            let text = format!("{} deleted #{}, so you are back in #{}.", owner, room, to);
+            if let Some(member) = self.member(conn) {
+                let _ = member.outbox.send(system_frame(&text));
+            }
+        }
+        self.send_stats_to_admins();
+        Ok(())
+    }
+
+    /// Hands the sender's room to `to`, who must be in it, which only its owner may do. Everyone
+    /// in the room is told, and sent the members with their new roles.
+    pub fn transfer(&mut self, conn: ConnId, to: &str) -> Result<(), &'static str> {
+        let member = self.member(conn).ok_or("not registered")?;
+        let room = member.room.clone();
+        let owner = member.nick.clone();
+        if self.owners.get(&room) != Some(&owner) {
+            return Err("only the room's owner can hand it over");
+        }
+        if to == owner || !self.members.iter().any(|m| m.room == room && m.nick == to) {
+            return Err("rooms can only be handed to someone else in them");
+        }
+        self.owners.insert(room.clone(), to.to_string());
+        let change = RoleChange {
+            owner: to,
+            previous: &owner,
+        };
+        self.broadcast(
+            &room,
+            &WebSocketMessage::with_payload(MsgTypes::RoleChange, &change).to_text(),
+        );
+        self.broadcast_users(&room);
+        let text = format!("{} handed #{} to {}", owner, room, to);
+        self.publish(&room, system_frame(&text));
+        Ok(())
+    }
+
+    /// The roles of the connection's room, as a `roomroles` frame.
+    pub fn room_roles(&self, conn: ConnId) -> Option<WebSocketMessage> {
+        self.member(conn).map(|m| self.room_roles_frame(&m.room))
+    }
+
+    /// Replaces the roles of the sender's room, which only its owner may do. Names are trimmed,
+    /// and members given a role the room doesn't have are left without. Everyone in the room is
+    /// sent the new roles, and the members with theirs.
+    pub fn set_room_roles(
+        &mut self,
+        conn: ConnId,
+        mut roles: RoomRoles,
+    ) -> Result<(), &'static str> {
+        let member = self.member(conn).ok_or("not registered")?;
+        let room = member.room.clone();
+        if self.owners.get(&room) != Some(&member.nick) {
+            return Err("only the room's owner can change its roles");
+        }
+        if roles.roles.len() > MAX_ROOM_ROLES {
+            return Err("too many roles");
+        }
+        let mut names = HashSet::new();
+        for role in &mut roles.roles {
+            role.name = role.name.trim().to_string();
+            if role.name.is_empty() || role.name.chars().count() > MAX_ROLE_NAME_LEN {
+                return Err("role names are 1 to 32 characters");
+            }
+            if !names.insert(role.name.clone()) {
+                return Err("two roles have the same name");
+            }
+        }
+        roles.members.retain(|_, role| names.contains(role));
+        if roles == RoomRoles::default() {
+            self.room_roles.remove(&room);
+        } else {
+            self.room_roles.insert(room.clone(), roles);
+        }
+        self.broadcast(&room, &self.room_roles_frame(&room).to_text());
+        self.broadcast_users(&room);
+        Ok(())
+    }
+
+    /// Sends `nick` out of the sender's room, back to the default one, which takes a role that may
+    /// kick. Moderators can't be kicked, nor anyone out of the default room. An invite-only or
+    /// password-protected room takes a code or its password again to come back.
+    pub fn kick(&mut self, conn: ConnId, nick: &str) -> Result<(), &'static str> {
+        let sender = self.member(conn).ok_or("not registered")?;
+        if !self.permissions(sender).kick {
+            return Err("their role in the room can't kick");
+        }
+        let (room, by) = (sender.room.clone(), sender.nick.clone());
+        if room == DEFAULT_ROOM {
+            return Err("no one can be kicked out of the default room");
+        }
+        let kicked = self
+            .members
+            .iter()
+            .find(|m| m.room == room && m.nick == nick)
+            .ok_or("they aren't in the room")?;
+        if self.is_moderator(kicked) {
+            return Err("moderators can't be kicked");
+        }
+        let kicked = kicked.conn;
+        if let Some(admitted) = self.invite_only.get_mut(&room) {
+            admitted.remove(nick);
+        }
+        if let Some(password) = self.passwords.get_mut(&room) {
+            password.admitted.remove(nick);
+        }
+        let Some(member) = self.member_mut(kicked) else {
+            return Ok(());
+        };
+        member.room = DEFAULT_ROOM.to_string();
+        member.previous = None;
+        member.voice = false;
+        let frame = Kicked {
+            room: &room,
+            by: &by,
+            to: DEFAULT_ROOM,
+        };
+        let _ = member
+            .outbox
+            .send(WebSocketMessage::with_payload(MsgTypes::Kicked, &frame).to_text());
+        self.owners
+            .entry(DEFAULT_ROOM.to_string())
+            .or_insert_with(|| nick.to_string());
+        self.broadcast_users(&room);
+        self.broadcast_users(DEFAULT_ROOM);
+        self.replay_history(kicked);
+        self.tell_if_banned(kicked);
+        // Only for them, after the history: it isn't news to the room they land in.
+        let text = format!("{} sent you out of #{}.", by, room);
+        if let Some(member) = self.member(kicked) {
+            let _ = member.outbox.send(system_frame(&text));
+        }
+        let text = format!("{} sent {} out of the room", by, nick);
+        self.publish(&room, system_frame(&text));
+        Ok(())
+    }
+
+    /// The pinned messages of the connection's room, as a `pins` frame.
+    pub fn pins(&self, conn: ConnId) -> Option<WebSocketMessage> {
+        self.member(conn).map(|m| self.pins_frame(&m.room))
+    }
+
+    /// Pins a kept message of the sender's room above the others, which only its owner,
+    /// administrators and those whose role may pin may do. Past the pin limit, the message pinned
+    /// longest ago is unpinned.
+    pub fn pin(&mut self, conn: ConnId, id: &str) -> Result<(), &'static str> {
+        let sender = self.member(conn).ok_or("not registered")?;
+        if !self.permissions(sender).pin {
+            return Err("their role in the room can't pin messages");
+        }
+        let (room, pinned_by) = (sender.room.clone(), sender.nick.clone());
+        let (_, message) = self
+            .kept_message(&room, id)
+            .ok_or("no such message in the room")?;
+        let pins = self.pins.entry(room.clone()).or_default();
+        if pins.iter().any(|p| p.message["id"] == id) {
+            return Err("already pinned");
+        }
+        while pins.len() >= self.pin_limit {
+            let oldest = (0..pins.len()).min_by_key(|&at| pins[at].pinned_at);
+            pins.remove(oldest.unwrap_or_default());
+        }
+        pins.insert(
+            0,
+            PinnedMessage {
+                message,
+                pinned_by,
+                pinned_at: now_ms(),
+            },
+        );
+        self.broadcast(&room, &self.pins_frame(&room).to_text());
+        Ok(())
+    }
+
+    /// Puts the pins of the sender's room in the order of `ids`, unpinning those left out, which
+    /// only those who may pin may do. Ids of messages that aren't pinned are ignored.
+    pub fn arrange_pins(&mut self, conn: ConnId, ids: &[String]) -> Result<(), &'static str> {
+        let sender = self.member(conn).ok_or("not registered")?;
+        if !self.permissions(sender).pin {
+            return Err("their role in the room can't arrange the pins");
+        }
+        let room = sender.room.clone();
+        let mut pins = self.pins.remove(&room).unwrap_or_default();
+        let arranged: Vec<PinnedMessage> = ids
+            .iter()
+            .filter_map(|id| {
+                let at = pins.iter().position(|p| p.message["id"] == id.as_str())?;
+                Some(pins.remove(at))
+            })
+            .collect();
+        if !arranged.is_empty() {
+            self.pins.insert(room.clone(), arranged);
+        }
+        self.broadcast(&room, &self.pins_frame(&room).to_text());
+        Ok(())
+    }
+
+    /// Reports a kept message of the sender's room to its moderators. Reporting it again, or
+    /// reporting our own, changes nothing.
+    pub fn report(&mut self, conn: ConnId, id: &str) -> Result<(), &'static str> {
+        let sender = self.member(conn).ok_or("not registered")?;
+        let (_, message) = self
+            .kept_message(&sender.room, id)
+            .ok_or("no such message in the room")?;
+        if message["from"] == sender.nick.as_str() {
+            return Err("can't report one's own message");
+        }
+        let (room, nick) = (sender.room.clone(), sender.nick.clone());
+        // Reports of messages the history has since let go of can't be acted on.
+        let kept: HashSet<String> = self
+            .history
+            .get(&room)
+            .into_iter()
+            .flatten()
+            .filter_map(|frame| serde_json::from_str::<WebSocketMessage>(frame).ok()?.data)
+            .filter_map(|data| {
+                Some(
+                    serde_json::from_str::<Value>(&data).ok()?["id"]
+                        .as_str()?
+                        .to_string(),
+                )
+            })
+            .collect();
+        let reports = self.reports.entry(room.clone()).or_default();
+        reports.retain(|r| kept.contains(&r.id));
+        match reports.iter_mut().find(|r| r.id == id) {
+            Some(report) if report.reporters.contains(&nick) => return Ok(()),
+            Some(report) => report.reporters.push(nick),
+            None => reports.push(Report {
+                id: id.to_string(),
+                reporters: vec![nick],
+            }),
+        }
+        self.send_reports(&room);
+        Ok(())
+    }
+
+    /// The reports of the connection's room, as a `reports` frame, which is empty for anyone but
+    /// its moderators.
+    pub fn reports(&self, conn: ConnId) -> Option<WebSocketMessage> {
+        let member = self.member(conn)?;
+        Some(if self.is_moderator(member) {
+            self.reports_frame(&member.room)
+        } else {
+            WebSocketMessage::new(MsgTypes::Reports)
+        })
+    }
+
+    /// Settles a report of the sender's room, which only its owner and administrators may. A
+    /// deleted message is taken out of the history and off everyone's screen; a ban also stops its
+    /// sender posting in the room.
+    pub fn moderate(&mut self, conn: ConnId, moderation: &Moderation) -> Result<(), &'static str> {
+        let sender = self.member(conn).ok_or("not registered")?;
+        if !self.is_moderator(sender) {
+            return Err("only the room's owner or an administrator can moderate");
+        }
+        let room = sender.room.clone();
+        let at = self
+            .reports
+            .get(&room)
+            .and_then(|reports| reports.iter().position(|r| r.id == moderation.id))
+            .ok_or("no such report")?;
+        let author = self
+            .kept_message(&room, &moderation.id)
+            .and_then(|(_, m)| m["from"].as_str().map(str::to_string));
+        if moderation.action == ModerationAction::Ban {
+            let author = author.as_deref().ok_or("no such message in the room")?;
+            if self.admins.contains(author)
+                || self.owners.get(&room).map(String::as_str) == Some(author)
+            {
+                return Err("moderators can't be banned");
+            }
+        }
+        if let Some(reports) = self.reports.get_mut(&room) {
+            reports.remove(at);
+        }
+        if moderation.action != ModerationAction::Approve {
+            if let Some((at, _)) = self.kept_message(&room, &moderation.id) {
+                if let Some(history) = self.history.get_mut(&room) {
+                    history.remove(at);
+                }
+            }
+            let deleted = WebSocketMessage {
+                data: Some(moderation.id.clone()),
+                ..WebSocketMessage::new(MsgTypes::Deleted)
+            };
+            self.broadcast(&room, &deleted.to_text());
+            if let Some(pins) = self.pins.get_mut(&room) {
+                let before = pins.len();
+                pins.retain(|p| p.message["id"] != moderation.id.as_str());
+                if pins.len() < before {
+                    self.broadcast(&room, &self.pins_frame(&room).to_text());
+                }
+            }
+        }
+        if let (ModerationAction::Ban, Some(author)) = (moderation.action, author) {
+            self.broadcast(&room, &banned_frame(&author).to_text());
+            self.banned.entry(room.clone()).or_default().insert(author);
+        }
+        self.send_reports(&room);
+        Ok(())
+    }
+
+    /// Sends `text` to `to` alone, and back to the sender to show what they whispered. Only
+    /// someone in the sender's room can be whispered to, by those who may post there.
+    pub fn whisper(&self, conn: ConnId, to: &str, text: &str) -> Result<(), &'static str> {
+        let sender = self.member(conn).ok_or("not registered")?;
+        if self.is_banned(sender) {
+            return Err("banned from the room");
+        }
+        if !self.permissions(sender).post {
+            return Err("their role in the room can't post");
+        }
+        let recipient = self
+            .member_named(to)
+            .filter(|r| r.room == sender.room && r.conn != conn)
+            .ok_or("nobody by that name in the room")?;
+        let text = self.mask(sender, text);
+        let frame = WebSocketMessage::with_payload(
+            MsgTypes::Whisper,
+            &MessageData {
+                id: &auth::random_hex(8),
+                from: &sender.nick,
+                message: &text,
+                attachment: None,
+                time: now_ms(),
+                bot: None,
+                to: Some(&recipient.nick),
+                announcement: false,
+                pinned_until: None,
+                system: false,
+            },
+        )
+        .to_text();
+        let _ = recipient.outbox.send(frame.clone());
+        let _ = sender.outbox.send(frame);
+        Ok(())
+    }
+
+    /// Posts an announcement to the sender's room, pinned for a while if asked. Only the room's
+    /// owner and administrators, told by `admin`, may announce.
+    pub fn announce(
+        &mut self,
+        conn: ConnId,
+        admin: bool,
+        announcement: &Announcement,
+    ) -> Result<(), &'static str> {
+        let sender = self.member(conn).ok_or("not registered")?;
+        if !admin && self.owners.get(&sender.room) != Some(&sender.nick) {
+            return Err("only the room's owner or an administrator can announce");
+        }
+        if announcement.text.trim().is_empty() {
+            return Err("nothing to announce");
+        }
+        let now = now_ms();
+        let frame = WebSocketMessage::with_payload(
+            MsgTypes::Message,
+            &MessageData {
+                id: &auth::random_hex(8),
+                from: &sender.nick,
+                message: &announcement.text,
+                attachment: None,
+                time: now,
+                bot: None,
+                to: None,
+                announcement: true,
+                pinned_until: announcement
+                    .pin_minutes
+                    .map(|minutes| now + minutes.min(MAX_PIN_MINUTES) * MINUTE_MS),
+                system: false,
+            },
+        )
+        .to_text();
+        let room = sender.room.clone();
+        self.publish(&room, frame);
+        Ok(())
+    }
+
+    /// Posts a message from an integration. The room doesn't need anyone in it.
+    pub fn post_bot(&mut self, room: &str, hook: &Webhook) {
+        let frame = WebSocketMessage::with_payload(
+            MsgTypes::Message,
+            &MessageData {
+                id: &auth::random_hex(8),
+                from: &hook.bot,
+                message: &hook.text,
+                attachment: None,
+                time: now_ms(),
+                bot: Some(&hook.card),
+                to: None,
+                announcement: false,
+                pinned_until: None,
+                system: false,
+            },
+        )
+        .to_text();
+        self.publish(room, frame);
+    }
+
+    /// The name and room of whoever registered on `conn`.
+    pub fn whereabouts(&self, conn: ConnId) -> Option<(&str, &str)> {
+        self.member(conn)
+            .map(|m| (m.nick.as_str(), m.room.as_str()))
+    }
+
+    /// Whether `reader` may see what is said in `room` without being in it: always, unless it is
+    /// invite-only and didn't let them in, or has a password they never gave. Its owner and the
+    /// administrators always may.
+    fn may_read(&self, reader: &str, room: &str) -> bool {
+        let owns = self.owners.get(room).is_some_and(|owner| owner == reader);
+        let inside = self
+            .members
+            .iter()
+            .any(|m| m.room == room && m.nick == reader);
+        let invited = self
+            .invite_only
+            .get(room)
+            .is_none_or(|admitted| admitted.contains(reader));
+        let unlocked = self
+            .passwords
+            .get(room)
+            .is_none_or(|lock| lock.admitted.contains(reader));
+        owns || self.admins.contains(reader) || ((inside || invited) && unlocked)
+    }
+
+    /// Up to `limit` kept messages of `room` from just before the one with id `before`, or the
+    /// latest ones without it. Nothing at all if `reader` may not see them.
+    pub fn page(
+        &self,
+        reader: &str,
+        room: &str,
+        before: Option<&str>,
+        limit: usize,
+    ) -> Option<HistoryPage> {
+        if !self.may_read(reader, room) {
+            return None;
+        }
+        let kept: Vec<Value> = self
+            .history
+            .get(room)
+            .into_iter()
+            .flatten()
+            .filter_map(|frame| serde_json::from_str::<WebSocketMessage>(frame).ok()?.data)
+            .filter_map(|data| serde_json::from_str(&data).ok())
+            .collect();
+        let end = match before {
+            // Older than anything we kept, or never here: nothing before it.
+            Some(id) => kept.iter().position(|m| m["id"] == id).unwrap_or(0),
+            None => kept.len(),
+        };
+        let start = end.saturating_sub(limit);
+        Some(HistoryPage {
+            messages: kept[start..end].to_vec(),
+            more: start > 0,
+        })
+    }
+
+    /// Who is in `room` and what it keeps, unless `reader` may not see it.
+    pub fn room_info<'a>(&'a self, reader: &str, room: &'a str) -> Option<RoomInfo<'a>> {
+        if !self.may_read(reader, room) {
+            return None;
+        }
+        Some(RoomInfo {
+            name: room,
+            members: self
+                .members
+                .iter()
+                .filter(|m| m.room == room)
+                .map(|m| m.payload(self.role(m), self.room_role(m)))
+                .collect(),
+            kept: self.history.get(room).map_or(0, VecDeque::len),
+            icon: self.icons.get(room).map(String::as_str),
+        })
+    }
+
+    /// Everyone connected, every room in use and how busy they are, for administrators.
+    pub fn stats(&self) -> AdminStats<'_> {
+        let now = now_ms();
+        let rooms: BTreeSet<&str> = self
+            .members
+            .iter()
+            .map(|m| m.room.as_str())
+            .chain(self.history.keys().map(String::as_str))
+            .collect();
+        AdminStats {
+            users: self
+                .members
+                .iter()
+                .map(|m| AdminUser {
+                    name: &m.nick,
+                    room: &m.room,
+                })
+                .collect(),
+            rooms: rooms
+                .into_iter()
+                .map(|room| AdminRoom {
+                    name: room,
+                    members: self.members.iter().filter(|m| m.room == room).count(),
+                    kept: self.history.get(room).map_or(0, VecDeque::len),
+                    icon: self.icons.get(room).map(String::as_str),
+                })
+                .collect(),
+            messages: Throughput {
+                total: self.posted_total,
+                last_minute: self.posted.iter().filter(|&&t| t + MINUTE_MS > now).count(),
+                last_hour: self.posted.iter().filter(|&&t| t + HOUR_MS > now).count(),
+            },
+            started_at: self.started_at,
+        }
+    }
+
+    /// The rooms anyone can enter, busiest first: those with someone in them or something kept,
+    /// and the default room, leaving out the invite-only ones.
+    pub fn public_rooms(&self) -> Vec<ListedRoom<'_>> {
+        let rooms: BTreeSet<&str> = self
+            .members
+            .iter()
+            .map(|m| m.room.as_str())
+            .chain(self.history.keys().map(String::as_str))
+            .chain([DEFAULT_ROOM])
+            .filter(|room| !self.invite_only.contains_key(*room))
+            .collect();
+        let mut listed: Vec<_> = rooms
+            .into_iter()
+            .map(|room| ListedRoom {
+                name: room,
+                topic: self.topics.get(room).map(String::as_str),
+                icon: self.icons.get(room).map(String::as_str),
+                members: self.members.iter().filter(|m| m.room == room).count(),
+                posted: self.posted_in.get(room).copied().unwrap_or_default(),
+                password_protected: self.passwords.contains_key(room),
+            })
+            .collect();
+        // Stable, so rooms as busy as each other stay in order of name.
+        listed.sort_by_key(|room| std::cmp::Reverse(room.members));
+        listed
+    }
+
+    /// What we know of `nick` while they are connected.
+    pub fn profile(&self, nick: &str) -> Option<Profile<'_>> {
+        self.member_named(nick).map(|m| Profile {
+            name: &m.nick,
+            avatar: m.avatar.as_deref(),
+            room: Some(&m.room),
+        })
+    }
+
+    /// Passes an encrypted direct message on to both parties, along with the public keys it was
+    /// (supposedly) sealed with. We only ever see ciphertext, so these aren't kept in the history.
+    pub fn direct(&self, conn: ConnId, to: &str, payload: &str) {
+        let (Some(sender), Some(recipient)) = (self.member(conn), self.member_named(to)) else {
+            return;
+        };
+        let frame = WebSocketMessage::with_payload(
+            MsgTypes::Direct,
+            &DirectMessage {
+                from: &sender.nick,
+                to: &recipient.nick,
+                payload,
+                from_key: sender.public_key.as_deref(),
+                to_key: recipient.public_key.as_deref(),
+                time: now_ms(),
+            },
+        )
+        .to_text();
+        for m in &self.members {
+            if m.nick == sender.nick || m.nick == recipient.nick {
+                let _ = m.outbox.send(frame.clone());
+            }
+        }
+    }
+
+    /// Tells the rest of the sender's room where they are, if they may post there, or that they
+    /// stopped sharing it when `location` is `None`.
+    pub fn share_location(
+        &self,
+        conn: ConnId,
+        location: Option<Location>,
+    ) -> Result<(), &'static str> {
+        let sender = self.member(conn).ok_or("not registered")?;
+        if self.is_banned(sender) {
+            return Err("banned from the room");
+        }
+        if location.is_some() && !self.permissions(sender).post {
+            return Err("their role in the room can't post");
+        }
+        if location.is_some_and(|l| !l.is_valid()) {
+            return Err("not a place on Earth");
+        }
+        let frame = WebSocketMessage {
+            data: location.map(|l| serde_json::to_string(&l).unwrap()),
+            from: Some(sender.nick.clone()),
+            ..WebSocketMessage::new(MsgTypes::Location)
+        }
+        .to_text();
+        for m in &self.members {
+            if m.room == sender.room && m.conn != conn {
+                let _ = m.outbox.send(frame.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Call signaling is passed on verbatim; the media itself flows peer to peer.
+    pub fn relay(&self, conn: ConnId, message_type: MsgTypes, to: &str, data: Option<String>) {
+        let (Some(sender), Some(recipient)) = (self.member(conn), self.member_named(to)) else {
+            return;
+        };
+        let frame = WebSocketMessage {
+            data,
+            from: Some(sender.nick.clone()),
+            ..WebSocketMessage::new(message_type)
+        };
+        let _ = recipient.outbox.send(frame.to_text());
+    }
+
+    fn member(&self, conn: ConnId) -> Option<&Member> {
+        self.members.iter().find(|m| m.conn == conn)
+    }
+
+    fn member_mut(&mut self, conn: ConnId) -> Option<&mut Member> {
+        self.members.iter_mut().find(|m| m.conn == conn)
+    }
+
+    fn member_named(&self, nick: &str) -> Option<&Member> {
+        self.members.iter().find(|m| m.nick == nick)
+    }
+
+    /// Whether whoever registered on `conn` owns the room they are in or is an administrator.
+    pub fn moderates(&self, conn: ConnId) -> bool {
+        self.member(conn).is_some_and(|m| self.is_moderator(m))
+    }
+
+    fn is_moderator(&self, member: &Member) -> bool {
+        self.admins.contains(&member.nick) || self.owners.get(&member.room) == Some(&member.nick)
+    }
+
+    fn role(&self, member: &Member) -> Option<Role> {
+        if self.owners.get(&member.room) == Some(&member.nick) {
+            Some(Role::Owner)
+        } else if self.admins.contains(&member.nick) {
+            Some(Role::Moderator)
+        } else {
+            None
+        }
+    }
+
+    /// The name of the member's role of their room's own, if they were given one.
+    fn room_role(&self, member: &Member) -> Option<&str> {
+        let roles = self.room_roles.get(&member.room)?;
+        roles.role_of(&member.nick).map(|r| r.name.as_str())
+    }
+
+    /// What the member may do in their room: everything for its moderators, else what their role
+    /// there, or everyone's, allows.
+    fn permissions(&self, member: &Member) -> Permissions {
+        if self.is_moderator(member) {
+            return Permissions::ALL;
+        }
+        self.room_roles
+            .get(&member.room)
+            .map_or_else(Permissions::default, |roles| {
+                roles.permissions_of(&member.nick)
+            })
+    }
+
+    fn is_banned(&self, member: &Member) -> bool {
+        self.banned
+            .get(&member.room)
+            .is_some_and(|banned| banned.contains(&member.nick))
+    }
+
+    /// The text with the blacklisted words of the member's room masked. If there were any, the
+    /// member is warned.
+    fn mask(&self, member: &Member, text: &str) -> String {
+        let words = self
+            .blacklists
+            .get(&member.room)
+            .map_or(&[][..], Vec::as_slice);
+        match blacklist::mask(text, words) {
+            Some(masked) => {
+                let warning = WebSocketMessage {
+                    data: Some("Words this room doesn't allow were masked in your message.".into()),
+                    ..WebSocketMessage::new(MsgTypes::Warning)
+                };
+                let _ = member.outbox.send(warning.to_text());
+                masked
+            }
+            None => text.to_string(),
+        }
+    }
+
+    /// Refuses a message with something the member's room doesn't allow, warning the member why.
+    fn check_restrictions(
+        &self,
+        member: &Member,
+        text: &str,
+        attachment: Option<&Value>,
+    ) -> Result<(), &'static str> {
+        let Some(restrictions) = self.restrictions.get(&member.room) else {
+            return Ok(());
+        };
+        let image = attachment
+            .and_then(|a| a["mime"].as_str())
+            .is_some_and(|mime| mime.starts_with("image/"));
+        let refused = if restrictions.attachments && attachment.is_some() {
+            Some("Attachments aren't allowed in this room.")
+        } else if restrictions.images && image {
+            Some("Images aren't allowed in this room.")
+        } else if restrictions.links && has_link(text) {
+            Some("Links aren't allowed in this room.")
+        } else {
+            None
+        };
+        match refused {
+            Some(reason) => {
+                let warning = WebSocketMessage {
+                    data: Some(reason.into()),
+                    ..WebSocketMessage::new(MsgTypes::Warning)
+                };
+                let _ = member.outbox.send(warning.to_text());
+                Err("not allowed in the room")
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn room_update_frame(&self, room: &str) -> WebSocketMessage {
+        WebSocketMessage::with_payload(
+            MsgTypes::RoomUpdate,
+            &RoomUpdate {
+                name: Some(room.to_string()),
+                icon: self.icons.get(room).cloned(),
+                topic: self.topics.get(room).cloned(),
+                invite_only: self.invite_only.contains_key(room),
+                password_protected: self.passwords.contains_key(room),
+            },
+        )
+    }
+
+    /// Whether `nick` may enter `room`. Its owner and the administrators always may. An
+    /// invite-only room lets in whoever it let in before and whoever has a live code of it, whom
+    /// it lets in from then on; a password-protected one, likewise, whoever gives its password.
+    fn let_in(
+        &mut self,
+        nick: &str,
+        room: &str,
+        invite: Option<&str>,
+        password: Option<&RoomKey>,
+    ) -> Result<(), Refusal> {
+        if self.owners.get(room).is_some_and(|owner| owner == nick) || self.admins.contains(nick) {
+            return Ok(());
+        }
+        self.check_invite(nick, room, invite)?;
+        self.check_password(nick, room, password)
+    }
+
+    fn check_invite(
+        &mut self,
+        nick: &str,
+        room: &str,
+        invite: Option<&str>,
+    ) -> Result<(), Refusal> {
+        match self.invite_only.get(room) {
+            Some(admitted) if !admitted.contains(nick) => {}
+            _ => return Ok(()),
+        }
+        let code = invite.ok_or(Refusal::Required)?;
+        let invite = self
+            .invites
+            .get(code)
+            .filter(|i| i.room == room)
+            .ok_or(Refusal::Invalid)?;
+        if invite.revoked {
+            return Err(Refusal::Revoked);
+        }
+        if invite.expires_at <= now_ms() {
+            return Err(Refusal::Expired);
+        }
+        if let Some(admitted) = self.invite_only.get_mut(room) {
+            admitted.insert(nick.to_string());
+        }
+        Ok(())
+    }
+
+    /// Wrong passwords count against the name, so reconnecting doesn't start the count over.
+    fn check_password(
+        &mut self,
+        nick: &str,
+        room: &str,
+        password: Option<&RoomKey>,
+    ) -> Result<(), Refusal> {
+        match self.passwords.get(room) {
+            Some(lock) if !lock.admitted.contains(nick) => {}
+            _ => return Ok(()),
+        }
+        if self.retry_in(nick, room).is_some() {
+            return Err(Refusal::TooManyAttempts);
+        }
+        let password = password.ok_or(Refusal::Password)?;
+        let key = (nick.to_string(), room.to_string());
+        let Some(lock) = self.passwords.get_mut(room) else {
+            return Ok(());
+        };
+        if lock.matches(password) {
+            lock.admitted.insert(nick.to_string());
+            self.attempts.remove(&key);
+            return Ok(());
+        }
+        let now = now_ms();
+        let attempts = self.attempts.entry(key).or_insert(Attempts {
+            failures: 0,
+            since: now,
+        });
+        if attempts.since + PASSWORD_LOCKOUT_MS <= now {
+            *attempts = Attempts {
+                failures: 0,
+                since: now,
+            };
+        }
+        attempts.failures += 1;
+        if attempts.failures >= MAX_PASSWORD_ATTEMPTS {
+            return Err(Refusal::TooManyAttempts);
+        }
+        Err(Refusal::WrongPassword)
+    }
+
+    /// Seconds until `nick` may try another password for `room`, if they gave too many wrong ones.
+    fn retry_in(&self, nick: &str, room: &str) -> Option<u64> {
+        let attempts = self.attempts.get(&(nick.to_string(), room.to_string()))?;
+        let until = attempts.since + PASSWORD_LOCKOUT_MS;
+        let now = now_ms();
+        (attempts.failures >= MAX_PASSWORD_ATTEMPTS && until > now)
+            .then(|| (until - now).div_ceil(1000))
+    }
+
+    /// Tells `nick` that `room` didn't let them in, and that they are in `to` instead.
+    fn refused_frame(&self, nick: &str, room: &str, reason: Refusal, to: &str) -> String {
+        let refused = JoinRefused {
+            room,
+            reason,
+            to,
+            retry_in: self.retry_in(nick, room),
+        };
+        WebSocketMessage::with_payload(MsgTypes::JoinRefused, &refused).to_text()
+    }
+
+    /// The sender's room, if they own it and it is invite-only.
+    fn owned_invite_only_room(&self, conn: ConnId) -> Result<String, &'static str> {
+        let member = self.member(conn).ok_or("not registered")?;
+        if self.owners.get(&member.room) != Some(&member.nick) {
+            return Err("only the room's owner can have its invite code");
+        }
+        if !self.invite_only.contains_key(&member.room) {
+            return Err("the room is open to everyone");
+        }
+        Ok(member.room.clone())
+    }
+
+    /// Makes a code that lets people into `room` for a while, and returns it.
+    fn new_invite(&mut self, room: &str) -> String {
+        let code = auth::random_hex(12);
+        self.invites.insert(
+            code.clone(),
+            InviteCode {
+                room: room.to_string(),
+                expires_at: now_ms() + INVITE_TTL_MS,
+                revoked: false,
+            },
+        );
+        code
+    }
+
+    fn revoke_invites(&mut self, room: &str) {
+        for invite in self.invites.values_mut().filter(|i| i.room == room) {
+            invite.revoked = true;
+        }
+    }
+
+    fn invite_frame(&self, code: &str) -> WebSocketMessage {
+        let invite = &self.invites[code];
+        WebSocketMessage::with_payload(
+            MsgTypes::Invite,
+            &Invite {
+                room: &invite.room,
+                code,
+                expires_at: invite.expires_at,
+            },
+        )
+    }
+
+    /// Whether anyone is in `room`, or it has an owner or anything kept.
+    fn in_use(&self, room: &str) -> bool {
+        self.owners.contains_key(room)
+            || self.history.contains_key(room)
+            || self.members.iter().any(|m| m.room == room)
+    }
+
+    /// Moves everything of room `from` to `to`, its members included, or forgets it all without a
+    /// `to`, leaving its members where they are.
+    fn move_room(&mut self, from: &str, to: Option<&str>) {
+        fn rekey<V>(map: &mut HashMap<String, V>, from: &str, to: Option<&str>) {
+            if let (Some(value), Some(to)) = (map.remove(from), to) {
+                map.insert(to.to_string(), value);
+            }
+        }
+        rekey(&mut self.history, from, to);
+        rekey(&mut self.owners, from, to);
+        rekey(&mut self.emoji, from, to);
+        rekey(&mut self.slow_mode, from, to);
+        rekey(&mut self.reports, from, to);
+        rekey(&mut self.banned, from, to);
+        rekey(&mut self.blacklists, from, to);
+        rekey(&mut self.restrictions, from, to);
+        rekey(&mut self.pins, from, to);
+        rekey(&mut self.icons, from, to);
+        rekey(&mut self.topics, from, to);
+        rekey(&mut self.posted_in, from, to);
+        rekey(&mut self.invite_only, from, to);
+        rekey(&mut self.passwords, from, to);
+        rekey(&mut self.room_roles, from, to);
+        self.attempts.retain(|(_, room), _| room != from);
+        // Codes of a deleted room are forgotten, rather than let anyone into one of the same name.
+        self.invites
+            .retain(|_, invite| invite.room != from || to.is_some());
+        for invite in self.invites.values_mut().filter(|i| i.room == from) {
+            invite.room = to.unwrap_or_default().to_string();
+        }
+        for member in &mut self.members {
+            if member.previous.as_deref() == Some(from) {
+                member.previous = to.map(str::to_string);
+            }
+            if let (true, Some(to)) = (member.room == from, to) {
+                member.room = to.to_string();
+            }
+        }
+    }
+
+    /// Sends the administrators connected the new state of the server, as when rooms change.
+    fn send_stats_to_admins(&self) {
+        let stats = WebSocketMessage::with_payload(MsgTypes::Admin, &self.stats()).to_text();
+        for admin in self
+            .members
+            .iter()
+            .filter(|m| self.admins.contains(&m.nick))
+        {
+            let _ = admin.outbox.send(stats.clone());
+        }
+    }
+
+    fn restrictions_frame(&self, room: &str) -> WebSocketMessage {
+        WebSocketMessage::with_payload(
+            MsgTypes::Restrictions,
+            &self.restrictions.get(room).copied().unwrap_or_default(),
+        )
+    }
+
+    fn blacklist_frame(&self, room: &str) -> WebSocketMessage {
+        WebSocketMessage::with_payload(
+            MsgTypes::Blacklist,
+            &self.blacklists.get(room).cloned().unwrap_or_default(),
+        )
+    }
+
+    /// Reminds a member entering a room they were banned from, so their client can say so.
+    fn tell_if_banned(&self, conn: ConnId) {
+        if let Some(member) = self.member(conn).filter(|m| self.is_banned(m)) {
+            let _ = member.outbox.send(banned_frame(&member.nick).to_text());
+        }
+    }
+
+    /// Where the kept message with this id is in the room's history, and the message itself.
+    fn kept_message(&self, room: &str, id: &str) -> Option<(usize, Value)> {
+        self.history
+            .get(room)?
+            .iter()
+            .enumerate()
+            .filter_map(|(at, frame)| {
+                let data = serde_json::from_str::<WebSocketMessage>(frame).ok()?.data?;
+                Some((at, serde_json::from_str::<Value>(&data).ok()?))
+            })
+            .find(|(_, m)| m["id"] == id)
+    }
+
+    /// Lists the room's reports whose message is still kept.
+    fn reports_frame(&self, room: &str) -> WebSocketMessage {
+        let reported: Vec<ReportedMessage> = self
+            .reports
+            .get(room)
+            .into_iter()
+            .flatten()
+            .filter_map(|r| {
+                Some(ReportedMessage {
+                    message: self.kept_message(room, &r.id)?.1,
+                    reporters: &r.reporters,
+                })
+            })
+            .collect();
+        WebSocketMessage::with_payload(MsgTypes::Reports, &reported)
+    }
+
+    fn send_reports(&self, room: &str) {
+        let frame = self.reports_frame(room).to_text();
+        for m in self
+            .members
+            .iter()
+            .filter(|m| m.room == room && self.is_moderator(m))
+        {
+            let _ = m.outbox.send(frame.clone());
+        }
+    }
+
+    fn room_roles_frame(&self, room: &str) -> WebSocketMessage {
+        let roles = self.room_roles.get(room).cloned().unwrap_or_default();
+        WebSocketMessage::with_payload(MsgTypes::RoomRoles, &roles)
+    }
+
+    fn pins_frame(&self, room: &str) -> WebSocketMessage {
+        WebSocketMessage::with_payload(
+            MsgTypes::Pins,
+            &self.pins.get(room).map_or(&[][..], Vec::as_slice),
+        )
+    }
+
+    /// Carries the seconds to wait between messages, or nothing when the room isn't in slow mode.
+    fn slow_mode_frame(&self, room: &str) -> WebSocketMessage {
+        WebSocketMessage {
+            data: self.slow_mode.get(room).map(u64::to_string),
+            ..WebSocketMessage::new(MsgTypes::SlowMode)
+        }
+    }
+
+    fn emoji_frame(&self, room: &str) -> WebSocketMessage {
+        WebSocketMessage::with_payload(
+            MsgTypes::Emoji,
+            &RoomEmoji {
+                owner: self.owners.get(room).map(String::as_str),
+                emoji: self
+                    .emoji
+                    .get(room)
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, image)| CustomEmoji { name, image })
+                    .collect(),
+            },
+        )
+    }
+
+    fn replay_history(&self, conn: ConnId) {
+        let Some(member) = self.member(conn) else {
+            return;
+        };
+        let Some(history) = self.history.get(&member.room) else {
+            return;
+        };
+        for frame in history
+            .iter()
+            .skip(history.len().saturating_sub(REPLAY_LEN))
+        {
+            let _ = member.outbox.send(frame.clone());
+        }
+    }
+
+    /// Sends a `message` frame to the room, keeping it for later arrivals.
+    fn publish(&mut self, room: &str, frame: String) {
+        let now = now_ms();
+        self.posted_total += 1;
+        *self.posted_in.entry(room.to_string()).or_default() += 1;
+        self.posted.push_back(now);
+        while self.posted.front().is_some_and(|&t| t + HOUR_MS <= now) {
+            self.posted.pop_front();
+        }
+        let history = self.history.entry(room.to_string()).or_default();
+        history.push_back(frame.clone());
+        while history.len() > self.history_len {
+            history.pop_front();
+        }
+        self.broadcast(room, &frame);
+    }
+
+    pub fn broadcast(&self, room: &str, frame: &str) {
+        for m in self.members.iter().filter(|m| m.room == room) {
+            let _ = m.outbox.send(frame.to_string());
+        }
+    }
+
+    fn broadcast_users(&self, room: &str) {
+        let members: Vec<&Member> = self.members.iter().filter(|m| m.room == room).collect();
+        let frame = WebSocketMessage {
+            // Bare names, for clients that predate avatars and keys.
+            data_array: Some(members.iter().map(|m| m.nick.clone()).collect()),
+            ..WebSocketMessage::with_payload(
+                MsgTypes::Users,
+                &members
+                    .iter()
+                    .map(|m| m.payload(self.role(m), self.room_role(m)))
+                    .collect::<Vec<_>>(),
+            )
+        };
+        self.broadcast(room, &frame.to_text());
+    }
+}
+
+/// Whether the text has an http(s) address in it, as clients would make a link of.
+fn has_link(text: &str) -> bool {
+    let text = text.to_ascii_lowercase();
+    text.contains("http://") || text.contains("https://")
+}
+
+/// A `message` frame of the server's own, shown as a system line.
+fn system_frame(text: &str) -> String {
+    WebSocketMessage::with_payload(
+        MsgTypes::Message,
+        &MessageData {
+            id: &auth::random_hex(8),
+            from: "",
+            message: text,
+            attachment: None,
+            time: now_ms(),
+            bot: None,
+            to: None,
+            announcement: false,
+            pinned_until: None,
+            system: true,
+        },
+    )
+    .to_text()
+}
+
+fn banned_frame(nick: &str) -> WebSocketMessage {
+    WebSocketMessage {
+        data: Some(nick.to_string()),
+        ..WebSocketMessage::new(MsgTypes::Banned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+    fn register(hub: &mut Hub, conn: ConnId, nick: &str, room: &str) -> UnboundedReceiver<String> {
+        let (outbox, inbox) = unbounded_channel();
+        let identity = Identity {
+            username: nick.into(),
+            avatar: None,
+        };
+        let entry = Entry {
+            room: Some(room.into()),
+            ..Entry::default()
+        };
+        hub.register(conn, identity, None, entry, false, outbox);
+        inbox
+    }
+
+    /// `password` as given for `room`, hashed as the server does before it locks the hub.
+    fn key(hub: &Hub, room: &str, password: &str) -> RoomKey {
+        let salt = hub.password_salt(room);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(RoomKey::new(password, salt)).unwrap()
+    }
+
+    fn drain(inbox: &mut UnboundedReceiver<String>) -> Vec<WebSocketMessage> {
+        std::iter::from_fn(|| inbox.try_recv().ok())
+            .map(|s| serde_json::from_str(&s).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn messages_stay_in_their_room() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        let mut bob = register(&mut hub, 2, "bob", "random");
+        drain(&mut alice);
+        drain(&mut bob);
+
+        hub.message(1, "hi", None).unwrap();
+        let received = drain(&mut alice);
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].message_type, MsgTypes::Message);
+        assert!(drain(&mut bob).is_empty());
+    }
+
+    #[test]
+    fn welcome_comes_with_registering() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        assert!(drain(&mut alice)
+            .iter()
+            .all(|m| m.message_type != MsgTypes::Welcome));
+
+        hub.set_welcome(Some("Be nice.".into()));
+        let mut bob = register(&mut hub, 2, "bob", "general");
+        let received = drain(&mut bob);
+        assert_eq!(received[0].message_type, MsgTypes::Welcome);
+        assert_eq!(received[0].data.as_deref(), Some("Be nice."));
+        // Only the newcomer is greeted.
+        assert!(drain(&mut alice)
+            .iter()
+            .all(|m| m.message_type != MsgTypes::Welcome));
+    }
+
+    #[test]
+    fn user_lists_follow_joins_and_leaves() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        let _bob = register(&mut hub, 2, "bob", "general");
+        let users = drain(&mut alice).pop().unwrap();
+        assert_eq!(users.message_type, MsgTypes::Users);
+        assert_eq!(users.data_array.unwrap(), ["alice", "bob"]);
+
+        hub.join(2, "random".into(), None, None);
+        assert_eq!(
+            drain(&mut alice).pop().unwrap().data_array.unwrap(),
+            ["alice"]
+        );
+
+        hub.join(2, "general".into(), None, None);
+        hub.leave(2);
+        assert_eq!(
+            drain(&mut alice).pop().unwrap().data_array.unwrap(),
+            ["alice"]
+        );
+    }
+
+    #[test]
+    fn user_lists_carry_roles() {
+        let mut hub = Hub::new(10);
+        hub.set_admins(HashSet::from(["carol".to_string()]));
+        let _alice = register(&mut hub, 1, "alice", "general");
+        let _bob = register(&mut hub, 2, "bob", "general");
+        let mut carol = register(&mut hub, 3, "carol", "general");
+        let users: Value =
+            serde_json::from_str(&drain(&mut carol).pop().unwrap().data.unwrap()).unwrap();
+        assert_eq!(users[0]["role"], "owner");
+        assert!(users[1].get("role").is_none());
+        assert_eq!(users[2]["role"], "moderator");
+
+        // Whoever opens a room owns it.
+        hub.join(3, "random".into(), None, None);
+        let users: Value =
+            serde_json::from_str(&drain(&mut carol).pop().unwrap().data.unwrap()).unwrap();
+        assert_eq!(users[0]["role"], "owner");
+    }
+
+    #[test]
+    fn gravatars_are_shown_to_the_room() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        drain(&mut alice);
+
+        hub.set_gravatar(1, Some("ab".repeat(32)));
+        let users = drain(&mut alice).pop().unwrap();
+        assert_eq!(
+            users.data.unwrap(),
+            format!(
+                r#"[{{"name":"alice","gravatar":"{}","role":"owner"}}]"#,
+                "ab".repeat(32)
+            )
+        );
+
+        hub.set_gravatar(1, Some("x?d=https://evil.example".into()));
+        assert_eq!(
+            drain(&mut alice).pop().unwrap().data.unwrap(),
+            r#"[{"name":"alice","role":"owner"}]"#
+        );
+    }
+
+    #[test]
+    fn only_moderators_announce() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        let _bob = register(&mut hub, 2, "bob", "general");
+        drain(&mut alice);
+        let announcement = Announcement {
+            text: "Maintenance at noon".into(),
+            pin_minutes: Some(10 * MAX_PIN_MINUTES),
+        };
+
+        assert!(hub.announce(2, false, &announcement).is_err());
+        assert!(drain(&mut alice).is_empty());
+        hub.announce(2, true, &announcement).unwrap();
+        hub.announce(1, false, &announcement).unwrap();
+
+        let frames = drain(&mut alice);
+        assert_eq!(frames.len(), 2);
+        let data: Value = serde_json::from_str(frames[0].data.as_deref().unwrap()).unwrap();
+        assert_eq!(data["announcement"], true);
+        let pinned_for = data["pinnedUntil"].as_u64().unwrap() - data["time"].as_u64().unwrap();
+        assert_eq!(pinned_for, MAX_PIN_MINUTES * MINUTE_MS);
+    }
+
+    #[test]
+    fn slow_mode_spaces_out_messages() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        let _bob = register(&mut hub, 2, "bob", "general");
+        drain(&mut alice);
+
+        assert!(hub.set_slow_mode(2, false, 30).is_err());
+        hub.set_slow_mode(1, false, 30).unwrap();
+        let frame = drain(&mut alice).pop().unwrap();
+        assert_eq!(frame.message_type, MsgTypes::SlowMode);
+        assert_eq!(frame.data.as_deref(), Some("30"));
+
+        hub.message(2, "first", None).unwrap();
+        assert!(hub.message(2, "second", None).is_err());
+        // Everyone waits for themselves only.
+        hub.message(1, "hi", None).unwrap();
+        assert_eq!(drain(&mut alice).len(), 2);
+
+        hub.set_slow_mode(1, false, 0).unwrap();
+        assert_eq!(hub.slow_mode(2).unwrap().data, None);
+        hub.message(2, "second", None).unwrap();
+    }
+
+    #[test]
+    fn whispers_reach_only_the_two_of_them() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        let mut bob = register(&mut hub, 2, "bob", "general");
+        let mut carol = register(&mut hub, 3, "carol", "general");
+        let mut dave = register(&mut hub, 4, "dave", "random");
+        for inbox in [&mut alice, &mut bob, &mut carol, &mut dave] {
+            drain(inbox);
+        }
+
+        hub.whisper(1, "bob", "psst").unwrap();
+        for inbox in [&mut alice, &mut bob] {
+            let frames = drain(inbox);
+            assert_eq!(frames.len(), 1);
+            assert_eq!(frames[0].message_type, MsgTypes::Whisper);
+            let data: Value = serde_json::from_str(frames[0].data.as_deref().unwrap()).unwrap();
+            assert_eq!(
+                (data["from"].as_str(), data["to"].as_str()),
+                (Some("alice"), Some("bob"))
+            );
+        }
+        assert!(drain(&mut carol).is_empty());
+        assert!(hub
+            .page("alice", "general", None, 10)
+            .unwrap()
+            .messages
+            .is_empty());
+
+        assert!(hub.whisper(1, "dave", "psst").is_err());
+        assert!(drain(&mut dave).is_empty());
+    }
+
+    #[test]
+    fn only_changes_of_away_are_broadcast() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        drain(&mut alice);
+
+        hub.set_away(1, true);
+        hub.set_away(1, true);
+        let frames = drain(&mut alice);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(
+            frames[0].data.as_deref(),
+            Some(r#"[{"name":"alice","away":true,"role":"owner"}]"#)
+        );
+
+        hub.set_away(1, false);
+        assert_eq!(
+            drain(&mut alice).pop().unwrap().data.unwrap(),
+            r#"[{"name":"alice","role":"owner"}]"#
+        );
+    }
+
+    #[test]
+    fn voice_channels_stay_in_their_room() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "cats");
+        let _bob = register(&mut hub, 2, "bob", "cats");
+        drain(&mut alice);
+
+        hub.set_voice(1, true).unwrap();
+        hub.set_voice(1, true).unwrap();
+        let frames = drain(&mut alice);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(
+            frames[0].data.as_deref(),
+            Some(r#"[{"name":"alice","voice":true,"role":"owner"},{"name":"bob"}]"#)
+        );
+
+        // Moving to another room hangs up, and coming back doesn't rejoin.
+        hub.join(1, "dogs".into(), None, None);
+        hub.join(1, "cats".into(), None, None);
+        assert!(!drain(&mut alice)
+            .pop()
+            .unwrap()
+            .data
+            .unwrap()
+            .contains("voice"));
+
+        // Those who may not post may not speak either.
+        let roles: RoomRoles = serde_json::from_str(r#"{"everyone": {"post": false}}"#).unwrap();
+        hub.set_room_roles(1, roles).unwrap();
+        assert!(hub.set_voice(2, true).is_err());
+        hub.set_voice(1, true).unwrap();
+    }
+
+    #[test]
+    fn live_locations_reach_the_rest_of_the_room() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "cats");
+        let mut bob = register(&mut hub, 2, "bob", "cats");
+        let mut carol = register(&mut hub, 3, "carol", "dogs");
+        drain(&mut alice);
+        drain(&mut bob);
+        drain(&mut carol);
+
+        let here = Location {
+            lat: 48.8584,
+            lon: 2.2945,
+        };
+        hub.share_location(1, Some(here)).unwrap();
+        let frames = drain(&mut bob);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].message_type, MsgTypes::Location);
+        assert_eq!(frames[0].from.as_deref(), Some("alice"));
+        assert_eq!(
+            frames[0].data.as_deref(),
+            Some(r#"{"lat":48.8584,"lon":2.2945}"#)
+        );
+        assert!(drain(&mut alice).is_empty());
+        assert!(drain(&mut carol).is_empty());
+
+        hub.share_location(1, None).unwrap();
+        assert_eq!(drain(&mut bob).pop().unwrap().data, None);
+        let nowhere = Location {
+            lat: 91.0,
+            lon: 0.0,
+        };
+        assert!(hub.share_location(1, Some(nowhere)).is_err());
+        assert!(drain(&mut bob).is_empty());
+    }
+
+    #[test]
+    fn a_second_connection_takes_over_only_when_asked() {
+        let mut hub = Hub::new(10);
+        let mut first = register(&mut hub, 1, "alice", "general");
+        drain(&mut first);
+
+        let mut second = register(&mut hub, 2, "alice", "general");
+        let refused: Vec<MsgTypes> = drain(&mut second)
+            .into_iter()
+            .map(|m| m.message_type)
+            .collect();
+        assert_eq!(refused, [MsgTypes::Conflict]);
+        assert!(drain(&mut first).is_empty());
+
+        let (outbox, mut second) = unbounded_channel();
+        let identity = Identity {
+            username: "alice".into(),
+            avatar: None,
+        };
+        let entry = Entry {
+            room: Some("general".into()),
+            ..Entry::default()
+        };
+        hub.register(2, identity, None, entry, true, outbox);
+        assert_eq!(drain(&mut first)[0].message_type, MsgTypes::Replaced);
+        assert_eq!(hub.whereabouts(1), None);
+        assert_eq!(hub.whereabouts(2), Some(("alice", "general")));
+        assert_eq!(
+            drain(&mut second).pop().unwrap().data.unwrap(),
+            r#"[{"name":"alice","role":"owner"}]"#
+        );
+    }
+
+    #[test]
+    fn only_the_first_in_a_room_changes_its_emoji() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        let mut bob = register(&mut hub, 2, "bob", "general");
+        drain(&mut alice);
+        drain(&mut bob);
+        let change = |name: &str, image: Option<&str>| EmojiChange {
+            name: name.into(),
+            image: image.map(str::to_string),
+        };
+
+        assert!(hub
+            .set_emoji(2, change("party", Some("data:image/png;base64,x")))
+            .is_err());
+        assert!(hub
+            .set_emoji(1, change("Party!", Some("data:image/png;base64,x")))
+            .is_err());
+        assert!(hub
+            .set_emoji(1, change("party", Some("https://example.com/a.png")))
+            .is_err());
+        assert!(drain(&mut bob).is_empty());
+
+        hub.set_emoji(1, change("party", Some("data:image/png;base64,x")))
+            .unwrap();
+        let expected =
+            r#"{"owner":"alice","emoji":[{"name":"party","image":"data:image/png;base64,x"}]}"#;
+        assert_eq!(drain(&mut bob)[0].data.as_deref(), Some(expected));
+
+        // Bob is first in a room of his own, and still sees general's emoji there.
+        hub.join(2, "random".into(), None, None);
+        assert_eq!(
+            hub.room_emoji(2).unwrap().data.as_deref(),
+            Some(r#"{"owner":"bob","emoji":[]}"#)
+        );
+        hub.join(2, "general".into(), None, None);
+        assert_eq!(hub.room_emoji(2).unwrap().data.as_deref(), Some(expected));
+
+        hub.set_emoji(1, change("party", None)).unwrap();
+        assert_eq!(
+            drain(&mut bob).pop().unwrap().data.as_deref(),
+            Some(r#"{"owner":"alice","emoji":[]}"#)
+        );
+    }
+
+    #[test]
+    fn stats_cover_every_room() {
+        let mut hub = Hub::new(10);
+        let _alice = register(&mut hub, 1, "alice", "general");
+        let _bob = register(&mut hub, 2, "bob", "random");
+        hub.message(1, "hi", None).unwrap();
+        hub.message(1, "again", None).unwrap();
+        hub.leave(2);
+        hub.post_bot(
+            "builds",
+            &serde_json::from_str(r#"{"bot":"ci","text":"ok"}"#).unwrap(),
+        );
+
+        let stats = serde_json::to_value(hub.stats()).unwrap();
+        assert_eq!(
+            stats["users"],
+            serde_json::json!([{"name": "alice", "room": "general"}])
+        );
+        assert_eq!(
+            stats["rooms"],
+            serde_json::json!([
+                {"name": "builds", "members": 0, "kept": 1},
+                {"name": "general", "members": 1, "kept": 2},
+            ])
+        );
+        assert_eq!(
+            stats["messages"],
+            serde_json::json!({"total": 3, "lastMinute": 3, "lastHour": 3})
+        );
+    }
+
+    #[test]
+    fn newcomers_get_the_recent_history() {
+        let mut hub = Hub::new(2);
+        let _alice = register(&mut hub, 1, "alice", "general");
+        for text in ["one", "two", "three"] {
+            hub.message(1, text, None).unwrap();
+        }
+
+        let mut bob = register(&mut hub, 2, "bob", "random");
+        assert!(drain(&mut bob)
+            .iter()
+            .all(|m| m.message_type == MsgTypes::Users));
+
+        hub.join(2, "general".into(), None, None);
+        let replayed: Vec<String> = drain(&mut bob)
+            .into_iter()
+            .filter(|m| m.message_type == MsgTypes::Message)
+            .map(|m| {
+                let data: Value = serde_json::from_str(&m.data.unwrap()).unwrap();
+                data["message"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(replayed, ["two", "three"]);
+    }
+
+    #[test]
+    fn replayed_messages_keep_their_ids() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        drain(&mut alice);
+        hub.message(1, "hi", None).unwrap();
+        let id_of = |m: &WebSocketMessage| -> String {
+            let data: Value = serde_json::from_str(m.data.as_ref().unwrap()).unwrap();
+            data["id"].as_str().unwrap().to_string()
+        };
+        let sent = id_of(&drain(&mut alice)[0]);
+
+        let mut bob = register(&mut hub, 2, "bob", "general");
+        let replayed = drain(&mut bob)
+            .into_iter()
+            .find(|m| m.message_type == MsgTypes::Message)
+            .unwrap();
+        assert_eq!(id_of(&replayed), sent);
+    }
+
+    #[test]
+    fn history_pages_go_back_from_a_message() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        for text in ["one", "two", "three"] {
+            hub.message(1, text, None).unwrap();
+        }
+        drain(&mut alice);
+        let texts = |page: &HistoryPage| -> Vec<String> {
+            page.messages
+                .iter()
+                .map(|m| m["message"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        let latest = hub.page("alice", "general", None, 2).unwrap();
+        assert_eq!(texts(&latest), ["two", "three"]);
+        assert!(latest.more);
+
+        let id = latest.messages[0]["id"].as_str().unwrap();
+        let older = hub.page("alice", "general", Some(id), 2).unwrap();
+        assert_eq!(texts(&older), ["one"]);
+        assert!(!older.more);
+
+        assert!(hub
+            .page("alice", "general", Some("gone"), 2)
+            .unwrap()
+            .messages
+            .is_empty());
+        assert!(hub
+            .page("alice", "random", None, 2)
+            .unwrap()
+            .messages
+            .is_empty());
+    }
+
+    #[test]
+    fn bots_post_into_rooms() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        drain(&mut alice);
+        let hook: Webhook = serde_json::from_str(
+            r#"{"bot":"ci","text":"Build failed","buttons":[{"action":"retry","label":"Retry"}]}"#,
+        )
+        .unwrap();
+
+        hub.post_bot("general", &hook);
+        let data: Value =
+            serde_json::from_str(&drain(&mut alice)[0].data.clone().unwrap()).unwrap();
+        assert_eq!(data["from"], "ci");
+        assert_eq!(data["bot"]["buttons"][0]["action"], "retry");
+    }
+
+    #[test]
+    fn moderators_settle_reports() {
+        let mut hub = Hub::new(10);
+        hub.set_admins(HashSet::from(["carol".to_string()]));
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        let mut bob = register(&mut hub, 2, "bob", "general");
+        let mut carol = register(&mut hub, 3, "carol", "general");
+        let mut dave = register(&mut hub, 4, "dave", "general");
+        hub.message(2, "spam", None).unwrap();
+        hub.message(2, "more spam", None).unwrap();
+        let ids: Vec<String> = drain(&mut dave)
+            .iter()
+            .filter(|m| m.message_type == MsgTypes::Message)
+            .map(|m| {
+                let data: Value = serde_json::from_str(m.data.as_ref().unwrap()).unwrap();
+                data["id"].as_str().unwrap().to_string()
+            })
+            .collect();
+        drain(&mut alice);
+        drain(&mut bob);
+        drain(&mut carol);
+
+        // The owner and the administrator are told; the reporter isn't.
+        assert!(hub.report(2, &ids[0]).is_err());
+        hub.report(4, &ids[0]).unwrap();
+        hub.report(4, &ids[1]).unwrap();
+        for inbox in [&mut alice, &mut carol] {
+            let reports = drain(inbox).pop().unwrap();
+            assert_eq!(reports.message_type, MsgTypes::Reports);
+            let reports: Value = serde_json::from_str(&reports.data.unwrap()).unwrap();
+            assert_eq!(reports[1]["message"]["message"], "more spam");
+            assert_eq!(reports[1]["reporters"][0], "dave");
+        }
+        assert!(drain(&mut dave).is_empty());
+        assert_eq!(hub.reports(4).unwrap().data, None);
+
+        let moderation = |id: &str, action| Moderation {
+            id: id.to_string(),
+            action,
+        };
+        assert!(hub
+            .moderate(4, &moderation(&ids[0], ModerationAction::Delete))
+            .is_err());
+        hub.moderate(1, &moderation(&ids[0], ModerationAction::Delete))
+            .unwrap();
+        let received = drain(&mut bob);
+        assert_eq!(received[0].message_type, MsgTypes::Deleted);
+        assert_eq!(received[0].data.as_ref(), Some(&ids[0]));
+        assert_eq!(
+            hub.page("alice", "general", None, 10)
+                .unwrap()
+                .messages
+                .len(),
+            1
+        );
+        drain(&mut dave);
+
+        hub.moderate(3, &moderation(&ids[1], ModerationAction::Ban))
+            .unwrap();
+        let received = drain(&mut dave);
+        assert_eq!(received[0].message_type, MsgTypes::Deleted);
+        assert_eq!(received[1].message_type, MsgTypes::Banned);
+        assert_eq!(received[1].data.as_deref(), Some("bob"));
+        assert!(hub.message(2, "let me back", None).is_err());
+        assert!(hub
+            .page("alice", "general", None, 10)
+            .unwrap()
+            .messages
+            .is_empty());
+        assert_eq!(hub.reports(1).unwrap().data.as_deref(), Some("[]"));
+
+        // Rejoining doesn't lift a ban.
+        hub.join(2, "random".into(), None, None);
+        drain(&mut bob);
+        hub.join(2, "general".into(), None, None);
+        assert_eq!(
+            drain(&mut bob).pop().unwrap().message_type,
+            MsgTypes::Banned
+        );
+    }
+
+    #[test]
+    fn blacklisted_words_are_masked() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        let mut bob = register(&mut hub, 2, "bob", "general");
+        drain(&mut alice);
+
+        assert!(hub.set_blacklist(2, vec!["darn".into()]).is_err());
+        hub.set_blacklist(1, vec!["Darn".into()]).unwrap();
+        let frame = drain(&mut bob).pop().unwrap();
+        assert_eq!(frame.message_type, MsgTypes::Blacklist);
+        assert_eq!(frame.data.as_deref(), Some(r#"["darn"]"#));
+        drain(&mut alice);
+
+        assert_eq!(hub.message(2, "darn it", None).unwrap(), "**** it");
+        let received = drain(&mut bob);
+        assert_eq!(received[0].message_type, MsgTypes::Warning);
+        assert!(received[1].data.as_ref().unwrap().contains("**** it"));
+        // Only the sender is warned.
+        assert_eq!(drain(&mut alice).len(), 1);
+        assert_eq!(
+            hub.page("alice", "general", None, 10).unwrap().messages[0]["message"],
+            "**** it"
+        );
+    }
+
+    #[test]
+    fn restricted_messages_are_refused() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        let mut bob = register(&mut hub, 2, "bob", "general");
+        drain(&mut alice);
+
+        let restrictions = Restrictions {
+            links: true,
+            images: true,
+            attachments: false,
+        };
+        assert!(hub.set_restrictions(2, restrictions).is_err());
+        hub.set_restrictions(1, restrictions).unwrap();
+        let frame = drain(&mut bob).pop().unwrap();
+        assert_eq!(frame.message_type, MsgTypes::Restrictions);
+        assert_eq!(
+            frame.data.as_deref(),
+            Some(r#"{"links":true,"images":true,"attachments":false}"#)
+        );
+        drain(&mut alice);
+
+        assert!(hub.message(2, "see HTTPS://example.com", None).is_err());
+        let image = serde_json::json!({"name": "cat.png", "mime": "image/png"});
+        assert!(hub.message(2, "", Some(&image)).is_err());
+        let received = drain(&mut bob);
+        assert_eq!(received.len(), 2);
+        assert!(received
+            .iter()
+            .all(|frame| frame.message_type == MsgTypes::Warning));
+        assert!(drain(&mut alice).is_empty());
+
+        let notes = serde_json::json!({"name": "notes.txt", "mime": "text/plain"});
+        hub.message(2, "example.com", Some(&notes)).unwrap();
+        assert_eq!(drain(&mut alice).len(), 1);
+
+        hub.set_restrictions(1, Restrictions::default()).unwrap();
+        assert!(!hub.restrictions.contains_key("general"));
+        hub.message(2, "https://example.com", None).unwrap();
+    }
+
+    #[test]
+    fn room_icons_are_set_by_the_owner() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        let mut bob = register(&mut hub, 2, "bob", "general");
+        drain(&mut alice);
+
+        let icon = |icon: &str| RoomUpdate {
+            icon: Some(icon.into()),
+            ..RoomUpdate::default()
+        };
+        assert!(hub.update_room(2, icon("🐈")).is_err());
+        assert!(hub.update_room(1, icon("two words")).is_err());
+        assert!(hub.update_room(1, icon("data:text/html,<b>")).is_err());
+        hub.update_room(1, icon("🐈")).unwrap();
+        let frame = drain(&mut bob).pop().unwrap();
+        assert_eq!(frame.message_type, MsgTypes::RoomUpdate);
+        assert_eq!(
+            frame.data.as_deref(),
+            Some(r#"{"name":"general","icon":"🐈"}"#)
+        );
+        assert_eq!(hub.room_info("alice", "general").unwrap().icon, Some("🐈"));
+        assert_eq!(hub.stats().rooms[0].icon, Some("🐈"));
+
+        hub.update_room(1, icon("data:image/png;base64,AAAA"))
+            .unwrap();
+        hub.update_room(1, RoomUpdate::default()).unwrap();
+        assert_eq!(
+            drain(&mut alice).pop().unwrap().data.as_deref(),
+            Some(r#"{"name":"general"}"#)
+        );
+        assert_eq!(hub.room_info("alice", "general").unwrap().icon, None);
+    }
+
+    #[test]
+    fn renaming_moves_the_room() {
+        let mut hub = Hub::new(10);
+        hub.set_admins(["carol".to_string()].into());
+        let mut alice = register(&mut hub, 1, "alice", "cats");
+        let mut bob = register(&mut hub, 2, "bob", "cats");
+        let mut carol = register(&mut hub, 3, "carol", "general");
+        let mut dave = register(&mut hub, 4, "dave", "dogs");
+        hub.message(2, "meow", None).unwrap();
+        hub.update_room(
+            1,
+            RoomUpdate {
+                icon: Some("🐈".into()),
+                ..RoomUpdate::default()
+            },
+        )
+        .unwrap();
+        drain(&mut alice);
+        drain(&mut bob);
+        drain(&mut carol);
+        drain(&mut dave);
+
+        let rename = |name: &str| RoomUpdate {
+            name: Some(name.into()),
+            icon: Some("🐈".into()),
+            ..RoomUpdate::default()
+        };
+        assert!(hub.update_room(2, rename("kittens")).is_err());
+        assert!(hub.update_room(1, rename("dogs")).is_err());
+        assert!(hub.update_room(1, rename("two words")).is_err());
+        assert!(hub.update_room(3, rename("chat")).is_err());
+        hub.update_room(1, rename("kittens")).unwrap();
+
+        let received = drain(&mut bob);
+        assert_eq!(received[0].message_type, MsgTypes::RoomUpdate);
+        assert_eq!(
+            received[0].data.as_deref(),
+            Some(r#"{"name":"kittens","icon":"🐈"}"#)
+        );
+        assert_eq!(received[1].message_type, MsgTypes::Message);
+        let system: Value = serde_json::from_str(received[1].data.as_deref().unwrap()).unwrap();
+        assert_eq!(system["message"], "alice renamed #cats to #kittens");
+        assert_eq!(system["system"], true);
+        assert_eq!(drain(&mut carol)[0].message_type, MsgTypes::Admin);
+        assert!(drain(&mut dave).is_empty());
+
+        assert_eq!(hub.whereabouts(2), Some(("bob", "kittens")));
+        assert_eq!(
+            hub.page("alice", "kittens", None, 10)
+                .unwrap()
+                .messages
+                .len(),
+            2
+        );
+        assert_eq!(hub.room_info("alice", "kittens").unwrap().icon, Some("🐈"));
+        assert!(!hub.in_use("cats"));
+        drain(&mut alice);
+        hub.message(2, "purr", None).unwrap();
+        assert_eq!(drain(&mut alice).len(), 1);
+    }
+
+    #[test]
+    fn deleting_takes_members_back() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        let mut bob = register(&mut hub, 2, "bob", "dogs");
+        let mut carol = register(&mut hub, 3, "carol", "cats");
+        hub.join(1, "cats".into(), None, None);
+        hub.join(2, "cats".into(), None, None);
+        hub.message(2, "meow", None).unwrap();
+        drain(&mut alice);
+        drain(&mut bob);
+        drain(&mut carol);
+
+        // Carol was first in, and has to give the name.
+        assert!(hub.delete_room(1, "cats").is_err());
+        assert!(hub.delete_room(3, "kittens").is_err());
+        hub.delete_room(3, "cats").unwrap();
+
+        let received = drain(&mut bob);
+        assert_eq!(received[0].message_type, MsgTypes::RoomDeleted);
+        assert_eq!(
+            received[0].data.as_deref(),
+            Some(r#"{"room":"cats","by":"carol","to":"dogs"}"#)
+        );
+        let last: Value =
+            serde_json::from_str(received.last().unwrap().data.as_deref().unwrap()).unwrap();
+        assert_eq!(
+            last["message"],
+            "carol deleted #cats, so you are back in #dogs."
+        );
+        assert_eq!(last["system"], true);
+        assert_eq!(hub.whereabouts(1), Some(("alice", "general")));
+        assert_eq!(hub.whereabouts(2), Some(("bob", "dogs")));
+        assert_eq!(hub.whereabouts(3), Some(("carol", "general")));
+        assert!(!hub.in_use("cats"));
+        assert!(hub
+            .page("alice", "cats", None, 10)
+            .unwrap()
+            .messages
+            .is_empty());
+
+        // Whoever is first in a room of that name again owns it anew.
+        hub.join(1, "cats".into(), None, None);
+        assert_eq!(hub.owners.get("cats").map(String::as_str), Some("alice"));
+        hub.delete_room(1, "cats").unwrap();
+        assert_eq!(hub.whereabouts(1), Some(("alice", "general")));
+        // Everyone starts there, so it stays, even for its owner.
+        assert!(hub.delete_room(1, "general").is_err());
+    }
+
+    #[test]
+    fn deleting_takes_no_one_back_into_a_room_that_closed() {
+        let mut hub = Hub::new(10);
+        let _alice = register(&mut hub, 1, "alice", "club");
+        let _mallory = register(&mut hub, 2, "mallory", "club");
+        hub.join(2, "mine".into(), None, None);
+        let closed = RoomUpdate {
+            invite_only: true,
+            ..RoomUpdate::default()
+        };
+        hub.update_room(1, closed).unwrap();
+
+        hub.delete_room(2, "mine").unwrap();
+        assert_eq!(hub.whereabouts(2), Some(("mallory", "general")));
+        assert!(hub.page("mallory", "club", None, 10).is_none());
+    }
+
+    #[test]
+    fn deleting_takes_no_one_back_into_a_room_that_locked() {
+        let mut hub = Hub::new(10);
+        let _alice = register(&mut hub, 1, "alice", "vault");
+        let _mallory = register(&mut hub, 2, "mallory", "vault");
+        hub.join(2, "mine".into(), None, None);
+        hub.set_room_password(1, Some(key(&hub, "vault", "sesame")))
+            .unwrap();
+
+        hub.delete_room(2, "mine").unwrap();
+        assert_eq!(hub.whereabouts(2), Some(("mallory", "general")));
+        assert!(hub.page("mallory", "vault", None, 10).is_none());
+    }
+
+    #[test]
+    fn owners_hand_rooms_over() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "cats");
+        let mut bob = register(&mut hub, 2, "bob", "cats");
+        let mut carol = register(&mut hub, 3, "carol", "dogs");
+        drain(&mut alice);
+        drain(&mut bob);
+        drain(&mut carol);
+
+        assert!(hub.transfer(2, "bob").is_err());
+        assert!(hub.transfer(1, "alice").is_err());
+        assert!(hub.transfer(1, "carol").is_err());
+        hub.transfer(1, "bob").unwrap();
+
+        let received = drain(&mut alice);
+        assert_eq!(received[0].message_type, MsgTypes::RoleChange);
+        assert_eq!(
+            received[0].data.as_deref(),
+            Some(r#"{"owner":"bob","previous":"alice"}"#)
+        );
+        assert_eq!(received[1].message_type, MsgTypes::Users);
+        assert!(received[1]
+            .data
+            .as_deref()
+            .unwrap()
+            .contains(r#"{"name":"bob","role":"owner"}"#));
+        let system: Value = serde_json::from_str(received[2].data.as_deref().unwrap()).unwrap();
+        assert_eq!(system["message"], "alice handed #cats to bob");
+        assert!(drain(&mut carol).is_empty());
+
+        // The owner's rights went with it.
+        assert!(hub.transfer(1, "bob").is_err());
+        assert!(hub.set_blacklist(1, vec!["heck".into()]).is_err());
+        hub.set_blacklist(2, vec!["heck".into()]).unwrap();
+    }
+
+    #[test]
+    fn pins_are_limited_and_arranged_by_moderators() {
+        let mut hub = Hub::new(10);
+        hub.set_pin_limit(2);
+        let mut alice = register(&mut hub, 1, "alice", "general");
+        let mut bob = register(&mut hub, 2, "bob", "general");
+        for text in ["one", "two", "three"] {
+            hub.message(2, text, None).unwrap();
+        }
+        let ids: Vec<String> = hub
+            .page("alice", "general", None, 10)
+            .unwrap()
+            .messages
+            .iter()
+            .map(|m| m["id"].as_str().unwrap().to_string())
+            .collect();
+        drain(&mut alice);
+        drain(&mut bob);
+        let pinned = |hub: &Hub| -> Vec<String> {
+            let data = hub.pins(2).unwrap().data.unwrap();
+            let pins: Vec<Value> = serde_json::from_str(&data).unwrap();
+            pins.iter()
+                .map(|p| p["message"]["message"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        assert!(hub.pin(2, &ids[0]).is_err());
+        assert!(hub.pin(1, "nope").is_err());
+        hub.pin(1, &ids[0]).unwrap();
+        assert!(hub.pin(1, &ids[0]).is_err());
+        let frame = drain(&mut bob).pop().unwrap();
+        assert_eq!(frame.message_type, MsgTypes::Pins);
+        assert!(frame.data.unwrap().contains(r#""pinnedBy":"alice""#));
+
+        hub.pin(1, &ids[1]).unwrap();
+        assert_eq!(pinned(&hub), ["two", "one"]);
+        hub.arrange_pins(1, &[ids[0].clone(), ids[1].clone()])
+            .unwrap();
+        assert_eq!(pinned(&hub), ["one", "two"]);
+        // The one pinned first goes, wherever it was moved to.
+        hub.pin(1, &ids[2]).unwrap();
+        assert_eq!(pinned(&hub), ["three", "two"]);
+
+        assert!(hub.arrange_pins(2, &[]).is_err());
+        hub.arrange_pins(1, &[ids[1].clone(), "nope".into()])
+            .unwrap();
+        assert_eq!(pinned(&hub), ["two"]);
+        hub.arrange_pins(1, &[]).unwrap();
+        assert!(pinned(&hub).is_empty());
+        assert!(!hub.pins.contains_key("general"));
+    }
+
+    #[test]
+    fn invite_only_rooms_hide_from_the_uninvited() {
+        let mut hub = Hub::new(10);
+        let _alice = register(&mut hub, 1, "alice", "club");
+        let _bob = register(&mut hub, 2, "bob", "general");
+        hub.set_admins(HashSet::from(["carol".to_string()]));
+        let closed = RoomUpdate {
+            invite_only: true,
+            ..RoomUpdate::default()
+        };
+        hub.update_room(1, closed).unwrap();
+
+        assert!(hub.page("bob", "club", None, 10).is_none());
+        assert!(hub.room_info("bob", "club").is_none());
+        assert!(hub.page("alice", "club", None, 10).is_some());
+        assert!(hub.room_info("carol", "club").is_some());
+        assert!(hub.page("bob", "general", None, 10).is_some());
+
+        let code = hub.invites.keys().next().unwrap().clone();
+        hub.join(2, "club".into(), Some(&code), None);
+        hub.join(2, "general".into(), None, None);
+        assert!(hub.room_info("bob", "club").is_some());
+    }
+
+    #[test]
+    fn invite_only_rooms_take_codes() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "club");
+        let mut bob = register(&mut hub, 2, "bob", "general");
+        let mut carol = register(&mut hub, 3, "carol", "general");
+        drain(&mut alice);
+        let refused = |inbox: &mut UnboundedReceiver<String>| {
+            let frame = drain(inbox).remove(0);
+            assert_eq!(frame.message_type, MsgTypes::JoinRefused);
+            let refused: Value = serde_json::from_str(frame.data.as_deref().unwrap()).unwrap();
+            refused["reason"].as_str().unwrap().to_string()
+        };
+        let code = |frame: &WebSocketMessage| {
+            let invite: Value = serde_json::from_str(frame.data.as_deref().unwrap()).unwrap();
+            invite["code"].as_str().unwrap().to_string()
+        };
+
+        let closed = RoomUpdate {
+            invite_only: true,
+            ..RoomUpdate::default()
+        };
+        assert!(hub.update_room(2, closed.clone()).is_err());
+        assert!(hub.invite(1).is_err());
+        hub.update_room(1, closed.clone()).unwrap();
+        let invite = drain(&mut alice).pop().unwrap();
+        assert_eq!(invite.message_type, MsgTypes::Invite);
+        let first = code(&invite);
+        assert_eq!(code(&hub.invite(1).unwrap()), first);
+        assert!(hub.invite(2).is_err());
+
+        drain(&mut bob);
+        hub.join(2, "club".into(), None, None);
+        assert_eq!(refused(&mut bob), "required");
+        hub.join(2, "club".into(), Some("nope"), None);
+        assert_eq!(refused(&mut bob), "invalid");
+        assert_eq!(hub.whereabouts(2).unwrap().1, "general");
+        hub.join(2, "club".into(), Some(&first), None);
+        assert_eq!(hub.whereabouts(2).unwrap().1, "club");
+        // Once let in, no code is needed to come back.
+        hub.join(2, "general".into(), None, None);
+        hub.join(2, "club".into(), None, None);
+        assert_eq!(hub.whereabouts(2).unwrap().1, "club");
+
+        let second = code(&hub.regenerate_invite(1).unwrap());
+        assert_ne!(first, second);
+        drain(&mut carol);
+        hub.join(3, "club".into(), Some(&first), None);
+        assert_eq!(refused(&mut carol), "revoked");
+        hub.invites.get_mut(&second).unwrap().expires_at = 0;
+        hub.join(3, "club".into(), Some(&second), None);
+        assert_eq!(refused(&mut carol), "expired");
+
+        // A fresh connection asking for the room lands in the default one.
+        let mut dave = register(&mut hub, 4, "dave", "club");
+        assert_eq!(refused(&mut dave), "required");
+        assert_eq!(hub.whereabouts(4).unwrap().1, DEFAULT_ROOM);
+
+        hub.update_room(1, RoomUpdate::default()).unwrap();
+        hub.join(3, "club".into(), None, None);
+        assert_eq!(hub.whereabouts(3).unwrap().1, "club");
+        assert!(hub.update_room(4, closed).is_err());
+    }
+
+    #[test]
+    fn password_rooms_hide_from_those_without_it() {
+        let mut hub = Hub::new(10);
+        let _alice = register(&mut hub, 1, "alice", "vault");
+        let _bob = register(&mut hub, 2, "bob", "general");
+        hub.set_admins(HashSet::from(["carol".to_string()]));
+        hub.set_room_password(1, Some(key(&hub, "vault", "sesame")))
+            .unwrap();
+
+        assert!(hub.page("bob", "vault", None, 10).is_none());
+        assert!(hub.room_info("bob", "vault").is_none());
+        assert!(hub.page("alice", "vault", None, 10).is_some());
+        assert!(hub.room_info("carol", "vault").is_some());
+
+        hub.join(2, "vault".into(), None, Some(&key(&hub, "vault", "sesame")));
+        hub.join(2, "general".into(), None, None);
+        assert!(hub.page("bob", "vault", None, 10).is_some());
+    }
+
+    #[test]
+    fn password_rooms_take_their_password() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "vault");
+        let mut bob = register(&mut hub, 2, "bob", "vault");
+        let mut carol = register(&mut hub, 3, "carol", "general");
+        drain(&mut alice);
+        drain(&mut bob);
+        let refused = |inbox: &mut UnboundedReceiver<String>| {
+            let frame = drain(inbox).remove(0);
+            assert_eq!(frame.message_type, MsgTypes::JoinRefused);
+            serde_json::from_str::<Value>(frame.data.as_deref().unwrap()).unwrap()
+        };
+
+        assert!(hub
+            .set_room_password(2, Some(key(&hub, "vault", "sesame")))
+            .is_err());
+        hub.set_room_password(1, Some(key(&hub, "vault", "sesame")))
+            .unwrap();
+        // Only that there is one reaches the room.
+        let update = drain(&mut bob).pop().unwrap();
+        assert_eq!(
+            update.data.as_deref(),
+            Some(r#"{"name":"vault","passwordProtected":true}"#)
+        );
+
+        drain(&mut carol);
+        hub.join(3, "vault".into(), None, None);
+        assert_eq!(refused(&mut carol)["reason"], "password");
+        hub.join(3, "vault".into(), None, Some(&key(&hub, "vault", "open")));
+        assert_eq!(refused(&mut carol)["reason"], "wrongPassword");
+        hub.join(3, "vault".into(), None, Some(&key(&hub, "vault", "sesame")));
+        assert_eq!(hub.whereabouts(3).unwrap().1, "vault");
+        // Those already in when it was set, and those who gave it, come and go freely.
+        hub.join(2, "general".into(), None, None);
+        hub.join(2, "vault".into(), None, None);
+        assert_eq!(hub.whereabouts(2).unwrap().1, "vault");
+
+        let mut dave = register(&mut hub, 4, "dave", "vault");
+        assert_eq!(refused(&mut dave)["reason"], "password");
+        for _ in 1..MAX_PASSWORD_ATTEMPTS {
+            hub.join(4, "vault".into(), None, Some(&key(&hub, "vault", "guess")));
+            assert_eq!(refused(&mut dave)["reason"], "wrongPassword");
+        }
+        hub.join(4, "vault".into(), None, Some(&key(&hub, "vault", "guess")));
+        let too_many = refused(&mut dave);
+        assert_eq!(too_many["reason"], "tooManyAttempts");
+        assert!(too_many["retryIn"].as_u64().unwrap() > 0);
+        // Even the right one waits.
+        hub.join(4, "vault".into(), None, Some(&key(&hub, "vault", "sesame")));
+        assert_eq!(refused(&mut dave)["reason"], "tooManyAttempts");
+        hub.attempts
+            .get_mut(&("dave".into(), "vault".into()))
+            .unwrap()
+            .since = 0;
+        hub.join(4, "vault".into(), None, Some(&key(&hub, "vault", "sesame")));
+        assert_eq!(hub.whereabouts(4).unwrap().1, "vault");
+
+        assert!(hub.set_room_password(4, None).is_err());
+        hub.set_room_password(1, None).unwrap();
+        assert_eq!(
+            drain(&mut alice).pop().unwrap().data.as_deref(),
+            Some(r#"{"name":"vault"}"#)
+        );
+        assert!(hub
+            .set_room_password(3, Some(key(&hub, "vault", "x")))
+            .is_err());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(runtime.block_on(RoomKey::new("", None)).is_err());
+        assert!(runtime
+            .block_on(RoomKey::new(&"x".repeat(129), None))
+            .is_err());
+    }
+
+    #[test]
+    fn directory_lists_the_open_rooms() {
+        let mut hub = Hub::new(10);
+        let _alice = register(&mut hub, 1, "alice", "cats");
+        let _bob = register(&mut hub, 2, "bob", "cats");
+        let _carol = register(&mut hub, 3, "carol", "club");
+        let _dave = register(&mut hub, 4, "dave", "vault");
+        let topic = |topic: &str| RoomUpdate {
+            topic: Some(topic.into()),
+            ..RoomUpdate::default()
+        };
+        assert!(hub.update_room(1, topic("line\nbreak")).is_err());
+        assert!(hub.update_room(2, topic("all about cats")).is_err());
+        hub.update_room(1, topic("  all about cats ")).unwrap();
+        hub.update_room(
+            3,
+            RoomUpdate {
+                invite_only: true,
+                ..RoomUpdate::default()
+            },
+        )
+        .unwrap();
+        hub.set_room_password(4, Some(key(&hub, "vault", "sesame")))
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(hub.public_rooms()).unwrap(),
+            serde_json::json!([
+                {"name": "cats", "topic": "all about cats", "members": 2, "posted": 0},
+                {"name": "vault", "members": 1, "posted": 0, "passwordProtected": true},
+                {"name": "general", "members": 0, "posted": 0},
+            ])
+        );
+        // Following the room when it is renamed, and going when it is cleared.
+        hub.update_room(
+            1,
+            RoomUpdate {
+                name: Some("kittens".into()),
+                topic: Some("all about cats".into()),
+                ..RoomUpdate::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(hub.public_rooms()[0].topic, Some("all about cats"));
+        // The rename was noted in the room, and the count went along with it.
+        assert_eq!(hub.public_rooms()[0].posted, 1);
+        hub.message(2, "meow", None).unwrap();
+        assert_eq!(hub.public_rooms()[0].posted, 2);
+        hub.update_room(1, RoomUpdate::default()).unwrap();
+        assert_eq!(hub.public_rooms()[0].topic, None);
+    }
+
+    #[test]
+    fn room_roles_grant_and_withhold() {
+        let mut hub = Hub::new(10);
+        let mut alice = register(&mut hub, 1, "alice", "cats");
+        let mut bob = register(&mut hub, 2, "bob", "cats");
+        let _carol = register(&mut hub, 3, "carol", "cats");
+        let mut dave = register(&mut hub, 4, "dave", "cats");
+        hub.message(1, "welcome", None).unwrap();
+        drain(&mut alice);
+        let id = hub.history["cats"][0].clone();
+        let id = serde_json::from_str::<Value>(
+            serde_json::from_str::<WebSocketMessage>(&id)
+                .unwrap()
+                .data
+                .as_deref()
+                .unwrap(),
+        )
+        .unwrap()["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let mut roles: RoomRoles = serde_json::from_str(
+            r#"{
+                "everyone": {"post": false},
+                "roles": [{"name": " speaker ", "permissions": {"post": true, "pin": true}},
+                          {"name": "bouncer", "permissions": {"post": false, "kick": true}}],
+                "members": {"bob": "speaker", "carol": "bouncer", "dave": "nobody"}
+            }"#,
+        )
+        .unwrap();
+        assert!(hub.set_room_roles(2, roles.clone()).is_err());
+        hub.set_room_roles(1, roles.clone()).unwrap();
+        let received = drain(&mut alice);
+        assert_eq!(received[0].message_type, MsgTypes::RoomRoles);
+        let sent: RoomRoles = serde_json::from_str(received[0].data.as_deref().unwrap()).unwrap();
+        assert_eq!(sent.roles[0].name, "speaker");
+        assert!(!sent.members.contains_key("dave"));
+        let users = received[1].data.as_deref().unwrap();
+        assert!(users.contains(r#"{"name":"bob","roomRole":"speaker"}"#));
+        assert!(users.contains(r#"{"name":"dave"}"#));
+
+        // Everyone lost posting, the speaker kept it, and the owner can do anything.
+        assert!(hub.message(4, "hi", None).is_err());
+        assert!(hub.message(3, "hi", None).is_err());
+        hub.message(2, "hi", None).unwrap();
+        hub.message(1, "hi", None).unwrap();
+        // Nor does it get around that by whispering or sharing where it is.
+        assert!(hub.whisper(4, "alice", "psst").is_err());
+        hub.whisper(2, "alice", "psst").unwrap();
+        let here = Location {
+            lat: 48.8584,
+            lon: 2.2945,
+        };
+        assert!(hub.share_location(4, Some(here)).is_err());
+        hub.share_location(4, None).unwrap();
+        hub.share_location(2, Some(here)).unwrap();
+        assert!(hub.pin(3, &id).is_err());
+        hub.pin(2, &id).unwrap();
+        assert!(hub.kick(2, "dave").is_err());
+        assert!(hub.kick(3, "alice").is_err());
+
+        drain(&mut dave);
+        hub.kick(3, "dave").unwrap();
+        assert_eq!(hub.whereabouts(4), Some(("dave", "general")));
+        let received = drain(&mut dave);
+        assert_eq!(received[0].message_type, MsgTypes::Kicked);
+        assert_eq!(
+            received[0].data.as_deref(),
+            Some(r#"{"room":"cats","by":"carol","to":"general"}"#)
+        );
+        let system: Value =
+            serde_json::from_str(received.last().unwrap().data.as_deref().unwrap()).unwrap();
+        assert_eq!(system["message"], "carol sent you out of #cats.");
+        let system: Value =
+            serde_json::from_str(drain(&mut bob).pop().unwrap().data.as_deref().unwrap()).unwrap();
+        assert_eq!(system["message"], "carol sent dave out of the room");
+        assert!(hub.kick(3, "dave").is_err());
+
+        // Back to what everyone may do by default, nothing is kept.
+        roles = RoomRoles::default();
+        hub.set_room_roles(1, roles).unwrap();
+        assert!(!hub.room_roles.contains_key("cats"));
+        hub.message(3, "at last", None).unwrap();
+    }
+}
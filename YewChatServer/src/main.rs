@@ -0,0 +1,792 @@
+//! A WebSocket server for YewChat, speaking the same protocol as `SimpleWebsocketServer`.
+
+mod api;
+mod auth;
+mod blacklist;
+mod hub;
+mod protocol;
+mod relay;
+mod terms;
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Response;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::{SinkExt, StreamExt};
+use subtle::ConstantTimeEq;
+use tokio::sync::mpsc::unbounded_channel;
+
+use auth::{Auth, Identity};
+use hub::{ConnId, Entry, Hub, Outbox, RoomKey, DEFAULT_ROOM};
+use protocol::{
+    Announcement, AuthError, BotAction, BotResponse, Credentials, EmojiChange, Location,
+    Moderation, MsgTypes, RelayConfig, Restrictions, RoomRoles, RoomUpdate, WebSocketMessage,
+    Webhook,
+};
+use relay::Relay;
+use terms::TermsGate;
+
+const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_HISTORY_LENGTH: usize = 100;
+
+struct AppState {
+    auth: Auth,
+    hub: Mutex<Hub>,
+    next_conn: AtomicU64,
+    /// Bearer token integrations must present to post. Webhooks are off without one.
+    webhook_token: Option<String>,
+    /// Accounts allowed to see the admin view.
+    admins: HashSet<String>,
+    terms: Mutex<TermsGate>,
+    /// Latest callback URL each bot gave, for delivering presses of its buttons.
+    bot_callbacks: Mutex<HashMap<String, String>>,
+    /// Where each room's messages are mirrored to, if anywhere.
+    relays: Mutex<HashMap<String, Relay>>,
+    http: reqwest::Client,
+}
+
+/// Current time as milliseconds since the Unix epoch.
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+/// A text setting, such as the message of the day: the contents of the file named by `<var>_FILE`,
+/// or else `<var>` itself.
+fn text_setting(var: &str) -> Option<String> {
+    let file_var = format!("{}_FILE", var);
+    let text = match env::var(&file_var) {
+        Ok(path) => std::fs::read_to_string(&path)
+            .map_err(|e| log::warn!("can't read {} {}: {}", file_var, path, e))
+            .ok(),
+        Err(_) => env::var(var).ok(),
+    };
+    text.filter(|t| !t.trim().is_empty())
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let port = env::var("PORT")
+        .ok()
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+    let history_len = env::var("HISTORY_LENGTH")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(DEFAULT_HISTORY_LENGTH);
+    // Without a fixed secret, a restart invalidates every session.
+    let secret = env::var("JWT_SECRET").unwrap_or_else(|_| auth::random_hex(32));
+    let admins: HashSet<String> = env::var("ADMINS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    let mut hub = Hub::new(history_len);
+    hub.set_welcome(text_setting("MOTD"));
+    hub.set_admins(admins.clone());
+    if let Some(limit) = env::var("PIN_LIMIT").ok().and_then(|n| n.parse().ok()) {
+        hub.set_pin_limit(limit);
+    }
+
+    let state = Arc::new(AppState {
+        auth: Auth::new(secret.into_bytes()),
+        hub: Mutex::new(hub),
+        next_conn: AtomicU64::new(0),
+        webhook_token: env::var("WEBHOOK_TOKEN").ok().filter(|t| !t.is_empty()),
+        admins,
+        terms: Mutex::new(TermsGate::new(text_setting("TERMS"))),
+        bot_callbacks: Mutex::new(HashMap::new()),
+        relays: Mutex::new(HashMap::new()),
+        http: reqwest::Client::new(),
+    });
+    let app = Router::new()
+        .route("/", get(upgrade))
+        .route("/hooks/:room", post(webhook))
+        .merge(api::routes())
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port))
+        .await
+        .expect("failed to bind");
+    log::info!("Listening on port {}", port);
+    axum::serve(listener, app).await.unwrap();
+}
+
+/// Browsers can't set headers on a WebSocket handshake, so the JWT comes in the query string.
+async fn upgrade(
+    ws: WebSocketUpgrade,
+    Query(query): Query<HashMap<String, String>>,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let identity = query.get("token").and_then(|t| state.auth.verify(t));
+    ws.on_upgrade(move |socket| connection(socket, state, identity))
+}
+
+/// Lets an integration post into `room`. See [`Webhook`] for the body.
+async fn webhook(
+    Path(room): Path<String>,
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Json(hook): Json<Webhook>,
+) -> StatusCode {
+    let Some(expected) = &state.webhook_token else {
+        return StatusCode::NOT_FOUND;
+    };
+    if !bool::from(bearer_token(&headers).as_bytes().ct_eq(expected.as_bytes())) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    if hook.bot.trim().is_empty() {
+        return StatusCode::UNPROCESSABLE_ENTITY;
+    }
+
+    if let Some(callback) = &hook.callback {
+        state
+            .bot_callbacks
+            .lock()
+            .unwrap()
+            .insert(hook.bot.clone(), callback.clone());
+    }
+    state.hub.lock().unwrap().post_bot(&room, &hook);
+    mirror(&state, &room, &hook.bot, &hook.text);
+    StatusCode::NO_CONTENT
+}
+
+/// The token of an `Authorization: Bearer` header, or `""` without one.
+fn bearer_token(headers: &HeaderMap) -> &str {
+    headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default()
+}
+
+async fn connection(socket: WebSocket, state: Arc<AppState>, identity: Option<Identity>) {
+    let conn = state.next_conn.fetch_add(1, Ordering::Relaxed);
+    log::info!("ws {} connected", conn);
+
+    let (mut sink, mut stream) = socket.split();
+    let (outbox, mut inbox) = unbounded_channel::<String>();
+    let writer = tokio::spawn(async move {
+        while let Some(frame) = inbox.recv().await {
+            if sink.send(Message::Text(frame)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(Ok(frame)) = stream.next().await {
+        match frame {
+            Message::Text(text) => match serde_json::from_str(&text) {
+                Ok(msg) => {
+                    let key = room_key(&state, &msg).await;
+                    handle(&state, conn, identity.as_ref(), &outbox, msg, key)
+                }
+                Err(e) => log::warn!("ws {}: error in message: {}", conn, e),
+            },
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+
+    state.hub.lock().unwrap().leave(conn);
+    writer.abort();
+    log::info!("ws {} disconnected", conn);
+}
+
+/// Answers a sign-in with the tokens it earned, or why it didn't.
+fn issued(outbox: &Outbox, result: Result<protocol::Tokens, AuthError>) {
+    let message = match result {
+        Ok(tokens) => WebSocketMessage::with_payload(MsgTypes::AuthOk, &tokens),
+        Err(e) => e.to_message(),
+    };
+    let _ = outbox.send(message.to_text());
+}
+
+/// The room password `msg` carries, if any, hashed before the hub is locked, as that takes a
+/// while: with the salt of the room's password, or a new one when the sender sets theirs. Frames
+/// from the one connection wait for it, so they still reach the hub in order.
+async fn room_key(
+    state: &AppState,
+    msg: &WebSocketMessage,
+) -> Option<Result<RoomKey, &'static str>> {
+    let (password, salt) = match msg.message_type {
+        MsgTypes::Register | MsgTypes::Takeover => {
+            let room = msg.room.as_deref().unwrap_or(DEFAULT_ROOM);
+            let salt = state.hub.lock().unwrap().password_salt(room)?;
+            (msg.password.as_deref()?, Some(salt))
+        }
+        MsgTypes::Join => {
+            let salt = state
+                .hub
+                .lock()
+                .unwrap()
+                .password_salt(msg.data.as_deref()?)?;
+            (msg.password.as_deref()?, Some(salt))
+        }
+        MsgTypes::RoomPassword => (msg.data.as_deref()?, None),
+        _ => return None,
+    };
+    Some(RoomKey::new(password, salt).await)
+}
+
+fn handle(
+    state: &Arc<AppState>,
+    conn: ConnId,
+    identity: Option<&Identity>,
+    outbox: &Outbox,
+    msg: WebSocketMessage,
+    key: Option<Result<RoomKey, &'static str>>,
+) {
+    let reply = |msg: WebSocketMessage| {
+        let _ = outbox.send(msg.to_text());
+    };
+    let credentials = || -> Option<Credentials> {
+        let parsed = msg.data.as_deref().map(serde_json::from_str);
+        match parsed {
+            Some(Ok(credentials)) => Some(credentials),
+            _ => {
+                log::warn!("ws {}: unreadable credentials", conn);
+                None
+            }
+        }
+    };
+
+    match msg.message_type {
+        // Passwords take a while to hash, so the answer comes once it is done.
+        MsgTypes::Signup => {
+            if let Some(credentials) = credentials() {
+                let (state, outbox) = (state.clone(), outbox.clone());
+                tokio::spawn(async move {
+                    issued(&outbox, state.auth.signup(&credentials).await);
+                });
+            }
+        }
+        MsgTypes::Login => {
+            if let Some(credentials) = credentials() {
+                let (state, outbox) = (state.clone(), outbox.clone());
+                tokio::spawn(async move {
+                    issued(&outbox, state.auth.login(&credentials).await);
+                });
+            }
+        }
+        MsgTypes::Refresh => issued(
+            outbox,
+            state.auth.refresh(msg.data.as_deref().unwrap_or_default()),
+        ),
+        MsgTypes::OAuth => {
+            // Exchanging the code needs the provider's client secret and an HTTP client; only
+            // the JS server does that for now.
+            log::warn!("ws {}: OAuth sign-in is not supported by this server", conn);
+            reply(AuthError::OAuthFailed.to_message());
+        }
+        MsgTypes::Register | MsgTypes::Takeover => match identity {
+            Some(identity) if !state.terms.lock().unwrap().allows(&identity.username) => {
+                let terms = state.terms.lock().unwrap();
+                reply(WebSocketMessage::with_payload(
+                    MsgTypes::Terms,
+                    &terms.terms(),
+                ));
+            }
+            Some(identity) => state.hub.lock().unwrap().register(
+                conn,
+                identity.clone(),
+                msg.public_key,
+                Entry {
+                    room: msg.room,
+                    invite: msg.invite,
+                    password: key.and_then(Result::ok),
+                },
+                msg.message_type == MsgTypes::Takeover,
+                outbox.clone(),
+            ),
+            None => reply(AuthError::InvalidToken.to_message()),
+        },
+        MsgTypes::Terms => {
+            if let (Some(identity), Some(version)) = (identity, msg.data.as_deref()) {
+                if state
+                    .terms
+                    .lock()
+                    .unwrap()
+                    .accept(&identity.username, version)
+                {
+                    log::info!("{} accepted the terms {}", identity.username, version);
+                }
+            }
+        }
+        MsgTypes::Unregister => state.hub.lock().unwrap().leave(conn),
+        MsgTypes::Join => {
+            if let Some(room) = msg.data {
+                state.hub.lock().unwrap().join(
+                    conn,
+                    room,
+                    msg.invite.as_deref(),
+                    key.and_then(Result::ok).as_ref(),
+                );
+            }
+        }
+        MsgTypes::Message => {
+            let text = msg.data.as_deref().unwrap_or_default();
+            let whereabouts = {
+                let mut hub = state.hub.lock().unwrap();
+                match hub.message(conn, text, msg.attachment.as_ref()) {
+                    Ok(text) => hub
+                        .whereabouts(conn)
+                        .map(|(user, room)| (user.to_string(), room.to_string(), text)),
+                    Err(e) => {
+                        log::warn!("ws {}: message not posted: {}", conn, e);
+                        None
+                    }
+                }
+            };
+            if let Some((user, room, text)) = whereabouts {
+                // The attachment itself stays here; say that there was one.
+                let name = msg.attachment.as_ref().and_then(|a| a["name"].as_str());
+                match name {
+                    Some(name) if text.is_empty() => {
+                        mirror(state, &room, &user, &format!("[{}]", name))
+                    }
+                    Some(name) => mirror(state, &room, &user, &format!("{} [{}]", text, name)),
+                    None => mirror(state, &room, &user, &text),
+                }
+            }
+        }
+        MsgTypes::Direct => {
+            if let (Some(to), Some(payload)) = (&msg.to, &msg.data) {
+                state.hub.lock().unwrap().direct(conn, to, payload);
+            }
+        }
+        MsgTypes::CallOffer
+        | MsgTypes::CallAnswer
+        | MsgTypes::IceCandidate
+        | MsgTypes::CallHangup
+        | MsgTypes::VoiceOffer
+        | MsgTypes::VoiceAnswer
+        | MsgTypes::VoiceCandidate
+        | MsgTypes::CallRecording
+        | MsgTypes::VoiceRecording => {
+            if let Some(to) = &msg.to {
+                state
+                    .hub
+                    .lock()
+                    .unwrap()
+                    .relay(conn, msg.message_type, to, msg.data.clone());
+            }
+        }
+        MsgTypes::BotAction => match msg.data.as_deref().map(serde_json::from_str::<BotAction>) {
+            Some(Ok(action)) => press(state, conn, action),
+            _ => log::warn!("ws {}: unreadable bot action", conn),
+        },
+        MsgTypes::Relay => configure_relay(state, conn, msg.data.as_deref(), reply),
+        MsgTypes::Gravatar => state.hub.lock().unwrap().set_gravatar(conn, msg.data),
+        MsgTypes::Announce => {
+            let admin = identity.is_some_and(|i| state.admins.contains(&i.username));
+            match msg
+                .data
+                .as_deref()
+                .map(serde_json::from_str::<Announcement>)
+            {
+                Some(Ok(announcement)) => {
+                    if let Err(e) = state
+                        .hub
+                        .lock()
+                        .unwrap()
+                        .announce(conn, admin, &announcement)
+                    {
+                        log::warn!("ws {}: announcement not posted: {}", conn, e);
+                    }
+                }
+                _ => log::warn!("ws {}: unreadable announcement", conn),
+            }
+        }
+        MsgTypes::SlowMode => {
+            let admin = identity.is_some_and(|i| state.admins.contains(&i.username));
+            let mut hub = state.hub.lock().unwrap();
+            match msg.data.as_deref().map(str::parse::<u64>) {
+                None => {
+                    if let Some(frame) = hub.slow_mode(conn) {
+                        reply(frame);
+                    }
+                }
+                Some(Ok(seconds)) => {
+                    if let Err(e) = hub.set_slow_mode(conn, admin, seconds) {
+                        log::warn!("ws {}: slow mode not changed: {}", conn, e);
+                    }
+                }
+                Some(Err(e)) => log::warn!("ws {}: unreadable slow mode: {}", conn, e),
+            }
+        }
+        MsgTypes::Blacklist => {
+            let mut hub = state.hub.lock().unwrap();
+            match msg.data.as_deref().map(serde_json::from_str::<Vec<String>>) {
+                None => {
+                    if let Some(frame) = hub.blacklist(conn) {
+                        reply(frame);
+                    }
+                }
+                Some(Ok(words)) => {
+                    if let Err(e) = hub.set_blacklist(conn, words) {
+                        log::warn!("ws {}: blacklist not changed: {}", conn, e);
+                    }
+                }
+                Some(Err(e)) => log::warn!("ws {}: unreadable blacklist: {}", conn, e),
+            }
+        }
+        MsgTypes::Pin => {
+            let Some(id) = &msg.data else {
+                return;
+            };
+            if let Err(e) = state.hub.lock().unwrap().pin(conn, id) {
+                log::warn!("ws {}: message {} not pinned: {}", conn, id, e);
+            }
+        }
+        MsgTypes::Pins => {
+            let mut hub = state.hub.lock().unwrap();
+            match msg.data.as_deref().map(serde_json::from_str::<Vec<String>>) {
+                None => {
+                    if let Some(frame) = hub.pins(conn) {
+                        reply(frame);
+                    }
+                }
+                Some(Ok(ids)) => {
+                    if let Err(e) = hub.arrange_pins(conn, &ids) {
+                        log::warn!("ws {}: pins not arranged: {}", conn, e);
+                    }
+                }
+                Some(Err(e)) => log::warn!("ws {}: unreadable pins: {}", conn, e),
+            }
+        }
+        MsgTypes::Restrictions => {
+            let mut hub = state.hub.lock().unwrap();
+            match msg
+                .data
+                .as_deref()
+                .map(serde_json::from_str::<Restrictions>)
+            {
+                None => {
+                    if let Some(frame) = hub.restrictions(conn) {
+                        reply(frame);
+                    }
+                }
+                Some(Ok(restrictions)) => {
+                    if let Err(e) = hub.set_restrictions(conn, restrictions) {
+                        log::warn!("ws {}: restrictions not changed: {}", conn, e);
+                    }
+                }
+                Some(Err(e)) => log::warn!("ws {}: unreadable restrictions: {}", conn, e),
+            }
+        }
+        MsgTypes::RoomUpdate => {
+            let mut hub = state.hub.lock().unwrap();
+            match msg.data.as_deref().map(serde_json::from_str::<RoomUpdate>) {
+                None => {
+                    if let Some(frame) = hub.room_update(conn) {
+                        reply(frame);
+                    }
+                }
+                Some(Ok(update)) => {
+                    let before = hub.whereabouts(conn).map(|(_, room)| room.to_string());
+                    if let Err(e) = hub.update_room(conn, update) {
+                        log::warn!("ws {}: room not updated: {}", conn, e);
+                    } else if let (Some(from), Some((_, to))) = (before, hub.whereabouts(conn)) {
+                        // Renamed: its relay goes with it.
+                        let mut relays = state.relays.lock().unwrap();
+                        if let Some(relay) = relays.remove(&from) {
+                            relays.insert(to.to_string(), relay);
+                        }
+                    }
+                }
+                Some(Err(e)) => log::warn!("ws {}: unreadable room update: {}", conn, e),
+            }
+        }
+        MsgTypes::DeleteRoom => {
+            let Some(name) = &msg.data else {
+                return;
+            };
+            let mut hub = state.hub.lock().unwrap();
+            match hub.delete_room(conn, name) {
+                Ok(()) => {
+                    state.relays.lock().unwrap().remove(name);
+                }
+                Err(e) => log::warn!("ws {}: room not deleted: {}", conn, e),
+            }
+        }
+        MsgTypes::Transfer => {
+            let Some(to) = &msg.data else {
+                return;
+            };
+            if let Err(e) = state.hub.lock().unwrap().transfer(conn, to) {
+                log::warn!("ws {}: room not handed to {}: {}", conn, to, e);
+            }
+        }
+        MsgTypes::Invite => match state.hub.lock().unwrap().invite(conn) {
+            Ok(frame) => reply(frame),
+            Err(e) => log::warn!("ws {}: no invite code: {}", conn, e),
+        },
+        MsgTypes::RegenerateInvite => match state.hub.lock().unwrap().regenerate_invite(conn) {
+            Ok(frame) => reply(frame),
+            Err(e) => log::warn!("ws {}: invite code not regenerated: {}", conn, e),
+        },
+        MsgTypes::RoomPassword => {
+            let changed = match key.transpose() {
+                Ok(key) => state.hub.lock().unwrap().set_room_password(conn, key),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = changed {
+                log::warn!("ws {}: room password not changed: {}", conn, e);
+            }
+        }
+        MsgTypes::Report => {
+            let Some(id) = &msg.data else {
+                return;
+            };
+            if let Err(e) = state.hub.lock().unwrap().report(conn, id) {
+                log::warn!("ws {}: message {} not reported: {}", conn, id, e);
+            }
+        }
+        MsgTypes::Reports => {
+            if let Some(frame) = state.hub.lock().unwrap().reports(conn) {
+                reply(frame);
+            }
+        }
+        MsgTypes::Moderate => match msg.data.as_deref().map(serde_json::from_str::<Moderation>) {
+            Some(Ok(moderation)) => {
+                if let Err(e) = state.hub.lock().unwrap().moderate(conn, &moderation) {
+                    log::warn!(
+                        "ws {}: report of {} not settled: {}",
+                        conn,
+                        moderation.id,
+                        e
+                    );
+                }
+            }
+            _ => log::warn!("ws {}: unreadable moderation", conn),
+        },
+        MsgTypes::Whisper => {
+            let (Some(to), Some(text)) = (&msg.to, &msg.data) else {
+                return;
+            };
+            if let Err(e) = state.hub.lock().unwrap().whisper(conn, to, text) {
+                log::warn!("ws {}: whisper to {} not sent: {}", conn, to, e);
+            }
+        }
+        MsgTypes::Away => state.hub.lock().unwrap().set_away(conn, msg.data.is_some()),
+        MsgTypes::Location => {
+            let location = match msg.data.as_deref().map(serde_json::from_str::<Location>) {
+                None => None,
+                Some(Ok(location)) => Some(location),
+                Some(Err(e)) => {
+                    log::warn!("ws {}: unreadable location: {}", conn, e);
+                    return;
+                }
+            };
+            if let Err(e) = state.hub.lock().unwrap().share_location(conn, location) {
+                log::warn!("ws {}: location not shared: {}", conn, e);
+            }
+        }
+        MsgTypes::Voice => {
+            if let Err(e) = state
+                .hub
+                .lock()
+                .unwrap()
+                .set_voice(conn, msg.data.is_some())
+            {
+                log::warn!("ws {}: voice not joined: {}", conn, e);
+            }
+        }
+        MsgTypes::Emoji => {
+            let mut hub = state.hub.lock().unwrap();
+            match msg.data.as_deref().map(serde_json::from_str::<EmojiChange>) {
+                None => {
+                    if let Some(frame) = hub.room_emoji(conn) {
+                        reply(frame);
+                    }
+                }
+                Some(Ok(change)) => {
+                    if let Err(e) = hub.set_emoji(conn, change) {
+                        log::warn!("ws {}: emoji not changed: {}", conn, e);
+                    }
+                }
+                Some(Err(e)) => log::warn!("ws {}: unreadable emoji change: {}", conn, e),
+            }
+        }
+        MsgTypes::RoomRoles => {
+            let mut hub = state.hub.lock().unwrap();
+            match msg.data.as_deref().map(serde_json::from_str::<RoomRoles>) {
+                None => {
+                    if let Some(frame) = hub.room_roles(conn) {
+                        reply(frame);
+                    }
+                }
+                Some(Ok(roles)) => {
+                    if let Err(e) = hub.set_room_roles(conn, roles) {
+                        log::warn!("ws {}: room roles not changed: {}", conn, e);
+                    }
+                }
+                Some(Err(e)) => log::warn!("ws {}: unreadable room roles: {}", conn, e),
+            }
+        }
+        MsgTypes::Kick => {
+            let Some(nick) = &msg.data else {
+                return;
+            };
+            if let Err(e) = state.hub.lock().unwrap().kick(conn, nick) {
+                log::warn!("ws {}: {} not kicked: {}", conn, nick, e);
+            }
+        }
+        MsgTypes::ListRooms => {
+            let hub = state.hub.lock().unwrap();
+            reply(WebSocketMessage::with_payload(
+                MsgTypes::ListRooms,
+                &hub.public_rooms(),
+            ));
+        }
+        MsgTypes::Admin => match identity {
+            Some(identity) if state.admins.contains(&identity.username) => {
+                let hub = state.hub.lock().unwrap();
+                reply(WebSocketMessage::with_payload(
+                    MsgTypes::Admin,
+                    &hub.stats(),
+                ));
+            }
+            _ => reply(WebSocketMessage::new(MsgTypes::Admin)),
+        },
+        // Only ever sent by the server.
+        MsgTypes::Users
+        | MsgTypes::AuthOk
+        | MsgTypes::AuthError
+        | MsgTypes::Conflict
+        | MsgTypes::Replaced
+        | MsgTypes::Welcome
+        | MsgTypes::Deleted
+        | MsgTypes::Banned
+        | MsgTypes::Warning
+        | MsgTypes::RoomDeleted
+        | MsgTypes::RoleChange
+        | MsgTypes::JoinRefused
+        | MsgTypes::Kicked => {}
+    }
+}
+
+/// Tells a bot that someone pressed one of its buttons.
+fn press(state: &AppState, conn: ConnId, action: BotAction) {
+    let Some(callback) = state
+        .bot_callbacks
+        .lock()
+        .unwrap()
+        .get(&action.bot)
+        .cloned()
+    else {
+        return;
+    };
+    let Some(body) = state
+        .hub
+        .lock()
+        .unwrap()
+        .whereabouts(conn)
+        .map(|(user, room)| {
+            serde_json::to_value(BotResponse {
+                room,
+                user,
+                card: action.card.as_deref(),
+                action: &action.action,
+            })
+            .unwrap()
+        })
+    else {
+        return;
+    };
+    let request = state.http.post(&callback).json(&body).send();
+    tokio::spawn(async move {
+        match request.await.and_then(|r| r.error_for_status()) {
+            Ok(_) => {}
+            Err(e) => log::warn!("bot {}: callback failed: {}", action.bot, e),
+        }
+    });
+}
+
+/// Answers a client's `relay`: with no data, the room's relay status; with a [`RelayConfig`], a
+/// change to it, which everyone in the room hears about.
+fn configure_relay(
+    state: &AppState,
+    conn: ConnId,
+    data: Option<&str>,
+    reply: impl Fn(WebSocketMessage),
+) {
+    let hub = state.hub.lock().unwrap();
+    let Some((user, room)) = hub.whereabouts(conn) else {
+        return;
+    };
+    let mut relays = state.relays.lock().unwrap();
+    let Some(data) = data else {
+        reply(relay::status(relays.get(room), None));
+        return;
+    };
+    let config = match serde_json::from_str::<RelayConfig>(data) {
+        Ok(config) => config,
+        Err(e) => {
+            log::warn!("ws {}: unreadable relay config: {}", conn, e);
+            return;
+        }
+    };
+
+    if let Err(refused) = relay::configure(&mut relays, room, hub.moderates(conn), config) {
+        reply(relay::status(relays.get(room), Some(refused)));
+        return;
+    }
+    log::info!("{} changed the relay of {}", user, room);
+    hub.broadcast(room, &relay::status(relays.get(room), None).to_text());
+}
+
+/// Posts a message to the room's relay, if it has one that is switched on.
+fn mirror(state: &Arc<AppState>, room: &str, from: &str, text: &str) {
+    let Some((url, target)) = state
+        .relays
+        .lock()
+        .unwrap()
+        .get(room)
+        .filter(|r| r.enabled)
+        .map(|r| (r.url.clone(), r.target))
+    else {
+        return;
+    };
+    let request = state.http.post(&url).json(&target.body(from, text)).send();
+    let state = state.clone();
+    let room = room.to_string();
+    tokio::spawn(async move {
+        let result = match request.await {
+            Ok(r) if r.status().is_success() => Ok(()),
+            Ok(r) => Err(format!("{} answered {}", target.label(), r.status())),
+            // The error would otherwise quote the URL, which the room mustn't see.
+            Err(e) => Err(format!(
+                "couldn't reach {}: {}",
+                target.label(),
+                e.without_url()
+            )),
+        };
+        if let Err(e) = &result {
+            log::warn!("relay of {}: {}", room, e);
+        }
+        let hub = state.hub.lock().unwrap();
+        let mut relays = state.relays.lock().unwrap();
+        // The relay may have been replaced while we waited.
+        if let Some(relay) = relays.get_mut(&room).filter(|r| r.url == url) {
+            if relay.delivered(result) {
+                hub.broadcast(&room, &relay::status(Some(relay), None).to_text());
+            }
+        }
+    });
+}
@@ -0,0 +1,736 @@
+//! The wire format shared with the YewChat client (`YewChat/src/protocol.rs`).
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MsgTypes {
+    Users,
+    Register,
+    Unregister,
+    Join,
+    Message,
+    Signup,
+    Login,
+    AuthOk,
+    AuthError,
+    OAuth,
+    Refresh,
+    Direct,
+    CallOffer,
+    CallAnswer,
+    IceCandidate,
+    CallHangup,
+    BotAction,
+    Relay,
+    /// Announces the sender's Gravatar hash, or clears it when `data` is absent.
+    Gravatar,
+    /// Registers like `register`, replacing whoever is already connected under the same name.
+    Takeover,
+    /// Sent instead of registering when the name is already connected; the client may `takeover`.
+    Conflict,
+    /// Sent to a connection another one took over from. It is no longer registered.
+    Replaced,
+    /// A room's custom emoji. Asked for without data; from the room's owner, an [`EmojiChange`].
+    Emoji,
+    /// Asks for [`AdminStats`]. Answered without data to anyone who isn't an administrator.
+    Admin,
+    /// Announces that the sender is away from the keyboard, or back when `data` is absent.
+    Away,
+    /// A message for `to` alone. They and the sender are sent it as [`MessageData`]; the rest of
+    /// the room never sees it, and it isn't kept.
+    Whisper,
+    /// An [`Announcement`] from the room's owner or an administrator, posted to the room as a
+    /// [`MessageData`] marked as one.
+    Announce,
+    /// The server's message of the day, sent on registering when it has one.
+    Welcome,
+    /// Accepts the [`Terms`] whose version is in `data`. Until they are accepted, registering is
+    /// answered with this, carrying the terms, instead.
+    Terms,
+    /// Seconds everyone in the room has to wait between messages, or none when there is no slow
+    /// mode. Asked for without data; from the room's owner or an administrator, sets it.
+    SlowMode,
+    /// Reports the message of the room whose id is in `data` to its moderators.
+    Report,
+    /// The room's [`ReportedMessage`]s, sent to its owner and the administrators in it whenever
+    /// they change. Asked for without data; answered without data to anyone else.
+    Reports,
+    /// A [`Moderation`] of a reported message, from the room's owner or an administrator.
+    Moderate,
+    /// The message whose id is in `data` was deleted by a moderator.
+    Deleted,
+    /// The member named in `data` may no longer post in the room.
+    Banned,
+    /// The words masked in the room's messages, as a list. Asked for without data; from the
+    /// room's owner or an administrator, replaces them.
+    Blacklist,
+    /// Something the sender should know about what they just sent, in `data`.
+    Warning,
+    /// Pins the message of the room whose id is in `data`, from the room's owner, an
+    /// administrator, or someone whose role may pin.
+    Pin,
+    /// The room's [`PinnedMessage`]s, in the order shown, sent to the room whenever they change.
+    /// Asked for without data; from whoever may pin, a list of ids puts the pins in that order and
+    /// unpins the rest.
+    Pins,
+    /// What the room doesn't allow in messages, as [`Restrictions`]. Asked for without data; from
+    /// the room's owner or an administrator, replaces them.
+    Restrictions,
+    /// The room's settings, as a [`RoomUpdate`], sent to the room whenever they change. Asked for
+    /// without data; from the room's owner, replaces them, which can rename the room.
+    RoomUpdate,
+    /// Deletes the sender's room, from its owner, with the room's name in `data` as confirmation.
+    DeleteRoom,
+    /// The room we were in was deleted, as a [`RoomDeleted`]. We are already in the one it names.
+    RoomDeleted,
+    /// Hands the sender's room to the member of it named in `data`, from its owner.
+    Transfer,
+    /// The room has a new owner, as a [`RoleChange`]. The `users` frame that follows has the
+    /// roles.
+    RoleChange,
+    /// The code of the sender's room, as an [`Invite`], to its owner and those whose role may
+    /// invite, when the room is invite-only. Asked for without data, which makes a code if the
+    /// last one expired.
+    Invite,
+    /// Revokes the invite code of the sender's room and makes a new one, from its owner, who is
+    /// sent it as `invite`.
+    RegenerateInvite,
+    /// The room we asked to enter, on registering or joining, wouldn't let us in, as a
+    /// [`JoinRefused`]. We are already in the one it names.
+    JoinRefused,
+    /// Sets the password of the sender's room to `data`, or removes it when `data` is absent,
+    /// from its owner. The password itself is never sent back.
+    RoomPassword,
+    /// Asks for the rooms anyone can enter, answered with a list of [`ListedRoom`]s.
+    ListRooms,
+    /// The room's own roles and what they may do, as [`RoomRoles`], sent to the room whenever
+    /// they change. Asked for without data; from the room's owner, replaces them.
+    RoomRoles,
+    /// Sends the member of the room named in `data` back to the default room, from someone whose
+    /// role in the room may kick.
+    Kick,
+    /// We were sent out of the room, as a [`Kicked`]. We are already in the one it names.
+    Kicked,
+    /// Joins the voice channel of the sender's room, or leaves it when `data` is absent. Those in
+    /// it are marked in the `users` frame; moving to another room leaves it.
+    Voice,
+    /// Voice channel signaling for `to`, passed on like a call's: an offer to connect, the answer
+    /// to one, and the ICE candidates of either side.
+    VoiceOffer,
+    VoiceAnswer,
+    VoiceCandidate,
+    /// Tells `to` that the sender started recording the call with them, or stopped when `data` is
+    /// absent. Passed on like the call's signaling.
+    CallRecording,
+    /// The same for the voice channel they are both in.
+    VoiceRecording,
+    /// Where the sender is, as a [`Location`], while they share it live with their room, or that
+    /// they stopped when `data` is absent. Passed on to the rest of the room and not kept.
+    Location,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSocketMessage {
+    pub message_type: MsgTypes,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_array: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    /// Relayed untouched, so the server doesn't need to know its shape.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    /// The invite code given with `register` or `join`, to enter an invite-only room.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite: Option<String>,
+    /// The password given with `join`, to enter a password-protected room.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+impl WebSocketMessage {
+    pub fn new(message_type: MsgTypes) -> Self {
+        Self {
+            message_type,
+            data_array: None,
+            data: None,
+            attachment: None,
+            room: None,
+            to: None,
+            from: None,
+            public_key: None,
+            invite: None,
+            password: None,
+        }
+    }
+
+    /// A frame whose `data` is `payload` serialized as JSON, as most server replies are.
+    pub fn with_payload(message_type: MsgTypes, payload: &impl Serialize) -> Self {
+        Self {
+            data: Some(serde_json::to_string(payload).unwrap()),
+            ..Self::new(message_type)
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+/// Payload of `message`, as broadcast to the room.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageData<'a> {
+    /// Unique per message, so it can be linked to as `#msg-<id>`.
+    pub id: &'a str,
+    pub from: &'a str,
+    pub message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<&'a Value>,
+    pub time: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bot: Option<&'a BotCard>,
+    /// Set on whispers: the only one besides the sender who sees it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<&'a str>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub announcement: bool,
+    /// Until when an announcement stays pinned above the room, in milliseconds since the epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned_until: Option<u64>,
+    /// Set on what the server itself records in the room, such as a rename.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub system: bool,
+}
+
+/// Payload of `announce` from a client.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Announcement {
+    pub text: String,
+    /// How long to pin it for, if at all.
+    #[serde(default)]
+    pub pin_minutes: Option<u64>,
+}
+
+/// Payload of `location`, in degrees.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Location {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Location {
+    pub fn is_valid(&self) -> bool {
+        (-90.0..=90.0).contains(&self.lat) && (-180.0..=180.0).contains(&self.lon)
+    }
+}
+
+/// One entry of `reports`: a kept message and who reported it.
+#[derive(Debug, Serialize)]
+pub struct ReportedMessage<'a> {
+    pub message: Value,
+    pub reporters: &'a [String],
+}
+
+/// One entry of `pins`: a message of the room, kept even after the history lets it go.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinnedMessage {
+    pub message: Value,
+    pub pinned_by: String,
+    /// Milliseconds since the epoch.
+    pub pinned_at: u64,
+}
+
+/// Payload of `moderate` from a client.
+#[derive(Debug, Deserialize)]
+pub struct Moderation {
+    /// Id of the reported message.
+    pub id: String,
+    pub action: ModerationAction,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationAction {
+    /// Leaves the message be and drops the report.
+    Approve,
+    /// Deletes the message for everyone.
+    Delete,
+    /// Deletes the message and stops its sender posting in the room.
+    Ban,
+}
+
+/// Payload of `roomdeleted`.
+#[derive(Debug, Serialize)]
+pub struct RoomDeleted<'a> {
+    pub room: &'a str,
+    /// The owner who deleted it.
+    pub by: &'a str,
+    /// Where its members were taken.
+    pub to: &'a str,
+}
+
+/// Payload of `kicked`.
+#[derive(Debug, Serialize)]
+pub struct Kicked<'a> {
+    pub room: &'a str,
+    /// Who sent us out.
+    pub by: &'a str,
+    /// Where we were taken.
+    pub to: &'a str,
+}
+
+/// Payload of `rolechange`.
+#[derive(Debug, Serialize)]
+pub struct RoleChange<'a> {
+    pub owner: &'a str,
+    /// Who owned the room until now.
+    pub previous: &'a str,
+}
+
+/// Payload of `invite`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Invite<'a> {
+    pub room: &'a str,
+    pub code: &'a str,
+    /// When the code stops letting anyone in, in milliseconds since the epoch.
+    pub expires_at: u64,
+}
+
+/// Why an invite-only or password-protected room didn't let someone in.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Refusal {
+    /// No invite code was given.
+    Required,
+    /// The code isn't one of the room's.
+    Invalid,
+    Expired,
+    /// The owner made a new code since, or opened the room to everyone.
+    Revoked,
+    /// No password was given.
+    Password,
+    WrongPassword,
+    /// Too many wrong passwords lately; the [`JoinRefused`] says when to try again.
+    TooManyAttempts,
+}
+
+/// Payload of `joinrefused`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinRefused<'a> {
+    pub room: &'a str,
+    pub reason: Refusal,
+    /// Where we are instead.
+    pub to: &'a str,
+    /// Seconds until another password may be tried, after too many wrong ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in: Option<u64>,
+}
+
+/// What a room's moderators don't allow in its messages. Each is true when refused.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Restrictions {
+    /// http(s) addresses in the text.
+    pub links: bool,
+    /// Attached images.
+    pub images: bool,
+    /// Attached files of any kind, images included.
+    pub attachments: bool,
+}
+
+/// What a member may do in their room. Its owner and the administrators may do it all.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Permissions {
+    pub post: bool,
+    /// Pin messages and arrange the pins.
+    pub pin: bool,
+    /// Have the invite code of an invite-only room.
+    pub invite: bool,
+    /// Send members back to the default room.
+    pub kick: bool,
+}
+
+impl Permissions {
+    pub const ALL: Self = Self {
+        post: true,
+        pin: true,
+        invite: true,
+        kick: true,
+    };
+}
+
+/// Everyone may post, and nothing more.
+impl Default for Permissions {
+    fn default() -> Self {
+        Self {
+            post: true,
+            pin: false,
+            invite: false,
+            kick: false,
+        }
+    }
+}
+
+/// A role of the room's own, which its owner gives to some of its members.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CustomRole {
+    pub name: String,
+    #[serde(default)]
+    pub permissions: Permissions,
+}
+
+/// Payload of `roomroles`: who may do what in the room, besides its moderators.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RoomRoles {
+    /// What the members without a role may do.
+    pub everyone: Permissions,
+    pub roles: Vec<CustomRole>,
+    /// The role of each member given one, by name. It replaces what everyone may do, so a role
+    /// can take a permission away as well as grant one.
+    pub members: BTreeMap<String, String>,
+}
+
+impl RoomRoles {
+    pub fn role_of(&self, nick: &str) -> Option<&CustomRole> {
+        let name = self.members.get(nick)?;
+        self.roles.iter().find(|r| r.name == *name)
+    }
+
+    pub fn permissions_of(&self, nick: &str) -> Permissions {
+        self.role_of(nick).map_or(self.everyone, |r| r.permissions)
+    }
+}
+
+/// The settings of a room its owner can change.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RoomUpdate {
+    /// The room's name. From its owner, a different one renames the room; left out, it stays.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Shown beside the room's name: an emoji or other short text, or a `data:image/...` URL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    /// A line on what the room is about, shown under its name and in the room directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    /// Whether only those with an invite code, and those already let in, can enter the room.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub invite_only: bool,
+    /// Whether entering the room takes its password. Only the server sets it; the password
+    /// itself is set with `roompassword`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub password_protected: bool,
+}
+
+/// A room of the `listrooms` reply: one that isn't invite-only.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListedRoom<'a> {
+    pub name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<&'a str>,
+    /// How many are in it now.
+    pub members: usize,
+    /// How many messages were posted in it since the server started, for clients to tell how many
+    /// they haven't seen.
+    pub posted: u64,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub password_protected: bool,
+}
+
+/// Body of a `POST /hooks/{room}` request: an integration posting into a room.
+#[derive(Debug, Deserialize)]
+pub struct Webhook {
+    /// Shown as the sender, with a bot badge.
+    pub bot: String,
+    pub text: String,
+    #[serde(flatten)]
+    pub card: BotCard,
+    /// Where presses of the card's buttons are POSTed, as a [`BotResponse`].
+    #[serde(default)]
+    pub callback: Option<String>,
+}
+
+/// The extras of a bot message, passed on to clients as the `bot` of its [`MessageData`].
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct BotCard {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+    #[serde(default)]
+    pub buttons: Vec<BotButton>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BotButton {
+    pub action: String,
+    pub label: String,
+}
+
+/// Payload of `botaction`, sent by a client when a card's button is pressed.
+#[derive(Debug, Deserialize)]
+pub struct BotAction {
+    pub bot: String,
+    pub card: Option<String>,
+    pub action: String,
+}
+
+/// What the bot's callback receives for a [`BotAction`].
+#[derive(Debug, Serialize)]
+pub struct BotResponse<'a> {
+    pub room: &'a str,
+    pub user: &'a str,
+    pub card: Option<&'a str>,
+    pub action: &'a str,
+}
+
+/// Payload of `relay` from a client: where to mirror the room's messages to.
+#[derive(Debug, Deserialize)]
+pub struct RelayConfig {
+    /// An incoming webhook URL. Left out to only switch the relay on or off; empty to remove it.
+    #[serde(default)]
+    pub url: Option<String>,
+    pub enabled: bool,
+}
+
+/// Payload of `relay` to clients. The webhook URL works as a password, so only its kind is sent.
+#[derive(Debug, Serialize)]
+pub struct RelayStatus<'a> {
+    /// `Discord` or `Slack`, or missing if the room has no relay.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<&'a str>,
+    pub enabled: bool,
+    /// Why the last delivery or change failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'a str>,
+}
+
+/// Payload of `emoji` from a client: adds or replaces `name`, or removes it without an `image`.
+#[derive(Debug, Deserialize)]
+pub struct EmojiChange {
+    pub name: String,
+    /// A `data:image/...` URL.
+    #[serde(default)]
+    pub image: Option<String>,
+}
+
+/// Payload of `emoji` to clients: the room's custom emoji, written `:name:` in messages.
+#[derive(Debug, Serialize)]
+pub struct RoomEmoji<'a> {
+    /// Who may change them: whoever entered the room first.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<&'a str>,
+    pub emoji: Vec<CustomEmoji<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CustomEmoji<'a> {
+    pub name: &'a str,
+    pub image: &'a str,
+}
+
+/// Query of `GET /api/rooms/{room}/messages`.
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    /// Id of the oldest message the client has; the page ends just before it.
+    pub before: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Body of `GET /api/rooms/{room}/messages`: [`MessageData`] payloads, oldest first.
+#[derive(Debug, Serialize)]
+pub struct HistoryPage {
+    pub messages: Vec<Value>,
+    /// Whether there are older messages still.
+    pub more: bool,
+}
+
+/// Body of `GET /api/rooms/{room}`.
+#[derive(Debug, Serialize)]
+pub struct RoomInfo<'a> {
+    pub name: &'a str,
+    pub members: Vec<UserPayload<'a>>,
+    /// How many of its messages the server remembers.
+    pub kept: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<&'a str>,
+}
+
+/// Payload of an `admin` reply: the whole server at a glance.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminStats<'a> {
+    /// Everyone connected, in order of registration.
+    pub users: Vec<AdminUser<'a>>,
+    /// Rooms with someone in them or something kept, by name.
+    pub rooms: Vec<AdminRoom<'a>>,
+    pub messages: Throughput,
+    /// Milliseconds since the Unix epoch.
+    pub started_at: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AdminUser<'a> {
+    pub name: &'a str,
+    pub room: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AdminRoom<'a> {
+    pub name: &'a str,
+    pub members: usize,
+    pub kept: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<&'a str>,
+}
+
+/// How many messages were posted, in all rooms.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Throughput {
+    pub total: u64,
+    pub last_minute: usize,
+    pub last_hour: usize,
+}
+
+/// Body of `GET /api/users/{name}`.
+#[derive(Debug, Serialize)]
+pub struct Profile<'a> {
+    pub name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<&'a str>,
+    /// Set while they are connected.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub room: Option<&'a str>,
+}
+
+/// One entry of the `users` payload.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserPayload<'a> {
+    pub name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<&'a str>,
+    /// SHA-256 of their Gravatar email; the address itself never leaves their browser.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gravatar: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<&'a str>,
+    /// Left out while they are around.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub away: Option<bool>,
+    /// In the room's voice channel; left out otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voice: Option<bool>,
+    /// Left out for everyone who doesn't moderate the room.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+    /// The name of the role the room's owner gave them, if any, as in its [`RoomRoles`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub room_role: Option<&'a str>,
+}
+
+/// What someone is in their room, besides a member.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Entered the room first, and moderates it.
+    Owner,
+    /// An administrator, who moderates every room.
+    Moderator,
+}
+
+/// Payload of a relayed `direct`. `payload` is ciphertext we can't read.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectMessage<'a> {
+    pub from: &'a str,
+    pub to: &'a str,
+    pub payload: &'a str,
+    pub from_key: Option<&'a str>,
+    pub to_key: Option<&'a str>,
+    pub time: u64,
+}
+
+/// The terms of service, also served at `/api/terms`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Terms {
+    /// Derived from the text, so that it changes whenever the text does.
+    pub version: String,
+    pub text: String,
+}
+
+/// Payload of `signup` and `login`. The client sends a hash, never the password itself.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Credentials {
+    pub username: String,
+    pub password_hash: String,
+}
+
+/// Payload of `authok`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tokens {
+    pub username: String,
+    pub token: String,
+    pub refresh_token: String,
+    /// Milliseconds since the Unix epoch.
+    pub expires_at: u64,
+}
+
+/// Reasons sent as the `data` of `autherror`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuthError {
+    WrongPassword,
+    UnknownUser,
+    NameTaken,
+    InvalidName,
+    InvalidToken,
+    OAuthFailed,
+}
+
+impl AuthError {
+    pub fn code(self) -> &'static str {
+        match self {
+            AuthError::WrongPassword => "wrong_password",
+            AuthError::UnknownUser => "unknown_user",
+            AuthError::NameTaken => "name_taken",
+            AuthError::InvalidName => "invalid_name",
+            AuthError::InvalidToken => "invalid_token",
+            AuthError::OAuthFailed => "o_auth_failed",
+        }
+    }
+
+    pub fn to_message(self) -> WebSocketMessage {
+        WebSocketMessage {
+            data: Some(self.code().to_string()),
+            ..WebSocketMessage::new(MsgTypes::AuthError)
+        }
+    }
+}
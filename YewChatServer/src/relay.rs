@@ -0,0 +1,228 @@
+//! Mirrors a room's messages to a Discord or Slack channel through an incoming webhook.
+
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::protocol::{MsgTypes, RelayConfig, RelayStatus, WebSocketMessage};
+
+/// Failed deliveries in a row after which a relay switches itself off.
+pub const MAX_FAILURES: u32 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Discord,
+    Slack,
+}
+
+impl Target {
+    /// Which service's incoming webhook `url` is, if it is one. Only HTTPS URLs of Discord and
+    /// Slack are accepted, as the URL is the only secret, and so the server posts nowhere else.
+    pub fn of(url: &str) -> Option<Self> {
+        let rest = url.strip_prefix("https://")?;
+        let (host, path) = rest.split_at(rest.find(['/', '?', '#']).unwrap_or(rest.len()));
+        match host {
+            "discord.com" | "discordapp.com" | "ptb.discord.com" | "canary.discord.com"
+                if path.starts_with("/api/webhooks/") =>
+            {
+                Some(Target::Discord)
+            }
+            "hooks.slack.com" if path.starts_with("/services/") => Some(Target::Slack),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Target::Discord => "Discord",
+            Target::Slack => "Slack",
+        }
+    }
+
+    /// The request body posting `text` as said by `from`.
+    pub fn body(self, from: &str, text: &str) -> Value {
+        match self {
+            // Discord shows `username` as the sender, so the message reads as it does here.
+            Target::Discord => json!({
+                "username": from,
+                "content": text,
+                "allowed_mentions": { "parse": [] },
+            }),
+            Target::Slack => json!({ "text": format!("*{}*: {}", from, text) }),
+        }
+    }
+}
+
+pub struct Relay {
+    pub url: String,
+    pub target: Target,
+    pub enabled: bool,
+    /// Failed deliveries since the last one that worked.
+    pub failures: u32,
+    pub error: Option<String>,
+}
+
+impl Relay {
+    pub fn new(url: String, target: Target) -> Self {
+        Self {
+            url,
+            target,
+            enabled: true,
+            failures: 0,
+            error: None,
+        }
+    }
+
+    /// Records how a delivery went, returning whether clients should hear about it.
+    pub fn delivered(&mut self, result: Result<(), String>) -> bool {
+        match result {
+            Ok(()) => {
+                self.failures = 0;
+                self.error.take().is_some()
+            }
+            Err(e) => {
+                self.failures += 1;
+                if self.failures >= MAX_FAILURES {
+                    self.enabled = false;
+                    self.error = Some(format!(
+                        "{} (switched off after {} failures)",
+                        e, self.failures
+                    ));
+                } else {
+                    self.error = Some(e);
+                }
+                true
+            }
+        }
+    }
+}
+
+/// Applies `config` to the relay of `room`, if whoever sent it moderates the room.
+pub fn configure(
+    relays: &mut HashMap<String, Relay>,
+    room: &str,
+    moderator: bool,
+    config: RelayConfig,
+) -> Result<(), &'static str> {
+    if !moderator {
+        return Err("Only the room's owner or an administrator can change its relay");
+    }
+    match config.url.as_deref().map(str::trim) {
+        Some("") => {
+            relays.remove(room);
+        }
+        Some(url) => {
+            let target = Target::of(url).ok_or("That isn't a Discord or Slack webhook URL")?;
+            let mut relay = Relay::new(url.to_string(), target);
+            relay.enabled = config.enabled;
+            relays.insert(room.to_string(), relay);
+        }
+        None => {
+            if let Some(relay) = relays.get_mut(room) {
+                relay.enabled = config.enabled;
+                relay.failures = 0;
+                relay.error = None;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The `relay` frame describing `relay`. `refused` explains why a change to it wasn't made.
+pub fn status(relay: Option<&Relay>, refused: Option<&str>) -> WebSocketMessage {
+    WebSocketMessage::with_payload(
+        MsgTypes::Relay,
+        &RelayStatus {
+            target: relay.map(|r| r.target.label()),
+            enabled: relay.is_some_and(|r| r.enabled),
+            error: refused.or(relay.and_then(|r| r.error.as_deref())),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_webhook_urls() {
+        assert_eq!(
+            Target::of("https://discord.com/api/webhooks/1/abc"),
+            Some(Target::Discord)
+        );
+        assert_eq!(
+            Target::of("https://hooks.slack.com/services/T/B/x"),
+            Some(Target::Slack)
+        );
+        assert_eq!(Target::of("http://hooks.slack.com/services/T/B/x"), None);
+        assert_eq!(Target::of("https:///nothing"), None);
+    }
+
+    #[test]
+    fn only_posts_to_discord_and_slack() {
+        assert_eq!(Target::of("https://attacker.example/hook"), None);
+        assert_eq!(Target::of("https://169.254.169.254/latest"), None);
+        assert_eq!(
+            Target::of("https://hooks.slack.com@attacker.example/services/x"),
+            None
+        );
+        assert_eq!(Target::of("https://discord.com/channels/1/2"), None);
+        assert_eq!(Target::of("https://hooks.slack.com/"), None);
+
+        let mut relays = HashMap::new();
+        let config = |url: &str| RelayConfig {
+            url: Some(url.to_string()),
+            enabled: true,
+        };
+        assert!(configure(
+            &mut relays,
+            "general",
+            true,
+            config("https://attacker.example/")
+        )
+        .is_err());
+        assert!(relays.is_empty());
+        let slack = "https://hooks.slack.com/services/T/B/x";
+        configure(&mut relays, "general", true, config(slack)).unwrap();
+        assert_eq!(relays["general"].target, Target::Slack);
+    }
+
+    #[test]
+    fn only_moderators_change_the_relay() {
+        let mut relays = HashMap::new();
+        let slack = RelayConfig {
+            url: Some("https://hooks.slack.com/services/T/B/x".into()),
+            enabled: true,
+        };
+        assert!(configure(&mut relays, "general", false, slack).is_err());
+        assert!(relays.is_empty());
+
+        relays.insert(
+            "general".into(),
+            Relay::new(
+                "https://hooks.slack.com/services/T/B/x".into(),
+                Target::Slack,
+            ),
+        );
+        let removal = RelayConfig {
+            url: Some(String::new()),
+            enabled: false,
+        };
+        assert!(configure(&mut relays, "general", false, removal).is_err());
+        assert!(relays["general"].enabled);
+    }
+
+    #[test]
+    fn switches_off_after_repeated_failures() {
+        let mut relay = Relay::new("https://hooks.slack.com/x".into(), Target::Slack);
+        assert!(!relay.delivered(Ok(())));
+        for _ in 0..MAX_FAILURES {
+            assert!(relay.delivered(Err("500 Internal Server Error".into())));
+        }
+        assert!(!relay.enabled);
+
+        relay.enabled = true;
+        assert!(relay.delivered(Ok(())));
+        assert_eq!(relay.error, None);
+    }
+}
@@ -0,0 +1,78 @@
+//! Terms of service that have to be accepted before registering. Who accepted which version is
+//! kept in memory only; clients say so again before every registration.
+
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
+
+use crate::protocol::Terms;
+
+pub struct TermsGate {
+    terms: Option<Terms>,
+    /// The version each account accepted last.
+    accepted: HashMap<String, String>,
+}
+
+impl TermsGate {
+    /// Lets everyone in when `text` is `None`.
+    pub fn new(text: Option<String>) -> Self {
+        Self {
+            terms: text.map(|text| Terms {
+                version: version_of(&text),
+                text,
+            }),
+            accepted: HashMap::new(),
+        }
+    }
+
+    pub fn terms(&self) -> Option<&Terms> {
+        self.terms.as_ref()
+    }
+
+    /// Notes that `username` accepted `version`. Anything but the current version is ignored.
+    pub fn accept(&mut self, username: &str, version: &str) -> bool {
+        let current = self.terms.as_ref().is_some_and(|t| t.version == version);
+        if current {
+            self.accepted
+                .insert(username.to_string(), version.to_string());
+        }
+        current
+    }
+
+    /// Whether `username` may register: there are no terms, or they accepted these.
+    pub fn allows(&self, username: &str) -> bool {
+        self.terms
+            .as_ref()
+            .is_none_or(|t| self.accepted.get(username) == Some(&t.version))
+    }
+}
+
+/// Changes with the text, so that edited terms are asked for again.
+fn version_of(text: &str) -> String {
+    Sha256::digest(text.as_bytes())[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_current_terms_let_in() {
+        assert!(TermsGate::new(None).allows("alice"));
+
+        let mut gate = TermsGate::new(Some("Be nice.".into()));
+        let version = gate.terms().unwrap().version.clone();
+        assert!(!gate.allows("alice"));
+        assert!(!gate.accept("alice", "0000000000000000"));
+        assert!(!gate.allows("alice"));
+        assert!(gate.accept("alice", &version));
+        assert!(gate.allows("alice"));
+        assert!(!gate.allows("bob"));
+
+        let edited = TermsGate::new(Some("Be very nice.".into()));
+        assert_ne!(edited.terms().unwrap().version, version);
+    }
+}